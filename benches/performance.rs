@@ -1,8 +1,47 @@
-use criterion::{criterion_group, criterion_main, Criterion};
-use flatten_rust::{run, Args};
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use flatten_rust::config::{FetchFuture, TemplateFetcher, TemplateManager};
+use flatten_rust::exclusions::{ExclusionManager, PatternSource};
+use flatten_rust::pipeline::{self, DirTree, ReadOptions};
+use flatten_rust::{run, Args, FlattenConfig};
+use std::alloc::{GlobalAlloc, Layout, System};
 use std::fs;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tempfile::{tempdir, TempDir};
 
+/// Аллокатор, подсчитывающий выделенные байты, чтобы сравнивать варианты не только по времени.
+struct CountingAlloc;
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size.saturating_sub(layout.size()) as u64, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Возвращает количество байт, выделенных за время выполнения `f`.
+fn allocated_by<T>(f: impl FnOnce() -> T) -> u64 {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    std::hint::black_box(f());
+    ALLOCATED.load(Ordering::Relaxed) - before
+}
+
 fn create_large_test_structure(num_files: usize) -> TempDir {
     let temp_dir = tempdir().expect("Failed to create temp dir");
     let root = temp_dir.path();
@@ -26,25 +65,19 @@ fn bench_flatten_performance(c: &mut Criterion) {
 
     c.bench_function("flatten_100_files", |b| {
         b.to_async(&runtime).iter(|| async {
-            let args = Args {
-                folders: vec![test_dir_path.clone()],
-                output: output_path.clone(),
-                skip_folders: vec![".git".to_string()],
-                skip_extensions: vec!["log".to_string()],
-                show_skipped: false,
-                threads: 0,
-                max_file_size: 0,
-                auto_detect: false,
-                include_hidden: false,
-                max_depth: 0,
-                show_stats: false,
-                dry_run: false,
-                list_templates: false,
-                enable_templates: vec![],
-                disable_templates: vec![],
-                force_update: false,
-                show_enabled: false,
-            };
+            let args = Args::parse_from([
+                "flatten-rust",
+                "-f",
+                test_dir_path.to_str().expect("path is utf8"),
+                "-o",
+                output_path.to_str().expect("path is utf8"),
+                "-s",
+                ".git",
+                "-x",
+                "log",
+                "-m",
+                "0",
+            ]);
             run(std::hint::black_box(&args))
                 .await
                 .expect("Run failed");
@@ -52,5 +85,346 @@ fn bench_flatten_performance(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_flatten_performance);
+/// Создает дерево из `num_files` файлов, разложенных по 100 в директории.
+fn create_wide_test_tree(num_files: usize) -> TempDir {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let root = temp_dir.path().join("repo");
+    for i in 0..num_files {
+        let dir = root.join(format!("module_{}/src", i / 100));
+        if i % 100 == 0 {
+            fs::create_dir_all(&dir).expect("Failed to create dir");
+        }
+        fs::write(dir.join(format!("file_{}.rs", i)), "").expect("Failed to write file");
+    }
+    temp_dir
+}
+
+fn bench_tree_walk(c: &mut Criterion) {
+    let temp_dir = create_wide_test_tree(50_000);
+    let root = temp_dir.path().join("repo");
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let args = Args::parse_from(["flatten-rust", "-f", root.to_str().expect("path is utf8")]);
+    let config = runtime
+        .block_on(FlattenConfig::new(&args))
+        .expect("Failed to build config");
+
+    let mut group = c.benchmark_group("walk_50k_files");
+    group.sample_size(10);
+    group.bench_function("separate_tree_and_files", |b| {
+        b.iter(|| {
+            let mut tree = Vec::new();
+            pipeline::render_tree(&root, &config, &mut tree).expect("Tree failed");
+            let files = pipeline::collect_files(&root, &config).expect("Walk failed");
+            std::hint::black_box((tree, files))
+        })
+    });
+    group.bench_function("single_dir_tree", |b| {
+        b.iter(|| {
+            let dir_tree = DirTree::walk(&root, &config).expect("Walk failed");
+            let mut tree = Vec::new();
+            dir_tree.render(&config, &mut tree).expect("Tree failed");
+            let files = dir_tree.files().count();
+            std::hint::black_box((tree, files))
+        })
+    });
+    group.finish();
+}
+
+/// Приемник, отказывающий в первой же записи: `render` возвращается сразу
+/// после формирования первой строки дерева.
+struct FirstLine;
+
+impl Write for FirstLine {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("first line is enough"))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Наименьшее время выполнения `f` из пяти запусков.
+fn fastest_of_five(mut f: impl FnMut()) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .expect("five runs")
+}
+
+fn bench_tree_first_line(c: &mut Criterion) {
+    let temp_dir = create_wide_test_tree(50_000);
+    let root = temp_dir.path().join("repo");
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let args = Args::parse_from(["flatten-rust", "-f", root.to_str().expect("path is utf8")]);
+    let config = runtime
+        .block_on(FlattenConfig::new(&args))
+        .expect("Failed to build config");
+    let dir_tree = DirTree::walk(&root, &config).expect("Walk failed");
+
+    let first_line = || {
+        dir_tree
+            .render(&config, &mut FirstLine)
+            .expect_err("Sink rejects writes");
+    };
+    let whole_tree = || dir_tree.render(&config, &mut std::io::sink()).expect("Tree failed");
+    let (first, whole) = (fastest_of_five(first_line), fastest_of_five(whole_tree));
+    eprintln!("tree of 50k files: first line after {:?}, whole tree in {:?}", first, whole);
+    assert!(
+        first * 4 < whole,
+        "the first line must not wait for the whole tree ({:?} vs {:?})",
+        first,
+        whole
+    );
+
+    let mut group = c.benchmark_group("tree_50k_files");
+    group.sample_size(10);
+    group.bench_function("first_line", |b| b.iter(first_line));
+    group.bench_function("whole_tree", |b| b.iter(whole_tree));
+    group.finish();
+}
+
+fn bench_parallel_walk(c: &mut Criterion) {
+    let temp_dir = create_wide_test_tree(50_000);
+    let root = temp_dir.path().join("repo");
+    let output_dir = tempdir().expect("Failed to create temp dir");
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+
+    let args_for = |name: &str, parallel: bool| {
+        let output = output_dir.path().join(name);
+        let mut argv = vec![
+            "flatten-rust".to_string(),
+            "-f".to_string(),
+            root.to_str().expect("path is utf8").to_string(),
+            "-o".to_string(),
+            output.to_str().expect("path is utf8").to_string(),
+        ];
+        if parallel {
+            argv.push("--parallel-walk".to_string());
+        }
+        (Args::parse_from(argv), output)
+    };
+    let (serial_args, serial_output) = args_for("serial.md", false);
+    let (parallel_args, parallel_output) = args_for("parallel.md", true);
+
+    runtime.block_on(run(&serial_args)).expect("Flatten failed");
+    runtime.block_on(run(&parallel_args)).expect("Flatten failed");
+    assert_eq!(
+        fs::read(&serial_output).expect("Failed to read output"),
+        fs::read(&parallel_output).expect("Failed to read output"),
+        "--parallel-walk must produce identical output"
+    );
+
+    let mut group = c.benchmark_group("flatten_50k_files");
+    group.sample_size(10);
+    group.bench_function("serial_walk", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { run(std::hint::black_box(&serial_args)).await.expect("Flatten failed") })
+    });
+    group.bench_function("parallel_walk", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { run(std::hint::black_box(&parallel_args)).await.expect("Flatten failed") })
+    });
+    group.finish();
+}
+
+fn bench_read_large_files(c: &mut Criterion) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let root = temp_dir.path().join("repo");
+    fs::create_dir(&root).expect("Failed to create dir");
+    let line = "let value = compute(input) * 2; // some typical source line\n";
+    let megabyte = line.repeat(1024 * 1024 / line.len());
+    let files: Vec<_> = (0..300)
+        .map(|i| {
+            let path = root.join(format!("file_{}.rs", i));
+            fs::write(&path, &megabyte).expect("Failed to write file");
+            path
+        })
+        .collect();
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let args = Args::parse_from(["flatten-rust", "-f", root.to_str().expect("path is utf8")]);
+    let config = runtime
+        .block_on(FlattenConfig::new(&args))
+        .expect("Failed to build config");
+    let options = ReadOptions::from_config(&config);
+
+    let read_mapped = || {
+        for path in &files {
+            std::hint::black_box(pipeline::read_file(path, &options).expect("Read failed"));
+        }
+    };
+    let flatten_to_sink = || {
+        runtime
+            .block_on(flatten_rust::run_to_writer(&args, &mut std::io::sink()))
+            .expect("Flatten failed")
+    };
+    eprintln!(
+        "allocated per run: read_file {} KB, run_to_writer {} KB",
+        allocated_by(read_mapped) / 1024,
+        allocated_by(flatten_to_sink) / 1024
+    );
+
+    let mut group = c.benchmark_group("read_300_files_1mb");
+    group.sample_size(10);
+    group.bench_function("read_file_mapped", |b| b.iter(read_mapped));
+    group.bench_function("run_to_writer", |b| b.iter(flatten_to_sink));
+    group.finish();
+}
+
+fn bench_read_tiny_files(c: &mut Criterion) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let root = temp_dir.path().join("repo");
+    let files: Vec<_> = (0..20_000)
+        .map(|i| {
+            let dir = root.join(format!("module_{}", i / 100));
+            if i % 100 == 0 {
+                fs::create_dir_all(&dir).expect("Failed to create dir");
+            }
+            let path = dir.join(format!("file_{}.rs", i));
+            fs::write(&path, format!("pub const VALUE_{}: u32 = {};\n", i, i))
+                .expect("Failed to write file");
+            path
+        })
+        .collect();
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let args_for = |threshold: &str| {
+        let folder = root.to_str().expect("path is utf8");
+        Args::parse_from(["flatten-rust", "-f", folder, "--mmap-threshold", threshold])
+    };
+    let (mapped_args, buffered_args) = (args_for("0"), args_for("64KB"));
+    let document = |args: &Args| {
+        let mut document = Vec::new();
+        runtime
+            .block_on(flatten_rust::run_to_writer(args, &mut document))
+            .expect("Flatten failed");
+        document
+    };
+    assert!(
+        document(&mapped_args) == document(&buffered_args),
+        "--mmap-threshold must not change the output"
+    );
+
+    let config = runtime
+        .block_on(FlattenConfig::new(&buffered_args))
+        .expect("Failed to build config");
+    let buffered = ReadOptions::from_config(&config);
+    let mapped = ReadOptions {
+        mmap_threshold: 0,
+        ..ReadOptions::from_config(&config)
+    };
+    let read_all = |options: &ReadOptions| {
+        for path in &files {
+            std::hint::black_box(pipeline::read_file(path, options).expect("Read failed"));
+        }
+    };
+    let (mapped_time, buffered_time) = (
+        fastest_of_five(|| read_all(&mapped)),
+        fastest_of_five(|| read_all(&buffered)),
+    );
+    eprintln!("20k tiny files: mmap {:?}, buffered read {:?}", mapped_time, buffered_time);
+
+    let mut group = c.benchmark_group("read_20k_tiny_files");
+    group.sample_size(10);
+    group.bench_function("read_file_mmap", |b| b.iter(|| read_all(&mapped)));
+    group.bench_function("read_file_buffered", |b| b.iter(|| read_all(&buffered)));
+    group.bench_function("run_to_writer_mmap", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(flatten_rust::run_to_writer(&mapped_args, &mut std::io::sink()))
+                .expect("Flatten failed")
+        })
+    });
+    group.bench_function("run_to_writer_buffered", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(flatten_rust::run_to_writer(&buffered_args, &mut std::io::sink()))
+                .expect("Flatten failed")
+        })
+    });
+    group.finish();
+}
+
+/// Источник шаблонов для бенчмарка: шаблоны берутся только из кэша.
+struct CacheOnly;
+
+impl TemplateFetcher for CacheOnly {
+    fn fetch_list(&self) -> FetchFuture<'_> {
+        Box::pin(async { Err(anyhow::anyhow!("cache only")) })
+    }
+}
+
+/// Создает кэш из `count` шаблонов по `lines` строк в формате `~/.flatten/templates_cache.json`.
+fn create_template_cache(count: usize, lines: usize) -> TempDir {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let templates: serde_json::Map<String, serde_json::Value> = (0..count)
+        .map(|i| {
+            let key = format!("large{}", i);
+            let contents: String = (0..lines)
+                .map(|line| match line % 3 {
+                    0 => format!("# section {}\n", line),
+                    1 => format!("build_{}_{}/\n", i, line),
+                    _ => format!("*.ext{}_{}\n", i, line),
+                })
+                .collect();
+            let template = serde_json::json!({ "key": key, "name": key, "contents": contents });
+            (key, template)
+        })
+        .collect();
+    fs::write(
+        temp_dir.path().join("templates_cache.json"),
+        serde_json::to_string(&templates).expect("Failed to serialize templates"),
+    )
+    .expect("Failed to write templates cache");
+    temp_dir
+}
+
+fn bench_template_patterns(c: &mut Criterion) {
+    let cache = create_template_cache(6, 10_000);
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let manager = || {
+        let templates = TemplateManager::with_fetcher(cache.path(), CacheOnly)
+            .expect("Failed to load templates");
+        let mut manager = ExclusionManager::with_template_manager(templates);
+        for i in 0..6 {
+            manager
+                .enable_template(&format!("large{}", i))
+                .expect("Template is cached");
+        }
+        manager
+    };
+
+    let mut group = c.benchmark_group("template_patterns_6x10k_lines");
+    group.sample_size(10);
+    // Те же запросы к паттернам, что делает `FlattenConfig::new`.
+    group.bench_function("config_construction", |b| {
+        b.iter_batched(
+            manager,
+            |manager| {
+                let sources: Vec<PatternSource> = manager.pattern_sources();
+                let folders = runtime.block_on(manager.get_folder_patterns());
+                let extensions = runtime.block_on(manager.get_extension_patterns());
+                std::hint::black_box((sources, folders, extensions))
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_flatten_performance,
+    bench_tree_walk,
+    bench_tree_first_line,
+    bench_parallel_walk,
+    bench_read_large_files,
+    bench_read_tiny_files,
+    bench_template_patterns
+);
 criterion_main!(benches);
@@ -0,0 +1,437 @@
+//! Модуль входных архивов (`-f project.zip`, `-f project.tar.gz`).
+//!
+//! Архив распознается по расширению или, если оно не подходит, по
+//! сигнатуре в начале файла. Записи читаются последовательно и
+//! распаковываются во временную директорию, которая становится базовой
+//! папкой: к ним применяются те же исключения, ограничения размера и
+//! конвертеры, а дерево структуры строится по путям записей. Параллельная
+//! стадия читает уже распакованные файлы с обычным ограничением
+//! `--max-in-flight-bytes`. Директория удаляется вместе с конфигурацией.
+//!
+//! Путь каждой записи проверяется правилами исключений до распаковки:
+//! пропущенная директория создается пустой, а файлы в ней не пишутся на
+//! диск. Файлы, которые в документе заменяются заглушкой (бинарные,
+//! больше `--max-file-size`), создаются разреженными с объявленным размером
+//! без чтения содержимого. Остальные записи копируются с подсчетом реально
+//! прочитанных байт: запись, оказавшаяся больше `--max-file-size`, или
+//! архив, распаковка которого превышает `--max-archive-bytes`, отвергается.
+//!
+//! Записи с путями вне архива (`../evil`, абсолютные пути) отвергаются
+//! вместе со всем архивом; символические ссылки и специальные файлы
+//! пропускаются.
+
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use tempfile::TempDir;
+
+/// Формат входного архива.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// ZIP-архив.
+    Zip,
+    /// Несжатый tar.
+    Tar,
+    /// tar, сжатый gzip.
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Определяет формат архива `path` по расширению или сигнатуре.
+    ///
+    /// Возвращает `None` для директорий и файлов, не похожих на архив.
+    pub fn detect(path: &Path) -> Option<Self> {
+        if !path.is_file() {
+            return None;
+        }
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            return Some(Self::Zip);
+        }
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            return Some(Self::TarGz);
+        }
+        if name.ends_with(".tar") {
+            return Some(Self::Tar);
+        }
+        // Файлы с известными расширениями не проверяются: `.gz` без tar
+        // или `.jar` не являются базовыми папками.
+        if path.extension().is_some() {
+            return None;
+        }
+        let mut head = [0u8; 262];
+        let read = File::open(path).and_then(|mut file| file.read(&mut head)).ok()?;
+        let head = &head[..read];
+        if head.starts_with(b"PK\x03\x04") {
+            Some(Self::Zip)
+        } else if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else if head.get(257..262) == Some(b"ustar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Что делать с записью архива, решенное по ее пути до распаковки.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryAction {
+    /// Распаковать запись.
+    Unpack,
+    /// Путь исключен: директория создается пустой, файл не создается.
+    Skip,
+    /// Файл заменяется в документе заглушкой: создается без содержимого.
+    Placeholder,
+}
+
+/// Правила отбора и ограничения размера при распаковке.
+pub struct UnpackLimits<'a> {
+    /// Решение для записи по пути в распакованной директории (корень, путь,
+    /// является ли запись директорией).
+    pub action: &'a dyn Fn(&Path, &Path, bool) -> EntryAction,
+    /// Наибольший размер файла (`--max-file-size`, 0 — без ограничений).
+    pub max_file_size: u64,
+    /// Наибольший объем распакованных байт (`--max-archive-bytes`, 0 — без ограничений).
+    pub max_total_bytes: u64,
+}
+
+impl Default for UnpackLimits<'_> {
+    fn default() -> Self {
+        Self {
+            action: &|_, _, _| EntryAction::Unpack,
+            max_file_size: 0,
+            max_total_bytes: 0,
+        }
+    }
+}
+
+/// Распаковка записей в корень с учетом `UnpackLimits`.
+struct Unpacker<'a> {
+    root: &'a Path,
+    limits: &'a UnpackLimits<'a>,
+    /// Пропущенные директории; записи внутри них не распаковываются.
+    skipped: Vec<PathBuf>,
+    /// Байты, уже записанные на диск.
+    written: u64,
+}
+
+impl<'a> Unpacker<'a> {
+    fn new(root: &'a Path, limits: &'a UnpackLimits<'a>) -> Self {
+        Self {
+            root,
+            limits,
+            skipped: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Решение для записи `relative`: внутри пропущенной директории запись
+    /// пропускается, а первая пропущенная директория на ее пути создается.
+    fn action(&mut self, relative: &Path, is_dir: bool) -> Result<EntryAction> {
+        if self.skipped.iter().any(|skipped| relative.starts_with(skipped)) {
+            return Ok(EntryAction::Skip);
+        }
+        let mut prefix = PathBuf::new();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            prefix.push(component);
+            let last = components.peek().is_none();
+            let action = (self.limits.action)(self.root, &self.root.join(&prefix), is_dir || !last);
+            if last {
+                return Ok(action);
+            }
+            if action == EntryAction::Skip {
+                self.create_dir(&prefix)?;
+                self.skipped.push(prefix);
+                return Ok(EntryAction::Skip);
+            }
+        }
+        Ok(EntryAction::Skip)
+    }
+
+    fn create_dir(&self, relative: &Path) -> Result<()> {
+        let target = self.root.join(relative);
+        std::fs::create_dir_all(&target)
+            .with_context(|| format!("Failed to create directory: {}", target.display()))
+    }
+
+    fn directory(&mut self, relative: &Path) -> Result<()> {
+        if self.action(relative, true)? == EntryAction::Skip {
+            if !self.skipped.iter().any(|skipped| relative.starts_with(skipped)) {
+                self.create_dir(relative)?;
+                self.skipped.push(relative.to_path_buf());
+            }
+            return Ok(());
+        }
+        self.create_dir(relative)
+    }
+
+    /// Распаковывает файл `relative` с объявленным размером `size`.
+    fn file(&mut self, relative: &Path, size: u64, content: &mut dyn Read) -> Result<()> {
+        let max_file_size = match self.limits.max_file_size {
+            0 => u64::MAX,
+            limit => limit,
+        };
+        let action = match self.action(relative, false)? {
+            EntryAction::Unpack if size > max_file_size => EntryAction::Placeholder,
+            action => action,
+        };
+        if action == EntryAction::Skip {
+            return Ok(());
+        }
+        let target = self.root.join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        let mut file = File::create(&target)
+            .with_context(|| format!("Failed to create file: {}", target.display()))?;
+        if action == EntryAction::Placeholder {
+            // Разреженный файл: размер для заглушки без записи содержимого.
+            return file
+                .set_len(size)
+                .with_context(|| format!("Failed to create file: {}", target.display()));
+        }
+        let remaining = match self.limits.max_total_bytes {
+            0 => u64::MAX,
+            limit => limit.saturating_sub(self.written),
+        };
+        let limit = max_file_size.min(remaining);
+        let copied = std::io::copy(&mut content.take(limit.saturating_add(1)), &mut file)
+            .with_context(|| format!("Failed to unpack {}", relative.display()))?;
+        if copied > max_file_size {
+            bail!(
+                "Archive entry {} is larger than --max-file-size ({} bytes)",
+                relative.display(),
+                self.limits.max_file_size
+            );
+        }
+        if copied > remaining {
+            bail!(
+                "Archive unpacks to more than --max-archive-bytes ({} bytes)",
+                self.limits.max_total_bytes
+            );
+        }
+        self.written += copied;
+        Ok(())
+    }
+}
+
+/// Архив, распакованный во временную директорию.
+#[derive(Debug)]
+pub struct UnpackedArchive {
+    /// Путь к архиву, указанный в `-f`.
+    pub source: PathBuf,
+    /// Директория с содержимым архива; ее имя — имя архива без расширения.
+    pub root: PathBuf,
+    /// Временная директория, удаляемая вместе со значением.
+    _directory: TempDir,
+}
+
+impl UnpackedArchive {
+    /// Распаковывает архив `source` формата `kind`, отбирая записи по `limits`.
+    ///
+    /// # Ошибки
+    /// Если архив не читается, содержит запись с путем вне архива или
+    /// превышает ограничения размера.
+    pub fn unpack(source: &Path, kind: ArchiveKind, limits: &UnpackLimits<'_>) -> Result<Self> {
+        let directory = tempfile::Builder::new()
+            .prefix("flatten-archive-")
+            .tempdir()
+            .context("Failed to create a directory for the archive")?;
+        let root = directory.path().join(archive_stem(source));
+        std::fs::create_dir(&root)
+            .with_context(|| format!("Failed to create directory: {}", root.display()))?;
+        let root = root.canonicalize().unwrap_or(root);
+
+        let file = File::open(source)
+            .with_context(|| format!("Failed to open archive: {}", source.display()))?;
+        let reader = BufReader::new(file);
+        let mut unpacker = Unpacker::new(&root, limits);
+        let unpacked = match kind {
+            ArchiveKind::Zip => unpack_zip(reader, &mut unpacker),
+            ArchiveKind::Tar => unpack_tar(reader, &mut unpacker),
+            ArchiveKind::TarGz => unpack_tar(GzDecoder::new(reader), &mut unpacker),
+        };
+        unpacked.with_context(|| format!("Failed to unpack archive: {}", source.display()))?;
+        Ok(Self {
+            source: source.to_path_buf(),
+            root,
+            _directory: directory,
+        })
+    }
+}
+
+/// Имя архива без расширений `.zip`, `.tar`, `.tar.gz` и `.tgz`.
+fn archive_stem(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "archive".to_string());
+    let lower = name.to_ascii_lowercase();
+    for suffix in [".tar.gz", ".tgz", ".tar", ".zip"] {
+        if lower.ends_with(suffix) && lower.len() > suffix.len() {
+            return name[..name.len() - suffix.len()].to_string();
+        }
+    }
+    name
+}
+
+/// Проверяет путь записи и возвращает его относительным путем внутри архива.
+///
+/// Отвергаются абсолютные пути и компоненты `..`; `None` — запись с пустым путем.
+fn entry_path(name: &Path) -> Result<Option<PathBuf>> {
+    let mut relative = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                bail!("Archive entry {} points outside the archive", name.display())
+            }
+        }
+    }
+    Ok((!relative.as_os_str().is_empty()).then_some(relative))
+}
+
+fn unpack_zip<R: Read + std::io::Seek>(reader: R, unpacker: &mut Unpacker<'_>) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let Some(relative) = entry_path(Path::new(entry.name()?.as_ref()))? else {
+            continue;
+        };
+        if entry.is_dir() {
+            unpacker.directory(&relative)?;
+        } else if !entry.is_symlink() {
+            let size = entry.size();
+            unpacker.file(&relative, size, &mut entry)?;
+        }
+    }
+    Ok(())
+}
+
+fn unpack_tar<R: Read>(reader: R, unpacker: &mut Unpacker<'_>) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let Some(relative) = entry_path(&entry.path()?)? else {
+            continue;
+        };
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => unpacker.directory(&relative)?,
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let size = entry.size();
+                unpacker.file(&relative, size, &mut entry)?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_path_rejects_traversal() {
+        assert_eq!(
+            entry_path(Path::new("./src/main.rs")).unwrap(),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(entry_path(Path::new("./")).unwrap(), None);
+        assert!(entry_path(Path::new("../evil")).is_err());
+        assert!(entry_path(Path::new("src/../../evil")).is_err());
+        assert!(entry_path(Path::new("/etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn test_archive_stem() {
+        assert_eq!(archive_stem(Path::new("dl/project.tar.gz")), "project");
+        assert_eq!(archive_stem(Path::new("project.TGZ")), "project");
+        assert_eq!(archive_stem(Path::new("project.zip")), "project");
+        assert_eq!(archive_stem(Path::new(".zip")), ".zip");
+    }
+
+    #[test]
+    fn test_unpack_zip_applies_limits() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let source = temp_dir.path().join("bomb.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&source)?);
+        let zeros = vec![0u8; 1 << 20];
+        let entries: [(&str, &[u8]); 3] = [
+            ("src/main.rs", b"fn main() {}"),
+            ("node_modules/dep/huge.bin", &zeros),
+            ("data/large.txt", &zeros),
+        ];
+        for (name, content) in entries {
+            writer.start_file(name, zip::write::SimpleFileOptions::default())?;
+            std::io::Write::write_all(&mut writer, content)?;
+        }
+        writer.finish()?;
+
+        let skip_modules = |root: &Path, path: &Path, _| {
+            if path.strip_prefix(root).is_ok_and(|path| path.starts_with("node_modules")) {
+                EntryAction::Skip
+            } else {
+                EntryAction::Unpack
+            }
+        };
+        let limits = UnpackLimits {
+            action: &skip_modules,
+            max_file_size: 1024,
+            max_total_bytes: 0,
+        };
+        let unpacked = UnpackedArchive::unpack(&source, ArchiveKind::Zip, &limits)?;
+        let root = &unpacked.root;
+        assert_eq!(std::fs::read_to_string(root.join("src/main.rs"))?, "fn main() {}");
+        // Пропущенная директория видна в дереве, но ее файлы не распакованы.
+        assert!(root.join("node_modules").is_dir());
+        assert!(!root.join("node_modules/dep").exists());
+        // Слишком большой файл сохраняет размер без содержимого.
+        let large = std::fs::metadata(root.join("data/large.txt"))?;
+        assert_eq!(large.len(), 1 << 20);
+        #[cfg(unix)]
+        assert!(std::os::unix::fs::MetadataExt::blocks(&large) * 512 < 1 << 20);
+
+        // Объем считается по прочитанным байтам, а не по объявленному размеру.
+        let limits = UnpackLimits {
+            max_total_bytes: 4096,
+            ..UnpackLimits::default()
+        };
+        let error = UnpackedArchive::unpack(&source, ArchiveKind::Zip, &limits).unwrap_err();
+        assert!(format!("{:#}", error).contains("--max-archive-bytes"), "{:#}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpack_tar_gz() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let source = temp_dir.path().join("bundle.tgz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&source)?,
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(12);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "src/main.rs", &b"fn main() {}"[..])?;
+        builder.into_inner()?.finish()?;
+
+        assert_eq!(ArchiveKind::detect(&source), Some(ArchiveKind::TarGz));
+        let unpacked = UnpackedArchive::unpack(&source, ArchiveKind::TarGz, &Default::default())?;
+        assert!(unpacked.root.ends_with("bundle"));
+        assert_eq!(std::fs::read_to_string(unpacked.root.join("src/main.rs"))?, "fn main() {}");
+
+        let root = unpacked.root.clone();
+        drop(unpacked);
+        assert!(!root.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,469 @@
+//! Модуль заглушек для изображений.
+//!
+//! Изображения (PNG, JPEG, GIF, SVG) не выводятся байтами: вместо содержимого
+//! в документ попадает строка вида `[Image: logo.png, PNG 512×512, 34.00 KB]`.
+//! Размеры берутся из заголовка файла — блока IHDR в PNG, маркера SOF в JPEG,
+//! логического экрана GIF и атрибутов `width`/`height` (или `viewBox`)
+//! корневого элемента SVG. Конвертер потоковый: файл не отображается в
+//! память, а чтение ограничено `HEADER_LIMIT` байтами. Если заголовок не
+//! разобрался, заглушка содержит только имя и размер файла.
+//!
+//! С `--include-svg-source` небольшие SVG (не больше `--svg-source-limit`)
+//! выводятся как текст. Статистика считает изображения по форматам.
+
+use crate::converters::{Conversion, ConverterSource, FileConverter};
+use crate::messages::{Message, Messages};
+use crate::report;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Имя конвертера изображений в статистике.
+pub const IMAGE_CONVERTER: &str = "image";
+
+/// Сколько байт заголовка читается в поисках размеров (JPEG с большими
+/// метаданными EXIF перед SOF разбирается в этих пределах).
+const HEADER_LIMIT: u64 = 256 * 1024;
+
+/// Сколько байт начала SVG просматривается в поисках корневого элемента.
+const SVG_SCAN_LIMIT: u64 = 8 * 1024;
+
+/// Сигнатура PNG.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Формат изображения.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Svg,
+}
+
+impl ImageFormat {
+    /// Расширения изображений и их форматы.
+    pub const EXTENSIONS: &[(&str, ImageFormat)] = &[
+        ("png", ImageFormat::Png),
+        ("jpg", ImageFormat::Jpeg),
+        ("jpeg", ImageFormat::Jpeg),
+        ("gif", ImageFormat::Gif),
+        ("svg", ImageFormat::Svg),
+    ];
+
+    /// Формат изображения по расширению файла (без учета регистра).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::EXTENSIONS
+            .iter()
+            .find(|(known, _)| *known == extension)
+            .map(|(_, format)| *format)
+    }
+
+    /// Название формата для заглушки и статистики.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Jpeg => "JPEG",
+            ImageFormat::Gif => "GIF",
+            ImageFormat::Svg => "SVG",
+        }
+    }
+}
+
+/// Размеры изображения в пикселях (у SVG — в пользовательских единицах).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dimensions {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl fmt::Display for Dimensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}×{}", self.width, self.height)
+    }
+}
+
+/// Читает размеры изображения формата `format` из начала `reader`.
+///
+/// Читается не больше `HEADER_LIMIT` байт; `None` — заголовок поврежден или
+/// размеры в нем не указаны.
+pub fn read_dimensions(format: ImageFormat, reader: &mut dyn Read) -> Option<Dimensions> {
+    let mut reader = reader.take(HEADER_LIMIT);
+    match format {
+        ImageFormat::Png => png_dimensions(&mut reader),
+        ImageFormat::Jpeg => jpeg_dimensions(&mut reader),
+        ImageFormat::Gif => gif_dimensions(&mut reader),
+        ImageFormat::Svg => {
+            let mut head = Vec::new();
+            reader.take(SVG_SCAN_LIMIT).read_to_end(&mut head).ok()?;
+            svg_dimensions(&String::from_utf8_lossy(&head))
+        }
+    }
+}
+
+/// Размеры из блока IHDR, который всегда идет первым после сигнатуры.
+fn png_dimensions(reader: &mut dyn Read) -> Option<Dimensions> {
+    let mut header = [0u8; 24];
+    reader.read_exact(&mut header).ok()?;
+    if &header[..8] != PNG_SIGNATURE || &header[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(header[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(header[20..24].try_into().ok()?);
+    raster(width, height)
+}
+
+/// Размеры логического экрана GIF.
+fn gif_dimensions(reader: &mut dyn Read) -> Option<Dimensions> {
+    let mut header = [0u8; 10];
+    reader.read_exact(&mut header).ok()?;
+    if &header[..6] != b"GIF87a" && &header[..6] != b"GIF89a" {
+        return None;
+    }
+    let width = u16::from_le_bytes([header[6], header[7]]);
+    let height = u16::from_le_bytes([header[8], header[9]]);
+    raster(width.into(), height.into())
+}
+
+/// Размеры из первого маркера SOF; предшествующие сегменты пропускаются.
+fn jpeg_dimensions(reader: &mut dyn Read) -> Option<Dimensions> {
+    let mut byte = [0u8; 1];
+    let mut next = |reader: &mut dyn Read| reader.read_exact(&mut byte).ok().map(|_| byte[0]);
+    if next(reader)? != 0xFF || next(reader)? != 0xD8 {
+        return None;
+    }
+    loop {
+        if next(reader)? != 0xFF {
+            return None;
+        }
+        // Перед маркером может стоять сколько угодно байт заполнения 0xFF.
+        let mut marker = next(reader)?;
+        while marker == 0xFF {
+            marker = next(reader)?;
+        }
+        match marker {
+            0x01 | 0xD0..=0xD7 => continue,
+            // Начало данных или конец файла до SOF.
+            0xD9 | 0xDA => return None,
+            _ => {}
+        }
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length).ok()?;
+        let length = u16::from_be_bytes(length).checked_sub(2)?;
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let mut frame = [0u8; 5];
+            reader.read_exact(&mut frame).ok()?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]);
+            let width = u16::from_be_bytes([frame[3], frame[4]]);
+            return raster(width.into(), height.into());
+        }
+        let skipped = io::copy(&mut (&mut *reader).take(length.into()), &mut io::sink()).ok()?;
+        if skipped < u64::from(length) {
+            return None;
+        }
+    }
+}
+
+/// Размеры растрового изображения; нулевые размеры означают поврежденный заголовок.
+fn raster(width: u32, height: u32) -> Option<Dimensions> {
+    (width > 0 && height > 0).then_some(Dimensions {
+        width: width.into(),
+        height: height.into(),
+    })
+}
+
+/// Размеры из атрибутов `width`/`height` корневого `<svg>`, иначе из `viewBox`.
+///
+/// Проценты и единицы, кроме `px`, размеров не задают.
+fn svg_dimensions(head: &str) -> Option<Dimensions> {
+    let start = head
+        .match_indices("<svg")
+        .map(|(index, _)| index)
+        .find(|&index| {
+            head[index + 4..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_whitespace() || c == '>')
+        })?;
+    let tag = &head[start..start + head[start..].find('>')?];
+    let length = |name| {
+        let value: &str = attribute(tag, name)?;
+        value.trim().trim_end_matches("px").parse::<f64>().ok()
+    };
+    if let (Some(width), Some(height)) = (length("width"), length("height")) {
+        return (width > 0.0 && height > 0.0).then_some(Dimensions { width, height });
+    }
+    let view_box: Vec<f64> = attribute(tag, "viewBox")?
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match view_box[..] {
+        [_, _, width, height] if width > 0.0 && height > 0.0 => Some(Dimensions { width, height }),
+        _ => None,
+    }
+}
+
+/// Значение атрибута `name` в открывающем теге `tag`.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    tag.match_indices(name).find_map(|(index, _)| {
+        let preceded = tag[..index].chars().next_back()?.is_whitespace();
+        let rest = tag[index + name.len()..].trim_start().strip_prefix('=')?;
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &rest[1..];
+        preceded.then(|| &value[..value.find(quote).unwrap_or(value.len())])
+    })
+}
+
+/// Текст заглушки изображения по каталогу `messages`.
+pub fn describe(
+    messages: &Messages,
+    path: &Path,
+    format: ImageFormat,
+    dimensions: Option<Dimensions>,
+    size: u64,
+) -> String {
+    let name = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    let size = report::format_size(size);
+    match dimensions {
+        Some(dimensions) => messages.format(
+            Message::Image,
+            &[
+                ("name", &name),
+                ("format", &format.as_str()),
+                ("dimensions", &dimensions),
+                ("size", &size),
+            ],
+        ),
+        None => messages.format(Message::ImageUnsized, &[("name", &name), ("size", &size)]),
+    }
+}
+
+/// Конвертер изображений в заглушки с размерами.
+#[derive(Debug, Default)]
+pub struct ImageConverter {
+    /// Наибольший размер SVG, выводимого как текст (`None` — SVG не выводятся).
+    svg_source_limit: Option<u64>,
+    /// Каталог текстов заглушек.
+    messages: Arc<Messages>,
+}
+
+impl ImageConverter {
+    /// Создает конвертер; `svg_source_limit` — наибольший размер SVG,
+    /// выводимого как текст (`None` — только заглушки).
+    pub fn new(svg_source_limit: Option<u64>, messages: Arc<Messages>) -> Self {
+        Self {
+            svg_source_limit,
+            messages,
+        }
+    }
+}
+
+impl FileConverter for ImageConverter {
+    fn name(&self) -> &'static str {
+        IMAGE_CONVERTER
+    }
+
+    fn is_streaming(&self) -> bool {
+        true
+    }
+
+    fn convert(&self, path: &Path, source: ConverterSource<'_>) -> Conversion {
+        let Some(format) = ImageFormat::from_path(path) else {
+            return Conversion::FallThrough;
+        };
+        let placeholder = |dimensions, len| {
+            Conversion::Placeholder(describe(&self.messages, path, format, dimensions, len))
+        };
+        let (reader, len): (&mut dyn Read, u64) = match source {
+            ConverterSource::Bytes(mut bytes) => {
+                let len = bytes.len() as u64;
+                let dimensions = read_dimensions(format, &mut bytes);
+                return placeholder(dimensions, len);
+            }
+            ConverterSource::Reader { reader, len } => (reader, len),
+        };
+        if format == ImageFormat::Svg && self.svg_source_limit.is_some_and(|limit| len <= limit) {
+            let mut source = Vec::new();
+            if reader.take(len).read_to_end(&mut source).is_ok() {
+                return match String::from_utf8(source) {
+                    Ok(text) => Conversion::Text(text),
+                    Err(e) => {
+                        let dimensions = svg_dimensions(&String::from_utf8_lossy(e.as_bytes()));
+                        placeholder(dimensions, len)
+                    }
+                };
+            }
+            return placeholder(None, len);
+        }
+        let dimensions = read_dimensions(format, reader);
+        placeholder(dimensions, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Сигнатура PNG и блок IHDR без контрольной суммы.
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&13u32.to_be_bytes());
+        bytes.extend_from_slice(b"IHDR");
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&[8, 6, 0, 0, 0]);
+        bytes
+    }
+
+    /// JPEG с сегментом APP0 перед SOF0.
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        bytes.extend_from_slice(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        bytes.extend_from_slice(&[0xFF, 0xFF, 0xC0, 0x00, 0x11, 0x08]);
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&[3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        bytes
+    }
+
+    fn convert(name: &str, bytes: &[u8], svg_source_limit: Option<u64>) -> Conversion {
+        ImageConverter::new(svg_source_limit, Arc::default()).convert(
+            Path::new(name),
+            ConverterSource::Reader {
+                reader: &mut &bytes[..],
+                len: bytes.len() as u64,
+            },
+        )
+    }
+
+    fn placeholder(name: &str, bytes: &[u8]) -> String {
+        match convert(name, bytes, None) {
+            Conversion::Placeholder(text) => text,
+            other => panic!("expected placeholder, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_png_dimensions() {
+        let bytes = png(512, 256);
+        assert_eq!(
+            placeholder("assets/logo.png", &bytes),
+            "[Image: logo.png, PNG 512×256, 29 bytes]"
+        );
+    }
+
+    #[test]
+    fn test_jpeg_dimensions() {
+        let bytes = jpeg(640, 480);
+        assert_eq!(
+            placeholder("photo.JPG", &bytes),
+            format!("[Image: photo.JPG, JPEG 640×480, {} bytes]", bytes.len())
+        );
+        // Сегмент без SOF до начала данных.
+        let bytes = [0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02];
+        assert_eq!(
+            placeholder("scan.jpeg", &bytes),
+            "[Image: scan.jpeg, 6 bytes]"
+        );
+    }
+
+    #[test]
+    fn test_gif_dimensions() {
+        let bytes = b"GIF89a\x10\x00\x20\x00\x80\x00\x00";
+        assert_eq!(
+            placeholder("spinner.gif", bytes),
+            "[Image: spinner.gif, GIF 16×32, 13 bytes]"
+        );
+    }
+
+    #[test]
+    fn test_svg_dimensions() {
+        let svg = r#"<?xml version="1.0"?>
+<!-- <svgs> in comments are not the root -->
+<svg xmlns="http://www.w3.org/2000/svg" width="24px" height='24' viewBox="0 0 48 48">
+<path d="M0 0h24v24H0z"/></svg>"#;
+        assert_eq!(
+            placeholder("icon.svg", svg.as_bytes()),
+            format!("[Image: icon.svg, SVG 24×24, {} bytes]", svg.len())
+        );
+        let svg = r#"<svg viewBox="0,0,120.5,60" width="100%"></svg>"#;
+        assert_eq!(
+            svg_dimensions(svg),
+            Some(Dimensions {
+                width: 120.5,
+                height: 60.0
+            })
+        );
+        assert_eq!(svg_dimensions(r#"<svg stroke-width="2"></svg>"#), None);
+    }
+
+    #[test]
+    fn test_corrupt_header_falls_back_to_size() {
+        let mut bytes = png(512, 512);
+        bytes[12..16].copy_from_slice(b"IDAT");
+        assert_eq!(
+            placeholder("logo.png", &bytes),
+            "[Image: logo.png, 29 bytes]"
+        );
+        assert_eq!(
+            placeholder("logo.png", &png(0, 512)),
+            "[Image: logo.png, 29 bytes]"
+        );
+        assert_eq!(
+            placeholder("cut.jpg", &jpeg(8, 8)[..24]),
+            "[Image: cut.jpg, 24 bytes]"
+        );
+        assert_eq!(
+            placeholder("fake.gif", b"GIF"),
+            "[Image: fake.gif, 3 bytes]"
+        );
+        assert_eq!(
+            placeholder("data.svg", b"\x00\x01"),
+            "[Image: data.svg, 2 bytes]"
+        );
+    }
+
+    #[test]
+    fn test_only_header_is_read() {
+        // Файл «размером» 1 ТБ: чтение до конца не завершилось бы.
+        let len = 1 << 40;
+        let mut reader = io::Cursor::new(png(4096, 4096)).chain(io::repeat(0).take(len));
+        let conversion = ImageConverter::new(Some(1024), Arc::default()).convert(
+            Path::new("huge.png"),
+            ConverterSource::Reader {
+                reader: &mut reader,
+                len,
+            },
+        );
+        let expected = format!(
+            "[Image: huge.png, PNG 4096×4096, {}]",
+            report::format_size(len)
+        );
+        assert_eq!(conversion, Conversion::Placeholder(expected));
+    }
+
+    #[test]
+    fn test_svg_source_under_limit() {
+        let svg = r#"<svg width="8" height="8"><rect width="8" height="8"/></svg>"#;
+        assert_eq!(
+            convert("dot.svg", svg.as_bytes(), Some(1024)),
+            Conversion::Text(svg.to_string())
+        );
+        assert!(matches!(
+            convert("dot.svg", svg.as_bytes(), Some(16)),
+            Conversion::Placeholder(text) if text.starts_with("[Image: dot.svg, SVG 8×8")
+        ));
+        // Растровые изображения как текст не выводятся.
+        assert!(matches!(
+            convert("logo.png", &png(1, 1), Some(1024)),
+            Conversion::Placeholder(_)
+        ));
+    }
+}
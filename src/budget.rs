@@ -0,0 +1,282 @@
+//! Модуль выбора файлов под бюджет размера документа (`--fit-budget`).
+//!
+//! Вместо обрезки документа на середине бюджет распределяется заранее, по
+//! размерам файлов из плана, до их чтения: файлы перебираются в порядке
+//! стратегии `--fit-strategy` и включаются, пока помещаются в остаток.
+//! Файл, не поместившийся целиком, пропускается, а перебор продолжается:
+//! меньший файл после него еще может поместиться. Файлы `--priority` всегда
+//! идут первыми, заглушки ничего не стоят. Бюджет общий для всех базовых
+//! папок, а пропущенные файлы перечисляются в конце документа в разделе
+//! «Omitted due to budget», чтобы ничего не пропадало молча.
+//!
+//! Бюджет считается по исходным размерам файлов: заголовки секций и
+//! преобразования (`--outline`, конвертеры) могут сдвинуть итог, но не больше
+//! чем на размер одного файла.
+
+use crate::grouping::PriorityRules;
+use crate::pipeline::PlannedFile;
+use crate::report::{self, BYTES_PER_TOKEN};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Порядок, в котором файлы претендуют на бюджет (`--fit-strategy`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum FitStrategy {
+    /// Сначала `--priority`, затем остальные от меньших к большим (по умолчанию).
+    #[default]
+    Priority,
+    /// По кругу между директориями: сначала самый маленький файл каждой
+    /// директории, затем следующий и т. д.
+    Breadth,
+    /// Директория за директорией в порядке дерева, в каждой — от меньших к большим.
+    Depth,
+}
+
+/// Разбирает бюджет: размер (`512KB`) или число токенов (`32k tokens`).
+///
+/// Токены переводятся в байты по тому же среднему, что и оценка экономии
+/// токенов в статистике.
+pub fn parse_budget(value: &str) -> Result<u64, String> {
+    let lower = value.trim().to_ascii_lowercase();
+    let bytes = match lower
+        .strip_suffix("tokens")
+        .or_else(|| lower.strip_suffix("token"))
+    {
+        Some(count) => {
+            let count = count.trim();
+            let (digits, multiplier) = match count.char_indices().last() {
+                Some((index, 'k')) => (&count[..index], 1_000),
+                Some((index, 'm')) => (&count[..index], 1_000_000),
+                _ => (count, 1),
+            };
+            let tokens: u64 = digits
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid token count: {}", value))?;
+            tokens
+                .checked_mul(multiplier * BYTES_PER_TOKEN)
+                .ok_or_else(|| format!("Budget is too large: {}", value))?
+        }
+        None => report::parse_size(value)?,
+    };
+    if bytes == 0 {
+        return Err("Budget must be greater than zero".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Файл, не вошедший в бюджет.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OmittedFile {
+    /// Путь файла в документе (с меткой базовой папки).
+    pub path: PathBuf,
+    /// Размер файла на диске.
+    pub size: u64,
+}
+
+/// Остаток бюджета, распределяемый между базовыми папками по очереди.
+#[derive(Debug, Clone)]
+pub struct BudgetFill {
+    /// Сколько байт еще можно включить.
+    remaining: u64,
+    strategy: FitStrategy,
+}
+
+impl BudgetFill {
+    /// Создает бюджет в `limit` байт.
+    pub fn new(limit: u64, strategy: FitStrategy) -> Self {
+        Self {
+            remaining: limit,
+            strategy,
+        }
+    }
+
+    /// Оставляет в `planned` базовой папки `base` файлы, вошедшие в остаток
+    /// бюджета, в прежнем порядке; возвращает остальные с их размерами в
+    /// порядке плана.
+    pub(crate) fn select(
+        &mut self,
+        base: &Path,
+        priority: &PriorityRules,
+        planned: &mut Vec<(PathBuf, PlannedFile)>,
+    ) -> Vec<(PathBuf, u64)> {
+        let mut selected = vec![false; planned.len()];
+        for index in self.order(base, priority, planned) {
+            let file = &planned[index].1;
+            let cost = if file.placeholder {
+                0
+            } else {
+                file.original_size
+            };
+            if cost <= self.remaining {
+                self.remaining -= cost;
+                selected[index] = true;
+            }
+        }
+        let mut omitted = Vec::new();
+        let mut selected = selected.into_iter();
+        planned.retain(|(path, file)| {
+            let keep = selected.next().unwrap_or(true);
+            if !keep {
+                omitted.push((path.clone(), file.original_size));
+            }
+            keep
+        });
+        omitted
+    }
+
+    /// Индексы `planned` в порядке, в котором файлы претендуют на бюджет.
+    fn order(
+        &self,
+        base: &Path,
+        priority: &PriorityRules,
+        planned: &[(PathBuf, PlannedFile)],
+    ) -> Vec<usize> {
+        let size = |index: usize| planned[index].1.original_size;
+        let (mut first, mut rest): (Vec<usize>, Vec<usize>) =
+            (0..planned.len()).partition(|&index| priority.rank(base, &planned[index].0).is_some());
+        first.sort_by_key(|&index| (priority.rank(base, &planned[index].0), size(index), index));
+
+        // Директории нумеруются в порядке дерева, то есть плана.
+        let mut directories: HashMap<&Path, usize> = HashMap::new();
+        let directory = |index: usize| planned[index].0.parent().unwrap_or(Path::new(""));
+        for &index in &rest {
+            let next = directories.len();
+            directories.entry(directory(index)).or_insert(next);
+        }
+        match self.strategy {
+            FitStrategy::Priority => rest.sort_by_key(|&index| (size(index), index)),
+            FitStrategy::Depth => {
+                rest.sort_by_key(|&index| (directories[directory(index)], size(index), index))
+            }
+            FitStrategy::Breadth => {
+                rest.sort_by_key(|&index| (directories[directory(index)], size(index), index));
+                // Номер файла внутри директории задает круг, в котором он выбирается.
+                let mut round = HashMap::new();
+                let rounds: HashMap<usize, usize> = rest
+                    .iter()
+                    .map(|&index| {
+                        let counter = round.entry(directory(index)).or_insert(0);
+                        *counter += 1;
+                        (index, *counter)
+                    })
+                    .collect();
+                rest.sort_by_key(|&index| (rounds[&index], directories[directory(index)]));
+            }
+        }
+        first.extend(rest);
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(files: &[(&str, u64)]) -> Vec<(PathBuf, PlannedFile)> {
+        files
+            .iter()
+            .map(|(path, size)| {
+                let file = PlannedFile {
+                    original_size: *size,
+                    ..PlannedFile::default()
+                };
+                (Path::new("/repo").join(path), file)
+            })
+            .collect()
+    }
+
+    fn paths(files: &[(PathBuf, PlannedFile)]) -> Vec<String> {
+        files
+            .iter()
+            .map(|(path, _)| path.strip_prefix("/repo").unwrap().display().to_string())
+            .collect()
+    }
+
+    const FILES: &[(&str, u64)] = &[
+        ("README.md", 300),
+        ("src/big.rs", 500),
+        ("src/lib.rs", 200),
+        ("src/util.rs", 100),
+        ("tests/a.rs", 150),
+        ("tests/b.rs", 250),
+    ];
+
+    fn select(strategy: FitStrategy, limit: u64) -> (Vec<String>, Vec<(String, u64)>) {
+        let priority = PriorityRules::new(&["README*".to_string()]).unwrap();
+        let mut planned = plan(FILES);
+        let omitted =
+            BudgetFill::new(limit, strategy).select(Path::new("/repo"), &priority, &mut planned);
+        let omitted = omitted
+            .into_iter()
+            .map(|(path, size)| {
+                let path = path.strip_prefix("/repo").unwrap().display().to_string();
+                (path, size)
+            })
+            .collect();
+        (paths(&planned), omitted)
+    }
+
+    #[test]
+    fn test_fit_strategies() {
+        // README первым, затем самые маленькие файлы.
+        let (kept, omitted) = select(FitStrategy::Priority, 800);
+        assert_eq!(
+            kept,
+            ["README.md", "src/lib.rs", "src/util.rs", "tests/a.rs"]
+        );
+        assert_eq!(
+            omitted,
+            [
+                ("src/big.rs".to_string(), 500),
+                ("tests/b.rs".to_string(), 250)
+            ]
+        );
+        // По кругу: самый маленький файл `src/`, затем `tests/`, затем второй в `src/`.
+        let (kept, _) = select(FitStrategy::Breadth, 800);
+        assert_eq!(
+            kept,
+            ["README.md", "src/lib.rs", "src/util.rs", "tests/a.rs"]
+        );
+        let (kept, _) = select(FitStrategy::Breadth, 600);
+        assert_eq!(kept, ["README.md", "src/util.rs", "tests/a.rs"]);
+        // Директория целиком, затем следующая; не поместившийся файл пропускается.
+        let (kept, omitted) = select(FitStrategy::Depth, 700);
+        assert_eq!(kept, ["README.md", "src/lib.rs", "src/util.rs"]);
+        assert_eq!(omitted.len(), 3);
+        let (kept, _) = select(FitStrategy::Priority, 700);
+        assert_eq!(kept, ["README.md", "src/util.rs", "tests/a.rs"]);
+    }
+
+    #[test]
+    fn test_budget_respected_and_omissions_complete() {
+        for strategy in [
+            FitStrategy::Priority,
+            FitStrategy::Breadth,
+            FitStrategy::Depth,
+        ] {
+            for limit in [1, 250, 700, 1000, 5000] {
+                let (kept, omitted) = select(strategy, limit);
+                let size = |name: &String| FILES.iter().find(|(path, _)| path == name).unwrap().1;
+                assert!(kept.iter().map(size).sum::<u64>() <= limit);
+                let mut all: Vec<String> = kept
+                    .into_iter()
+                    .chain(omitted.into_iter().map(|o| o.0))
+                    .collect();
+                all.sort();
+                assert_eq!(all, paths(&plan(FILES)), "{:?} {}", strategy, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_budget() {
+        assert_eq!(parse_budget("512KB"), Ok(512 * 1024));
+        assert_eq!(parse_budget("32k tokens"), Ok(128_000));
+        assert_eq!(parse_budget("1000tokens"), Ok(4000));
+        assert!(parse_budget("0").is_err());
+        assert!(parse_budget("many tokens").is_err());
+    }
+}
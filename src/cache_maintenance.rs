@@ -0,0 +1,499 @@
+//! Модуль обслуживания директории кэша `~/.flatten/` (`--cache-status`, `--cache-prune`).
+//!
+//! `TemplateManager` записывает конфигурацию и кэш шаблонов атомарно: во
+//! временный файл `.flatten-tmp*` рядом, который затем переименовывается, —
+//! под блокировкой файла `.lock`. Прерванная запись оставляет временный файл,
+//! а восстановление поврежденного файла — копию `<имя>.corrupt-<время>`.
+//!
+//! `--cache-prune` удаляет копии старше срока хранения и временные файлы, а с
+//! `--all` — все содержимое директории, кроме файла блокировки. Очистка берет
+//! ту же блокировку без ожидания и не начинается, пока идет запись кэша,
+//! поэтому любой найденный временный файл заведомо брошен. Перед удалением
+//! путь канонизируется и сверяется с директорией кэша: файл, который через
+//! символическую ссылку ведет за ее пределы, не удаляется.
+
+use crate::output::OutputFormat;
+use crate::report::format_size;
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Файл блокировки записи кэша.
+pub const LOCK_FILE: &str = ".lock";
+/// Начало имени временного файла атомарной записи.
+pub const TEMP_PREFIX: &str = ".flatten-tmp";
+/// Метка в имени сохраненного поврежденного файла.
+pub const CORRUPT_MARKER: &str = ".corrupt-";
+
+/// Вид файла в директории кэша.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheFileKind {
+    /// Конфигурация менеджера шаблонов.
+    Config,
+    /// Кэш шаблонов.
+    Templates,
+    /// Сохраненная копия поврежденного файла.
+    Corrupt,
+    /// Временный файл атомарной записи.
+    Temporary,
+    /// Файл блокировки.
+    Lock,
+    /// Прочие файлы и директории.
+    Other,
+}
+
+impl CacheFileKind {
+    /// Определяет вид файла по имени.
+    fn of(name: &str) -> Self {
+        if name == LOCK_FILE {
+            CacheFileKind::Lock
+        } else if name.starts_with(TEMP_PREFIX) {
+            CacheFileKind::Temporary
+        } else if name.contains(CORRUPT_MARKER) {
+            CacheFileKind::Corrupt
+        } else if name == "manager_config.json" {
+            CacheFileKind::Config
+        } else if name == "templates_cache.json" {
+            CacheFileKind::Templates
+        } else {
+            CacheFileKind::Other
+        }
+    }
+
+    /// Возвращает короткое имя вида для вывода в консоль.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheFileKind::Config => "config",
+            CacheFileKind::Templates => "templates",
+            CacheFileKind::Corrupt => "corrupt",
+            CacheFileKind::Temporary => "temporary",
+            CacheFileKind::Lock => "lock",
+            CacheFileKind::Other => "other",
+        }
+    }
+}
+
+/// Файл (или директория с содержимым) в директории кэша.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CacheFile {
+    /// Путь к файлу.
+    pub path: PathBuf,
+    /// Размер; для директории — суммарный размер ее файлов.
+    pub bytes: u64,
+    /// Вид файла.
+    pub kind: CacheFileKind,
+    /// Время изменения.
+    #[serde(skip)]
+    modified: Option<SystemTime>,
+}
+
+/// Содержимое директории кэша с размерами (`--cache-status`).
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStatus {
+    /// Директория кэша.
+    pub directory: PathBuf,
+    /// Файлы по убыванию размера.
+    pub files: Vec<CacheFile>,
+    /// Суммарный размер директории.
+    pub total_bytes: u64,
+}
+
+impl CacheStatus {
+    /// Собирает содержимое директории `directory`; отсутствующая директория пуста.
+    ///
+    /// # Ошибки
+    /// Если директорию не удалось прочитать.
+    pub fn collect(directory: &Path) -> Result<Self> {
+        let mut files = if directory.exists() {
+            list_files(directory)?
+        } else {
+            Vec::new()
+        };
+        files.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            total_bytes: files.iter().map(|file| file.bytes).sum(),
+            files,
+        })
+    }
+
+    /// Печатает содержимое в формате `format` (JSON или текст).
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+        println!(
+            "Cache directory: {} ({} in {} files)",
+            self.directory.display(),
+            format_size(self.total_bytes),
+            self.files.len()
+        );
+        for file in &self.files {
+            let name = file.path.strip_prefix(&self.directory).unwrap_or(&file.path);
+            println!(
+                "  {:>10}  {} ({})",
+                format_size(file.bytes),
+                name.display(),
+                file.kind.as_str()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Что удалять при очистке (`--cache-prune`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PruneOptions {
+    /// Сколько хранить копии поврежденных файлов.
+    pub retention: Duration,
+    /// Удалить все содержимое директории (`--all`).
+    pub all: bool,
+}
+
+/// Итог очистки кэша.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+    /// Удаленные файлы.
+    pub removed: Vec<CacheFile>,
+    /// Освобожденный объем.
+    pub freed_bytes: u64,
+    /// Файлы, которые ведут за пределы директории кэша и потому не удалены.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub outside: Vec<PathBuf>,
+}
+
+impl PruneReport {
+    /// Печатает итог в формате `format` (JSON или текст).
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+        for file in &self.removed {
+            println!(
+                "Removed {} ({}, {})",
+                file.path.display(),
+                file.kind.as_str(),
+                format_size(file.bytes)
+            );
+        }
+        for path in &self.outside {
+            println!("Kept {}: it resolves outside the cache directory", path.display());
+        }
+        println!(
+            "Freed {} ({} files removed)",
+            format_size(self.freed_bytes),
+            self.removed.len()
+        );
+        Ok(())
+    }
+}
+
+/// Блокировка записи в директорию кэша; снимается при освобождении.
+#[derive(Debug)]
+pub struct CacheLock {
+    _file: File,
+}
+
+impl CacheLock {
+    /// Ждет и берет блокировку директории `directory`.
+    ///
+    /// # Ошибки
+    /// Если файл блокировки не удалось открыть или заблокировать.
+    pub fn acquire(directory: &Path) -> Result<Self> {
+        let file = open_lock(directory)?;
+        file.lock()
+            .with_context(|| format!("Failed to lock cache directory: {}", directory.display()))?;
+        Ok(Self { _file: file })
+    }
+
+    /// Берет блокировку, не дожидаясь; `None`, если она занята другим процессом.
+    ///
+    /// # Ошибки
+    /// Если файл блокировки не удалось открыть.
+    pub fn try_acquire(directory: &Path) -> Result<Option<Self>> {
+        let file = open_lock(directory)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(fs::TryLockError::WouldBlock) => Ok(None),
+            Err(fs::TryLockError::Error(error)) => Err(error).with_context(|| {
+                format!("Failed to lock cache directory: {}", directory.display())
+            }),
+        }
+    }
+}
+
+fn open_lock(directory: &Path) -> Result<File> {
+    let path = directory.join(LOCK_FILE);
+    File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open cache lock: {}", path.display()))
+}
+
+/// Атомарно записывает `content` в файл `path` директории кэша под блокировкой.
+///
+/// # Ошибки
+/// Если блокировку не удалось взять или файл не удалось записать.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let directory = path.parent().context("Cache file has no parent directory")?;
+    let _lock = CacheLock::acquire(directory)?;
+    let mut file = tempfile::Builder::new()
+        .prefix(TEMP_PREFIX)
+        .tempfile_in(directory)?;
+    file.write_all(content)?;
+    file.flush()?;
+    file.persist(path)?;
+    Ok(())
+}
+
+/// Удаляет из директории кэша `directory` брошенные и устаревшие файлы.
+///
+/// Копии поврежденных файлов удаляются, если они старше
+/// `options.retention` на момент `now`; временные файлы — всегда.
+///
+/// # Ошибки
+/// Если кэш сейчас записывается другим процессом, директорию не удалось
+/// прочитать или файл не удалось удалить.
+pub fn prune(directory: &Path, options: &PruneOptions, now: SystemTime) -> Result<PruneReport> {
+    let mut report = PruneReport::default();
+    if !directory.exists() {
+        return Ok(report);
+    }
+    let root = directory.canonicalize().with_context(|| {
+        format!("Failed to resolve cache directory: {}", directory.display())
+    })?;
+    let Some(_lock) = CacheLock::try_acquire(&root)? else {
+        bail!(
+            "Cache directory {} is being updated by another process; try again later",
+            directory.display()
+        );
+    };
+    for file in list_files(&root)? {
+        let expired = |file: &CacheFile| {
+            let created = corrupt_timestamp(&file.path).or(file.modified);
+            created.is_some_and(|created| {
+                now.duration_since(created).unwrap_or_default() > options.retention
+            })
+        };
+        let remove = match file.kind {
+            CacheFileKind::Lock => false,
+            _ if options.all => true,
+            CacheFileKind::Temporary => true,
+            CacheFileKind::Corrupt => expired(&file),
+            _ => false,
+        };
+        if !remove {
+            continue;
+        }
+        // Файл, ведущий наружу, не удаляется, даже если лежит в директории.
+        if !file
+            .path
+            .canonicalize()
+            .is_ok_and(|resolved| resolved.starts_with(&root) && resolved != root)
+        {
+            report.outside.push(file.path);
+            continue;
+        }
+        let removed = if file.path.is_dir() {
+            fs::remove_dir_all(&file.path)
+        } else {
+            fs::remove_file(&file.path)
+        };
+        removed.with_context(|| format!("Failed to remove {}", file.path.display()))?;
+        report.freed_bytes += file.bytes;
+        report.removed.push(file);
+    }
+    Ok(report)
+}
+
+/// Спрашивает подтверждение удаления всего кэша (`--cache-prune --all`).
+///
+/// Без терминала (`input` — `None`) подтверждение не получить: нужен `--yes`.
+pub fn confirm_all(
+    status: &CacheStatus,
+    input: Option<&mut dyn BufRead>,
+    output: &mut dyn Write,
+) -> Result<bool> {
+    let Some(input) = input else {
+        writeln!(output, "Not removing the whole cache without confirmation (pass --yes)")?;
+        return Ok(false);
+    };
+    write!(
+        output,
+        "Remove all {} files ({}) in {}? [y/N] ",
+        status.files.len(),
+        format_size(status.total_bytes),
+        status.directory.display()
+    )?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// Перечисляет файлы верхнего уровня директории с размерами.
+fn list_files(directory: &Path) -> Result<Vec<CacheFile>> {
+    let read_error = || format!("Failed to read cache directory: {}", directory.display());
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory).with_context(read_error)? {
+        let entry = entry.with_context(read_error)?;
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let bytes = if metadata.is_dir() {
+            crate::pipeline::directory_usage(&path).1
+        } else {
+            metadata.len()
+        };
+        files.push(CacheFile {
+            kind: CacheFileKind::of(&entry.file_name().to_string_lossy()),
+            path,
+            bytes,
+            modified: metadata.modified().ok(),
+        });
+    }
+    Ok(files)
+}
+
+/// Время сохранения поврежденного файла из его имени (`<имя>.corrupt-<секунды>`).
+fn corrupt_timestamp(path: &Path) -> Option<SystemTime> {
+    let name = path.file_name()?.to_str()?;
+    let (_, seconds) = name.rsplit_once(CORRUPT_MARKER)?;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    /// Кэш с файлами каждого вида и копиями поврежденных файлов разного возраста.
+    fn stale_cache(now: u64) -> Result<tempfile::TempDir> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        fs::write(root.join("manager_config.json"), "{}")?;
+        fs::write(root.join("templates_cache.json"), "{\"rust\": 1}")?;
+        fs::write(
+            root.join(format!("templates_cache.json.corrupt-{}", now - 90 * DAY)),
+            "old!",
+        )?;
+        fs::write(
+            root.join(format!("manager_config.json.corrupt-{}", now - DAY)),
+            "new",
+        )?;
+        fs::write(root.join(".flatten-tmpAbC123"), "partial write")?;
+        fs::write(root.join("notes.txt"), "mine")?;
+        Ok(dir)
+    }
+
+    fn names(files: &[CacheFile]) -> Vec<String> {
+        let mut names: Vec<String> = files
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_prune_removes_only_stale_artifacts() -> Result<()> {
+        let now = 2_000_000_000;
+        let dir = stale_cache(now)?;
+        let options = PruneOptions {
+            retention: Duration::from_secs(30 * DAY),
+            all: false,
+        };
+        let report = prune(dir.path(), &options, UNIX_EPOCH + Duration::from_secs(now))?;
+        assert_eq!(
+            names(&report.removed),
+            [
+                ".flatten-tmpAbC123".to_string(),
+                format!("templates_cache.json.corrupt-{}", now - 90 * DAY),
+            ]
+        );
+        assert_eq!(report.freed_bytes, 17);
+        let status = CacheStatus::collect(dir.path())?;
+        assert_eq!(
+            names(&status.files),
+            [
+                ".lock".to_string(),
+                "manager_config.json".to_string(),
+                format!("manager_config.json.corrupt-{}", now - DAY),
+                "notes.txt".to_string(),
+                "templates_cache.json".to_string(),
+            ]
+        );
+        assert_eq!(status.files[0].kind, CacheFileKind::Templates);
+        assert_eq!(status.total_bytes, 2 + 11 + 3 + 4);
+
+        // С `--all` удаляется все, кроме файла блокировки.
+        let all = PruneOptions { all: true, ..options };
+        let report = prune(dir.path(), &all, SystemTime::now())?;
+        assert_eq!(report.removed.len(), 4);
+        assert_eq!(names(&CacheStatus::collect(dir.path())?.files), [".lock"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_respects_lock() -> Result<()> {
+        let dir = stale_cache(2_000_000_000)?;
+        let _lock = CacheLock::acquire(dir.path())?;
+        let options = PruneOptions {
+            retention: Duration::ZERO,
+            all: true,
+        };
+        let error = prune(dir.path(), &options, SystemTime::now()).unwrap_err();
+        assert!(error.to_string().contains("being updated"), "{}", error);
+        assert!(dir.path().join(".flatten-tmpAbC123").exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prune_keeps_links_outside_cache() -> Result<()> {
+        let outside = tempfile::tempdir()?;
+        let target = outside.path().join("precious.corrupt-1");
+        fs::write(&target, "keep me")?;
+        let dir = tempfile::tempdir()?;
+        let link = dir.path().join("templates_cache.json.corrupt-1");
+        std::os::unix::fs::symlink(&target, &link)?;
+
+        let options = PruneOptions {
+            retention: Duration::ZERO,
+            all: false,
+        };
+        let report = prune(dir.path(), &options, SystemTime::now())?;
+        assert!(report.removed.is_empty());
+        assert_eq!(report.outside, vec![link.clone()]);
+        assert!(target.exists() && link.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_confirm_all() -> Result<()> {
+        let dir = stale_cache(2_000_000_000)?;
+        let status = CacheStatus::collect(dir.path())?;
+        let mut output = Vec::new();
+        assert!(confirm_all(&status, Some(&mut Cursor::new("y\n")), &mut output)?);
+        assert!(!confirm_all(&status, Some(&mut Cursor::new("\n")), &mut output)?);
+        assert!(!confirm_all(&status, None, &mut output)?);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Remove all 6 files"), "{}", output);
+        assert!(output.contains("pass --yes"));
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! Модуль разбиения содержимого файлов на фрагменты (`--chunk-size`).
+//!
+//! Для загрузки в RAG-конвейеры содержимое файла в форматах JSON и JSONL
+//! можно выдавать фрагментами ограниченного размера в символах. Фрагмент
+//! по возможности заканчивается на границе строки и никогда не разрезает
+//! многобайтовый символ. Каждый фрагмент, кроме первого, начинается с
+//! последних `overlap` символов предыдущего, поэтому исходный текст
+//! восстанавливается склейкой фрагментов без их первых `overlap` символов.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Параметры разбиения на фрагменты.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkOptions {
+    /// Наибольший размер фрагмента в символах.
+    pub size: usize,
+    /// Сколько символов предыдущего фрагмента повторяется в начале следующего.
+    pub overlap: usize,
+}
+
+/// Фрагмент содержимого: байтовый диапазон текста и номера его строк.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Начало фрагмента в байтах.
+    pub start: usize,
+    /// Конец фрагмента в байтах (не включительно).
+    pub end: usize,
+    /// Номер строки первого символа (с 1).
+    pub start_line: usize,
+    /// Номер строки последнего символа.
+    pub end_line: usize,
+}
+
+impl ChunkOptions {
+    /// Проверяет параметры: размер больше нуля, перекрытие меньше размера.
+    pub fn new(size: usize, overlap: usize) -> Result<Self> {
+        if size == 0 {
+            bail!("--chunk-size must be greater than 0");
+        }
+        if overlap >= size {
+            bail!(
+                "--chunk-overlap ({}) must be smaller than --chunk-size ({})",
+                overlap,
+                size
+            );
+        }
+        Ok(Self { size, overlap })
+    }
+
+    /// Разбивает `text` на фрагменты; у пустого текста один пустой фрагмент.
+    pub fn split(&self, text: &str) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let mut start_lines = LineCursor::default();
+        let mut end_lines = LineCursor::default();
+        let mut start = 0;
+        loop {
+            let limit = advance(text, start, self.size);
+            let mut end = limit;
+            // Граница строки подходит, только если после нее фрагмент длиннее
+            // перекрытия: иначе следующий фрагмент не продвинулся бы вперед.
+            if limit < text.len()
+                && let Some(newline) = text[start..limit].rfind('\n')
+                && text[start..start + newline + 1].chars().count() > self.overlap
+            {
+                end = start + newline + 1;
+            }
+
+            let start_line = start_lines.line_at(text, start);
+            let end_line = if end > start {
+                end_lines.line_at(text, end - 1)
+            } else {
+                start_line
+            };
+            chunks.push(Chunk {
+                start,
+                end,
+                start_line,
+                end_line,
+            });
+            if end >= text.len() {
+                return chunks;
+            }
+            start = retreat(text, end, self.overlap);
+        }
+    }
+}
+
+/// Возвращает байтовую позицию через `count` символов после `start`.
+fn advance(text: &str, start: usize, count: usize) -> usize {
+    text[start..]
+        .char_indices()
+        .nth(count)
+        .map_or(text.len(), |(offset, _)| start + offset)
+}
+
+/// Возвращает байтовую позицию за `count` символов до `end`.
+fn retreat(text: &str, end: usize, count: usize) -> usize {
+    match count {
+        0 => end,
+        _ => text[..end]
+            .char_indices()
+            .rev()
+            .nth(count - 1)
+            .map_or(0, |(offset, _)| offset),
+    }
+}
+
+/// Считает строки до монотонно растущих позиций, не перечитывая текст с начала.
+#[derive(Default)]
+struct LineCursor {
+    position: usize,
+    newlines: usize,
+}
+
+impl LineCursor {
+    /// Возвращает номер строки байта `position` (не меньше предыдущего запроса).
+    fn line_at(&mut self, text: &str, position: usize) -> usize {
+        self.newlines += text.as_bytes()[self.position..position]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count();
+        self.position = position;
+        self.newlines + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Склеивает фрагменты, отбрасывая перекрытие в начале каждого, кроме первого.
+    fn reconstruct(text: &str, chunks: &[Chunk], overlap: usize) -> String {
+        let mut result = String::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let content = &text[chunk.start..chunk.end];
+            let skip = if index == 0 { 0 } else { overlap };
+            result.extend(content.chars().skip(skip));
+        }
+        result
+    }
+
+    #[test]
+    fn test_chunks_prefer_line_boundaries() -> Result<()> {
+        let text = "first line\nsecond\nthird line here\n";
+        let chunks = ChunkOptions::new(20, 0)?.split(text);
+        let parts: Vec<_> = chunks.iter().map(|c| &text[c.start..c.end]).collect();
+        assert_eq!(parts, vec!["first line\nsecond\n", "third line here\n"]);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 2));
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (3, 3));
+
+        assert_eq!(ChunkOptions::new(8, 0)?.split("").len(), 1);
+        assert!(ChunkOptions::new(4, 4).is_err());
+        assert!(ChunkOptions::new(0, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunks_reconstruct_original() -> Result<()> {
+        // Детерминированный генератор вместо внешнего крейта для property-тестов.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let alphabet = ['a', 'b', ' ', '\n', 'é', 'ж', '€', '🦀', '\r', '\t'];
+
+        for _ in 0..300 {
+            let length = (next() % 200) as usize;
+            let text: String = (0..length)
+                .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+                .collect();
+            let size = 1 + (next() % 40) as usize;
+            let overlap = (next() % size as u64) as usize;
+            let chunks = ChunkOptions::new(size, overlap)?.split(&text);
+
+            assert_eq!(reconstruct(&text, &chunks, overlap), text, "{:?}", text);
+            for chunk in &chunks {
+                let content = &text[chunk.start..chunk.end];
+                assert!(content.chars().count() <= size);
+                let lines = content
+                    .strip_suffix('\n')
+                    .unwrap_or(content)
+                    .matches('\n')
+                    .count();
+                assert_eq!(
+                    chunk.end_line - chunk.start_line,
+                    lines,
+                    "{:?} in {:?}",
+                    chunk,
+                    text
+                );
+            }
+        }
+        Ok(())
+    }
+}
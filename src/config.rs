@@ -3,14 +3,51 @@
 //! Предоставляет функциональность для загрузки, кэширования и обновления
 //! шаблонов в формате gitignore из внешнего API (toptal.com).
 //! Управление конфигурацией и кэшем происходит в директории `~/.flatten/`.
+//!
+//! Полный список шаблонов (несколько мегабайт) загружается, только когда он
+//! действительно нужен: для `--list-templates`, `--auto-detect` и подсказок к
+//! неизвестному ключу. Шаблон из `-e` при холодном кэше догружается отдельным
+//! запросом и сохраняется рядом с остальными; такой кэш помечается как
+//! частичный (`partial_cache` в конфигурации), пока не загрузится полный список.
+//!
+//! HTTP-доступ вынесен в трейт `TemplateFetcher`, поэтому источник шаблонов
+//! можно подменить (например, в тестах). Реализация для toptal.com доступна
+//! только с включенной feature `network`; без нее менеджер работает с
+//! локальным кэшем, а обновление возвращает ошибку.
+//!
+//! Поврежденный файл кэша или конфигурации (не разбирается или нарушает
+//! ограничения: пустой ключ шаблона, слишком большое содержимое) не
+//! заменяется молча: он сохраняется рядом как `<имя>.corrupt-<время>`,
+//! в stderr выводится предупреждение с путем и ошибкой, после чего файл
+//! строится заново. Сохраненные файлы показывает `--doctor`.
+//!
+//! Загруженные шаблоны нормализуются (см. модуль `template_lint`); шаблон,
+//! не прошедший проверку, в кэш не сохраняется, о чем выводится предупреждение.
+//!
+//! Файлы записываются атомарно под блокировкой директории (см.
+//! `cache_maintenance`), поэтому одновременные запуски не портят кэш.
 
+use crate::cache_maintenance::{self, CORRUPT_MARKER};
+use crate::error::FlattenError;
+use crate::presets::PresetDefinition;
+use crate::template_lint::{self, TemplateLint};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Наибольший размер содержимого одного шаблона в кэше (1 МБ).
+const MAX_TEMPLATE_SIZE: usize = 1024 * 1024;
+
+#[cfg(feature = "network")]
 const API_LIST_URL: &str = "https://www.toptal.com/developers/gitignore/api/list?format=json";
+/// Адрес отдельного шаблона без ключа.
+#[cfg(feature = "network")]
+const API_TEMPLATE_URL: &str = "https://www.toptal.com/developers/gitignore/api/";
 
 /// Конфигурация менеджера шаблонов.
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,6 +57,29 @@ pub struct ManagerConfig {
     pub last_updated: u64,
     /// Продолжительность хранения кэша в секундах.
     pub cache_duration: u64,
+    /// Пользовательские наборы настроек для `--preset`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub presets: BTreeMap<String, PresetDefinition>,
+    /// Пользовательские синонимы ключей шаблонов (`alias` → ключ Toptal).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub template_aliases: BTreeMap<String, String>,
+    /// Дополнительные соответствия имен и суффиксов файлов языкам
+    /// (`"Jenkinsfile"` или `".proto"` → язык).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub languages: BTreeMap<String, String>,
+    /// Настройки отдельных базовых папок (см. модуль `folder_overrides`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub folders: BTreeMap<String, serde_json::Value>,
+    /// Кэш содержит только отдельно загруженные шаблоны: полный список еще не загружался.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub partial_cache: bool,
+    /// Дополнительные защищенные пути (см. модуль `protected_paths`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub protected_paths: Vec<PathBuf>,
+    /// Скрытые имена, включаемые без `--include-hidden` в дополнение к
+    /// `--include-hidden-names`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include_hidden_names: Vec<String>,
 }
 
 impl Default for ManagerConfig {
@@ -27,6 +87,13 @@ impl Default for ManagerConfig {
         Self {
             last_updated: 0,
             cache_duration: 86_400, // 24 часа
+            presets: BTreeMap::new(),
+            template_aliases: BTreeMap::new(),
+            languages: BTreeMap::new(),
+            folders: BTreeMap::new(),
+            partial_cache: false,
+            protected_paths: Vec::new(),
+            include_hidden_names: Vec::new(),
         }
     }
 }
@@ -38,40 +105,217 @@ pub struct Template {
     pub key: String,
     /// Имя шаблона.
     pub name: String,
-    /// Содержимое шаблона (в формате gitignore).
+    /// Содержимое шаблона (в формате gitignore), приведенное к каноническому виду.
     pub contents: String,
+    /// Правки, внесенные при нормализации (см. модуль `template_lint`).
+    #[serde(default, skip_serializing_if = "TemplateLint::is_clean")]
+    pub lint: TemplateLint,
 }
 
-/// Вспомогательная структура для парсинга ответа от Toptal API.
-#[derive(Debug, Deserialize)]
-struct ToptalEntry {
-    name: String,
+/// Элемент ответа Toptal API: шаблон без ключа.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToptalEntry {
+    /// Имя шаблона.
+    pub name: String,
+    /// Содержимое шаблона (в формате gitignore).
     #[serde(default)]
-    contents: String,
+    pub contents: String,
+}
+
+/// Будущее, возвращаемое `TemplateFetcher::fetch_list`.
+pub type FetchFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HashMap<String, ToptalEntry>>> + Send + 'a>>;
+
+/// Будущее, возвращаемое `TemplateFetcher::fetch_template`.
+pub type TemplateFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<String>>> + Send + 'a>>;
+
+/// Источник шаблонов: полный список и отдельные шаблоны.
+///
+/// Эквивалент `async fn fetch_list(&self) -> Result<HashMap<String, ToptalEntry>>`;
+/// будущее упаковано в `Box`, чтобы трейт можно было хранить как `dyn`.
+pub trait TemplateFetcher: Send + Sync {
+    /// Загружает все шаблоны; ключ карты — идентификатор шаблона.
+    fn fetch_list(&self) -> FetchFuture<'_>;
+
+    /// Загружает содержимое одного шаблона; `None`, если шаблона с таким ключом нет.
+    ///
+    /// По умолчанию источник не умеет загружать шаблоны по одному.
+    fn fetch_template(&self, key: &str) -> TemplateFuture<'_> {
+        let key = key.to_string();
+        Box::pin(async move {
+            Err(anyhow::anyhow!("Template source cannot fetch template '{}' on its own", key))
+        })
+    }
+}
+
+/// Загружает шаблоны из API toptal.com через `reqwest`.
+#[cfg(feature = "network")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ToptalFetcher;
+
+#[cfg(feature = "network")]
+impl TemplateFetcher for ToptalFetcher {
+    /// Использует endpoint `list?format=json`, который возвращает полный список
+    /// шаблонов с их содержимым, что позволяет избежать N+1 запросов.
+    fn fetch_list(&self) -> FetchFuture<'_> {
+        Box::pin(async {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(15))
+                .build()?;
+
+            let response = client
+                .get(API_LIST_URL)
+                .send()
+                .await
+                .context("Failed to connect to templates API")?;
+
+            // Парсим ответ как HashMap, где ключ - ID шаблона, значение - структура с содержимым
+            // Это решает проблему неправильного парсинга строк
+            response
+                .json()
+                .await
+                .context("Failed to parse templates JSON")
+        })
+    }
+
+    /// Использует endpoint `api/<ключ>`, который возвращает шаблон текстом.
+    fn fetch_template(&self, key: &str) -> TemplateFuture<'_> {
+        let url = format!("{}{}", API_TEMPLATE_URL, key);
+        Box::pin(async move {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(15))
+                .build()?;
+
+            let response = client
+                .get(&url)
+                .send()
+                .await
+                .context("Failed to connect to templates API")?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let contents = response
+                .error_for_status()
+                .context("Templates API returned an error")?
+                .text()
+                .await
+                .context("Failed to read template")?;
+            // Для неизвестного ключа API отвечает текстом ошибки в комментарии.
+            Ok((!contents.starts_with("#!! ERROR")).then_some(contents))
+        })
+    }
+}
+
+/// Источник, используемый без feature `network`: загрузка всегда завершается ошибкой.
+#[cfg(not(feature = "network"))]
+struct NoNetworkFetcher;
+
+#[cfg(not(feature = "network"))]
+impl TemplateFetcher for NoNetworkFetcher {
+    fn fetch_list(&self) -> FetchFuture<'_> {
+        Box::pin(async {
+            Err(anyhow::anyhow!(
+                "Template updates are unavailable: flatten-rust was built without the `network` feature"
+            ))
+        })
+    }
+
+    fn fetch_template(&self, _key: &str) -> TemplateFuture<'_> {
+        Box::pin(async {
+            Err(anyhow::anyhow!(
+                "Template downloads are unavailable: flatten-rust was built without the `network` feature"
+            ))
+        })
+    }
 }
 
 /// Управляет получением, кэшированием и доступом к шаблонам исключений.
-#[derive(Debug)]
 pub struct TemplateManager {
     config_path: PathBuf,
     templates_path: PathBuf,
     config: ManagerConfig,
     templates: HashMap<String, Template>,
+    /// Кэш-файл существовал, но не разобрался.
+    cache_corrupt: bool,
+    /// Файл конфигурации существовал, но не разобрался.
+    config_corrupt: bool,
+    /// Файлы только читаются: директория и конфигурация не создаются,
+    /// поврежденные файлы остаются на месте.
+    read_only: bool,
+    fetcher: Box<dyn TemplateFetcher>,
+}
+
+impl fmt::Debug for TemplateManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TemplateManager")
+            .field("config_path", &self.config_path)
+            .field("templates_path", &self.templates_path)
+            .field("config", &self.config)
+            .field("templates", &self.templates.len())
+            .field("cache_corrupt", &self.cache_corrupt)
+            .field("config_corrupt", &self.config_corrupt)
+            .field("read_only", &self.read_only)
+            .finish_non_exhaustive()
+    }
 }
 
 impl TemplateManager {
     /// Создает новый экземпляр `TemplateManager`.
     ///
-    /// Инициализирует пути, загружает конфигурацию и кэшированные шаблоны.
+    /// Инициализирует пути в `~/.flatten/`, загружает конфигурацию и кэшированные
+    /// шаблоны. Шаблоны обновляются из toptal.com (с feature `network`).
     ///
     /// # Ошибки
     /// Возвращает ошибку, если не удается определить домашнюю директорию
     /// или создать/прочитать файлы конфигурации.
     pub fn new() -> Result<Self> {
-        let home_dir = dirs::home_dir().context("Could not determine home directory")?;
-        let flatten_dir = home_dir.join(".flatten");
+        #[cfg(feature = "network")]
+        let fetcher = ToptalFetcher;
+        #[cfg(not(feature = "network"))]
+        let fetcher = NoNetworkFetcher;
+
+        Self::with_fetcher(default_directory()?, fetcher)
+    }
+
+    /// Открывает кэш в `~/.flatten/` только для чтения (`--doctor`).
+    ///
+    /// В отличие от `new`, ничего не записывает: отсутствующие директория и
+    /// `manager_config.json` не создаются, поврежденные файлы не переименовываются,
+    /// а конфигурация по умолчанию используется только в памяти.
+    ///
+    /// # Ошибки
+    /// Возвращает ошибку, если не удается определить домашнюю директорию
+    /// или прочитать файлы конфигурации.
+    pub fn open_read_only() -> Result<Self> {
+        #[cfg(feature = "network")]
+        let fetcher = ToptalFetcher;
+        #[cfg(not(feature = "network"))]
+        let fetcher = NoNetworkFetcher;
 
-        std::fs::create_dir_all(&flatten_dir).context("Failed to create .flatten directory")?;
+        Self::open(default_directory()?, fetcher, true)
+    }
+
+    /// Создает `TemplateManager` с кэшем в `flatten_dir` и собственным источником шаблонов.
+    ///
+    /// # Ошибки
+    /// Возвращает ошибку, если не удается создать директорию или прочитать
+    /// файлы конфигурации.
+    pub fn with_fetcher(
+        flatten_dir: impl Into<PathBuf>,
+        fetcher: impl TemplateFetcher + 'static,
+    ) -> Result<Self> {
+        Self::open(flatten_dir.into(), fetcher, false)
+    }
+
+    fn open(
+        flatten_dir: PathBuf,
+        fetcher: impl TemplateFetcher + 'static,
+        read_only: bool,
+    ) -> Result<Self> {
+        if !read_only {
+            std::fs::create_dir_all(&flatten_dir)
+                .context("Failed to create .flatten directory")?;
+        }
 
         let config_path = flatten_dir.join("manager_config.json");
         let templates_path = flatten_dir.join("templates_cache.json");
@@ -81,6 +325,10 @@ impl TemplateManager {
             templates_path,
             config: ManagerConfig::default(),
             templates: HashMap::new(),
+            cache_corrupt: false,
+            config_corrupt: false,
+            read_only,
+            fetcher: Box::new(fetcher),
         };
 
         manager.load_config()?;
@@ -90,13 +338,24 @@ impl TemplateManager {
     }
 
     /// Загружает конфигурацию из файла или создает новую, если файл отсутствует.
+    ///
+    /// Поврежденный файл сохраняется (см. `preserve_corrupt`) и заменяется
+    /// конфигурацией по умолчанию. В режиме только для чтения файл не
+    /// создается и не заменяется.
     fn load_config(&mut self) -> Result<()> {
         if self.config_path.exists() {
             let content = std::fs::read_to_string(&self.config_path)
                 .context("Failed to read config file")?;
-            // Если конфиг поврежден, используем дефолтный, не падаем
-            self.config = serde_json::from_str(&content).unwrap_or_default();
-        } else {
+            match serde_json::from_str(&content) {
+                Ok(config) => self.config = config,
+                Err(_) if self.read_only => self.config_corrupt = true,
+                Err(e) => {
+                    self.config_corrupt = true;
+                    preserve_corrupt(&self.config_path, &e);
+                    self.save_config()?;
+                }
+            }
+        } else if !self.read_only {
             self.save_config()?;
         }
         Ok(())
@@ -106,17 +365,30 @@ impl TemplateManager {
     fn save_config(&self) -> Result<()> {
         let content =
             serde_json::to_string_pretty(&self.config).context("Failed to serialize config")?;
-        std::fs::write(&self.config_path, content).context("Failed to write config file")?;
+        cache_maintenance::write_atomic(&self.config_path, content.as_bytes())
+            .context("Failed to write config file")?;
         Ok(())
     }
 
     /// Загружает кэшированные шаблоны из файла.
+    ///
+    /// Поврежденный кэш сохраняется (см. `preserve_corrupt`), шаблоны остаются
+    /// пустыми и загружаются заново при следующем обновлении.
     fn load_templates(&mut self) -> Result<()> {
         if self.templates_path.exists() {
             let content = std::fs::read_to_string(&self.templates_path)
                 .context("Failed to read templates file")?;
-            // Если кэш поврежден, инициализируем пустой картой
-            self.templates = serde_json::from_str(&content).unwrap_or_default();
+            let parsed = serde_json::from_str(&content)
+                .map_err(anyhow::Error::from)
+                .and_then(|templates| validate_templates(&templates).map(|()| templates));
+            match parsed {
+                Ok(templates) => self.templates = templates,
+                Err(_) if self.read_only => self.cache_corrupt = true,
+                Err(e) => {
+                    self.cache_corrupt = true;
+                    preserve_corrupt(&self.templates_path, &e);
+                }
+            }
         }
         Ok(())
     }
@@ -125,19 +397,20 @@ impl TemplateManager {
     fn save_templates(&self) -> Result<()> {
         let content =
             serde_json::to_string_pretty(&self.templates).context("Failed to serialize templates")?;
-        std::fs::write(&self.templates_path, content).context("Failed to write templates file")?;
+        cache_maintenance::write_atomic(&self.templates_path, content.as_bytes())
+            .context("Failed to write templates file")?;
         Ok(())
     }
 
     /// Проверяет, истек ли срок действия кэша шаблонов.
-    fn needs_update(&self) -> bool {
+    pub fn needs_update(&self) -> bool {
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
             .as_secs();
         
-        // Если шаблонов нет совсем, обновление обязательно нужно
-        if self.templates.is_empty() {
+        // Если шаблонов нет совсем или есть только отдельные, обновление обязательно нужно
+        if self.templates.is_empty() || self.config.partial_cache {
             return true;
         }
 
@@ -151,7 +424,8 @@ impl TemplateManager {
     /// 1. Если кэш актуален -> ничего не делаем.
     /// 2. Если кэш устарел, пробуем скачать.
     /// 3. Если скачивание не удалось, но есть старый кэш -> используем его (soft fail).
-    /// 4. Если шаблонов нет вообще и скачивание не удалось -> возвращаем ошибку.
+    /// 4. Если шаблонов нет вообще и скачивание не удалось -> возвращаем ошибку
+    ///    (`FlattenError::CacheCorrupt`, если кэш-файл не удалось разобрать).
     pub async fn update_if_needed(&mut self) -> Result<()> {
         if !self.needs_update() {
             return Ok(());
@@ -163,7 +437,12 @@ impl TemplateManager {
                 // Успех, ничего не пишем в консоль, чтобы не спамить
             },
             Err(e) => {
-                if self.templates.is_empty() {
+                if self.templates.is_empty() && self.cache_corrupt {
+                    return Err(FlattenError::CacheCorrupt {
+                        path: self.templates_path.clone(),
+                    }
+                    .into());
+                } else if self.templates.is_empty() {
                     // Критическая ошибка: нет ни кэша, ни сети
                     return Err(e.context("Failed to fetch initial templates and cache is empty"));
                 } else {
@@ -175,41 +454,74 @@ impl TemplateManager {
         Ok(())
     }
     
-    /// Принудительно обновляет шаблоны из API.
-    pub async fn force_update(&mut self) -> Result<()> {
-        println!("🔄 Force updating exclusion templates from API...");
-        match self.fetch_templates().await {
-            Ok(_) => {
-                println!("✅ Templates updated successfully");
-                Ok(())
-            }
-            Err(e) => {
-                // При явном обновлении ошибку нужно показать
-                Err(e)
-            }
+    /// Обновляет устаревший полный список шаблонов при запуске.
+    ///
+    /// Пустой или частичный кэш целиком не загружается: отдельные шаблоны
+    /// догружаются в `fetch_template`, а полный список — в `update_if_needed`,
+    /// когда он нужен.
+    pub async fn refresh_if_stale(&mut self) -> Result<()> {
+        if self.templates.is_empty() || self.config.partial_cache {
+            return Ok(());
         }
+        self.update_if_needed().await
     }
 
-    /// Получает шаблоны из API toptal.com.
+    /// Догружает один шаблон по ключу и сохраняет его в кэш.
     ///
-    /// Использует endpoint `list?format=json`, который возвращает полный список
-    /// шаблонов с их содержимым, что позволяет избежать N+1 запросов.
-    async fn fetch_templates(&mut self) -> Result<()> {
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(15))
-            .build()?;
-
-        let response = client.get(API_LIST_URL)
-            .send()
+    /// Возвращает `false`, если шаблона с таким ключом нет. Шаблон, уже
+    /// лежащий в кэше, повторно не загружается.
+    ///
+    /// # Ошибки
+    /// Если шаблон не удалось загрузить или сохранить.
+    pub async fn fetch_template(&mut self, key: &str) -> Result<bool> {
+        if self.templates.contains_key(key) {
+            return Ok(true);
+        }
+        let contents = self
+            .fetcher
+            .fetch_template(key)
             .await
-            .context("Failed to connect to templates API")?;
+            .with_context(|| format!("Failed to fetch template '{}'", key))?;
+        let Some(contents) = contents else {
+            return Ok(false);
+        };
+        let Some((contents, lint)) = checked_template(key, &contents) else {
+            return Ok(false);
+        };
+        if contents.len() > MAX_TEMPLATE_SIZE {
+            anyhow::bail!(
+                "Template '{}' is {} bytes, more than the {} byte limit",
+                key,
+                contents.len(),
+                MAX_TEMPLATE_SIZE
+            );
+        }
+        let template = Template {
+            key: key.to_string(),
+            name: key.to_string(),
+            contents,
+            lint,
+        };
+        // К полному списку новый шаблон дописывается без пометки.
+        self.config.partial_cache |= self.templates.is_empty();
+        self.templates.insert(key.to_string(), template);
+        self.cache_corrupt = false;
+        self.save_templates()?;
+        self.save_config()?;
+        Ok(true)
+    }
 
-        // Парсим ответ как HashMap, где ключ - ID шаблона, значение - структура с содержимым
-        // Это решает проблему неправильного парсинга строк
-        let api_data: HashMap<String, ToptalEntry> = response
-            .json()
-            .await
-            .context("Failed to parse templates JSON")?;
+    /// Принудительно обновляет шаблоны из API.
+    pub async fn force_update(&mut self) -> Result<()> {
+        // При явном обновлении ошибку нужно показать
+        self.fetch_templates().await
+    }
+
+    /// Получает шаблоны через `TemplateFetcher` и сохраняет их в кэш.
+    ///
+    /// Пустой ответ считается ошибкой, чтобы не затереть рабочий кэш.
+    async fn fetch_templates(&mut self) -> Result<()> {
+        let api_data = self.fetcher.fetch_list().await?;
 
         if api_data.is_empty() {
             return Err(anyhow::anyhow!("Received empty templates list from API"));
@@ -218,17 +530,19 @@ impl TemplateManager {
         // Конвертируем во внутренний формат
         self.templates = api_data
             .into_iter()
-            .map(|(key, entry)| {
-                (
-                    key.clone(),
-                    Template {
-                        key,
-                        name: entry.name,
-                        contents: entry.contents,
-                    },
-                )
+            .filter_map(|(key, entry)| {
+                let (contents, lint) = checked_template(&key, &entry.contents)?;
+                let template = Template {
+                    key: key.clone(),
+                    name: entry.name,
+                    contents,
+                    lint,
+                };
+                Some((key, template))
             })
             .collect();
+        self.cache_corrupt = false;
+        self.config.partial_cache = false;
 
         // Обновляем метку времени только при успехе
         self.config.last_updated = SystemTime::now()
@@ -246,8 +560,390 @@ impl TemplateManager {
         self.templates.keys().cloned().collect()
     }
 
+    /// Возвращает путь к файлу конфигурации менеджера.
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Возвращает путь к кэш-файлу шаблонов.
+    pub fn templates_path(&self) -> &Path {
+        &self.templates_path
+    }
+
+    /// Возвращает загруженную конфигурацию менеджера.
+    pub fn config(&self) -> &ManagerConfig {
+        &self.config
+    }
+
+    /// Кэш-файл шаблонов существует, но не разбирается.
+    pub fn is_cache_corrupt(&self) -> bool {
+        self.cache_corrupt
+    }
+
+    /// Кэш содержит только отдельно загруженные шаблоны, полный список еще не загружался.
+    pub fn is_partial(&self) -> bool {
+        self.config.partial_cache
+    }
+
+    /// Файл конфигурации существует, но не разбирается (используются значения по умолчанию).
+    pub fn is_config_corrupt(&self) -> bool {
+        self.config_corrupt
+    }
+
+    /// Сохраненные поврежденные файлы кэша и конфигурации, по имени.
+    pub fn corrupt_files(&self) -> Vec<PathBuf> {
+        let Some(directory) = self.config_path.parent() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.contains(CORRUPT_MARKER))
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Возвращает пользовательские синонимы ключей шаблонов.
+    pub fn template_aliases(&self) -> &BTreeMap<String, String> {
+        &self.config.template_aliases
+    }
+
+    /// Возвращает шаблон по его ключу.
+    pub fn get_template(&self, key: &str) -> Option<&Template> {
+        self.templates.get(key)
+    }
+
     /// Возвращает содержимое шаблона по его ключу.
     pub fn get_template_contents(&self, key: &str) -> Option<&str> {
         self.templates.get(key).map(|t| t.contents.as_str())
     }
 }
+
+/// Директория конфигурации и кэша по умолчанию (`~/.flatten/`).
+///
+/// # Ошибки
+/// Возвращает ошибку, если не удается определить домашнюю директорию.
+pub fn default_directory() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().context("Could not determine home directory")?;
+    Ok(home_dir.join(".flatten"))
+}
+
+/// Нормализует загруженный шаблон `key`; `None` с предупреждением в stderr,
+/// если шаблон не прошел проверку и не должен попасть в кэш.
+fn checked_template(key: &str, contents: &str) -> Option<(String, TemplateLint)> {
+    match template_lint::normalize(contents) {
+        Ok(checked) => Some(checked),
+        Err(rejection) => {
+            eprintln!("Warning: template '{}' is not cached: {}", key, rejection);
+            None
+        }
+    }
+}
+
+/// Проверяет ограничения кэша шаблонов, которые JSON сам по себе не гарантирует.
+fn validate_templates(templates: &HashMap<String, Template>) -> Result<()> {
+    for (key, template) in templates {
+        if key.trim().is_empty() || template.key.trim().is_empty() {
+            anyhow::bail!("template with an empty key");
+        }
+        if template.contents.len() > MAX_TEMPLATE_SIZE {
+            anyhow::bail!(
+                "template '{}' is {} bytes, more than the {} byte limit",
+                key,
+                template.contents.len(),
+                MAX_TEMPLATE_SIZE
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Сохраняет поврежденный файл `path` рядом как `<имя>.corrupt-<время>` и
+/// предупреждает об этом в stderr.
+///
+/// Если файл не удалось переименовать, он будет перезаписан при восстановлении.
+fn preserve_corrupt(path: &Path, error: &dyn fmt::Display) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut preserved = path.as_os_str().to_owned();
+    preserved.push(format!("{}{}", CORRUPT_MARKER, timestamp));
+    let preserved = PathBuf::from(preserved);
+    match std::fs::rename(path, &preserved) {
+        Ok(()) => eprintln!(
+            "Warning: {} is corrupt ({:#}); kept as {} and rebuilding",
+            path.display(),
+            error,
+            preserved.display()
+        ),
+        Err(rename_error) => eprintln!(
+            "Warning: {} is corrupt ({:#}) and could not be preserved ({}); rebuilding",
+            path.display(),
+            error,
+            rename_error
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Поддельный источник: отдает заранее заданный ответ и считает вызовы.
+    #[derive(Clone, Default)]
+    struct FakeFetcher {
+        entries: Option<HashMap<String, ToptalEntry>>,
+        calls: Arc<AtomicUsize>,
+        template_calls: Arc<AtomicUsize>,
+    }
+
+    impl FakeFetcher {
+        fn returning(keys: &[&str]) -> Self {
+            let entries = keys
+                .iter()
+                .map(|key| {
+                    let entry = ToptalEntry {
+                        name: key.to_string(),
+                        contents: format!("{}_build/", key),
+                    };
+                    (key.to_string(), entry)
+                })
+                .collect();
+            Self {
+                entries: Some(entries),
+                ..Self::default()
+            }
+        }
+
+        fn failing() -> Self {
+            Self::default()
+        }
+    }
+
+    impl TemplateFetcher for FakeFetcher {
+        fn fetch_list(&self) -> FetchFuture<'_> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let result = self
+                .entries
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("connection refused"));
+            Box::pin(async move { result })
+        }
+
+        fn fetch_template(&self, key: &str) -> TemplateFuture<'_> {
+            self.template_calls.fetch_add(1, Ordering::Relaxed);
+            let result = self
+                .entries
+                .as_ref()
+                .map(|entries| entries.get(key).map(|entry| entry.contents.clone()))
+                .ok_or_else(|| anyhow::anyhow!("connection refused"));
+            Box::pin(async move { result })
+        }
+    }
+
+    /// Создает кэш с шаблоном `rust` и просроченной меткой обновления.
+    async fn warm_cache(dir: &std::path::Path) -> Result<()> {
+        let mut manager = TemplateManager::with_fetcher(dir, FakeFetcher::returning(&["rust"]))?;
+        manager.force_update().await?;
+        manager.config.last_updated = 0;
+        manager.save_config()
+    }
+
+    #[tokio::test]
+    async fn test_empty_api_response_rejected() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::returning(&[]))?;
+        let error = manager.update_if_needed().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("empty templates list"));
+        assert!(manager.get_available_templates().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_error_with_warm_cache_falls_back() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        warm_cache(dir.path()).await?;
+
+        let fetcher = FakeFetcher::failing();
+        let mut manager = TemplateManager::with_fetcher(dir.path(), fetcher.clone())?;
+        manager.update_if_needed().await?;
+        assert_eq!(fetcher.calls.load(Ordering::Relaxed), 1);
+        assert_eq!(manager.get_template_contents("rust"), Some("rust_build/"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_error_with_cold_cache_fails() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::failing())?;
+        let error = manager.update_if_needed().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("cache is empty"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_template_fetch_on_cold_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let fetcher = FakeFetcher::returning(&["rust", "node"]);
+        let mut manager = TemplateManager::with_fetcher(dir.path(), fetcher.clone())?;
+        manager.refresh_if_stale().await?;
+        assert!(manager.fetch_template("rust").await?);
+        assert!(manager.fetch_template("rust").await?);
+        assert!(!manager.fetch_template("missing").await?);
+        assert_eq!(fetcher.calls.load(Ordering::Relaxed), 0);
+        assert_eq!(fetcher.template_calls.load(Ordering::Relaxed), 2);
+        assert!(manager.is_partial());
+        assert!(manager.needs_update());
+
+        // Частичный кэш переживает перезапуск и не обновляется при запуске.
+        let mut manager = TemplateManager::with_fetcher(dir.path(), fetcher.clone())?;
+        manager.refresh_if_stale().await?;
+        assert!(manager.is_partial());
+        assert_eq!(manager.get_available_templates(), vec!["rust".to_string()]);
+        assert_eq!(manager.get_template_contents("rust"), Some("rust_build/"));
+
+        // Полный список загружается, только когда он нужен.
+        manager.update_if_needed().await?;
+        assert_eq!(fetcher.calls.load(Ordering::Relaxed), 1);
+        assert!(!manager.is_partial());
+        assert_eq!(manager.get_available_templates().len(), 2);
+
+        let mut manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::failing())?;
+        assert!(!manager.is_partial());
+        let error = manager.fetch_template("go").await.unwrap_err();
+        assert!(format!("{:#}", error).contains("Failed to fetch template 'go'"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetched_templates_normalized_or_rejected() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let entry = |contents: &str| ToptalEntry {
+            name: "Template".to_string(),
+            contents: contents.to_string(),
+        };
+        let entries = HashMap::from([
+            ("csharp".to_string(), entry("\u{FEFF}bin\\Debug\\\r\n*.pdb\r\n")),
+            ("banner".to_string(), entry("# ===\r\n\r\n")),
+            ("binary".to_string(), entry("build/\n\u{FFFD}\u{FFFD}\n")),
+        ]);
+        let fetcher = FakeFetcher {
+            entries: Some(entries),
+            ..FakeFetcher::default()
+        };
+        let mut manager = TemplateManager::with_fetcher(dir.path(), fetcher.clone())?;
+        manager.force_update().await?;
+        assert_eq!(manager.get_available_templates(), vec!["csharp".to_string()]);
+        assert_eq!(manager.get_template_contents("csharp"), Some("bin/Debug/\n*.pdb"));
+
+        // Сведения о правках сохраняются в кэше вместе с шаблоном.
+        let manager = TemplateManager::with_fetcher(dir.path(), fetcher.clone())?;
+        let lint = manager.get_template("csharp").unwrap().lint;
+        assert_eq!(
+            lint.to_string(),
+            "byte order mark removed, 2 line endings normalized, 1 lines with backslash separators"
+        );
+
+        // Отдельно загруженный шаблон проверяется так же.
+        let dir = tempfile::tempdir()?;
+        let mut manager = TemplateManager::with_fetcher(dir.path(), fetcher)?;
+        assert!(!manager.fetch_template("binary").await?);
+        assert!(manager.get_available_templates().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_cache_without_network() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("templates_cache.json"), "{not json")?;
+        let mut manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::failing())?;
+        let error = manager.update_if_needed().await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<FlattenError>(),
+            Some(FlattenError::CacheCorrupt { path }) if path.ends_with("templates_cache.json")
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncated_cache_preserved_and_rebuilt() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        warm_cache(dir.path()).await?;
+        let cache = dir.path().join("templates_cache.json");
+        let contents = std::fs::read_to_string(&cache)?;
+        let truncated = &contents[..contents.len() / 2];
+        std::fs::write(&cache, truncated)?;
+
+        let fetcher = FakeFetcher::returning(&["rust", "node"]);
+        let mut manager = TemplateManager::with_fetcher(dir.path(), fetcher)?;
+        assert!(manager.is_cache_corrupt());
+        assert!(!cache.exists());
+        let preserved = manager.corrupt_files();
+        assert_eq!(preserved.len(), 1);
+        assert!(preserved[0].to_string_lossy().contains("templates_cache.json.corrupt-"));
+        assert_eq!(std::fs::read_to_string(&preserved[0])?, truncated);
+
+        // Поврежденный кэш загружается заново, а не используется пустым.
+        manager.update_if_needed().await?;
+        assert!(!manager.is_cache_corrupt());
+        assert_eq!(manager.get_available_templates().len(), 2);
+        let manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::failing())?;
+        assert!(!manager.is_cache_corrupt());
+        assert_eq!(manager.get_template_contents("node"), Some("node_build/"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_invariants_violation_is_corruption() -> Result<()> {
+        let oversized = "x".repeat(MAX_TEMPLATE_SIZE + 1);
+        let caches = [
+            serde_json::json!({"": {"key": "", "name": "Empty", "contents": "dist/"}}),
+            serde_json::json!({"big": {"key": "big", "name": "Big", "contents": oversized}}),
+        ];
+        for cache in caches {
+            let dir = tempfile::tempdir()?;
+            std::fs::write(dir.path().join("templates_cache.json"), cache.to_string())?;
+            let manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::failing())?;
+            assert!(manager.is_cache_corrupt());
+            assert!(manager.get_available_templates().is_empty());
+            assert_eq!(manager.corrupt_files().len(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_config_rebuilt_with_defaults() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = dir.path().join("manager_config.json");
+        std::fs::write(&config, "{\"last_updated\": 17, \"presets\": {")?;
+        let manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::failing())?;
+        assert!(manager.is_config_corrupt());
+        assert_eq!(manager.config().last_updated, 0);
+        let rebuilt: ManagerConfig = serde_json::from_str(&std::fs::read_to_string(&config)?)?;
+        assert_eq!(rebuilt.cache_duration, 86_400);
+        assert_eq!(manager.corrupt_files().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_force_update_surfaces_error() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        warm_cache(dir.path()).await?;
+
+        let mut manager = TemplateManager::with_fetcher(dir.path(), FakeFetcher::failing())?;
+        let error = manager.force_update().await.unwrap_err();
+        assert!(error.to_string().contains("connection refused"));
+        assert_eq!(manager.get_template_contents("rust"), Some("rust_build/"));
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! Модуль кэша обработанного содержимого (`--content-cache`).
+//!
+//! Кэш хранит результат обработки файла (конвертеры, `--skip-generated`,
+//! `--outline`, нормализация окончаний строк, фрагменты `--chunk-size`) под
+//! ключом из SHA-256 исходных байт и отпечатка параметров обработки. Время
+//! изменения файлов в ключ не входит, поэтому свежий клон неизмененного
+//! репозитория обрабатывается из кэша, хотя файлы все равно читаются для
+//! вычисления хеша.
+//!
+//! Каждая запись — отдельный JSON-файл в директории кэша. Время изменения
+//! записи обновляется при каждом попадании, и в конце запуска самые давно
+//! использованные записи удаляются, пока кэш больше `--content-cache-max-size`.
+//! Кэш не влияет на вывод: запись, которую не удалось прочитать, считается
+//! промахом, а ошибка записи в кэш не прерывает запуск.
+
+use crate::chunking::Chunk;
+use crate::normalize::LineEndings;
+use crate::pipeline::FileContent;
+use crate::report::{CacheStats, SkipReason};
+use crate::sanitize::SanitizeCounts;
+use crate::transform::TransformMetric;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+/// Расширение файлов записей кэша.
+const ENTRY_EXTENSION: &str = "json";
+
+/// Обработанное содержимое одного файла в кэше.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedContent {
+    /// Текст, попадающий в выходной документ.
+    pub content: String,
+    /// Имя конвертера, если содержимое было преобразовано.
+    pub converter: Option<String>,
+    /// Причина, по которой содержимое заменено заглушкой.
+    pub skip_reason: Option<SkipReason>,
+    /// Исходный стиль окончаний строк.
+    pub line_endings: Option<LineEndings>,
+    /// Фрагменты содержимого при `--chunk-size`.
+    pub chunks: Vec<Chunk>,
+    /// Что заменено в содержимом при `--sanitize`.
+    #[serde(default)]
+    pub sanitized: Option<SanitizeCounts>,
+    /// Изменения содержимого преобразованиями: имя, размер до и после.
+    #[serde(default)]
+    pub transforms: Vec<(String, u64, u64)>,
+}
+
+impl CachedContent {
+    /// Запоминает результат обработки файла.
+    pub fn from_content(content: &FileContent) -> Self {
+        Self {
+            content: content.content.to_string(),
+            converter: content.converter.map(str::to_string),
+            skip_reason: content.skip_reason,
+            line_endings: content.line_endings,
+            chunks: content.chunks.clone(),
+            sanitized: content.sanitized,
+            transforms: content
+                .transforms
+                .iter()
+                .map(|metric| (metric.transform.to_string(), metric.before, metric.after))
+                .collect(),
+        }
+    }
+
+    /// Восстанавливает результат обработки файла размером `size`.
+    ///
+    /// `converter` и `transform` сопоставляют сохраненные имена конвертера и
+    /// преобразований с текущими; `None`, если какого-то из них больше нет.
+    pub fn into_content(
+        self,
+        size: u64,
+        converter: impl Fn(&str) -> Option<&'static str>,
+        transform: impl Fn(&str) -> Option<&'static str>,
+    ) -> Option<FileContent> {
+        let converter = match &self.converter {
+            Some(name) => Some(converter(name)?),
+            None => None,
+        };
+        let transforms = self
+            .transforms
+            .iter()
+            .map(|(name, before, after)| {
+                Some(TransformMetric {
+                    transform: transform(name)?,
+                    before: *before,
+                    after: *after,
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(FileContent {
+            content: self.content.into(),
+            original_size: size,
+            read_bytes: size,
+            converter,
+            skip_reason: self.skip_reason,
+            line_endings: self.line_endings,
+            chunks: self.chunks,
+            lfs: None,
+            stale: false,
+            sanitized: self.sanitized,
+            transforms,
+            mode: None,
+        })
+    }
+}
+
+/// Директория кэша с ограничением размера и счетчиками попаданий.
+#[derive(Debug)]
+pub struct ContentCache {
+    directory: PathBuf,
+    max_size: u64,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ContentCache {
+    /// Открывает кэш в `directory`, создавая директорию при необходимости.
+    /// `max_size` — наибольший суммарный размер записей в байтах (0 — без ограничения).
+    ///
+    /// # Ошибки
+    /// Если директорию не удалось создать.
+    pub fn open(directory: &Path, max_size: u64) -> Result<Self> {
+        fs::create_dir_all(directory).with_context(|| {
+            format!("Failed to create content cache directory: {}", directory.display())
+        })?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            max_size,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    /// Ключ записи: SHA-256 исходных байт файла и отпечатка параметров обработки.
+    pub fn key(bytes: &[u8], fingerprint: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(Sha256::digest(bytes));
+        hasher.update(fingerprint.as_bytes());
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Возвращает запись по ключу и отмечает ее как недавно использованную.
+    ///
+    /// Отсутствующая или поврежденная запись считается промахом.
+    pub fn get(&self, key: &str) -> Option<CachedContent> {
+        let path = self.entry_path(key);
+        let cached = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        match cached {
+            Some(_) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                if let Ok(file) = File::options().write(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        cached
+    }
+
+    /// Сохраняет запись; ошибки записи не прерывают запуск, запись просто не появится.
+    pub fn put(&self, key: &str, content: &CachedContent) {
+        let write = || -> Result<()> {
+            let mut file = tempfile::NamedTempFile::new_in(&self.directory)?;
+            serde_json::to_writer(&mut file, content)?;
+            file.flush()?;
+            file.persist(self.entry_path(key))?;
+            Ok(())
+        };
+        let _ = write();
+    }
+
+    /// Попадания и промахи с момента открытия кэша.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Удаляет самые давно использованные записи, пока кэш больше `max_size`.
+    ///
+    /// Возвращает количество удаленных записей.
+    ///
+    /// # Ошибки
+    /// Если директорию кэша не удалось прочитать или запись не удалось удалить.
+    pub fn evict(&self) -> Result<usize> {
+        if self.max_size == 0 {
+            return Ok(0);
+        }
+        let read_error =
+            || format!("Failed to read content cache directory: {}", self.directory.display());
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.directory).with_context(read_error)? {
+            let entry = entry.with_context(read_error)?;
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != ENTRY_EXTENSION) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((used, metadata.len(), path));
+        }
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        let mut removed = 0;
+        for (_, len, path) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            fs::remove_file(&path).with_context(|| {
+                format!("Failed to remove content cache entry: {}", path.display())
+            })?;
+            total -= len;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Путь файла записи.
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.{}", key, ENTRY_EXTENSION))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn entry(content: &str) -> CachedContent {
+        CachedContent {
+            content: content.to_string(),
+            converter: None,
+            skip_reason: None,
+            line_endings: None,
+            chunks: Vec::new(),
+            sanitized: None,
+            transforms: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let unbounded = ContentCache::open(temp_dir.path(), 0)?;
+        let keys: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|bytes| ContentCache::key(bytes.as_bytes(), "options"))
+            .collect();
+        assert_ne!(keys[0], ContentCache::key(b"a", "other options"));
+        for (index, key) in keys.iter().enumerate() {
+            unbounded.put(key, &entry(&"x".repeat(100)));
+            // Записи различаются временем использования.
+            let used = SystemTime::UNIX_EPOCH + Duration::from_secs(1000 * (index as u64 + 1));
+            File::options()
+                .write(true)
+                .open(unbounded.entry_path(key))?
+                .set_modified(used)?;
+        }
+        assert_eq!(unbounded.evict()?, 0);
+
+        let entry_size = fs::metadata(unbounded.entry_path(&keys[0]))?.len();
+        let cache = ContentCache::open(temp_dir.path(), entry_size * 2)?;
+        // Попадание делает самую старую запись самой новой.
+        assert_eq!(cache.get(&keys[0]).map(|cached| cached.content.len()), Some(100));
+        assert!(cache.get("missing").is_none());
+        assert_eq!(cache.evict()?, 1);
+        assert!(cache.get(&keys[0]).is_some());
+        assert!(cache.get(&keys[1]).is_none());
+        assert!(cache.get(&keys[2]).is_some());
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 2 });
+        Ok(())
+    }
+}
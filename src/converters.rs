@@ -0,0 +1,531 @@
+//! Модуль конвертеров содержимого.
+//!
+//! Конвертер получает путь и "сырые" байты файла определенного формата до
+//! декодирования в UTF-8 и возвращает `Conversion`: текст для документа,
+//! заглушку или отказ, после которого файл обрабатывается как обычно.
+//! Конвертеры регистрируются по расширению файла в `ConverterRegistry`
+//! (из кода — через `FlattenConfig::register_converter`) и вызываются из
+//! `process_files_parallel`. Паника конвертера становится ошибкой чтения
+//! этого файла.
+//!
+//! Встроенные конвертеры:
+//!
+//! - `NotebookConverter`: Jupyter-ноутбуки (`.ipynb`) -> ячейки кода и markdown.
+//! - `CsvPreviewConverter`: CSV/TSV-файлы -> заголовок и первые N строк.
+//! - `assets::ImageConverter`: изображения -> заглушка с форматом и размерами.
+//!
+//! # Пример
+//!
+//! Скомпилированные дескрипторы `.proto` выводятся списком имен, а исходные
+//! тексты `.proto` обрабатываются как обычные файлы:
+//!
+//! ```no_run
+//! use clap::Parser;
+//! use flatten_rust::converters::{Conversion, ConverterSource, FileConverter};
+//! use flatten_rust::pipeline::{ReadOptions, read_file};
+//! use flatten_rust::{Args, FlattenConfig};
+//! use std::path::Path;
+//! use std::sync::Arc;
+//!
+//! #[derive(Debug)]
+//! struct DescriptorListing;
+//!
+//! impl FileConverter for DescriptorListing {
+//!     fn name(&self) -> &'static str {
+//!         "proto-descriptor"
+//!     }
+//!
+//!     fn convert(&self, _path: &Path, source: ConverterSource<'_>) -> Conversion {
+//!         let Ok(bytes) = source.into_bytes() else {
+//!             return Conversion::Placeholder("[Unreadable descriptor]".to_string());
+//!         };
+//!         if std::str::from_utf8(&bytes).is_ok() {
+//!             return Conversion::FallThrough;
+//!         }
+//!         // Имена сообщений и полей хранятся в дескрипторе строками.
+//!         let names: Vec<_> = bytes
+//!             .split(|byte| !byte.is_ascii_graphic())
+//!             .filter(|run| run.len() > 2)
+//!             .map(String::from_utf8_lossy)
+//!             .collect();
+//!         Conversion::Text(format!("[Descriptor: {} names]\n{}", names.len(), names.join("\n")))
+//!     }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let mut config = FlattenConfig::new(&Args::parse_from(["flatten-rust", "-f", "."])).await?;
+//! config.register_converter("proto", Arc::new(DescriptorListing));
+//! let content = read_file(Path::new("api.proto"), &ReadOptions::from_config(&config))?;
+//! println!("{}", &*content.content);
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Источник данных, передаваемый конвертеру.
+pub enum ConverterSource<'a> {
+    /// Полное содержимое файла (обычно отображенное в память).
+    Bytes(&'a [u8]),
+    /// Открытый файл для потокового чтения и его полный размер в байтах.
+    Reader { reader: &'a mut dyn Read, len: u64 },
+}
+
+impl<'a> ConverterSource<'a> {
+    /// Возвращает полное содержимое источника, дочитывая поток при необходимости.
+    pub fn into_bytes(self) -> std::io::Result<Cow<'a, [u8]>> {
+        match self {
+            ConverterSource::Bytes(bytes) => Ok(Cow::Borrowed(bytes)),
+            ConverterSource::Reader { reader, .. } => {
+                let mut buffer = Vec::new();
+                reader.read_to_end(&mut buffer)?;
+                Ok(Cow::Owned(buffer))
+            }
+        }
+    }
+}
+
+/// Результат работы конвертера.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conversion {
+    /// Текстовое представление, которое попадает в документ вместо содержимого.
+    Text(String),
+    /// Заглушка вместо содержимого: файл учитывается как пропущенный.
+    Placeholder(String),
+    /// Конвертер не обрабатывает файл: он читается как обычный.
+    FallThrough,
+}
+
+/// Преобразователь содержимого файла в текст.
+///
+/// Реализации вызываются из потоков rayon, поэтому должны быть `Send + Sync`.
+pub trait FileConverter: Send + Sync + Debug {
+    /// Короткое имя конвертера, используемое в статистике (например, `"notebook"`).
+    fn name(&self) -> &'static str;
+
+    /// Читает ли конвертер файл потоково.
+    ///
+    /// Потоковые конвертеры получают `ConverterSource::Reader`, файл не
+    /// отображается в память, а ограничение `max_file_size` конвертер
+    /// соблюдает самостоятельно.
+    fn is_streaming(&self) -> bool {
+        false
+    }
+
+    /// Преобразует содержимое файла в текст для вывода.
+    ///
+    /// О файле, который не удалось разобрать, конвертер сообщает заглушкой
+    /// `Conversion::Placeholder`; паника становится ошибкой чтения файла.
+    fn convert(&self, path: &Path, source: ConverterSource<'_>) -> Conversion;
+}
+
+/// Реестр конвертеров, сопоставляющий расширения файлов с конвертерами.
+#[derive(Debug, Clone)]
+pub struct ConverterRegistry {
+    by_extension: HashMap<String, Arc<dyn FileConverter>>,
+}
+
+impl Default for ConverterRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl ConverterRegistry {
+    /// Создает пустой реестр без конвертеров.
+    pub fn empty() -> Self {
+        Self {
+            by_extension: HashMap::new(),
+        }
+    }
+
+    /// Создает реестр со встроенными конвертерами.
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        registry.register("ipynb", Arc::new(NotebookConverter));
+        registry
+    }
+
+    /// Регистрирует конвертер для расширения (без точки, без учета регистра).
+    pub fn register(&mut self, extension: &str, converter: Arc<dyn FileConverter>) {
+        self.by_extension
+            .insert(extension.to_ascii_lowercase(), converter);
+    }
+
+    /// Возвращает конвертер для файла, если он зарегистрирован для его расширения.
+    pub fn find(&self, path: &Path) -> Option<&Arc<dyn FileConverter>> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.by_extension.get(&extension)
+    }
+
+    /// Возвращает реестр, содержащий только конвертер для файла `path` (если он есть).
+    pub(crate) fn only_for(&self, path: &Path) -> Self {
+        let mut registry = Self::empty();
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str())
+            && let Some(converter) = self.find(path)
+        {
+            registry.register(extension, converter.clone());
+        }
+        registry
+    }
+}
+
+/// Минимальное представление ноутбука формата nbformat 4.
+#[derive(Debug, Deserialize)]
+struct Notebook {
+    nbformat: u32,
+    #[serde(default)]
+    metadata: NotebookMetadata,
+    cells: Vec<NotebookCell>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct NotebookMetadata {
+    #[serde(default)]
+    kernelspec: Option<KernelSpec>,
+    #[serde(default)]
+    language_info: Option<LanguageInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KernelSpec {
+    #[serde(default)]
+    language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LanguageInfo {
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NotebookCell {
+    cell_type: String,
+    #[serde(default)]
+    source: CellSource,
+}
+
+/// Исходный текст ячейки: строка или массив строк.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CellSource {
+    Lines(Vec<String>),
+    Text(String),
+}
+
+impl Default for CellSource {
+    fn default() -> Self {
+        CellSource::Text(String::new())
+    }
+}
+
+impl CellSource {
+    fn into_text(self) -> String {
+        match self {
+            CellSource::Lines(lines) => lines.concat(),
+            CellSource::Text(text) => text,
+        }
+    }
+}
+
+/// Конвертер Jupyter-ноутбуков.
+///
+/// Ячейки кода выводятся в виде fenced-блоков, markdown-ячейки - как есть.
+/// Выводы ячеек (включая base64-изображения) отбрасываются.
+#[derive(Debug, Default)]
+pub struct NotebookConverter;
+
+impl FileConverter for NotebookConverter {
+    fn name(&self) -> &'static str {
+        "notebook"
+    }
+
+    fn convert(&self, _path: &Path, source: ConverterSource<'_>) -> Conversion {
+        let unparseable = |reason: &dyn std::fmt::Display| {
+            Conversion::Placeholder(format!("[Unparseable notebook: {}]", reason))
+        };
+        let bytes = match source.into_bytes() {
+            Ok(bytes) => bytes,
+            Err(e) => return unparseable(&e),
+        };
+        let notebook: Notebook = match serde_json::from_slice(&bytes) {
+            Ok(notebook) => notebook,
+            Err(e) => return unparseable(&e),
+        };
+        if notebook.nbformat < 4 {
+            return unparseable(&format!("unsupported nbformat {}", notebook.nbformat));
+        }
+
+        let language = notebook
+            .metadata
+            .kernelspec
+            .and_then(|k| k.language)
+            .or_else(|| notebook.metadata.language_info.and_then(|l| l.name))
+            .unwrap_or_else(|| "python".to_string());
+
+        let mut code_cells = 0;
+        let mut markdown_cells = 0;
+        let mut body = String::new();
+
+        for cell in notebook.cells {
+            let source = cell.source.into_text();
+            match cell.cell_type.as_str() {
+                "code" => {
+                    code_cells += 1;
+                    let fence = fence_for(&source);
+                    body.push_str(&format!("{}{}\n", fence, language));
+                    body.push_str(source.trim_end_matches('\n'));
+                    body.push_str(&format!("\n{}\n\n", fence));
+                }
+                "markdown" => {
+                    markdown_cells += 1;
+                    body.push_str(source.trim_end_matches('\n'));
+                    body.push_str("\n\n");
+                }
+                _ => {}
+            }
+        }
+
+        Conversion::Text(format!(
+            "[Notebook: {} code {}, {} markdown {}]\n\n{}",
+            code_cells,
+            plural_cell(code_cells),
+            markdown_cells,
+            plural_cell(markdown_cells),
+            body.trim_end_matches('\n')
+        ))
+    }
+}
+
+/// Конвертер CSV/TSV-файлов, оставляющий заголовок и первые строки данных.
+///
+/// Файл читается потоково настоящим CSV-парсером, поэтому поля в кавычках,
+/// содержащие переводы строк, обрабатываются корректно. Оставшиеся строки
+/// только подсчитываются; подсчет прекращается после `byte_limit` байт.
+#[derive(Debug)]
+pub struct CsvPreviewConverter {
+    delimiter: u8,
+    preview_rows: usize,
+    byte_limit: u64,
+}
+
+impl CsvPreviewConverter {
+    /// Создает конвертер для CSV-файлов (разделитель `,`).
+    ///
+    /// `byte_limit` ограничивает объем данных, читаемых при подсчете строк
+    /// (0 = без ограничений).
+    pub fn csv(preview_rows: usize, byte_limit: u64) -> Self {
+        Self {
+            delimiter: b',',
+            preview_rows,
+            byte_limit,
+        }
+    }
+
+    /// Создает конвертер для TSV-файлов (разделитель `\t`).
+    pub fn tsv(preview_rows: usize, byte_limit: u64) -> Self {
+        Self {
+            delimiter: b'\t',
+            ..Self::csv(preview_rows, byte_limit)
+        }
+    }
+
+    fn language(&self) -> &'static str {
+        if self.delimiter == b'\t' {
+            "tsv"
+        } else {
+            "csv"
+        }
+    }
+}
+
+impl FileConverter for CsvPreviewConverter {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn is_streaming(&self) -> bool {
+        true
+    }
+
+    fn convert(&self, _path: &Path, source: ConverterSource<'_>) -> Conversion {
+        let unparseable = |e: csv::Error| Conversion::Placeholder(format!("[Unparseable CSV: {}]", e));
+        let (reader, len): (Box<dyn Read + '_>, u64) = match source {
+            ConverterSource::Bytes(bytes) => (Box::new(bytes), bytes.len() as u64),
+            ConverterSource::Reader { reader, len } => (Box::new(reader), len),
+        };
+
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut csv_writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(Vec::new());
+
+        let mut record = csv::ByteRecord::new();
+        let mut emitted = 0;
+        // Заголовок + строки предпросмотра.
+        while emitted <= self.preview_rows {
+            match csv_reader.read_byte_record(&mut record) {
+                Ok(true) => {
+                    if let Err(e) = csv_writer.write_byte_record(&record) {
+                        return unparseable(e);
+                    }
+                    emitted += 1;
+                }
+                Ok(false) => break,
+                Err(e) => return unparseable(e),
+            }
+        }
+        let preview_end = csv_reader.position().byte();
+
+        let mut remaining = 0u64;
+        let mut complete = true;
+        loop {
+            if self.byte_limit > 0 && csv_reader.position().byte() >= self.byte_limit {
+                complete = false;
+                break;
+            }
+            match csv_reader.read_byte_record(&mut record) {
+                Ok(true) => remaining += 1,
+                Ok(false) => break,
+                Err(_) => {
+                    complete = false;
+                    break;
+                }
+            }
+        }
+
+        let preview = match csv_writer.into_inner() {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) => return unparseable(e.into_error().into()),
+        };
+        let fence = fence_for(&preview);
+        let mut text = format!("{}{}\n{}{}", fence, self.language(), preview, fence);
+        if remaining > 0 || !complete {
+            text.push_str(&format!(
+                "\n… {}{} more rows ({}) omitted",
+                crate::report::format_count(remaining as usize),
+                if complete { "" } else { "+" },
+                crate::report::format_size(len.saturating_sub(preview_end))
+            ));
+        }
+        Conversion::Text(text)
+    }
+}
+
+/// Подбирает ограничитель блока кода, который не встречается внутри `source`.
+pub(crate) fn fence_for(source: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+    for ch in source.chars() {
+        if ch == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn plural_cell(count: usize) -> &'static str {
+    if count == 1 { "cell" } else { "cells" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(conversion: Conversion) -> String {
+        match conversion {
+            Conversion::Text(text) | Conversion::Placeholder(text) => text,
+            Conversion::FallThrough => panic!("converter fell through"),
+        }
+    }
+
+    const NOTEBOOK: &str = r##"{
+        "nbformat": 4,
+        "nbformat_minor": 5,
+        "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+        "cells": [
+            {"cell_type": "markdown", "source": ["# Title\n", "Intro"]},
+            {"cell_type": "code", "source": "import os\nprint(os.getcwd())",
+             "outputs": [{"output_type": "display_data", "data": {"image/png": "iVBORw0KGgo="}}]},
+            {"cell_type": "raw", "source": "ignored"}
+        ]
+    }"##;
+
+    #[test]
+    fn test_notebook_extracts_cells_and_drops_outputs() {
+        let text = text(NotebookConverter.convert(
+            Path::new("a.ipynb"),
+            ConverterSource::Bytes(NOTEBOOK.as_bytes()),
+        ));
+        assert!(text.starts_with("[Notebook: 1 code cell, 1 markdown cell]"));
+        assert!(text.contains("# Title\nIntro"));
+        assert!(text.contains("```python\nimport os\nprint(os.getcwd())\n```"));
+        assert!(!text.contains("iVBORw0KGgo"));
+        assert!(!text.contains("ignored"));
+    }
+
+    #[test]
+    fn test_notebook_fallback_for_corrupt_and_v3() {
+        let corrupt =
+            NotebookConverter.convert(Path::new("a.ipynb"), ConverterSource::Bytes(b"{not json"));
+        assert!(
+            matches!(&corrupt, Conversion::Placeholder(text) if text.starts_with("[Unparseable notebook:"))
+        );
+
+        let v3 = r#"{"nbformat": 3, "worksheets": [{"cells": []}], "cells": []}"#;
+        let conversion =
+            NotebookConverter.convert(Path::new("a.ipynb"), ConverterSource::Bytes(v3.as_bytes()));
+        assert_eq!(
+            conversion,
+            Conversion::Placeholder("[Unparseable notebook: unsupported nbformat 3]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_csv_preview_truncates_rows() {
+        let data = "id,note\n1,\"multi\nline\"\n2,b\n3,c\n4,d\n";
+        let text = text(CsvPreviewConverter::csv(2, 0).convert(
+            Path::new("data.csv"),
+            ConverterSource::Reader {
+                reader: &mut data.as_bytes(),
+                len: data.len() as u64,
+            },
+        ));
+        assert!(text.starts_with("```csv\nid,note\n1,\"multi\nline\"\n2,b\n```"));
+        assert!(text.ends_with("… 2 more rows (8 bytes) omitted"));
+    }
+
+    #[test]
+    fn test_csv_preview_stops_counting_at_byte_limit() {
+        let data = "a\tb\n1\t2\n3\t4\n5\t6\n7\t8\n";
+        let text = text(CsvPreviewConverter::tsv(1, 10).convert(
+            Path::new("data.tsv"),
+            ConverterSource::Bytes(data.as_bytes()),
+        ));
+        assert!(text.starts_with("```tsv\na\tb\n1\t2\n```"));
+        assert!(text.contains("more rows"));
+        assert!(text.contains("+ more rows"));
+    }
+
+    #[test]
+    fn test_registry_lookup_is_case_insensitive() {
+        let registry = ConverterRegistry::builtin();
+        assert!(registry.find(Path::new("Analysis.IPYNB")).is_some());
+        assert!(registry.find(Path::new("main.rs")).is_none());
+    }
+}
@@ -0,0 +1,469 @@
+//! Модуль сводки зависимостей из манифестов (`--deps-summary`).
+//!
+//! При обходе базовой папки распознаются манифесты `Cargo.toml`,
+//! `package.json`, `pyproject.toml`, `requirements.txt` и `go.mod`. Из каждого
+//! извлекаются объявленные зависимости с версиями: TOML и JSON разбираются
+//! полноценными парсерами, `requirements.txt` и `go.mod` — построчно по их
+//! формату. Манифест, который не удалось прочитать или разобрать, попадает в
+//! сводку с примечанием вместо зависимостей и не прерывает обработку.
+
+use crate::toml_value;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Тип манифеста.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestKind {
+    /// `Cargo.toml`.
+    Cargo,
+    /// `package.json`.
+    Npm,
+    /// `pyproject.toml`.
+    Pyproject,
+    /// `requirements.txt`.
+    Requirements,
+    /// `go.mod`.
+    GoMod,
+}
+
+/// Объявленная зависимость.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Dependency {
+    /// Имя пакета.
+    pub name: String,
+    /// Требование к версии или источник (`path`, `git`, `workspace`); пусто, если не указано.
+    pub version: String,
+}
+
+/// Раздел зависимостей манифеста (например, `dependencies` или `dev-dependencies`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencySection {
+    /// Имя раздела.
+    pub name: String,
+    /// Зависимости раздела в порядке имен.
+    pub dependencies: Vec<Dependency>,
+}
+
+/// Зависимости одного манифеста.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestDependencies {
+    /// Путь к манифесту.
+    pub path: PathBuf,
+    /// Тип манифеста.
+    pub kind: ManifestKind,
+    /// Имя пакета или модуля, если оно указано.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Разделы зависимостей.
+    pub sections: Vec<DependencySection>,
+    /// Почему зависимости не удалось извлечь.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Определяет тип манифеста по имени файла.
+pub fn manifest_kind(path: &Path) -> Option<ManifestKind> {
+    match path.file_name()?.to_str()? {
+        "Cargo.toml" => Some(ManifestKind::Cargo),
+        "package.json" => Some(ManifestKind::Npm),
+        "pyproject.toml" => Some(ManifestKind::Pyproject),
+        "requirements.txt" => Some(ManifestKind::Requirements),
+        "go.mod" => Some(ManifestKind::GoMod),
+        _ => None,
+    }
+}
+
+/// Читает и разбирает манифест; ошибки превращаются в примечание.
+pub fn read_manifest(path: &Path, kind: ManifestKind) -> ManifestDependencies {
+    let parsed = std::fs::read_to_string(path)
+        .context("failed to read manifest")
+        .and_then(|content| parse_manifest(kind, &content));
+    let (package, sections, note) = match parsed {
+        Ok((package, sections)) => (package, sections, None),
+        Err(e) => (None, Vec::new(), Some(format!("{:#}", e))),
+    };
+    ManifestDependencies {
+        path: path.to_path_buf(),
+        kind,
+        package,
+        sections,
+        note,
+    }
+}
+
+/// Извлекает имя пакета и разделы зависимостей из содержимого манифеста.
+pub fn parse_manifest(
+    kind: ManifestKind,
+    content: &str,
+) -> Result<(Option<String>, Vec<DependencySection>)> {
+    match kind {
+        ManifestKind::Cargo => {
+            let value = toml_value::parse(content).context("invalid TOML")?;
+            Ok((
+                string_at(&value, &["package", "name"]),
+                cargo_sections(&value),
+            ))
+        }
+        ManifestKind::Npm => {
+            let value: Value = serde_json::from_str(content).context("invalid JSON")?;
+            let sections = [
+                "dependencies",
+                "devDependencies",
+                "peerDependencies",
+                "optionalDependencies",
+            ]
+            .iter()
+            .filter_map(|name| table_section(name, &value[*name]))
+            .collect();
+            Ok((string_at(&value, &["name"]), sections))
+        }
+        ManifestKind::Pyproject => {
+            let value = toml_value::parse(content).context("invalid TOML")?;
+            let package = string_at(&value, &["project", "name"])
+                .or_else(|| string_at(&value, &["tool", "poetry", "name"]));
+            Ok((package, pyproject_sections(&value)))
+        }
+        ManifestKind::Requirements => {
+            let dependencies: Vec<_> = content
+                .lines()
+                .map(|line| line.split(" #").next().unwrap_or(line).trim())
+                .filter(|line| !line.is_empty() && !line.starts_with(['#', '-']))
+                .map(requirement)
+                .collect();
+            Ok((
+                None,
+                named_section("requirements", dependencies)
+                    .into_iter()
+                    .collect(),
+            ))
+        }
+        ManifestKind::GoMod => Ok(go_mod(content)),
+    }
+}
+
+/// Извлекает имя и версию пакета из содержимого манифеста.
+///
+/// `None`, если манифест не разобран или не называет пакет (`requirements.txt`).
+pub fn parse_package(kind: ManifestKind, content: &str) -> Option<(String, Option<String>)> {
+    let (value, table): (Value, &[&str]) = match kind {
+        ManifestKind::Cargo => (toml_value::parse(content).ok()?, &["package"]),
+        ManifestKind::Npm => (serde_json::from_str(content).ok()?, &[]),
+        ManifestKind::Pyproject => {
+            let value = toml_value::parse(content).ok()?;
+            let table: &[&str] = match value.get("project") {
+                Some(_) => &["project"],
+                None => &["tool", "poetry"],
+            };
+            (value, table)
+        }
+        ManifestKind::Requirements => return None,
+        ManifestKind::GoMod => return go_mod(content).0.map(|module| (module, None)),
+    };
+    let field = |key: &str| string_at(&value, &[table, &[key]].concat());
+    Some((field("name")?, field("version")))
+}
+
+/// Строковое значение по пути ключей.
+fn string_at(value: &Value, path: &[&str]) -> Option<String> {
+    path.iter()
+        .try_fold(value, |value, key| value.get(key))?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Разделы `Cargo.toml`: обычные, целевые (`target.<cfg>`) и общие для workspace.
+fn cargo_sections(value: &Value) -> Vec<DependencySection> {
+    const KINDS: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+    let mut sections: Vec<_> = KINDS
+        .iter()
+        .filter_map(|kind| table_section(kind, &value[*kind]))
+        .collect();
+    if let Some(targets) = value["target"].as_object() {
+        for (target, table) in targets {
+            sections.extend(KINDS.iter().filter_map(|kind| {
+                table_section(&format!("target.{}.{}", target, kind), &table[*kind])
+            }));
+        }
+    }
+    sections.extend(table_section(
+        "workspace.dependencies",
+        &value["workspace"]["dependencies"],
+    ));
+    sections
+}
+
+/// Разделы `pyproject.toml`: PEP 621 (`project`) и Poetry.
+fn pyproject_sections(value: &Value) -> Vec<DependencySection> {
+    let mut sections = Vec::new();
+    if let Some(items) = value["project"]["dependencies"].as_array() {
+        sections.extend(named_section(
+            "dependencies",
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(requirement)
+                .collect(),
+        ));
+    }
+    if let Some(extras) = value["project"]["optional-dependencies"].as_object() {
+        for (extra, items) in extras {
+            let dependencies = items
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(requirement)
+                .collect();
+            sections.extend(named_section(
+                &format!("optional-dependencies.{}", extra),
+                dependencies,
+            ));
+        }
+    }
+    let poetry = &value["tool"]["poetry"];
+    sections.extend(table_section(
+        "tool.poetry.dependencies",
+        &poetry["dependencies"],
+    ));
+    if let Some(groups) = poetry["group"].as_object() {
+        for (group, table) in groups {
+            sections.extend(table_section(
+                &format!("tool.poetry.group.{}.dependencies", group),
+                &table["dependencies"],
+            ));
+        }
+    }
+    sections
+}
+
+/// Раздел из таблицы `имя = требование`; пустая или отсутствующая таблица не дает раздела.
+fn table_section(name: &str, table: &Value) -> Option<DependencySection> {
+    let table = table.as_object()?;
+    let dependencies = table
+        .iter()
+        .map(|(name, spec)| Dependency {
+            name: name.clone(),
+            version: requirement_of(spec),
+        })
+        .collect();
+    named_section(name, dependencies)
+}
+
+fn named_section(name: &str, mut dependencies: Vec<Dependency>) -> Option<DependencySection> {
+    if dependencies.is_empty() {
+        return None;
+    }
+    dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+    Some(DependencySection {
+        name: name.to_string(),
+        dependencies,
+    })
+}
+
+/// Требование к версии из значения таблицы: строка или таблица с `version`, `path`, `git`.
+fn requirement_of(spec: &Value) -> String {
+    if let Some(version) = spec.as_str() {
+        return version.to_string();
+    }
+    if let Some(version) = spec["version"].as_str() {
+        return version.to_string();
+    }
+    if spec["workspace"] == Value::Bool(true) {
+        return "workspace".to_string();
+    }
+    for source in ["path", "git", "url"] {
+        if let Some(location) = spec[source].as_str() {
+            return format!("{}: {}", source, location);
+        }
+    }
+    String::new()
+}
+
+/// Разбирает требование PEP 508 (`requests[socks]>=2.0; python_version < "3.8"`).
+fn requirement(line: &str) -> Dependency {
+    let line = line.trim();
+    let name_end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+        .unwrap_or(line.len());
+    let mut rest = line[name_end..].trim_start();
+    if rest.starts_with('[') {
+        rest = rest
+            .find(']')
+            .map_or("", |end| rest[end + 1..].trim_start());
+    }
+    Dependency {
+        name: line[..name_end].to_string(),
+        version: rest.to_string(),
+    }
+}
+
+/// Разбирает `go.mod`: имя модуля и директивы `require` (строчные и блоком).
+fn go_mod(content: &str) -> (Option<String>, Vec<DependencySection>) {
+    let mut module = None;
+    let mut direct = Vec::new();
+    let mut indirect = Vec::new();
+    let mut in_block = false;
+    for line in content.lines() {
+        let (code, comment) = match line.split_once("//") {
+            Some((code, comment)) => (code.trim(), comment.trim()),
+            None => (line.trim(), ""),
+        };
+        let requirement = if in_block {
+            if code == ")" {
+                in_block = false;
+                continue;
+            }
+            code
+        } else if let Some(rest) = code.strip_prefix("module ") {
+            module = Some(rest.trim().trim_matches('"').to_string());
+            continue;
+        } else if let Some(rest) = code.strip_prefix("require") {
+            let rest = rest.trim();
+            if rest == "(" {
+                in_block = true;
+                continue;
+            }
+            rest
+        } else {
+            continue;
+        };
+        let mut fields = requirement.split_whitespace();
+        let (Some(name), Some(version)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let dependency = Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+        };
+        if comment == "indirect" {
+            indirect.push(dependency);
+        } else {
+            direct.push(dependency);
+        }
+    }
+    let sections = named_section("require", direct)
+        .into_iter()
+        .chain(named_section("require (indirect)", indirect))
+        .collect();
+    (module, sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(sections: &[DependencySection]) -> Vec<(String, String, String)> {
+        sections
+            .iter()
+            .flat_map(|section| {
+                section.dependencies.iter().map(|dependency| {
+                    (
+                        section.name.clone(),
+                        dependency.name.clone(),
+                        dependency.version.clone(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn row(section: &str, name: &str, version: &str) -> (String, String, String) {
+        (section.to_string(), name.to_string(), version.to_string())
+    }
+
+    #[test]
+    fn test_cargo_and_pyproject() -> Result<()> {
+        let (package, sections) = parse_manifest(
+            ManifestKind::Cargo,
+            r#"
+[package]
+name = "app"
+[dependencies]
+serde = { version = "1", features = ["derive"] }
+core = { path = "../core" }
+anyhow.workspace = true
+[target.'cfg(windows)'.dependencies]
+winapi = "0.3"
+"#,
+        )?;
+        assert_eq!(package.as_deref(), Some("app"));
+        assert_eq!(
+            rows(&sections),
+            vec![
+                row("dependencies", "anyhow", "workspace"),
+                row("dependencies", "core", "path: ../core"),
+                row("dependencies", "serde", "1"),
+                row("target.cfg(windows).dependencies", "winapi", "0.3"),
+            ]
+        );
+
+        let (package, sections) = parse_manifest(
+            ManifestKind::Pyproject,
+            r#"
+[project]
+name = "ml"
+dependencies = ["numpy>=1.26", "requests[socks] ==2.31; python_version >= '3.9'"]
+[project.optional-dependencies]
+test = ["pytest"]
+"#,
+        )?;
+        assert_eq!(package.as_deref(), Some("ml"));
+        assert_eq!(
+            rows(&sections),
+            vec![
+                row("dependencies", "numpy", ">=1.26"),
+                row(
+                    "dependencies",
+                    "requests",
+                    "==2.31; python_version >= '3.9'"
+                ),
+                row("optional-dependencies.test", "pytest", ""),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_requirements_and_go_mod() -> Result<()> {
+        let (_, sections) = parse_manifest(
+            ManifestKind::Requirements,
+            "# pinned\n-r base.txt\nflask==3.0 # web\n\nDjango>=4\n",
+        )?;
+        assert_eq!(
+            rows(&sections),
+            vec![
+                row("requirements", "Django", ">=4"),
+                row("requirements", "flask", "==3.0"),
+            ]
+        );
+
+        let (module, sections) = parse_manifest(
+            ManifestKind::GoMod,
+            "module example.com/svc\n\ngo 1.22\n\nrequire github.com/a/b v1.2.0\n\
+             require (\n\tgolang.org/x/text v0.14.0 // indirect\n\tgithub.com/c/d v0.1.0\n)\n",
+        )?;
+        assert_eq!(module.as_deref(), Some("example.com/svc"));
+        assert_eq!(
+            rows(&sections),
+            vec![
+                row("require", "github.com/a/b", "v1.2.0"),
+                row("require", "github.com/c/d", "v0.1.0"),
+                row("require (indirect)", "golang.org/x/text", "v0.14.0"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_failure_becomes_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        let manifest = read_manifest(&path, ManifestKind::Npm);
+        assert!(manifest.sections.is_empty());
+        assert!(manifest.note.unwrap().starts_with("invalid JSON: "));
+    }
+}
@@ -0,0 +1,439 @@
+//! Модуль диагностики окружения (`--doctor`).
+//!
+//! Проверки используют те же пути загрузки, что и обычный запуск:
+//! `TemplateManager` читает конфигурацию и кэш из `~/.flatten/` (только для
+//! чтения: диагностика ничего не создает и не переименовывает), соединение с
+//! API проверяется тем же источником шаблонов, а источники настроек берутся
+//! из `Args::sources`. Каждая проверка завершается статусом pass, warn, fail
+//! или skip и при проблеме — подсказкой, как ее устранить.
+
+use crate::Args;
+use crate::config::TemplateManager;
+use crate::environment::ValueSource;
+use crate::flattenignore::FLATTENIGNORE_FILE;
+use crate::folder_overrides;
+use crate::output::OutputFormat;
+use anyhow::Result;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Итог одной проверки.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// Все в порядке.
+    Pass,
+    /// Работать можно, но стоит обратить внимание.
+    Warn,
+    /// Проблема, из-за которой запуск завершится ошибкой или будет неполным.
+    Fail,
+    /// Проверка не выполнялась.
+    Skip,
+}
+
+impl CheckStatus {
+    /// Возвращает короткое имя статуса для вывода в консоль.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "pass",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "fail",
+            CheckStatus::Skip => "skip",
+        }
+    }
+}
+
+/// Результат одной проверки.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// Что проверялось.
+    pub name: &'static str,
+    /// Итог проверки.
+    pub status: CheckStatus,
+    /// Подробности в одну строку.
+    pub detail: String,
+    /// Как устранить проблему.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remedy: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: String) -> Self {
+        Self {
+            name,
+            status,
+            detail,
+            remedy: None,
+        }
+    }
+
+    fn with_remedy(mut self, remedy: impl Into<String>) -> Self {
+        self.remedy = Some(remedy.into());
+        self
+    }
+}
+
+/// Источник настроек в порядке убывания приоритета.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigSource {
+    /// Вид источника: `cli`, `env`, `preset` или `file`.
+    pub kind: &'static str,
+    /// Имя переменной, набора или путь к файлу.
+    pub location: String,
+}
+
+/// Результаты всех проверок.
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    /// Версия утилиты.
+    pub version: &'static str,
+    /// Проверки в порядке выполнения.
+    pub checks: Vec<Check>,
+    /// Найденные источники настроек, от самого сильного к самому слабому.
+    pub config_sources: Vec<ConfigSource>,
+}
+
+impl DoctorReport {
+    /// Выполняет все проверки.
+    ///
+    /// Соединение с API не проверяется с `--offline` и без feature `network`.
+    pub async fn collect(args: &Args) -> Self {
+        let mut checks = Vec::new();
+        let manager = TemplateManager::open_read_only();
+        match &manager {
+            Ok(manager) => {
+                checks.push(check_cache_directory(manager));
+                checks.push(check_templates_cache(manager));
+                checks.push(check_manager_config(manager));
+                checks.push(check_corrupt_files(manager));
+            }
+            Err(error) => checks.push(
+                Check::new("cache directory", CheckStatus::Fail, format!("{:#}", error))
+                    .with_remedy("Make sure HOME is set and ~/.flatten can be created"),
+            ),
+        }
+        checks.push(check_template_api(args).await);
+        let config_sources = config_sources(args, manager.as_ref().ok());
+        let locations: Vec<String> = config_sources
+            .iter()
+            .map(|source| format!("{} {}", source.kind, source.location))
+            .collect();
+        checks.push(Check::new(
+            "config sources",
+            CheckStatus::Pass,
+            format!("{} > defaults", locations.join(" > ")),
+        ));
+        checks.push(check_threads(args));
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            checks,
+            config_sources,
+        }
+    }
+
+    /// Есть ли проваленные проверки.
+    pub fn has_failures(&self) -> bool {
+        self.failures() > 0
+    }
+
+    /// Число проваленных проверок.
+    pub fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .count()
+    }
+
+    /// Выводит результаты в консоль или в JSON (`--format json`).
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+        println!("flatten-rust {} doctor", self.version);
+        for check in &self.checks {
+            println!(
+                "  [{}] {}: {}",
+                check.status.as_str(),
+                check.name,
+                check.detail
+            );
+            if let Some(remedy) = &check.remedy {
+                println!("         -> {}", remedy);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_cache_directory(manager: &TemplateManager) -> Check {
+    let directory = manager
+        .config_path()
+        .parent()
+        .unwrap_or(manager.config_path());
+    if !directory.exists() {
+        return Check::new(
+            "cache directory",
+            CheckStatus::Warn,
+            format!("{} does not exist yet", directory.display()),
+        )
+        .with_remedy("It is created by the first regular run");
+    }
+    match tempfile::tempfile_in(directory) {
+        Ok(_) => Check::new(
+            "cache directory",
+            CheckStatus::Pass,
+            format!("{} (writable)", directory.display()),
+        ),
+        Err(error) => Check::new(
+            "cache directory",
+            CheckStatus::Fail,
+            format!("{} is not writable: {}", directory.display(), error),
+        )
+        .with_remedy(format!("Fix the permissions of {}", directory.display())),
+    }
+}
+
+fn check_templates_cache(manager: &TemplateManager) -> Check {
+    let path = manager.templates_path();
+    if manager.is_cache_corrupt() {
+        return Check::new(
+            "templates cache",
+            CheckStatus::Fail,
+            format!("{} is corrupt", path.display()),
+        )
+        .with_remedy("Run flatten-rust --force-update to set it aside and rebuild the cache");
+    }
+    if !path.exists() {
+        return Check::new(
+            "templates cache",
+            CheckStatus::Warn,
+            format!("{} does not exist yet", path.display()),
+        )
+        .with_remedy("Run flatten-rust --force-update to download templates");
+    }
+    let count = manager.get_available_templates().len();
+    if manager.is_partial() {
+        return Check::new(
+            "templates cache",
+            CheckStatus::Warn,
+            format!("{} individually fetched templates, full list not fetched yet", count),
+        )
+        .with_remedy("Run flatten-rust --list-templates or --force-update to download the rest");
+    }
+    let config = manager.config();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let detail = format!(
+        "{} templates, updated {} ago (refresh after {})",
+        count,
+        format_duration(now.saturating_sub(config.last_updated)),
+        format_duration(config.cache_duration)
+    );
+    if manager.needs_update() {
+        Check::new(
+            "templates cache",
+            CheckStatus::Warn,
+            format!("{}, expired", detail),
+        )
+        .with_remedy("The next online run refreshes it; or run --force-update")
+    } else {
+        Check::new("templates cache", CheckStatus::Pass, detail)
+    }
+}
+
+fn check_manager_config(manager: &TemplateManager) -> Check {
+    let path = manager.config_path();
+    if manager.is_config_corrupt() {
+        return Check::new(
+            "manager config",
+            CheckStatus::Fail,
+            format!("{} is corrupt, defaults are used", path.display()),
+        )
+        .with_remedy(
+            "The next regular run sets it aside and rebuilds it; restore presets and aliases \
+             from the preserved copy if needed",
+        );
+    }
+    if !path.exists() {
+        return Check::new(
+            "manager config",
+            CheckStatus::Pass,
+            format!("{} does not exist yet, defaults are used", path.display()),
+        );
+    }
+    let config = manager.config();
+    Check::new(
+        "manager config",
+        CheckStatus::Pass,
+        format!(
+            "{} ({} presets, {} template aliases)",
+            path.display(),
+            config.presets.len(),
+            config.template_aliases.len()
+        ),
+    )
+}
+
+fn check_corrupt_files(manager: &TemplateManager) -> Check {
+    let files = manager.corrupt_files();
+    if files.is_empty() {
+        return Check::new("corrupt files", CheckStatus::Pass, "none".to_string());
+    }
+    let paths: Vec<String> = files.iter().map(|path| path.display().to_string()).collect();
+    Check::new(
+        "corrupt files",
+        CheckStatus::Warn,
+        format!("{} preserved: {}", files.len(), paths.join(", ")),
+    )
+    .with_remedy("Inspect them, then run flatten-rust --cache-prune to remove old copies")
+}
+
+#[cfg(feature = "network")]
+async fn check_template_api(args: &Args) -> Check {
+    use crate::config::{TemplateFetcher, ToptalFetcher};
+
+    if args.offline {
+        return Check::new("template API", CheckStatus::Skip, "--offline".to_string());
+    }
+    let started = std::time::Instant::now();
+    match ToptalFetcher.fetch_list().await {
+        Ok(templates) => Check::new(
+            "template API",
+            CheckStatus::Pass,
+            format!(
+                "reachable, {} templates in {} ms",
+                templates.len(),
+                started.elapsed().as_millis()
+            ),
+        ),
+        Err(error) => Check::new("template API", CheckStatus::Fail, format!("{:#}", error))
+            .with_remedy("Check the network or proxy settings, or use --offline with a warm cache"),
+    }
+}
+
+#[cfg(not(feature = "network"))]
+async fn check_template_api(_args: &Args) -> Check {
+    Check::new(
+        "template API",
+        CheckStatus::Skip,
+        "built without the network feature".to_string(),
+    )
+}
+
+/// Перечисляет найденные источники настроек от самого сильного к самому слабому.
+fn config_sources(args: &Args, manager: Option<&TemplateManager>) -> Vec<ConfigSource> {
+    // Блок папки сильнее флагов, но только для своей папки.
+    let mut sources: Vec<ConfigSource> = args
+        .folders
+        .iter()
+        .filter_map(|folder| folder_overrides::find(&args.folder_overrides, folder))
+        .map(|(key, _)| ConfigSource {
+            kind: "folder",
+            location: format!("folders.\"{}\" in manager_config.json", key),
+        })
+        .collect();
+    sources.push(ConfigSource {
+        kind: "cli",
+        location: "command-line flags".to_string(),
+    });
+    sources.extend(
+        args.sources
+            .iter()
+            .filter(|value| value.source == ValueSource::Env)
+            .map(|value| ConfigSource {
+                kind: "env",
+                location: value.env.clone(),
+            }),
+    );
+    if let Some(preset) = &args.preset {
+        sources.push(ConfigSource {
+            kind: "preset",
+            location: preset.clone(),
+        });
+    }
+    if let Some(manager) = manager
+        && manager.config_path().exists()
+    {
+        sources.push(ConfigSource {
+            kind: "file",
+            location: manager.config_path().display().to_string(),
+        });
+    }
+    for folder in &args.folders {
+        let ignore_file = folder.join(FLATTENIGNORE_FILE);
+        if !args.no_flattenignore && ignore_file.is_file() {
+            sources.push(ConfigSource {
+                kind: "file",
+                location: ignore_file.display().to_string(),
+            });
+        }
+    }
+    sources
+}
+
+fn check_threads(args: &Args) -> Check {
+    let detail = if args.threads > 0 {
+        format!("{} worker threads (--threads)", args.threads)
+    } else {
+        format!("{} worker threads (auto)", rayon::current_num_threads())
+    };
+    Check::new("threads", CheckStatus::Pass, detail)
+}
+
+/// Форматирует длительность в секундах в виде `2d 3h`, `4h 5m` или `6m`.
+fn format_duration(seconds: u64) -> String {
+    let (days, hours, minutes) = (
+        seconds / 86_400,
+        seconds % 86_400 / 3600,
+        seconds % 3600 / 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(59), "0m");
+        assert_eq!(format_duration(3 * 3600 + 120), "3h 2m");
+        assert_eq!(format_duration(86_400 * 2 + 3600), "2d 1h");
+    }
+
+    #[test]
+    fn test_config_sources_order() {
+        let mut args = Args::parse_from(["flatten-rust", "--preset", "llm"]);
+        args.sources = vec![crate::environment::ResolvedValue {
+            name: "output".to_string(),
+            env: "FLATTEN_OUTPUT".to_string(),
+            values: vec!["env.md".to_string()],
+            source: ValueSource::Env,
+            preset: None,
+            folder: None,
+        }];
+        let sources = config_sources(&args, None);
+        let kinds: Vec<(&str, &str)> = sources
+            .iter()
+            .map(|source| (source.kind, source.location.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("cli", "command-line flags"),
+                ("env", "FLATTEN_OUTPUT"),
+                ("preset", "llm")
+            ]
+        );
+    }
+}
@@ -0,0 +1,613 @@
+//! Модуль настройки через переменные окружения.
+//!
+//! Любой флаг `--имя-флага` можно задать переменной `FLATTEN_ИМЯ_ФЛАГА`.
+//! Флаги командной строки сильнее переменных окружения, переменные — сильнее
+//! значений по умолчанию. Для каждого значения запоминается его источник,
+//! который показывает `--print-config`.
+//!
+//! `--preset` раскрывается после учета переменных окружения: флаги набора
+//! подставляются только для параметров, не заданных явно, и помечаются
+//! источником `preset`. Аргументы, разобранные в обход `parse_args`,
+//! раскрывает `apply_preset` при запуске из библиотеки.
+//!
+//! Списки в переменных разделяются запятыми; `\,` означает запятую внутри
+//! элемента, `\\` — обратную косую черту. Логические флаги принимают
+//! `1`/`true`/`yes`/`on` и `0`/`false`/`no`/`off`.
+
+use crate::Args;
+use crate::error::{EXIT_OK, EXIT_USAGE};
+use crate::folder_overrides;
+use crate::invocation;
+use crate::output::OutputFormat;
+use crate::presets::{self, PresetDefinition};
+use anyhow::{Context, Result};
+use clap::parser::ValueSource as ClapValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Write;
+
+/// Префикс имен переменных окружения.
+pub const ENV_PREFIX: &str = "FLATTEN_";
+
+/// Источник значения параметра.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSource {
+    /// Флаг командной строки.
+    Cli,
+    /// Переменная окружения.
+    Env,
+    /// Набор настроек `--preset`.
+    Preset,
+    /// Блок базовой папки в `manager_config.json`.
+    Folder,
+    /// Значение по умолчанию.
+    #[default]
+    Default,
+}
+
+impl ValueSource {
+    /// Возвращает короткое имя источника для вывода в консоль.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueSource::Cli => "cli",
+            ValueSource::Env => "env",
+            ValueSource::Preset => "preset",
+            ValueSource::Folder => "folder",
+            ValueSource::Default => "default",
+        }
+    }
+}
+
+/// Итоговое значение одного параметра и его источник.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResolvedValue {
+    /// Имя флага без `--`.
+    pub name: String,
+    /// Имя соответствующей переменной окружения.
+    pub env: String,
+    /// Значения в исходном виде (пусто, если параметр не задан).
+    pub values: Vec<String>,
+    /// Откуда взято значение.
+    pub source: ValueSource,
+    /// Имя набора, если значение подставлено `--preset`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Ключ блока папки, если значение действует только для нее.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+}
+
+/// Возвращает имя переменной окружения для флага `--long`.
+pub fn env_var_name(long: &str) -> String {
+    format!(
+        "{}{}",
+        ENV_PREFIX,
+        long.to_ascii_uppercase().replace('-', "_")
+    )
+}
+
+/// Разбивает значение переменной на элементы списка.
+pub fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ (',' | '\\')) => current.push(escaped),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => current.push('\\'),
+            },
+            ',' => items.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    items.push(current);
+    items
+        .into_iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Разбирает аргументы командной строки, дополняя их переменными окружения.
+///
+/// `env` возвращает значение переменной по имени; в тестах вместо окружения
+/// процесса можно передать произвольную таблицу. `--preset` принимает только
+/// встроенные наборы.
+pub fn parse_args<I, T>(argv: I, env: impl Fn(&str) -> Option<String>) -> Result<Args, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    parse_args_with_presets(argv, env, &presets::builtin_presets())
+}
+
+/// Разбирает аргументы как `parse_args`, раскрывая `--preset` по таблице `presets`.
+pub fn parse_args_with_presets<I, T>(
+    argv: I,
+    env: impl Fn(&str) -> Option<String>,
+    presets: &BTreeMap<String, PresetDefinition>,
+) -> Result<Args, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+    let command = Args::command();
+    let cli_matches = command.clone().try_get_matches_from(&argv)?;
+
+    let mut from_env = Vec::new();
+    for arg in command.get_arguments() {
+        let (Some(long), id) = (arg.get_long(), arg.get_id().as_str()) else {
+            continue;
+        };
+        if cli_matches.value_source(id) == Some(ClapValueSource::CommandLine) {
+            continue;
+        }
+        let name = env_var_name(long);
+        let Some(value) = env(&name) else {
+            continue;
+        };
+        from_env.push(id.to_string());
+
+        if !arg.get_action().takes_values() {
+            match value.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => argv.push(format!("--{}", long).into()),
+                "" | "0" | "false" | "no" | "off" => {}
+                _ => {
+                    return Err(clap::Error::raw(
+                        clap::error::ErrorKind::InvalidValue,
+                        format!("Invalid boolean value for {}: {}\n", name, value),
+                    ));
+                }
+            }
+        } else if arg
+            .get_num_args()
+            .is_some_and(|range| range.max_values() > 1)
+        {
+            argv.push(format!("--{}", long).into());
+            argv.extend(split_list(&value).into_iter().map(OsString::from));
+        } else if matches!(arg.get_action(), ArgAction::Append) {
+            argv.extend(
+                split_list(&value)
+                    .into_iter()
+                    .map(|item| OsString::from(format!("--{}={}", long, item))),
+            );
+        } else {
+            argv.push(format!("--{}={}", long, value).into());
+        }
+    }
+
+    let explicit = command.clone().try_get_matches_from(&argv)?;
+    let mut from_preset = Vec::new();
+    let preset_name = explicit.get_one::<String>("preset").cloned();
+    if let Some(name) = &preset_name {
+        let preset = find_preset(presets, name)?;
+        for flag in preset.expansion() {
+            let long = flag.trim_start_matches('-');
+            let long = long.split_once('=').map_or(long, |(long, _)| long);
+            let Some(arg) = command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(long))
+            else {
+                return Err(clap::Error::raw(
+                    clap::error::ErrorKind::UnknownArgument,
+                    format!("Preset '{}' uses unknown flag: {}\n", name, flag),
+                ));
+            };
+            let id = arg.get_id().as_str();
+            // Списки набора дополняют явные значения, остальное только заполняет пробелы.
+            if explicit.value_source(id) == Some(ClapValueSource::CommandLine) {
+                if !matches!(arg.get_action(), ArgAction::Append) {
+                    continue;
+                }
+            } else if !from_preset.iter().any(|known| known == id) {
+                from_preset.push(id.to_string());
+            }
+            argv.push(flag.into());
+        }
+    }
+
+    let matches = command.clone().try_get_matches_from(&argv)?;
+    let mut args = Args::from_arg_matches(&matches)?;
+    args.sources = command
+        .get_arguments()
+        .filter_map(|arg| {
+            let long = arg.get_long()?;
+            let id = arg.get_id().as_str();
+            if matches!(id, "help" | "version") {
+                return None;
+            }
+            let preset = from_preset.iter().any(|name| name == id);
+            let source = if from_env.iter().any(|name| name == id) {
+                ValueSource::Env
+            } else if preset {
+                ValueSource::Preset
+            } else if matches.value_source(id) == Some(ClapValueSource::CommandLine) {
+                ValueSource::Cli
+            } else {
+                ValueSource::Default
+            };
+            let values = matches
+                .get_raw(id)
+                .map(|values| {
+                    values
+                        .map(|value| value.to_string_lossy().into_owned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(ResolvedValue {
+                name: long.to_string(),
+                env: env_var_name(long),
+                values,
+                source,
+                preset: preset.then(|| preset_name.clone()).flatten(),
+                folder: None,
+            })
+        })
+        .collect();
+    Ok(args)
+}
+
+/// Ищет набор `name` в `presets`.
+fn find_preset<'a>(
+    presets: &'a BTreeMap<String, PresetDefinition>,
+    name: &str,
+) -> Result<&'a PresetDefinition, clap::Error> {
+    presets.get(name).ok_or_else(|| {
+        let names: Vec<&str> = presets.keys().map(String::as_str).collect();
+        clap::Error::raw(
+            clap::error::ErrorKind::InvalidValue,
+            format!(
+                "Unknown preset '{}'. Available presets: {}\n",
+                name,
+                names.join(", ")
+            ),
+        )
+    })
+}
+
+/// Результат разбора командной строки, сохраненный в `Args`.
+///
+/// Заполняется при любом разборе `Args` (в том числе `Args::parse_from`
+/// в библиотечном коде) и не добавляет флагов. По нему `apply_preset`
+/// отличает флаги, заданные явно, от значений по умолчанию.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedMatches(Option<clap::ArgMatches>);
+
+impl ParsedMatches {
+    /// Задан ли параметр `id` в командной строке.
+    fn explicit(&self, id: &str) -> bool {
+        self.0.as_ref().and_then(|matches| matches.value_source(id))
+            == Some(ClapValueSource::CommandLine)
+    }
+
+    /// Значения параметра `id` в том виде, в каком они были переданы.
+    fn raw_values(&self, id: &str) -> Vec<OsString> {
+        self.0
+            .as_ref()
+            .and_then(|matches| matches.get_raw(id))
+            .map(|values| values.map(OsString::from).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl FromArgMatches for ParsedMatches {
+    fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Self, clap::Error> {
+        Ok(Self(Some(matches.clone())))
+    }
+
+    /// Обновление значений (например, раскрытие набора) не меняет сведений о разборе.
+    fn update_from_arg_matches(&mut self, _matches: &clap::ArgMatches) -> Result<(), clap::Error> {
+        Ok(())
+    }
+}
+
+impl clap::Args for ParsedMatches {
+    fn augment_args(command: clap::Command) -> clap::Command {
+        command
+    }
+
+    fn augment_args_for_update(command: clap::Command) -> clap::Command {
+        command
+    }
+}
+
+/// Раскрывает `--preset` в аргументах, разобранных без `parse_args`
+/// (например, `Args::parse_from` в библиотечном коде).
+///
+/// Как и в `parse_args`, флаги набора заполняют только параметры, не заданные
+/// в командной строке, а списки (шаблоны набора) дополняют явные значения.
+/// Поля, измененные после разбора, сохраняются, если набор их не задает.
+/// Аргументы из `parse_args` с заполненным `sources` уже раскрыты и
+/// возвращаются как есть.
+///
+/// # Ошибки
+/// Если набор неизвестен или его флаги не разбираются.
+pub fn apply_preset(args: &Args, presets: &BTreeMap<String, PresetDefinition>) -> Result<Args> {
+    let Some(name) = args.preset.as_deref().filter(|_| args.sources.is_empty()) else {
+        return Ok(args.clone());
+    };
+    let preset = find_preset(presets, name)?;
+    let command = Args::command();
+    let mut argv: Vec<OsString> = vec!["flatten-rust".into()];
+    let mut from_preset: Vec<&str> = Vec::new();
+    for flag in preset.expansion() {
+        let long = flag.trim_start_matches('-');
+        let (long, value) = long.split_once('=').unwrap_or((long, ""));
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long))
+        else {
+            anyhow::bail!("Preset '{}' uses unknown flag: {}", name, flag);
+        };
+        let id = arg.get_id().as_str();
+        if args.parsed.explicit(id) {
+            if !matches!(arg.get_action(), ArgAction::Append) {
+                continue;
+            }
+            // Список заново собирается из явных значений и значений набора.
+            let current = args.parsed.raw_values(id);
+            if !from_preset.contains(&id) {
+                from_preset.push(id);
+                argv.extend(current.iter().map(|item| {
+                    let mut flag = OsString::from(format!("--{}=", long));
+                    flag.push(item);
+                    flag
+                }));
+            }
+            if current.iter().any(|item| item.as_os_str() == value) {
+                continue;
+            }
+        }
+        if !from_preset.contains(&id) {
+            from_preset.push(id);
+        }
+        argv.push(flag.into());
+    }
+
+    let mut matches = command
+        .clone()
+        .try_get_matches_from(&argv)
+        .with_context(|| format!("Preset '{}' has invalid flags", name))?;
+    // Поля без значений в `matches` не обновляются.
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if !from_preset.contains(&id) {
+            matches.try_clear_id(id)?;
+        }
+    }
+    let mut expanded = args.clone();
+    expanded.update_from_arg_matches(&matches)?;
+    Ok(expanded)
+}
+
+/// Разбирает аргументы процесса и его переменные окружения; при ошибке завершает процесс.
+///
+/// Ошибки разбора завершают процесс с кодом `EXIT_USAGE`, `--help` и `--version` — с кодом 0.
+/// Наборы `--preset` — встроенные и пользовательские из `~/.flatten/manager_config.json`,
+/// оттуда же читаются настройки отдельных папок; ошибка в них тоже завершает процесс
+/// с кодом `EXIT_USAGE`. С `--replay` значения берутся из снимка (см. `invocation`).
+pub fn parse_process_args() -> Args {
+    let env = |name: &str| std::env::var(name).ok();
+    let presets = presets::available_presets();
+    let mut args =
+        parse_args_with_presets(std::env::args_os(), env, &presets).unwrap_or_else(|error| {
+            let _ = error.print();
+            std::process::exit(if error.use_stderr() { EXIT_USAGE } else { EXIT_OK })
+        });
+    if let Some(path) = args.replay.clone() {
+        let (args, warnings) = invocation::replay_args(&path, std::env::args_os(), &presets)
+            .unwrap_or_else(|error| {
+                eprintln!("Error: {:#}", error);
+                std::process::exit(EXIT_USAGE)
+            });
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        return args;
+    }
+    args.folder_overrides = folder_overrides::available_overrides().unwrap_or_else(|error| {
+        eprintln!("Error: {:#}", error);
+        std::process::exit(EXIT_USAGE)
+    });
+    args
+}
+
+/// Выводит итоговые значения параметров и их источники (`--print-config`).
+///
+/// Для базовых папок с собственным блоком настроек дополнительно выводятся
+/// значения, действующие только для них (источник `folder`).
+pub fn print_config(args: &Args) -> Result<()> {
+    write_config(args, &mut std::io::stdout().lock())
+}
+
+/// Записывает в `output` то же, что выводит `print_config`.
+pub fn write_config(args: &Args, output: &mut dyn Write) -> Result<()> {
+    let folders: Vec<(String, Vec<ResolvedValue>)> = args
+        .folders
+        .iter()
+        .filter_map(|folder| {
+            let (key, block) = folder_overrides::find(&args.folder_overrides, folder)?;
+            Some((folder.display().to_string(), block.resolved_values(key, &args.sources)))
+        })
+        .collect();
+    if args.format == OutputFormat::Json {
+        let all: Vec<&ResolvedValue> = args
+            .sources
+            .iter()
+            .chain(folders.iter().flat_map(|(_, values)| values))
+            .collect();
+        writeln!(output, "{}", serde_json::to_string_pretty(&all)?)?;
+        return Ok(());
+    }
+    write_values(&args.sources, output)?;
+    for (folder, values) in &folders {
+        writeln!(output)?;
+        writeln!(output, "Folder {} (other settings as above):", folder)?;
+        write_values(values, output)?;
+    }
+    Ok(())
+}
+
+fn write_values(sources: &[ResolvedValue], output: &mut dyn Write) -> Result<()> {
+    for value in sources {
+        let source = match &value.preset {
+            Some(preset) => format!("preset:{}", preset),
+            None => value.source.as_str().to_string(),
+        };
+        writeln!(
+            output,
+            "  {:<24} {:<8} {}",
+            value.name,
+            source,
+            value.values.join(", ")
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_split_list_escaping() {
+        assert_eq!(split_list("a, b,,c"), vec!["a", "b", "c"]);
+        assert_eq!(split_list(r"x\,y,z\\"), vec!["x,y", r"z\"]);
+    }
+
+    #[test]
+    fn test_env_precedence() -> Result<()> {
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("FLATTEN_OUTPUT", "env.md"),
+            ("FLATTEN_SKIP_FOLDERS", "vendor,out"),
+            ("FLATTEN_INCLUDE_HIDDEN", "true"),
+            ("FLATTEN_ENABLE_TEMPLATE", "rust,node"),
+        ]);
+        let lookup = |name: &str| env.get(name).map(|v| v.to_string());
+
+        let args = parse_args(["flatten-rust", "-f", "."], lookup)?;
+        assert_eq!(args.output, vec![PathBuf::from("env.md")]);
+        assert_eq!(args.skip_folders, vec!["vendor", "out"]);
+        assert!(args.include_hidden);
+        assert_eq!(args.enable_templates, vec!["rust", "node"]);
+
+        let args = parse_args(["flatten-rust", "-f", ".", "-o", "cli.md"], lookup)?;
+        assert_eq!(args.output, vec![PathBuf::from("cli.md")]);
+        let source = |name: &str| {
+            args.sources
+                .iter()
+                .find(|value| value.name == name)
+                .map(|value| value.source)
+        };
+        assert_eq!(source("output"), Some(ValueSource::Cli));
+        assert_eq!(source("skip-folders"), Some(ValueSource::Env));
+        assert_eq!(source("max-depth"), Some(ValueSource::Default));
+        Ok(())
+    }
+
+    #[test]
+    fn test_preset_expansion_and_provenance() -> Result<()> {
+        let env: HashMap<&str, &str> = HashMap::from([("FLATTEN_MAX_FILE_SIZE", "5")]);
+        let lookup = |name: &str| env.get(name).map(|v| v.to_string());
+
+        let args = parse_args(["flatten-rust", "--preset", "llm", "-e", "rust"], lookup)?;
+        assert!(args.skip_lockfiles);
+        assert_eq!(args.max_file_size, 5);
+        let source = |name: &str| {
+            args.sources
+                .iter()
+                .find(|value| value.name == name)
+                .map(|value| (value.source, value.preset.clone()))
+        };
+        assert_eq!(
+            source("skip-lockfiles"),
+            Some((ValueSource::Preset, Some("llm".to_string())))
+        );
+        assert_eq!(source("max-file-size"), Some((ValueSource::Env, None)));
+
+        let args = parse_args(["flatten-rust", "--preset=web", "-e", "rust"], |_| None)?;
+        assert_eq!(
+            args.enable_templates,
+            vec!["rust", "node", "react", "nextjs", "sass"]
+        );
+
+        let error = parse_args(["flatten-rust", "--preset", "nope"], |_| None).unwrap_err();
+        assert!(error.to_string().contains(
+            "Unknown preset 'nope'. Available presets: jvm, llm, python-ml, web"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_preset_to_library_args() -> Result<()> {
+        let presets = presets::builtin_presets();
+        // Аргументы библиотечного вызова: набор не раскрыт при разборе.
+        let args = Args::parse_from(["flatten-rust", "--preset", "llm"]);
+        assert!(!args.skip_lockfiles);
+        let expanded = apply_preset(&args, &presets)?;
+        assert!(expanded.skip_lockfiles);
+        assert_eq!(expanded.max_file_size, 1048576);
+
+        // Явные значения остаются, списки дополняются.
+        let args = Args::parse_from(["flatten-rust", "--preset=web", "-m", "5", "-e", "rust"]);
+        let expanded = apply_preset(&args, &presets)?;
+        assert_eq!(expanded.max_file_size, 5);
+        assert_eq!(
+            expanded.enable_templates,
+            vec!["rust", "node", "react", "nextjs", "sass"]
+        );
+        let again = apply_preset(&expanded, &presets)?;
+        assert_eq!(again.enable_templates, expanded.enable_templates);
+
+        // Явное значение, совпадающее с умолчанием, тоже остается.
+        let argv = ["flatten-rust", "--preset", "llm", "-m", "104857600"];
+        let expanded = apply_preset(&Args::parse_from(argv), &presets)?;
+        assert_eq!(expanded.max_file_size, 104857600);
+        assert!(expanded.skip_lockfiles);
+
+        // Поля, измененные после разбора, набор не трогает.
+        let mut args = Args::parse_from(["flatten-rust", "--preset", "llm"]);
+        args.folders = vec![PathBuf::from("src")];
+        assert_eq!(apply_preset(&args, &presets)?.folders, args.folders);
+
+        // Аргументы из `parse_args` уже раскрыты: явное значение по умолчанию остается.
+        let argv = ["flatten-rust", "--preset", "llm", "-m", "104857600"];
+        let parsed = parse_args(argv, |_| None)?;
+        assert_eq!(apply_preset(&parsed, &presets)?.max_file_size, 104857600);
+
+        let args = Args::parse_from(["flatten-rust", "--preset", "nope"]);
+        assert!(apply_preset(&args, &presets).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_preset_keeps_non_utf8_paths() -> Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let folder = std::ffi::OsStr::from_bytes(b"proj\xff");
+        let argv = [
+            OsString::from("flatten-rust"),
+            OsString::from("--preset=llm"),
+            OsString::from("-f"),
+            folder.to_os_string(),
+        ];
+        let expanded = apply_preset(&Args::parse_from(argv), &presets::builtin_presets())?;
+        assert_eq!(expanded.folders, vec![PathBuf::from(folder)]);
+        assert!(expanded.skip_lockfiles);
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+//! Модуль ошибок библиотечного API.
+//!
+//! Точки входа (`run`, `run_with`, `run_blocking`, `run_to_writer`) и
+//! `FlattenConfig::new` возвращают `FlattenError`, чтобы вызывающий код мог
+//! отличить недоступный для записи вывод от недоступного API шаблонов или
+//! неверной конфигурации. Внутренние функции по-прежнему используют `anyhow`;
+//! ошибка чтения отдельного файла не прерывает обработку, а попадает в
+//! `FlattenError::Partial` после записи всего документа.
+//!
+//! Каждой ошибке соответствует код завершения процесса (`exit_code`).
+
+use crate::integrity::IntegrityCheck;
+use crate::pipeline::TreeMismatch;
+use crate::report::FlattenReport;
+use crate::time_limit::Truncation;
+use crate::verify::VerifyReport;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Код завершения при успехе.
+pub const EXIT_OK: i32 = 0;
+/// Код завершения при ошибке аргументов или конфигурации.
+pub const EXIT_USAGE: i32 = 1;
+/// Код завершения при фатальной ошибке ввода-вывода.
+pub const EXIT_IO: i32 = 2;
+/// Код завершения, если часть файлов не прочиталась (`--fail-on-error`).
+pub const EXIT_PARTIAL: i32 = 3;
+/// Код завершения, если документ обрезан по `--time-limit`.
+pub const EXIT_TIME_LIMIT: i32 = 4;
+/// Код завершения, если `--explain` нашел пути, не попадающие в вывод.
+///
+/// Равен [`EXIT_USAGE`]: для скриптов исключенный путь — обычная неудача команды.
+pub const EXIT_NOT_INCLUDED: i32 = 1;
+/// Код завершения, если `--verify` нашел расхождения документа с файлами.
+///
+/// Равен [`EXIT_USAGE`]: CI проверяет только, что код ненулевой.
+pub const EXIT_OUTDATED: i32 = 1;
+/// Код завершения, если проверки `--doctor` нашли проблемы.
+pub const EXIT_DOCTOR_FAILED: i32 = 7;
+/// Код завершения, если документ не совпадает со своей строкой целостности.
+pub const EXIT_INTEGRITY: i32 = 8;
+
+/// Результат библиотечных функций.
+pub type FlattenResult<T> = std::result::Result<T, FlattenError>;
+
+/// Ошибка процесса "сглаживания".
+#[derive(Debug, thiserror::Error)]
+pub enum FlattenError {
+    /// Не удалось создать или записать файл вывода (документ, манифест, статистику).
+    #[error("Failed to write output file {}", path.display())]
+    OutputIo {
+        /// Путь к файлу вывода.
+        path: PathBuf,
+        /// Исходная ошибка ввода-вывода.
+        source: std::io::Error,
+    },
+    /// Не удалось получить шаблоны исключений из API.
+    #[error("Failed to fetch exclusion templates")]
+    TemplateFetch {
+        /// Исходная ошибка загрузки.
+        source: anyhow::Error,
+    },
+    /// Кэш шаблонов поврежден, а загрузить новый не удалось.
+    #[error("Template cache {} is corrupt and could not be refreshed", path.display())]
+    CacheCorrupt {
+        /// Путь к файлу кэша.
+        path: PathBuf,
+    },
+    /// Неверные аргументы или паттерны.
+    #[error("{0}")]
+    InvalidConfig(String),
+    /// Документ записан, но часть файлов прочитать не удалось.
+    #[error("{} file(s) could not be read", errors.len())]
+    Partial {
+        /// Отчет о выполнении.
+        report: Box<FlattenReport>,
+        /// Ошибки чтения файлов в порядке вывода.
+        errors: Vec<FileError>,
+    },
+    /// Документ записан, но до истечения `--time-limit` обработаны не все файлы.
+    #[error("Output truncated: {truncation}")]
+    TimeLimit {
+        /// Отчет о выполнении.
+        report: Box<FlattenReport>,
+        /// Сколько осталось необработанным.
+        truncation: Truncation,
+    },
+    /// Дерево структуры и секции содержимого папки разошлись (`--self-check`).
+    #[error("Self-check failed for {}: {mismatch}", folder.display())]
+    SelfCheck {
+        /// Базовая папка в том виде, в котором она указана в документе.
+        folder: PathBuf,
+        /// Файлы, по которым они разошлись.
+        mismatch: TreeMismatch,
+    },
+    /// `--explain`: часть путей не попадает в вывод; объяснения уже выведены.
+    #[error("{} of the explained paths are not included", paths.len())]
+    NotIncluded {
+        /// Исключенные пути в порядке аргументов.
+        paths: Vec<PathBuf>,
+    },
+    /// `--verify`: документ не соответствует текущим файлам; расхождения уже выведены.
+    #[error("Document {} is out of date", document.display())]
+    Outdated {
+        /// Проверенный документ.
+        document: PathBuf,
+        /// Найденные расхождения.
+        report: Box<VerifyReport>,
+    },
+    /// `--check`, `--verify`: документ не совпадает со своей строкой целостности;
+    /// расхождение уже выведено.
+    #[error("Integrity check failed for {}", document.display())]
+    IntegrityMismatch {
+        /// Проверенный документ.
+        document: PathBuf,
+        /// Записанные и найденные значения.
+        check: Box<IntegrityCheck>,
+    },
+    /// `--doctor`: часть проверок провалилась; результаты уже выведены.
+    #[error("{failed} doctor check(s) failed")]
+    DoctorFailed {
+        /// Число проваленных проверок.
+        failed: usize,
+    },
+    /// Пользователь отказался обрабатывать большую базовую папку (`--confirm-threshold`)
+    /// или удалять весь кэш (`--cache-prune --all`).
+    #[error("Aborted: run was not confirmed")]
+    Aborted,
+    /// Прочие ошибки.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl FlattenError {
+    /// Возвращает код завершения процесса для этой ошибки.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FlattenError::InvalidConfig(_) | FlattenError::Aborted => EXIT_USAGE,
+            FlattenError::Partial { .. } => EXIT_PARTIAL,
+            FlattenError::TimeLimit { .. } => EXIT_TIME_LIMIT,
+            FlattenError::NotIncluded { .. } => EXIT_NOT_INCLUDED,
+            FlattenError::Outdated { .. } => EXIT_OUTDATED,
+            FlattenError::DoctorFailed { .. } => EXIT_DOCTOR_FAILED,
+            FlattenError::IntegrityMismatch { .. } => EXIT_INTEGRITY,
+            FlattenError::OutputIo { .. }
+            | FlattenError::TemplateFetch { .. }
+            | FlattenError::CacheCorrupt { .. }
+            | FlattenError::SelfCheck { .. }
+            | FlattenError::Other(_) => EXIT_IO,
+        }
+    }
+
+    /// Оборачивает ошибку загрузки шаблонов, сохраняя уже типизированные ошибки.
+    pub(crate) fn template_fetch(error: anyhow::Error) -> Self {
+        match error.downcast::<FlattenError>() {
+            Ok(error) => error,
+            Err(source) => FlattenError::TemplateFetch { source },
+        }
+    }
+
+    /// Преобразует ошибку разбора аргументов или паттернов в `InvalidConfig`.
+    pub(crate) fn invalid_config(error: anyhow::Error) -> Self {
+        match error.downcast::<FlattenError>() {
+            Ok(error) => error,
+            Err(error) => FlattenError::InvalidConfig(format!("{:#}", error)),
+        }
+    }
+
+    /// Преобразует ошибку записи в файл `path` в `OutputIo`, если ее причина — ввод-вывод.
+    pub(crate) fn output(path: impl Into<PathBuf>, error: anyhow::Error) -> Self {
+        match error.downcast::<std::io::Error>() {
+            Ok(source) => FlattenError::OutputIo {
+                path: path.into(),
+                source,
+            },
+            Err(error) => error.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for FlattenError {
+    fn from(error: anyhow::Error) -> Self {
+        error
+            .downcast::<FlattenError>()
+            .unwrap_or_else(FlattenError::Other)
+    }
+}
+
+/// Ошибка чтения одного файла.
+#[derive(Debug)]
+pub struct FileError {
+    /// Путь к файлу в том виде, в котором он указан в документе.
+    pub path: PathBuf,
+    /// Причина ошибки.
+    pub source: anyhow::Error,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:#}", self.path.display(), self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_errors_survive_anyhow() {
+        let wrapped = anyhow::Error::from(FlattenError::CacheCorrupt {
+            path: PathBuf::from("cache.json"),
+        });
+        assert!(matches!(
+            FlattenError::template_fetch(wrapped),
+            FlattenError::CacheCorrupt { .. }
+        ));
+
+        let error = FlattenError::template_fetch(anyhow::anyhow!("timeout"));
+        assert!(matches!(error, FlattenError::TemplateFetch { .. }));
+
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let error = FlattenError::output("out.md", anyhow::Error::from(io).context("open"));
+        assert!(matches!(error, FlattenError::OutputIo { ref path, .. } if path.ends_with("out.md")));
+
+        let error = FlattenError::from(anyhow::anyhow!("other"));
+        assert!(matches!(error, FlattenError::Other(_)));
+    }
+}
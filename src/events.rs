@@ -0,0 +1,213 @@
+//! Модуль потока событий обработки для интеграции с редакторами (`--events`).
+//!
+//! Каждое событие — JSON-объект на строку с полем `event`, поток сбрасывается
+//! после каждого события. Расширение редактора может показывать по нему ход
+//! обработки и переходить к секции файла по смещению из `file_written`.
+//! События строятся из уведомлений `ProgressSink`, поэтому те же сведения
+//! доступны и в процессе через собственную реализацию трейта.
+//!
+//! Порядок событий: `run_started`, затем для каждой базовой папки
+//! `folder_started` и события ее файлов, в конце `run_finished`. События
+//! чтения (`file_included`, `file_skipped`, `file_failed`) приходят из рабочих
+//! потоков в порядке завершения, но раньше `file_written` того же файла;
+//! `file_written` идут в порядке документа.
+
+use crate::cache_maintenance::CacheStatus;
+use crate::manifest::ContentSpan;
+use crate::preview::RunPreview;
+use crate::progress::{ProgressSink, ProgressSummary};
+use crate::report::{FlattenReport, SkipReason, StatsSummary};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Событие потока `--events`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Начат запуск.
+    RunStarted {
+        /// Версия утилиты.
+        version: &'static str,
+    },
+    /// Начат обход базовой папки.
+    FolderStarted {
+        /// Путь базовой папки.
+        path: &'a Path,
+    },
+    /// Файл прочитан.
+    FileIncluded {
+        /// Путь файла.
+        path: &'a Path,
+        /// Исходный размер файла в байтах.
+        size: u64,
+    },
+    /// Файл заменен заглушкой.
+    FileSkipped {
+        /// Путь файла.
+        path: &'a Path,
+        /// Причина замены.
+        reason: SkipReason,
+    },
+    /// Файл не удалось прочитать.
+    FileFailed {
+        /// Путь файла.
+        path: &'a Path,
+    },
+    /// Файл записан в документ.
+    FileWritten {
+        /// Путь файла.
+        path: &'a Path,
+        /// Смещение содержимого в документе (только для форматов, где оно известно).
+        #[serde(skip_serializing_if = "Option::is_none")]
+        offset: Option<u64>,
+        /// Длина содержимого в байтах.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        length: Option<u64>,
+    },
+    /// Запуск завершен.
+    RunFinished {
+        /// Сводная статистика запуска.
+        report: &'a StatsSummary,
+    },
+}
+
+/// Получатель уведомлений, записывающий их потоком JSON-событий.
+///
+/// Уведомления передаются дальше в `inner` (например, полосе прогресса).
+/// Ошибки записи событий не прерывают запуск: поток просто обрывается.
+pub struct EventStream {
+    writer: Mutex<Box<dyn Write + Send>>,
+    inner: Option<Arc<dyn ProgressSink>>,
+}
+
+impl EventStream {
+    /// Создает поток поверх `writer`.
+    pub fn new(writer: impl Write + Send + 'static, inner: Option<Arc<dyn ProgressSink>>) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            inner,
+        }
+    }
+
+    /// Открывает поток в файле `target` (`-` — стандартный вывод).
+    ///
+    /// # Ошибки
+    /// Если файл не удалось создать.
+    pub fn open(target: &Path, inner: Option<Arc<dyn ProgressSink>>) -> Result<Self> {
+        if target == Path::new("-") {
+            return Ok(Self::new(std::io::stdout(), inner));
+        }
+        let file = File::create(target)
+            .with_context(|| format!("Failed to create events file: {}", target.display()))?;
+        Ok(Self::new(BufWriter::new(file), inner))
+    }
+
+    /// Записывает событие строкой и сбрасывает поток.
+    fn emit(&self, event: &Event) {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = serde_json::to_writer(&mut *writer, event)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(writer))
+            .and_then(|()| writer.flush());
+    }
+
+    /// Получатель, которому передаются уведомления.
+    fn inner(&self) -> Option<&dyn ProgressSink> {
+        self.inner.as_deref()
+    }
+}
+
+impl ProgressSink for EventStream {
+    fn on_run_started(&self) {
+        if let Some(inner) = self.inner() {
+            inner.on_run_started();
+        }
+        self.emit(&Event::RunStarted {
+            version: env!("CARGO_PKG_VERSION"),
+        });
+    }
+
+    fn on_folder_started(&self, folder: &Path) {
+        if let Some(inner) = self.inner() {
+            inner.on_folder_started(folder);
+        }
+        self.emit(&Event::FolderStarted { path: folder });
+    }
+
+    fn on_start(&self, total: usize) {
+        if let Some(inner) = self.inner() {
+            inner.on_start(total);
+        }
+    }
+
+    fn on_discovered(&self, count: usize) {
+        if let Some(inner) = self.inner() {
+            inner.on_discovered(count);
+        }
+    }
+
+    fn on_file_started(&self, relative: &Path) {
+        if let Some(inner) = self.inner() {
+            inner.on_file_started(relative);
+        }
+    }
+
+    fn on_file_done(&self, path: &Path, bytes: u64, ok: bool) {
+        if let Some(inner) = self.inner() {
+            inner.on_file_done(path, bytes, ok);
+        }
+        self.emit(&match ok {
+            true => Event::FileIncluded { path, size: bytes },
+            false => Event::FileFailed { path },
+        });
+    }
+
+    fn on_file_skipped(&self, path: &Path, reason: SkipReason) {
+        if let Some(inner) = self.inner() {
+            inner.on_file_skipped(path, reason);
+        }
+        self.emit(&Event::FileSkipped { path, reason });
+    }
+
+    fn on_file_written(&self, path: &Path, span: Option<ContentSpan>) {
+        if let Some(inner) = self.inner() {
+            inner.on_file_written(path, span);
+        }
+        self.emit(&Event::FileWritten {
+            path,
+            offset: span.map(|span| span.offset),
+            length: span.map(|span| span.length),
+        });
+    }
+
+    fn confirm_run(&self, preview: &RunPreview) -> bool {
+        self.inner().is_none_or(|inner| inner.confirm_run(preview))
+    }
+
+    fn confirm_cache_prune(&self, status: &CacheStatus) -> bool {
+        self.inner().is_some_and(|inner| inner.confirm_cache_prune(status))
+    }
+
+    fn stop_requested(&self) -> bool {
+        self.inner().is_some_and(|inner| inner.stop_requested())
+    }
+
+    fn on_finish(&self, summary: &ProgressSummary) {
+        if let Some(inner) = self.inner() {
+            inner.on_finish(summary);
+        }
+    }
+
+    fn on_run_finished(&self, report: &FlattenReport) {
+        if let Some(inner) = self.inner() {
+            inner.on_run_finished(report);
+        }
+        self.emit(&Event::RunFinished {
+            report: &report.summary(),
+        });
+    }
+}
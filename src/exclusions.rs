@@ -3,11 +3,348 @@
 //! `ExclusionManager` является центральным компонентом, который использует
 //! `TemplateManager` для получения шаблонов и применяет их для
 //! определения, какие файлы и папки следует исключить из обработки.
+//!
+//! Ключи шаблонов сравниваются без учета регистра и проходят через таблицу
+//! синонимов (`vscode` → `visualstudiocode`); для неизвестного ключа ошибка
+//! предлагает три ближайших по написанию шаблона.
+//!
+//! Содержимое каждого включенного шаблона разбирается на паттерны один раз
+//! за время жизни менеджера (лениво, при первом обращении); кэш сбрасывается
+//! при принудительном обновлении шаблонов.
+//!
+//! Правила исключений хранятся упорядоченными списками с происхождением
+//! (`RuleList`). Старшинство слоев: флаги командной строки, затем файлы
+//! `.flattenignore`, затем шаблоны в порядке включения, затем встроенные
+//! умолчания. Решение принимает самый старший слой, в котором совпало хотя бы
+//! одно правило; внутри слоя, как в `.gitignore`, побеждает последнее
+//! совпавшее правило, а `!` возвращает путь.
+//!
+//! Строки шаблонов с простым именем или расширением (`target/`, `*.log`)
+//! проверяются по таблицам; составные паттерны (`build/*.o`, `**/*.orig`,
+//! `.vscode/*`, `*.py[cod]`) компилируются в glob и сопоставляются с путем
+//! относительно базовой папки — и при сборе файлов, и при построении дерева.
 
 use crate::config::TemplateManager;
-use anyhow::Result;
-use std::collections::HashSet;
+use anyhow::{Context, Result, bail};
+use glob::{MatchOptions, Pattern};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
 use std::path::Path;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Источник паттерна: шаблон и строка, из которой он был извлечен.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternSource {
+    /// Ключ шаблона (например, "python").
+    pub template: String,
+    /// Номер строки в шаблоне (начиная с 1).
+    pub line: usize,
+    /// Исходный текст паттерна.
+    pub pattern: String,
+}
+
+/// Происхождение правила исключения.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternOrigin {
+    /// Встроенное значение по умолчанию.
+    Default,
+    /// Явно указано пользователем во флагах командной строки.
+    UserFlag,
+    /// Извлечено из строки включенного шаблона.
+    Template(PatternSource),
+}
+
+impl std::fmt::Display for PatternOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternOrigin::Default => write!(f, "default"),
+            PatternOrigin::UserFlag => write!(f, "command line"),
+            PatternOrigin::Template(source) => write!(
+                f,
+                "template '{}', pattern `{}` from line {}",
+                source.template, source.pattern, source.line
+            ),
+        }
+    }
+}
+
+impl PatternOrigin {
+    /// Название слоя правил, к которому относится происхождение.
+    pub fn layer(&self) -> &'static str {
+        match self {
+            PatternOrigin::Default => "default",
+            PatternOrigin::UserFlag => "command line",
+            PatternOrigin::Template(_) => "template",
+        }
+    }
+}
+
+/// Паттерн папки с разделителями пути (например, `/build` или `services/*/generated`)
+/// или составной паттерн шаблона (`build/*.o`, `docs/**/*.pdf`).
+///
+/// В отличие от простых имен, сопоставляется с путем относительно базовой папки;
+/// `*` не пересекает границы компонентов пути, `**` совпадает с любым числом директорий.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    raw: String,
+    pattern: Pattern,
+}
+
+impl PathPattern {
+    /// Является ли значение `--skip-folders` паттерном пути, а не простым именем.
+    pub fn is_path_pattern(value: &str) -> bool {
+        value.trim_end_matches('/').contains('/')
+    }
+
+    /// Компилирует строку шаблона в стиле `.gitignore`: паттерн с `/`
+    /// сопоставляется с путем относительно базовой папки, паттерн без `/` — с
+    /// именем на любой глубине (как `**/паттерн`).
+    pub fn glob(raw: &str) -> Result<Self> {
+        if Self::is_path_pattern(raw) {
+            return Self::new(raw);
+        }
+        let normalized = raw.trim_end_matches('/');
+        let pattern = Pattern::new(&format!("**/{}", normalized))
+            .with_context(|| format!("Invalid pattern: {}", raw))?;
+        Ok(Self {
+            raw: normalized.to_string(),
+            pattern,
+        })
+    }
+
+    /// Компилирует паттерн. Ведущий и завершающий `/` не влияют на сопоставление.
+    pub fn new(raw: &str) -> Result<Self> {
+        let normalized = raw.trim_matches('/');
+        let pattern = Pattern::new(normalized)
+            .with_context(|| format!("Invalid folder pattern: {}", raw))?;
+        Ok(Self {
+            raw: raw.trim_end_matches('/').to_string(),
+            pattern,
+        })
+    }
+
+    /// Паттерн в том виде, в котором он был указан (без завершающего `/`).
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Проверяет путь относительно базовой папки.
+    pub fn matches(&self, relative: &Path) -> bool {
+        let components: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.pattern.matches_with(&components.join("/"), options)
+    }
+}
+
+/// На что распространяется правило исключения.
+#[derive(Debug, Clone)]
+pub enum RuleTarget {
+    /// Имя папки или файла на любой глубине.
+    Name(String),
+    /// Паттерн пути относительно базовой папки.
+    Path(PathPattern),
+    /// Расширение файла без точки.
+    Extension(String),
+    /// Составной паттерн шаблона (`build/*.o`, `**/*.orig`, `*.py[cod]`).
+    Glob(PathPattern),
+}
+
+impl RuleTarget {
+    /// Правило папки: простое имя или паттерн пути.
+    ///
+    /// # Ошибки
+    /// Если паттерн пути не компилируется.
+    pub fn folder(value: &str) -> Result<Self> {
+        Ok(if PathPattern::is_path_pattern(value) {
+            Self::Path(PathPattern::new(value)?)
+        } else {
+            Self::Name(value.to_string())
+        })
+    }
+
+    /// Правило в том виде, в котором оно выводится (`*.log` для расширений).
+    pub fn as_pattern(&self) -> String {
+        match self {
+            Self::Name(name) => name.clone(),
+            Self::Path(pattern) | Self::Glob(pattern) => pattern.as_str().to_string(),
+            Self::Extension(extension) => format!("*.{}", extension),
+        }
+    }
+
+    /// Применяется ли правило к расширениям, а не к путям.
+    pub fn is_extension(&self) -> bool {
+        matches!(self, Self::Extension(_))
+    }
+
+    /// Цель строки шаблона `pattern` (без `!`).
+    ///
+    /// Простые имена и расширения (`target/`, `*.log`) проверяются по таблицам
+    /// имен и расширений, как раньше; паттерны с `/` внутри или с символами
+    /// glob компилируются в `Glob`. Имена файлов без символов glob
+    /// (`pip-log.txt`, `Cargo.lock`) не применяются: в шаблонах это обычно
+    /// файлы, которые в контексте проекта нужны.
+    ///
+    /// # Ошибки
+    /// Если паттерн не компилируется.
+    pub fn from_template(pattern: &str) -> Result<Option<Self>> {
+        if let Some(folder) = ExclusionManager::extract_folder_name(pattern) {
+            return Self::folder(&folder).map(Some);
+        }
+        if let Some(extension) = ExclusionManager::extract_extension(pattern) {
+            return Ok(Some(Self::Extension(extension)));
+        }
+        let body = pattern.trim_end_matches('/');
+        if body.is_empty() || !(PathPattern::is_path_pattern(body) || has_glob_syntax(body)) {
+            return Ok(None);
+        }
+        PathPattern::glob(pattern).map(|pattern| Some(Self::Glob(pattern)))
+    }
+}
+
+/// Есть ли в паттерне символы glob.
+fn has_glob_syntax(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Правило исключения вместе с его происхождением.
+#[derive(Debug, Clone)]
+pub struct ExclusionRule {
+    /// Что исключает (или возвращает) правило.
+    pub target: RuleTarget,
+    /// Возвращает ли правило путь (`!` в шаблоне, `--include-extension`).
+    pub negated: bool,
+    /// Откуда взято правило; определяет его слой.
+    pub origin: PatternOrigin,
+}
+
+impl ExclusionRule {
+    /// Исключающее правило.
+    pub fn exclude(target: RuleTarget, origin: PatternOrigin) -> Self {
+        Self {
+            target,
+            negated: false,
+            origin,
+        }
+    }
+
+    /// Разбирает строку шаблона; паттерны, которые не применяются (см.
+    /// `RuleTarget::from_template`), дают `Ok(None)`.
+    ///
+    /// # Ошибки
+    /// Если паттерн не компилируется.
+    pub fn from_template(source: PatternSource) -> Result<Option<Self>> {
+        let (negated, body) = match source.pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, source.pattern.as_str()),
+        };
+        let Some(target) = RuleTarget::from_template(body)? else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            target,
+            negated,
+            origin: PatternOrigin::Template(source),
+        }))
+    }
+
+    /// Правило в исходном виде, с `!` для возвращающих правил.
+    pub fn pattern(&self) -> String {
+        let pattern = self.target.as_pattern();
+        if self.negated {
+            format!("!{}", pattern)
+        } else {
+            pattern
+        }
+    }
+
+    /// Совпадает ли правило пути с путем `relative` (относительно базовой папки)
+    /// или с его последним компонентом `name`.
+    fn matches_path(&self, name: &OsStr, relative: &Path) -> bool {
+        match &self.target {
+            RuleTarget::Name(rule) => name == OsStr::new(rule),
+            RuleTarget::Path(pattern) | RuleTarget::Glob(pattern) => pattern.matches(relative),
+            RuleTarget::Extension(_) => false,
+        }
+    }
+}
+
+/// Упорядоченный список правил одного слоя.
+///
+/// Из совпавших правил побеждает последнее. Для имен и расширений хранится
+/// индекс последнего правила, поэтому проверка пути не перебирает весь список.
+#[derive(Debug, Clone, Default)]
+pub struct RuleList {
+    rules: Vec<ExclusionRule>,
+    names: HashMap<String, usize>,
+    extensions: HashMap<String, usize>,
+    paths: Vec<usize>,
+}
+
+impl RuleList {
+    /// Добавляет правило в конец списка: оно сильнее всех предыдущих.
+    pub fn push(&mut self, rule: ExclusionRule) {
+        let index = self.rules.len();
+        match &rule.target {
+            RuleTarget::Name(name) => {
+                self.names.insert(name.clone(), index);
+            }
+            RuleTarget::Extension(extension) => {
+                self.extensions.insert(extension.clone(), index);
+            }
+            RuleTarget::Path(_) | RuleTarget::Glob(_) => self.paths.push(index),
+        }
+        self.rules.push(rule);
+    }
+
+    /// Правила в порядке добавления.
+    pub fn rules(&self) -> &[ExclusionRule] {
+        &self.rules
+    }
+
+    /// Побеждающее правило имени или пути для пути `relative` с последним
+    /// компонентом `name`.
+    pub fn path_rule(&self, name: &OsStr, relative: &Path) -> Option<&ExclusionRule> {
+        let by_name = name.to_str().and_then(|name| self.names.get(name)).copied();
+        let by_path = self
+            .paths
+            .iter()
+            .rev()
+            .find(|&&index| self.rules[index].matches_path(name, relative))
+            .copied();
+        by_name.max(by_path).map(|index| &self.rules[index])
+    }
+
+    /// Побеждающее правило для расширения `extension`.
+    pub fn extension_rule(&self, extension: &str) -> Option<&ExclusionRule> {
+        self.extensions.get(extension).map(|&index| &self.rules[index])
+    }
+
+    /// Все правила имен и путей, совпавшие с путем, от последнего к первому
+    /// (первое в результате побеждает).
+    pub fn path_matches(&self, name: &OsStr, relative: &Path) -> Vec<&ExclusionRule> {
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| rule.matches_path(name, relative))
+            .collect()
+    }
+
+    /// Все правила расширения, от последнего к первому.
+    pub fn extension_matches(&self, extension: &str) -> Vec<&ExclusionRule> {
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| matches!(&rule.target, RuleTarget::Extension(e) if e == extension))
+            .collect()
+    }
+}
 
 /// Управляет логикой исключения файлов и папок.
 ///
@@ -16,7 +353,14 @@ use std::path::Path;
 #[derive(Debug)]
 pub struct ExclusionManager {
     template_manager: TemplateManager,
-    enabled_templates: HashSet<String>,
+    /// Включенные шаблоны в порядке включения.
+    enabled_templates: Vec<String>,
+    /// Ключи в том виде, в котором их указал пользователь, если они отличаются.
+    requested_as: HashMap<String, String>,
+    /// Разобранные паттерны шаблонов: номер строки и паттерн.
+    parsed: HashMap<String, OnceLock<Vec<(usize, String)>>>,
+    /// Сколько раз разбиралось содержимое шаблонов.
+    parses: AtomicUsize,
 }
 
 impl ExclusionManager {
@@ -25,6 +369,9 @@ impl ExclusionManager {
     /// # Ошибки
     /// Возвращает ошибку, если не удается инициализировать `TemplateManager`.
     ///
+    /// Пустой кэш при этом не заполняется: нужные шаблоны догружает
+    /// `fetch_missing_template`, а полный список — `ensure_full_template_list`.
+    ///
     /// # Examples
     /// ```no_run
     /// # use flatten_rust::exclusions::ExclusionManager;
@@ -35,14 +382,41 @@ impl ExclusionManager {
     /// # Ok(())
     /// # }
     /// ```
+    #[cfg(feature = "network")]
     pub async fn new() -> Result<Self> {
         let mut template_manager = TemplateManager::new()?;
-        template_manager.update_if_needed().await?;
+        template_manager.refresh_if_stale().await?;
 
-        Ok(Self {
+        Ok(Self::with_template_manager(template_manager))
+    }
+
+    /// Создает `ExclusionManager` без обращения к сети.
+    ///
+    /// Используются только шаблоны из локального кэша `~/.flatten/`; если кэша
+    /// нет, доступны лишь пользовательские паттерны и встроенные исключения
+    /// `FlattenConfig`.
+    ///
+    /// # Ошибки
+    /// Возвращает ошибку, если не удается инициализировать `TemplateManager`.
+    pub fn offline() -> Result<Self> {
+        Ok(Self::with_template_manager(TemplateManager::new()?))
+    }
+
+    /// Создает `ExclusionManager` поверх готового `TemplateManager` (например, с
+    /// кэшем в другой директории).
+    pub fn with_template_manager(template_manager: TemplateManager) -> Self {
+        Self {
             template_manager,
-            enabled_templates: HashSet::new(),
-        })
+            enabled_templates: Vec::new(),
+            requested_as: HashMap::new(),
+            parsed: HashMap::new(),
+            parses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Менеджер шаблонов, из кэша которого берутся шаблоны.
+    pub fn template_manager(&self) -> &TemplateManager {
+        &self.template_manager
     }
     
     /// Автоматически включает шаблоны, релевантные для указанного проекта.
@@ -53,7 +427,7 @@ impl ExclusionManager {
         for (template_key, file_indicators) in detection_map {
             for indicator in file_indicators {
                 if project_path.join(indicator).exists() {
-                    self.enabled_templates.insert(template_key.to_string());
+                    self.insert_enabled(template_key.to_string());
                     break;
                 }
             }
@@ -73,79 +447,245 @@ impl ExclusionManager {
             ("php", vec!["composer.json"]),
         ]
     }
+
+    /// Возвращает встроенные синонимы ключей шаблонов.
+    ///
+    /// Дополняется полем `template_aliases` файла `~/.flatten/manager_config.json`.
+    fn get_alias_map() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("vscode", "visualstudiocode"),
+            ("code", "visualstudiocode"),
+            ("vs", "visualstudio"),
+            ("idea", "jetbrains+all"),
+            ("intellij", "jetbrains+all"),
+            ("js", "node"),
+            ("javascript", "node"),
+            ("ts", "node"),
+            ("typescript", "node"),
+            ("npm", "node"),
+            ("cpp", "c++"),
+            ("cxx", "c++"),
+            ("py", "python"),
+            ("golang", "go"),
+            ("rs", "rust"),
+            ("rb", "ruby"),
+            ("cs", "csharp"),
+            ("kt", "kotlin"),
+            ("osx", "macos"),
+        ]
+    }
+
+    /// Догружает шаблон `template_key`, если его нет в кэше и полный список
+    /// еще не загружался.
+    ///
+    /// Ключ сопоставляется только с синонимами. Если такого шаблона нет,
+    /// загружается полный список, чтобы `enable_template` мог подсказать ключи.
+    ///
+    /// # Ошибки
+    /// Если шаблон или список не удалось загрузить.
+    pub async fn fetch_missing_template(&mut self, template_key: &str) -> Result<()> {
+        let manager = &self.template_manager;
+        if !manager.get_available_templates().is_empty() && !manager.is_partial() {
+            return Ok(());
+        }
+        let key = resolve_template_key(template_key, &[], manager.template_aliases())?;
+        if !self.template_manager.fetch_template(&key).await? {
+            self.ensure_full_template_list().await?;
+        }
+        Ok(())
+    }
+
+    /// Загружает полный список шаблонов, если кэш пуст, частичен или устарел.
+    ///
+    /// # Ошибки
+    /// Если список не удалось загрузить.
+    pub async fn ensure_full_template_list(&mut self) -> Result<()> {
+        self.template_manager.update_if_needed().await?;
+        for cell in self.parsed.values_mut() {
+            cell.take();
+        }
+        Ok(())
+    }
+
+    /// Приводит ключ шаблона к каноническому виду.
+    ///
+    /// # Ошибки
+    /// Возвращает ошибку с подсказками, если шаблон не найден среди доступных.
+    /// Пока кэш шаблонов пуст, ключ проверить не с чем, и он принимается как есть.
+    pub fn resolve_template_key(&self, key: &str) -> Result<String> {
+        let available = self.template_manager.get_available_templates();
+        resolve_template_key(key, &available, self.template_manager.template_aliases())
+    }
     
     /// Возвращает все паттерны из включенных шаблонов.
     pub fn get_all_patterns(&self) -> Vec<String> {
-        let mut patterns = Vec::new();
-        for key in &self.enabled_templates {
-            if let Some(contents) = self.template_manager.get_template_contents(key) {
-                patterns.extend(Self::parse_ignore_patterns(contents));
-            }
-        }
-        patterns
+        self.enabled_templates
+            .iter()
+            .flat_map(|key| self.template_patterns(key))
+            .map(|(_, pattern)| pattern.clone())
+            .collect()
+    }
+
+    /// Возвращает разобранные паттерны включенного шаблона, разбирая его при первом обращении.
+    fn template_patterns(&self, key: &str) -> &[(usize, String)] {
+        let Some(cell) = self.parsed.get(key) else {
+            return &[];
+        };
+        cell.get_or_init(|| {
+            self.parses.fetch_add(1, Ordering::Relaxed);
+            self.template_manager
+                .get_template_contents(key)
+                .map(Self::parse_numbered_patterns)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Сколько раз за время жизни менеджера разбиралось содержимое шаблонов.
+    #[cfg(test)]
+    fn parse_count(&self) -> usize {
+        self.parses.load(Ordering::Relaxed)
     }
 
     /// Парсит содержимое шаблона, возвращая список паттернов.
+    #[cfg(test)]
     fn parse_ignore_patterns(content: &str) -> Vec<String> {
+        Self::parse_numbered_patterns(content)
+            .into_iter()
+            .map(|(_, pattern)| pattern)
+            .collect()
+    }
+
+    /// Парсит содержимое шаблона, возвращая паттерны с номерами строк (с 1).
+    fn parse_numbered_patterns(content: &str) -> Vec<(usize, String)> {
         content
             .lines()
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty() && !s.starts_with('#'))
-            .map(|s| s.to_string())
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, s)| !s.is_empty() && !s.starts_with('#'))
+            .map(|(line, s)| (line, s.to_string()))
             .collect()
     }
     
-    /// Возвращает набор паттернов для исключения папок.
-    pub async fn get_folder_patterns(&self) -> HashSet<String> {
-        self.get_all_patterns()
-            .iter()
-            .filter_map(|p| Self::extract_folder_name(p))
-            .collect()
+    /// Возвращает паттерны исключения папок с их происхождением.
+    ///
+    /// Паттерны идут в порядке включения шаблонов; паттерн из нескольких
+    /// шаблонов повторяется с каждым источником, поэтому происхождение не
+    /// теряется при объединении.
+    pub async fn get_folder_patterns(&self) -> Vec<(String, PatternOrigin)> {
+        self.extracted_patterns(Self::extract_folder_name)
     }
 
-    /// Возвращает набор паттернов для исключения файлов по расширению.
-    pub async fn get_extension_patterns(&self) -> HashSet<String> {
-        self.get_all_patterns()
-            .iter()
-            .filter_map(|p| Self::extract_extension(p))
+    /// Возвращает паттерны исключения файлов по расширению с их
+    /// происхождением (см. `get_folder_patterns`).
+    pub async fn get_extension_patterns(&self) -> Vec<(String, PatternOrigin)> {
+        self.extracted_patterns(Self::extract_extension)
+    }
+
+    /// Извлекает из паттернов шаблонов значения `extract` вместе с их строками.
+    fn extracted_patterns(
+        &self,
+        extract: fn(&str) -> Option<String>,
+    ) -> Vec<(String, PatternOrigin)> {
+        self.pattern_sources()
+            .into_iter()
+            .filter_map(|source| {
+                let value = extract(&source.pattern)?;
+                Some((value, PatternOrigin::Template(source)))
+            })
             .collect()
     }
 
     /// Извлекает имя папки из паттерна.
-    fn extract_folder_name(pattern: &str) -> Option<String> {
+    ///
+    /// Возвращающие паттерны (`!dist`) именем папки не считаются.
+    pub(crate) fn extract_folder_name(pattern: &str) -> Option<String> {
         let p = pattern.trim_end_matches('/');
-        if !p.contains('*') && !p.contains('.') {
+        if !p.is_empty() && !p.contains('*') && !p.contains('.') && !p.starts_with('!') {
             return Some(p.to_string());
         }
         None
     }
 
     /// Извлекает расширение файла из паттерна.
-    fn extract_extension(pattern: &str) -> Option<String> {
-        if pattern.starts_with("*.") {
-            return Some(pattern.trim_start_matches("*.").to_string());
+    ///
+    /// Составные расширения (`*.rs.bk`, `*.py[cod]`, `*.egg-info/`) простыми не
+    /// считаются: они применяются как `RuleTarget::Glob`.
+    pub(crate) fn extract_extension(pattern: &str) -> Option<String> {
+        let extension = pattern.strip_prefix("*.")?;
+        if extension.is_empty() || extension.contains(['.', '/']) || has_glob_syntax(extension) {
+            return None;
         }
-        None
+        Some(extension.to_string())
     }
 
-    /// Возвращает список включенных шаблонов.
+    /// Возвращает все паттерны включенных шаблонов вместе с их источниками.
+    ///
+    /// Порядок стабилен: шаблоны в порядке включения, затем по номеру строки.
+    /// Более поздние паттерны сильнее более ранних.
+    pub fn pattern_sources(&self) -> Vec<PatternSource> {
+        let mut sources = Vec::new();
+        for key in &self.enabled_templates {
+            for (line, pattern) in self.template_patterns(key) {
+                sources.push(PatternSource {
+                    template: key.clone(),
+                    line: *line,
+                    pattern: pattern.clone(),
+                });
+            }
+        }
+        sources
+    }
+
+    /// Возвращает список включенных шаблонов в порядке включения.
     pub fn get_enabled_templates(&self) -> Vec<&str> {
         self.enabled_templates.iter().map(|s| s.as_str()).collect()
     }
 
-    /// Включает шаблон по ключу.
-    pub fn enable_template(&mut self, template_key: String) {
-        self.enabled_templates.insert(template_key);
+    /// Возвращает ключ в том виде, в котором его указал пользователь, если он отличается.
+    pub fn requested_as(&self, template_key: &str) -> Option<&str> {
+        self.requested_as.get(template_key).map(String::as_str)
+    }
+
+    /// Включает шаблон по ключу и возвращает канонический ключ.
+    ///
+    /// # Ошибки
+    /// Возвращает ошибку, если ключ не удалось сопоставить ни с одним шаблоном.
+    pub fn enable_template(&mut self, template_key: &str) -> Result<String> {
+        let key = self.resolve_template_key(template_key)?;
+        if key != template_key {
+            self.requested_as.insert(key.clone(), template_key.to_string());
+        }
+        self.insert_enabled(key.clone());
+        Ok(key)
+    }
+
+    /// Добавляет ключ во включенные шаблоны, заводя для него место в кэше разбора.
+    ///
+    /// Повторное включение не меняет позицию шаблона.
+    fn insert_enabled(&mut self, key: String) {
+        self.parsed.entry(key.clone()).or_default();
+        if !self.enabled_templates.contains(&key) {
+            self.enabled_templates.push(key);
+        }
     }
 
-    /// Отключает шаблон по ключу.
+    /// Отключает шаблон по ключу; неизвестный ключ ничего не меняет.
     pub fn disable_template(&mut self, template_key: &str) {
-        self.enabled_templates.remove(template_key);
+        let key = self
+            .resolve_template_key(template_key)
+            .unwrap_or_else(|_| template_key.to_lowercase());
+        self.enabled_templates.retain(|enabled| *enabled != key);
     }
 
     /// Принудительно обновляет шаблоны через `TemplateManager`.
+    ///
+    /// Разобранные паттерны сбрасываются: содержимое шаблонов могло измениться.
     pub async fn force_update_templates(&mut self) -> Result<()> {
-        self.template_manager.force_update().await
+        self.template_manager.force_update().await?;
+        for cell in self.parsed.values_mut() {
+            cell.take();
+        }
+        Ok(())
     }
 
     /// Возвращает список всех доступных шаблонов.
@@ -154,9 +694,79 @@ impl ExclusionManager {
     }
 }
 
+/// Сопоставляет ключ с доступными шаблонами с учетом регистра и синонимов.
+///
+/// Синонимы из `aliases` сильнее встроенных. Пустой `available` означает, что
+/// список шаблонов неизвестен, и ключ возвращается без проверки.
+pub fn resolve_template_key(
+    key: &str,
+    available: &[String],
+    aliases: &BTreeMap<String, String>,
+) -> Result<String> {
+    let folded = key.trim().to_lowercase();
+    if available.contains(&folded) {
+        return Ok(folded);
+    }
+    let alias = aliases
+        .iter()
+        .find(|(alias, _)| alias.to_lowercase() == folded)
+        .map(|(_, target)| target.to_lowercase())
+        .or_else(|| {
+            ExclusionManager::get_alias_map()
+                .into_iter()
+                .find(|(alias, _)| *alias == folded)
+                .map(|(_, target)| target.to_string())
+        });
+    match alias {
+        Some(target) if available.is_empty() || available.contains(&target) => Ok(target),
+        _ if available.is_empty() => Ok(folded),
+        _ => bail!(
+            "Unknown template '{}'. Did you mean: {}? Use --list-templates to see all keys",
+            key,
+            suggest_templates(&folded, available, 3).join(", ")
+        ),
+    }
+}
+
+/// Возвращает до `limit` ключей, ближайших к `query`.
+///
+/// Сначала идут ключи, содержащие `query` (или содержащиеся в нем), затем
+/// остальные; внутри групп — по расстоянию Левенштейна и по алфавиту.
+pub fn suggest_templates(query: &str, available: &[String], limit: usize) -> Vec<String> {
+    let mut ranked: Vec<(bool, usize, &String)> = available
+        .iter()
+        .map(|candidate| {
+            let related = candidate.contains(query) || query.contains(candidate.as_str());
+            (!related, edit_distance(query, candidate), candidate)
+        })
+        .collect();
+    ranked.sort();
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(_, _, candidate)| candidate.clone())
+        .collect()
+}
+
+/// Расстояние Левенштейна между строками (по символам).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, left) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, right) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left != *right);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{FetchFuture, TemplateFetcher, TemplateFuture, ToptalEntry};
 
     #[test]
     fn test_parse_ignore_patterns() {
@@ -170,12 +780,161 @@ file.txt
         assert_eq!(patterns, vec!["target/", "*.log", "file.txt"]);
     }
 
+    /// Источник, всегда отдающий шаблоны `rust` и `node`.
+    struct StaticFetcher;
+
+    impl TemplateFetcher for StaticFetcher {
+        fn fetch_list(&self) -> FetchFuture<'_> {
+            Box::pin(async {
+                Ok([
+                    ("rust", "target/\n*.rs.bk\n"),
+                    ("node", "# deps\nnode_modules/\n*.log\n"),
+                    ("python", "__pycache__/\n*.log\n"),
+                ]
+                .into_iter()
+                    .map(|(key, contents)| {
+                        let entry = ToptalEntry {
+                            name: key.to_string(),
+                            contents: contents.to_string(),
+                        };
+                        (key.to_string(), entry)
+                    })
+                    .collect())
+            })
+        }
+
+        fn fetch_template(&self, key: &str) -> TemplateFuture<'_> {
+            let key = key.to_string();
+            Box::pin(async move { Ok((key == "rust").then(|| "target/\n".to_string())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enable_template_on_cold_cache() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut manager = ExclusionManager::with_template_manager(TemplateManager::with_fetcher(
+            temp_dir.path(),
+            StaticFetcher,
+        )?);
+        manager.fetch_missing_template("RS").await?;
+        assert_eq!(manager.enable_template("RS")?, "rust");
+        assert!(manager.template_manager().is_partial());
+        assert_eq!(manager.get_available_templates().await, vec!["rust".to_string()]);
+        assert_eq!(manager.get_all_patterns(), vec!["target/"]);
+
+        // Для неизвестного ключа загружается полный список с подсказками.
+        manager.fetch_missing_template("pyton").await?;
+        assert!(!manager.template_manager().is_partial());
+        let error = manager.enable_template("pyton").unwrap_err();
+        assert!(error.to_string().contains("Did you mean: python"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_templates_parsed_once() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut manager = ExclusionManager::with_template_manager(TemplateManager::with_fetcher(
+            temp_dir.path(),
+            StaticFetcher,
+        )?);
+        manager.force_update_templates().await?;
+        manager.enable_template("rust")?;
+        manager.enable_template("node")?;
+        assert_eq!(manager.parse_count(), 0);
+
+        for _ in 0..2 {
+            let folders = manager.get_folder_patterns().await;
+            let extensions = manager.get_extension_patterns().await;
+            let names = |patterns: &[(String, PatternOrigin)]| -> Vec<String> {
+                patterns.iter().map(|(name, _)| name.clone()).collect()
+            };
+            assert_eq!(names(&folders), vec!["target", "node_modules"]);
+            assert_eq!(names(&extensions), vec!["log"]);
+            let lines: Vec<usize> = manager.pattern_sources().iter().map(|source| source.line).collect();
+            assert_eq!(lines, vec![1, 2, 2, 3]);
+        }
+        assert_eq!(manager.parse_count(), 2);
+
+        manager.force_update_templates().await?;
+        manager.pattern_sources();
+        assert_eq!(manager.parse_count(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pattern_origins_survive_merge() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut manager = ExclusionManager::with_template_manager(TemplateManager::with_fetcher(
+            temp_dir.path(),
+            StaticFetcher,
+        )?);
+        manager.force_update_templates().await?;
+        manager.enable_template("node")?;
+        manager.enable_template("python")?;
+
+        let origins: Vec<(String, String, usize)> = manager
+            .get_extension_patterns()
+            .await
+            .into_iter()
+            .map(|(extension, origin)| match origin {
+                PatternOrigin::Template(source) => (extension, source.template, source.line),
+                other => panic!("unexpected origin {}", other),
+            })
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                ("log".to_string(), "node".to_string(), 3),
+                ("log".to_string(), "python".to_string(), 2),
+            ]
+        );
+        let folders = manager.get_folder_patterns().await;
+        assert_eq!(folders[1].0, "__pycache__");
+        assert_eq!(folders[1].1.to_string(), "template 'python', pattern `__pycache__/` from line 1");
+        Ok(())
+    }
+
     #[test]
     fn test_extract_folder_name() {
         assert_eq!(ExclusionManager::extract_folder_name("target/"), Some("target".to_string()));
         assert_eq!(ExclusionManager::extract_folder_name("node_modules"), Some("node_modules".to_string()));
         assert_eq!(ExclusionManager::extract_folder_name("*.log"), None);
         assert_eq!(ExclusionManager::extract_folder_name("file.txt"), None);
+        assert_eq!(ExclusionManager::extract_folder_name("!dist"), None);
+    }
+
+    fn template_rule(template: &str, line: usize, pattern: &str) -> ExclusionRule {
+        let source = PatternSource {
+            template: template.to_string(),
+            line,
+            pattern: pattern.to_string(),
+        };
+        ExclusionRule::from_template(source).unwrap().expect("pattern is applied")
+    }
+
+    #[test]
+    fn test_rule_list_last_match_wins() {
+        let mut rules = RuleList::default();
+        rules.push(template_rule("web", 1, "dist/"));
+        rules.push(template_rule("web", 2, "*.log"));
+        rules.push(template_rule("docs", 4, "!/dist"));
+        rules.push(template_rule("docs", 5, "!*.log"));
+        assert!(ExclusionRule::from_template(PatternSource {
+            template: "docs".to_string(),
+            line: 6,
+            pattern: "!file.txt".to_string(),
+        })
+        .unwrap()
+        .is_none());
+
+        let top = rules.path_rule(OsStr::new("dist"), Path::new("dist")).unwrap();
+        assert!(top.negated);
+        assert_eq!(top.pattern(), "!/dist");
+        let nested = rules.path_rule(OsStr::new("dist"), Path::new("app/dist")).unwrap();
+        assert!(!nested.negated);
+        assert!(rules.extension_rule("log").unwrap().negated);
+        assert_eq!(rules.path_matches(OsStr::new("dist"), Path::new("dist")).len(), 2);
+        assert_eq!(rules.extension_matches("log")[0].pattern(), "!*.log");
     }
 
     #[test]
@@ -184,5 +943,102 @@ file.txt
         assert_eq!(ExclusionManager::extract_extension("*.pyc"), Some("pyc".to_string()));
         assert_eq!(ExclusionManager::extract_extension("target/"), None);
         assert_eq!(ExclusionManager::extract_extension("file.txt"), None);
+        assert_eq!(ExclusionManager::extract_extension("*.rs.bk"), None);
+        assert_eq!(ExclusionManager::extract_extension("*.py[cod]"), None);
+        assert_eq!(ExclusionManager::extract_extension("*.egg-info/"), None);
+    }
+
+    #[test]
+    fn test_compound_template_patterns() -> Result<()> {
+        let mut rules = RuleList::default();
+        for (line, pattern) in ["build/*.o", "**/*.orig", ".vscode/*", "docs/**/*.pdf", "*.py[cod]"]
+            .into_iter()
+            .enumerate()
+        {
+            let rule = template_rule("mixed", line + 1, pattern);
+            assert!(matches!(rule.target, RuleTarget::Glob(_)), "{}", pattern);
+            assert_eq!(rule.pattern(), pattern);
+            rules.push(rule);
+        }
+        rules.push(template_rule("mixed", 6, "!build/keep.o"));
+
+        let excluded = |relative: &str| {
+            let relative = Path::new(relative);
+            let name = relative.file_name().expect("path has a name");
+            rules.path_rule(name, relative).is_some_and(|rule| !rule.negated)
+        };
+        assert!(excluded("build/main.o"));
+        assert!(!excluded("build/keep.o"));
+        assert!(!excluded("src/build/main.o"));
+        assert!(!excluded("build/main.c"));
+        assert!(excluded("merge.orig"));
+        assert!(excluded("src/deep/merge.orig"));
+        assert!(excluded(".vscode/settings.json"));
+        assert!(!excluded(".vscode"));
+        assert!(excluded("docs/manual.pdf"));
+        assert!(excluded("docs/guide/v2/manual.pdf"));
+        assert!(!excluded("assets/docs/manual.pdf"));
+        assert!(excluded("pkg/module.pyc"));
+        assert!(!excluded("pkg/module.py"));
+
+        // Простые паттерны по-прежнему идут через таблицы имен и расширений.
+        assert!(matches!(RuleTarget::from_template("target/")?, Some(RuleTarget::Name(_))));
+        assert!(matches!(RuleTarget::from_template("*.log")?, Some(RuleTarget::Extension(_))));
+        assert!(RuleTarget::from_template("pip-log.txt")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_pattern_matching() -> Result<()> {
+        assert!(PathPattern::is_path_pattern("/build"));
+        assert!(PathPattern::is_path_pattern("docs/_site/"));
+        assert!(!PathPattern::is_path_pattern("build/"));
+
+        let top_level = PathPattern::new("/build")?;
+        assert!(top_level.matches(Path::new("build")));
+        assert!(!top_level.matches(Path::new("src/build")));
+
+        let nested = PathPattern::new("services/*/generated")?;
+        assert!(nested.matches(Path::new("services/api/generated")));
+        assert!(!nested.matches(Path::new("services/api/v1/generated")));
+        assert_eq!(nested.as_str(), "services/*/generated");
+        Ok(())
+    }
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_template_key_resolution() -> Result<()> {
+        let available = keys(&["visualstudiocode", "node", "c++", "rust", "jetbrains+all"]);
+        let no_aliases = BTreeMap::new();
+        assert_eq!(resolve_template_key("VSCode", &available, &no_aliases)?, "visualstudiocode");
+        assert_eq!(resolve_template_key("Rust", &available, &no_aliases)?, "rust");
+        assert_eq!(resolve_template_key("js", &available, &no_aliases)?, "node");
+        assert_eq!(resolve_template_key("CPP", &available, &no_aliases)?, "c++");
+
+        let aliases = BTreeMap::from([("RS".to_string(), "c++".to_string())]);
+        assert_eq!(resolve_template_key("rs", &available, &aliases)?, "c++");
+
+        // Без списка шаблонов ключ только приводится к нижнему регистру.
+        assert_eq!(resolve_template_key("Kotlin", &[], &no_aliases)?, "kotlin");
+        assert_eq!(resolve_template_key("vscode", &[], &no_aliases)?, "visualstudiocode");
+
+        let error = resolve_template_key("jetbrain", &available, &no_aliases).unwrap_err();
+        assert!(error.to_string().starts_with(
+            "Unknown template 'jetbrain'. Did you mean: jetbrains+all, rust, c++?"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_suggestion_ranking() {
+        let available = keys(&["ruby", "rust", "python", "go", "rust-analyzer"]);
+        assert_eq!(suggest_templates("rst", &available, 3), vec!["rust", "go", "ruby"]);
+        assert_eq!(suggest_templates("rust", &available, 2), vec!["rust", "rust-analyzer"]);
+        assert_eq!(suggest_templates("pyhton", &available, 1), vec!["python"]);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
     }
 }
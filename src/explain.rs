@@ -0,0 +1,666 @@
+//! Модуль объяснения решений о включении файлов (`--explain`).
+//!
+//! Для заданного пути последовательно применяет те же правила, что и обход
+//! директорий, и выводит вердикт каждого правила вместе с его источником,
+//! а также все совпавшие правила исключений по старшинству с отметкой
+//! победившего. Также выводит итоговый набор правил исключений
+//! (`--print-exclusions`) в порядке применения.
+
+use crate::FlattenConfig;
+use crate::pipeline::is_generated;
+use crate::exclusions::{ExclusionRule, PatternOrigin, PatternSource, RuleList, RuleTarget};
+use crate::flattenignore::IgnoreVerdict;
+use crate::output::OutputFormat;
+use crate::report::{SkipReason, format_size};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Вердикт одного правила.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleVerdict {
+    /// Имя правила (например, `"hidden"`).
+    pub rule: &'static str,
+    /// Исключает ли правило путь.
+    pub excluded: bool,
+    /// Пояснение с источником правила.
+    pub detail: String,
+}
+
+/// Результат объяснения для одного пути.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    /// Объясняемый путь.
+    pub path: PathBuf,
+    /// Базовая папка, содержащая путь (если найдена).
+    pub base_folder: Option<PathBuf>,
+    /// Вердикты правил в порядке их применения.
+    pub verdicts: Vec<RuleVerdict>,
+    /// Совпавшие правила исключений для компонентов пути и расширения файла.
+    pub matches: Vec<RuleMatches>,
+}
+
+/// Правило исключения, совпавшее с путем.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatch {
+    /// Слой правила: `command line`, `.flattenignore`, источник правил git
+    /// (`.gitignore`, `info/exclude`, `global excludes`), `template` или `default`.
+    pub layer: &'static str,
+    /// Правило в исходном виде.
+    pub pattern: String,
+    /// Откуда взято правило (шаблон и строка, файл и строка).
+    pub source: String,
+    /// Исключает ли правило путь (`false` — возвращает его).
+    pub excludes: bool,
+}
+
+/// Все правила, совпавшие с одним компонентом пути или расширением файла.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatches {
+    /// Что сопоставлялось: компонент пути (`dist`) или расширение (`*.log`).
+    pub subject: String,
+    /// Совпавшие правила от старшего к младшему; первое побеждает.
+    pub rules: Vec<RuleMatch>,
+}
+
+impl Explanation {
+    /// Попадает ли содержимое файла в вывод.
+    pub fn is_included(&self) -> bool {
+        self.base_folder.is_some() && self.verdicts.iter().all(|v| !v.excluded)
+    }
+
+    /// Выводит объяснение в консоль.
+    pub fn print(&self) {
+        println!("{}", self.path.display());
+        match &self.base_folder {
+            Some(base) => println!("  base folder: {}", base.display()),
+            None => println!("  [excluded] path is not under any base folder"),
+        }
+        for verdict in &self.verdicts {
+            let mark = if verdict.excluded { "excluded" } else { "pass" };
+            println!("  [{}] {}: {}", mark, verdict.rule, verdict.detail);
+        }
+        for matches in &self.matches {
+            println!("  rules matching `{}` (strongest first):", matches.subject);
+            for (index, rule) in matches.rules.iter().enumerate() {
+                let mark = if index == 0 { "wins" } else { "overridden" };
+                let effect = if rule.excludes { "exclude" } else { "include" };
+                println!(
+                    "    [{}] {} `{}` from {} ({})",
+                    mark, effect, rule.pattern, rule.layer, rule.source
+                );
+            }
+        }
+        let result = if self.is_included() {
+            "INCLUDED"
+        } else {
+            "EXCLUDED"
+        };
+        println!("  => {}", result);
+    }
+}
+
+/// Объясняет, почему путь включается в вывод или исключается из него.
+pub fn explain_path(path: &Path, folders: &[PathBuf], config: &FlattenConfig) -> Explanation {
+    let absolute = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let base_folder = folders.iter().find_map(|folder| {
+        let canonical = folder.canonicalize().ok()?;
+        absolute.starts_with(&canonical).then_some(canonical)
+    });
+
+    let mut explanation = Explanation {
+        path: path.to_path_buf(),
+        base_folder: base_folder.clone(),
+        verdicts: Vec::new(),
+        matches: Vec::new(),
+    };
+    let Some(base) = base_folder else {
+        return explanation;
+    };
+    let config = config.for_folder(&base);
+    explanation.matches = ancestors_matches(&base, &absolute, config);
+    let verdicts = &mut explanation.verdicts;
+
+    // Те же проверки, что и при обходе: для каждого пути ниже базовой папки
+    // до самого файла. Сама базовая папка указана явно и не проверяется.
+    let relative = absolute.strip_prefix(&base).unwrap_or(Path::new(""));
+    let depth = relative.components().count();
+    let mut current = base.clone();
+    let mut ancestors = vec![current.clone()];
+    for component in relative.components() {
+        current.push(component);
+        ancestors.push(current.clone());
+    }
+
+    let mut walk_verdict = None;
+    for ancestor in ancestors.iter().skip(1) {
+        if let Some(reason) = config.path_skip_reason(&base, ancestor) {
+            let name = ancestor
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let verdict = match reason {
+                SkipReason::FlattenIgnore => config.flattenignore_verdict(&base, ancestor),
+                SkipReason::GitIgnore => config.gitignore_verdict(&base, ancestor),
+                _ => None,
+            };
+            let detail = match verdict {
+                Some(verdict) => format!(
+                    "`{}` excluded by `{}` from {}:{}",
+                    name,
+                    verdict.pattern,
+                    verdict.source.display(),
+                    verdict.line
+                ),
+                None => describe_path_skip(reason, &name, config.path_rule(&base, ancestor)),
+            };
+            walk_verdict = Some(RuleVerdict {
+                rule: reason.as_str(),
+                excluded: true,
+                detail,
+            });
+            break;
+        }
+    }
+    verdicts.push(walk_verdict.unwrap_or(RuleVerdict {
+        rule: "folders",
+        excluded: false,
+        detail: "no hidden or skipped path components".to_string(),
+    }));
+
+    if config.max_depth > 0 {
+        verdicts.push(RuleVerdict {
+            rule: "max-depth",
+            excluded: depth > config.max_depth,
+            detail: format!("depth {} (limit {})", depth, config.max_depth),
+        });
+    }
+
+    if absolute.is_dir() {
+        return explanation;
+    }
+
+    match config.file_skip_reason(&base, &absolute) {
+        Some(SkipReason::Lockfile) => {
+            verdicts.push(RuleVerdict {
+                rule: "lockfile",
+                excluded: true,
+                detail: "lockfile replaced by a placeholder (use --keep-lockfiles)".to_string(),
+            });
+        }
+        Some(SkipReason::Symlink) => {
+            verdicts.push(RuleVerdict {
+                rule: "symlink",
+                excluded: true,
+                detail: "symlink replaced by a placeholder with its target (--file-modes)"
+                    .to_string(),
+            });
+        }
+        Some(_) => {
+            let extension = absolute
+                .extension()
+                .map(|e| e.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let origin = describe_origin(
+                "--skip-extensions",
+                config.extension_rule(&absolute).map(|rule| &rule.origin),
+            );
+            verdicts.push(RuleVerdict {
+                rule: "extension",
+                excluded: true,
+                detail: format!(
+                    "extension `{}` excluded by {} (listed as a binary placeholder)",
+                    extension, origin
+                ),
+            });
+        }
+        None => {
+            let extension = absolute
+                .extension()
+                .map(|e| e.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let detail = match config.extension_rule(&absolute) {
+                Some(rule) if rule.negated => format!(
+                    "extension `{}` re-included by {}",
+                    extension,
+                    describe_origin("--include-extension", Some(&rule.origin))
+                ),
+                _ => "extension is not in the skip list".to_string(),
+            };
+            verdicts.push(RuleVerdict {
+                rule: "extension",
+                excluded: false,
+                detail,
+            });
+        }
+    }
+
+    if config.skip_generated
+        && let Ok(content) = std::fs::read_to_string(&absolute)
+    {
+        let generated = is_generated(&content);
+        verdicts.push(RuleVerdict {
+            rule: "generated",
+            excluded: generated,
+            detail: if generated {
+                "generated-file marker in the first lines (--skip-generated)".to_string()
+            } else {
+                "no generated-file marker".to_string()
+            },
+        });
+    }
+
+    if let Ok(metadata) = std::fs::metadata(&absolute) {
+        let size = metadata.len();
+        let too_large = config.max_file_size > 0 && size > config.max_file_size;
+        let limit = if config.max_file_size > 0 {
+            format_size(config.max_file_size)
+        } else {
+            "unlimited".to_string()
+        };
+        verdicts.push(RuleVerdict {
+            rule: "size",
+            excluded: too_large,
+            detail: format!("{} (limit {})", format_size(size), limit),
+        });
+    }
+
+    explanation
+}
+
+/// Описывает, каким правилом и откуда исключен компонент пути.
+fn describe_path_skip(reason: SkipReason, name: &str, rule: Option<&ExclusionRule>) -> String {
+    match reason {
+        SkipReason::Hidden => format!(
+            "`{}` is hidden (use --include-hidden or --include-hidden-names)",
+            name
+        ),
+        SkipReason::FlattenIgnore => {
+            format!("`{}` is the project ignore file (use --no-flattenignore)", name)
+        }
+        SkipReason::Submodule => format!(
+            "`{}` is a nested git repository (use --submodules include)",
+            name
+        ),
+        SkipReason::Vendored => format!(
+            "`{}` is vendored third-party code (use --vendored include or --keep)",
+            name
+        ),
+        SkipReason::Crate => format!("`{}` is a workspace crate excluded by --exclude-crate", name),
+        _ => {
+            let origin = describe_origin("--skip-folders", rule.map(|rule| &rule.origin));
+            match rule.map(|rule| rule.pattern()) {
+                Some(pattern) if pattern != name => {
+                    format!("`{}` matches `{}` excluded by {}", name, pattern, origin)
+                }
+                _ => format!("`{}` excluded by {}", name, origin),
+            }
+        }
+    }
+}
+
+/// Собирает совпавшие правила для каждого компонента пути ниже `base` и для
+/// расширения файла; компоненты без совпадений пропускаются.
+fn ancestors_matches(base: &Path, absolute: &Path, config: &FlattenConfig) -> Vec<RuleMatches> {
+    let relative = absolute.strip_prefix(base).unwrap_or(Path::new(""));
+    let mut current = base.to_path_buf();
+    let mut result = Vec::new();
+    for component in relative.components() {
+        current.push(component);
+        let name = component.as_os_str();
+        let relative = current.strip_prefix(base).unwrap_or(&current);
+        let mut project: Vec<_> = config
+            .flatten_ignore
+            .as_ref()
+            .map(|ignore| ignore.matches(base, &current))
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .map(|verdict| (".flattenignore", verdict))
+            .collect();
+        if let Some(git_ignore) = &config.git_ignore {
+            let git = git_ignore.matches(base, &current).into_iter().rev();
+            project.extend(git.map(|(source, verdict)| (source.as_str(), verdict)));
+        }
+        let rules = layered_matches(
+            config,
+            |rules| rules.path_matches(name, relative),
+            project.into_iter(),
+        );
+        if !rules.is_empty() {
+            result.push(RuleMatches {
+                subject: relative.display().to_string(),
+                rules,
+            });
+        }
+    }
+    if let Some(extension) = absolute
+        .is_file()
+        .then(|| absolute.extension().and_then(|e| e.to_str()))
+        .flatten()
+    {
+        // Для расширений `.flattenignore` и git важен только возврат (`!`):
+        // исключение по имени уже решено на уровне пути.
+        let project = match config.flattenignore_verdict(base, absolute) {
+            Some(verdict) => Some((".flattenignore", verdict)),
+            None => config
+                .git_ignore
+                .as_ref()
+                .and_then(|ignore| ignore.matches(base, absolute).pop())
+                .map(|(source, verdict)| (source.as_str(), verdict)),
+        }
+        .filter(|(_, verdict)| !verdict.ignored);
+        let rules = layered_matches(
+            config,
+            |rules| rules.extension_matches(extension),
+            project.into_iter(),
+        );
+        if !rules.is_empty() {
+            result.push(RuleMatches {
+                subject: format!("*.{}", extension),
+                rules,
+            });
+        }
+    }
+    result
+}
+
+/// Объединяет совпадения слоев по старшинству: командная строка, `.flattenignore`
+/// и правила git (уже от сильного правила к слабому, с названием слоя), шаблоны,
+/// умолчания.
+fn layered_matches<'a>(
+    config: &'a FlattenConfig,
+    layer_matches: impl Fn(&'a RuleList) -> Vec<&'a ExclusionRule>,
+    project: impl Iterator<Item = (&'static str, IgnoreVerdict)>,
+) -> Vec<RuleMatch> {
+    let from_rules = |rules: Vec<&ExclusionRule>| -> Vec<RuleMatch> {
+        rules
+            .into_iter()
+            .map(|rule| RuleMatch {
+                layer: rule.origin.layer(),
+                pattern: rule.pattern(),
+                source: describe_source(&rule.origin),
+                excludes: !rule.negated,
+            })
+            .collect()
+    };
+    let mut matches = from_rules(layer_matches(&config.user_rules));
+    matches.extend(project.map(|(layer, verdict)| RuleMatch {
+        layer,
+        pattern: verdict.pattern.clone(),
+        source: format!("{}:{}", verdict.source.display(), verdict.line),
+        excludes: verdict.ignored,
+    }));
+    matches.extend(from_rules(layer_matches(&config.template_rules)));
+    matches.extend(from_rules(layer_matches(&config.default_rules)));
+    matches
+}
+
+/// Краткий источник правила для списков правил.
+fn describe_source(origin: &PatternOrigin) -> String {
+    match origin {
+        PatternOrigin::Default => "built-in defaults".to_string(),
+        PatternOrigin::UserFlag => "flags".to_string(),
+        PatternOrigin::Template(source) => format!("'{}' line {}", source.template, source.line),
+    }
+}
+
+/// Описывает происхождение правила: флаг командной строки или шаблон.
+fn describe_origin(flag: &str, origin: Option<&PatternOrigin>) -> String {
+    match origin {
+        Some(PatternOrigin::Default) => format!("{} (default)", flag),
+        Some(origin @ PatternOrigin::Template(_)) => origin.to_string(),
+        _ => flag.to_string(),
+    }
+}
+
+/// Элемент набора исключений вместе с его происхождением.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExclusionEntry {
+    /// Имя папки или расширение.
+    pub value: String,
+    /// Происхождение правила.
+    pub origin: String,
+}
+
+/// Паттерн включенного шаблона и то, как он применяется.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplatePatternEntry {
+    /// Ключ шаблона.
+    pub template: String,
+    /// Номер строки в шаблоне (с единицы).
+    pub line: usize,
+    /// Паттерн в исходном виде.
+    pub pattern: String,
+    /// Как паттерн применяется: `folder`, `extension` или `ignored`.
+    pub applied_as: &'static str,
+}
+
+/// Правило исключения в итоговом упорядоченном списке.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedRuleEntry {
+    /// Слой правила: `default`, `template` или `command line`.
+    pub layer: &'static str,
+    /// Правило в исходном виде (`!` у возвращающих правил).
+    pub pattern: String,
+    /// `exclude` или `include`.
+    pub effect: &'static str,
+    /// Происхождение правила.
+    pub origin: String,
+    /// Перекрыто ли правило более поздним правилом для того же паттерна.
+    pub overridden: bool,
+}
+
+/// Итоговые правила исключений после объединения всех источников.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExclusionsSummary {
+    /// Все правила в порядке применения, от слабого к сильному: умолчания,
+    /// шаблоны в порядке включения, командная строка. Побеждает последнее
+    /// совпавшее правило; `.flattenignore` стоит между шаблонами и командной
+    /// строкой и читается во время обхода.
+    pub rules: Vec<ResolvedRuleEntry>,
+    /// Пропускаемые папки, по алфавиту.
+    pub skip_folders: Vec<ExclusionEntry>,
+    /// Пропускаемые расширения, по алфавиту.
+    pub skip_extensions: Vec<ExclusionEntry>,
+    /// Паттерны включенных шаблонов в порядке включения и по номеру строки.
+    pub template_patterns: Vec<TemplatePatternEntry>,
+    /// Паттерны `--keep`: пути, которые никогда не пропускаются, по алфавиту.
+    pub include_globs: Vec<String>,
+    /// Расширения, возвращенные флагом `--include-extension`, по алфавиту.
+    pub include_extensions: Vec<String>,
+    /// Максимальный размер файла в байтах (0 — без ограничения).
+    pub max_file_size: u64,
+    /// Максимальная глубина обхода (0 — без ограничения).
+    pub max_depth: usize,
+    /// Включаются ли скрытые файлы и папки.
+    pub include_hidden: bool,
+    /// Скрытые имена, включаемые без `--include-hidden`, по алфавиту.
+    pub include_hidden_names: Vec<String>,
+    /// Имена файлов, заменяемых заглушкой (lock-файлы), по алфавиту.
+    pub skip_files: Vec<String>,
+    /// Заменяются ли заглушкой файлы с маркерами генерации.
+    pub skip_generated: bool,
+}
+
+/// Собирает итоговые правила исключений в стабильном порядке.
+pub fn exclusions_summary(config: &FlattenConfig) -> ExclusionsSummary {
+    // От слабого к сильному: правило перекрыто, если позже встречается
+    // правило с тем же паттерном.
+    let ordered: Vec<&ExclusionRule> = config
+        .rule_layers()
+        .into_iter()
+        .rev()
+        .flat_map(|rules| rules.rules())
+        .collect();
+    let key = |rule: &ExclusionRule| (rule.target.is_extension(), rule.target.as_pattern());
+    let rules = ordered
+        .iter()
+        .enumerate()
+        .map(|(index, rule)| ResolvedRuleEntry {
+            layer: rule.origin.layer(),
+            pattern: rule.pattern(),
+            effect: if rule.negated { "include" } else { "exclude" },
+            origin: rule.origin.to_string(),
+            overridden: ordered[index + 1..].iter().any(|later| key(later) == key(rule)),
+        })
+        .collect();
+
+    // Итоговое решение по каждому паттерну — его последнее правило.
+    let mut resolved: BTreeMap<(bool, String), &ExclusionRule> = BTreeMap::new();
+    for rule in &ordered {
+        resolved.insert(key(rule), rule);
+    }
+    let entries = |extensions: bool| -> Vec<ExclusionEntry> {
+        resolved
+            .iter()
+            .filter(|((is_extension, _), rule)| *is_extension == extensions && !rule.negated)
+            .map(|(_, rule)| ExclusionEntry {
+                value: match &rule.target {
+                    RuleTarget::Extension(extension) => extension.clone(),
+                    target => target.as_pattern(),
+                },
+                origin: rule.origin.to_string(),
+            })
+            .collect()
+    };
+
+    let template_patterns = config
+        .exclusion_manager
+        .pattern_sources()
+        .into_iter()
+        .map(|source| {
+            let applied_as = match ExclusionRule::from_template(source.clone()) {
+                Ok(Some(rule)) if rule.target.is_extension() => "extension",
+                Ok(Some(rule)) if matches!(rule.target, RuleTarget::Glob(_)) => "glob",
+                Ok(Some(_)) => "folder",
+                _ => "ignored",
+            };
+            let PatternSource {
+                template,
+                line,
+                pattern,
+            } = source;
+            TemplatePatternEntry {
+                template,
+                line,
+                pattern,
+                applied_as,
+            }
+        })
+        .collect();
+
+    let mut include_hidden_names: Vec<String> = config.hidden_names.iter().cloned().collect();
+    include_hidden_names.sort();
+    let mut include_extensions: Vec<String> = config
+        .user_rules
+        .rules()
+        .iter()
+        .filter(|rule| rule.negated)
+        .filter_map(|rule| match &rule.target {
+            RuleTarget::Extension(extension) => Some(extension.clone()),
+            _ => None,
+        })
+        .collect();
+    include_extensions.sort();
+    include_extensions.dedup();
+    let mut include_globs: Vec<String> =
+        config.keep.iter().map(|rule| rule.raw().to_string()).collect();
+    include_globs.sort();
+    include_globs.dedup();
+    let mut skip_files: Vec<String> = config.skip_files.iter().cloned().collect();
+    skip_files.sort();
+
+    ExclusionsSummary {
+        rules,
+        skip_folders: entries(false),
+        skip_extensions: entries(true),
+        template_patterns,
+        include_globs,
+        include_extensions,
+        max_file_size: config.max_file_size,
+        max_depth: config.max_depth,
+        include_hidden: config.include_hidden,
+        include_hidden_names,
+        skip_files,
+        skip_generated: config.skip_generated,
+    }
+}
+
+/// Выводит итоговые правила исключений в консоль в выбранном формате.
+pub fn print_exclusions(config: &FlattenConfig, format: OutputFormat) -> Result<()> {
+    let summary = exclusions_summary(config);
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!("Resolved rules ({}, later rules win):", summary.rules.len());
+    let mut layer = "";
+    for entry in &summary.rules {
+        if entry.layer == "command line" && layer != "command line" {
+            println!("  -- .flattenignore files (read during the walk)");
+        }
+        layer = entry.layer;
+        let overridden = if entry.overridden { " (overridden)" } else { "" };
+        println!(
+            "  {:<8} {:<30} {}{}",
+            entry.effect, entry.pattern, entry.origin, overridden
+        );
+    }
+    if layer != "command line" {
+        println!("  -- .flattenignore files (read during the walk)");
+    }
+    println!();
+    println!("Skip folders ({}):", summary.skip_folders.len());
+    for entry in &summary.skip_folders {
+        println!("  {:<30} {}", entry.value, entry.origin);
+    }
+    println!();
+    println!("Skip extensions ({}):", summary.skip_extensions.len());
+    for entry in &summary.skip_extensions {
+        println!("  {:<30} {}", entry.value, entry.origin);
+    }
+    println!();
+    println!("Template patterns ({}):", summary.template_patterns.len());
+    for entry in &summary.template_patterns {
+        println!(
+            "  {:<30} {:<10} {}:{}",
+            entry.pattern, entry.applied_as, entry.template, entry.line
+        );
+    }
+    println!();
+    if summary.include_globs.is_empty() {
+        println!("Include globs: none");
+    } else {
+        println!("Include globs (--keep): {}", summary.include_globs.join(", "));
+    }
+    if !summary.include_extensions.is_empty() {
+        println!("Include extensions: {}", summary.include_extensions.join(", "));
+    }
+    let max_size = if summary.max_file_size > 0 {
+        format_size(summary.max_file_size)
+    } else {
+        "unlimited".to_string()
+    };
+    println!("Max file size: {}", max_size);
+    let max_depth = if summary.max_depth > 0 {
+        summary.max_depth.to_string()
+    } else {
+        "unlimited".to_string()
+    };
+    println!("Max depth: {}", max_depth);
+    println!("Include hidden: {}", summary.include_hidden);
+    if !summary.include_hidden && !summary.include_hidden_names.is_empty() {
+        println!(
+            "Hidden names included anyway: {}",
+            summary.include_hidden_names.join(", ")
+        );
+    }
+    if !summary.skip_files.is_empty() {
+        println!("Skip files: {}", summary.skip_files.join(", "));
+    }
+    println!("Skip generated files: {}", summary.skip_generated);
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Модуль готового списка файлов (`--files-from`).
+//!
+//! Вместо обхода базовых папок файлы можно передать списком, например
+//! из `git grep -l` или `fd`: по одному пути на строку или, с
+//! `--files-from0`, через NUL. Относительные пути отсчитываются от текущей
+//! директории. Базовой папкой становится общий предок перечисленных
+//! файлов, а дерево структуры строится только из них.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Читает список файлов из `source` (`-` — stdin).
+///
+/// Пустые строки пропускаются, повторы удаляются; порядок путей сохраняется.
+pub fn read_file_list(source: &Path, nul_separated: bool) -> Result<Vec<PathBuf>> {
+    let mut data = Vec::new();
+    if source == Path::new("-") {
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .context("Failed to read file list from stdin")?;
+    } else {
+        data = std::fs::read(source)
+            .with_context(|| format!("Failed to read file list: {}", source.display()))?;
+    }
+    let current_dir = std::env::current_dir().context("Failed to resolve current directory")?;
+    Ok(parse_file_list(&data, nul_separated, &current_dir))
+}
+
+/// Разбирает список файлов, отсчитывая относительные пути от `current_dir`.
+pub fn parse_file_list(data: &[u8], nul_separated: bool, current_dir: &Path) -> Vec<PathBuf> {
+    let separator = if nul_separated { b'\0' } else { b'\n' };
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for mut item in data.split(|&byte| byte == separator) {
+        if !nul_separated {
+            item = item.strip_suffix(b"\r").unwrap_or(item);
+        }
+        if item.is_empty() {
+            continue;
+        }
+        let path = normalize(&current_dir.join(path_from_bytes(item)));
+        if seen.insert(path.clone()) {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Возвращает ближайшую общую директорию перечисленных файлов.
+pub fn common_ancestor(files: &[PathBuf]) -> PathBuf {
+    let mut parents = files.iter().map(|file| file.parent().unwrap_or(file));
+    let Some(first) = parents.next() else {
+        return PathBuf::new();
+    };
+    let mut ancestor = first.to_path_buf();
+    for parent in parents {
+        while !parent.starts_with(&ancestor) && ancestor.pop() {}
+    }
+    ancestor
+}
+
+/// Убирает из пути компоненты `.` и `..` без обращения к файловой системе.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_list() {
+        let cwd = Path::new("/work/repo");
+        let files = parse_file_list(
+            b"src/lib.rs\r\n\n./src/lib.rs\n../other/a.rs\n/abs/b.rs",
+            false,
+            cwd,
+        );
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("/work/repo/src/lib.rs"),
+                PathBuf::from("/work/other/a.rs"),
+                PathBuf::from("/abs/b.rs"),
+            ]
+        );
+        let files = parse_file_list(b"a b\nc\0d\0", true, cwd);
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("/work/repo/a b\nc"),
+                PathBuf::from("/work/repo/d")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        let files = [
+            PathBuf::from("/work/repo/src/lib.rs"),
+            PathBuf::from("/work/repo/src/bin/main.rs"),
+            PathBuf::from("/work/repo/tests/it.rs"),
+        ];
+        assert_eq!(common_ancestor(&files), PathBuf::from("/work/repo"));
+        assert_eq!(
+            common_ancestor(&files[..1]),
+            PathBuf::from("/work/repo/src")
+        );
+        assert_eq!(
+            common_ancestor(&[PathBuf::from("/a/x"), PathBuf::from("/b/y")]),
+            PathBuf::from("/")
+        );
+    }
+}
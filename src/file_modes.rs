@@ -0,0 +1,180 @@
+//! Модуль прав доступа файлов (`--file-modes`).
+//!
+//! Для каждого записанного файла в заголовок и манифест попадают биты прав
+//! Unix (`mode: 0755`); вне Unix записывается только атрибут «только чтение».
+//! Символические ссылки при `--file-modes` не пропускаются при обходе, но и
+//! не читаются: вместо содержимого выводится заглушка с целью ссылки. Цель
+//! внутри базовой папки записывается относительно директории ссылки, цель
+//! вне ее — как есть, с пометкой. Цель сравнивается с папкой без обращения к
+//! файловой системе, поэтому ссылки на несуществующие файлы тоже описываются.
+
+use crate::file_list::normalize;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// Права доступа файла.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileMode {
+    /// Биты прав Unix (`"0755"`); вне Unix не определяются.
+    #[serde(
+        serialize_with = "serialize_octal",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub permissions: Option<u32>,
+    /// Атрибут «только чтение» (записывается вне Unix).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readonly: Option<bool>,
+    /// Цель, если файл — символическая ссылка.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlink: Option<SymlinkTarget>,
+}
+
+/// Цель символической ссылки.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SymlinkTarget {
+    /// Путь относительно директории ссылки или, вне базовой папки, как в ссылке.
+    pub target: PathBuf,
+    /// Указывает ли ссылка за пределы базовой папки.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub outside_tree: bool,
+}
+
+impl FileMode {
+    /// Права файла `path` базовой папки `base`; `None`, если метаданные недоступны.
+    pub fn of(base: &Path, path: &Path) -> Option<Self> {
+        let metadata = path.symlink_metadata().ok()?;
+        let symlink = if metadata.file_type().is_symlink() {
+            SymlinkTarget::of(base, path)
+        } else {
+            None
+        };
+        #[cfg(unix)]
+        let (permissions, readonly) = {
+            use std::os::unix::fs::PermissionsExt;
+            (Some(metadata.permissions().mode() & 0o7777), None)
+        };
+        #[cfg(not(unix))]
+        let (permissions, readonly) = (None, Some(metadata.permissions().readonly()));
+        Some(Self {
+            permissions,
+            readonly,
+            symlink,
+        })
+    }
+}
+
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.permissions, self.readonly) {
+            (Some(permissions), _) => write!(f, "mode: {:04o}", permissions)?,
+            (None, Some(true)) => write!(f, "mode: readonly")?,
+            (None, _) => write!(f, "mode: writable")?,
+        }
+        if let Some(symlink) = &self.symlink {
+            write!(f, ", {}", symlink)?;
+        }
+        Ok(())
+    }
+}
+
+impl SymlinkTarget {
+    /// Цель ссылки `link` базовой папки `base`; `None`, если ссылку не прочитать.
+    pub fn of(base: &Path, link: &Path) -> Option<Self> {
+        let target = std::fs::read_link(link).ok()?;
+        let base = normalize(&std::path::absolute(base).ok()?);
+        let directory = normalize(std::path::absolute(link).ok()?.parent()?);
+        let resolved = normalize(&directory.join(&target));
+        Some(if resolved.starts_with(&base) {
+            Self {
+                target: relative_path(&directory, &resolved),
+                outside_tree: false,
+            }
+        } else {
+            Self {
+                target,
+                outside_tree: true,
+            }
+        })
+    }
+}
+
+impl fmt::Display for SymlinkTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "symlink -> {}", self.target.display())?;
+        if self.outside_tree {
+            write!(f, " (outside tree)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Путь к `path` относительно директории `directory` (оба пути нормализованы).
+fn relative_path(directory: &Path, path: &Path) -> PathBuf {
+    let common = directory
+        .components()
+        .zip(path.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative: PathBuf = directory
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(path.components().skip(common));
+    if relative.as_os_str().is_empty() {
+        relative.push(Component::CurDir);
+    }
+    relative
+}
+
+fn serialize_octal<S: Serializer>(
+    permissions: &Option<u32>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match permissions {
+        Some(permissions) => serializer.serialize_str(&format!("{:04o}", permissions)),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        let path = |path: &str| PathBuf::from(path);
+        assert_eq!(
+            relative_path(&path("/a/bin"), &path("/a/scripts/run.sh")),
+            path("../scripts/run.sh")
+        );
+        assert_eq!(
+            relative_path(&path("/a"), &path("/a/run.sh")),
+            path("run.sh")
+        );
+        assert_eq!(relative_path(&path("/a/b"), &path("/a/b")), path("."));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_targets() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("app");
+        std::fs::create_dir_all(root.join("bin")).unwrap();
+        std::fs::write(root.join("run.sh"), "echo\n").unwrap();
+        std::os::unix::fs::symlink(root.join("run.sh"), root.join("bin/run")).unwrap();
+        std::os::unix::fs::symlink("../../etc/hosts", root.join("bin/hosts")).unwrap();
+
+        // Абсолютная цель внутри папки записывается относительно ссылки.
+        let inside = SymlinkTarget::of(&root, &root.join("bin/run")).unwrap();
+        assert_eq!(inside.to_string(), "symlink -> ../run.sh");
+        let outside = SymlinkTarget::of(&root, &root.join("bin/hosts")).unwrap();
+        assert!(outside.outside_tree);
+        assert_eq!(
+            outside.to_string(),
+            "symlink -> ../../etc/hosts (outside tree)"
+        );
+        assert_eq!(SymlinkTarget::of(&root, &root.join("run.sh")), None);
+    }
+}
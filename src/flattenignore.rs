@@ -0,0 +1,240 @@
+//! Модуль файлов `.flattenignore`.
+//!
+//! Файл `.flattenignore` в любой директории базовой папки задает исключения
+//! в стиле `.gitignore`: паттерн без `/` сопоставляется с именем на любой
+//! глубине, паттерн с `/` — с путем относительно директории файла, `!`
+//! возвращает ранее исключенный путь. Правила вложенных файлов применяются
+//! после правил родительских, побеждает последнее совпавшее правило.
+
+use crate::exclusions::PathPattern;
+use anyhow::Result;
+use glob::Pattern;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Имя файла с правилами исключений проекта.
+pub const FLATTENIGNORE_FILE: &str = ".flattenignore";
+
+/// Способ сопоставления правила с путем.
+#[derive(Debug, Clone)]
+enum RulePattern {
+    /// Glob по имени последнего компонента пути.
+    Name(Pattern),
+    /// Паттерн пути относительно директории правила.
+    Path(PathPattern),
+}
+
+/// Одно правило `.flattenignore` или флага `--keep`.
+#[derive(Debug, Clone)]
+pub struct IgnoreRule {
+    pattern: RulePattern,
+    negated: bool,
+    dir_only: bool,
+    line: usize,
+    raw: String,
+}
+
+impl IgnoreRule {
+    /// Разбирает строку правила. Пустые строки и комментарии дают `Ok(None)`.
+    pub fn parse(line: &str, number: usize) -> Result<Option<Self>> {
+        let raw = line.trim();
+        if raw.is_empty() || raw.starts_with('#') {
+            return Ok(None);
+        }
+        let (negated, body) = match raw.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, raw),
+        };
+        let dir_only = body.ends_with('/');
+        let body = body.trim_end_matches('/');
+        let pattern = if PathPattern::is_path_pattern(body) {
+            RulePattern::Path(PathPattern::new(body)?)
+        } else {
+            RulePattern::Name(Pattern::new(body)?)
+        };
+        Ok(Some(Self {
+            pattern,
+            negated,
+            dir_only,
+            line: number,
+            raw: raw.to_string(),
+        }))
+    }
+
+    /// Разбирает содержимое файла; некорректные строки пропускаются с предупреждением.
+    pub fn parse_all(contents: &str, source: &Path) -> Vec<Self> {
+        contents
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| match Self::parse(line, index + 1) {
+                Ok(rule) => rule,
+                Err(e) => {
+                    eprintln!(
+                        "Warning: {}:{}: invalid pattern `{}`: {}",
+                        source.display(),
+                        index + 1,
+                        line.trim(),
+                        e
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Проверяет путь `relative` (относительно директории правила).
+    ///
+    /// `path` нужен только для проверки, является ли путь директорией.
+    pub fn matches(&self, relative: &Path, path: &Path) -> bool {
+        let matched = match &self.pattern {
+            RulePattern::Name(pattern) => relative
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|name| pattern.matches(name)),
+            RulePattern::Path(pattern) => pattern.matches(relative),
+        };
+        matched && (!self.dir_only || path.is_dir())
+    }
+
+    /// Текст правила, как он был записан.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Решение этого правила из файла `source`.
+    pub fn verdict(&self, source: &Path) -> IgnoreVerdict {
+        IgnoreVerdict {
+            ignored: !self.negated,
+            source: source.to_path_buf(),
+            line: self.line,
+            pattern: self.raw.clone(),
+        }
+    }
+}
+
+/// Решение `.flattenignore` для пути вместе с правилом, которое его дало.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnoreVerdict {
+    /// `true` — путь исключен, `false` — возвращен правилом с `!`.
+    pub ignored: bool,
+    /// Файл `.flattenignore`, содержащий правило.
+    pub source: PathBuf,
+    /// Номер строки правила (с единицы).
+    pub line: usize,
+    /// Правило в исходном виде.
+    pub pattern: String,
+}
+
+/// Правила всех файлов `.flattenignore`, загружаемые по мере обхода.
+///
+/// Файлы читаются один раз на директорию; кэш разделяется между потоками.
+/// Так же читаются файлы `.gitignore` (`FlattenIgnore::named`).
+#[derive(Debug)]
+pub struct FlattenIgnore {
+    file_name: &'static str,
+    cache: Mutex<HashMap<PathBuf, Arc<Vec<IgnoreRule>>>>,
+}
+
+impl Default for FlattenIgnore {
+    fn default() -> Self {
+        Self::named(FLATTENIGNORE_FILE)
+    }
+}
+
+impl FlattenIgnore {
+    /// Создает набор правил из файлов с именем `file_name` в каждой директории.
+    pub fn named(file_name: &'static str) -> Self {
+        Self {
+            file_name,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Возвращает решение для `path` внутри базовой папки `base`, если какое-либо
+    /// правило с ним совпало.
+    pub fn verdict(&self, base: &Path, path: &Path) -> Option<IgnoreVerdict> {
+        let components: Vec<_> = path.strip_prefix(base).ok()?.components().collect();
+        let mut directory = base.to_path_buf();
+        let mut verdict = None;
+        for (index, component) in components.iter().enumerate() {
+            let relative: PathBuf = components[index..].iter().collect();
+            let rules = self.rules_for(&directory);
+            if let Some(rule) = rules.iter().rev().find(|r| r.matches(&relative, path)) {
+                verdict = Some(rule.verdict(&directory.join(self.file_name)));
+            }
+            directory.push(component);
+        }
+        verdict
+    }
+
+    /// Возвращает все правила, совпавшие с `path`, в порядке применения:
+    /// последнее из них дает решение `verdict`.
+    pub fn matches(&self, base: &Path, path: &Path) -> Vec<IgnoreVerdict> {
+        let Ok(relative) = path.strip_prefix(base) else {
+            return Vec::new();
+        };
+        let components: Vec<_> = relative.components().collect();
+        let mut directory = base.to_path_buf();
+        let mut matches = Vec::new();
+        for (index, component) in components.iter().enumerate() {
+            let relative: PathBuf = components[index..].iter().collect();
+            let rules = self.rules_for(&directory);
+            for rule in rules.iter().filter(|r| r.matches(&relative, path)) {
+                matches.push(rule.verdict(&directory.join(self.file_name)));
+            }
+            directory.push(component);
+        }
+        matches
+    }
+
+    /// Возвращает правила файла директории (пустые, если файла нет).
+    fn rules_for(&self, directory: &Path) -> Arc<Vec<IgnoreRule>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| {
+                let source = directory.join(self.file_name);
+                let rules = std::fs::read_to_string(&source)
+                    .map(|contents| IgnoreRule::parse_all(&contents, &source))
+                    .unwrap_or_default();
+                Arc::new(rules)
+            })
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_nested_rules_and_negation() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("fixtures/keep"))?;
+        fs::create_dir_all(root.join("src/generated"))?;
+        fs::write(root.join(FLATTENIGNORE_FILE), "*.snap\nfixtures/\n")?;
+        fs::write(
+            root.join("src").join(FLATTENIGNORE_FILE),
+            "generated/\n!important.snap\n",
+        )?;
+
+        let ignore = FlattenIgnore::default();
+        let verdict = |path: &str| ignore.verdict(root, &root.join(path)).map(|v| v.ignored);
+        assert_eq!(verdict("fixtures"), Some(true));
+        assert_eq!(verdict("a.snap"), Some(true));
+        assert_eq!(verdict("src/generated"), Some(true));
+        assert_eq!(verdict("src/important.snap"), Some(false));
+        assert_eq!(verdict("src/main.rs"), None);
+        // Паттерн только для директорий не совпадает с файлом того же имени.
+        assert_eq!(verdict("generated"), None);
+
+        let matches = ignore.matches(root, &root.join("src/important.snap"));
+        let patterns: Vec<&str> = matches.iter().map(|m| m.pattern.as_str()).collect();
+        assert_eq!(patterns, vec!["*.snap", "!important.snap"]);
+        assert_eq!(matches.last(), ignore.verdict(root, &root.join("src/important.snap")).as_ref());
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+//! Модуль настроек отдельных базовых папок.
+//!
+//! Поле `folders` файла `~/.flatten/manager_config.json` задает блоки
+//! настроек для отдельных папок:
+//! `{"folders": {"backend": {"enable_templates": ["python"], "max_file_size": "1MB"}}}`.
+//! Ключ блока сравнивается с аргументом `-f` как есть или, если путь
+//! существует, с его каноническим путем (относительные ключи — от текущей
+//! директории). Настройки блока заменяют общие значения только для этой
+//! папки: для нее строится своя `FlattenConfig`, а остальные папки запуска
+//! обрабатываются по общим правилам. Списки заменяют общие списки целиком;
+//! для добавления к ним есть `extra_skip_folders` и `extra_skip_extensions`.
+
+use crate::Args;
+use crate::config::ManagerConfig;
+use crate::environment::{ResolvedValue, ValueSource, env_var_name};
+use crate::report::parse_size;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Размер в байтах или строкой с единицей (`"1MB"`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SizeSetting {
+    /// Число байт.
+    Bytes(u64),
+    /// Размер с единицей: B, KB, MB или GB.
+    Text(String),
+}
+
+impl SizeSetting {
+    /// Размер в байтах.
+    ///
+    /// # Ошибки
+    /// Если строка не является размером.
+    pub fn bytes(&self) -> Result<u64> {
+        match self {
+            Self::Bytes(bytes) => Ok(*bytes),
+            Self::Text(text) => parse_size(text).map_err(|error| anyhow!(error)),
+        }
+    }
+
+    fn display(&self) -> String {
+        match self {
+            Self::Bytes(bytes) => bytes.to_string(),
+            Self::Text(text) => text.clone(),
+        }
+    }
+}
+
+/// Настройки одной базовой папки; незаданные поля берутся из общих настроек.
+///
+/// Имена полей совпадают с именами флагов (`max_file_size` — `--max-file-size`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FolderOverride {
+    /// Шаблоны исключений (`--enable-template`).
+    pub enable_templates: Option<Vec<String>>,
+    /// Отключаемые шаблоны (`--disable-template`).
+    pub disable_templates: Option<Vec<String>>,
+    /// Пропускаемые папки вместо общего списка (`--skip-folders`).
+    pub skip_folders: Option<Vec<String>>,
+    /// Папки, пропускаемые дополнительно (`--extra-skip-folders`).
+    pub extra_skip_folders: Option<Vec<String>>,
+    /// Пропускаемые расширения вместо общего списка (`--skip-extensions`).
+    pub skip_extensions: Option<Vec<String>>,
+    /// Расширения, пропускаемые дополнительно (`--extra-skip-extensions`).
+    pub extra_skip_extensions: Option<Vec<String>>,
+    /// Возвращаемые расширения (`--include-extension`).
+    pub include_extensions: Option<Vec<String>>,
+    /// Паттерны, которые никогда не пропускаются (`--keep`).
+    pub keep: Option<Vec<String>>,
+    /// Скрытые имена, включаемые без `--include-hidden` (`--include-hidden-names`).
+    pub include_hidden_names: Option<Vec<String>>,
+    /// Максимальный размер файла (`--max-file-size`).
+    pub max_file_size: Option<SizeSetting>,
+    /// Максимальная глубина обхода (`--max-depth`).
+    pub max_depth: Option<usize>,
+    /// Включать скрытые файлы и папки (`--include-hidden`).
+    pub include_hidden: Option<bool>,
+    /// Заменять заглушкой сгенерированные файлы (`--skip-generated`).
+    pub skip_generated: Option<bool>,
+    /// Заменять заглушкой lock-файлы (`--skip-lockfiles`).
+    pub skip_lockfiles: Option<bool>,
+    /// Определять тип проекта по файлам папки (`--auto-detect`).
+    pub auto_detect: Option<bool>,
+}
+
+impl FolderOverride {
+    /// Применяет настройки блока к копии общих аргументов.
+    ///
+    /// # Ошибки
+    /// Если размер в `max_file_size` задан неверно.
+    pub fn apply(&self, args: &mut Args) -> Result<()> {
+        let lists = [
+            (&self.enable_templates, &mut args.enable_templates),
+            (&self.disable_templates, &mut args.disable_templates),
+            (&self.skip_folders, &mut args.skip_folders),
+            (&self.extra_skip_folders, &mut args.extra_skip_folders),
+            (&self.skip_extensions, &mut args.skip_extensions),
+            (&self.extra_skip_extensions, &mut args.extra_skip_extensions),
+            (&self.include_extensions, &mut args.include_extensions),
+            (&self.keep, &mut args.keep),
+            (&self.include_hidden_names, &mut args.include_hidden_names),
+        ];
+        for (value, target) in lists {
+            if let Some(value) = value {
+                target.clone_from(value);
+            }
+        }
+        if let Some(size) = &self.max_file_size {
+            args.max_file_size = size
+                .bytes()
+                .with_context(|| format!("Invalid max_file_size `{}`", size.display()))?;
+        }
+        let flags = [
+            (self.include_hidden, &mut args.include_hidden),
+            (self.skip_generated, &mut args.skip_generated),
+            (self.skip_lockfiles, &mut args.skip_lockfiles),
+            (self.auto_detect, &mut args.auto_detect),
+        ];
+        for (value, target) in flags {
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+        if let Some(depth) = self.max_depth {
+            args.max_depth = depth;
+        }
+        Ok(())
+    }
+
+    /// Заданные в блоке настройки: имя флага и значения в исходном виде.
+    pub fn settings(&self) -> Vec<(&'static str, Vec<String>)> {
+        let mut settings = Vec::new();
+        let lists = [
+            ("enable-template", &self.enable_templates),
+            ("disable-template", &self.disable_templates),
+            ("skip-folders", &self.skip_folders),
+            ("extra-skip-folders", &self.extra_skip_folders),
+            ("skip-extensions", &self.skip_extensions),
+            ("extra-skip-extensions", &self.extra_skip_extensions),
+            ("include-extension", &self.include_extensions),
+            ("keep", &self.keep),
+            ("include-hidden-names", &self.include_hidden_names),
+        ];
+        for (name, value) in lists {
+            if let Some(value) = value {
+                settings.push((name, value.clone()));
+            }
+        }
+        if let Some(size) = &self.max_file_size {
+            settings.push(("max-file-size", vec![size.display()]));
+        }
+        if let Some(depth) = self.max_depth {
+            settings.push(("max-depth", vec![depth.to_string()]));
+        }
+        let flags = [
+            ("include-hidden", self.include_hidden),
+            ("skip-generated", self.skip_generated),
+            ("skip-lockfiles", self.skip_lockfiles),
+            ("auto-detect", self.auto_detect),
+        ];
+        for (name, value) in flags {
+            if let Some(value) = value {
+                settings.push((name, vec![value.to_string()]));
+            }
+        }
+        settings
+    }
+
+    /// Значения параметров для `--print-config`: настройки блока с источником
+    /// `folder` поверх общих значений `sources`.
+    pub fn resolved_values(&self, folder: &str, sources: &[ResolvedValue]) -> Vec<ResolvedValue> {
+        self.settings()
+            .into_iter()
+            .map(|(name, values)| ResolvedValue {
+                name: name.to_string(),
+                env: sources
+                    .iter()
+                    .find(|value| value.name == name)
+                    .map_or_else(|| env_var_name(name), |value| value.env.clone()),
+                values,
+                source: ValueSource::Folder,
+                preset: None,
+                folder: Some(folder.to_string()),
+            })
+            .collect()
+    }
+}
+
+/// Разбирает блоки `folders` из содержимого `manager_config.json`.
+///
+/// # Ошибки
+/// Если блок содержит неизвестную настройку или значение неверного типа.
+pub fn parse_overrides(config: &ManagerConfig) -> Result<BTreeMap<String, FolderOverride>> {
+    config
+        .folders
+        .iter()
+        .map(|(folder, value)| {
+            let block = FolderOverride::deserialize(value)
+                .with_context(|| format!("Invalid settings for folder \"{}\"", folder))?;
+            Ok((folder.clone(), block))
+        })
+        .collect()
+}
+
+/// Читает блоки папок из `manager_config.json` в `flatten_dir`.
+///
+/// Отсутствующий или поврежденный файл означает отсутствие блоков, как и для
+/// наборов `--preset`; ошибка в самом блоке возвращается.
+pub fn load_overrides(flatten_dir: &Path) -> Result<BTreeMap<String, FolderOverride>> {
+    let config = std::fs::read_to_string(flatten_dir.join("manager_config.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<ManagerConfig>(&content).ok());
+    match config {
+        Some(config) => parse_overrides(&config),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/// Читает блоки папок из `~/.flatten/manager_config.json`.
+///
+/// # Ошибки
+/// Если блок содержит неизвестную настройку или значение неверного типа.
+pub fn available_overrides() -> Result<BTreeMap<String, FolderOverride>> {
+    match dirs::home_dir() {
+        Some(home_dir) => load_overrides(&home_dir.join(".flatten")),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/// Находит блок для аргумента `-f`: сначала по записи пути, затем по
+/// каноническому пути.
+pub fn find<'a>(
+    overrides: &'a BTreeMap<String, FolderOverride>,
+    folder: &Path,
+) -> Option<(&'a str, &'a FolderOverride)> {
+    let normalize = |path: &Path| {
+        let text = path.to_string_lossy();
+        let text = text.trim_end_matches('/');
+        text.strip_prefix("./").unwrap_or(text).to_string()
+    };
+    let written = normalize(folder);
+    if let Some((key, block)) = overrides
+        .iter()
+        .find(|(key, _)| normalize(Path::new(key.as_str())) == written)
+    {
+        return Some((key, block));
+    }
+    let canonical = folder.canonicalize().ok()?;
+    overrides
+        .iter()
+        .find(|(key, _)| Path::new(key.as_str()).canonicalize().ok().as_ref() == Some(&canonical))
+        .map(|(key, block)| (key.as_str(), block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_parse_and_apply_override() -> Result<()> {
+        let config: ManagerConfig = serde_json::from_str(
+            r#"{"folders": {"./backend/": {"enable_templates": ["python"], "max_file_size": "1MB"},
+                            "frontend": {"max_file_size": 2048, "include_hidden": true},
+                            "docs/": {"include_hidden_names": [".vitepress"]}}}"#,
+        )?;
+        let overrides = parse_overrides(&config)?;
+        let (key, block) = find(&overrides, Path::new("backend")).expect("block for backend");
+        assert_eq!(key, "./backend/");
+
+        let mut args = Args::parse_from(["flatten-rust", "-f", "backend", "-e", "rust", "-m", "5"]);
+        block.apply(&mut args)?;
+        assert_eq!(args.enable_templates, vec!["python"]);
+        assert_eq!(args.max_file_size, 1024 * 1024);
+
+        let (_, block) = find(&overrides, Path::new("frontend")).expect("block for frontend");
+        let settings = block.settings();
+        assert_eq!(settings[0], ("max-file-size", vec!["2048".to_string()]));
+        assert_eq!(settings[1], ("include-hidden", vec!["true".to_string()]));
+        assert!(find(&overrides, Path::new("site")).is_none());
+
+        let (_, block) = find(&overrides, Path::new("docs")).expect("block for docs");
+        let mut args = Args::parse_from(["flatten-rust", "-f", "docs"]);
+        block.apply(&mut args)?;
+        assert_eq!(args.include_hidden_names, vec![".vitepress"]);
+
+        let config: ManagerConfig =
+            serde_json::from_str(r#"{"folders": {"web": {"max_file_sise": "1MB"}}}"#)?;
+        let error = parse_overrides(&config).unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid settings for folder \"web\": unknown field"));
+        Ok(())
+    }
+}
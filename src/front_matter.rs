@@ -0,0 +1,283 @@
+//! Модуль YAML-заголовков документа (`--front-matter`).
+//!
+//! В начало документа записывается блок `--- ... ---` со сведениями о запуске:
+//! версия утилиты, время создания, базовые папки, число файлов и их общий
+//! размер. В режиме `per-file` такой же небольшой блок предшествует каждому
+//! файлу. Все строковые значения записываются в двойных кавычках, поэтому
+//! YAML остается корректным для путей с `:`, `#`, кавычками и юникодом.
+//! `--reproducible` убирает время создания, чтобы повторные запуски давали
+//! побайтово одинаковый вывод. Сведения о репозитории (`--git-info`) содержат
+//! только время коммита и повторяемости не мешают.
+
+use crate::git_info::RepositoryInfo;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Какие YAML-заголовки записывать.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+pub enum FrontMatter {
+    /// Только заголовок документа.
+    Document,
+    /// Заголовок документа и блок перед каждым файлом.
+    PerFile,
+}
+
+/// Базовая папка в заголовке документа.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeaderFolder {
+    /// Метка папки в путях вывода.
+    pub label: String,
+    /// Путь к папке.
+    pub path: PathBuf,
+    /// Git-репозиторий папки (`--git-info`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<RepositoryInfo>,
+}
+
+/// Сведения о документе в целом.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DocumentHeader {
+    /// Имя и версия утилиты.
+    pub generator: String,
+    /// Время создания в UTC (RFC 3339); `None` при `--reproducible`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<String>,
+    /// Базовые папки в порядке вывода.
+    pub base_folders: Vec<HeaderFolder>,
+    /// Число файлов в документе, включая заглушки.
+    pub file_count: usize,
+    /// Суммарный исходный размер файлов в байтах.
+    pub total_bytes: u64,
+}
+
+/// Сведения об одном файле.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileHeader {
+    /// Путь файла в том виде, в котором он указан в выводе.
+    pub path: PathBuf,
+    /// Язык содержимого, если он известен.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// Исходный размер файла в байтах.
+    pub size: u64,
+    /// SHA-256 записанного содержимого (с `--manifest`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Ошибка чтения файла.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DocumentHeader {
+    /// Создает заголовок; без `reproducible` в него записывается текущее время.
+    pub fn new(
+        base_folders: Vec<HeaderFolder>,
+        file_count: usize,
+        total_bytes: u64,
+        reproducible: bool,
+    ) -> Self {
+        let generated_at = (!reproducible).then(|| {
+            let seconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            format_timestamp(seconds)
+        });
+        Self {
+            generator: format!("flatten-rust {}", env!("CARGO_PKG_VERSION")),
+            generated_at,
+            base_folders,
+            file_count,
+            total_bytes,
+        }
+    }
+
+    /// Записывает заголовок блоком YAML между строками `---`.
+    pub fn write_yaml(&self, output: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(output, "---")?;
+        writeln!(output, "generator: {}", yaml_string(&self.generator))?;
+        if let Some(generated_at) = &self.generated_at {
+            writeln!(output, "generated_at: {}", yaml_string(generated_at))?;
+        }
+        if self.base_folders.is_empty() {
+            writeln!(output, "base_folders: []")?;
+        } else {
+            writeln!(output, "base_folders:")?;
+        }
+        for folder in &self.base_folders {
+            writeln!(output, "  - label: {}", yaml_string(&folder.label))?;
+            let path = folder.path.display().to_string();
+            writeln!(output, "    path: {}", yaml_string(&path))?;
+            if let Some(repository) = &folder.repository {
+                write_repository_yaml(output, repository)?;
+            }
+        }
+        writeln!(output, "file_count: {}", self.file_count)?;
+        writeln!(output, "total_bytes: {}", self.total_bytes)?;
+        writeln!(output, "---")
+    }
+}
+
+impl FileHeader {
+    /// Записывает сведения о файле блоком YAML между строками `---`.
+    pub fn write_yaml(&self, output: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(output, "---")?;
+        let path = self.path.display().to_string();
+        writeln!(output, "path: {}", yaml_string(&path))?;
+        if let Some(lang) = &self.lang {
+            writeln!(output, "lang: {}", yaml_string(lang))?;
+        }
+        writeln!(output, "size: {}", self.size)?;
+        if let Some(sha256) = &self.sha256 {
+            writeln!(output, "sha256: {}", yaml_string(sha256))?;
+        }
+        if let Some(error) = &self.error {
+            writeln!(output, "error: {}", yaml_string(error))?;
+        }
+        writeln!(output, "---")
+    }
+}
+
+/// Записывает сведения о репозитории вложенным блоком элемента `base_folders`.
+fn write_repository_yaml(output: &mut dyn Write, repository: &RepositoryInfo) -> std::io::Result<()> {
+    writeln!(output, "    repository:")?;
+    let root = repository.root.display().to_string();
+    writeln!(output, "      root: {}", yaml_string(&root))?;
+    if let Some(branch) = &repository.branch {
+        writeln!(output, "      branch: {}", yaml_string(branch))?;
+    }
+    writeln!(output, "      commit: {}", yaml_string(&repository.commit))?;
+    writeln!(output, "      subject: {}", yaml_string(&repository.subject))?;
+    writeln!(output, "      committed_at: {}", yaml_string(&repository.committed_at))?;
+    if let Some(tag) = &repository.tag {
+        writeln!(output, "      tag: {}", yaml_string(tag))?;
+    }
+    if let Some(remote) = &repository.remote {
+        writeln!(output, "      remote: {}", yaml_string(remote))?;
+    }
+    writeln!(output, "      dirty: {}", repository.dirty)?;
+    writeln!(output, "      shallow: {}", repository.shallow)
+}
+
+/// Записывает строку YAML в двойных кавычках.
+///
+/// Внутри кавычек экранируются только `\`, `"` и управляющие символы, так что
+/// значение читается как строка независимо от содержимого (`yes`, `1.0`,
+/// `a: b`, `# c` и т. п.), а юникод остается читаемым.
+pub fn yaml_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            // Разделители строк Unicode YAML тоже считает переводами строки.
+            c if c.is_control() || matches!(c, '\u{2028}' | '\u{2029}' | '\u{feff}') => {
+                quoted.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Форматирует секунды Unix как время UTC в формате RFC 3339.
+pub fn format_timestamp(seconds: u64) -> String {
+    let (year, month, day) = civil_date(seconds);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Форматирует секунды Unix как время UTC для имени файла: `yyyymmdd-HHMM`.
+pub fn format_compact_timestamp(seconds: u64) -> String {
+    let (year, month, day) = civil_date(seconds);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60
+    )
+}
+
+/// Возвращает год, месяц и день даты UTC для секунд Unix.
+fn civil_date(seconds: u64) -> (i64, i64, i64) {
+    let days = (seconds / 86_400) as i64;
+    // Преобразование числа дней в дату григорианского календаря
+    // (алгоритм Говарда Хиннанта `civil_from_days`).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_quoting_and_timestamps() {
+        assert_eq!(yaml_string("src/a: b.rs"), "\"src/a: b.rs\"");
+        assert_eq!(yaml_string("проект/\"x\"\\y"), "\"проект/\\\"x\\\"\\\\y\"");
+        assert_eq!(yaml_string("a\nb\u{7}\u{2028}"), "\"a\\nb\\u0007\\u2028\"");
+
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_792_000_000), "2026-10-14T17:46:40Z");
+        assert_eq!(format_compact_timestamp(1_792_000_000), "20261014-1746");
+    }
+
+    #[test]
+    fn test_document_header_yaml() -> std::io::Result<()> {
+        let header = DocumentHeader::new(
+            vec![HeaderFolder {
+                label: "web: app".to_string(),
+                path: PathBuf::from("/srv/web"),
+                repository: None,
+            }],
+            3,
+            1024,
+            true,
+        );
+        let mut output = Vec::new();
+        header.write_yaml(&mut output)?;
+        assert_eq!(
+            String::from_utf8_lossy(&output),
+            format!(
+                "---\ngenerator: \"flatten-rust {}\"\nbase_folders:\n  \
+                 - label: \"web: app\"\n    path: \"/srv/web\"\n\
+                 file_count: 3\ntotal_bytes: 1024\n---\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+//! Модуль правил игнорирования git (`--gitignore`).
+//!
+//! Для базовой папки внутри рабочего дерева git читаются те же источники, что
+//! и у самого git, от сильного к слабому: файлы `.gitignore` от корня рабочего
+//! дерева до директории пути (правила вложенных сильнее), `.git/info/exclude`
+//! и глобальный файл исключений — `core.excludesFile` или, если настройка не
+//! задана, `$XDG_CONFIG_HOME/git/ignore` (`~/.config/git/ignore`). Если `git`
+//! не установлен, `core.excludesFile` не читается, а остальные источники
+//! учитываются как обычно. Вне рабочего дерева правила git не применяются.
+//!
+//! Правила git слабее флагов командной строки и `.flattenignore`, но сильнее
+//! шаблонов и умолчаний.
+
+use crate::flattenignore::{FlattenIgnore, IgnoreRule, IgnoreVerdict};
+use crate::git_info;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Имя файла с правилами игнорирования git.
+pub const GITIGNORE_FILE: &str = ".gitignore";
+
+/// Источник правила git.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitIgnoreSource {
+    /// Глобальный файл исключений.
+    Global,
+    /// `.git/info/exclude`.
+    InfoExclude,
+    /// Файлы `.gitignore` рабочего дерева.
+    GitIgnore,
+}
+
+impl GitIgnoreSource {
+    /// Название источника для `--explain`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Global => "global excludes",
+            Self::InfoExclude => "info/exclude",
+            Self::GitIgnore => ".gitignore",
+        }
+    }
+}
+
+/// Файл правил, действующий на все рабочее дерево.
+#[derive(Debug)]
+struct RuleFile {
+    source: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl RuleFile {
+    /// Читает файл; отсутствующий или нечитаемый файл означает отсутствие правил.
+    fn load(source: PathBuf) -> Option<Self> {
+        let contents = std::fs::read_to_string(&source).ok()?;
+        let rules = IgnoreRule::parse_all(&contents, &source);
+        Some(Self { source, rules })
+    }
+}
+
+/// Корень рабочего дерева и его правила вне файлов `.gitignore`.
+#[derive(Debug)]
+struct Repository {
+    root: PathBuf,
+    info_exclude: Option<RuleFile>,
+    global: Option<RuleFile>,
+}
+
+/// Правила игнорирования git, загружаемые по мере обхода.
+///
+/// Рабочее дерево определяется один раз на базовую папку; кэш разделяется
+/// между потоками.
+#[derive(Debug)]
+pub struct GitIgnore {
+    /// Глобальный файл исключений, если `core.excludesFile` не задан.
+    default_global: Option<PathBuf>,
+    files: FlattenIgnore,
+    repositories: Mutex<HashMap<PathBuf, Option<Arc<Repository>>>>,
+}
+
+impl GitIgnore {
+    /// Создает набор правил; `default_global` — глобальный файл исключений на
+    /// случай, если `core.excludesFile` не задан (см. `default_excludes_file`).
+    pub fn new(default_global: Option<PathBuf>) -> Self {
+        Self {
+            default_global,
+            files: FlattenIgnore::named(GITIGNORE_FILE),
+            repositories: Mutex::default(),
+        }
+    }
+
+    /// Возвращает решение для `path` внутри базовой папки `base`, если какое-либо
+    /// правило с ним совпало.
+    pub fn verdict(&self, base: &Path, path: &Path) -> Option<IgnoreVerdict> {
+        self.matches(base, path).pop().map(|(_, verdict)| verdict)
+    }
+
+    /// Возвращает все правила, совпавшие с `path`, с их источниками в порядке
+    /// применения: последнее из них дает решение `verdict`.
+    pub fn matches(&self, base: &Path, path: &Path) -> Vec<(GitIgnoreSource, IgnoreVerdict)> {
+        let Some(repository) = self.repository(base) else {
+            return Vec::new();
+        };
+        let Ok(relative) = path.strip_prefix(&repository.root) else {
+            return Vec::new();
+        };
+        let mut matches = Vec::new();
+        let files = [
+            (GitIgnoreSource::Global, &repository.global),
+            (GitIgnoreSource::InfoExclude, &repository.info_exclude),
+        ];
+        for (source, file) in files {
+            let Some(file) = file else { continue };
+            for rule in file.rules.iter().filter(|rule| rule.matches(relative, path)) {
+                matches.push((source, rule.verdict(&file.source)));
+            }
+        }
+        matches.extend(
+            self.files
+                .matches(&repository.root, path)
+                .into_iter()
+                .map(|verdict| (GitIgnoreSource::GitIgnore, verdict)),
+        );
+        matches
+    }
+
+    /// Рабочее дерево, содержащее `base`.
+    fn repository(&self, base: &Path) -> Option<Arc<Repository>> {
+        let mut repositories = self.repositories.lock().unwrap_or_else(|e| e.into_inner());
+        repositories
+            .entry(base.to_path_buf())
+            .or_insert_with(|| {
+                let root = base.ancestors().find(|dir| dir.join(".git").exists())?;
+                let info_exclude = git_info::git_dir(root)
+                    .and_then(|git_dir| RuleFile::load(git_dir.join("info").join("exclude")));
+                let global = git_info::excludes_file(root)
+                    .or_else(|| self.default_global.clone())
+                    .and_then(RuleFile::load);
+                Some(Arc::new(Repository {
+                    root: root.to_path_buf(),
+                    info_exclude,
+                    global,
+                }))
+            })
+            .clone()
+    }
+}
+
+/// Глобальный файл исключений git по умолчанию: `$XDG_CONFIG_HOME/git/ignore`
+/// или `~/.config/git/ignore`.
+pub fn default_excludes_file() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+    Some(config_home.join("git").join("ignore"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_sources_precedence() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let home = temp_dir.path().join("home");
+        let root = temp_dir.path().join("repo");
+        fs::create_dir_all(home.join(".config/git"))?;
+        fs::create_dir_all(root.join(".git/info"))?;
+        fs::create_dir_all(root.join("app"))?;
+        let global = home.join(".config/git/ignore");
+        fs::write(&global, "*.swp\n.envrc.local\nnotes.md\n")?;
+        fs::write(root.join(".git/info/exclude"), "scratch/\n!notes.md\n")?;
+        fs::write(root.join(GITIGNORE_FILE), "*.log\n")?;
+        fs::write(root.join("app").join(GITIGNORE_FILE), "!keep.log\n")?;
+
+        // Базовая папка ниже корня: правила корня все равно действуют.
+        let base = root.join("app");
+        let ignore = GitIgnore::new(Some(global.clone()));
+        let verdict = |path: &str| ignore.verdict(&base, &root.join(path)).map(|v| v.ignored);
+        assert_eq!(verdict("app/main.rs.swp"), Some(true));
+        assert_eq!(verdict("app/.envrc.local"), Some(true));
+        assert_eq!(verdict("app/notes.md"), Some(false));
+        assert_eq!(verdict("app/debug.log"), Some(true));
+        assert_eq!(verdict("app/keep.log"), Some(false));
+        assert_eq!(verdict("app/main.rs"), None);
+
+        let matches = ignore.matches(&base, &root.join("app/notes.md"));
+        let sources: Vec<_> = matches.iter().map(|(source, v)| (*source, v.line)).collect();
+        assert_eq!(
+            sources,
+            vec![(GitIgnoreSource::Global, 3), (GitIgnoreSource::InfoExclude, 2)]
+        );
+        assert_eq!(matches[0].1.source, global);
+
+        // Вне рабочего дерева правила git не применяются.
+        let outside = temp_dir.path().join("home");
+        assert_eq!(ignore.verdict(&outside, &outside.join("x.swp")), None);
+        Ok(())
+    }
+}
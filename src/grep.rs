@@ -0,0 +1,84 @@
+//! Модуль фильтра файлов по содержимому (`--grep`).
+//!
+//! С `--grep` в вывод попадают только файлы, текст которых совпадает хотя бы
+//! с одним из регулярных выражений, с `--grep-invert` — только не совпавшие.
+//! Заглушки и похожие на бинарные файлы не проверяются и отбрасываются в
+//! обоих режимах, а файлы с ошибкой чтения сохраняются, чтобы ошибка не
+//! потерялась. Дерево структуры строится по оставшимся файлам.
+
+use crate::pipeline::FileContent;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexSet};
+
+/// Регулярные выражения `--grep` и режим `--grep-invert`.
+#[derive(Debug, Clone)]
+pub struct ContentFilter {
+    patterns: RegexSet,
+    invert: bool,
+}
+
+impl ContentFilter {
+    /// Компилирует выражения; ошибка указывает на первое некорректное из них.
+    pub fn new(patterns: &[String], invert: bool) -> Result<Self> {
+        for pattern in patterns {
+            Regex::new(pattern).with_context(|| format!("Invalid --grep pattern: {}", pattern))?;
+        }
+        let patterns = RegexSet::new(patterns).context("Failed to compile --grep patterns")?;
+        Ok(Self { patterns, invert })
+    }
+
+    /// Решает, остается ли файл в выводе.
+    pub fn accepts(&self, content: &Result<FileContent>) -> bool {
+        match content {
+            Ok(file_content) => {
+                file_content.skip_reason.is_none()
+                    && !file_content.content.looks_binary()
+                    && self.patterns.is_match(&file_content.content) != self.invert
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str) -> Result<FileContent> {
+        Ok(FileContent {
+            content: content.to_string().into(),
+            original_size: content.len() as u64,
+            read_bytes: content.len() as u64,
+            converter: None,
+            skip_reason: None,
+            line_endings: None,
+            chunks: Vec::new(),
+            lfs: None,
+            stale: false,
+            sanitized: None,
+            transforms: Vec::new(),
+            mode: None,
+        })
+    }
+
+    #[test]
+    fn test_content_filter() -> Result<()> {
+        let patterns = ["\\bFlattenConfig\\b".to_string(), "TODO".to_string()];
+        let filter = ContentFilter::new(&patterns, false)?;
+        assert!(filter.accepts(&text("use crate::FlattenConfig;")));
+        assert!(filter.accepts(&text("// TODO: remove")));
+        assert!(!filter.accepts(&text("FlattenConfigs")));
+        assert!(!filter.accepts(&text("TODO\0binary")));
+        assert!(filter.accepts(&Err(anyhow::anyhow!("denied"))));
+
+        let inverted = ContentFilter::new(&patterns, true)?;
+        assert!(inverted.accepts(&text("FlattenConfigs")));
+        assert!(!inverted.accepts(&text("// TODO")));
+        assert!(!inverted.accepts(&text("\0")));
+
+        let error =
+            ContentFilter::new(&["ok".to_string(), "(unclosed".to_string()], false).unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid --grep pattern: (unclosed"));
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! Модуль группировки и порядка файлов в секции содержимого.
+//!
+//! Файлы одной базовой папки разбиваются на группы по расширению, языку или
+//! директории верхнего уровня (`--group-by`). Группы и файлы внутри них
+//! упорядочены по алфавиту, поэтому вывод не зависит от порядка обхода.
+//! Приоритетные файлы (`--priority`) перемещаются в начало своей группы.
+//! Сводка по языкам для `--overview` строится теми же группами по языку.
+//!
+//! Несколько базовых папок можно обрабатывать не в порядке аргументов, а по
+//! числу файлов (`--folder-order`); порядок секций в документе при этом не
+//! меняется.
+
+use crate::exclusions::PathPattern;
+use crate::language::LanguageTable;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use glob::Pattern;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Способ группировки файлов.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum GroupBy {
+    /// Без группировки, в порядке обхода (по умолчанию).
+    #[default]
+    None,
+    /// По расширению файла.
+    Ext,
+    /// По языку (см. модуль `language`).
+    Lang,
+    /// По директории верхнего уровня внутри базовой папки.
+    Dir,
+}
+
+/// Порядок обработки базовых папок.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum FolderOrder {
+    /// В порядке аргументов (по умолчанию).
+    #[default]
+    Args,
+    /// Сначала папки с меньшим числом файлов.
+    SmallestFirst,
+    /// Сначала папки с большим числом файлов.
+    LargestFirst,
+}
+
+impl FolderOrder {
+    /// Возвращает индексы папок с числом файлов `counts` в порядке обработки.
+    ///
+    /// Папки с равным числом файлов остаются в порядке аргументов.
+    pub fn schedule(self, counts: &[usize]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..counts.len()).collect();
+        match self {
+            FolderOrder::Args => {}
+            FolderOrder::SmallestFirst => order.sort_by_key(|&i| counts[i]),
+            FolderOrder::LargestFirst => order.sort_by_key(|&i| std::cmp::Reverse(counts[i])),
+        }
+        order
+    }
+}
+
+/// Заголовок группы файлов.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileGroup {
+    /// Имя группы.
+    pub name: String,
+    /// Количество файлов в группе.
+    pub files: usize,
+    /// Суммарный исходный размер файлов группы в байтах.
+    pub bytes: u64,
+}
+
+/// Группы файлов: заголовок (если группировка включена) и файлы группы.
+pub type GroupedFiles<T> = Vec<(Option<FileGroup>, Vec<(PathBuf, T)>)>;
+
+/// Возвращает имя группы файла; язык определяется по таблице `languages`.
+pub fn group_name(
+    group_by: GroupBy,
+    languages: &LanguageTable,
+    base_folder: &Path,
+    path: &Path,
+) -> String {
+    match group_by {
+        GroupBy::None => String::new(),
+        GroupBy::Ext => path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "(no extension)".to_string()),
+        GroupBy::Lang => languages.language_for(path).unwrap_or("other").to_string(),
+        GroupBy::Dir => {
+            let relative = path.strip_prefix(base_folder).unwrap_or(path);
+            let mut components = relative.components();
+            match (components.next(), components.next()) {
+                (Some(first), Some(_)) => first.as_os_str().to_string_lossy().into_owned(),
+                _ => "(root)".to_string(),
+            }
+        }
+    }
+}
+
+/// Разбивает файлы на группы.
+///
+/// `size` возвращает исходный размер элемента. Без группировки возвращается
+/// одна группа без заголовка с исходным порядком элементов.
+pub fn group_files<T>(
+    group_by: GroupBy,
+    languages: &LanguageTable,
+    base_folder: &Path,
+    items: Vec<(PathBuf, T)>,
+    size: impl Fn(&T) -> u64,
+) -> GroupedFiles<T> {
+    if group_by == GroupBy::None {
+        return vec![(None, items)];
+    }
+
+    let mut groups: BTreeMap<String, Vec<(PathBuf, T)>> = BTreeMap::new();
+    for (path, item) in items {
+        groups
+            .entry(group_name(group_by, languages, base_folder, &path))
+            .or_default()
+            .push((path, item));
+    }
+    groups
+        .into_iter()
+        .map(|(name, mut files)| {
+            files.sort_by(|a, b| a.0.cmp(&b.0));
+            let header = FileGroup {
+                name,
+                files: files.len(),
+                bytes: files.iter().map(|(_, item)| size(item)).sum(),
+            };
+            (Some(header), files)
+        })
+        .collect()
+}
+
+/// Возвращает сводку по языкам: число и суммарный размер файлов каждого языка.
+///
+/// Языки упорядочены по убыванию числа файлов, затем по имени.
+pub fn language_summary<T>(
+    languages: &LanguageTable,
+    base_folder: &Path,
+    items: &[(PathBuf, T)],
+    size: impl Fn(&T) -> u64,
+) -> Vec<FileGroup> {
+    let sizes = items
+        .iter()
+        .map(|(path, item)| (path.clone(), size(item)))
+        .collect();
+    let mut summary: Vec<FileGroup> =
+        group_files(GroupBy::Lang, languages, base_folder, sizes, |size| *size)
+            .into_iter()
+            .filter_map(|(header, _)| header)
+            .collect();
+    summary.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.name.cmp(&b.name)));
+    summary
+}
+
+/// Паттерн приоритетного файла.
+#[derive(Debug, Clone)]
+enum PriorityPattern {
+    /// Glob по имени файла на любой глубине.
+    Name(Pattern),
+    /// Паттерн пути относительно базовой папки.
+    Path(PathPattern),
+}
+
+/// Паттерны `--priority` в порядке их указания.
+#[derive(Debug, Clone, Default)]
+pub struct PriorityRules {
+    patterns: Vec<PriorityPattern>,
+}
+
+impl PriorityRules {
+    /// Компилирует паттерны. Паттерн с `/` сопоставляется с путем от базовой папки.
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|raw| {
+                if PathPattern::is_path_pattern(raw) {
+                    PathPattern::new(raw).map(PriorityPattern::Path)
+                } else {
+                    Pattern::new(raw)
+                        .map(PriorityPattern::Name)
+                        .with_context(|| format!("Invalid priority pattern: {}", raw))
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Возвращает номер первого совпавшего паттерна (меньше — раньше в выводе).
+    pub fn rank(&self, base_folder: &Path, path: &Path) -> Option<usize> {
+        let relative = path.strip_prefix(base_folder).unwrap_or(path);
+        let name = path.file_name()?.to_str()?;
+        self.patterns.iter().position(|pattern| match pattern {
+            PriorityPattern::Name(pattern) => pattern.matches(name),
+            PriorityPattern::Path(pattern) => pattern.matches(relative),
+        })
+    }
+
+    /// Перемещает приоритетные файлы в начало списка.
+    ///
+    /// Приоритетные файлы упорядочены по номеру паттерна, затем по глубине и пути;
+    /// остальные сохраняют исходный порядок.
+    pub fn prioritize<T>(&self, base_folder: &Path, files: &mut Vec<(PathBuf, T)>) {
+        if self.patterns.is_empty() {
+            return;
+        }
+        let (mut first, rest): (Vec<_>, Vec<_>) = std::mem::take(files)
+            .into_iter()
+            .map(|(path, item)| (self.rank(base_folder, &path), path, item))
+            .partition(|(rank, _, _)| rank.is_some());
+        first.sort_by(|a, b| {
+            (a.0, a.1.components().count(), &a.1).cmp(&(b.0, b.1.components().count(), &b.1))
+        });
+        files.extend(
+            first
+                .into_iter()
+                .chain(rest)
+                .map(|(_, path, item)| (path, item)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_files_by_dir() {
+        let base = Path::new("/repo");
+        let items = vec![
+            (PathBuf::from("/repo/src/lib.rs"), 10),
+            (PathBuf::from("/repo/README.md"), 5),
+            (PathBuf::from("/repo/src/a/mod.rs"), 20),
+        ];
+        let languages = LanguageTable::builtin();
+        let groups = group_files(GroupBy::Dir, &languages, base, items, |size| *size);
+
+        let names: Vec<_> = groups
+            .iter()
+            .map(|(header, _)| header.as_ref().unwrap().name.as_str())
+            .collect();
+        assert_eq!(names, vec!["(root)", "src"]);
+        let (header, files) = &groups[1];
+        assert_eq!(header.as_ref().unwrap().bytes, 30);
+        assert_eq!(files[0].0, PathBuf::from("/repo/src/a/mod.rs"));
+    }
+
+    #[test]
+    fn test_folder_schedule() {
+        let counts = [3, 10, 1, 10];
+        assert_eq!(FolderOrder::Args.schedule(&counts), [0, 1, 2, 3]);
+        assert_eq!(FolderOrder::SmallestFirst.schedule(&counts), [2, 0, 1, 3]);
+        assert_eq!(FolderOrder::LargestFirst.schedule(&counts), [1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn test_language_summary() {
+        let base = Path::new("/repo");
+        let items = vec![
+            (PathBuf::from("/repo/README.md"), 5),
+            (PathBuf::from("/repo/src/lib.rs"), 10),
+            (PathBuf::from("/repo/src/main.rs"), 20),
+            (PathBuf::from("/repo/LICENSE"), 7),
+            (PathBuf::from("/repo/Dockerfile"), 3),
+        ];
+        let summary = language_summary(&LanguageTable::builtin(), base, &items, |size| *size);
+        let rows: Vec<_> = summary
+            .iter()
+            .map(|group| (group.name.as_str(), group.files, group.bytes))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("rust", 2, 30), ("dockerfile", 1, 3), ("markdown", 1, 5), ("other", 1, 7)]
+        );
+    }
+
+    #[test]
+    fn test_priority_order() -> Result<()> {
+        let base = Path::new("/repo");
+        let rules = PriorityRules::new(&["README*".to_string(), "Cargo.toml".to_string()])?;
+        let mut files = vec![
+            (PathBuf::from("/repo/src/main.rs"), ()),
+            (PathBuf::from("/repo/Cargo.toml"), ()),
+            (PathBuf::from("/repo/docs/README.md"), ()),
+            (PathBuf::from("/repo/README.md"), ()),
+            (PathBuf::from("/repo/src/lib.rs"), ()),
+        ];
+        rules.prioritize(base, &mut files);
+
+        let order: Vec<_> = files.iter().map(|(p, _)| p.to_str().unwrap()).collect();
+        assert_eq!(
+            order,
+            vec![
+                "/repo/README.md",
+                "/repo/docs/README.md",
+                "/repo/Cargo.toml",
+                "/repo/src/main.rs",
+                "/repo/src/lib.rs",
+            ]
+        );
+        Ok(())
+    }
+}
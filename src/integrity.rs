@@ -0,0 +1,245 @@
+//! Модуль завершающей строки целостности документа (`--check`).
+//!
+//! Markdown-документ, записанный в файл, заканчивается строкой
+//! `### FLATTEN TRAILER sections=N bytes=M sha256=H ###` с числом секций
+//! файлов, размером и SHA-256 всего, что выше нее. Хеш и размер считает
+//! `manifest::CountingWriter` по мере записи, поэтому документ не
+//! перечитывается. По строке видно, что документ дошел целиком: обрезка при
+//! пересылке через чаты и облачные диски уносит ее вместе с концом файла,
+//! а правка в середине меняет хеш. В stdout и потоковых форматах строка не
+//! пишется: дописывать ее туда бессмысленно. Документ, остановленный по
+//! `--time-limit`, цел, но неполон: строка получает поле `truncated=time-limit`.
+
+use crate::manifest::sha256_hex;
+use crate::report::{format_count, format_size};
+use crate::verify;
+use anyhow::{Context, Result, bail};
+use std::fmt;
+use std::path::Path;
+
+/// Начало завершающей строки.
+const PREFIX: &str = "### FLATTEN TRAILER ";
+
+/// Причина обрезки документа по `--time-limit` в поле `truncated`.
+pub const TIME_LIMIT_TRUNCATION: &str = "time-limit";
+
+/// Сведения завершающей строки документа.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trailer {
+    /// Число секций файлов.
+    pub sections: usize,
+    /// Размер документа над строкой.
+    pub bytes: u64,
+    /// SHA-256 документа над строкой.
+    pub sha256: String,
+    /// Почему документ записан не полностью (`None` — полностью).
+    pub truncated: Option<String>,
+}
+
+impl Trailer {
+    /// Разбирает строку без перевода строки; `None`, если это не завершающая строка.
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields = line.strip_prefix(PREFIX)?.strip_suffix(" ###")?;
+        let (mut sections, mut bytes, mut sha256, mut truncated) = (None, None, None, None);
+        for field in fields.split(' ') {
+            match field.split_once('=')? {
+                ("sections", value) => sections = value.parse().ok(),
+                ("bytes", value) => bytes = value.parse().ok(),
+                ("sha256", value) => sha256 = Some(value.to_string()),
+                ("truncated", value) => truncated = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Some(Self {
+            sections: sections?,
+            bytes: bytes?,
+            sha256: sha256?,
+            truncated,
+        })
+    }
+}
+
+impl fmt::Display for Trailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}sections={} bytes={} sha256={}",
+            PREFIX, self.sections, self.bytes, self.sha256
+        )?;
+        if let Some(reason) = &self.truncated {
+            write!(f, " truncated={}", reason)?;
+        }
+        write!(f, " ###")
+    }
+}
+
+/// Результат сверки документа с его завершающей строкой.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityCheck {
+    /// Что записано в завершающей строке.
+    pub expected: Trailer,
+    /// Что найдено в документе над ней.
+    pub actual: Trailer,
+}
+
+impl IntegrityCheck {
+    /// Сверяет документ `content`; `None`, если завершающей строки нет.
+    pub fn of(content: &[u8]) -> Option<Self> {
+        let body = content.strip_suffix(b"\n").unwrap_or(content);
+        let start = body
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |i| i + 1);
+        let expected = Trailer::parse(std::str::from_utf8(&body[start..]).ok()?)?;
+        let above = &content[..start];
+        let actual = Trailer {
+            sections: verify::parse_document(&String::from_utf8_lossy(above)).sections,
+            bytes: above.len() as u64,
+            sha256: sha256_hex(above),
+            // Обрезка не проверяется: о ней можно узнать только из самой строки.
+            truncated: expected.truncated.clone(),
+        };
+        Some(Self { expected, actual })
+    }
+
+    /// Совпадает ли документ с завершающей строкой.
+    pub fn is_intact(&self) -> bool {
+        self.expected == self.actual
+    }
+
+    /// Описание расхождения для документа `path`; `None`, если его нет.
+    pub fn problem(&self, path: &Path) -> Option<String> {
+        if self.is_intact() {
+            return None;
+        }
+        let (expected, actual) = (&self.expected, &self.actual);
+        let missing_sections = expected.sections.saturating_sub(actual.sections);
+        let detail = if actual.bytes < expected.bytes {
+            format!(
+                "{} and {} sections appear to be missing",
+                format_size(expected.bytes - actual.bytes),
+                missing_sections
+            )
+        } else if actual.bytes > expected.bytes {
+            format!(
+                "{} were added ({} sections expected, {} found)",
+                format_size(actual.bytes - expected.bytes),
+                expected.sections,
+                actual.sections
+            )
+        } else if missing_sections > 0 {
+            format!("{} sections appear to be missing", missing_sections)
+        } else {
+            "content was modified (checksum mismatch)".to_string()
+        };
+        Some(format!(
+            "Integrity check failed for {}: {}",
+            path.display(),
+            detail
+        ))
+    }
+
+    /// Печатает итог сверки документа `path`.
+    pub fn print(&self, path: &Path) {
+        match self.problem(path) {
+            Some(problem) => println!("{}", problem),
+            None => println!(
+                "{} is intact ({} sections, {}){}",
+                path.display(),
+                format_count(self.actual.sections),
+                format_size(self.actual.bytes),
+                match &self.expected.truncated {
+                    Some(reason) => format!(", but was truncated ({})", reason),
+                    None => String::new(),
+                }
+            ),
+        }
+    }
+}
+
+/// Сверяет документ `path` с его завершающей строкой (`--check`).
+///
+/// # Ошибки
+/// Возвращает ошибку, если документ не читается или строки в нем нет.
+pub fn check_file(path: &Path) -> Result<IntegrityCheck> {
+    let content = std::fs::read(path)
+        .with_context(|| format!("Failed to read document: {}", path.display()))?;
+    match IntegrityCheck::of(&content) {
+        Some(check) => Ok(check),
+        None => bail!(
+            "No integrity trailer in {}: the document is truncated or was written without one",
+            path.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(body: &str, sections: usize) -> String {
+        let trailer = Trailer {
+            sections,
+            bytes: body.len() as u64,
+            sha256: sha256_hex(body.as_bytes()),
+            truncated: None,
+        };
+        format!("{}{}\n", body, trailer)
+    }
+
+    const BODY: &str = "### DIRECTORY app FLATTENED CONTENT ###\n\
+                        ### app/a.rs BEGIN ###\nfn a() {}\n### app/a.rs END ###\n\n\
+                        ### app/b.rs BEGIN ###\nfn b() {}\n### app/b.rs END ###\n\n\
+                        ### DIRECTORY app FLATTENED CONTENT ###\n";
+
+    #[test]
+    fn test_trailer_round_trip() {
+        let trailer = Trailer {
+            sections: 2,
+            bytes: 120,
+            sha256: "ab".repeat(32),
+            truncated: None,
+        };
+        assert_eq!(Trailer::parse(&trailer.to_string()), Some(trailer.clone()));
+        let truncated = Trailer {
+            truncated: Some(TIME_LIMIT_TRUNCATION.to_string()),
+            ..trailer
+        };
+        assert!(truncated.to_string().ends_with(" truncated=time-limit ###"));
+        assert_eq!(Trailer::parse(&truncated.to_string()), Some(truncated));
+        assert_eq!(Trailer::parse("### FLATTEN TRAILER sections=2 ###"), None);
+        assert_eq!(Trailer::parse("### app/a.rs BEGIN ###"), None);
+    }
+
+    #[test]
+    fn test_intact_and_damaged_documents() {
+        let intact = document(BODY, 2);
+        let check = IntegrityCheck::of(intact.as_bytes()).unwrap();
+        assert!(check.is_intact(), "{:?}", check);
+        assert_eq!(check.problem(Path::new("out.md")), None);
+
+        // Потеряна вторая секция: не хватает байт и секции.
+        let cut = BODY.find("### app/b.rs BEGIN").unwrap();
+        let damaged = intact.replace(&BODY[cut..BODY.rfind("### DIRECTORY").unwrap()], "");
+        let check = IntegrityCheck::of(damaged.as_bytes()).unwrap();
+        assert_eq!(check.actual.sections, 1);
+        assert_eq!(
+            check.problem(Path::new("out.md")).unwrap(),
+            "Integrity check failed for out.md: 55 bytes and 1 sections appear to be missing"
+        );
+
+        // Тот же размер, другое содержимое.
+        let edited = intact.replace("fn a() {}", "fn z() {}");
+        let problem = IntegrityCheck::of(edited.as_bytes())
+            .unwrap()
+            .problem(Path::new("out.md"));
+        assert!(
+            problem
+                .unwrap()
+                .ends_with("content was modified (checksum mismatch)")
+        );
+
+        // Обрезанный документ теряет строку целиком.
+        assert_eq!(IntegrityCheck::of(&intact.as_bytes()[..100]), None);
+    }
+}
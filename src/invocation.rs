@@ -0,0 +1,352 @@
+//! Модуль снимков конфигурации запуска (`--save-invocation`, `--replay`).
+//!
+//! Снимок — JSON-файл с итоговыми значениями всех параметров после учета
+//! командной строки, переменных окружения и `--preset`, с источником каждого
+//! значения (как в `--print-config`), блоками настроек базовых папок из
+//! `manager_config.json` и версией утилиты. В отличие от
+//! `manager_config.json`, снимок не задает умолчания, а фиксирует один
+//! конкретный запуск, чтобы повторить его позже или приложить к отчету об
+//! ошибке.
+//!
+//! `--replay` берет значения из снимка, не читая переменные окружения и
+//! `manager_config.json`; флаги, указанные в командной строке вместе с
+//! `--replay`, сильнее снимка. Параметры со значением по умолчанию берутся из
+//! текущей версии, а если умолчание изменилось — из снимка. Значения, которые
+//! больше не разрешаются (неизвестные флаги, отсутствующие папки, неизвестные
+//! шаблоны), пропускаются с предупреждением. Все поля снимка необязательны,
+//! поэтому снимки других версий читаются.
+
+use crate::Args;
+use crate::environment::{self, ResolvedValue, ValueSource};
+use crate::folder_overrides::{self, FolderOverride};
+use crate::presets::PresetDefinition;
+use anyhow::{Context, Result};
+use clap::parser::ValueSource as ClapValueSource;
+use clap::{ArgAction, CommandFactory};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Параметры самих снимков: в снимок не сохраняются.
+const SNAPSHOT_FLAGS: &[&str] = &["save-invocation", "replay"];
+
+/// Снимок конфигурации одного запуска.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Invocation {
+    /// Версия утилиты, сохранившей снимок.
+    pub version: String,
+    /// Аргументы командной строки запуска.
+    pub command_line: Vec<String>,
+    /// Итоговые значения параметров и их источники.
+    pub values: Vec<ResolvedValue>,
+    /// Блоки настроек базовых папок запуска (поле `folders` `manager_config.json`).
+    pub folders: BTreeMap<String, FolderOverride>,
+}
+
+impl Invocation {
+    /// Снимает итоговую конфигурацию разобранных аргументов.
+    pub fn capture(args: &Args) -> Self {
+        let folders = args
+            .folders
+            .iter()
+            .filter_map(|folder| folder_overrides::find(&args.folder_overrides, folder))
+            .map(|(key, block)| (key.to_string(), block.clone()))
+            .collect();
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            command_line: command_line(args),
+            values: args
+                .sources
+                .iter()
+                .filter(|value| !SNAPSHOT_FLAGS.contains(&value.name.as_str()))
+                .cloned()
+                .collect(),
+            folders,
+        }
+    }
+
+    /// Читает снимок из JSON-файла.
+    ///
+    /// # Ошибки
+    /// Если файл не читается или не является снимком.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read invocation file: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid invocation file: {}", path.display()))
+    }
+
+    /// Записывает снимок в JSON-файл.
+    ///
+    /// # Ошибки
+    /// Если файл не удалось записать.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content =
+            serde_json::to_string_pretty(self).context("Failed to serialize invocation")?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write invocation file: {}", path.display()))
+    }
+}
+
+/// Командная строка запуска, восстановленная из разобранных аргументов.
+///
+/// Содержит имя программы и параметры с источником `cli` в виде `--имя=значение`,
+/// поэтому не зависит от `argv` процесса и не требует, чтобы он был в UTF-8.
+pub fn command_line(args: &Args) -> Vec<String> {
+    let mut command = Args::command();
+    command.build();
+    let mut line = vec![command.get_name().to_string()];
+    for value in args.sources.iter().filter(|value| value.source == ValueSource::Cli) {
+        if let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(value.name.as_str()))
+        {
+            line.extend(flag_arguments(arg, value));
+        }
+    }
+    line
+}
+
+/// Аргументы, задающие значение `value` параметра `arg`.
+fn flag_arguments(arg: &clap::Arg, value: &ResolvedValue) -> Vec<String> {
+    let long = &value.name;
+    if !arg.get_action().takes_values() {
+        if value.values.iter().any(|value| value == "true") {
+            return vec![format!("--{}", long)];
+        }
+    } else if value.values.is_empty() {
+        // Пустой список задается флагом без значений, отсутствующее значение — ничем.
+        if arg.get_num_args().is_some_and(|range| range.min_values() == 0) {
+            return vec![format!("--{}", long)];
+        }
+    } else if matches!(arg.get_action(), ArgAction::Append) {
+        return value.values.iter().map(|item| format!("--{}={}", long, item)).collect();
+    } else {
+        return vec![format!("--{}={}", long, value.values[0])];
+    }
+    Vec::new()
+}
+
+/// Разбирает аргументы `argv` поверх снимка `path` (`--replay`).
+///
+/// Возвращает аргументы с источниками значений из снимка и предупреждения о
+/// значениях, которые больше не разрешаются. Переменные окружения не
+/// читаются; `presets` нужны только для `--preset`, указанного в `argv`.
+///
+/// # Ошибки
+/// Если снимок не читается или его значения не проходят разбор.
+pub fn replay_args<I, T>(
+    path: &Path,
+    argv: I,
+    presets: &BTreeMap<String, PresetDefinition>,
+) -> Result<(Args, Vec<String>)>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let invocation = Invocation::load(path)?;
+    let mut warnings = Vec::new();
+    if invocation.version != env!("CARGO_PKG_VERSION") {
+        warnings.push(format!(
+            "{} was saved by flatten-rust {}, replaying with {}",
+            path.display(),
+            if invocation.version.is_empty() { "unknown" } else { &invocation.version },
+            env!("CARGO_PKG_VERSION")
+        ));
+    }
+
+    let mut argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+    let mut command = Args::command();
+    // Умолчания логических флагов появляются только при сборке команды.
+    command.build();
+    let cli_matches = command.clone().try_get_matches_from(&argv)?;
+    let mut replayed = Vec::new();
+    for value in &invocation.values {
+        if SNAPSHOT_FLAGS.contains(&value.name.as_str()) {
+            continue;
+        }
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(value.name.as_str()))
+        else {
+            warnings.push(format!("--{} is no longer supported; ignored", value.name));
+            continue;
+        };
+        let id = arg.get_id().as_str();
+        if cli_matches.value_source(id) == Some(ClapValueSource::CommandLine) {
+            continue;
+        }
+        if value.source == ValueSource::Default {
+            let current: Vec<String> = arg
+                .get_default_values()
+                .iter()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect();
+            if current == value.values {
+                continue;
+            }
+            warnings.push(format!(
+                "default of --{} changed from [{}] to [{}]; using the saved value",
+                value.name,
+                value.values.join(", "),
+                current.join(", ")
+            ));
+        }
+        replayed.push(value);
+        // `--preset` уже раскрыт в значения остальных параметров.
+        if id == "preset" {
+            continue;
+        }
+        argv.extend(flag_arguments(arg, value).into_iter().map(OsString::from));
+    }
+
+    let mut args = environment::parse_args_with_presets(argv, |_| None, presets)?;
+    for source in &mut args.sources {
+        if let Some(saved) = replayed.iter().find(|saved| saved.name == source.name) {
+            source.source = saved.source;
+            source.preset.clone_from(&saved.preset);
+            if source.name == "preset" {
+                source.values.clone_from(&saved.values);
+                args.preset = saved.values.first().cloned();
+            }
+        }
+    }
+    args.folder_overrides = invocation.folders;
+    for folder in &args.folders {
+        if !folder.exists() {
+            warnings.push(format!("folder {} no longer exists", folder.display()));
+        }
+    }
+    Ok((args, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use std::collections::HashMap;
+
+    /// Вывод `--print-config` без строк самих `--save-invocation` и `--replay`.
+    fn effective_config(args: &Args) -> Result<String> {
+        let mut output = Vec::new();
+        environment::write_config(args, &mut output)?;
+        let own = |line: &&str| {
+            SNAPSHOT_FLAGS
+                .iter()
+                .any(|name| line.starts_with(&format!("  {} ", name)))
+        };
+        Ok(String::from_utf8(output)?
+            .lines()
+            .filter(|line| !own(line))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    #[test]
+    fn test_save_and_replay_invocation() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let project = temp_dir.path().join("project");
+        std::fs::create_dir(&project)?;
+        let snapshot = temp_dir.path().join("run.json");
+        let project = project.to_str().expect("path is utf8");
+        let snapshot_arg = snapshot.to_str().expect("path is utf8");
+
+        let saved_env = HashMap::from([
+            ("FLATTEN_MAX_FILE_SIZE", "5"),
+            ("FLATTEN_SKIP_FOLDERS", "vendor,out"),
+        ]);
+        let argv = [
+            "flatten-rust",
+            "-f",
+            project,
+            "--preset",
+            "llm",
+            "--save-invocation",
+            snapshot_arg,
+        ];
+        let saved_lookup = |name: &str| saved_env.get(name).map(|v| v.to_string());
+        let args = environment::parse_args(argv, saved_lookup)?;
+        Invocation::capture(&args).write(&snapshot)?;
+
+        // Другое окружение меняет обычный разбор, но не повтор.
+        let changed_env = HashMap::from([("FLATTEN_MAX_FILE_SIZE", "7")]);
+        let changed_lookup = |name: &str| changed_env.get(name).map(|v| v.to_string());
+        let changed = environment::parse_args(["flatten-rust", "-f", project], changed_lookup)?;
+        assert_ne!(effective_config(&changed)?, effective_config(&args)?);
+
+        let replay = ["flatten-rust", "--replay", snapshot_arg];
+        let (replayed, warnings) = replay_args(&snapshot, replay, &presets::builtin_presets())?;
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(effective_config(&replayed)?, effective_config(&args)?);
+        assert_eq!(replayed.max_file_size, 5);
+        assert!(replayed.skip_lockfiles);
+
+        // Флаги командной строки сильнее снимка.
+        let replay = ["flatten-rust", "--replay", snapshot_arg, "-m", "9"];
+        let (replayed, _) = replay_args(&snapshot, replay, &presets::builtin_presets())?;
+        assert_eq!(replayed.max_file_size, 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_line_from_args() -> Result<()> {
+        let env = HashMap::from([("FLATTEN_MAX_DEPTH", "2")]);
+        let lookup = |name: &str| env.get(name).map(|v| v.to_string());
+        let argv = ["flatten-rust", "-f", "a", "-m", "9", "--include-hidden", "-s", "x", "-s", "y"];
+        let line = command_line(&environment::parse_args(argv, lookup)?);
+        assert_eq!(line[0], "flatten-rust");
+        for expected in [
+            "--folders=a",
+            "--max-file-size=9",
+            "--include-hidden",
+            "--skip-folders=x",
+            "--skip-folders=y",
+        ] {
+            assert!(line.iter().any(|arg| arg == expected), "{} not in {:?}", expected, line);
+        }
+        // Значения из окружения и умолчания в командную строку не попадают.
+        assert!(!line.iter().any(|arg| arg.starts_with("--max-depth")), "{:?}", line);
+
+        // Путь не в UTF-8 не мешает восстановить командную строку.
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let folder = std::ffi::OsStr::from_bytes(b"dir\xff");
+            let argv = [OsString::from("flatten-rust"), "-f".into(), folder.into()];
+            let line = command_line(&environment::parse_args(argv, |_| None)?);
+            assert_eq!(line, vec!["flatten-rust", "--folders=dir\u{fffd}"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_warns_about_unresolved_values() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let snapshot = temp_dir.path().join("run.json");
+        let missing = temp_dir.path().join("gone");
+        std::fs::write(
+            &snapshot,
+            serde_json::json!({
+                "version": "0.0.1",
+                "values": [
+                    {"name": "folders", "values": [missing.to_str()], "source": "cli"},
+                    {"name": "tree-style", "values": ["fancy"], "source": "cli"},
+                    {"name": "max-depth", "values": ["3"], "source": "default"}
+                ],
+                "future_field": true
+            })
+            .to_string(),
+        )?;
+        let replay = ["flatten-rust", "--replay", "run.json"];
+        let (args, warnings) = replay_args(&snapshot, replay, &BTreeMap::new())?;
+        assert_eq!(args.max_depth, 3);
+        assert_eq!(args.folders, vec![missing.clone()]);
+        assert!(warnings[0].contains("saved by flatten-rust 0.0.1"));
+        assert_eq!(warnings[1], "--tree-style is no longer supported; ignored");
+        assert!(warnings[2].starts_with("default of --max-depth changed from [3] to [0]"));
+        assert_eq!(warnings[3], format!("folder {} no longer exists", missing.display()));
+        Ok(())
+    }
+}
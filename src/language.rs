@@ -0,0 +1,193 @@
+//! Модуль определения языка файла.
+//!
+//! Язык определяется таблицей `LanguageTable` по полному имени файла
+//! (`Dockerfile`, `CMakeLists.txt`, `go.mod`) или, если имени нет в таблице,
+//! по самому длинному известному составному суффиксу (`.d.ts`, `.blade.php`),
+//! затем по последнему расширению; регистр не учитывается. Одна таблица
+//! используется для языка блоков кода, сводки по языкам (`--overview`) и
+//! группировки (`--group-by lang`), поэтому они не расходятся. Таблица
+//! дополняется полем `languages` файла `~/.flatten/manager_config.json`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Соответствие расширений и составных суффиксов (без начальной точки) языкам.
+const SUFFIX_LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("toml", "toml"),
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("rst", "rst"),
+    ("txt", "text"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("ipynb", "python"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("d.ts", "typescript"),
+    ("go", "go"),
+    ("java", "java"),
+    ("kt", "kotlin"),
+    ("kts", "kotlin"),
+    ("groovy", "groovy"),
+    ("gradle", "groovy"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cpp", "cpp"),
+    ("cc", "cpp"),
+    ("hpp", "cpp"),
+    ("cs", "csharp"),
+    ("rb", "ruby"),
+    ("php", "php"),
+    ("blade.php", "php"),
+    ("swift", "swift"),
+    ("sh", "bash"),
+    ("bash", "bash"),
+    ("ps1", "powershell"),
+    ("sql", "sql"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("css", "css"),
+    ("scss", "scss"),
+    ("json", "json"),
+    ("yml", "yaml"),
+    ("yaml", "yaml"),
+    ("xml", "xml"),
+    ("csv", "csv"),
+    ("tsv", "tsv"),
+    ("cmake", "cmake"),
+    ("mk", "makefile"),
+    ("dockerfile", "dockerfile"),
+];
+
+/// Соответствие полных имен файлов (в нижнем регистре) языкам.
+const NAME_LANGUAGES: &[(&str, &str)] = &[
+    ("dockerfile", "dockerfile"),
+    ("containerfile", "dockerfile"),
+    ("makefile", "makefile"),
+    ("gnumakefile", "makefile"),
+    ("jenkinsfile", "groovy"),
+    ("cmakelists.txt", "cmake"),
+    ("go.mod", "go"),
+    ("go.sum", "text"),
+    ("gemfile", "ruby"),
+    ("rakefile", "ruby"),
+    ("vagrantfile", "ruby"),
+    ("cargo.lock", "toml"),
+    (".bashrc", "bash"),
+    (".zshrc", "bash"),
+];
+
+/// Таблица языков: встроенные соответствия и дополнения пользователя.
+#[derive(Debug, Clone)]
+pub struct LanguageTable {
+    /// Полные имена файлов в нижнем регистре.
+    names: HashMap<String, String>,
+    /// Суффиксы без начальной точки в нижнем регистре.
+    suffixes: HashMap<String, String>,
+}
+
+impl Default for LanguageTable {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl LanguageTable {
+    /// Создает таблицу со встроенными соответствиями.
+    pub fn builtin() -> Self {
+        let owned = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(key, language)| (key.to_string(), language.to_string()))
+                .collect()
+        };
+        Self {
+            names: owned(NAME_LANGUAGES),
+            suffixes: owned(SUFFIX_LANGUAGES),
+        }
+    }
+
+    /// Добавляет соответствия пользователя поверх встроенных.
+    ///
+    /// Ключ с начальной точкой (`.d.ts`, `.proto`) — суффикс, иначе — полное
+    /// имя файла (`Jenkinsfile`). Пустой язык отменяет определение: файл
+    /// выводится без языка, а более короткие суффиксы не проверяются.
+    pub fn extend(&mut self, custom: &BTreeMap<String, String>) {
+        for (key, language) in custom {
+            let key = key.to_lowercase();
+            match key.strip_prefix('.') {
+                Some(suffix) if !suffix.is_empty() => {
+                    self.suffixes.insert(suffix.to_string(), language.clone())
+                }
+                _ => self.names.insert(key, language.clone()),
+            };
+        }
+    }
+
+    /// Возвращает язык файла по имени или самому длинному известному суффиксу.
+    pub fn language_for(&self, path: &Path) -> Option<&str> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        let language = self.names.get(&name).or_else(|| {
+            // Точка в начале имени (`.eslintrc.json`) не начинает суффикс.
+            name.match_indices('.')
+                .filter(|(index, _)| *index > 0)
+                .find_map(|(index, _)| self.suffixes.get(&name[index + 1..]))
+        })?;
+        (!language.is_empty()).then_some(language.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_for() {
+        let table = LanguageTable::builtin();
+        let cases = [
+            ("src/main.rs", Some("rust")),
+            ("Cargo.TOML", Some("toml")),
+            ("README", None),
+            ("Dockerfile", Some("dockerfile")),
+            ("docker/DOCKERFILE", Some("dockerfile")),
+            ("api.Dockerfile", Some("dockerfile")),
+            ("Makefile", Some("makefile")),
+            ("Jenkinsfile", Some("groovy")),
+            ("CMakeLists.txt", Some("cmake")),
+            ("notes.txt", Some("text")),
+            ("go.mod", Some("go")),
+            ("src/App.TSX", Some("typescript")),
+            ("types/index.d.ts", Some("typescript")),
+            ("views/home.blade.php", Some("php")),
+            ("jquery.min.js", Some("javascript")),
+            ("dist/site.tar.gz", None),
+            (".eslintrc.json", Some("json")),
+            (".gitignore", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(table.language_for(Path::new(path)), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_custom_languages() {
+        let mut table = LanguageTable::builtin();
+        let custom = BTreeMap::from([
+            (".proto".to_string(), "protobuf".to_string()),
+            ("BUILD".to_string(), "starlark".to_string()),
+            (".min.js".to_string(), String::new()),
+            ("Makefile".to_string(), "make".to_string()),
+        ]);
+        table.extend(&custom);
+        assert_eq!(table.language_for(Path::new("api/v1.PROTO")), Some("protobuf"));
+        assert_eq!(table.language_for(Path::new("pkg/BUILD")), Some("starlark"));
+        assert_eq!(table.language_for(Path::new("makefile")), Some("make"));
+        assert_eq!(table.language_for(Path::new("jquery.min.js")), None);
+        assert_eq!(table.language_for(Path::new("app.js")), Some("javascript"));
+    }
+}
@@ -10,8 +10,51 @@
 //! - `Args`: Структура для парсинга аргументов командной строки с использованием `clap`.
 //! - `run`: Асинхронная функция, являющаяся основной точкой входа в библиотеку.
 //! - `FlattenConfig`: Структура для управления конфигурацией процесса "сглаживания".
+//! - `archive`: Модуль входных архивов (`-f project.zip`, `-f project.tar.gz`).
+//! - `assets`: Модуль заглушек для изображений с их размерами.
+//! - `cache_maintenance`: Модуль обслуживания директории кэша (`--cache-prune`).
+//! - `chunking`: Модуль разбиения содержимого на фрагменты для RAG-конвейеров.
 //! - `config`: Модуль для управления шаблонами исключений (например, из `.gitignore`).
+//! - `content_cache`: Модуль кэша обработанного содержимого (`--content-cache`).
+//! - `converters`: Модуль конвертеров содержимого (например, Jupyter-ноутбуков).
+//! - `dependencies`: Модуль сводки зависимостей из манифестов (`--deps-summary`).
+//! - `doctor`: Модуль диагностики окружения (`--doctor`).
+//! - `environment`: Модуль настройки через переменные окружения `FLATTEN_*`.
+//! - `error`: Модуль ошибок библиотечного API (`FlattenError`).
+//! - `events`: Модуль потока событий обработки для редакторов и IDE (`--events`).
 //! - `exclusions`: Модуль для управления логикой исключения файлов и папок.
+//! - `explain`: Модуль объяснения решений о включении файлов.
+//! - `file_list`: Модуль готового списка файлов (`--files-from`).
+//! - `front_matter`: Модуль YAML-заголовков документа (`--front-matter`).
+//! - `git_info`: Модуль сведений о git-репозитории базовой папки (`--git-info`).
+//! - `gitignore`: Модуль правил игнорирования git (`--gitignore`).
+//! - `flattenignore`: Модуль файлов `.flattenignore` с исключениями проекта.
+//! - `folder_overrides`: Модуль настроек отдельных базовых папок.
+//! - `grep`: Модуль фильтра файлов по содержимому (`--grep`).
+//! - `grouping`: Модуль группировки файлов в секции содержимого.
+//! - `integrity`: Модуль завершающей строки целостности документа (`--check`).
+//! - `invocation`: Модуль снимков конфигурации запуска (`--save-invocation`, `--replay`).
+//! - `language`: Модуль определения языка файла.
+//! - `manifest`: Модуль манифеста выходного документа.
+//! - `messages`: Модуль каталога текстов документа (`--messages`).
+//! - `normalize`: Модуль нормализации текста содержимого.
+//! - `outline`: Модуль сокращенного вывода содержимого (`--outline`, `--head-lines`).
+//! - `output`: Модуль форматов выходного документа (Markdown, JSON, JSONL, HTML).
+//! - `output_names`: Модуль автоматических имен выходных файлов (`--output-dir`).
+//! - `pipeline`: Модуль этапов обработки: обход, чтение файлов и дерево структуры.
+//! - `presets`: Модуль наборов настроек (`--preset`).
+//! - `preview`: Модуль сводки и подтверждения больших запусков.
+//! - `progress`: Модуль уведомлений о ходе обработки (`ProgressSink`).
+//! - `protected_paths`: Модуль защиты домашней директории и корня от обработки.
+//! - `report`: Модуль отчета о выполнении и статистики.
+//! - `sanitize`: Модуль очистки содержимого от управляющих символов (`--sanitize`).
+//! - `serve`: Модуль предпросмотра документа по HTTP (`--serve`, feature `serve`).
+//! - `template_lint`: Модуль проверки и нормализации шаблонов (`--show-template --lint`).
+//! - `terminal`: Модуль ширины терминала для консольного вывода (`--width`).
+//! - `time_limit`: Модуль ограничения времени запуска (`--time-limit`).
+//! - `transform`: Модуль упорядоченной цепочки преобразований содержимого.
+//! - `verify`: Модуль проверки актуальности выходного документа.
+//! - `workspace`: Модуль Cargo workspace базовой папки (`--exclude-crate`).
 //!
 //! # Примеры
 //!
@@ -31,37 +74,186 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! Из синхронного кода документ можно получить через `run_blocking` или,
+//! без сети и консольного вывода, через `flatten_paths`.
 
+pub mod archive;
+pub mod assets;
+pub mod budget;
+pub mod cache_maintenance;
+pub mod chunking;
 pub mod config;
+pub mod content_cache;
+pub mod converters;
+pub mod dependencies;
+pub mod doctor;
+pub mod environment;
+pub mod error;
+pub mod events;
 pub mod exclusions;
+pub mod explain;
+pub mod file_list;
+pub mod file_modes;
+pub mod flattenignore;
+pub mod folder_overrides;
+pub mod front_matter;
+pub mod git_info;
+pub mod gitignore;
+pub mod grep;
+pub mod grouping;
+pub mod integrity;
+pub mod invocation;
+pub mod language;
+pub mod manifest;
+pub mod messages;
+pub mod normalize;
+pub mod outline;
+pub mod output;
+pub mod output_names;
+pub mod pipeline;
+pub mod presets;
+pub mod preview;
+pub mod progress;
+pub mod protected_paths;
+pub mod report;
+pub mod sanitize;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod template_lint;
+pub mod terminal;
+pub mod time_limit;
+mod toml_value;
+pub mod transform;
+pub mod vendored;
+pub mod verify;
+pub mod workspace;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use archive::{ArchiveKind, EntryAction, UnpackLimits, UnpackedArchive};
+use assets::{ImageConverter, ImageFormat};
+use budget::{BudgetFill, FitStrategy, OmittedFile};
+use chunking::ChunkOptions;
+use clap::{Parser, ValueEnum};
+use content_cache::ContentCache;
 use console::{style, Emoji};
-use exclusions::ExclusionManager;
-use indicatif::{ProgressBar, ProgressStyle};
-use memmap2::MmapOptions;
-use rayon::prelude::*;
-use std::collections::HashSet;
-use std::ffi::OsStr;
-use std::fs::{File, OpenOptions};
+use converters::{ConverterRegistry, CsvPreviewConverter, FileConverter};
+use dependencies::ManifestDependencies;
+use error::{FileError, FlattenError, FlattenResult};
+use exclusions::{ExclusionManager, ExclusionRule, PatternOrigin, RuleList, RuleTarget};
+use flattenignore::{FLATTENIGNORE_FILE, FlattenIgnore, IgnoreRule, IgnoreVerdict};
+use gitignore::GitIgnore;
+use folder_overrides::FolderOverride;
+use front_matter::{DocumentHeader, FrontMatter, HeaderFolder};
+use git_info::{RepositoryInfo, SubmoduleMode};
+use grep::ContentFilter;
+use grouping::{FolderOrder, GroupBy, PriorityRules};
+use language::LanguageTable;
+use manifest::{ContentSpan, FileStatus, Manifest, ManifestEntry};
+use normalize::EolMode;
+use output::{HeadingStyle, OutputFormat};
+use progress::{IndicatifProgress, NoProgress, ProgressSink, ProgressSummary};
+use pipeline::{DirTree, FileContent, PlannedFile, ReadOptions, TreeAnnotations};
+use preview::RunPreview;
+use report::{FileReport, FlattenReport, OutputReport, SkipReason, WarningKind};
+use transform::TransformChain;
+use vendored::VendoredMode;
+use messages::{Message, Messages};
+use time_limit::{TimeLimit, Truncation};
+use workspace::CargoWorkspace;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use walkdir::WalkDir;
+use std::path::{Component, Path, PathBuf};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Выводит служебное сообщение в stdout, а если туда пишется документ (`-o -`)
+/// или поток событий (`--events -`), — в stderr.
+macro_rules! status {
+    ($config:expr) => {
+        status!($config, "")
+    };
+    ($config:expr, $($arg:tt)*) => {
+        if $config.document_to_stdout || $config.events_to_stdout {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 static FOLDER: Emoji<'_, '_> = Emoji("📁", "DIR");
 static FILE: Emoji<'_, '_> = Emoji("📄", "FILE");
 static SKIP: Emoji<'_, '_> = Emoji("⏭️", "SKIP");
+static MORE: Emoji<'_, '_> = Emoji("…", "...");
 static ROCKET: Emoji<'_, '_> = Emoji("🚀", "=>");
-const PROGRESS_STYLE: &str =
-    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})";
+static SEARCH: Emoji<'_, '_> = Emoji("🔍", "?");
+static REFRESH: Emoji<'_, '_> = Emoji("🔄", "~");
+static SUCCESS: Emoji<'_, '_> = Emoji("✅", "OK");
+static FAILURE: Emoji<'_, '_> = Emoji("❌", "ERR");
+static CHECK: Emoji<'_, '_> = Emoji("✓", "OK");
+/// Папки, пропускаемые по умолчанию.
+const DEFAULT_SKIP_FOLDERS: &[&str] = &[".git", "node_modules", "target", "dist", "build"];
+/// Расширения файлов, пропускаемые по умолчанию.
+const DEFAULT_SKIP_EXTENSIONS: &[&str] = &[
+    "exe", "dll", "so", "dylib", "bin", "jar", "apk", "ipa", "msi", "class", "pyc",
+];
+/// Файлы, выводимые в начале секции содержимого по умолчанию.
+const DEFAULT_PRIORITY: &[&str] = &[
+    "README*",
+    "LICENSE",
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "go.mod",
+];
+/// Скрытые файлы и папки, включаемые в вывод по умолчанию.
+const DEFAULT_HIDDEN_NAMES: &[&str] = &[
+    ".gitignore",
+    ".dockerignore",
+    ".editorconfig",
+    ".env.example",
+    ".github",
+];
+/// Имена lock-файлов и других автоматически генерируемых манифестов (`--skip-lockfiles`).
+const DEFAULT_LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "go.sum",
+    "Pipfile.lock",
+    "composer.lock",
+    "Gemfile.lock",
+];
+
+/// Режим цветного вывода в терминал.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum ColorMode {
+    /// Цвета, если соответствующий поток (stdout или stderr) является терминалом.
+    #[default]
+    Auto,
+    /// Всегда использовать цвета.
+    Always,
+    /// Никогда не использовать цвета.
+    Never,
+}
+
+/// Возвращает эмодзи или его ASCII-замену.
+fn glyph(emoji: &Emoji<'static, 'static>, enabled: bool) -> &'static str {
+    if enabled { emoji.0 } else { emoji.1 }
+}
 
 /// # Высокопроизводительный инструмент для "сглаживания" кодовой базы с умными исключениями
 ///
 /// Утилита для рекурсивного обхода директорий, конкатенации текстовых файлов
 /// в один Markdown-файл с сохранением структуры проекта.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone, Serialize, Deserialize)]
 #[command(name = "flatten-rust")]
 #[command(about = "High-performance codebase flattening tool with intelligent exclusions")]
 #[command(version)]
@@ -78,6 +270,23 @@ const PROGRESS_STYLE: &str =
     
     --show-enabled                 Показать текущие включенные шаблоны
 
+ПЕРЕМЕННЫЕ ОКРУЖЕНИЯ:
+  Любой флаг можно задать переменной FLATTEN_<ИМЯ_ФЛАГА>, например FLATTEN_OUTPUT
+  или FLATTEN_MAX_FILE_SIZE. Флаги командной строки сильнее переменных окружения.
+  Списки разделяются запятыми (\, — запятая внутри элемента, \\ — обратная черта),
+  логические флаги принимают 1/true/yes/on и 0/false/no/off.
+  --print-config показывает итоговые значения и их источники.
+
+КОДЫ ЗАВЕРШЕНИЯ:
+  0  успешно
+  1  ошибка аргументов или конфигурации (в том числе, если ни одна папка -f не существует);
+     --explain: путь не попадает в вывод; --verify: документ устарел
+  2  фатальная ошибка ввода-вывода (файл вывода, API или кэш шаблонов)
+  3  документ записан, но часть файлов не прочиталась (только с --fail-on-error)
+  4  документ записан, но обрезан по --time-limit
+  7  --doctor: хотя бы одна проверка провалилась
+  8  --check, --verify: документ не совпадает со своей строкой целостности
+
 ПРИМЕРЫ:
   # Базовое использование с авто-определением
   flatten-rust -f ./project -a
@@ -93,19 +302,67 @@ const PROGRESS_STYLE: &str =
   flatten-rust -u
 "##)]
 pub struct Args {
-    /// Базовые папки для обработки
+    /// Какие флаги заданы в командной строке (заполняется при разборе,
+    /// нужно `environment::apply_preset`). Стоит первым: следующие поля
+    /// забирают свои значения из `ArgMatches`.
+    #[command(flatten)]
+    #[serde(skip)]
+    pub(crate) parsed: environment::ParsedMatches,
+
+    /// Базовые папки или отдельные файлы для обработки
     #[arg(long = "folders", short = 'f', num_args = 1..)]
     pub folders: Vec<PathBuf>,
 
-    /// Папки для пропуска при обработке (поддерживаются glob-паттерны)
-    #[arg(long = "skip-folders", short = 's', num_args = 0.., default_values = [
-        ".git", "node_modules", "target", "dist", "build"
-    ])]
+    /// Взять список файлов из файла или stdin (`-`) вместо обхода базовых папок
+    #[arg(long = "files-from", value_name = "PATH", conflicts_with = "folders")]
+    pub files_from: Option<PathBuf>,
+
+    /// Пути в списке `--files-from` разделены NUL, а не переводом строки
+    #[arg(long = "files-from0", requires = "files_from")]
+    pub files_from0: bool,
+
+    /// Папки для пропуска: имена на любой глубине или паттерны пути с `/` от базовой папки
+    #[arg(long = "skip-folders", short = 's', num_args = 0.., default_values = DEFAULT_SKIP_FOLDERS)]
     pub skip_folders: Vec<String>,
 
-    /// Выходной файл
+    /// Выходной файл (`-` — stdout); для существующей директории имя выбирается автоматически.
+    /// Можно указать несколько раз: формат каждого файла определяется по расширению
     #[arg(long = "output", short = 'o', default_value = "codebase.md")]
-    pub output: PathBuf,
+    pub output: Vec<PathBuf>,
+
+    /// Директория для документов с автоматическими именами `<папка>-<yyyymmdd-HHMM>.md`
+    #[arg(long = "output-dir", value_name = "DIR", conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Записать каждую базовую папку в отдельный документ (вместе с `--output-dir`)
+    #[arg(long = "split-per-folder")]
+    pub split_per_folder: bool,
+
+    /// Не предупреждать, что выходной файл лежит внутри базовой папки
+    #[arg(long = "allow-output-inside")]
+    pub allow_output_inside: bool,
+
+    /// Разрешить обработку домашней директории, корня файловой системы и путей
+    /// из `protected_paths` конфигурации
+    #[arg(long = "allow-protected-paths")]
+    pub allow_protected_paths: bool,
+
+    /// Вложенные git-репозитории и подмодули: include, skip или summarize
+    #[arg(long = "submodules", value_enum, default_value = "include")]
+    pub submodules: SubmoduleMode,
+
+    /// Сторонний код (vendor/, third_party/, extern/, вложенные копии проектов):
+    /// include, summarize или skip
+    #[arg(long = "vendored", value_enum)]
+    pub vendored: Option<VendoredMode>,
+
+    /// Считать путь относительно базовой папки сторонним кодом (можно повторять)
+    #[arg(long = "vendored-path", value_name = "RELPATH")]
+    pub vendored_paths: Vec<PathBuf>,
+
+    /// Исключить крейт Cargo workspace по имени пакета (можно повторять)
+    #[arg(long = "exclude-crate", value_name = "NAME")]
+    pub exclude_crates: Vec<String>,
 
     /// Показывать пропущенные папки в дереве структуры
     #[arg(long = "show-skipped", short = 'k')]
@@ -120,11 +377,79 @@ pub struct Args {
     pub max_file_size: u64,
 
     /// Паттерны расширений файлов для пропуска
-    #[arg(long = "skip-extensions", short = 'x', num_args = 0.., default_values = [
-        "exe", "dll", "so", "dylib", "bin", "jar", "apk", "ipa", "msi", "class", "pyc"
-    ])]
+    #[arg(long = "skip-extensions", short = 'x', num_args = 0.., default_values = DEFAULT_SKIP_EXTENSIONS)]
     pub skip_extensions: Vec<String>,
 
+    /// Не использовать список папок для пропуска по умолчанию
+    #[arg(long = "no-default-skips")]
+    pub no_default_skips: bool,
+
+    /// Не использовать список расширений для пропуска по умолчанию
+    #[arg(long = "no-default-extensions")]
+    pub no_default_extensions: bool,
+
+    /// Дополнительные папки для пропуска (добавляются к списку по умолчанию)
+    #[arg(long = "extra-skip-folders", num_args = 1..)]
+    pub extra_skip_folders: Vec<String>,
+
+    /// Дополнительные расширения для пропуска (добавляются к списку по умолчанию)
+    #[arg(long = "extra-skip-extensions", num_args = 1..)]
+    pub extra_skip_extensions: Vec<String>,
+
+    /// Расширения, которые не пропускаются, даже если их исключает шаблон или список
+    #[arg(long = "include-extension", num_args = 1..)]
+    pub include_extensions: Vec<String>,
+
+    /// Паттерны путей, которые никогда не пропускаются (сильнее `.flattenignore`)
+    #[arg(long = "keep", num_args = 1..)]
+    pub keep: Vec<String>,
+
+    /// Не читать файлы `.flattenignore`
+    #[arg(long = "no-flattenignore")]
+    pub no_flattenignore: bool,
+
+    /// Учитывать правила git: `.gitignore`, `.git/info/exclude` и глобальный файл
+    /// исключений (`core.excludesFile`)
+    #[arg(long = "gitignore")]
+    pub gitignore: bool,
+
+    /// Заменять lock-файлы (Cargo.lock, package-lock.json и т.п.) однострочной заглушкой
+    #[arg(long = "skip-lockfiles")]
+    pub skip_lockfiles: bool,
+
+    /// Выводить lock-файлы полностью, даже при `--skip-lockfiles` или `--preset llm`
+    #[arg(long = "keep-lockfiles")]
+    pub keep_lockfiles: bool,
+
+    /// Заменять заглушкой файлы с маркером `@generated` или `DO NOT EDIT` в первых 5 строках
+    #[arg(long = "skip-generated")]
+    pub skip_generated: bool,
+
+    /// Выводить файлы-указатели Git LFS как есть вместо заглушки
+    #[arg(long = "lfs-include-pointers")]
+    pub lfs_include_pointers: bool,
+
+    /// Выводить вместо указателя Git LFS содержимое объекта из `.git/lfs/objects`, если он скачан
+    #[arg(long = "lfs-resolve")]
+    pub lfs_resolve: bool,
+
+    /// Обрабатывать вложенные и повторяющиеся базовые папки по отдельности (файлы попадут в вывод несколько раз)
+    #[arg(long = "allow-overlap")]
+    pub allow_overlap: bool,
+
+    /// Завершаться с кодом 3, если часть файлов не удалось прочитать (по умолчанию — предупреждение и код 0)
+    #[arg(long = "fail-on-error")]
+    pub fail_on_error: bool,
+
+    /// Прерывать запуск, если не удалось записать один из нескольких выходных файлов `-o`
+    /// (по умолчанию — предупреждение, остальные файлы дописываются)
+    #[arg(long = "strict")]
+    pub strict: bool,
+
+    /// Набор шаблонов и флагов: web, python-ml, jvm, llm или свой из конфигурации (см. `--list-presets`)
+    #[arg(long = "preset", value_name = "NAME")]
+    pub preset: Option<String>,
+
     /// Автоматически определять тип проекта и настраивать соответствующие пропуски
     #[arg(long = "auto-detect", short = 'a')]
     pub auto_detect: bool,
@@ -133,22 +458,118 @@ pub struct Args {
     #[arg(long = "include-hidden")]
     pub include_hidden: bool,
 
+    /// Скрытые имена, включаемые без `--include-hidden` (пустой список отключает исключения)
+    #[arg(long = "include-hidden-names", num_args = 0.., default_values = DEFAULT_HIDDEN_NAMES)]
+    pub include_hidden_names: Vec<String>,
+
     /// Максимальная глубина обхода директорий (0 = без ограничений)
     #[arg(long = "max-depth", default_value = "0")]
     pub max_depth: usize,
 
+    /// Глубина, глубже которой обход не спускается, с предупреждением (0 = без ограничений)
+    #[arg(long = "depth-ceiling", default_value = "128")]
+    pub depth_ceiling: usize,
+
+    /// Сколько включенных файлов одной директории выводить до строки-сводки (0 = без ограничений)
+    #[arg(long = "dir-file-limit", default_value = "0")]
+    pub dir_file_limit: usize,
+
+    /// Выбрать файлы, помещающиеся в бюджет размера (`512KB`) или токенов (`32k tokens`);
+    /// остальные перечисляются в конце документа
+    #[arg(long = "fit-budget", value_name = "SIZE|TOKENS", value_parser = budget::parse_budget)]
+    pub fit_budget: Option<u64>,
+
+    /// Порядок выбора файлов под `--fit-budget`: priority, breadth или depth
+    #[arg(long = "fit-strategy", value_enum, default_value = "priority", requires = "fit_budget")]
+    pub fit_strategy: FitStrategy,
+
+    /// Максимальная глубина дерева структуры; на сбор файлов не влияет (0 = без ограничений)
+    #[arg(long = "tree-depth", default_value = "0")]
+    pub tree_depth: usize,
+
+    /// Сколько строк дерева структуры выводить, остальные заменяются пометкой (0 = без ограничений)
+    #[arg(long = "tree-max-entries", default_value = "0")]
+    pub tree_max_entries: usize,
+
+    /// Обходить директории параллельно, начиная чтение файлов до завершения обхода
+    #[arg(long = "parallel-walk")]
+    pub parallel_walk: bool,
+
+    /// Сколько байт прочитанных, но еще не записанных файлов держать в памяти (0 = без ограничений)
+    #[arg(long = "max-in-flight-bytes", default_value = "256MB", value_parser = report::parse_size)]
+    pub max_in_flight_bytes: u64,
+
+    /// Сколько байт распаковывать из одного архива в `-f` (0 = без ограничений)
+    #[arg(long = "max-archive-bytes", default_value = "1GB", value_parser = report::parse_size)]
+    pub max_archive_bytes: u64,
+
+    /// Файлы меньше этого размера читаются обычным чтением, большие отображаются в память
+    /// (0 = отображать все)
+    #[arg(long = "mmap-threshold", default_value = "64KB", value_parser = report::parse_size)]
+    pub mmap_threshold: u64,
+
+    /// Кэшировать обработанное содержимое файлов в директории по хешу содержимого
+    #[arg(long = "content-cache", value_name = "DIR")]
+    pub content_cache: Option<PathBuf>,
+
+    /// Наибольший размер кэша содержимого; давно не использованные записи удаляются
+    /// (0 = без ограничения)
+    #[arg(long = "content-cache-max-size", default_value = "512MB", value_parser = report::parse_size)]
+    pub content_cache_max_size: u64,
+
+    /// Сколько секунд ждать открытия и чтения одного файла (0 = без ограничения)
+    #[arg(long = "file-timeout", default_value = "30")]
+    pub file_timeout: u64,
+
+    /// Наибольшая длительность запуска (`90`, `30s`, `5m`): после нее новые файлы не читаются,
+    /// а документ записывается с пометкой об обрезке
+    #[arg(long = "time-limit", value_name = "DURATION", value_parser = time_limit::parse_duration)]
+    pub time_limit: Option<Duration>,
+
+    /// По истечении `--time-limit` прерывать и начатые чтения файлов
+    #[arg(long = "time-limit-hard", requires = "time_limit")]
+    pub time_limit_hard: bool,
+
+    /// Перечитать один раз файлы, изменившиеся во время запуска, вместо пометки устаревшими
+    #[arg(long = "recheck")]
+    pub recheck: bool,
+
+    /// Показать сводку и запросить подтверждение, если в базовой папке больше файлов (0 = никогда)
+    #[arg(long = "confirm-threshold", default_value = "10000")]
+    pub confirm_threshold: usize,
+
+    /// Не запрашивать подтверждение больших запусков
+    #[arg(long = "yes", short = 'y')]
+    pub yes: bool,
+
     /// Показать детальную статистику после обработки
     #[arg(long = "stats", short = 'S')]
     pub show_stats: bool,
 
+    /// Не выводить в конце сводку предупреждений и список файлов с одинаковыми именами
+    #[arg(long = "quiet", short = 'q')]
+    pub quiet: bool,
+
     /// Тестовый запуск - показать, что будет обработано, без создания выходного файла
     #[arg(long = "dry-run", short = 'd')]
     pub dry_run: bool,
 
+    /// Обзор проекта: дерево, сводка по языкам и только файлы `--priority` (README, манифесты)
+    #[arg(long = "overview")]
+    pub overview: bool,
+
+    /// Вывести после структуры папки зависимости из манифестов (Cargo.toml, package.json и др.)
+    #[arg(long = "deps-summary")]
+    pub deps_summary: bool,
+
     /// Показать список всех доступных шаблонов исключений
     #[arg(long = "list-templates", short = 'l')]
     pub list_templates: bool,
 
+    /// Показать доступные наборы `--preset` и флаги, в которые они раскрываются
+    #[arg(long = "list-presets")]
+    pub list_presets: bool,
+
     /// Включить определенный шаблон исключений
     #[arg(long = "enable-template", short = 'e', num_args = 1..)]
     pub enable_templates: Vec<String>,
@@ -161,9 +582,253 @@ pub struct Args {
     #[arg(long = "force-update", short = 'u')]
     pub force_update: bool,
 
+    /// Не обращаться к API шаблонов: использовать только локальный кэш
+    #[arg(long = "offline", conflicts_with = "force_update")]
+    pub offline: bool,
+
     /// Показать включенные шаблоны
     #[arg(long = "show-enabled")]
     pub show_enabled: bool,
+
+    /// Показать содержимое шаблона в том виде, в каком оно хранится в кэше
+    #[arg(long = "show-template", value_name = "KEY")]
+    pub show_template: Option<String>,
+
+    /// С `--show-template`: вместо содержимого показать правки, внесенные при нормализации
+    #[arg(long = "lint", requires = "show_template")]
+    pub lint: bool,
+
+    /// Количество строк данных в предпросмотре CSV/TSV-файлов (0 = полное содержимое)
+    #[arg(long = "csv-preview-rows", default_value = "20")]
+    pub csv_preview_rows: usize,
+
+    /// Выводить небольшие SVG как текст, а не заглушкой с размерами изображения
+    #[arg(long = "include-svg-source")]
+    pub include_svg_source: bool,
+
+    /// Наибольший размер SVG, выводимого как текст с `--include-svg-source`
+    #[arg(
+        long = "svg-source-limit",
+        default_value = "16KB",
+        value_parser = report::parse_size,
+        requires = "include_svg_source"
+    )]
+    pub svg_source_limit: u64,
+
+    /// Выводить вместо содержимого структуру файла: объявления с номерами строк
+    /// (Rust, Python, TypeScript/JavaScript; остальные языки — по `--head-lines`)
+    #[arg(long = "outline")]
+    pub outline: bool,
+
+    /// Выводить только первые N строк файлов без структуры (0 = полное содержимое)
+    #[arg(long = "head-lines", default_value = "0")]
+    pub head_lines: usize,
+
+    /// Записать статистику выполнения в JSON-файл
+    #[arg(long = "stats-json")]
+    pub stats_json: Option<PathBuf>,
+
+    /// Добавить в конец документа раздел с пропущенным содержимым по причинам и расширениям
+    #[arg(long = "skipped-appendix")]
+    pub skipped_appendix: bool,
+
+    /// Записывать события обработки построчно в JSON для редакторов и IDE (`-` = stdout)
+    #[arg(long = "events", value_name = "PATH")]
+    pub events: Option<PathBuf>,
+
+    /// Объяснить, почему путь включается в вывод или исключается (без создания вывода)
+    #[arg(long = "explain", num_args = 1..)]
+    pub explain: Vec<PathBuf>,
+
+    /// Паттерны файлов, выводимых первыми, в порядке приоритета (пустой список отключает)
+    #[arg(long = "priority", num_args = 0.., default_values = DEFAULT_PRIORITY)]
+    pub priority: Vec<String>,
+
+    /// Записать манифест выходного документа (смещения, размеры и хеши файлов) в JSON-файл
+    #[arg(long = "manifest")]
+    pub manifest: Option<PathBuf>,
+
+    /// Записывать права доступа файлов (`mode: 0755`) и цели символических ссылок
+    #[arg(long = "file-modes")]
+    pub file_modes: bool,
+
+    /// Не дописывать в Markdown-документ строку целостности (число секций и SHA-256)
+    #[arg(long = "no-trailer")]
+    pub no_trailer: bool,
+
+    /// Сохранить итоговую конфигурацию запуска с источниками значений в JSON-файл
+    #[arg(long = "save-invocation", value_name = "FILE")]
+    pub save_invocation: Option<PathBuf>,
+
+    /// Повторить запуск по конфигурации, сохраненной `--save-invocation`
+    #[arg(long = "replay", value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Проверить, что ранее созданный документ соответствует текущим файлам (без создания вывода)
+    #[arg(long = "verify")]
+    pub verify: Option<PathBuf>,
+
+    /// Проверить строку целостности документа: не обрезан и не изменен ли он (без создания вывода)
+    #[arg(long = "check", value_name = "FILE")]
+    pub check: Option<PathBuf>,
+
+    /// Метка базовой папки в выводе вместо ее пути (`имя=путь`, можно повторять)
+    #[arg(long = "alias", num_args = 1..)]
+    pub aliases: Vec<String>,
+
+    /// Группировка файлов в секции содержимого: none, ext, lang или dir
+    #[arg(long = "group-by", value_enum, default_value = "none")]
+    pub group_by: GroupBy,
+
+    /// Порядок обработки нескольких базовых папок: args, smallest-first или
+    /// largest-first; секции документа всегда идут в порядке аргументов
+    #[arg(long = "folder-order", value_enum, default_value = "args")]
+    pub folder_order: FolderOrder,
+
+    /// Сверить файлы дерева структуры с секциями содержимого и завершиться
+    /// ошибкой при расхождении (в отладочных сборках включено всегда)
+    #[arg(long = "self-check")]
+    pub self_check: bool,
+
+    /// Формат выходного документа и служебных отчетов
+    #[arg(long = "format", value_enum, default_value = "markdown")]
+    pub format: OutputFormat,
+
+    /// Запустить HTTP-сервер предпросмотра: HTML по `/`, Markdown по `/raw`, статистика по `/stats.json`
+    #[arg(
+        long = "serve",
+        value_name = "ADDR:PORT",
+        num_args = 0..=1,
+        default_missing_value = "127.0.0.1:8080"
+    )]
+    pub serve: Option<String>,
+
+    /// Оформление секций Markdown-документа: маркеры `### ... ###` или заголовки Markdown
+    #[arg(long = "heading-style", value_enum, default_value = "markers")]
+    pub heading_style: HeadingStyle,
+
+    /// Каталог текстов документа в TOML (заглушки, пометки, заголовки разделов)
+    #[arg(long = "messages", value_name = "FILE")]
+    pub messages: Option<PathBuf>,
+
+    /// Уровень заголовка базовой папки с `--heading-style headings` (остальные глубже)
+    #[arg(
+        long = "base-heading-level",
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..=4)
+    )]
+    pub base_heading_level: u8,
+
+    /// Встроить в HTML-документ скрипт highlight.js из файла для подсветки синтаксиса
+    #[arg(long = "html-highlight-js")]
+    pub html_highlight_js: Option<PathBuf>,
+
+    /// Записать в начало документа YAML-заголовок, а с `per-file` — и перед каждым файлом
+    #[arg(
+        long = "front-matter",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "document"
+    )]
+    pub front_matter: Option<FrontMatter>,
+
+    /// Вывести в начале документа сведения о git-репозитории каждой базовой папки
+    #[arg(long = "git-info")]
+    pub git_info: bool,
+
+    /// Не записывать время создания в заголовки и манифест, чтобы вывод повторялся побайтово
+    #[arg(long = "reproducible")]
+    pub reproducible: bool,
+
+    /// Включать только файлы, содержимое которых совпадает с регулярным выражением (можно указать несколько)
+    #[arg(long = "grep", value_name = "REGEX")]
+    pub grep: Vec<String>,
+
+    /// Включать только файлы, не совпавшие ни с одним `--grep`
+    #[arg(long = "grep-invert", requires = "grep")]
+    pub grep_invert: bool,
+
+    /// Выводить содержимое только файлов внутри этих путей относительно базовой папки;
+    /// дерево структуры по-прежнему показывает всю папку
+    #[arg(long = "content-paths", value_name = "RELPATH", num_args = 1..)]
+    pub content_paths: Vec<PathBuf>,
+
+    /// Разбивать содержимое файлов на фрагменты до N символов (только json и jsonl; 0 — файл целиком)
+    #[arg(long = "chunk-size", default_value = "0")]
+    pub chunk_size: usize,
+
+    /// Сколько символов конца фрагмента повторять в начале следующего
+    #[arg(long = "chunk-overlap", default_value = "0")]
+    pub chunk_overlap: usize,
+
+    /// Окончания строк в выводе: lf, crlf или keep
+    #[arg(long = "normalize-eol", value_enum, default_value = "lf")]
+    pub normalize_eol: EolMode,
+
+    /// Заменить управляющие символы и разделители строк Unicode в содержимом видимыми
+    /// экранами и удалить escape-последовательности ANSI
+    #[arg(long = "sanitize")]
+    pub sanitize: bool,
+
+    /// Вывести итоговые правила исключений после объединения всех источников
+    #[arg(long = "print-exclusions")]
+    pub print_exclusions: bool,
+
+    /// Вывести итоговые значения параметров и их источники (cli, env, preset, default)
+    #[arg(long = "print-config")]
+    pub print_config: bool,
+
+    /// Проверить кэш, конфигурацию и доступ к API шаблонов; код 1, если есть ошибки
+    #[arg(long = "doctor")]
+    pub doctor: bool,
+
+    /// Показать содержимое директории кэша `~/.flatten/` с размерами файлов
+    #[arg(long = "cache-status")]
+    pub cache_status: bool,
+
+    /// Удалить из кэша копии поврежденных файлов старше `--cache-retention` и
+    /// брошенные временные файлы прерванной записи
+    #[arg(long = "cache-prune")]
+    pub cache_prune: bool,
+
+    /// С `--cache-prune`: удалить весь кэш (после подтверждения или с `--yes`)
+    #[arg(long = "all", requires = "cache_prune")]
+    pub all: bool,
+
+    /// Сколько дней хранить копии поврежденных файлов для `--cache-prune`
+    #[arg(long = "cache-retention", value_name = "DAYS", default_value_t = 30)]
+    pub cache_retention: u64,
+
+    /// Использовать ASCII-замены вместо эмодзи в консоли и в выходном файле
+    #[arg(long = "no-emoji")]
+    pub no_emoji: bool,
+
+    /// Ширина консоли в колонках для дерева `--dry-run`, списка шаблонов и полосы
+    /// прогресса (по умолчанию ширина терминала, вне терминала 80)
+    #[arg(long = "width", value_name = "COLUMNS")]
+    pub width: Option<usize>,
+
+    /// Не объединять в дереве цепочки директорий с единственной поддиректорией
+    #[arg(long = "no-collapse-dirs")]
+    pub no_collapse_dirs: bool,
+
+    /// Пометки директорий в дереве: none, counts, sizes или both
+    #[arg(long = "tree-annotations", value_enum, default_value = "none")]
+    pub tree_annotations: TreeAnnotations,
+
+    /// Цветной вывод в терминал: auto, always или never
+    #[arg(long = "color", value_enum, default_value = "auto")]
+    pub color: ColorMode,
+
+    /// Источники значений параметров (заполняется `environment::parse_args`).
+    #[arg(skip)]
+    pub sources: Vec<environment::ResolvedValue>,
+
+    /// Настройки отдельных базовых папок по ключу блока (заполняется
+    /// `environment::parse_process_args` из `~/.flatten/manager_config.json`).
+    #[arg(skip)]
+    pub folder_overrides: BTreeMap<String, FolderOverride>,
 }
 
 /// Конфигурация процесса "сглаживания".
@@ -174,53 +839,348 @@ pub struct Args {
 pub struct FlattenConfig {
     /// Менеджер для работы с шаблонами исключений.
     exclusion_manager: ExclusionManager,
-    /// Набор папок для пропуска.
-    skip_folders: HashSet<String>,
-    /// Набор расширений файлов для пропуска.
-    skip_extensions: HashSet<String>,
+    /// Правила флагов командной строки в порядке указания.
+    user_rules: RuleList,
+    /// Правила включенных шаблонов в порядке включения шаблонов.
+    template_rules: RuleList,
+    /// Встроенные правила по умолчанию (`--skip-folders`, `--skip-extensions`).
+    default_rules: RuleList,
+    /// Правила файлов `.flattenignore` (`None` при `--no-flattenignore`).
+    flatten_ignore: Option<FlattenIgnore>,
+    /// Правила игнорирования git (`None` без `--gitignore`).
+    git_ignore: Option<GitIgnore>,
+    /// Паттерны `--keep`, отменяющие любые исключения.
+    keep: Vec<IgnoreRule>,
+    /// Имена файлов, заменяемых заглушкой (lock-файлы).
+    skip_files: HashSet<String>,
+    /// Заменять ли заглушкой файлы с маркерами генерации.
+    skip_generated: bool,
+    /// Выводить ли указатели Git LFS как есть.
+    lfs_include_pointers: bool,
+    /// Читать ли объекты Git LFS вместо указателей.
+    lfs_resolve: bool,
     /// Показывать ли пропущенные элементы в выводе.
     show_skipped: bool,
+    /// Обработка вложенных репозиториев и подмодулей.
+    submodules: SubmoduleMode,
+    /// Обработка стороннего кода (`--vendored`).
+    vendored: VendoredMode,
+    /// Пути стороннего кода относительно базовой папки (`--vendored-path`).
+    vendored_paths: Vec<PathBuf>,
+    /// Директории крейтов workspace, исключенных `--exclude-crate`.
+    excluded_crates: Vec<PathBuf>,
     /// Максимальный размер файла для обработки.
     max_file_size: u64,
     /// Включать ли скрытые файлы и папки.
     include_hidden: bool,
+    /// Паттерны файлов, выводимых в начале секции содержимого.
+    priority: PriorityRules,
+    /// Скрытые имена, включаемые даже без `include_hidden`.
+    hidden_names: HashSet<String>,
     /// Максимальная глубина рекурсии.
     max_depth: usize,
+    /// Глубина, на которой обход останавливается с предупреждением (0 — без ограничений).
+    depth_ceiling: usize,
+    /// Наибольшее число включенных файлов одной директории (0 — без ограничений).
+    dir_file_limit: usize,
+    /// Бюджет документа в байтах (`--fit-budget`).
+    fit_budget: Option<u64>,
+    /// Порядок выбора файлов под бюджет.
+    fit_strategy: FitStrategy,
+    /// Максимальная глубина дерева структуры (0 — без ограничений).
+    tree_depth: usize,
+    /// Наибольшее число строк дерева структуры (0 — без ограничений).
+    tree_max_entries: usize,
+    /// Обходить ли директории параллельно.
+    parallel_walk: bool,
+    /// Бюджет байт прочитанных, но еще не записанных файлов (0 — без ограничения).
+    max_in_flight_bytes: u64,
+    /// Сколько ждать открытия и чтения одного файла (`None` — без ограничения).
+    file_timeout: Option<Duration>,
+    /// Лимит времени запуска, общий для всех конфигураций папок (`--time-limit`).
+    time_limit: Option<Arc<TimeLimit>>,
+    /// Перечитывать ли файлы, изменившиеся между сбором и чтением (`--recheck`).
+    recheck: bool,
+    /// Число файлов базовой папки, выше которого запрашивается подтверждение (0 — никогда).
+    confirm_threshold: usize,
     /// Показывать ли статистику в конце.
     show_stats: bool,
+    quiet: bool,
     /// Выполнять ли тестовый запуск.
     dry_run: bool,
+    /// Выводить ли только обзор проекта (`--overview`).
+    overview: bool,
+    /// Выводить ли сводку зависимостей из манифестов (`--deps-summary`).
+    deps_summary: bool,
+    /// Пишется ли документ в stdout (`-o -`); служебные сообщения тогда идут в stderr.
+    document_to_stdout: bool,
+    /// Пишется ли поток событий в stdout (`--events -`); служебные сообщения тогда идут в stderr.
+    events_to_stdout: bool,
+    /// Директория для документов с автоматическими именами (`None` — файл `--output`).
+    output_dir: Option<PathBuf>,
+    /// Записывать ли каждую базовую папку в отдельный документ.
+    split_per_folder: bool,
+    /// Канонические пути выходных файлов запуска, лежащих внутри базовых папок.
+    own_outputs: Vec<PathBuf>,
+    /// Подсчитывать ли размер пропущенных файлов и содержимого пропущенных директорий.
+    measure_skipped: bool,
+    /// Дописывать ли в документ раздел пропущенного содержимого (`--skipped-appendix`).
+    skipped_appendix: bool,
+    /// Сверять ли дерево структуры с секциями содержимого (`--self-check`).
+    self_check: bool,
+    /// Записывать ли права доступа файлов и выводить ли символические ссылки (`--file-modes`).
+    file_modes: bool,
+    /// Выводить ли эмодзи (иначе — ASCII-замены).
+    emoji: bool,
+    /// Ширина консоли в колонках для вывода в stdout.
+    console_width: usize,
+    /// Объединять ли в дереве цепочки директорий с единственной поддиректорией.
+    collapse_dirs: bool,
+    /// Какие сведения о директориях выводить в дереве.
+    tree_annotations: TreeAnnotations,
+    /// Уровень заголовка базовой папки (`None` — маркеры `### ... ###`).
+    heading_level: Option<usize>,
+    /// Тексты заглушек, пометок и заголовков разделов (`--messages`).
+    messages: Arc<Messages>,
+    /// Скрипт highlight.js, встраиваемый в HTML-документ.
+    html_highlight_script: Option<String>,
+    /// Разбиение содержимого на фрагменты (`None` — файлы целиком).
+    chunking: Option<ChunkOptions>,
+    /// Файлы из `--files-from` (`None` — базовые папки обходятся).
+    listed_files: Option<Vec<PathBuf>>,
+    /// Архивы из `-f`, распакованные во временные директории.
+    archives: Vec<UnpackedArchive>,
+    /// Фильтр файлов по содержимому (`--grep`).
+    content_filter: Option<ContentFilter>,
+    /// Пути относительно базовой папки, содержимое файлов которых выводится
+    /// (`--content-paths`; пусто — всех).
+    content_paths: Vec<PathBuf>,
+    /// YAML-заголовки документа и файлов (`--front-matter`).
+    front_matter: Option<FrontMatter>,
+    /// Указывать ли SHA-256 в сведениях о файлах (вместе с `--manifest`).
+    front_matter_sha256: bool,
+    /// Дописывать ли строку целостности в документы, записываемые в файл.
+    trailer: bool,
+    /// Выводить ли сведения о git-репозиториях базовых папок.
+    git_info: bool,
+    /// Не записывать ли время создания (`--reproducible`).
+    reproducible: bool,
+    /// Режим приведения окончаний строк содержимого.
+    normalize_eol: EolMode,
+    /// Экранировать ли управляющие символы содержимого (`--sanitize`).
+    sanitize: bool,
+    /// Конвертеры содержимого по расширениям файлов.
+    converters: ConverterRegistry,
+    /// Языки файлов для блоков кода, сводки и группировки.
+    languages: LanguageTable,
+    /// Выводить ли структуру файлов вместо содержимого (`--outline`).
+    outline: bool,
+    /// Сколько первых строк файлов выводить без структуры (0 — все).
+    head_lines: usize,
+    /// Преобразования содержимого в порядке применения, по параметрам выше.
+    transforms: TransformChain,
+    /// Размер, начиная с которого файлы отображаются в память (`--mmap-threshold`).
+    mmap_threshold: u64,
+    /// Кэш обработанного содержимого (`--content-cache`), общий для всех папок.
+    content_cache: Option<Arc<ContentCache>>,
+    /// Конфигурации базовых папок со своим блоком настроек, по каноническому пути.
+    folder_views: Vec<(PathBuf, FlattenConfig)>,
 }
 
 impl FlattenConfig {
     /// Создает новый экземпляр `FlattenConfig` на основе аргументов командной строки.
     ///
     /// Асинхронно инициализирует `ExclusionManager`, загружает и обновляет шаблоны
-    /// исключений, а также обрабатывает команды управления шаблонами.
-    pub async fn new(args: &Args) -> Result<Self> {
-        let mut exclusion_manager = ExclusionManager::new().await?;
+    /// исключений. Команды вывода шаблонов (`--list-templates`, `--show-enabled`,
+    /// `--show-template`) выполняет `run_with`.
+    ///
+    /// # Ошибки
+    /// `FlattenError::TemplateFetch` или `FlattenError::CacheCorrupt`, если шаблоны
+    /// не удалось получить; `FlattenError::InvalidConfig` для неверных паттернов.
+    pub async fn new(args: &Args) -> FlattenResult<Self> {
+        let args = &expand_preset(args)?;
+        if !args.allow_protected_paths {
+            check_protected_paths(&args.folders)?;
+        }
+        let exclusion_manager = Self::load_exclusions(args).await?;
+        Self::with_exclusions(args, exclusion_manager).await
+    }
+
+    /// Собирает и проверяет конфигурацию поверх загруженных шаблонов исключений.
+    async fn with_exclusions(
+        args: &Args,
+        exclusion_manager: ExclusionManager,
+    ) -> FlattenResult<Self> {
+        let mut config = Self::build(args, exclusion_manager)
+            .await
+            .map_err(FlattenError::invalid_config)?;
+        config
+            .build_folder_views(args)
+            .await
+            .map_err(FlattenError::invalid_config)?;
+        config
+            .validate_content_paths(args)
+            .map_err(FlattenError::invalid_config)?;
+        Ok(config)
+    }
+
+    /// Загружает шаблоны исключений: кэш, `--force-update` и шаблоны из аргументов.
+    async fn load_exclusions(args: &Args) -> FlattenResult<ExclusionManager> {
+        #[cfg(feature = "network")]
+        let mut exclusion_manager = if args.offline {
+            ExclusionManager::offline()?
+        } else {
+            ExclusionManager::new()
+                .await
+                .map_err(FlattenError::template_fetch)?
+        };
+        #[cfg(not(feature = "network"))]
+        let mut exclusion_manager = ExclusionManager::offline()?;
 
         if args.force_update {
-            exclusion_manager.force_update_templates().await?;
+            // Как и `status!`, при выводе документа или событий в stdout
+            // сообщения идут в stderr.
+            let to_stderr = args.output.iter().any(|output| is_stdout(output))
+                || args.events.as_deref().is_some_and(is_stdout);
+            let say = |message: String| {
+                if to_stderr {
+                    eprintln!("{}", message)
+                } else {
+                    println!("{}", message)
+                }
+            };
+            say(format!(
+                "{} Force updating exclusion templates from API...",
+                glyph(&REFRESH, !args.no_emoji)
+            ));
+            exclusion_manager
+                .force_update_templates()
+                .await
+                .map_err(FlattenError::template_fetch)?;
+            say(format!("{} Templates updated successfully", glyph(&SUCCESS, !args.no_emoji)));
         }
 
-        if args.list_templates {
-            Self::handle_list_templates(&exclusion_manager).await?;
-            std::process::exit(0);
+        #[cfg(feature = "network")]
+        if !args.offline {
+            Self::fetch_template_args(args, &mut exclusion_manager)
+                .await
+                .map_err(FlattenError::template_fetch)?;
         }
+        Ok(exclusion_manager)
+    }
 
+    /// Выполняет `--list-templates`, `--show-enabled` или `--show-template`.
+    async fn print_templates(args: &Args) -> FlattenResult<()> {
+        let mut exclusion_manager = Self::load_exclusions(args).await?;
+        if args.list_templates {
+            return Ok(Self::handle_list_templates(args, &exclusion_manager).await?);
+        }
         if args.show_enabled {
+            Self::apply_template_args(args, &mut exclusion_manager)
+                .map_err(FlattenError::invalid_config)?;
             Self::handle_show_enabled(&exclusion_manager);
-            std::process::exit(0);
+            return Ok(());
+        }
+        if let Some(key) = &args.show_template {
+            Self::handle_show_template(key, args.lint, &exclusion_manager)
+                .map_err(FlattenError::invalid_config)?;
         }
+        Ok(())
+    }
 
-        for template in &args.enable_templates {
-            exclusion_manager.enable_template(template.clone());
+    /// Проверяет, что каждый путь `--content-paths` есть хотя бы в одной базовой папке.
+    fn validate_content_paths(&self, args: &Args) -> Result<()> {
+        let bases: Vec<PathBuf> = match &self.listed_files {
+            Some(files) => vec![file_list::common_ancestor(files)],
+            None => args
+                .folders
+                .iter()
+                .map(|folder| {
+                    self.unpacked_root(folder)
+                        .map_or_else(|| folder.clone(), Path::to_path_buf)
+                })
+                .collect(),
+        };
+        for path in &self.content_paths {
+            if !bases.iter().any(|base| base.join(path).exists()) {
+                anyhow::bail!(
+                    "--content-paths {} does not exist under any base folder",
+                    path.display()
+                );
+            }
         }
+        Ok(())
+    }
 
-        for template in &args.disable_templates {
-            exclusion_manager.disable_template(template);
+    /// Строит конфигурации базовых папок, для которых в `args.folder_overrides`
+    /// есть блок настроек: общие аргументы, поверх которых применен блок.
+    ///
+    /// Шаблоны берутся из кэша, уже обновленного общей конфигурацией.
+    async fn build_folder_views(&mut self, args: &Args) -> Result<()> {
+        if self.listed_files.is_some() {
+            return Ok(());
+        }
+        for folder in &args.folders {
+            let Some((key, block)) = folder_overrides::find(&args.folder_overrides, folder) else {
+                continue;
+            };
+            let base = self
+                .unpacked_root(folder)
+                .map_or_else(|| folder.clone(), Path::to_path_buf);
+            // Несуществующая папка попадет в отчет при обходе.
+            let Ok(canonical) = base.canonicalize() else {
+                continue;
+            };
+            let mut folder_args = args.clone();
+            folder_args.folders = vec![base];
+            folder_args.folder_overrides.clear();
+            block
+                .apply(&mut folder_args)
+                .with_context(|| format!("Invalid settings for folder \"{}\"", key))?;
+            #[cfg(feature = "network")]
+            let exclusion_manager = {
+                let mut exclusion_manager = ExclusionManager::offline()?;
+                if !args.offline {
+                    Self::fetch_template_args(&folder_args, &mut exclusion_manager).await?;
+                }
+                exclusion_manager
+            };
+            #[cfg(not(feature = "network"))]
+            let exclusion_manager = ExclusionManager::offline()?;
+            let mut view = Self::build(&folder_args, exclusion_manager).await?;
+            view.content_cache.clone_from(&self.content_cache);
+            view.time_limit.clone_from(&self.time_limit);
+            self.folder_views.push((canonical, view));
+        }
+        Ok(())
+    }
+
+    /// Конфигурация, по которой обрабатывается базовая папка: собственная, если
+    /// для папки задан блок настроек, иначе общая.
+    pub fn for_folder(&self, base_folder: &Path) -> &FlattenConfig {
+        if self.folder_views.is_empty() {
+            return self;
+        }
+        let Ok(canonical) = base_folder.canonicalize() else {
+            return self;
+        };
+        self.folder_views
+            .iter()
+            .find(|(folder, _)| *folder == canonical)
+            .map_or(self, |(_, view)| view)
+    }
+
+    /// Запоминает выходные файлы запуска во всех конфигурациях папок.
+    fn set_own_outputs(&mut self, outputs: Vec<PathBuf>) {
+        for (_, view) in &mut self.folder_views {
+            view.own_outputs.clone_from(&outputs);
         }
+        self.own_outputs = outputs;
+    }
+
+    /// Собирает конфигурацию из аргументов поверх готового `ExclusionManager`.
+    async fn build(args: &Args, mut exclusion_manager: ExclusionManager) -> Result<Self> {
+        Self::apply_template_args(args, &mut exclusion_manager)?;
 
         if args.auto_detect && !args.folders.is_empty() {
             for folder in &args.folders {
@@ -232,494 +1192,3603 @@ impl FlattenConfig {
             }
         }
 
+        let messages = Arc::new(match &args.messages {
+            Some(path) => Messages::load(path)?,
+            None => Messages::default(),
+        });
+        let mut converters = ConverterRegistry::builtin();
+        if args.csv_preview_rows > 0 {
+            converters.register(
+                "csv",
+                Arc::new(CsvPreviewConverter::csv(args.csv_preview_rows, args.max_file_size)),
+            );
+            converters.register(
+                "tsv",
+                Arc::new(CsvPreviewConverter::tsv(args.csv_preview_rows, args.max_file_size)),
+            );
+        }
+        let images = Arc::new(ImageConverter::new(
+            args.include_svg_source.then_some(args.svg_source_limit),
+            messages.clone(),
+        ));
+        for (extension, _) in ImageFormat::EXTENSIONS {
+            converters.register(extension, images.clone());
+        }
+
+        let mut languages = LanguageTable::builtin();
+        languages.extend(&exclusion_manager.template_manager().config().languages);
+        let hidden_names = args
+            .include_hidden_names
+            .iter()
+            .chain(&exclusion_manager.template_manager().config().include_hidden_names)
+            .cloned()
+            .collect();
+
+        let html_highlight_script = match &args.html_highlight_js {
+            Some(path) => Some(std::fs::read_to_string(path).with_context(|| {
+                format!("Failed to read highlight.js script: {}", path.display())
+            })?),
+            None => None,
+        };
+
+        let formats: Vec<OutputFormat> =
+            args.output.iter().map(|output| output_format(args, output)).collect();
+        let heading_level = match args.heading_style {
+            HeadingStyle::Markers => None,
+            HeadingStyle::Headings if !formats.contains(&OutputFormat::Markdown) => {
+                anyhow::bail!("--heading-style headings requires --format markdown")
+            }
+            HeadingStyle::Headings => Some(usize::from(args.base_heading_level)),
+        };
+
+        // Сохраненный `\r` нарушил бы гарантию `--sanitize`, а экранированный — `crlf`.
+        if args.sanitize && args.normalize_eol == EolMode::Crlf {
+            anyhow::bail!("--sanitize cannot be combined with --normalize-eol crlf");
+        }
+
+        let chunking = match args.chunk_size {
+            0 => None,
+            _ if !formats
+                .iter()
+                .any(|format| matches!(format, OutputFormat::Json | OutputFormat::Jsonl)) =>
+            {
+                anyhow::bail!("--chunk-size requires --format json or jsonl")
+            }
+            size => Some(ChunkOptions::new(size, args.chunk_overlap)?),
+        };
+
+        // Пробный запуск не читает содержимое и не создает директорию кэша.
+        let content_cache = match &args.content_cache {
+            Some(directory) if !args.dry_run => Some(Arc::new(ContentCache::open(
+                directory,
+                args.content_cache_max_size,
+            )?)),
+            _ => None,
+        };
+
+        let content_paths = args
+            .content_paths
+            .iter()
+            .map(|path| relative_arg("--content-paths", path))
+            .collect::<Result<Vec<_>>>()?;
+        let vendored_paths = args
+            .vendored_paths
+            .iter()
+            .map(|path| relative_arg("--vendored-path", path))
+            .collect::<Result<Vec<_>>>()?;
+        let excluded_crates = excluded_crates(&args.folders, &args.exclude_crates)?;
+
+        let listed_files = match &args.files_from {
+            Some(source) => {
+                let files = file_list::read_file_list(source, args.files_from0)?;
+                if files.is_empty() {
+                    anyhow::bail!("--files-from list is empty: {}", source.display());
+                }
+                Some(files)
+            }
+            None => None,
+        };
+        let content_filter = (!args.grep.is_empty())
+            .then(|| ContentFilter::new(&args.grep, args.grep_invert))
+            .transpose()?;
+
+        let output_dir = match args.output.as_slice() {
+            [output] if output.is_dir() => Some(output.clone()),
+            _ => args.output_dir.clone(),
+        };
+        if args.output.len() > 1 {
+            if let Some(directory) = args.output.iter().find(|output| output.is_dir()) {
+                anyhow::bail!(
+                    "-o {} is a directory; a directory cannot be combined with other outputs",
+                    directory.display()
+                );
+            }
+            if args.output.iter().filter(|output| *output == Path::new("-")).count() > 1 {
+                anyhow::bail!("Only one --output can be -");
+            }
+        }
+        let document_to_stdout = !args.dry_run
+            && output_dir.is_none()
+            && args.output.iter().any(|output| output == Path::new("-"));
+        if args.split_per_folder {
+            if output_dir.is_none() {
+                anyhow::bail!("--split-per-folder requires --output-dir or -o <DIR>");
+            }
+            if args.manifest.is_some() {
+                anyhow::bail!("--manifest cannot be combined with --split-per-folder");
+            }
+        }
+        let events_to_stdout = args.events.as_deref() == Some(Path::new("-"));
+        if events_to_stdout {
+            if args.dry_run {
+                anyhow::bail!("--events - cannot be combined with --dry-run");
+            }
+            if document_to_stdout {
+                anyhow::bail!("--events - cannot be combined with -o -");
+            }
+        }
+
         let mut config = Self {
-            skip_folders: args.skip_folders.iter().cloned().collect(),
-            skip_extensions: args.skip_extensions.iter().cloned().collect(),
+            user_rules: RuleList::default(),
+            template_rules: RuleList::default(),
+            default_rules: RuleList::default(),
+            flatten_ignore: (!args.no_flattenignore).then(FlattenIgnore::default),
+            git_ignore: args
+                .gitignore
+                .then(|| GitIgnore::new(gitignore::default_excludes_file())),
+            keep: args
+                .keep
+                .iter()
+                .enumerate()
+                .map(|(index, pattern)| IgnoreRule::parse(pattern, index + 1))
+                .filter_map(Result::transpose)
+                .collect::<Result<_>>()?,
+            skip_files: if args.skip_lockfiles && !args.keep_lockfiles {
+                DEFAULT_LOCKFILES.iter().map(|name| name.to_string()).collect()
+            } else {
+                HashSet::new()
+            },
+            skip_generated: args.skip_generated,
+            lfs_include_pointers: args.lfs_include_pointers,
+            lfs_resolve: args.lfs_resolve,
             show_skipped: args.show_skipped,
+            submodules: args.submodules,
             max_file_size: args.max_file_size,
             include_hidden: args.include_hidden,
+            hidden_names,
+            priority: PriorityRules::new(&args.priority)?,
             max_depth: args.max_depth,
+            depth_ceiling: args.depth_ceiling,
+            dir_file_limit: args.dir_file_limit,
+            fit_budget: args.fit_budget,
+            fit_strategy: args.fit_strategy,
+            tree_depth: args.tree_depth,
+            tree_max_entries: args.tree_max_entries,
+            // Готовый список, `--grep` и `--front-matter` требуют знать файлы
+            // до вывода дерева, а `--overview` и `--content-paths` читают не все файлы.
+            parallel_walk: args.parallel_walk
+                && !args.overview
+                && listed_files.is_none()
+                && content_filter.is_none()
+                && args.content_paths.is_empty()
+                && args.front_matter.is_none(),
+            max_in_flight_bytes: args.max_in_flight_bytes,
+            file_timeout: (args.file_timeout > 0).then(|| Duration::from_secs(args.file_timeout)),
+            time_limit: args
+                .time_limit
+                .map(|limit| Arc::new(TimeLimit::new(limit, args.time_limit_hard))),
+            recheck: args.recheck,
+            confirm_threshold: if args.yes { 0 } else { args.confirm_threshold },
             show_stats: args.show_stats,
+            quiet: args.quiet,
             dry_run: args.dry_run,
+            overview: args.overview,
+            deps_summary: args.deps_summary,
+            document_to_stdout,
+            events_to_stdout,
+            output_dir,
+            split_per_folder: args.split_per_folder,
+            own_outputs: Vec::new(),
+            measure_skipped: args.show_stats || args.stats_json.is_some() || args.skipped_appendix,
+            skipped_appendix: args.skipped_appendix,
+            self_check: args.self_check || cfg!(debug_assertions),
+            file_modes: args.file_modes,
+            emoji: !args.no_emoji,
+            console_width: terminal::width(&console::Term::stdout(), args.width),
+            collapse_dirs: !args.no_collapse_dirs,
+            tree_annotations: args.tree_annotations,
+            heading_level,
+            messages,
+            html_highlight_script,
+            chunking,
+            listed_files,
+            archives: Vec::new(),
+            content_filter,
+            content_paths,
+            vendored: args.vendored.unwrap_or(match vendored_paths.is_empty() {
+                true => VendoredMode::Include,
+                false => VendoredMode::Summarize,
+            }),
+            vendored_paths,
+            excluded_crates,
+            front_matter: args.front_matter,
+            front_matter_sha256: args.manifest.is_some(),
+            trailer: !args.no_trailer,
+            git_info: args.git_info,
+            reproducible: args.reproducible,
+            normalize_eol: args.normalize_eol,
+            sanitize: args.sanitize,
+            converters,
+            languages,
+            outline: args.outline,
+            head_lines: args.head_lines,
+            transforms: TransformChain::default(),
+            mmap_threshold: args.mmap_threshold,
+            content_cache,
             exclusion_manager,
+            folder_views: Vec::new(),
+        };
+        config.transforms = TransformChain::from_config(&config);
+
+        // Явный список заменяет умолчания и относится к командной строке;
+        // `--include-extension` добавляется последним и побеждает в своем слое.
+        let cli_origin = |values: &[String], defaults: &[&str], disabled: bool| {
+            if !values.iter().map(String::as_str).eq(defaults.iter().copied()) {
+                Some(PatternOrigin::UserFlag)
+            } else if disabled {
+                None
+            } else {
+                Some(PatternOrigin::Default)
+            }
         };
+        if let Some(origin) =
+            cli_origin(&args.skip_folders, DEFAULT_SKIP_FOLDERS, args.no_default_skips)
+        {
+            for folder in &args.skip_folders {
+                let target = RuleTarget::folder(folder)?;
+                config.add_rule(ExclusionRule::exclude(target, origin.clone()));
+            }
+        }
+        if let Some(origin) = cli_origin(
+            &args.skip_extensions,
+            DEFAULT_SKIP_EXTENSIONS,
+            args.no_default_extensions,
+        ) {
+            for extension in &args.skip_extensions {
+                let target = RuleTarget::Extension(extension.clone());
+                config.add_rule(ExclusionRule::exclude(target, origin.clone()));
+            }
+        }
+        for folder in &args.extra_skip_folders {
+            let target = RuleTarget::folder(folder)?;
+            config.add_rule(ExclusionRule::exclude(target, PatternOrigin::UserFlag));
+        }
+        for extension in &args.extra_skip_extensions {
+            let target = RuleTarget::Extension(normalize_extension(extension));
+            config.add_rule(ExclusionRule::exclude(target, PatternOrigin::UserFlag));
+        }
+        for extension in &args.include_extensions {
+            config.add_rule(ExclusionRule {
+                target: RuleTarget::Extension(normalize_extension(extension)),
+                negated: true,
+                origin: PatternOrigin::UserFlag,
+            });
+        }
 
-        let folder_patterns = config.exclusion_manager.get_folder_patterns().await;
-        let extension_patterns = config.exclusion_manager.get_extension_patterns().await;
+        for source in config.exclusion_manager.pattern_sources() {
+            if let Some(rule) = ExclusionRule::from_template(source)? {
+                config.add_rule(rule);
+            }
+        }
 
-        config.skip_folders.extend(folder_patterns);
-        config.skip_extensions.extend(extension_patterns);
+        // Записи архивов отбираются уже готовыми правилами исключений.
+        if config.listed_files.is_none() {
+            let mut archives = Vec::new();
+            for folder in &args.folders {
+                if let Some(kind) = ArchiveKind::detect(folder) {
+                    let action = |root: &Path, path: &Path, is_dir| {
+                        config.archive_entry_action(root, path, is_dir)
+                    };
+                    let limits = UnpackLimits {
+                        action: &action,
+                        max_file_size: config.max_file_size,
+                        max_total_bytes: args.max_archive_bytes,
+                    };
+                    archives.push(UnpackedArchive::unpack(folder, kind, &limits)?);
+                }
+            }
+            config.archives = archives;
+        }
 
         Ok(config)
     }
 
+    /// Решение для записи архива по правилам исключений, до ее распаковки.
+    fn archive_entry_action(&self, root: &Path, path: &Path, is_dir: bool) -> EntryAction {
+        // Сторонний код распаковывается: его сводке нужны файлы.
+        if self.rule_skip_reason(root, path).is_some() {
+            EntryAction::Skip
+        } else if !is_dir && self.file_skip_reason(root, path).is_some() {
+            EntryAction::Placeholder
+        } else {
+            EntryAction::Unpack
+        }
+    }
+
+    /// Регистрирует конвертер для расширения `ext` (без точки, без учета регистра).
+    ///
+    /// Конвертер заменяет встроенный для того же расширения и получает "сырые"
+    /// байты файла до декодирования в UTF-8 (см. модуль `converters`).
+    pub fn register_converter(&mut self, ext: &str, converter: Arc<dyn FileConverter>) {
+        self.converters.register(ext, converter);
+    }
+
+    /// Добавляет правило в список его слоя.
+    fn add_rule(&mut self, rule: ExclusionRule) {
+        match rule.origin {
+            PatternOrigin::Default => self.default_rules.push(rule),
+            PatternOrigin::UserFlag => self.user_rules.push(rule),
+            PatternOrigin::Template(_) => self.template_rules.push(rule),
+        }
+    }
+
+    /// Слои правил от старшего к младшему (без `.flattenignore`, который
+    /// стоит между командной строкой и шаблонами).
+    fn rule_layers(&self) -> [&RuleList; 3] {
+        [&self.user_rules, &self.template_rules, &self.default_rules]
+    }
+
     /// Обрабатывает команду вывода списка доступных шаблонов.
-    async fn handle_list_templates(exclusion_manager: &ExclusionManager) -> Result<()> {
+    async fn handle_list_templates(
+        args: &Args,
+        exclusion_manager: &ExclusionManager,
+    ) -> Result<()> {
         let templates = exclusion_manager.get_available_templates().await;
+        if exclusion_manager.template_manager().is_partial() {
+            println!(
+                "The full template list hasn't been fetched yet; only {} individually \
+                 fetched templates are cached. Run with network access or --force-update.",
+                templates.len()
+            );
+            println!();
+        }
         println!("Available exclusion templates ({} total):", templates.len());
         println!();
         let mut sorted_templates = templates;
         sorted_templates.sort();
-        for chunk in sorted_templates.chunks(5) {
-            println!("  {}", chunk.join(", "));
+        let width = terminal::width(&console::Term::stdout(), args.width);
+        for line in terminal::columns(&sorted_templates, width, 2) {
+            println!("{}", line);
         }
         Ok(())
     }
 
     /// Обрабатывает команду вывода списка включенных шаблонов.
     fn handle_show_enabled(exclusion_manager: &ExclusionManager) {
-        let enabled = exclusion_manager.get_enabled_templates();
+        let mut enabled = exclusion_manager.get_enabled_templates();
+        enabled.sort_unstable();
         if enabled.is_empty() {
             println!("No templates currently enabled.");
         } else {
             println!("Enabled templates ({}):", enabled.len());
             for template in enabled {
-                println!("  - {}", template);
+                match exclusion_manager.requested_as(template) {
+                    Some(requested) => println!("  - {} (requested as {})", template, requested),
+                    None => println!("  - {}", template),
+                }
             }
         }
     }
 
-    /// Проверяет, следует ли пропустить данный путь (директорию).
-    fn should_skip_path(&self, path: &Path) -> bool {
-        if let Some(name) = path.file_name()
-           && let Some(name_str) = name.to_str() {
-            if !self.include_hidden && name_str.starts_with('.') {
-                return true;
-            }
-            return self.skip_folders.contains(name_str);
+    /// Обрабатывает команду вывода шаблона (`--show-template`, `--lint`).
+    fn handle_show_template(
+        key: &str,
+        lint: bool,
+        exclusion_manager: &ExclusionManager,
+    ) -> Result<()> {
+        let key = exclusion_manager.resolve_template_key(key)?;
+        let Some(template) = exclusion_manager.template_manager().get_template(&key) else {
+            anyhow::bail!("Template '{}' is not cached; run with network access to fetch it", key);
+        };
+        if !lint {
+            println!("{}", template.contents);
+            return Ok(());
         }
-        false
+        println!(
+            "Template {}: {} lines stored, {}",
+            key,
+            template.contents.lines().count(),
+            template.lint
+        );
+        Ok(())
     }
 
-    /// Проверяет, следует ли пропустить данный файл (по расширению).
-    fn should_skip_file(&self, path: &Path) -> bool {
-        if let Some(extension) = path.extension()
-            && let Some(ext_str) = extension.to_str() {
-            return self.skip_extensions.contains(ext_str);
+    /// Догружает шаблоны, которых нет в кэше: по одному для `--enable-template`,
+    /// полный список для `--list-templates` и `--auto-detect`.
+    #[cfg(feature = "network")]
+    async fn fetch_template_args(
+        args: &Args,
+        exclusion_manager: &mut ExclusionManager,
+    ) -> Result<()> {
+        if args.list_templates || args.auto_detect {
+            exclusion_manager.ensure_full_template_list().await?;
         }
-        false
-    }
-}
-
-/// Основная функция-точка входа для запуска процесса "сглаживания".
-///
-/// # Аргументы
-/// * `args` - Ссылка на структуру `Args` с параметрами командной строки.
-///
-/// # Ошибки
-/// Возвращает ошибку, если возникают проблемы с файловыми операциями,
-/// настройкой потоков или обработкой данных.
-pub async fn run(args: &Args) -> Result<()> {
-    if (args.list_templates
-        || args.show_enabled
-        || args.force_update
-        || !args.enable_templates.is_empty()
-        || !args.disable_templates.is_empty())
-        && args.folders.is_empty()
-    {
-        let _ = FlattenConfig::new(args).await?;
-        return Ok(());
+        for template in args.enable_templates.iter().chain(&args.show_template) {
+            exclusion_manager.fetch_missing_template(template).await?;
+        }
+        Ok(())
     }
 
-    if args.folders.is_empty() {
-        return Err(anyhow::anyhow!("Error: --folders argument is required. Use --help for more information."));
-    }
+    /// Включает и отключает шаблоны из `--enable-template` и `--disable-template`.
+    fn apply_template_args(args: &Args, exclusion_manager: &mut ExclusionManager) -> Result<()> {
+        for template in &args.enable_templates {
+            match exclusion_manager.enable_template(template) {
+                // Снимок мог сохранить шаблон, которого больше нет.
+                Err(error) if args.replay.is_some() => {
+                    eprintln!("Warning: replayed template ignored: {:#}", error)
+                }
+                result => {
+                    result?;
+                }
+            }
+        }
 
-    if args.threads > 0 {
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(args.threads)
-            .build_global()
-            .context("Failed to configure thread pool")?;
+        for template in &args.disable_templates {
+            exclusion_manager.disable_template(template);
+        }
+        Ok(())
+    }
+
+    /// Проверяет, следует ли пропустить данный путь (директорию) внутри `base`.
+    #[cfg(test)]
+    fn should_skip_path(&self, base: &Path, path: &Path) -> bool {
+        self.path_skip_reason(base, path).is_some()
+    }
+
+    /// Возвращает причину пропуска пути (директории), если он должен быть пропущен.
+    ///
+    /// Паттерны с разделителями сопоставляются с путем относительно `base`.
+    /// Сторонний код (`--vendored`) проверяется после правил: директория,
+    /// исключенная ими, в сводку стороннего кода не попадает.
+    fn path_skip_reason(&self, base: &Path, path: &Path) -> Option<SkipReason> {
+        let reason = self.rule_skip_reason(base, path);
+        if reason.is_none()
+            && self.vendored != VendoredMode::Include
+            && !self.is_kept(base, path)
+            && vendored::is_vendored(base, path, &self.vendored_paths)
+        {
+            return Some(SkipReason::Vendored);
+        }
+        reason
+    }
+
+    /// Причина пропуска пути по правилам исключений, без учета стороннего кода.
+    fn rule_skip_reason(&self, base: &Path, path: &Path) -> Option<SkipReason> {
+        let name_str = path.file_name()?.to_str()?;
+        if self.is_own_output(path) {
+            return Some(SkipReason::Output);
+        }
+        if self.is_kept(base, path) {
+            return None;
+        }
+        if self.excluded_crates.iter().any(|directory| directory == path) {
+            return Some(SkipReason::Crate);
+        }
+        if self.submodules != SubmoduleMode::Include && git_info::is_nested_repository(path) {
+            return Some(SkipReason::Submodule);
+        }
+        if !self.include_hidden
+            && name_str.starts_with('.')
+            && !self.hidden_names.contains(name_str)
+        {
+            return Some(SkipReason::Hidden);
+        }
+        let name = path.file_name()?;
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        if self
+            .user_rules
+            .path_rule(name, relative)
+            .is_some_and(|rule| !rule.negated)
+        {
+            return Some(SkipReason::Folder);
+        }
+        if let Some(flatten_ignore) = &self.flatten_ignore {
+            if name_str == FLATTENIGNORE_FILE {
+                return Some(SkipReason::FlattenIgnore);
+            }
+            match flatten_ignore.verdict(base, path) {
+                Some(verdict) if verdict.ignored => return Some(SkipReason::FlattenIgnore),
+                Some(_) => return None,
+                None => {}
+            }
+        }
+        match self.gitignore_verdict(base, path) {
+            Some(verdict) if verdict.ignored => return Some(SkipReason::GitIgnore),
+            Some(_) => return None,
+            None => {}
+        }
+        if let Some(rule) = self.template_rules.path_rule(name, relative) {
+            return (!rule.negated).then_some(SkipReason::Template);
+        }
+        self.default_rules
+            .path_rule(name, relative)
+            .filter(|rule| !rule.negated)
+            .map(|_| SkipReason::Folder)
+    }
+
+    /// Возвращает правило имени или пути, решившее судьбу пути, с учетом
+    /// старшинства слоев (без `.flattenignore`).
+    fn path_rule(&self, base: &Path, path: &Path) -> Option<&ExclusionRule> {
+        let name = path.file_name()?;
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        self.rule_layers()
+            .into_iter()
+            .find_map(|rules| rules.path_rule(name, relative))
+    }
+
+    /// Возвращает правило расширения, решившее судьбу файла, с учетом
+    /// старшинства слоев (без `.flattenignore`).
+    fn extension_rule(&self, path: &Path) -> Option<&ExclusionRule> {
+        let extension = path.extension()?.to_str()?;
+        self.rule_layers()
+            .into_iter()
+            .find_map(|rules| rules.extension_rule(extension))
+    }
+
+    /// Директория с содержимым архива `input` из `-f`, если это архив.
+    fn unpacked_root(&self, input: &Path) -> Option<&Path> {
+        self.archives
+            .iter()
+            .find(|archive| archive.source == input)
+            .map(|archive| archive.root.as_path())
+    }
+
+    /// Путь из `-f` для базовой папки: архив вместо директории с его содержимым.
+    fn input_path<'a>(&'a self, folder: &'a Path) -> &'a Path {
+        self.archives
+            .iter()
+            .find(|archive| archive.root == folder)
+            .map_or(folder, |archive| archive.source.as_path())
+    }
+
+    /// Является ли путь выходным файлом этого запуска.
+    ///
+    /// Путь канонизируется, только если совпало имя файла.
+    fn is_own_output(&self, path: &Path) -> bool {
+        self.own_outputs
+            .iter()
+            .any(|output| output.file_name() == path.file_name())
+            && path
+                .canonicalize()
+                .is_ok_and(|path| self.own_outputs.contains(&path))
+    }
+
+    /// Выводится ли содержимое файла `path` внутри `base` (`--content-paths`).
+    ///
+    /// Базовый файл из `-f` выводится всегда.
+    fn is_content_path(&self, base: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        self.content_paths.is_empty()
+            || relative.as_os_str().is_empty()
+            || self
+                .content_paths
+                .iter()
+                .any(|content_path| relative.starts_with(content_path))
+    }
+
+    /// Совпадает ли путь с одним из паттернов `--keep`.
+    fn is_kept(&self, base: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        self.keep.iter().any(|rule| rule.matches(relative, path))
+    }
+
+    /// Возвращает решение `.flattenignore` для пути, если какое-либо правило совпало.
+    fn flattenignore_verdict(&self, base: &Path, path: &Path) -> Option<IgnoreVerdict> {
+        self.flatten_ignore.as_ref()?.verdict(base, path)
+    }
+
+    /// Возвращает решение правил git для пути, если какое-либо правило совпало.
+    fn gitignore_verdict(&self, base: &Path, path: &Path) -> Option<IgnoreVerdict> {
+        self.git_ignore.as_ref()?.verdict(base, path)
+    }
+
+    /// Возвращает ли путь правило `.flattenignore` или, если оно не совпало, git (`!`).
+    fn is_returned(&self, base: &Path, path: &Path) -> bool {
+        self.flattenignore_verdict(base, path)
+            .or_else(|| self.gitignore_verdict(base, path))
+            .is_some_and(|verdict| !verdict.ignored)
+    }
+
+    /// Проверяет, следует ли пропустить данный файл внутри `base` (по расширению).
+    #[cfg(test)]
+    fn should_skip_file(&self, base: &Path, path: &Path) -> bool {
+        self.file_skip_reason(base, path).is_some()
+    }
+
+    /// Возвращает причину пропуска файла по расширению, если он должен быть пропущен.
+    ///
+    /// `--keep` отменяет пропуск. Правила расширений командной строки сильнее
+    /// `.flattenignore`, а возвращающие правила `.flattenignore` и git (`!`)
+    /// сильнее шаблонов и умолчаний. Файл, указанный в `-f` как базовый (`path == base`),
+    /// не пропускается.
+    fn file_skip_reason(&self, base: &Path, path: &Path) -> Option<SkipReason> {
+        if path == base {
+            return None;
+        }
+        // Символическая ссылка видна только с `--file-modes` и не читается.
+        if self.file_modes && path.is_symlink() {
+            return Some(SkipReason::Symlink);
+        }
+        if self.is_kept(base, path) {
+            return None;
+        }
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let user_rule = extension.and_then(|extension| self.user_rules.extension_rule(extension));
+        if user_rule.is_some_and(|rule| !rule.negated) {
+            return Some(SkipReason::Extension);
+        }
+        if self.is_returned(base, path) {
+            return None;
+        }
+        if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| self.skip_files.contains(name))
+        {
+            return Some(SkipReason::Lockfile);
+        }
+        if user_rule.is_some() {
+            return None;
+        }
+        let extension = extension?;
+        if let Some(rule) = self.template_rules.extension_rule(extension) {
+            return (!rule.negated).then_some(SkipReason::Template);
+        }
+        self.default_rules
+            .extension_rule(extension)
+            .filter(|rule| !rule.negated)
+            .map(|_| SkipReason::Extension)
+    }
+
+    /// Возвращает ключ шаблона, паттерн которого исключил путь.
+    ///
+    /// Директории и файлы, исключенные по имени, проверяются по правилам путей,
+    /// остальные файлы — по расширению.
+    fn skip_template(&self, base: &Path, path: &Path) -> Option<&str> {
+        let rule = match self.path_skip_reason(base, path) {
+            Some(SkipReason::Template) => self.path_rule(base, path),
+            Some(_) => None,
+            None => match self.file_skip_reason(base, path) {
+                Some(SkipReason::Template) => self.extension_rule(path),
+                _ => None,
+            },
+        };
+        match rule.map(|rule| &rule.origin) {
+            Some(PatternOrigin::Template(source)) => Some(&source.template),
+            _ => None,
+        }
+    }
+}
+
+/// Приводит расширение к виду без ведущей точки (`.json` и `*.json` → `json`).
+fn normalize_extension(extension: &str) -> String {
+    extension
+        .trim_start_matches("*.")
+        .trim_start_matches('.')
+        .to_string()
+}
+
+/// Основная функция-точка входа для запуска процесса "сглаживания".
+///
+/// # Аргументы
+/// * `args` - Ссылка на структуру `Args` с параметрами командной строки.
+///
+/// Возвращает отчет о запуске; для служебных команд (`--print-config`,
+/// `--cache-status` и т. п.) он пуст.
+///
+/// # Ошибки
+/// Возвращает `FlattenError`: например, `OutputIo`, если файл вывода не удалось
+/// записать, или `Partial`, если документ записан, но часть файлов не прочиталась.
+pub async fn run(args: &Args) -> FlattenResult<FlattenReport> {
+    let progress = IndicatifProgress::with_width(args.width);
+    run_with(args, Some(Arc::new(progress))).await
+}
+
+/// Синхронный вариант `run` для вызова вне асинхронного контекста.
+///
+/// Рантайм `tokio` создается, только если он нужен для загрузки шаблонов
+/// (feature `network` без `--offline`); иначе будущее выполняется на
+/// встроенном минимальном исполнителе. Функцию можно вызывать и из потока,
+/// где уже работает рантайм `tokio`: тогда свой рантайм запускается в
+/// отдельном потоке.
+///
+/// Ни одна команда не завершает процесс: служебные команды (`--doctor`,
+/// `--check`, `--list-templates` и т. п.) печатают результат и возвращают
+/// пустой отчет или `FlattenError`, поэтому функцию можно вызывать из
+/// долгоживущего процесса.
+///
+/// # Ошибки
+/// Те же, что у `run`, а также ошибка создания рантайма.
+///
+/// # Examples
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use clap::Parser;
+/// use flatten_rust::Args;
+///
+/// let temp = tempfile::tempdir()?;
+/// std::fs::write(temp.path().join("main.rs"), "fn main() {}\n")?;
+/// let output = temp.path().join("flat.md");
+/// let args = Args::parse_from([
+///     "flatten-rust",
+///     "-f",
+///     temp.path().to_str().unwrap(),
+///     "-o",
+///     output.to_str().unwrap(),
+///     "--offline",
+///     "--quiet",
+/// ]);
+/// let report = flatten_rust::run_blocking(&args)?;
+/// assert_eq!(report.files.len(), 1);
+/// assert!(std::fs::read_to_string(&output)?.contains("fn main() {}"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn run_blocking(args: &Args) -> FlattenResult<FlattenReport> {
+    let progress = IndicatifProgress::with_width(args.width);
+    run_blocking_with(args, Some(Arc::new(progress)))
+}
+
+/// Синхронный вариант `run_with`: получатель `progress` задается вызывающим
+/// кодом, в остальном как `run_blocking`. CLI передает сюда полосу прогресса
+/// с вопросом перед большим запуском (`--confirm-threshold`).
+pub fn run_blocking_with(
+    args: &Args,
+    progress: Option<Arc<dyn ProgressSink>>,
+) -> FlattenResult<FlattenReport> {
+    #[cfg(feature = "network")]
+    if !args.offline {
+        return block_on_own_runtime(|| run_with(args, progress));
+    }
+    block_on(run_with(args, progress))
+}
+
+/// Параметры `flatten_paths`: те же флаги, что у CLI.
+///
+/// Базовые папки задаются отдельно, а параметры вывода в файлы, служебные
+/// команды, загрузка шаблонов и консольный вывод игнорируются: шаблоны
+/// берутся только из кэша.
+#[derive(Debug, Clone)]
+pub struct FlattenOptions {
+    args: Args,
+}
+
+impl FlattenOptions {
+    /// Разбирает флаги CLI без имени программы (`["--format", "json", "-m", "1000"]`).
+    ///
+    /// # Ошибки
+    /// `FlattenError::InvalidConfig`, если флаги не разбираются.
+    pub fn parse<I, T>(flags: I) -> FlattenResult<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let argv = std::iter::once(std::ffi::OsString::from("flatten-rust"))
+            .chain(flags.into_iter().map(Into::into));
+        let args = Args::try_parse_from(argv)
+            .map_err(|error| FlattenError::InvalidConfig(error.to_string()))?;
+        Ok(Self::from_args(&args))
+    }
+
+    /// Параметры из готовых аргументов CLI.
+    pub fn from_args(args: &Args) -> Self {
+        Self { args: args.clone() }
+    }
+
+    /// Аргументы запуска по базовым папкам `paths` без сети и консоли.
+    fn args_for(&self, paths: &[PathBuf]) -> Args {
+        let mut args = self.args.clone();
+        args.folders = paths.to_vec();
+        args.files_from = None;
+        args.offline = true;
+        args.force_update = false;
+        args.list_templates = false;
+        args.show_enabled = false;
+        args.show_template = None;
+        args.quiet = true;
+        args.yes = true;
+        args
+    }
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        Self::from_args(&Args::parse_from(["flatten-rust"]))
+    }
+}
+
+/// Формирует документ по базовым папкам `paths` в `sink` синхронно.
+///
+/// В отличие от `run`, функция не загружает шаблоны из сети, не завершает
+/// процесс, ничего не печатает в stdout и не меняет глобальный пул потоков
+/// `rayon`: `--threads` не применяется, а файлы читаются в текущем пуле
+/// (вызов можно обернуть в `ThreadPool::install` собственного пула). Рантайм
+/// `tokio` не нужен, поэтому функцию можно вызывать из любого потока, в том
+/// числе из асинхронного кода. Документ тот же, что у `run_to_writer`.
+///
+/// # Ошибки
+/// Те же, что у `run_to_writer`.
+///
+/// # Examples
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use flatten_rust::FlattenOptions;
+///
+/// let temp = tempfile::tempdir()?;
+/// std::fs::write(temp.path().join("lib.rs"), "pub fn answer() -> u32 { 42 }\n")?;
+/// let options = FlattenOptions::parse(["--format", "json"])?;
+/// let mut document = Vec::new();
+/// let paths = [temp.path().to_path_buf()];
+/// let report = flatten_rust::flatten_paths(&paths, &options, &mut document)?;
+/// assert_eq!(report.files.len(), 1);
+/// assert!(String::from_utf8(document)?.contains("answer"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn flatten_paths(
+    paths: &[PathBuf],
+    options: &FlattenOptions,
+    mut sink: &mut dyn Write,
+) -> FlattenResult<FlattenReport> {
+    block_on(run_to_writer(&options.args_for(paths), &mut sink))
+}
+
+/// Выполняет будущее, созданное `make_future`, на собственном рантайме `tokio`.
+///
+/// Второй рантайм нельзя запустить в потоке, где уже работает первый, поэтому
+/// в таком потоке будущее выполняется в отдельном.
+#[cfg(feature = "network")]
+fn block_on_own_runtime<T, F>(make_future: impl FnOnce() -> F + Send) -> FlattenResult<T>
+where
+    T: Send,
+    F: std::future::Future<Output = FlattenResult<T>>,
+{
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return std::thread::scope(|scope| {
+            scope
+                .spawn(|| block_on_runtime(make_future()))
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        });
+    }
+    block_on_runtime(make_future())
+}
+
+#[cfg(feature = "network")]
+fn block_on_runtime<T>(
+    future: impl std::future::Future<Output = FlattenResult<T>>,
+) -> FlattenResult<T> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create async runtime")?
+        .block_on(future)
+}
+
+/// Выполняет будущее в текущем потоке, усыпляя поток до пробуждения.
+///
+/// Без загрузки шаблонов конвейер не ждет внешних событий, поэтому опрос
+/// завершается сразу.
+fn block_on<T>(future: impl std::future::Future<Output = T>) -> T {
+    use std::task::{Context as TaskContext, Poll, Wake, Waker};
+
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = TaskContext::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => return result,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+/// Раскрывает `--preset` в аргументах библиотечного вызова (см. `environment::apply_preset`).
+fn expand_preset(args: &Args) -> FlattenResult<Args> {
+    if args.preset.is_none() || !args.sources.is_empty() {
+        return Ok(args.clone());
+    }
+    environment::apply_preset(args, &presets::available_presets())
+        .map_err(FlattenError::invalid_config)
+}
+
+/// Запускает процесс "сглаживания", сообщая о ходе обработки в `progress`.
+///
+/// Без `progress` уведомления не отправляются. `run` вызывает эту функцию
+/// с полосой прогресса `indicatif`.
+pub async fn run_with(
+    args: &Args,
+    progress: Option<Arc<dyn ProgressSink>>,
+) -> FlattenResult<FlattenReport> {
+    let args = &expand_preset(args)?;
+    // В режиме auto `console` сам проверяет stdout и stderr по отдельности.
+    match args.color {
+        ColorMode::Auto => {}
+        ColorMode::Always | ColorMode::Never => {
+            let enabled = args.color == ColorMode::Always;
+            console::set_colors_enabled(enabled);
+            console::set_colors_enabled_stderr(enabled);
+        }
+    }
+
+    if let Some(path) = &args.save_invocation {
+        invocation::Invocation::capture(args).write(path)?;
+    }
+
+    if args.print_config {
+        environment::print_config(args)?;
+        return Ok(FlattenReport::default());
+    }
+
+    if args.doctor {
+        let report = doctor::DoctorReport::collect(args).await;
+        report.print(args.format)?;
+        if report.has_failures() {
+            return Err(FlattenError::DoctorFailed {
+                failed: report.failures(),
+            });
+        }
+        return Ok(FlattenReport::default());
+    }
+
+    if args.cache_status || args.cache_prune {
+        maintain_cache(args, progress.as_deref())?;
+        return Ok(FlattenReport::default());
+    }
+
+    if args.list_presets {
+        presets::print_presets(&presets::available_presets(), args.format)?;
+        return Ok(FlattenReport::default());
+    }
+
+    if let Some(document) = &args.check {
+        let check = integrity::check_file(document)?;
+        check.print(document);
+        if !check.is_intact() {
+            return Err(FlattenError::IntegrityMismatch {
+                document: document.clone(),
+                check: Box::new(check),
+            });
+        }
+        return Ok(FlattenReport::default());
+    }
+
+    if args.list_templates || args.show_enabled || args.show_template.is_some() {
+        FlattenConfig::print_templates(args).await?;
+        return Ok(FlattenReport::default());
+    }
+
+    if args.print_exclusions {
+        let config = FlattenConfig::new(args).await?;
+        explain::print_exclusions(&config, args.format)?;
+        return Ok(FlattenReport::default());
+    }
+
+    if (args.force_update
+        || !args.enable_templates.is_empty()
+        || !args.disable_templates.is_empty())
+        && args.folders.is_empty()
+    {
+        let _ = FlattenConfig::new(args).await?;
+        return Ok(FlattenReport::default());
+    }
+
+    if args.folders.is_empty() && args.files_from.is_none() {
+        return Err(FlattenError::InvalidConfig(
+            "--folders argument is required. Use --help for more information.".to_string(),
+        ));
+    }
+
+    // Собственный пул вместо глобального: глобальный пул `rayon` задается
+    // один раз на процесс, и повторный запуск с `--threads` завершился бы ошибкой.
+    let pool = thread_pool(args.threads)?;
+
+    if let Some(address) = &args.serve {
+        #[cfg(feature = "serve")]
+        {
+            serve::serve(args, address, pool.as_ref(), progress).await?;
+            return Ok(FlattenReport::default());
+        }
+        #[cfg(not(feature = "serve"))]
+        return Err(FlattenError::InvalidConfig(format!(
+            "--serve {} requires flatten-rust built with the `serve` feature",
+            address
+        )));
     }
 
     let config = FlattenConfig::new(args).await?;
+    in_pool(pool.as_ref(), || run_configured(args, config, progress))
+}
+
+/// Пул `rayon` на `threads` потоков (`--threads`); `None` при 0 — текущий пул.
+fn thread_pool(threads: usize) -> FlattenResult<Option<rayon::ThreadPool>> {
+    if threads == 0 {
+        return Ok(None);
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .context("Failed to configure thread pool")?;
+    Ok(Some(pool))
+}
+
+/// Выполняет `operation` в пуле `pool` или, без него, в текущем пуле.
+fn in_pool<R: Send>(pool: Option<&rayon::ThreadPool>, operation: impl FnOnce() -> R + Send) -> R {
+    match pool {
+        Some(pool) => pool.install(operation),
+        None => operation(),
+    }
+}
+
+/// Выполняет запуск с готовой конфигурацией: информационные команды
+/// (`--explain`, `--verify`) или запись документов.
+fn run_configured(
+    args: &Args,
+    mut config: FlattenConfig,
+    progress: Option<Arc<dyn ProgressSink>>,
+) -> FlattenResult<FlattenReport> {
+    if !args.explain.is_empty() {
+        let mut excluded = Vec::new();
+        for path in &args.explain {
+            let explanation = explain::explain_path(path, &args.folders, &config);
+            explanation.print();
+            if !explanation.is_included() {
+                excluded.push(path.clone());
+            }
+        }
+        if !excluded.is_empty() {
+            return Err(FlattenError::NotIncluded { paths: excluded });
+        }
+        return Ok(FlattenReport::default());
+    }
+
+    if let Some(document) = &args.verify {
+        // Документ со строкой целостности сначала сверяется с ней.
+        let content = std::fs::read(document)
+            .with_context(|| format!("Failed to read document: {}", document.display()))?;
+        if let Some(check) = integrity::IntegrityCheck::of(&content)
+            && !check.is_intact()
+        {
+            check.print(document);
+            return Err(FlattenError::IntegrityMismatch {
+                document: document.clone(),
+                check: Box::new(check),
+            });
+        }
+        let mut expected = verify::load_expected(document, args.manifest.as_deref())?;
+        let (actual, own_paths) = current_hashes(args, &config, document)?;
+        for path in own_paths {
+            expected.hashes.remove(&path);
+        }
+        let report = verify::VerifyReport::compare(&expected, &actual);
+        report.print(document);
+        if !report.is_clean() {
+            return Err(FlattenError::Outdated {
+                document: document.clone(),
+                report: Box::new(report),
+            });
+        }
+        return Ok(FlattenReport::default());
+    }
+
+    let progress = match &args.events {
+        Some(target) => {
+            let stream = events::EventStream::open(target, progress)
+                .map_err(|error| FlattenError::output(target, error))?;
+            Some(Arc::new(stream) as Arc<dyn ProgressSink>)
+        }
+        None => progress,
+    };
+    let progress: &dyn ProgressSink = match &progress {
+        Some(progress) => progress.as_ref(),
+        None => &NoProgress,
+    };
+
+    let mut report = FlattenReport::with_templates(config.exclusion_manager.get_enabled_templates());
+    let folders = base_folders(args, &config, &mut report);
+    if folders.is_empty() {
+        return Err(no_folders_error());
+    }
+    status!(config, "{} Starting flatten process...", glyph(&ROCKET, config.emoji));
+    status!(config, "Processing {} folders", folders.len());
+    let targets = output_targets(args, &config, &folders);
+    if !config.dry_run {
+        let manifest_path = args.manifest.iter();
+        config.set_own_outputs(
+            targets
+                .iter()
+                .map(|target| &target.path)
+                .chain(manifest_path)
+                .filter(|path| !is_stdout(path))
+                .filter(|path| containing_folder(path, &folders).is_some())
+                .filter_map(|path| canonical_output(path))
+                .collect(),
+        );
+    }
+    if !config.dry_run && !args.allow_output_inside {
+        for target in targets.iter().filter(|target| !is_stdout(&target.path)) {
+            if let Some(folder) = containing_folder(&target.path, &folders) {
+                report.warn(
+                    WarningKind::OutputInsideInput,
+                    format!(
+                        "Output file {} is inside base folder {}; the next run will include it \
+                         (use --allow-output-inside to silence this warning)",
+                        target.path.display(),
+                        folder.display()
+                    ),
+                );
+            }
+        }
+    }
+    if config.dry_run {
+        status!(
+            config,
+            "{} DRY RUN MODE - No output file will be created",
+            glyph(&SEARCH, config.emoji)
+        );
+    } else {
+        for target in &targets {
+            status!(config, "Output file: {}", output_name(&target.path));
+        }
+    }
+    status!(config);
+
+    let mut manifest = match &args.manifest {
+        Some(_) if !config.dry_run => {
+            let mut manifest = Manifest::new(
+                &targets[0].path,
+                explain::exclusions_summary(&config),
+                config.reproducible,
+            );
+            manifest.command_line = invocation::command_line(args);
+            manifest.invocation = args.save_invocation.clone().or_else(|| args.replay.clone());
+            Some(manifest)
+        }
+        _ => None,
+    };
+
+    progress.on_run_started();
+    let mut errors = Vec::new();
+    if config.dry_run {
+        let mut emitter = output::ListingEmitter::new(
+            std::io::stdout(),
+            config.emoji,
+            config.console_width,
+        );
+        let sinks = DocumentSinks {
+            emitter: &mut emitter,
+            manifest: None,
+            progress,
+        };
+        errors = emit_document(args, &config, &folders, sinks, &mut report, true)?;
+    } else {
+        if let Some(directory) = &config.output_dir {
+            std::fs::create_dir_all(directory).map_err(|source| FlattenError::OutputIo {
+                path: directory.clone(),
+                source,
+            })?;
+        }
+        // Документы с одними и теми же папками (несколько `-o`) строятся за один обход.
+        for group in targets.chunk_by(|a, b| a.folders == b.folders) {
+            // Документ внутри базовой папки создается только после полного
+            // обхода и чтения файлов, чтобы запуск не прочитал свой же вывод.
+            let inside: Vec<bool> = group
+                .iter()
+                .map(|target| {
+                    !is_stdout(&target.path) && containing_folder(&target.path, &folders).is_some()
+                })
+                .collect();
+            let mut buffers = vec![Vec::new(); group.len()];
+            let mut opened = vec![false; group.len()];
+            let mut emitter = output::FanOutEmitter::new(args.strict);
+            let mut open_error = None;
+            for (index, (target, buffer)) in group.iter().zip(&mut buffers).enumerate() {
+                let writer: Box<dyn Write + '_> = if is_stdout(&target.path) {
+                    Box::new(std::io::stdout())
+                } else if inside[index] {
+                    Box::new(buffer)
+                } else {
+                    match open_output(&target.path) {
+                        Ok(file) => Box::new(file),
+                        Err(error) if group.len() > 1 && !args.strict => {
+                            let error = anyhow::Error::from(error);
+                            report.warn(WarningKind::OutputFailed, format!("{:#}", error));
+                            open_error = Some(error);
+                            continue;
+                        }
+                        Err(error) => return Err(error),
+                    }
+                };
+                let format = output_format(args, &target.path);
+                let trailer = config.trailer && !is_stdout(&target.path);
+                let target_emitter = output::emitter_for(format, writer, &config, trailer);
+                emitter.add(target.path.clone(), target_emitter);
+                opened[index] = true;
+            }
+            if let Some(error) = open_error.filter(|_| emitter.is_empty()) {
+                return Err(error.into());
+            }
+            let sinks = DocumentSinks {
+                emitter: &mut emitter,
+                manifest: manifest.as_mut(),
+                progress,
+            };
+            errors.extend(
+                emit_document(args, &config, &group[0].folders, sinks, &mut report, true)
+                    .map_err(|error| FlattenError::output(&group[0].path, error))?,
+            );
+            for (path, error) in emitter.into_failures() {
+                report.warn(
+                    WarningKind::OutputFailed,
+                    format!("Failed to write output file {}: {:#}", path.display(), error),
+                );
+                opened[group.iter().position(|target| target.path == path).unwrap_or(0)] = false;
+            }
+            for (index, target) in group.iter().enumerate() {
+                if !opened[index] {
+                    continue;
+                }
+                if inside[index] {
+                    let written = open_output(&target.path).and_then(|mut file| {
+                        file.write_all(&buffers[index]).map_err(|source| {
+                            FlattenError::OutputIo {
+                                path: target.path.clone(),
+                                source,
+                            }
+                        })
+                    });
+                    match written {
+                        Ok(()) => {}
+                        Err(error) if group.len() > 1 && !args.strict => {
+                            let error = anyhow::Error::from(error);
+                            report.warn(WarningKind::OutputFailed, format!("{:#}", error));
+                            continue;
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+                report.outputs.push(OutputReport {
+                    path: target.path.clone(),
+                    format: output_format(args, &target.path),
+                    bytes: (!is_stdout(&target.path))
+                        .then(|| std::fs::metadata(&target.path).ok().map(|file| file.len()))
+                        .flatten(),
+                });
+            }
+        }
+    }
+
+    if let (Some(manifest), Some(path)) = (&manifest, &args.manifest) {
+        manifest
+            .write(path)
+            .map_err(|error| FlattenError::output(path, error))?;
+    }
+
+    status!(config);
+    status!(
+        config,
+        "{} Flatten completed successfully!",
+        style(glyph(&CHECK, config.emoji)).green()
+    );
+    let placeholders = report.files.iter().filter(|file| file.is_placeholder()).count();
+    status!(
+        config,
+        "Total files processed: {}",
+        report.files.len() - placeholders + errors.len()
+    );
+    if placeholders > 0 {
+        status!(config, "Files replaced by placeholders: {}", placeholders);
+    }
+    // Сводка идет в stderr вместе с самими предупреждениями.
+    if !config.quiet {
+        let _ = report.write_warnings(&mut std::io::stderr());
+    }
+
+    if config.show_stats {
+        if config.document_to_stdout || config.events_to_stdout {
+            let _ = report.write_stats(&mut std::io::stderr());
+        } else {
+            report.print_stats();
+        }
+    }
+
+    if let Some(stats_path) = &args.stats_json {
+        report
+            .write_stats_json(stats_path)
+            .map_err(|error| FlattenError::output(stats_path, error))?;
+    }
+
+    for output in &report.outputs {
+        status!(config, "Output written to: {}", output_name(&output.path));
+    }
+
+    progress.on_run_finished(&report);
+    partial_result(report, errors)
+}
+
+/// Распределяет базовые папки по выходным документам.
+///
+/// Без `--output-dir` (и в тестовом запуске) все папки пишутся в каждый `--output`.
+/// Время в именах файлов не указывается с `--reproducible`.
+fn output_targets(
+    args: &Args,
+    config: &FlattenConfig,
+    folders: &[PathBuf],
+) -> Vec<output_names::OutputTarget> {
+    match &config.output_dir {
+        Some(directory) if !config.dry_run => {
+            let timestamp = (!config.reproducible).then(|| {
+                let seconds = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                front_matter::format_compact_timestamp(seconds)
+            });
+            output_names::plan_outputs(
+                directory,
+                folders,
+                config.split_per_folder,
+                args.format.extension(),
+                timestamp.as_deref(),
+            )
+        }
+        _ => args
+            .output
+            .iter()
+            .map(|output| output_names::OutputTarget {
+                path: output.clone(),
+                folders: folders.to_vec(),
+            })
+            .collect(),
+    }
+}
+
+/// Пишется ли выходной документ `path` в стандартный вывод (`-o -`).
+fn is_stdout(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Возвращает формат выходного файла `path`.
+///
+/// При нескольких `-o` формат определяется по расширению файла, иначе (и для
+/// неизвестного расширения) используется `--format`.
+fn output_format(args: &Args, path: &Path) -> OutputFormat {
+    match args.output.len() {
+        1 => args.format,
+        _ => OutputFormat::from_extension(path).unwrap_or(args.format),
+    }
+}
+
+/// Возвращает канонический путь выходного файла, которого может еще не быть.
+fn canonical_output(output: &Path) -> Option<PathBuf> {
+    let parent = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(output.file_name()?))
+}
+
+/// Возвращает базовую папку, внутри которой окажется выходной файл `output`.
+///
+/// Базовые папки сравниваются в каноническом виде.
+fn containing_folder<'a>(output: &Path, folders: &'a [PathBuf]) -> Option<&'a Path> {
+    let output = canonical_output(output)?;
+    folders.iter().map(PathBuf::as_path).find(|folder| {
+        folder
+            .canonicalize()
+            .is_ok_and(|folder| folder.is_dir() && output.starts_with(folder))
+    })
+}
+
+/// Создает или перезаписывает выходной файл.
+fn open_output(path: &Path) -> FlattenResult<std::fs::File> {
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|source| FlattenError::OutputIo {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Возвращает имя вывода для служебных сообщений.
+fn output_name(path: &Path) -> String {
+    if is_stdout(path) {
+        "<stdout>".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Спрашивает `progress`, продолжать ли обработку базовой папки, в которой
+/// больше `--confirm-threshold` файлов.
+///
+/// Вызывается после сбора файлов и до их чтения; сам вопрос (и чтение stdin)
+/// остается за получателем, например за CLI.
+fn confirm_large_run(
+    config: &FlattenConfig,
+    base_folder: &Path,
+    planned: &[(PathBuf, PlannedFile)],
+    progress: &dyn ProgressSink,
+) -> Result<()> {
+    if config.confirm_threshold == 0 || planned.len() <= config.confirm_threshold {
+        return Ok(());
+    }
+    let preview = RunPreview::new(
+        base_folder,
+        planned.iter().map(|(path, file)| (path.as_path(), file.budget)),
+    );
+    if progress.confirm_run(&preview) {
+        Ok(())
+    } else {
+        Err(FlattenError::Aborted.into())
+    }
+}
+
+/// Показывает (`--cache-status`) или очищает (`--cache-prune`) директорию кэша.
+///
+/// Удаление всего кэша (`--all`) без `--yes` требует подтверждения
+/// `ProgressSink::confirm_cache_prune`; без `progress` кэш не удаляется.
+fn maintain_cache(args: &Args, progress: Option<&dyn ProgressSink>) -> Result<()> {
+    let directory = config::default_directory()?;
+    let status = cache_maintenance::CacheStatus::collect(&directory)?;
+    if !args.cache_prune {
+        return status.print(args.format);
+    }
+    if args.all
+        && !args.yes
+        && !status.files.is_empty()
+        && !progress.is_some_and(|progress| progress.confirm_cache_prune(&status))
+    {
+        return Err(FlattenError::Aborted.into());
+    }
+    let options = cache_maintenance::PruneOptions {
+        retention: std::time::Duration::from_secs(args.cache_retention.saturating_mul(86_400)),
+        all: args.all,
+    };
+    cache_maintenance::prune(&directory, &options, std::time::SystemTime::now())?
+        .print(args.format)
+}
+
+/// Ошибка для случая, когда ни одна из указанных базовых папок не существует.
+fn no_folders_error() -> FlattenError {
+    FlattenError::InvalidConfig("None of the specified folders exist".to_string())
+}
+
+/// Возвращает `FlattenError::TimeLimit`, если запуск остановлен по `--time-limit`,
+/// иначе `FlattenError::Partial`, если часть файлов не удалось прочитать.
+fn partial_result(report: FlattenReport, errors: Vec<FileError>) -> FlattenResult<FlattenReport> {
+    if let Some(truncation) = report.time_limit {
+        Err(FlattenError::TimeLimit {
+            report: Box::new(report),
+            truncation,
+        })
+    } else if errors.is_empty() {
+        Ok(report)
+    } else {
+        Err(FlattenError::Partial {
+            report: Box::new(report),
+            errors,
+        })
+    }
+}
+
+/// Формирует документ по базовым папкам `folders` в любой `writer`.
+///
+/// Документ совпадает с тем, что `run` записывает в `--output`, но файл
+/// вывода не открывается, в консоль ничего не печатается, а манифест и
+/// строка целостности не формируются. Информационные команды (`--explain`, `--verify` и т. п.)
+/// игнорируются.
+///
+/// # Ошибки
+/// Возвращает ошибку, если не удается инициализировать конфигурацию,
+/// обойти папки или записать документ в `writer`. Если часть файлов не
+/// прочиталась, документ записывается полностью, а отчет возвращается
+/// внутри `FlattenError::Partial`; при истечении `--time-limit` — внутри
+/// `FlattenError::TimeLimit`.
+///
+/// # Examples
+/// ```no_run
+/// # use clap::Parser;
+/// # use flatten_rust::Args;
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let args = Args::parse_from(["flatten-rust", "-f", "src"]);
+/// let mut document = Vec::new();
+/// let report = flatten_rust::run_to_writer(&args, &mut document).await?;
+/// println!("{} files, {} bytes", report.files.len(), document.len());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run_to_writer<W: Write>(args: &Args, writer: &mut W) -> FlattenResult<FlattenReport> {
+    let args = &expand_preset(args)?;
+    let config = FlattenConfig::new(args).await?;
+    write_document(args, &config, writer)
+}
+
+/// Записывает документ по готовой конфигурации в `writer` (см. `run_to_writer`).
+pub(crate) fn write_document<W: Write>(
+    args: &Args,
+    config: &FlattenConfig,
+    writer: &mut W,
+) -> FlattenResult<FlattenReport> {
+    let mut report = FlattenReport::with_templates(config.exclusion_manager.get_enabled_templates());
+    let folders = base_folders(args, config, &mut report);
+    if folders.is_empty() {
+        return Err(no_folders_error());
+    }
+
+    let mut emitter = output::emitter_for(args.format, writer, config, false);
+    let sinks = DocumentSinks {
+        emitter: emitter.as_mut(),
+        manifest: None,
+        progress: &NoProgress,
+    };
+    let errors = emit_document(args, config, &folders, sinks, &mut report, false)?;
+    partial_result(report, errors)
+}
+
+/// Получатели результатов одного прохода по базовым папкам.
+struct DocumentSinks<'a> {
+    emitter: &'a mut dyn output::Emitter,
+    manifest: Option<&'a mut Manifest>,
+    progress: &'a dyn ProgressSink,
+}
+
+/// Обходит базовые папки и передает их содержимое в `sinks`, дополняя `report`.
+///
+/// С `announce` в консоль выводится, какая папка обрабатывается.
+/// Возвращает ошибки чтения файлов; сами файлы попадают в документ с текстом ошибки.
+fn emit_document(
+    args: &Args,
+    config: &FlattenConfig,
+    folders: &[PathBuf],
+    sinks: DocumentSinks<'_>,
+    report: &mut FlattenReport,
+    announce: bool,
+) -> Result<Vec<FileError>> {
+    let DocumentSinks {
+        emitter,
+        mut manifest,
+        progress,
+    } = sinks;
+    let mut errors = Vec::new();
+
+    let labels = folder_labels(folders, &args.aliases, config).map_err(FlattenError::invalid_config)?;
+    let repositories: Vec<Option<RepositoryInfo>> = folders
+        .iter()
+        .map(|folder| config.git_info.then(|| RepositoryInfo::collect(folder)).flatten())
+        .collect();
+    if let Some(manifest) = manifest.as_deref_mut() {
+        for repository in repositories.iter().flatten() {
+            if !manifest.repositories.contains(repository) {
+                manifest.repositories.push(repository.clone());
+            }
+        }
+    }
+    let mut budget = config
+        .fit_budget
+        .map(|limit| BudgetFill::new(limit, config.fit_strategy));
+    let mut omitted = Vec::new();
+    let mut prepare = |base_folder: &Path, label: &str, report: &mut FlattenReport| {
+        let mark = report.skipped_content.entries.len();
+        let mut prepared = prepare_folder(base_folder, config, report, progress, announce)?;
+        report
+            .skipped_content
+            .relabel_entries(mark, |path| labeled_path(label, base_folder, path));
+        if let Some(budget) = &mut budget {
+            let folder_config = config.for_folder(base_folder);
+            let priority = &folder_config.priority;
+            for (path, size) in budget.select(base_folder, priority, &mut prepared.planned) {
+                prepared.withheld.insert(path.clone());
+                let path = labeled_path(label, base_folder, &path);
+                report.record_skipped_file(SkipReason::Budget, &path, size);
+                omitted.push(OmittedFile { path, size });
+            }
+        }
+        anyhow::Ok(prepared)
+    };
+    // Заголовку документа нужны итоги по всем папкам, а порядку обработки по
+    // размеру — число файлов каждой, поэтому с `--front-matter` и
+    // `--folder-order` все папки обходятся до начала вывода.
+    let mut prepared_ahead = VecDeque::new();
+    if config.front_matter.is_some() || args.folder_order != FolderOrder::Args {
+        for (base_folder, label) in folders.iter().zip(&labels) {
+            prepared_ahead.push_back(prepare(base_folder, label, report)?);
+        }
+    }
+    if config.front_matter.is_some() {
+        let header_folders = folders
+            .iter()
+            .zip(&labels)
+            .zip(&repositories)
+            .map(|((path, label), repository)| HeaderFolder {
+                label: label.clone(),
+                path: config.input_path(path).to_path_buf(),
+                repository: repository.clone(),
+            })
+            .collect();
+        let planned = prepared_ahead.iter().flat_map(|folder| &folder.planned);
+        let total_bytes = planned.clone().map(|(_, file)| file.original_size).sum();
+        emitter.document_header(&DocumentHeader::new(
+            header_folders,
+            planned.count(),
+            total_bytes,
+            config.reproducible,
+        ))?;
+    }
+
+    for (label, repository) in labels.iter().zip(&repositories) {
+        if let Some(repository) = repository {
+            emitter.repository(label, repository)?;
+        }
+    }
+
+    // Папки, прочитанные не в порядке аргументов, ждут в памяти своей очереди
+    // вывода, поэтому порядок секций документа от `--folder-order` не зависит.
+    // Полоса хода обработки одна на все папки и сразу получает их итог.
+    let mut read_ahead = HashMap::new();
+    if args.folder_order != FolderOrder::Args {
+        let counts: Vec<usize> = prepared_ahead.iter().map(|folder| folder.planned.len()).collect();
+        let total = prepared_ahead
+            .iter()
+            .filter(|folder| !folder.progress_started)
+            .flat_map(|folder| &folder.planned)
+            .filter(|(_, file)| !file.placeholder)
+            .count();
+        progress.on_start(total);
+        let mut summary = ProgressSummary::default();
+        for index in args.folder_order.schedule(&counts) {
+            if counts[index] == 0 || config.time_limit.as_ref().is_some_and(|limit| limit.check()) {
+                continue;
+            }
+            let base_folder = &folders[index];
+            let folder = &mut prepared_ahead[index];
+            let planned = std::mem::take(&mut folder.planned);
+            let (ordered, group_starts) =
+                order_files(args, config, base_folder, planned, folder.workspace.as_ref());
+            let mut results = Vec::with_capacity(ordered.len());
+            let (peak, unprocessed) = pipeline::process_files_windowed(
+                base_folder,
+                &ordered,
+                std::mem::take(&mut folder.ready),
+                config.for_folder(base_folder),
+                progress,
+                |index, file_path, content_result| {
+                    count_file(&mut summary, &ordered[index].1, &content_result);
+                    results.push((index, file_path.to_path_buf(), content_result));
+                    Ok(false)
+                },
+            )?;
+            read_ahead.insert(
+                index,
+                ReadFolder {
+                    ordered,
+                    group_starts,
+                    results,
+                    peak,
+                    unprocessed,
+                },
+            );
+        }
+        progress.on_finish(&summary);
+    }
+
+    let mut truncation = config.time_limit.as_deref().map(Truncation::new);
+    for (index, (base_folder, label)) in folders.iter().zip(&labels).enumerate() {
+        let read = read_ahead.remove(&index);
+        // После лимита времени оставшиеся папки не обходятся.
+        if read.is_none()
+            && let Some(truncation) = &mut truncation
+            && config.time_limit.as_ref().is_some_and(|limit| limit.check())
+        {
+            let planned = prepared_ahead.pop_front().map(|folder| folder.planned);
+            for (path, file) in planned.iter().flatten() {
+                let path = labeled_path(label, base_folder, path);
+                report.record_skipped_file(SkipReason::TimeLimit, &path, file.original_size);
+                truncation.unprocessed_files += 1;
+            }
+            truncation.unprocessed_folders += 1;
+            continue;
+        }
+        let PreparedFolder {
+            tree,
+            planned,
+            ready,
+            mut withheld,
+            walk_peak,
+            progress_started,
+            languages,
+            mut dependencies,
+            workspace,
+        } = match prepared_ahead.pop_front() {
+            Some(prepared) => prepared,
+            None => prepare(base_folder, label, report)?,
+        };
+        emitter.folder_structure(&tree, label, config)?;
+        if let Some(languages) = &languages {
+            emitter.language_summary(label, languages)?;
+        }
+        if let Some(dependencies) = &mut dependencies {
+            for manifest in dependencies.iter_mut() {
+                manifest.path = labeled_path(label, base_folder, &manifest.path);
+            }
+            emitter.dependencies(label, dependencies)?;
+        }
+        if let Some(workspace) = &workspace
+            && (config.overview || config.deps_summary)
+        {
+            emitter.workspace(label, workspace)?;
+        }
+
+        let folder_config = config.for_folder(base_folder);
+        let file_count = read.as_ref().map_or(planned.len(), |read| read.ordered.len());
+        let mut emitted = config.self_check.then(HashSet::new);
+        if file_count == 0 {
+            verify_folder(&tree, emitted.as_ref(), &withheld, label, base_folder)?;
+            if announce && config.overview {
+                status!(config, "No priority files found in {}", base_folder.display());
+            } else if announce {
+                status!(config, "No files found in {}", base_folder.display());
+            }
+            if progress_started {
+                progress.on_finish(&ProgressSummary::default());
+            }
+            continue;
+        }
+        if read.is_none() && !progress_started {
+            progress.on_start(planned.iter().filter(|(_, file)| !file.placeholder).count());
+        }
+        emitter.begin_content(label)?;
+
+        let buffered = read.is_some();
+        let (ordered, group_starts, read) = match read {
+            Some(read) => (
+                read.ordered,
+                read.group_starts,
+                Some((read.results, read.peak, read.unprocessed)),
+            ),
+            None => {
+                let (ordered, group_starts) =
+                    order_files(args, config, base_folder, planned, workspace.as_ref());
+                (ordered, group_starts, None)
+            }
+        };
+
+        let mut summary = ProgressSummary::default();
+        let from_archive = config.input_path(base_folder) != base_folder;
+        let mut emit =
+            |index: usize, file_path: &Path, mut content_result: Result<FileContent>| {
+                if let Some(group) = group_starts.get(&index) {
+                    emitter.begin_group(group)?;
+                }
+                // Текст из отображения файла перепроверяется непосредственно перед записью.
+                if let Ok(content) = &mut content_result {
+                    match content.content.recheck_mapped(file_path) {
+                        Ok(changed) => content.stale |= changed,
+                        Err(error) => content_result = Err(error),
+                    }
+                }
+                let display_path = labeled_path(label, base_folder, file_path);
+                // Заглушка двоичной записи архива называет ее так же, как заголовок,
+                // а не путем во временной директории распаковки.
+                if from_archive
+                    && let Ok(content) = &mut content_result
+                    && content.skip_reason == Some(SkipReason::Extension)
+                {
+                    let path = display_path.display();
+                    content.content =
+                        config.messages.format(Message::BinarySkipped, &[("path", &path)]).into();
+                }
+                count_file(&mut summary, &ordered[index].1, &content_result);
+                if let Some(emitted) = &mut emitted {
+                    emitted.insert(file_path.to_path_buf());
+                }
+
+                let priority = folder_config.priority.rank(base_folder, file_path).is_some();
+                let span = emitter.file(&display_path, &content_result, priority)?;
+                progress.on_file_written(file_path, span);
+                if let Some(manifest) = manifest.as_deref_mut() {
+                    manifest.files.push(manifest_entry(&display_path, span, &content_result));
+                }
+
+                let file_content = match content_result {
+                    Ok(file_content) => file_content,
+                    // Слишком длинный путь не считается ошибкой запуска.
+                    Err(source) if pipeline::is_path_too_long(&source) => {
+                        report.warn(
+                            WarningKind::PathTooLong,
+                            format!("{}: {:#}", display_path.display(), source),
+                        );
+                        return Ok(());
+                    }
+                    Err(source) => {
+                        report.record_warning(
+                            WarningKind::ReadError,
+                            format!("{}: {:#}", display_path.display(), source),
+                        );
+                        errors.push(FileError {
+                            path: display_path,
+                            source,
+                        });
+                        return Ok(());
+                    }
+                };
+                // Сводки `--dir-file-limit` и `--vendored` — не файлы; их файлы
+                // учтены при построении.
+                if matches!(
+                    file_content.skip_reason,
+                    Some(SkipReason::DirFileLimit | SkipReason::Vendored)
+                ) {
+                    return Ok(());
+                }
+                if file_content.stale {
+                    report.warn(
+                        WarningKind::StaleFile,
+                        format!(
+                            "{}: file changed during run; content may be stale",
+                            display_path.display()
+                        ),
+                    );
+                }
+                report.chunks += file_content.chunks.len();
+                if let Some(state) = file_content.lfs {
+                    report.record_lfs(state);
+                }
+                if let Some(name) = file_content.converter {
+                    report.record_conversion(
+                        name,
+                        file_content.original_size,
+                        file_content.content.len() as u64,
+                    );
+                    if name == assets::IMAGE_CONVERTER
+                        && let Some(format) = ImageFormat::from_path(file_path)
+                    {
+                        report.record_asset(format.as_str(), file_content.original_size);
+                    }
+                }
+                for metric in &file_content.transforms {
+                    report.record_transform(metric.transform, metric.before, metric.after);
+                }
+                if let Some(reason) = file_content.skip_reason {
+                    report.record_skipped_file(reason, &display_path, file_content.original_size);
+                    if let Some(template) = folder_config.skip_template(base_folder, file_path) {
+                        report.record_template_skip(template, false, 1, file_content.original_size);
+                    }
+                }
+                if let Some(member) = workspace.as_ref().and_then(|w| w.crate_of(file_path)) {
+                    report.record_crate(&member.name, file_content.original_size);
+                }
+                report.record_file(FileReport {
+                    base_folder: PathBuf::from(label),
+                    path: display_path,
+                    original_bytes: file_content.original_size,
+                    read_bytes: file_content.read_bytes,
+                    emitted_bytes: file_content.content.len() as u64,
+                    skip_reason: file_content.skip_reason,
+                    sanitized: file_content.sanitized,
+                });
+                Ok(())
+            };
+        let (peak, unprocessed) = match read {
+            Some((results, peak, unprocessed)) => {
+                for (index, file_path, content_result) in results {
+                    emit(index, &file_path, content_result)?;
+                }
+                (peak, unprocessed)
+            }
+            None => pipeline::process_files_windowed(
+                base_folder,
+                &ordered,
+                ready,
+                folder_config,
+                progress,
+                |index, file_path, content_result| {
+                    emit(index, file_path, content_result).map(|()| false)
+                },
+            )?,
+        };
+        report.peak_in_flight_bytes = report.peak_in_flight_bytes.max(peak).max(walk_peak);
+        if let Some(truncation) = &mut truncation {
+            for index in unprocessed {
+                let (path, file) = &ordered[index];
+                withheld.insert(path.clone());
+                let path = labeled_path(label, base_folder, path);
+                report.record_skipped_file(SkipReason::TimeLimit, &path, file.original_size);
+                truncation.unprocessed_files += 1;
+            }
+        }
+        verify_folder(&tree, emitted.as_ref(), &withheld, label, base_folder)?;
+
+        emitter.end_content(label)?;
+        // Ход чтения папок, прочитанных заранее, завершен вместе.
+        if !buffered {
+            progress.on_finish(&summary);
+        }
+    }
+
+    if !omitted.is_empty() {
+        emitter.budget_omissions(&omitted)?;
+        if announce {
+            status!(
+                config,
+                "Omitted due to budget: {} files ({})",
+                omitted.len(),
+                report::format_size(omitted.iter().map(|file| file.size).sum())
+            );
+        }
+    }
+    if config.skipped_appendix {
+        emitter.skipped_appendix(&report.skipped_content)?;
+    }
+    if let Some(truncation) =
+        truncation.filter(|t| t.unprocessed_files + t.unprocessed_folders > 0)
+    {
+        emitter.truncated(&truncation)?;
+        report.time_limit = Some(truncation);
+    }
+    if let Some(cache) = &config.content_cache {
+        report.content_cache = Some(cache.stats());
+        if let Err(e) = cache.evict() {
+            report.warn(WarningKind::ContentCache, format!("{:#}", e));
+        }
+    }
+    emitter.finish()?;
+    Ok(errors)
+}
+
+/// Раскладывает файлы базовой папки в порядке вывода: по группам `--group-by`,
+/// внутри групп — приоритетные файлы и crate workspace первыми.
+///
+/// Возвращает файлы и начала групп (индекс первого файла группы → группа).
+/// Порядок зависит только от путей, поэтому известен до чтения файлов.
+fn order_files(
+    args: &Args,
+    config: &FlattenConfig,
+    base_folder: &Path,
+    planned: Vec<(PathBuf, PlannedFile)>,
+    workspace: Option<&CargoWorkspace>,
+) -> (Vec<(PathBuf, PlannedFile)>, HashMap<usize, grouping::FileGroup>) {
+    let priority = &config.for_folder(base_folder).priority;
+    let mut ordered = Vec::with_capacity(planned.len());
+    let mut group_starts = HashMap::new();
+    let groups = grouping::group_files(
+        args.group_by,
+        &config.languages,
+        base_folder,
+        planned,
+        |file| file.original_size,
+    );
+    for (group, mut group_files) in groups {
+        match workspace {
+            Some(workspace) => workspace.arrange(base_folder, priority, &mut group_files),
+            None => priority.prioritize(base_folder, &mut group_files),
+        }
+        if let Some(group) = group {
+            group_starts.insert(ordered.len(), group);
+        }
+        ordered.extend(group_files);
+    }
+    (ordered, group_starts)
+}
+
+/// Сверяет дерево структуры базовой папки с выведенными для нее секциями
+/// содержимого; `emitted` нет, если `--self-check` выключен.
+fn verify_folder(
+    tree: &DirTree,
+    emitted: Option<&HashSet<PathBuf>>,
+    withheld: &HashSet<PathBuf>,
+    label: &str,
+    base_folder: &Path,
+) -> FlattenResult<()> {
+    let Some(emitted) = emitted else {
+        return Ok(());
+    };
+    tree.verify_content(emitted, withheld).map_err(|mut mismatch| {
+        for path in mismatch.tree_only.iter_mut().chain(&mut mismatch.content_only) {
+            *path = labeled_path(label, base_folder, path);
+        }
+        FlattenError::SelfCheck {
+            folder: PathBuf::from(label),
+            mismatch,
+        }
+    })
+}
+
+/// Учитывает прочитанный файл в сводке хода обработки; заглушки не считаются.
+fn count_file(summary: &mut ProgressSummary, file: &PlannedFile, result: &Result<FileContent>) {
+    if !file.placeholder {
+        summary.files += 1;
+        match result {
+            Ok(content) => summary.bytes += content.read_bytes,
+            Err(_) => summary.failed += 1,
+        }
+    }
+}
+
+/// Базовая папка, прочитанная заранее по `--folder-order`.
+struct ReadFolder {
+    /// Файлы в порядке вывода.
+    ordered: Vec<(PathBuf, PlannedFile)>,
+    /// Начала групп `--group-by`.
+    group_starts: HashMap<usize, grouping::FileGroup>,
+    /// Результаты чтения в порядке вывода с индексами в `ordered`.
+    results: Vec<(usize, PathBuf, Result<FileContent>)>,
+    /// Наибольший объем байт, прочитанных одновременно.
+    peak: u64,
+    /// Индексы файлов, не прочитанных из-за лимита времени.
+    unprocessed: Vec<usize>,
+}
+
+/// Базовая папка, обойденная и подготовленная к выводу.
+struct PreparedFolder {
+    tree: DirTree,
+    /// Файлы в порядке обхода с оценкой их чтения.
+    planned: Vec<(PathBuf, PlannedFile)>,
+    /// Файлы, уже прочитанные при обходе или проверке `--grep`.
+    ready: HashMap<PathBuf, Result<FileContent>>,
+    /// Файлы дерева, содержимое которых намеренно не выводится.
+    withheld: HashSet<PathBuf>,
+    /// Наибольший объем байт, прочитанных во время обхода.
+    walk_peak: u64,
+    /// Сообщено ли уже `progress` о начале обработки папки.
+    progress_started: bool,
+    /// Сводка по языкам всех файлов папки (`--overview`).
+    languages: Option<Vec<grouping::FileGroup>>,
+    /// Зависимости из манифестов папки (`--deps-summary`).
+    dependencies: Option<Vec<ManifestDependencies>>,
+    /// Cargo workspace, в который входит папка.
+    workspace: Option<CargoWorkspace>,
+}
+
+/// Обходит базовую папку, оценивает чтение файлов и применяет `--grep`.
+///
+/// С `--overview` из плана остаются только файлы `--priority`, а остальные
+/// учитываются лишь в сводке по языкам.
+fn prepare_folder(
+    base_folder: &Path,
+    config: &FlattenConfig,
+    report: &mut FlattenReport,
+    progress: &dyn ProgressSink,
+    announce: bool,
+) -> Result<PreparedFolder> {
+    if announce {
+        let input = config.input_path(base_folder);
+        let kind = if input != base_folder {
+            "archive"
+        } else if base_folder.is_dir() {
+            "folder"
+        } else {
+            "file"
+        };
+        status!(config, "Processing {}: {}", kind, input.display());
+    }
+    progress.on_folder_started(base_folder);
+    let config = config.for_folder(base_folder);
+    let (mut tree, prefetched) = if config.parallel_walk && base_folder.is_dir() {
+        let (tree, prefetched) =
+            walk_and_process_parallel(base_folder, config, report, progress)?;
+        (tree, Some(prefetched))
+    } else {
+        (walk_base_folder(base_folder, config, report)?, None)
+    };
+
+    let mut progress_started = prefetched.is_some();
+    let (mut planned, mut ready, walk_peak) = match prefetched {
+        Some(prefetched) => {
+            let PrefetchedFiles {
+                mut planned,
+                ready,
+                peak,
+            } = prefetched;
+            let planned = tree
+                .files()
+                .map(|entry| {
+                    let file = planned.remove(&entry.path).unwrap_or_default();
+                    (entry.path.clone(), file)
+                })
+                .collect();
+            (planned, ready, peak)
+        }
+        None => {
+            let entries: Vec<_> = tree.files().collect();
+            let planned: Vec<(PathBuf, PlannedFile)> = entries
+                .par_iter()
+                .map(|entry| (entry.path.clone(), PlannedFile::new(entry, config)))
+                .collect();
+            (planned, HashMap::new(), 0)
+        }
+    };
+    let workspace = base_folder
+        .is_dir()
+        .then(|| CargoWorkspace::discover(base_folder))
+        .flatten()
+        .map(|mut workspace| {
+            for member in &mut workspace.members {
+                member.excluded = member
+                    .local
+                    .as_ref()
+                    .is_some_and(|local| config.excluded_crates.contains(local));
+            }
+            workspace
+        });
+    // Манифесты ищутся среди всех найденных файлов, до `--overview` и `--grep`.
+    let dependencies = config.deps_summary.then(|| {
+        planned
+            .par_iter()
+            .filter(|(_, file)| !file.placeholder)
+            .filter_map(|(path, _)| {
+                let kind = dependencies::manifest_kind(path)?;
+                Some(dependencies::read_manifest(path, kind))
+            })
+            .collect::<Vec<_>>()
+    });
+    let mut withheld = HashSet::new();
+    let languages = config.overview.then(|| {
+        let languages = grouping::language_summary(
+            &config.languages,
+            base_folder,
+            &planned,
+            |file| file.original_size,
+        );
+        planned.retain(|(path, _)| {
+            let priority = config.priority.rank(base_folder, path).is_some();
+            if !priority {
+                withheld.insert(path.clone());
+            }
+            priority
+        });
+        languages
+    });
+    // Остальные файлы остаются в дереве, но их содержимое не выводится.
+    if !config.content_paths.is_empty() {
+        planned.retain(|(path, file)| {
+            let content = config.is_content_path(base_folder, path);
+            if !content {
+                report.record_skipped_file(SkipReason::StructureOnly, path, file.original_size);
+                withheld.insert(path.clone());
+            }
+            content
+        });
+    }
+    if announce && !planned.is_empty() {
+        confirm_large_run(config, base_folder, &planned, progress)?;
+    }
+    // Дерево структуры должно совпадать с оставшимися файлами, поэтому
+    // `--grep` проверяет содержимое до его вывода.
+    if let Some(filter) = &config.content_filter
+        && !planned.is_empty()
+    {
+        let prefetched = std::mem::take(&mut ready);
+        let (kept, retained) = pipeline::grep_tree(
+            base_folder, &mut tree, prefetched, filter, config, report, progress,
+        )?;
+        ready = retained;
+        planned.retain(|(path, _)| kept.contains(path));
+        progress_started = true;
+    }
+    // Лимит считается по файлам, оставшимся после `--grep`.
+    if config.dir_file_limit > 0 {
+        let summaries = pipeline::limit_dir_files(&mut tree, config, report);
+        if !summaries.is_empty() {
+            let mut files: HashMap<PathBuf, PlannedFile> = planned.into_iter().collect();
+            planned = tree
+                .files()
+                .filter_map(|entry| {
+                    let file = files.remove(&entry.path).or_else(|| {
+                        summaries.get(&entry.path).map(|summary| PlannedFile {
+                            original_size: summary.original_size,
+                            placeholder: true,
+                            budget: 0,
+                            modified: None,
+                        })
+                    })?;
+                    Some((entry.path.clone(), file))
+                })
+                .collect();
+            ready.retain(|path, _| !files.contains_key(path));
+            ready.extend(summaries.into_iter().map(|(path, summary)| (path, Ok(summary))));
+        }
+    }
+    // Сводки стороннего кода встают в план на место своих директорий.
+    if config.vendored != VendoredMode::Include {
+        let summaries = pipeline::summarize_vendored(&tree, config, report);
+        if !summaries.is_empty() {
+            let mut files: HashMap<PathBuf, PlannedFile> = planned.into_iter().collect();
+            planned = tree
+                .entries
+                .iter()
+                .filter_map(|entry| {
+                    let file = files.remove(&entry.path).or_else(|| {
+                        summaries.get(&entry.path).map(|summary| PlannedFile {
+                            original_size: summary.original_size,
+                            placeholder: true,
+                            budget: 0,
+                            modified: None,
+                        })
+                    })?;
+                    Some((entry.path.clone(), file))
+                })
+                .collect();
+            ready.extend(summaries.into_iter().map(|(path, summary)| (path, Ok(summary))));
+        }
+    }
+    Ok(PreparedFolder {
+        tree,
+        planned,
+        ready,
+        withheld,
+        walk_peak,
+        progress_started,
+        languages,
+        dependencies,
+        workspace,
+    })
+}
+
+/// Файлы, найденные параллельным обходом.
+struct PrefetchedFiles {
+    /// Размеры каждого найденного файла.
+    planned: HashMap<PathBuf, PlannedFile>,
+    /// Файлы, прочитанные во время обхода; их бюджет еще не освобожден.
+    ready: HashMap<PathBuf, Result<FileContent>>,
+    /// Наибольший объем байт, прочитанных во время обхода.
+    peak: u64,
+}
+
+/// Обходит базовую папку параллельно, читая файлы по мере их обнаружения (`--parallel-walk`).
+///
+/// Во время обхода читаются только файлы, помещающиеся в бюджет
+/// `--max-in-flight-bytes`; остальные дочитываются после обхода в порядке
+/// вывода. Документ совпадает с последовательным режимом байт в байт.
+///
+/// Найдя больше `--confirm-threshold` файлов, обход перестает читать их:
+/// вопрос о большом запуске задается до чтения остальных файлов.
+fn walk_and_process_parallel(
+    base_folder: &Path,
+    config: &FlattenConfig,
+    report: &mut FlattenReport,
+    progress: &dyn ProgressSink,
+) -> Result<(DirTree, PrefetchedFiles)> {
+    let options = ReadOptions::from_config(config);
+    let limit = match config.max_in_flight_bytes {
+        0 => u64::MAX,
+        limit => limit,
+    };
+    let in_flight = AtomicU64::new(0);
+    let peak = AtomicU64::new(0);
+    let discovered = AtomicUsize::new(0);
+    let planned = Mutex::new(HashMap::new());
+    let ready = Mutex::new(HashMap::new());
+
+    progress.on_start(0);
+    let tree = DirTree::walk_parallel_with_report(base_folder, config, report, &|entry| {
+        let file = PlannedFile::new(entry, config);
+        if !file.placeholder {
+            progress.on_discovered(1);
+        }
+        planned
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(entry.path.clone(), file);
+        let count = discovered.fetch_add(1, Ordering::AcqRel) + 1;
+        if config.confirm_threshold > 0 && count > config.confirm_threshold {
+            return;
+        }
+
+        let acquired = in_flight.fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+            current
+                .checked_add(file.budget)
+                .filter(|total| *total <= limit)
+        });
+        let Ok(previous) = acquired else {
+            return;
+        };
+        peak.fetch_max(previous + file.budget, Ordering::AcqRel);
+        let result = pipeline::process_file(
+            base_folder,
+            &entry.path,
+            Some(file.original_size),
+            config,
+            &options,
+            progress,
+        );
+        ready
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(entry.path.clone(), result);
+    })?;
+
+    Ok((
+        tree,
+        PrefetchedFiles {
+            planned: planned.into_inner().unwrap_or_else(PoisonError::into_inner),
+            ready: ready.into_inner().unwrap_or_else(PoisonError::into_inner),
+            peak: peak.into_inner(),
+        },
+    ))
+}
+
+/// Выводит в `writer` древовидную структуру базовой папки по готовому обходу.
+///
+/// Маркеры содержат метку папки `label`, а не ее фактический путь. С `width`
+/// имена укорачиваются, чтобы строки дерева помещались в консоль.
+fn print_folder_structure<W: Write>(
+    tree: &DirTree,
+    label: &str,
+    writer: &mut W,
+    config: &FlattenConfig,
+    width: Option<usize>,
+) -> Result<()> {
+    writeln!(writer, "### DIRECTORY {} FOLDER STRUCTURE ###", label)?;
+
+    tree.render_fitted(config, writer, width)?;
+
+    writeln!(writer, "### DIRECTORY {} FOLDER STRUCTURE ###\n", label)?;
+    Ok(())
+}
+
+/// Возвращает базовые папки запуска; несуществующие из указанных попадают в `report`.
+///
+/// С `--files-from` базовая папка одна — общий предок перечисленных файлов.
+fn base_folders(args: &Args, config: &FlattenConfig, report: &mut FlattenReport) -> Vec<PathBuf> {
+    match &config.listed_files {
+        Some(files) => vec![file_list::common_ancestor(files)],
+        None => resolve_folders(&args.folders, args.allow_overlap, report)
+            .into_iter()
+            .map(|folder| match config.unpacked_root(&folder) {
+                Some(root) => root.to_path_buf(),
+                None => folder,
+            })
+            .collect(),
+    }
+}
+
+/// Строит дерево базовой папки: из списка `--files-from` или ее обходом.
+///
+/// Базовый файл из `-f` выводится целиком, даже если его расширение
+/// пропускается: о таком файле выводится предупреждение.
+fn walk_base_folder(
+    base_folder: &Path,
+    config: &FlattenConfig,
+    report: &mut FlattenReport,
+) -> Result<DirTree> {
+    match &config.listed_files {
+        Some(files) => Ok(DirTree::from_files(base_folder, files, config)),
+        None if base_folder.is_file() => {
+            let parent = base_folder.parent().unwrap_or(base_folder);
+            if let Some(reason) = config.file_skip_reason(parent, base_folder) {
+                report.warn(
+                    WarningKind::ExplicitFile,
+                    format!(
+                        "File {} would be skipped ({}), including it because it was listed explicitly",
+                        base_folder.display(),
+                        reason.as_str()
+                    ),
+                );
+            }
+            Ok(DirTree::single_file(base_folder))
+        }
+        None => DirTree::walk_with_report(base_folder, config, report),
+    }
+}
+
+/// Отказывается обрабатывать защищенные базовые папки (домашнюю директорию,
+/// корень файловой системы) без `--allow-protected-paths`.
+fn check_protected_paths(folders: &[PathBuf]) -> FlattenResult<()> {
+    if folders.is_empty() {
+        return Ok(());
+    }
+    let protected = protected_paths::available_protected_paths();
+    for folder in folders {
+        if let Some(protection) = protected.check(folder) {
+            return Err(FlattenError::InvalidConfig(format!(
+                "Refusing to process {}: {} (pass --allow-protected-paths to process it anyway)",
+                folder.display(),
+                protection
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Отбирает базовые папки для обработки.
+///
+/// Несуществующие папки записываются в `report.missing_folders`. Без
+/// `allow_overlap` повторы (после канонизации) отбрасываются, а из вложенных
+/// папок остается только внешняя, чтобы файлы не попадали в вывод дважды.
+fn resolve_folders(
+    folders: &[PathBuf],
+    allow_overlap: bool,
+    report: &mut FlattenReport,
+) -> Vec<PathBuf> {
+    let mut resolved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for folder in folders {
+        let Ok(canonical) = folder.canonicalize() else {
+            report.warn(
+                WarningKind::MissingInput,
+                format!("{} {} does not exist, skipping", input_kind(folder), folder.display()),
+            );
+            report.missing_folders.push(folder.clone());
+            continue;
+        };
+        // Отдельные файлы указаны явно: они не поглощаются папками и не поглощают их.
+        if !canonical.is_dir() {
+            if resolved.iter().any(|(_, kept)| *kept == canonical) {
+                report.warn(
+                    WarningKind::IgnoredInput,
+                    format!("File {} is listed twice, skipping", folder.display()),
+                );
+            } else {
+                resolved.push((folder.clone(), canonical));
+            }
+            continue;
+        }
+        if allow_overlap {
+            resolved.push((folder.clone(), canonical));
+            continue;
+        }
+        if let Some((outer, kept)) = resolved
+            .iter()
+            .find(|(_, kept)| kept.is_dir() && canonical.starts_with(kept))
+        {
+            let message = if *kept == canonical {
+                format!("Folder {} is listed twice, skipping", folder.display())
+            } else {
+                overlap_message(folder, outer)
+            };
+            report.warn(WarningKind::IgnoredInput, message);
+            continue;
+        }
+        resolved.retain(|(inner, kept)| {
+            let nested = kept.is_dir() && kept.starts_with(&canonical);
+            if nested {
+                report.warn(WarningKind::IgnoredInput, overlap_message(inner, folder));
+            }
+            !nested
+        });
+        resolved.push((folder.clone(), canonical));
+    }
+    resolved.into_iter().map(|(folder, _)| folder).collect()
+}
+
+/// Сообщение о базовой папке `inner`, пропущенной из-за внешней `outer`.
+fn overlap_message(inner: &Path, outer: &Path) -> String {
+    format!(
+        "Folder {} is inside {}, skipping (use --allow-overlap to process both)",
+        inner.display(),
+        outer.display()
+    )
+}
+
+/// Угадывает по несуществующему пути, имелся ли в виду файл или папка.
+fn input_kind(path: &Path) -> &'static str {
+    let trailing_separator = path
+        .as_os_str()
+        .to_string_lossy()
+        .ends_with(std::path::is_separator);
+    if path.extension().is_some() && !trailing_separator {
+        "File"
+    } else {
+        "Folder"
+    }
+}
+
+/// Возвращает метки базовых папок для маркеров вывода.
+///
+/// Папка с `--alias имя=путь` получает указанное имя, остальные — последний
+/// компонент пути (для архива — его имя без расширения); совпадающие метки
+/// дополняются суффиксами `-2`, `-3` и т.д. Имена из `--alias` занимаются
+/// первыми, поэтому суффикс получают только метки по имени папки; два
+/// одинаковых имени в `--alias` — ошибка.
+fn folder_labels(
+    folders: &[PathBuf],
+    aliases: &[String],
+    config: &FlattenConfig,
+) -> Result<Vec<String>> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut parsed: Vec<(String, PathBuf)> = Vec::new();
+    for alias in aliases {
+        let (name, path) = alias
+            .split_once('=')
+            .filter(|(name, path)| !name.is_empty() && !path.is_empty())
+            .with_context(|| format!("Invalid --alias value `{}`: expected name=path", alias))?;
+        if parsed.iter().any(|(known, _)| known == name) {
+            anyhow::bail!("Duplicate --alias name `{}`", name);
+        }
+        parsed.push((name.to_string(), canonical(Path::new(path))));
+    }
+
+    let explicit: Vec<Option<&str>> = folders
+        .iter()
+        .map(|folder| {
+            let input_path = canonical(config.input_path(folder));
+            parsed
+                .iter()
+                .find(|(_, path)| *path == input_path)
+                .map(|(name, _)| name.as_str())
+        })
+        .collect();
+    let reserved: HashSet<&str> = explicit.iter().flatten().copied().collect();
+    let mut used = HashSet::new();
+    let mut labels = Vec::with_capacity(folders.len());
+    for (folder, alias) in folders.iter().zip(&explicit) {
+        let base = match alias {
+            Some(name) => name.to_string(),
+            None => canonical(folder)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| folder.display().to_string()),
+        };
+        let mut label = base.clone();
+        let mut suffix = 2;
+        let taken = |label: &str| alias.is_none() && reserved.contains(label);
+        while taken(&label) || !used.insert(label.clone()) {
+            label = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+        labels.push(label);
+    }
+    Ok(labels)
+}
+
+/// Формирует запись манифеста для записанного файла.
+fn manifest_entry(
+    path: &Path,
+    span: Option<ContentSpan>,
+    content: &Result<FileContent>,
+) -> ManifestEntry {
+    match content {
+        Ok(file_content) => ManifestEntry {
+            path: path.to_path_buf(),
+            span,
+            original_size: file_content.original_size,
+            sha256: manifest::sha256_hex(file_content.content.as_bytes()),
+            status: if file_content.skip_reason.is_some() {
+                FileStatus::Skipped
+            } else if file_content.converter.is_some() {
+                FileStatus::Converted
+            } else {
+                FileStatus::Full
+            },
+            converter: file_content.converter,
+            skip_reason: file_content.skip_reason,
+            line_endings: file_content.line_endings,
+            stale: file_content.stale,
+            mode: file_content.mode.clone(),
+        },
+        Err(_) => ManifestEntry {
+            path: path.to_path_buf(),
+            span,
+            original_size: 0,
+            sha256: String::new(),
+            status: FileStatus::Error,
+            converter: None,
+            skip_reason: None,
+            line_endings: None,
+            stale: false,
+            mode: None,
+        },
+    }
+}
+
+/// Собирает хеши текущего содержимого файлов так, как они были бы записаны в вывод.
+///
+/// Сам проверяемый документ и манифест исключаются, если лежат внутри базовых папок;
+/// их пути в выводе возвращаются вторым элементом.
+fn current_hashes(
+    args: &Args,
+    config: &FlattenConfig,
+    document: &Path,
+) -> Result<(verify::ContentHashes, Vec<PathBuf>)> {
+    let own_files: Vec<PathBuf> = [Some(document), args.manifest.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+    let mut report = FlattenReport::default();
+    let folders = base_folders(args, config, &mut report);
+    let labels = folder_labels(&folders, &args.aliases, config)?;
+    let mut hashes = verify::ContentHashes::new();
+    let mut own_paths = Vec::new();
+
+    for (base_folder, label) in folders.iter().zip(&labels) {
+        let config = config.for_folder(base_folder);
+        let (own, files): (Vec<PathBuf>, Vec<PathBuf>) =
+            walk_base_folder(base_folder, config, &mut report)?
+                .files()
+                .map(|entry| entry.path.clone())
+                .partition(|path| {
+                    path.canonicalize()
+                        .is_ok_and(|path| own_files.contains(&path))
+                });
+        own_paths.extend(own.iter().map(|path| labeled_path(label, base_folder, path)));
+        let files = files
+            .into_iter()
+            .filter(|path| config.is_content_path(base_folder, path))
+            .collect();
+        for (file_path, content) in pipeline::process_files_parallel(base_folder, files, config, &NoProgress) {
+            if let Some(filter) = &config.content_filter
+                && !filter.accepts(&content)
+            {
+                continue;
+            }
+            let display_path = labeled_path(label, base_folder, &file_path);
+            let entry = manifest_entry(&display_path, None, &content);
+            hashes.insert(display_path, entry.sha256);
+        }
+    }
+    Ok((hashes, own_paths))
+}
+
+/// Проверяет, что путь аргумента `flag` относителен, и убирает из него `.`.
+fn relative_arg(flag: &str, path: &Path) -> Result<PathBuf> {
+    let relative = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !relative {
+        anyhow::bail!("{} {} must be relative to a base folder", flag, path.display());
+    }
+    Ok(path
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect())
+}
+
+/// Находит директории крейтов `--exclude-crate` в workspace базовых папок `folders`.
+///
+/// Имя, которого нет ни в одном workspace, — ошибка конфигурации.
+fn excluded_crates(folders: &[PathBuf], names: &[String]) -> Result<Vec<PathBuf>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let workspaces: Vec<CargoWorkspace> = folders
+        .iter()
+        .filter(|folder| folder.is_dir())
+        .filter_map(|folder| CargoWorkspace::discover(folder))
+        .collect();
+    let members: Vec<_> = workspaces.iter().flat_map(|workspace| &workspace.members).collect();
+    if members.is_empty() {
+        anyhow::bail!("--exclude-crate requires a base folder inside a Cargo workspace");
+    }
+    let mut directories = Vec::new();
+    for name in names {
+        let matching: Vec<_> = members.iter().filter(|member| member.name == *name).collect();
+        if matching.is_empty() {
+            let known: Vec<&str> = members.iter().map(|member| member.name.as_str()).collect();
+            anyhow::bail!(
+                "Unknown workspace crate for --exclude-crate: {} (crates: {})",
+                name,
+                known.join(", ")
+            );
+        }
+        directories.extend(matching.iter().filter_map(|member| member.local.clone()));
+    }
+    Ok(directories)
+}
+
+/// Возвращает путь файла для вывода: метка базовой папки и путь относительно нее.
+fn labeled_path(label: &str, base_folder: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(base_folder) {
+        // Базовый файл из `-f` обозначается одной меткой.
+        Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from(label),
+        Ok(relative) => Path::new(label).join(relative),
+        Err(_) => Path::new(label).join(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use converters::{Conversion, ConverterSource};
+    use report::CacheStats;
+    use std::fs::{self, File};
+    use tempfile::TempDir;
+
+    /// Создает временную структуру директорий и файлов для тестов.
+    fn create_test_structure() -> Result<TempDir> {
+        let temp_dir = tempfile::tempdir()?;
+        fs::create_dir_all(temp_dir.path().join("src"))?;
+        fs::create_dir_all(temp_dir.path().join(".hidden_dir"))?;
+        fs::write(
+            temp_dir.path().join("src/main.rs"),
+            "fn main() {}",
+        )?;
+        fs::write(temp_dir.path().join(".hidden_file.txt"), "hidden")?;
+        fs::write(temp_dir.path().join("config.exe"), "binary")?;
+        Ok(temp_dir)
+    }
+
+    #[tokio::test]
+    async fn test_config_skip_path() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            temp_dir.path().to_str().expect("path is utf8"),
+            "-s",
+            "skip_me",
+        ]);
+        let config = FlattenConfig::new(&args).await?;
+
+        assert!(config.should_skip_path(Path::new(""), Path::new("skip_me")));
+        assert!(!config.should_skip_path(Path::new(""), Path::new("src")));
+        // Тест скрытых файлов
+        assert!(config.should_skip_path(Path::new(""), Path::new(".hidden_dir")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_config_include_hidden() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            temp_dir.path().to_str().expect("path is utf8"),
+            "--include-hidden",
+        ]);
+        let config = FlattenConfig::new(&args).await?;
+
+        assert!(!config.should_skip_path(Path::new(""), Path::new(".hidden_dir")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_config_skip_file() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            temp_dir.path().to_str().expect("path is utf8"),
+            "-x",
+            "exe",
+        ]);
+        let config = FlattenConfig::new(&args).await?;
 
-    println!("{} Starting flatten process...", ROCKET);
-    println!("Processing {} folders", args.folders.len());
-    if config.dry_run {
-        println!("🔍 DRY RUN MODE - No output file will be created");
-    } else {
-        println!("Output file: {}", args.output.display());
+        assert!(config.should_skip_file(Path::new(""), Path::new("some.exe")));
+        assert!(!config.should_skip_file(Path::new(""), Path::new("main.rs")));
+        Ok(())
     }
-    println!();
-
-    let mut output_file = if !config.dry_run {
-        Some(
-            OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(&args.output)
-                .with_context(|| {
-                    format!("Failed to create output file: {}", args.output.display())
-                })?,
-        )
-    } else {
-        None
-    };
 
-    let total_files = AtomicUsize::new(0);
-    let total_bytes_processed = AtomicUsize::new(0);
-    let mut any_folder_found = false;
+    #[tokio::test]
+    async fn test_default_skip_precedence() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let folder = temp_dir.path().to_str().expect("path is utf8");
 
-    for base_folder in &args.folders {
-        if !base_folder.exists() {
-            eprintln!(
-                "Warning: Folder {} does not exist, skipping",
-                base_folder.display()
-            );
-            continue;
-        }
-        any_folder_found = true;
+        // Дополнительные значения добавляются к умолчаниям.
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            folder,
+            "--extra-skip-folders",
+            "vendor",
+            "--extra-skip-extensions",
+            ".lock",
+        ]);
+        let config = FlattenConfig::new(&args).await?;
+        assert!(config.should_skip_path(Path::new(""), Path::new("vendor")));
+        assert!(config.should_skip_path(Path::new(""), Path::new("node_modules")));
+        assert!(config.should_skip_file(Path::new(""), Path::new("Cargo.lock")));
+        assert!(config.should_skip_file(Path::new(""), Path::new("app.exe")));
 
-        println!("Processing folder: {}", base_folder.display());
+        // Без умолчаний остаются только дополнительные значения.
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            folder,
+            "--no-default-skips",
+            "--no-default-extensions",
+            "--extra-skip-folders",
+            "vendor",
+        ]);
+        let config = FlattenConfig::new(&args).await?;
+        assert!(config.should_skip_path(Path::new(""), Path::new("vendor")));
+        assert!(!config.should_skip_path(Path::new(""), Path::new("node_modules")));
+        assert!(!config.should_skip_file(Path::new(""), Path::new("app.exe")));
 
-        if let Some(ref mut output) = output_file {
-            print_folder_structure(base_folder, output, &config)?;
-        } else {
-            println!("📁 Folder structure for {}", base_folder.display());
-            let mut console_output = Vec::new();
-            print_folder_structure(base_folder, &mut console_output, &config)?;
-            println!("{}", String::from_utf8_lossy(&console_output));
-        }
+        // Явный список не отключается флагом `--no-default-skips`.
+        let args = Args::parse_from(["flatten-rust", "-f", folder, "--no-default-skips", "-s", "out"]);
+        let config = FlattenConfig::new(&args).await?;
+        assert!(config.should_skip_path(Path::new(""), Path::new("out")));
 
-        let files = collect_files(base_folder, &config)?;
-        let file_count = files.len();
-        total_files.fetch_add(file_count, Ordering::Relaxed);
+        // `--include-extension` побеждает списки и шаблоны.
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            folder,
+            "--extra-skip-extensions",
+            "json",
+            "-e",
+            "node",
+            "--include-extension",
+            "json",
+            "*.log",
+            "exe",
+        ]);
+        let config = FlattenConfig::new(&args).await?;
+        assert!(!config.should_skip_file(Path::new(""), Path::new("fixture.json")));
+        assert!(!config.should_skip_file(Path::new(""), Path::new("debug.log")));
+        assert!(!config.should_skip_file(Path::new(""), Path::new("app.exe")));
+        assert!(config.should_skip_file(Path::new(""), Path::new("lib.dll")));
+        Ok(())
+    }
 
-        if file_count == 0 {
-            println!("No files found in {}", base_folder.display());
-            continue;
+    /// Все перестановки порядка, в котором добавляются слои; порядок шаблонов
+    /// между собой задает их порядок включения.
+    fn layer_orders(layers: usize) -> Vec<Vec<usize>> {
+        if layers == 0 {
+            return vec![Vec::new()];
         }
-
-        let pb = ProgressBar::new(file_count as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template(PROGRESS_STYLE)
-                .context("Invalid progress bar template")?
-                .progress_chars("#>-"),
-        );
-
-        if let Some(ref mut output) = output_file {
-            writeln!(
-                output,
-                "### DIRECTORY {} FLATTENED CONTENT ###",
-                base_folder.display()
-            )?;
-        } else {
-            println!("📄 Files to process from {}:", base_folder.display());
+        let mut orders = Vec::new();
+        for order in layer_orders(layers - 1) {
+            for position in 0..=order.len() {
+                let mut order = order.clone();
+                order.insert(position, layers - 1);
+                orders.push(order);
+            }
         }
+        orders
+    }
+
+    #[tokio::test]
+    async fn test_rule_precedence_is_order_independent() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let root = temp_dir.path();
+        fs::write(root.join(FLATTENIGNORE_FILE), "!build\n!out\n")?;
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            root.to_str().expect("path is utf8"),
+            "--no-default-skips",
+            "--no-default-extensions",
+        ]);
+        let mut config = FlattenConfig::new(&args).await?;
 
-        let results = process_files_parallel(files, &config, Some(pb.clone()));
+        let template = |key: &str, line: usize, pattern: &str| {
+            let source = exclusions::PatternSource {
+                template: key.to_string(),
+                line,
+                pattern: pattern.to_string(),
+            };
+            ExclusionRule::from_template(source).unwrap().expect("pattern is applied")
+        };
+        let exclude = |target: RuleTarget, origin: PatternOrigin| ExclusionRule::exclude(target, origin);
+        let layers = [
+            vec![
+                exclude(RuleTarget::Name("dist".into()), PatternOrigin::Default),
+                exclude(RuleTarget::Name("vendor".into()), PatternOrigin::Default),
+                exclude(RuleTarget::Extension("log".into()), PatternOrigin::Default),
+            ],
+            vec![template("web", 1, "dist/"), template("web", 2, "build/"), template("web", 3, "*.log")],
+            vec![template("docs", 1, "!dist"), template("docs", 2, "!*.log")],
+            vec![exclude(RuleTarget::Name("out".into()), PatternOrigin::UserFlag)],
+        ];
 
-        for (file_path, content_result) in results {
-            if let Some(ref mut output) = output_file {
-                writeln!(output, "### {} BEGIN ###", file_path.display())?;
-                match content_result {
-                    Ok((content, bytes_processed)) => {
-                        output.write_all(content.as_bytes())?;
-                        total_bytes_processed
-                            .fetch_add(bytes_processed as usize, Ordering::Relaxed);
-                    }
-                    Err(e) => {
-                        writeln!(output, "[Error reading file: {}]", e)?;
-                    }
-                }
-                writeln!(output, "\n### {} END ###\n", file_path.display())?;
-            } else {
-                match content_result {
-                    Ok((_, bytes_processed)) => {
-                        println!("  ✅ {} ({} bytes)", file_path.display(), bytes_processed);
-                        total_bytes_processed
-                            .fetch_add(bytes_processed as usize, Ordering::Relaxed);
-                    }
-                    Err(e) => {
-                        println!("  ❌ {} ({})", file_path.display(), e);
-                    }
+        for order in layer_orders(layers.len()) {
+            config.user_rules = RuleList::default();
+            config.template_rules = RuleList::default();
+            config.default_rules = RuleList::default();
+            for &layer in &order {
+                for rule in &layers[layer] {
+                    config.add_rule(rule.clone());
                 }
             }
-        }
+            let docs_last = order.iter().position(|&l| l == 2) > order.iter().position(|&l| l == 1);
+            let contested = (!docs_last).then_some(SkipReason::Template);
 
-        if let Some(ref mut output) = output_file {
-            writeln!(
-                output,
-                "### DIRECTORY {} FLATTENED CONTENT ###",
-                base_folder.display()
-            )?;
+            let path = |name: &str| config.path_skip_reason(root, &root.join(name));
+            // Шаблоны сильнее умолчаний, между шаблонами побеждает включенный позже.
+            assert_eq!(path("dist"), contested, "order {:?}", order);
+            assert_eq!(config.file_skip_reason(root, &root.join("debug.log")), contested);
+            // `.flattenignore` сильнее шаблонов, командная строка сильнее `.flattenignore`.
+            assert_eq!(path("build"), None);
+            assert_eq!(path("out"), Some(SkipReason::Folder));
+            assert_eq!(path("vendor"), Some(SkipReason::Folder));
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_blocking_outside_runtime() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let output = temp_dir.path().join("out.md");
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            temp_dir.path().join("src").to_str().expect("path is utf8"),
+            "-o",
+            output.to_str().expect("path is utf8"),
+        ]);
+        let report = run_blocking(&args)?;
+        assert_eq!(report.outputs.len(), 1);
+        assert!(fs::read_to_string(&output)?.contains("fn main() {}"));
+        Ok(())
+    }
 
-        pb.finish_with_message("Done");
+    #[test]
+    fn test_run_blocking_twice_with_threads() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let output = temp_dir.path().join("out.md");
+        for threads in ["2", "3"] {
+            let args = Args::parse_from([
+                "flatten-rust",
+                "-f",
+                temp_dir.path().join("src").to_str().expect("path is utf8"),
+                "-o",
+                output.to_str().expect("path is utf8"),
+                "--offline",
+                "-t",
+                threads,
+            ]);
+            let report = run_blocking(&args)?;
+            assert_eq!(report.outputs.len(), 1);
+        }
+        assert!(fs::read_to_string(&output)?.contains("fn main() {}"));
+        Ok(())
     }
 
-    if !any_folder_found {
-        return Ok(());
+    #[cfg(feature = "network")]
+    #[tokio::test]
+    async fn test_run_blocking_inside_runtime() -> Result<()> {
+        // Без `--offline` нужен собственный рантайм, который не должен
+        // конфликтовать с рантаймом теста. Будущее не читает `~/.flatten`.
+        let flavor = block_on_own_runtime(|| async {
+            tokio::task::yield_now().await;
+            Ok(tokio::runtime::Handle::current().runtime_flavor())
+        })?;
+        assert_eq!(flavor, tokio::runtime::RuntimeFlavor::CurrentThread);
+        Ok(())
     }
 
-    println!();
-    println!("{} Flatten completed successfully!", style("✓").green());
-    let total = total_files.load(Ordering::Relaxed);
-    println!("Total files processed: {}", total);
+    #[test]
+    fn test_flatten_paths_ignores_outputs_and_commands() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let ignored = temp_dir.path().join("ignored.md");
+        let options = FlattenOptions::parse([
+            "-o",
+            ignored.to_str().expect("path is utf8"),
+            "--list-templates",
+            "--threads",
+            "2",
+        ])?;
+        let mut document = Vec::new();
+        let report = flatten_paths(&[temp_dir.path().join("src")], &options, &mut document)?;
+        let document = String::from_utf8(document)?;
+        assert!(document.contains("fn main() {}"));
+        assert!(report.files.iter().any(|file| file.path.ends_with("main.rs")));
+        assert!(!ignored.exists());
 
-    if config.show_stats {
-        print_stats(total, total_bytes_processed.load(Ordering::Relaxed) as u64);
+        assert!(FlattenOptions::parse(["--no-such-flag"]).is_err());
+        let mut empty = Vec::new();
+        let missing = temp_dir.path().join("missing");
+        assert!(flatten_paths(&[missing], &FlattenOptions::default(), &mut empty).is_err());
+        Ok(())
     }
 
-    if !config.dry_run {
-        println!("Output written to: {}", args.output.display());
+    #[tokio::test]
+    async fn test_flatten_paths_inside_runtime() -> Result<()> {
+        let temp_dir = create_test_structure()?;
+        let options = FlattenOptions::parse(["--format", "json"])?;
+        let mut document = Vec::new();
+        flatten_paths(&[temp_dir.path().join("src")], &options, &mut document)?;
+        let document: serde_json::Value = serde_json::from_slice(&document)?;
+        assert!(document.to_string().contains("fn main() {}"));
+        Ok(())
     }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn test_deep_tree_stops_at_depth_ceiling() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("deep");
+        let mut directory = root.clone();
+        for level in 1..=300 {
+            directory.push("d");
+            if level == 40 {
+                fs::create_dir_all(&directory)?;
+                fs::write(directory.join("middle.txt"), "middle")?;
+            }
+        }
+        fs::create_dir_all(&directory)?;
+        fs::write(directory.join("bottom.txt"), "bottom")?;
 
-/// Выводит статистику по завершении работы.
-fn print_stats(total_files: usize, total_bytes: u64) {
-    const KB: f64 = 1024.0;
-    const MB: f64 = 1_048_576.0;
+        for extra in [None, Some("--parallel-walk")] {
+            let mut argv = vec!["flatten-rust", "-f", root.to_str().expect("path is utf8")];
+            argv.extend(["--no-collapse-dirs"].into_iter().chain(extra));
+            let mut document = Vec::new();
+            let report = run_to_writer(&Args::parse_from(&argv), &mut document).await?;
+            let document = String::from_utf8(document)?;
 
-    let bytes_str = if total_bytes as f64 >= MB {
-        format!("{:.2} MB", total_bytes as f64 / MB)
-    } else if total_bytes as f64 >= KB {
-        format!("{:.2} KB", total_bytes as f64 / KB)
-    } else {
-        format!("{} bytes", total_bytes)
-    };
-    println!("Total bytes processed: {}", bytes_str);
+            let ceilings: Vec<_> = report
+                .warnings
+                .iter()
+                .filter(|warning| warning.kind == WarningKind::DepthCeiling)
+                .collect();
+            assert_eq!(ceilings.len(), 1, "{:?}", report.warnings);
+            assert!(ceilings[0].message.contains("128 levels deep"));
+            assert!(document.contains("middle"), "file above the ceiling is kept");
+            assert!(!document.contains("bottom"));
+            // Отступ ограничен, глубина записана числом.
+            assert!(document.contains("[depth 128] 📁 d/\n"));
+            assert!(document.len() < 64 * 1024, "{} bytes", document.len());
+        }
 
-    if total_files > 0 {
-        let avg_size = total_bytes / total_files as u64;
-        let avg_str = if avg_size as f64 >= KB {
-            format!("{:.2} KB", avg_size as f64 / KB)
-        } else {
-            format!("{} bytes", avg_size)
-        };
-        println!("Average file size: {}", avg_str);
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            root.to_str().expect("path is utf8"),
+            "--depth-ceiling",
+            "0",
+        ]);
+        let mut document = Vec::new();
+        let report = run_to_writer(&args, &mut document).await?;
+        assert!(String::from_utf8(document)?.contains("bottom"));
+        assert!(report.warnings.is_empty());
+        Ok(())
     }
-}
 
-/// Рекурсивно собирает пути ко всем файлам в директории, учитывая конфигурацию.
-fn collect_files(directory: &Path, config: &FlattenConfig) -> Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
-    let mut walkdir = WalkDir::new(directory).follow_links(false);
+    #[tokio::test]
+    async fn test_dir_file_limit_summarizes_large_directory() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        let migrations = root.join("migrations");
+        fs::create_dir_all(&migrations)?;
+        for index in 0..1000 {
+            fs::write(migrations.join(format!("{:04}.sql", index)), "select 1;\n")?;
+        }
+        fs::write(migrations.join("README.md"), "migrations")?;
+        fs::write(root.join("main.rs"), "fn main() {}")?;
 
-    if config.max_depth > 0 {
-        walkdir = walkdir.max_depth(config.max_depth);
-    }
+        for extra in [None, Some("--parallel-walk")] {
+            let mut argv = vec!["flatten-rust", "-f", root.to_str().expect("path is utf8")];
+            argv.extend(["--dir-file-limit", "10"].into_iter().chain(extra));
+            let mut document = Vec::new();
+            let report = run_to_writer(&Args::parse_from(&argv), &mut document).await?;
+            let document = String::from_utf8(document)?;
 
-    for entry in walkdir
-        .into_iter()
-        .filter_entry(|e| !config.should_skip_path(e.path()))
-    {
-        let entry = entry?;
-        if entry.file_type().is_file() {
-            files.push(entry.path().to_path_buf());
+            // Остаются первые по имени файлы; README.md идет после цифр.
+            assert!(document.contains("### project/migrations/0009.sql BEGIN ###"));
+            assert!(!document.contains("### project/migrations/0010.sql BEGIN ###"));
+            assert!(!document.contains("README.md"));
+            assert!(document.contains("fn main() {}"));
+            let summary = "… and 991 more files in migrations/ (9.68 KB total)";
+            assert!(document.contains(&format!("    {}\n", summary)), "{}", document);
+            assert!(document.contains(&format!("BEGIN ###\n{}\n", summary)));
+            assert_eq!(report.skipped.get(&SkipReason::DirFileLimit), Some(&991));
+            assert_eq!(report.files.len(), 11);
         }
+
+        // `--keep` снимает ограничение с подходящих файлов.
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            root.to_str().expect("path is utf8"),
+            "--dir-file-limit",
+            "10",
+            "--keep",
+            "migrations/*.md",
+        ]);
+        let mut document = Vec::new();
+        run_to_writer(&args, &mut document).await?;
+        let document = String::from_utf8(document)?;
+        assert!(document.contains("### project/migrations/README.md BEGIN ###"));
+        assert!(document.contains("… and 990 more files in migrations/"));
+        Ok(())
     }
-    Ok(files)
-}
 
-/// Выводит в `writer` древовидную структуру директории.
-fn print_folder_structure<W: Write>(
-    directory: &Path,
-    writer: &mut W,
-    config: &FlattenConfig,
-) -> Result<()> {
-    writeln!(
-        writer,
-        "### DIRECTORY {} FOLDER STRUCTURE ###",
-        directory.display()
-    )?;
+    #[tokio::test]
+    async fn test_outline_replaces_contents() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(&root)?;
+        let body = "    let value = 1;\n".repeat(50);
+        fs::write(root.join("lib.rs"), format!("pub fn run() {{\n{}}}\n", body))?;
+        fs::write(root.join("notes.txt"), "first\nsecond\nthird\n")?;
+
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            root.to_str().expect("path is utf8"),
+            "--outline",
+            "--head-lines",
+            "1",
+        ]);
+        let mut document = Vec::new();
+        let report = run_to_writer(&args, &mut document).await?;
+        let document = String::from_utf8(document)?;
+        assert!(document.contains("[Outline: 1 of 52 lines]\n1: pub fn run() {\n"));
+        assert!(!document.contains("let value"));
+        assert!(document.contains("first\n[… 2 more lines]\n"));
 
-    let mut walkdir = WalkDir::new(directory).follow_links(false);
-    if config.max_depth > 0 {
-        walkdir = walkdir.max_depth(config.max_depth);
+        let outline = &report.conversions["outline"];
+        assert_eq!((outline.files, outline.original_bytes), (1, 967));
+        assert_eq!(outline.tokens_saved, (967 - outline.emitted_bytes as usize) / 4);
+        assert_eq!(report.conversions["head"].files, 1);
+        Ok(())
     }
 
-    for entry in walkdir.into_iter().filter_entry(|e| {
-        if e.file_type().is_dir() {
-            !config.should_skip_path(e.path()) || config.show_skipped
-        } else {
-            !config.should_skip_file(e.path())
-        }
-    }) {
-        let entry = entry?;
-        let path = entry.path();
-        let depth = entry.depth();
-        if depth == 0 {
-            continue;
+    #[tokio::test]
+    async fn test_content_cache_survives_mtime_reset() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(&root)?;
+        fs::write(root.join("lib.rs"), "pub fn run() {\n    let value = 1;\n}\n")?;
+        fs::write(root.join("notes.txt"), "first\r\nsecond\r\n")?;
+        let cache = temp_dir.path().join("cache");
+
+        let args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            root.to_str().expect("path is utf8"),
+            "--outline",
+            "--content-cache",
+            cache.to_str().expect("path is utf8"),
+        ]);
+        let mut first = Vec::new();
+        let report = run_to_writer(&args, &mut first).await?;
+        assert_eq!(report.content_cache, Some(CacheStats { hits: 0, misses: 2 }));
+
+        // Как после свежего клона: время изменения другое, содержимое то же.
+        for name in ["lib.rs", "notes.txt"] {
+            File::options()
+                .write(true)
+                .open(root.join(name))?
+                .set_modified(std::time::SystemTime::UNIX_EPOCH)?;
         }
+        let mut second = Vec::new();
+        let report = run_to_writer(&args, &mut second).await?;
+        assert_eq!(report.content_cache, Some(CacheStats { hits: 2, misses: 0 }));
+        assert_eq!(first, second);
+        assert_eq!(report.conversions["outline"].files, 1);
+        Ok(())
+    }
 
-        let indent = "    ".repeat(depth - 1);
-        let file_name = path.file_name().unwrap_or_else(|| OsStr::new(""));
+    /// Переписывает файл из другого потока сразу после его чтения.
+    struct RewriteAfterRead(&'static str);
 
-        if entry.file_type().is_dir() {
-            if config.should_skip_path(path) {
-                writeln!(
-                    writer,
-                    "{}{} {}/ (skipped)",
-                    indent,
-                    SKIP,
-                    file_name.to_string_lossy()
-                )?;
-            } else {
-                writeln!(
-                    writer,
-                    "{}{} {}/",
-                    indent,
-                    FOLDER,
-                    file_name.to_string_lossy()
-                )?;
+    impl ProgressSink for RewriteAfterRead {
+        fn on_file_done(&self, path: &Path, _bytes: u64, _ok: bool) {
+            if path.file_name().is_some_and(|name| name == self.0) {
+                let path = path.to_path_buf();
+                // Обработка продолжается только после записи.
+                std::thread::spawn(move || fs::write(path, "fn main() { changed(); }\n"))
+                    .join()
+                    .expect("writer thread panicked")
+                    .expect("file is writable");
             }
-        } else {
-            writeln!(
-                writer,
-                "{}{} {}",
-                indent,
-                FILE,
-                file_name.to_string_lossy()
-            )?;
         }
     }
 
-    writeln!(
-        writer,
-        "### DIRECTORY {} FOLDER STRUCTURE ###\n",
-        directory.display()
-    )?;
-    Ok(())
-}
+    #[tokio::test]
+    async fn test_file_changed_during_run() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(&root)?;
+        fs::write(root.join("main.rs"), "fn main() {}\n")?;
+        fs::write(root.join("lib.rs"), "pub fn lib() {}\n")?;
+        let output = temp_dir.path().join("out.md");
+        let args = |extra: &[&str]| {
+            let mut argv = vec![
+                "flatten-rust",
+                "-f",
+                root.to_str().expect("path is utf8"),
+                "-o",
+                output.to_str().expect("path is utf8"),
+            ];
+            argv.extend(extra);
+            Args::parse_from(argv)
+        };
+        let sink = || -> Option<Arc<dyn ProgressSink>> {
+            Some(Arc::new(RewriteAfterRead("main.rs")))
+        };
 
-/// Эффективно читает содержимое файла, используя memory-mapping.
-fn read_file_content_fast(path: &Path, max_size: u64) -> Result<(String, u64)> {
-    let file =
-        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
-    let metadata = file
-        .metadata()
-        .with_context(|| format!("Failed to read metadata: {}", path.display()))?;
-    let file_size = metadata.len();
+        run_with(&args(&[]), sink()).await?;
+        let document = fs::read_to_string(&output)?;
+        let marked = format!("main.rs BEGIN ### {}\nfn main() {{}}\n", output::STALE_NOTE);
+        assert!(document.contains(&marked));
+        assert!(document.contains("lib.rs BEGIN ###\n"));
 
-    if max_size > 0 && file_size > max_size {
-        return Ok((
-            format!("[File too large: {} bytes]", file_size),
-            file_size,
-        ));
+        // С `--recheck` выводится содержимое после изменения, без пометки.
+        fs::write(root.join("main.rs"), "fn main() {}\n")?;
+        run_with(&args(&["--recheck"]), sink()).await?;
+        let document = fs::read_to_string(&output)?;
+        assert!(document.contains("main.rs BEGIN ###\nfn main() { changed(); }\n"));
+        assert!(!document.contains(output::STALE_NOTE));
+        Ok(())
     }
-    if file_size == 0 {
-        return Ok((String::new(), 0));
+
+    /// Отказывается от большого запуска и считает прочитанные до вопроса файлы.
+    #[derive(Default)]
+    struct DeclineLargeRun {
+        read: AtomicUsize,
+        asked: AtomicUsize,
     }
 
-    // SAFETY: Memory mapping a file is safe. The file is read-only, and the lifetime
-    // of the mmap is tied to the function's scope, ensuring the file handle outlives it.
-    // The underlying file is not modified while the map is active.
-    let mmap = unsafe {
-        MmapOptions::new()
-            .map(&file)
-            .with_context(|| format!("Failed to memory map file: {}", path.display()))?
-    };
+    impl ProgressSink for DeclineLargeRun {
+        fn on_file_started(&self, _relative: &Path) {
+            self.read.fetch_add(1, Ordering::SeqCst);
+        }
 
-    let content =
-        String::from_utf8(mmap.to_vec()).unwrap_or_else(|_| String::from_utf8_lossy(&mmap).into());
+        fn confirm_run(&self, preview: &RunPreview) -> bool {
+            assert_eq!(preview.files, 6);
+            self.asked.fetch_add(1, Ordering::SeqCst);
+            false
+        }
+    }
 
-    Ok((content, file_size))
-}
+    #[tokio::test]
+    async fn test_confirm_large_run_before_reading() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(&root)?;
+        for i in 0..6 {
+            fs::write(root.join(format!("file_{}.rs", i)), "fn f() {}\n")?;
+        }
+        let folder = root.to_str().expect("path is utf8");
+        let output = temp_dir.path().join("out.md");
+        let output = output.to_str().expect("path is utf8");
 
-/// Обрабатывает список файлов в параллельном режиме.
-fn process_files_parallel(
-    files: Vec<PathBuf>,
-    config: &FlattenConfig,
-    progress_bar: Option<ProgressBar>,
-) -> Vec<(PathBuf, Result<(String, u64)>)> {
-    let processed_count = AtomicUsize::new(0);
-
-    files
-        .into_par_iter()
-        .map(|file_path| {
-            let result = if config.should_skip_file(&file_path) {
-                Ok((
-                    format!("[Binary file skipped: {}]", file_path.display()),
-                    0,
-                ))
-            } else {
-                read_file_content_fast(&file_path, config.max_file_size)
-            };
+        for extra in [None, Some("--parallel-walk")] {
+            let argv = ["flatten-rust", "-f", folder, "-o", output, "--confirm-threshold", "2"];
+            let args = Args::parse_from(argv.into_iter().chain(extra));
+            let sink = Arc::new(DeclineLargeRun::default());
+            let result = run_with(&args, Some(sink.clone() as Arc<dyn ProgressSink>)).await;
 
-            let count = processed_count.fetch_add(1, Ordering::Relaxed);
-            if let Some(pb) = &progress_bar {
-                pb.set_position(count as u64 + 1);
-            }
+            assert!(matches!(result, Err(FlattenError::Aborted)), "{:?}", extra);
+            assert_eq!(sink.asked.load(Ordering::SeqCst), 1);
+            // Параллельный обход перестает читать файлы, найдя их больше порога.
+            assert!(sink.read.load(Ordering::SeqCst) <= 2, "{:?}", extra);
+        }
+        Ok(())
+    }
 
-            (file_path, result)
-        })
-        .collect()
-}
+    /// Конвертер `.slow`, который читает файл `delay`.
+    #[derive(Debug)]
+    struct SlowConverter(Duration);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::TempDir;
+    impl FileConverter for SlowConverter {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
 
-    /// Создает временную структуру директорий и файлов для тестов.
-    fn create_test_structure() -> Result<TempDir> {
+        fn convert(&self, _path: &Path, _source: ConverterSource<'_>) -> Conversion {
+            std::thread::sleep(self.0);
+            Conversion::Text("done".to_string())
+        }
+    }
+
+    /// Строит документ из `files` медленных файлов с лимитом `--time-limit 300ms`.
+    ///
+    /// Возвращает документ, итог запуска и его длительность.
+    async fn run_slow_files(
+        files: usize,
+        delay: Duration,
+        extra: &[&str],
+    ) -> Result<(String, FlattenResult<FlattenReport>, Duration)> {
         let temp_dir = tempfile::tempdir()?;
-        fs::create_dir_all(temp_dir.path().join("src"))?;
-        fs::create_dir_all(temp_dir.path().join(".hidden_dir"))?;
-        fs::write(
-            temp_dir.path().join("src/main.rs"),
-            "fn main() {}",
-        )?;
-        fs::write(temp_dir.path().join(".hidden_file.txt"), "hidden")?;
-        fs::write(temp_dir.path().join("config.exe"), "binary")?;
-        Ok(temp_dir)
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(&root)?;
+        for index in 0..files {
+            fs::write(root.join(format!("file{:03}.slow", index)), "data")?;
+        }
+        let mut argv = vec!["flatten-rust", "-f", root.to_str().expect("path is utf8")];
+        argv.extend(["--time-limit", "300ms"]);
+        argv.extend(extra);
+        let args = Args::parse_from(argv);
+
+        let started = std::time::Instant::now();
+        let mut config = FlattenConfig::new(&args).await?;
+        config.register_converter("slow", Arc::new(SlowConverter(delay)));
+        let mut report = FlattenReport::default();
+        let mut document = Vec::new();
+        let errors = {
+            let format = OutputFormat::Markdown;
+            let mut emitter = output::emitter_for(format, &mut document, &config, true);
+            let sinks = DocumentSinks {
+                emitter: emitter.as_mut(),
+                manifest: None,
+                progress: &NoProgress,
+            };
+            emit_document(&args, &config, std::slice::from_ref(&root), sinks, &mut report, false)?
+        };
+        let elapsed = started.elapsed();
+        Ok((String::from_utf8(document)?, partial_result(report, errors), elapsed))
     }
 
     #[tokio::test]
-    async fn test_config_skip_path() -> Result<()> {
+    async fn test_time_limit_stops_scheduling_files() -> Result<()> {
+        // Начатые файлы дочитываются: запуск длится не дольше лимита и одного чтения.
+        let delay = Duration::from_millis(100);
+        let (document, result, elapsed) = run_slow_files(40, delay, &[]).await?;
+        assert!(elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+        let Err(error @ FlattenError::TimeLimit { .. }) = result else {
+            panic!("expected a time limit error: {:?}", result);
+        };
+        assert_eq!(error.exit_code(), error::EXIT_TIME_LIMIT);
+        let FlattenError::TimeLimit { report, truncation } = error else {
+            unreachable!();
+        };
+        let written = document.matches(" BEGIN ###\ndone\n").count();
+        assert!(written > 0 && written < 40, "{} files written", written);
+        assert_eq!(report.files.len(), written);
+        assert_eq!(truncation.unprocessed_files, 40 - written);
+        assert_eq!(report.summary().unprocessed_files, 40 - written);
+        assert!(document.contains(&format!(
+            "### TRUNCATED: time limit of 300ms reached, {} files not processed ###",
+            40 - written
+        )));
+        let trailer = document.lines().last().expect("document has a trailer");
+        assert!(trailer.ends_with(" truncated=time-limit ###"), "{}", trailer);
+
+        // С `--time-limit-hard` начатые чтения прерываются и не попадают в документ.
+        let delay = Duration::from_secs(5);
+        let (document, result, elapsed) = run_slow_files(4, delay, &["--time-limit-hard"]).await?;
+        assert!(elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+        let Err(FlattenError::TimeLimit { report, truncation }) = result else {
+            panic!("expected a time limit error: {:?}", result);
+        };
+        assert!(report.files.is_empty());
+        assert_eq!(truncation.unprocessed_files, 4);
+        assert!(!document.contains(" BEGIN ###"));
+
+        // Лимит, который не истек, ничего не меняет.
+        let (document, result, _) = run_slow_files(2, Duration::ZERO, &[]).await?;
+        assert_eq!(result?.files.len(), 2);
+        assert!(!document.contains("TRUNCATED"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_help_lists_every_exit_code() {
+        use clap::CommandFactory;
+        let help = Args::command().render_long_help().to_string();
+        let codes = help.split("КОДЫ ЗАВЕРШЕНИЯ:").nth(1).expect("exit code section");
+        for code in [
+            error::EXIT_OK,
+            error::EXIT_USAGE,
+            error::EXIT_IO,
+            error::EXIT_PARTIAL,
+            error::EXIT_TIME_LIMIT,
+            error::EXIT_NOT_INCLUDED,
+            error::EXIT_OUTDATED,
+            error::EXIT_DOCTOR_FAILED,
+            error::EXIT_INTEGRITY,
+        ] {
+            assert!(codes.contains(&format!("\n  {}  ", code)), "code {} is not listed", code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_to_writer_renders_in_memory() -> Result<()> {
         let temp_dir = create_test_structure()?;
+        let src = temp_dir.path().join("src");
+        let output = temp_dir.path().join("out.md");
         let args = Args::parse_from([
             "flatten-rust",
             "-f",
-            temp_dir.path().to_str().expect("path is utf8"),
-            "-s",
-            "skip_me",
+            src.to_str().expect("path is utf8"),
+            "-o",
+            output.to_str().expect("path is utf8"),
         ]);
-        let config = FlattenConfig::new(&args).await?;
 
-        assert!(config.should_skip_path(Path::new("skip_me")));
-        assert!(!config.should_skip_path(Path::new("src")));
-        // Тест скрытых файлов
-        assert!(config.should_skip_path(Path::new(".hidden_dir")));
+        let mut document = Vec::new();
+        let report = run_to_writer(&args, &mut document).await?;
+        let document = String::from_utf8(document)?;
+
+        assert!(document.starts_with("### DIRECTORY src FOLDER STRUCTURE ###\n"));
+        assert!(document.contains("### src/main.rs BEGIN ###\nfn main() {}\n### src/main.rs END ###"));
+        assert!(document.ends_with("### DIRECTORY src FLATTENED CONTENT ###\n"));
+        assert_eq!(report.files.len(), 1);
+        assert!(!output.exists());
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_config_include_hidden() -> Result<()> {
+    async fn test_folder_overrides_apply_per_folder() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let backend = temp_dir.path().join("backend");
+        let frontend = temp_dir.path().join("frontend");
+        for folder in [&backend, &frontend] {
+            fs::create_dir_all(folder.join("cache"))?;
+            fs::write(folder.join("cache/state.txt"), "cached")?;
+            fs::write(folder.join("notes.md"), "# notes")?;
+            fs::write(folder.join("data.txt"), "x".repeat(64))?;
+        }
+        let mut args = Args::parse_from([
+            "flatten-rust",
+            "-f",
+            backend.to_str().expect("path is utf8"),
+            frontend.to_str().expect("path is utf8"),
+            "--extra-skip-folders",
+            "cache",
+        ]);
+        // Блок `backend` найден по каноническому пути, `frontend` — по записи `-f`.
+        let config: config::ManagerConfig = serde_json::from_value(serde_json::json!({
+            "folders": {
+                backend.canonicalize()?.to_str().expect("path is utf8"): {
+                    "max_file_size": "32B",
+                    "extra_skip_extensions": ["md"]
+                },
+                frontend.to_str().expect("path is utf8"): {
+                    "extra_skip_folders": [],
+                    "extra_skip_extensions": ["txt"],
+                    "include_extensions": ["md"]
+                }
+            }
+        }))?;
+        args.folder_overrides = folder_overrides::parse_overrides(&config)?;
+
+        let mut document = Vec::new();
+        let report = run_to_writer(&args, &mut document).await?;
+        let reason = |folder: &str, file: &str| {
+            report
+                .files
+                .iter()
+                .find(|entry| entry.path == Path::new(folder).join(file))
+                .map(|entry| entry.skip_reason)
+        };
+        assert_eq!(reason("backend", "data.txt"), Some(Some(SkipReason::TooLarge)));
+        assert_eq!(reason("backend", "notes.md"), Some(Some(SkipReason::Extension)));
+        assert_eq!(reason("backend", "cache/state.txt"), None);
+        assert_eq!(reason("frontend", "data.txt"), Some(Some(SkipReason::Extension)));
+        assert_eq!(reason("frontend", "notes.md"), Some(None));
+        assert_eq!(reason("frontend", "cache/state.txt"), Some(Some(SkipReason::Extension)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_heading_styles_snapshot() -> Result<()> {
         let temp_dir = create_test_structure()?;
+        let src = temp_dir.path().join("src");
+        let render = |extra: &[&str]| {
+            let mut argv = vec!["flatten-rust", "-f", src.to_str().expect("path is utf8")];
+            argv.extend_from_slice(extra);
+            let args = Args::parse_from(argv);
+            async move {
+                let mut document = Vec::new();
+                run_to_writer(&args, &mut document).await?;
+                Ok::<_, anyhow::Error>(String::from_utf8(document)?)
+            }
+        };
+
+        assert_eq!(
+            render(&[]).await?,
+            "### DIRECTORY src FOLDER STRUCTURE ###\n📄 main.rs\n\
+             ### DIRECTORY src FOLDER STRUCTURE ###\n\n\
+             ### DIRECTORY src FLATTENED CONTENT ###\n\
+             ### src/main.rs BEGIN ###\nfn main() {}\n### src/main.rs END ###\n\n\
+             ### DIRECTORY src FLATTENED CONTENT ###\n"
+        );
+        assert_eq!(
+            render(&["--heading-style", "headings"]).await?,
+            "# src\n\n## Folder structure\n\n```\n📄 main.rs\n```\n\n## Files\n\n\
+             ### src/main.rs\n```rust\nfn main() {}\n```\n\n"
+        );
+        assert_eq!(
+            render(&["--heading-style", "headings", "--base-heading-level", "3"]).await?,
+            "### src\n\n#### Folder structure\n\n```\n📄 main.rs\n```\n\n#### Files\n\n\
+             ##### src/main.rs\n```rust\nfn main() {}\n```\n\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stats_separate_read_and_skipped_bytes() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src"))?;
+        std::fs::write(root.join("src/main.rs"), "fn main() {}")?;
+        std::fs::write(root.join("huge.txt"), "x".repeat(5000))?;
+        std::fs::write(root.join("app.exe"), b"MZ\x00\x01")?;
         let args = Args::parse_from([
             "flatten-rust",
             "-f",
-            temp_dir.path().to_str().expect("path is utf8"),
-            "--include-hidden",
+            root.to_str().expect("path is utf8"),
+            "-m",
+            "1000",
         ]);
-        let config = FlattenConfig::new(&args).await?;
 
-        assert!(!config.should_skip_path(Path::new(".hidden_dir")));
+        let mut document = Vec::new();
+        let summary = run_to_writer(&args, &mut document).await?.summary();
+        assert_eq!(summary.total_files, 1);
+        assert_eq!(summary.placeholder_files, 2);
+        assert_eq!(summary.read_bytes, 12);
+        assert_eq!(summary.skipped_bytes, 5004);
+        assert_eq!(summary.emitted_bytes, {
+            let placeholders = "[File too large: 5000 bytes]".len()
+                + format!("[Binary file skipped: {}]", root.join("app.exe").display()).len();
+            12 + placeholders as u64
+        });
+        assert_eq!(summary.skipped[&SkipReason::TooLarge], 1);
+        assert_eq!(summary.skipped[&SkipReason::Extension], 1);
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_config_skip_file() -> Result<()> {
+    async fn test_in_flight_budget_caps_buffered_bytes() -> Result<()> {
+        const MB: u64 = 1024 * 1024;
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root)?;
+        let chunk = "x".repeat(10 * MB as usize);
+        for i in 0..8 {
+            std::fs::write(root.join(format!("file_{}.txt", i)), &chunk)?;
+        }
+        let folder = root.to_str().expect("path is utf8");
+
+        for budget in ["25MB", "5MB"] {
+            let extras: [&[&str]; 4] = [
+                &[],
+                &["--parallel-walk"],
+                &["--grep", "x"],
+                &["--grep", "x", "--parallel-walk"],
+            ];
+            for extra in extras {
+                let args = Args::parse_from(
+                    ["flatten-rust", "-f", folder, "--max-in-flight-bytes", budget]
+                        .iter()
+                        .chain(extra),
+                );
+                let mut document = Vec::new();
+                let report = run_to_writer(&args, &mut document).await?;
+
+                assert_eq!(report.files.len(), 8);
+                assert!(document.len() as u64 > 80 * MB);
+                let limit = report::parse_size(budget).expect("valid size");
+                // Файл больше бюджета обрабатывается в одиночку.
+                assert!(report.peak_in_flight_bytes <= limit.max(10 * MB), "{}", budget);
+                assert!(report.peak_in_flight_bytes >= 10 * MB);
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_variants() -> Result<()> {
         let temp_dir = create_test_structure()?;
+        let folder = temp_dir.path().to_str().expect("path is utf8");
+
+        let args = Args::parse_from(["flatten-rust", "-d"]);
+        let error = run_with(&args, None).await.unwrap_err();
+        assert!(matches!(error, FlattenError::InvalidConfig(ref message) if message.contains("--folders")));
+
+        let output = temp_dir.path().join("missing/out.md");
         let args = Args::parse_from([
             "flatten-rust",
             "-f",
-            temp_dir.path().to_str().expect("path is utf8"),
-            "-x",
-            "exe",
+            folder,
+            "-o",
+            output.to_str().expect("path is utf8"),
         ]);
-        let config = FlattenConfig::new(&args).await?;
+        let error = run_with(&args, None).await.unwrap_err();
+        assert!(matches!(error, FlattenError::OutputIo { ref path, .. } if *path == output));
+
+        let args = Args::parse_from(["flatten-rust", "-f", folder, "--priority", "[oops"]);
+        let error = FlattenConfig::new(&args).await.unwrap_err();
+        assert!(matches!(error, FlattenError::InvalidConfig(ref message) if message.contains("[oops")));
 
-        assert!(config.should_skip_file(Path::new("some.exe")));
-        assert!(!config.should_skip_file(Path::new("main.rs")));
+        let args = Args::parse_from(["flatten-rust", "-f", folder, "--alias", "no-equals-sign"]);
+        let error = run_to_writer(&args, &mut Vec::new()).await.unwrap_err();
+        assert!(matches!(error, FlattenError::InvalidConfig(ref message) if message.contains("--alias")));
         Ok(())
     }
 }
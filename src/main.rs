@@ -2,14 +2,96 @@
 //!
 //! Этот крейт предоставляет исполняемый файл для утилиты `flatten-rust`.
 //! Он служит тонкой оберткой вокруг библиотеки `flatten_rust`, отвечая за
-//! парсинг аргументов командной строки и запуск основного процесса.
+//! парсинг аргументов командной строки (с учетом переменных окружения `FLATTEN_*`),
+//! запуск основного процесса, вопросы перед большим запуском
+//! (`--confirm-threshold`) и удалением всего кэша (`--cache-prune --all`), остановку
+//! `--serve` по Ctrl-C и выбор кода завершения.
 
-use anyhow::Result;
-use clap::Parser;
-use flatten_rust::Args;
+use flatten_rust::cache_maintenance::{self, CacheStatus};
+use flatten_rust::error::{EXIT_OK, FlattenError};
+use flatten_rust::preview::RunPreview;
+use flatten_rust::progress::IndicatifProgress;
+use std::io::{BufRead, IsTerminal};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
-    flatten_rust::run(&args).await
+fn main() -> ExitCode {
+    let args = flatten_rust::environment::parse_process_args();
+    let stop = Arc::new(AtomicBool::new(false));
+    // Ctrl-C перехватывается только для `--serve`; остальные запуски он прерывает как обычно.
+    #[cfg(feature = "serve")]
+    if args.serve.is_some()
+        && let Err(error) = stop_on_ctrl_c(&stop)
+    {
+        eprintln!("Error: Failed to install Ctrl-C handler: {}", error);
+        return ExitCode::from(flatten_rust::error::EXIT_IO as u8);
+    }
+    let progress = IndicatifProgress::with_width(args.width)
+        .with_confirm(confirm_large_run)
+        .with_cache_prune_confirm(confirm_cache_prune)
+        .with_stop_flag(stop);
+    let code = match flatten_rust::run_blocking_with(&args, Some(Arc::new(progress))) {
+        Ok(_) => EXIT_OK,
+        // Ошибки чтения отдельных файлов уже записаны в документ; без `--quiet`
+        // они перечислены и в сводке предупреждений.
+        Err(error @ FlattenError::Partial { .. }) => {
+            eprintln!("Warning: {}", error);
+            if let FlattenError::Partial { errors, .. } = &error
+                && args.quiet
+            {
+                for file_error in errors {
+                    eprintln!("  {}", file_error);
+                }
+            }
+            if args.fail_on_error {
+                error.exit_code()
+            } else {
+                EXIT_OK
+            }
+        }
+        // Команда уже вывела свой результат; остается только код завершения.
+        Err(
+            error @ (FlattenError::NotIncluded { .. }
+            | FlattenError::Outdated { .. }
+            | FlattenError::IntegrityMismatch { .. }
+            | FlattenError::DoctorFailed { .. }),
+        ) => error.exit_code(),
+        // Документ записан, но обрезан: предупреждение и отдельный код завершения.
+        Err(error @ FlattenError::TimeLimit { .. }) => {
+            eprintln!("Warning: {}", error);
+            error.exit_code()
+        }
+        Err(error) => {
+            let code = error.exit_code();
+            eprintln!("Error: {:?}", anyhow::Error::from(error));
+            code
+        }
+    };
+    ExitCode::from(code as u8)
+}
+
+/// Выводит сводку большого запуска (`--confirm-threshold`) и, если stdin —
+/// терминал, спрашивает, продолжать ли обработку.
+fn confirm_large_run(preview: &RunPreview) -> bool {
+    let stdin = std::io::stdin();
+    let mut terminal = stdin.is_terminal().then(|| stdin.lock());
+    let input = terminal.as_mut().map(|lock| lock as &mut dyn BufRead);
+    preview.confirm(input, &mut std::io::stderr()).unwrap_or(false)
+}
+
+/// Спрашивает, удалять ли весь кэш (`--cache-prune --all` без `--yes`), если
+/// stdin — терминал; иначе подсказывает `--yes`.
+fn confirm_cache_prune(status: &CacheStatus) -> bool {
+    let stdin = std::io::stdin();
+    let mut terminal = stdin.is_terminal().then(|| stdin.lock());
+    let input = terminal.as_mut().map(|lock| lock as &mut dyn BufRead);
+    cache_maintenance::confirm_all(status, input, &mut std::io::stderr()).unwrap_or(false)
+}
+
+/// Устанавливает флаг `stop` по Ctrl-C, чтобы `--serve` завершился.
+#[cfg(feature = "serve")]
+fn stop_on_ctrl_c(stop: &Arc<AtomicBool>) -> Result<(), ctrlc::Error> {
+    let stop = Arc::clone(stop);
+    ctrlc::set_handler(move || stop.store(true, std::sync::atomic::Ordering::SeqCst))
 }
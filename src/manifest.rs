@@ -0,0 +1,200 @@
+//! Модуль манифеста выходного документа (`--manifest`).
+//!
+//! Манифест — JSON-файл рядом с выводом, в котором для каждого записанного
+//! файла указаны его путь, смещение и длина содержимого внутри документа,
+//! хеш содержимого и статус обработки. По манифесту файл можно извлечь
+//! из документа точно, не разбирая маркеры.
+
+use crate::explain::ExclusionsSummary;
+use crate::file_modes::FileMode;
+use crate::git_info::RepositoryInfo;
+use crate::normalize::LineEndings;
+use crate::report::SkipReason;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Писатель, подсчитывающий количество записанных байт и их SHA-256.
+#[derive(Debug)]
+pub struct CountingWriter<W: Write> {
+    inner: W,
+    position: u64,
+    hasher: Sha256,
+}
+
+impl<W: Write> CountingWriter<W> {
+    /// Оборачивает `inner`, начиная отсчет с нуля.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            position: 0,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Количество байт, записанных с момента создания.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// SHA-256 всех записанных байт в шестнадцатеричном виде.
+    pub fn sha256_hex(&self) -> String {
+        self.hasher
+            .clone()
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written as u64;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Положение содержимого файла внутри выходного документа.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ContentSpan {
+    /// Смещение первого байта содержимого.
+    pub offset: u64,
+    /// Длина содержимого в байтах.
+    pub length: u64,
+}
+
+/// Статус обработки файла.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    /// Содержимое записано без изменений.
+    Full,
+    /// Содержимое преобразовано конвертером (например, предпросмотр CSV).
+    Converted,
+    /// Вместо содержимого записан заполнитель.
+    Skipped,
+    /// Файл не удалось прочитать.
+    Error,
+}
+
+/// Запись манифеста об одном файле.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    /// Путь файла в том виде, в котором он указан в маркерах.
+    pub path: PathBuf,
+    /// Положение содержимого в документе (только для Markdown).
+    #[serde(flatten)]
+    pub span: Option<ContentSpan>,
+    /// Исходный размер файла в байтах.
+    pub original_size: u64,
+    /// SHA-256 записанного содержимого (до экранирования маркеров) в шестнадцатеричном виде.
+    pub sha256: String,
+    /// Статус обработки.
+    pub status: FileStatus,
+    /// Имя конвертера, если содержимое преобразовано.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub converter: Option<&'static str>,
+    /// Причина пропуска, если записан заполнитель.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<SkipReason>,
+    /// Исходный стиль окончаний строк до `--normalize-eol`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_endings: Option<LineEndings>,
+    /// Файл изменился во время запуска, содержимое может быть устаревшим.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    /// Права доступа и цель символической ссылки (`--file-modes`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<FileMode>,
+}
+
+/// Манифест выходного документа.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    /// Версия утилиты.
+    pub version: &'static str,
+    /// Время создания (секунды Unix); `None` при `--reproducible`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<u64>,
+    /// Аргументы командной строки запуска.
+    pub command_line: Vec<String>,
+    /// Снимок конфигурации запуска (`--save-invocation` или `--replay`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invocation: Option<PathBuf>,
+    /// Путь к выходному документу.
+    pub output: PathBuf,
+    /// Итоговые правила исключений.
+    pub config: ExclusionsSummary,
+    /// Git-репозитории базовых папок (`--git-info`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub repositories: Vec<RepositoryInfo>,
+    /// Записанные файлы в порядке вывода.
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Создает пустой манифест для документа `output`; `reproducible` опускает время создания.
+    ///
+    /// Командная строка остается пустой: ее заполняет вызывающий код.
+    pub fn new(output: &Path, config: ExclusionsSummary, reproducible: bool) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            generated_at: (!reproducible).then(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0)
+            }),
+            command_line: Vec::new(),
+            invocation: None,
+            output: output.to_path_buf(),
+            config,
+            repositories: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Записывает манифест в JSON-файл.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self).context("Failed to serialize manifest")?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write manifest file: {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Возвращает SHA-256 данных в шестнадцатеричном виде.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counting_writer_and_hash() -> Result<()> {
+        let mut writer = CountingWriter::new(Vec::new());
+        writer.write_all(b"hello ")?;
+        writer.write_all("мир".as_bytes())?;
+        assert_eq!(writer.position(), 12);
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Модуль каталога текстов, попадающих в документ (`--messages`).
+//!
+//! Заглушки вместо содержимого (`[File too large: …]`, `[Binary file skipped: …]`),
+//! пометки внутри содержимого и заголовки разделов Markdown берутся из
+//! каталога по стабильным ключам (`file_too_large`, `read_error`). Встроенные
+//! английские тексты — значения по умолчанию; TOML-файл `--messages`
+//! переопределяет любые из них, остальные остаются встроенными.
+//!
+//! Параметры подставляются по имени: `{path}`, `{size}`, `{count}`. Текст
+//! с числом может иметь форму для единицы с суффиксом `_one`
+//! (`more_files_one`); если в каталоге задана только основная форма, она
+//! используется и для единицы. Маркеры разметки (`### путь BEGIN ###`,
+//! `### DIRECTORY … ###`, пометки `[priority]` и устаревшего содержимого) в
+//! каталог не входят: по ним документ разбирают `--verify` и `--check`.
+
+use crate::report;
+use crate::toml_value;
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+
+/// Текст каталога.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Message {
+    /// Заглушка файла больше `--max-file-size`.
+    FileTooLarge,
+    /// Заглушка бинарного или исключенного по расширению файла.
+    BinarySkipped,
+    /// Заглушка lock-файла (`--skip-lockfiles`).
+    LockfileSkipped,
+    /// Заглушка сгенерированного файла (`--skip-generated`).
+    GeneratedSkipped,
+    /// Текст вместо содержимого файла, который не удалось прочитать.
+    ReadError,
+    /// Заглушка указателя Git LFS.
+    LfsPointer,
+    /// Заглушка изображения с размерами.
+    Image,
+    /// Заглушка изображения, размеры которого не определены.
+    ImageUnsized,
+    /// Секция стороннего кода (`--vendored summarize`).
+    VendoredCode,
+    /// Сводка файлов сверх `--dir-file-limit`.
+    MoreFiles,
+    /// Заголовок структуры файла (`--outline`).
+    Outline,
+    /// Пометка о строках сверх `--head-lines`.
+    MoreLines,
+    /// Заголовок раздела с деревом папки.
+    FolderStructure,
+    /// Заголовок сводки по языкам (`--overview`).
+    Languages,
+    /// Заголовок раздела зависимостей (`--deps-summary`).
+    Dependencies,
+    /// Заголовок раздела крейтов Cargo workspace.
+    WorkspaceCrates,
+    /// Заголовок раздела содержимого файлов.
+    Files,
+    /// Заголовок сведений о git-репозитории (`--git-info`).
+    Repository,
+    /// Заголовок списка файлов, не вошедших в `--fit-budget`.
+    OmittedDueToBudget,
+    /// Заголовок раздела пропущенного содержимого (`--skipped-appendix`).
+    SkippedContent,
+    /// Заглушка символической ссылки (`--file-modes`).
+    Symlink,
+    /// Заглушка символической ссылки за пределы базовой папки.
+    SymlinkOutside,
+}
+
+impl Message {
+    /// Все тексты каталога.
+    pub const ALL: &[Message] = &[
+        Message::FileTooLarge,
+        Message::BinarySkipped,
+        Message::LockfileSkipped,
+        Message::GeneratedSkipped,
+        Message::ReadError,
+        Message::LfsPointer,
+        Message::Image,
+        Message::ImageUnsized,
+        Message::VendoredCode,
+        Message::MoreFiles,
+        Message::Outline,
+        Message::MoreLines,
+        Message::FolderStructure,
+        Message::Languages,
+        Message::Dependencies,
+        Message::WorkspaceCrates,
+        Message::Files,
+        Message::Repository,
+        Message::OmittedDueToBudget,
+        Message::SkippedContent,
+        Message::Symlink,
+        Message::SymlinkOutside,
+    ];
+
+    /// Ключ текста в каталоге.
+    pub fn key(self) -> &'static str {
+        match self {
+            Message::FileTooLarge => "file_too_large",
+            Message::BinarySkipped => "binary_skipped",
+            Message::LockfileSkipped => "lockfile_skipped",
+            Message::GeneratedSkipped => "generated_skipped",
+            Message::ReadError => "read_error",
+            Message::LfsPointer => "lfs_pointer",
+            Message::Image => "image",
+            Message::ImageUnsized => "image_unsized",
+            Message::VendoredCode => "vendored_code",
+            Message::MoreFiles => "more_files",
+            Message::Outline => "outline",
+            Message::MoreLines => "more_lines",
+            Message::FolderStructure => "folder_structure",
+            Message::Languages => "languages",
+            Message::Dependencies => "dependencies",
+            Message::WorkspaceCrates => "workspace_crates",
+            Message::Files => "files",
+            Message::Repository => "repository",
+            Message::OmittedDueToBudget => "omitted_due_to_budget",
+            Message::SkippedContent => "skipped_content",
+            Message::Symlink => "symlink",
+            Message::SymlinkOutside => "symlink_outside",
+        }
+    }
+
+    /// Встроенный текст.
+    pub fn default_text(self) -> &'static str {
+        match self {
+            Message::FileTooLarge => "[File too large: {bytes} bytes]",
+            Message::BinarySkipped => "[Binary file skipped: {path}]",
+            Message::LockfileSkipped => "[Lockfile skipped: {size}]",
+            Message::GeneratedSkipped => "[Generated file skipped: {size}]",
+            Message::ReadError => "[Error reading file: {error}]",
+            Message::LfsPointer => {
+                "[Git LFS pointer: {size} object {oid}…, content not available locally]"
+            }
+            Message::Image => "[Image: {name}, {format} {dimensions}, {size}]",
+            Message::ImageUnsized => "[Image: {name}, {size}]",
+            Message::VendoredCode => {
+                "[Vendored code in {directory}: {projects}{count} files, {size}]"
+            }
+            Message::MoreFiles => "and {count} more files in {directory} ({size} total)",
+            Message::Outline => "[Outline: {definitions} of {lines} lines]",
+            Message::MoreLines => "[… {count} more lines]",
+            Message::FolderStructure => "Folder structure",
+            Message::Languages => "Languages",
+            Message::Dependencies => "Dependencies",
+            Message::WorkspaceCrates => "Workspace crates",
+            Message::Files => "Files",
+            Message::Repository => "{label} repository",
+            Message::OmittedDueToBudget => "Omitted due to budget",
+            Message::SkippedContent => "Skipped content",
+            Message::Symlink => "[Symlink to {target}]",
+            Message::SymlinkOutside => "[Symlink to {target}, outside the tree]",
+        }
+    }
+
+    /// Встроенная форма для единицы, если текст зависит от числа.
+    fn default_one(self) -> Option<&'static str> {
+        match self {
+            Message::VendoredCode => {
+                Some("[Vendored code in {directory}: {projects}{count} file, {size}]")
+            }
+            Message::MoreFiles => Some("and {count} more file in {directory} ({size} total)"),
+            Message::MoreLines => Some("[… {count} more line]"),
+            _ => None,
+        }
+    }
+}
+
+/// Каталог текстов: переопределения поверх встроенных.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Messages {
+    /// Тексты из `--messages` по ключам (с формами `_one`).
+    custom: BTreeMap<String, String>,
+}
+
+impl Messages {
+    /// Загружает каталог из TOML-файла.
+    ///
+    /// # Ошибки
+    /// Возвращает ошибку, если файл не читается, не разбирается или содержит
+    /// неизвестный ключ либо значение, отличное от строки.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read messages: {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid messages: {}", path.display()))
+    }
+
+    /// Разбирает каталог из текста TOML.
+    pub fn parse(content: &str) -> Result<Self> {
+        let value = toml_value::parse(content)?;
+        let Some(table) = value.as_object() else {
+            bail!("expected a table of messages");
+        };
+        let known: Vec<String> = Message::ALL
+            .iter()
+            .flat_map(|message| {
+                let one = message
+                    .default_one()
+                    .map(|_| format!("{}_one", message.key()));
+                [Some(message.key().to_string()), one]
+            })
+            .flatten()
+            .collect();
+        let mut custom = BTreeMap::new();
+        for (key, value) in table {
+            if !known.contains(key) {
+                bail!(
+                    "unknown message key `{}` (known: {})",
+                    key,
+                    known.join(", ")
+                );
+            }
+            let Some(text) = value.as_str() else {
+                bail!("message `{}` must be a string", key);
+            };
+            custom.insert(key.clone(), text.to_string());
+        }
+        Ok(Self { custom })
+    }
+
+    /// Текст `message` с подставленными параметрами `args`.
+    pub fn format(&self, message: Message, args: &[(&str, &dyn Display)]) -> String {
+        let template = self
+            .custom
+            .get(message.key())
+            .map_or(message.default_text(), String::as_str);
+        substitute(template, args)
+    }
+
+    /// Текст `message` для числа `count` (параметр `{count}`) с параметрами `args`.
+    pub fn format_count(
+        &self,
+        message: Message,
+        count: usize,
+        args: &[(&str, &dyn Display)],
+    ) -> String {
+        let custom = |key: &str| self.custom.get(key).map(String::as_str);
+        let template = match message.default_one() {
+            Some(default_one) if count == 1 => custom(&format!("{}_one", message.key()))
+                .or_else(|| custom(message.key()))
+                .unwrap_or(default_one),
+            _ => custom(message.key()).unwrap_or(message.default_text()),
+        };
+        let count = report::format_count(count);
+        let mut all: Vec<(&str, &dyn Display)> = vec![("count", &count)];
+        all.extend_from_slice(args);
+        substitute(template, &all)
+    }
+}
+
+/// Подставляет `{имя}` из `args`; неизвестные имена остаются как есть.
+fn substitute(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut result = String::with_capacity(template.len() + 32);
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let value = tail.find('}').and_then(|end| {
+            let (_, value) = args.iter().find(|(name, _)| *name == &tail[1..end])?;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                result.push_str(&value.to_string());
+                rest = &tail[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_texts_override_defaults() {
+        let messages = Messages::parse(
+            "file_too_large = \"[Слишком большой: {bytes} байт]\"\n\
+             more_lines = \"[… еще строк: {count}]\"\n",
+        )
+        .unwrap();
+        let bytes = 2048;
+        assert_eq!(
+            messages.format(Message::FileTooLarge, &[("bytes", &bytes)]),
+            "[Слишком большой: 2048 байт]"
+        );
+        // Незаданные тексты остаются встроенными.
+        let error = "denied";
+        assert_eq!(
+            messages.format(Message::ReadError, &[("error", &error)]),
+            "[Error reading file: denied]"
+        );
+        // Без формы `_one` основная форма используется и для единицы.
+        assert_eq!(
+            messages.format_count(Message::MoreLines, 1, &[]),
+            "[… еще строк: 1]"
+        );
+        assert_eq!(
+            Messages::default().format_count(Message::MoreLines, 1, &[]),
+            "[… 1 more line]"
+        );
+        assert_eq!(
+            Messages::default().format_count(Message::MoreLines, 1200, &[]),
+            format!("[… {} more lines]", report::format_count(1200))
+        );
+        // Неизвестный параметр остается как есть.
+        assert_eq!(substitute("{x} {y}", &[("x", &1)]), "1 {y}");
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_keys() {
+        let error = Messages::parse("file_too_big = \"x\"")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("unknown message key `file_too_big`"),
+            "{}",
+            error
+        );
+        assert!(error.contains("more_files_one"), "{}", error);
+        assert!(Messages::parse("files = 3").is_err());
+        assert!(Messages::parse("image_unsized_one = \"x\"").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Модуль нормализации текста содержимого.
+//!
+//! Перед записью в вывод у текста удаляется ведущий BOM, а окончания строк
+//! приводятся к единому виду (`--normalize-eol`). Исходный стиль окончаний
+//! сохраняется в манифесте, чтобы его можно было восстановить.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Режим приведения окончаний строк.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum EolMode {
+    /// Привести к `\n` (по умолчанию).
+    #[default]
+    Lf,
+    /// Привести к `\r\n`.
+    Crlf,
+    /// Оставить как есть.
+    Keep,
+}
+
+/// Исходный стиль окончаний строк файла.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEndings {
+    /// Только `\n`.
+    Lf,
+    /// Только `\r\n`.
+    Crlf,
+    /// Оба вида.
+    Mixed,
+    /// Текст без переводов строк.
+    None,
+}
+
+/// Определяет стиль окончаний строк текста.
+pub fn detect_line_endings(text: &str) -> LineEndings {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    match (lf, crlf) {
+        (0, 0) => LineEndings::None,
+        (_, 0) => LineEndings::Lf,
+        (0, _) => LineEndings::Crlf,
+        _ => LineEndings::Mixed,
+    }
+}
+
+/// Удаляет ведущий BOM и приводит окончания строк согласно `mode`.
+///
+/// Возвращает исходный стиль окончаний строк.
+pub fn normalize_text(text: &mut String, mode: EolMode) -> LineEndings {
+    if text.starts_with('\u{feff}') {
+        text.drain(..'\u{feff}'.len_utf8());
+    }
+    let endings = detect_line_endings(text);
+    if let Some(normalized) = convert_line_endings(text, mode, endings) {
+        *text = normalized;
+    }
+    endings
+}
+
+/// Приводит окончания строк текста со стилем `endings` согласно `mode`.
+///
+/// Возвращает `None`, если текст не меняется.
+pub fn convert_line_endings(text: &str, mode: EolMode, endings: LineEndings) -> Option<String> {
+    match (mode, endings) {
+        (EolMode::Lf, LineEndings::Crlf | LineEndings::Mixed) => Some(text.replace("\r\n", "\n")),
+        (EolMode::Crlf, LineEndings::Lf) => Some(text.replace('\n', "\r\n")),
+        (EolMode::Crlf, LineEndings::Mixed) => {
+            Some(text.replace("\r\n", "\n").replace('\n', "\r\n"))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_text() {
+        let mut text = "\u{feff}a\r\nb\nc\r\n".to_string();
+        assert_eq!(normalize_text(&mut text, EolMode::Lf), LineEndings::Mixed);
+        assert_eq!(text, "a\nb\nc\n");
+
+        let mut text = "a\nb\r\n".to_string();
+        normalize_text(&mut text, EolMode::Crlf);
+        assert_eq!(text, "a\r\nb\r\n");
+
+        let mut text = "a\r\nb".to_string();
+        assert_eq!(normalize_text(&mut text, EolMode::Keep), LineEndings::Crlf);
+        assert_eq!(text, "a\r\nb");
+    }
+}
@@ -0,0 +1,398 @@
+//! Модуль сокращенного вывода содержимого (`--outline`, `--head-lines`).
+//!
+//! С `--outline` для поддерживаемых языков вместо содержимого выводится
+//! структура файла: строки с объявлениями и их номера. Объявления ищутся
+//! построчно, без полного разбора: строка проверяется, только если она
+//! начинается вне комментария и строкового литерала, а вложенные объявления
+//! (методы внутри `impl` или класса) сохраняют отступ.
+//!
+//! - Rust: `pub fn` и `struct`/`enum`/`trait`/`union`/`type`/`impl`/`mod`/`macro_rules!`.
+//! - Python: `def`, `async def` и `class`.
+//! - TypeScript и JavaScript: объявления с `export`.
+//!
+//! Файлы остальных языков выводятся первыми `--head-lines` строками (0 — целиком).
+//! Сокращенный файл учитывается в статистике как преобразованный
+//! конвертером `outline` или `head`.
+
+use crate::messages::{Message, Messages};
+use std::fmt::Write as _;
+
+/// Язык, для которого строится структура файла.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Rust.
+    Rust,
+    /// Python.
+    Python,
+    /// TypeScript и JavaScript.
+    TypeScript,
+}
+
+impl Syntax {
+    /// Возвращает синтаксис по имени языка из `LanguageTable`.
+    pub fn for_language(language: &str) -> Option<Self> {
+        match language {
+            "rust" => Some(Self::Rust),
+            "python" => Some(Self::Python),
+            "typescript" | "javascript" => Some(Self::TypeScript),
+            _ => None,
+        }
+    }
+
+    /// Является ли строка, начинающаяся вне комментария и литерала, объявлением.
+    fn is_definition(self, line: &str) -> bool {
+        let line = line.trim_start();
+        match self {
+            Self::Rust => is_rust_definition(line),
+            Self::Python => ["def ", "async def ", "class "]
+                .iter()
+                .any(|keyword| line.starts_with(keyword)),
+            Self::TypeScript => line.starts_with("export "),
+        }
+    }
+}
+
+/// Как сокращается содержимое одного файла.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Abridge {
+    /// Синтаксис для `--outline` (`None` — язык не поддерживается или режим выключен).
+    pub syntax: Option<Syntax>,
+    /// Сколько первых строк выводить, если структура не строится (0 — все).
+    pub head_lines: usize,
+}
+
+impl Abridge {
+    /// Имя преобразования для структуры файла.
+    pub const OUTLINE: &'static str = "outline";
+    /// Имя преобразования для первых строк файла.
+    pub const HEAD: &'static str = "head";
+
+    /// Сокращает текст; возвращает имя преобразования для статистики и
+    /// новый текст или `None`, если текст выводится как есть.
+    pub fn apply(&self, text: &str, messages: &Messages) -> Option<(&'static str, String)> {
+        if let Some(syntax) = self.syntax {
+            return Some((Self::OUTLINE, outline(syntax, text, messages)));
+        }
+        head(text, self.head_lines, messages).map(|text| (Self::HEAD, text))
+    }
+}
+
+/// Строит структуру файла: заголовок с числом строк и объявления с номерами.
+pub fn outline(syntax: Syntax, text: &str, messages: &Messages) -> String {
+    let mut state = State::Code;
+    let mut definitions = Vec::new();
+    let mut total = 0;
+    for (index, line) in text.lines().enumerate() {
+        total = index + 1;
+        if state == State::Code && syntax.is_definition(line) {
+            definitions.push((index + 1, line.trim_end()));
+        }
+        state = scan(syntax, line, state);
+    }
+
+    let width = definitions.last().map_or(1, |(number, _)| number.to_string().len());
+    let mut result = messages.format(
+        Message::Outline,
+        &[("definitions", &definitions.len()), ("lines", &total)],
+    );
+    result.push('\n');
+    for (number, line) in definitions {
+        let _ = writeln!(result, "{:>width$}: {}", number, line, width = width);
+    }
+    result
+}
+
+/// Первые `lines` строк текста с пометкой о числе остальных; `None`, если
+/// текст короче или `lines` равно 0.
+pub fn head(text: &str, lines: usize, messages: &Messages) -> Option<String> {
+    if lines == 0 {
+        return None;
+    }
+    let end = text
+        .match_indices('\n')
+        .nth(lines - 1)
+        .map(|(index, _)| index + 1)?;
+    let rest = text[end..].lines().count();
+    if rest == 0 {
+        return None;
+    }
+    let more = messages.format_count(Message::MoreLines, rest, &[]);
+    Some(format!("{}{}\n", &text[..end], more))
+}
+
+/// Проверяет строку Rust без начальных пробелов.
+///
+/// Функции учитываются только с видимостью `pub`, остальные объявления — с любой.
+fn is_rust_definition(line: &str) -> bool {
+    let (public, mut rest) = match line.strip_prefix("pub") {
+        Some(rest) if rest.starts_with(' ') => (true, rest.trim_start()),
+        // `pub(crate)`, `pub(super)`, `pub(in path)`.
+        Some(rest) if rest.starts_with('(') => match rest.find(')') {
+            Some(end) => (true, rest[end + 1..].trim_start()),
+            None => return false,
+        },
+        _ => (false, line),
+    };
+    while let Some(stripped) = ["default ", "const ", "async ", "unsafe ", "extern \"C\" "]
+        .iter()
+        .find_map(|qualifier| rest.strip_prefix(qualifier))
+    {
+        rest = stripped;
+    }
+    if rest.starts_with("fn ") {
+        return public;
+    }
+    ["struct ", "enum ", "trait ", "union ", "type ", "mod ", "impl ", "impl<", "macro_rules!"]
+        .iter()
+        .any(|keyword| rest.starts_with(keyword))
+}
+
+/// Состояние разбора на границе строк.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Обычный код.
+    Code,
+    /// Блочный комментарий с глубиной вложенности (Rust допускает вложенные).
+    BlockComment(usize),
+    /// Строковый литерал, продолжающийся на следующих строках.
+    Str {
+        /// Закрывающая кавычка.
+        quote: u8,
+        /// Для Python — тройная кавычка.
+        triple: bool,
+        /// Для сырых строк Rust — число `#`; экранирование в них не действует.
+        raw: Option<usize>,
+    },
+}
+
+/// Возвращает состояние в конце строки `line`, начатой в состоянии `state`.
+fn scan(syntax: Syntax, line: &str, mut state: State) -> State {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        match state {
+            State::Code => match rest {
+                [b'/', b'/', ..] if syntax != Syntax::Python => return State::Code,
+                [b'#', ..] if syntax == Syntax::Python => return State::Code,
+                [b'/', b'*', ..] if syntax != Syntax::Python => {
+                    state = State::BlockComment(1);
+                    i += 1;
+                }
+                [quote @ (b'"' | b'\''), ..] if syntax == Syntax::Python => {
+                    let triple = rest.starts_with(&[*quote; 3]);
+                    state = State::Str { quote: *quote, triple, raw: None };
+                    if triple {
+                        i += 2;
+                    }
+                }
+                [quote @ (b'"' | b'\'' | b'`'), ..] if syntax == Syntax::TypeScript => {
+                    state = State::Str { quote: *quote, triple: false, raw: None };
+                }
+                [b'"', ..] => state = State::Str { quote: b'"', triple: false, raw: None },
+                [b'r', ..] if syntax == Syntax::Rust && starts_raw(bytes, i).is_some() => {
+                    let hashes = starts_raw(bytes, i).unwrap_or_default();
+                    state = State::Str { quote: b'"', triple: false, raw: Some(hashes) };
+                    i += hashes + 1;
+                }
+                // Символьный литерал Rust (`'"'`, `'\''`); иначе это время жизни.
+                [b'\'', b'\\', _, ..] if syntax == Syntax::Rust => {
+                    i += rest[2..].iter().position(|&b| b == b'\'').map_or(rest.len(), |p| p + 2);
+                }
+                [b'\'', _, b'\'', ..] if syntax == Syntax::Rust => i += 2,
+                _ => {}
+            },
+            State::BlockComment(depth) => match rest {
+                [b'*', b'/', ..] => {
+                    state = if depth == 1 { State::Code } else { State::BlockComment(depth - 1) };
+                    i += 1;
+                }
+                [b'/', b'*', ..] if syntax == Syntax::Rust => {
+                    state = State::BlockComment(depth + 1);
+                    i += 1;
+                }
+                _ => {}
+            },
+            State::Str { quote, triple, raw } => match (rest, raw) {
+                ([b'"', ..], Some(hashes)) => {
+                    let closing = rest[1..].iter().take_while(|&&b| b == b'#').count();
+                    if closing >= hashes {
+                        state = State::Code;
+                        i += hashes;
+                    }
+                }
+                (_, Some(_)) => {}
+                ([b'\\', ..], None) => i += 1,
+                ([b, ..], None) if *b == quote && (!triple || rest.starts_with(&[quote; 3])) => {
+                    state = State::Code;
+                    if triple {
+                        i += 2;
+                    }
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+    // Обычные строки Python и TypeScript не переходят на следующую строку.
+    match state {
+        State::Str { quote, triple: false, .. } if syntax != Syntax::Rust && quote != b'`' => {
+            State::Code
+        }
+        state => state,
+    }
+}
+
+/// Число `#` сырой строки Rust, начинающейся в `bytes[start]` (`r"`, `r#"`, `br"`).
+fn starts_raw(bytes: &[u8], start: usize) -> Option<usize> {
+    let identifier = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_';
+    let before = start.checked_sub(1).map(|index| &bytes[index]);
+    if before.is_some_and(|b| identifier(b) && *b != b'b') {
+        return None;
+    }
+    let hashes = bytes[start + 1..].iter().take_while(|&&b| b == b'#').count();
+    (bytes.get(start + 1 + hashes) == Some(&b'"')).then_some(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST_SOURCE: &str = r##"//! pub fn in_doc_comment() {}
+use std::fmt;
+
+/// Настройки.
+pub struct Config {
+    name: String,
+}
+
+/* pub fn commented_out() {}
+   /* nested */ pub struct StillComment;
+*/
+pub(crate) enum Mode {
+    Fast,
+}
+
+impl Config {
+    pub fn new(name: &str) -> Self {
+        let text = "
+pub fn inside_string() {}";
+        let raw = r#"pub struct InsideRaw "quoted" {}"#;
+        let quote = '"';
+        Self { name: name.to_string() }
+    }
+
+    fn private_helper(&self) {}
+
+    pub async fn load<'a>(&'a self) {}
+}
+
+impl<T> fmt::Debug for Wrapper<T> {}
+
+#[cfg(test)]
+mod tests {
+    fn test_private() {}
+}
+"##;
+
+    const RUST_OUTLINE: &str = "[Outline: 7 of 35 lines]
+ 5: pub struct Config {
+12: pub(crate) enum Mode {
+16: impl Config {
+17:     pub fn new(name: &str) -> Self {
+27:     pub async fn load<'a>(&'a self) {}
+30: impl<T> fmt::Debug for Wrapper<T> {}
+33: mod tests {
+";
+
+    const PYTHON_SOURCE: &str = r#"import os
+
+class Loader:
+    """Загрузчик.
+
+    def not_a_method(self):
+    """
+
+    def __init__(self, path):
+        self.path = path  # def in comment
+
+    async def load(self):
+        query = "def inside_string():"
+        return query
+
+def main():
+    # def commented():
+    def nested():
+        pass
+    return Loader(os.getcwd())
+"#;
+
+    const PYTHON_OUTLINE: &str = "[Outline: 5 of 20 lines]
+ 3: class Loader:
+ 9:     def __init__(self, path):
+12:     async def load(self):
+16: def main():
+18:     def nested():
+";
+
+    const TYPESCRIPT_SOURCE: &str = r#"import { api } from "./api";
+
+/*
+export function commentedOut() {}
+*/
+export interface User {
+  name: string;
+}
+
+const template = `
+export const insideTemplate = 1;
+`;
+
+export async function loadUser(id: string): Promise<User> {
+  return api.get(`/users/${id}`);
+}
+
+function internal() {}
+
+export default class Store {}
+export { internal };
+"#;
+
+    const TYPESCRIPT_OUTLINE: &str = "[Outline: 4 of 21 lines]
+ 6: export interface User {
+14: export async function loadUser(id: string): Promise<User> {
+20: export default class Store {}
+21: export { internal };
+";
+
+    #[test]
+    fn test_rust_outline() {
+        assert_eq!(outline(Syntax::Rust, RUST_SOURCE, &Messages::default()), RUST_OUTLINE);
+    }
+
+    #[test]
+    fn test_python_outline() {
+        assert_eq!(outline(Syntax::Python, PYTHON_SOURCE, &Messages::default()), PYTHON_OUTLINE);
+    }
+
+    #[test]
+    fn test_typescript_outline() {
+        let outline = outline(Syntax::TypeScript, TYPESCRIPT_SOURCE, &Messages::default());
+        assert_eq!(outline, TYPESCRIPT_OUTLINE);
+    }
+
+    #[test]
+    fn test_head_lines_fallback() {
+        let abridge = Abridge {
+            syntax: None,
+            head_lines: 2,
+        };
+        assert_eq!(
+            abridge.apply("a\nb\nc\nd\n", &Messages::default()),
+            Some(("head", "a\nb\n[… 2 more lines]\n".to_string()))
+        );
+        assert_eq!(abridge.apply("a\nb\n", &Messages::default()), None);
+        let messages = Messages::default();
+        assert_eq!(Abridge::default().apply("a\nb\nc\n", &messages), None);
+    }
+}
@@ -0,0 +1,2082 @@
+//! Модуль форматов выходного документа.
+//!
+//! Каждый формат реализует трейт `Emitter`, который получает события обхода
+//! (структура папки, начало содержимого, очередной файл, конец содержимого)
+//! в том порядке, в котором их формирует `run`.
+//!
+//! JSONL-формат пишет по одному JSON-объекту на строку и сбрасывает вывод
+//! после каждой строки, чтобы потребители могли читать его во время работы.
+//! При `--chunk-size` JSON и JSONL выдают содержимое файлов фрагментами.
+//!
+//! С `--front-matter` документ начинается со сведений о запуске: YAML-блок
+//! в Markdown, поле `front_matter` в JSON, запись `document` в JSONL и
+//! JSON-блок в заголовке HTML. В режиме `per-file` сведения о каждом файле
+//! предшествуют его содержимому или добавляются к его записи.
+//!
+//! С `--git-info` перед структурами папок выводится абзац со сведениями о
+//! git-репозитории каждой базовой папки, лежащей в рабочем дереве.
+//!
+//! В режиме `--overview` после структуры папки выводится сводка по языкам,
+//! а с `--deps-summary` — зависимости из найденных манифестов.
+//!
+//! С `--heading-style headings` Markdown-документ вместо маркеров использует
+//! настоящие заголовки: `# <папка>`, `## Folder structure`, `## Files` и
+//! `### <путь>` на файл (уровни сдвигаются `--base-heading-level`), а дерево
+//! и содержимое файлов помещаются в блоки кода.
+//!
+//! HTML-документ самодостаточен: стили встроены, дерево папки ссылается на
+//! свернутые секции файлов, а скрипт highlight.js встраивается, только если
+//! передан `--html-highlight-js`.
+
+use crate::budget::OmittedFile;
+use crate::converters::fence_for;
+use crate::dependencies::ManifestDependencies;
+use crate::error::FlattenError;
+use crate::front_matter::{DocumentHeader, FileHeader, FrontMatter};
+use crate::git_info::RepositoryInfo;
+use crate::file_modes::FileMode;
+use crate::grouping::FileGroup;
+use crate::integrity::{TIME_LIMIT_TRUNCATION, Trailer};
+use crate::language::LanguageTable;
+use crate::manifest::{ContentSpan, CountingWriter, sha256_hex};
+use crate::messages::{Message, Messages};
+use crate::report::{SkipReason, SkippedContent, format_count, format_size};
+use crate::pipeline::{DirTree, FileContent, truncation_note};
+use crate::time_limit::Truncation;
+use crate::workspace::{CargoWorkspace, WorkspaceCrate};
+use crate::{
+    FAILURE, FILE, FOLDER, FlattenConfig, SUCCESS, glyph, labeled_path, print_folder_structure,
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{BufWriter, LineWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Пометка в заголовке файла, изменившегося между сбором и чтением.
+pub const STALE_NOTE: &str = "[content may be stale: file changed during run]";
+
+/// Формат выходного документа и служебных отчетов.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Markdown-документ с маркерами `### ... ###` (по умолчанию).
+    #[default]
+    Markdown,
+    /// Единый JSON-документ.
+    Json,
+    /// JSON-объект на строку: дерево папки, затем каждый файл по мере обработки.
+    Jsonl,
+    /// Самодостаточный HTML-документ со сворачиваемыми секциями файлов.
+    Html,
+}
+
+impl OutputFormat {
+    /// Возвращает расширение файла документа без точки (для `--output-dir`).
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Html => "html",
+        }
+    }
+
+    /// Определяет формат по расширению файла (`None` для неизвестного расширения).
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Some(OutputFormat::Markdown),
+            "json" => Some(OutputFormat::Json),
+            "jsonl" | "ndjson" => Some(OutputFormat::Jsonl),
+            "html" | "htm" => Some(OutputFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Оформление секций Markdown-документа.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum HeadingStyle {
+    /// Маркеры `### ... ###` (по умолчанию).
+    #[default]
+    Markers,
+    /// Заголовки Markdown, дерево и файлы в блоках кода.
+    Headings,
+}
+
+/// Получатель событий, формирующий выходной документ.
+pub(crate) trait Emitter {
+    /// Записывает сведения о документе (`--front-matter`) до первой базовой папки.
+    fn document_header(&mut self, header: &DocumentHeader) -> Result<()>;
+
+    /// Записывает сведения о git-репозитории базовой папки (`--git-info`).
+    ///
+    /// Вызывается для всех папок до структуры первой из них.
+    fn repository(&mut self, label: &str, info: &RepositoryInfo) -> Result<()>;
+
+    /// Записывает структуру базовой папки, обозначенной в выводе меткой `label`.
+    fn folder_structure(
+        &mut self,
+        tree: &DirTree,
+        label: &str,
+        config: &FlattenConfig,
+    ) -> Result<()>;
+
+    /// Записывает сводку по языкам базовой папки (`--overview`).
+    fn language_summary(&mut self, label: &str, languages: &[FileGroup]) -> Result<()>;
+
+    /// Записывает зависимости из манифестов базовой папки (`--deps-summary`).
+    fn dependencies(&mut self, label: &str, manifests: &[ManifestDependencies]) -> Result<()>;
+
+    /// Записывает крейты Cargo workspace базовой папки (`--overview`, `--deps-summary`).
+    fn workspace(&mut self, label: &str, workspace: &CargoWorkspace) -> Result<()>;
+
+    /// Начинает секцию содержимого базовой папки.
+    fn begin_content(&mut self, label: &str) -> Result<()>;
+
+    /// Начинает группу файлов (`--group-by`).
+    fn begin_group(&mut self, group: &FileGroup) -> Result<()>;
+
+    /// Записывает один файл (или ошибку его чтения); `priority` отмечает файлы `--priority`.
+    ///
+    /// Возвращает положение записанного содержимого в документе, если формат
+    /// позволяет его указать.
+    fn file(
+        &mut self,
+        path: &Path,
+        content: &Result<FileContent>,
+        priority: bool,
+    ) -> Result<Option<ContentSpan>>;
+
+    /// Завершает секцию содержимого базовой папки.
+    fn end_content(&mut self, label: &str) -> Result<()>;
+
+    /// Перечисляет после всех папок файлы, не вошедшие в `--fit-budget`.
+    fn budget_omissions(&mut self, files: &[OmittedFile]) -> Result<()>;
+
+    /// Дописывает раздел пропущенного содержимого (`--skipped-appendix`).
+    fn skipped_appendix(&mut self, skipped: &SkippedContent) -> Result<()>;
+
+    /// Отмечает в конце документа, что он обрезан по `--time-limit`.
+    fn truncated(&mut self, truncation: &Truncation) -> Result<()>;
+
+    /// Дописывает и сбрасывает документ.
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// Создает эмиттер для указанного формата поверх `writer`.
+///
+/// С `trailer` Markdown-документ завершается строкой целостности
+/// (`integrity::Trailer`); остальные форматы ее не пишут.
+pub(crate) fn emitter_for<'a, W: Write + 'a>(
+    format: OutputFormat,
+    writer: W,
+    config: &'a FlattenConfig,
+    trailer: bool,
+) -> Box<dyn Emitter + 'a> {
+    let file_headers = (config.front_matter == Some(FrontMatter::PerFile)).then_some(FileHeaders {
+        sha256: config.front_matter_sha256,
+    });
+    match format {
+        OutputFormat::Markdown => Box::new(MarkdownEmitter {
+            writer: CountingWriter::new(BufWriter::new(writer)),
+            file_headers,
+            languages: &config.languages,
+            heading_level: config.heading_level,
+            messages: &config.messages,
+            grouped: false,
+            trailer,
+            sections: 0,
+            truncated: false,
+        }),
+        OutputFormat::Json => Box::new(JsonEmitter {
+            writer: BufWriter::new(writer),
+            document: JsonDocument {
+                version: env!("CARGO_PKG_VERSION"),
+                front_matter: None,
+                repositories: Vec::new(),
+                folders: Vec::new(),
+                omitted_due_to_budget: Vec::new(),
+                skipped: None,
+                truncated: None,
+            },
+            file_headers,
+            languages: &config.languages,
+        }),
+        OutputFormat::Jsonl => Box::new(JsonlEmitter {
+            writer: LineWriter::new(writer),
+            file_headers,
+            languages: &config.languages,
+        }),
+        OutputFormat::Html => Box::new(HtmlEmitter {
+            writer: BufWriter::new(writer),
+            highlight_script: config.html_highlight_script.as_deref(),
+            header: None,
+            file_headers,
+            languages: &config.languages,
+            messages: &config.messages,
+            started: false,
+            anchors: HashMap::new(),
+            next_anchor: 0,
+        }),
+    }
+}
+
+/// Настройки сведений о каждом файле (`--front-matter per-file`).
+#[derive(Debug, Clone, Copy)]
+struct FileHeaders {
+    /// Вычислять ли SHA-256 содержимого.
+    sha256: bool,
+}
+
+impl FileHeaders {
+    /// Собирает сведения о файле `path` с содержимым `content`.
+    fn header(
+        &self,
+        languages: &LanguageTable,
+        path: &Path,
+        content: &Result<FileContent>,
+    ) -> FileHeader {
+        match content {
+            Ok(file_content) => FileHeader {
+                path: path.to_path_buf(),
+                lang: match file_content.skip_reason {
+                    None => languages.language_for(path).map(str::to_string),
+                    Some(_) => None,
+                },
+                size: file_content.original_size,
+                sha256: self
+                    .sha256
+                    .then(|| sha256_hex(file_content.content.as_bytes())),
+                error: None,
+            },
+            Err(e) => FileHeader {
+                path: path.to_path_buf(),
+                lang: None,
+                size: 0,
+                sha256: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Markdown-документ с маркерами начала и конца секций или с заголовками.
+struct MarkdownEmitter<'a, W: Write> {
+    writer: CountingWriter<BufWriter<W>>,
+    file_headers: Option<FileHeaders>,
+    languages: &'a LanguageTable,
+    /// Уровень заголовка базовой папки (`None` — маркеры).
+    heading_level: Option<usize>,
+    /// Тексты заголовков разделов и ошибок чтения (`--messages`).
+    messages: &'a Messages,
+    /// Разбито ли содержимое текущей папки на группы (файлы на уровень глубже).
+    grouped: bool,
+    /// Дописывать ли строку целостности.
+    trailer: bool,
+    /// Количество записанных секций файлов.
+    sections: usize,
+    /// Обрезан ли документ по `--time-limit`.
+    truncated: bool,
+}
+
+impl<W: Write> MarkdownEmitter<'_, W> {
+    /// Записывает заголовок на `depth` уровней глубже заголовка базовой папки.
+    fn heading(&mut self, level: usize, depth: usize, title: &str) -> Result<()> {
+        writeln!(self.writer, "{} {}\n", "#".repeat((level + depth).min(6)), title)?;
+        Ok(())
+    }
+
+    /// Записывает заголовок раздела с текстом `message` из каталога.
+    fn section(&mut self, level: usize, depth: usize, message: Message) -> Result<()> {
+        let title = self.messages.format(message, &[]);
+        self.heading(level, depth, &title)
+    }
+
+    /// Записывает файл заголовком `path` и блоком кода сразу под ним.
+    ///
+    /// Ограничитель блока длиннее любой серии `` ` `` в содержимом, поэтому
+    /// содержимое записывается без экранирования.
+    fn fenced_file(
+        &mut self,
+        level: usize,
+        path: &Path,
+        content: &Result<FileContent>,
+        note: &str,
+    ) -> Result<Option<ContentSpan>> {
+        let depth = if self.grouped { 3 } else { 2 };
+        let body = match content {
+            Ok(file_content) => Cow::Borrowed(&*file_content.content),
+            Err(e) => Cow::Owned(format!("{}\n", read_error(self.messages, e))),
+        };
+        let fence = fence_for(&body);
+        let language = self.languages.language_for(path).unwrap_or("");
+        let hashes = "#".repeat((level + depth).min(6));
+        writeln!(self.writer, "{} {}{}", hashes, path.display(), note)?;
+        writeln!(self.writer, "{}{}", fence, language)?;
+        let offset = self.writer.position();
+        self.writer.write_all(body.as_bytes())?;
+        let length = self.writer.position() - offset;
+        writeln!(self.writer, "\n{}\n", fence)?;
+        Ok(Some(ContentSpan { offset, length }))
+    }
+}
+
+impl<W: Write> Emitter for MarkdownEmitter<'_, W> {
+    fn document_header(&mut self, header: &DocumentHeader) -> Result<()> {
+        header.write_yaml(&mut self.writer)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn repository(&mut self, label: &str, info: &RepositoryInfo) -> Result<()> {
+        match self.heading_level {
+            Some(level) => {
+                let title = self.messages.format(Message::Repository, &[("label", &label)]);
+                self.heading(level, 0, &title)?;
+                writeln!(self.writer, "{}\n", info.summary())?;
+            }
+            None => {
+                writeln!(self.writer, "### DIRECTORY {} REPOSITORY ###", label)?;
+                writeln!(self.writer, "{}", info.summary())?;
+                writeln!(self.writer, "### DIRECTORY {} REPOSITORY ###\n", label)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn folder_structure(
+        &mut self,
+        tree: &DirTree,
+        label: &str,
+        config: &FlattenConfig,
+    ) -> Result<()> {
+        let Some(level) = self.heading_level else {
+            return print_folder_structure(tree, label, &mut self.writer, config, None);
+        };
+        // Обратные кавычки в дереве встречаются только в именах, поэтому
+        // ограничитель известен до вывода и дерево пишется без буфера.
+        let fence = tree
+            .entries
+            .iter()
+            .filter_map(|entry| entry.path.file_name())
+            .map(|name| fence_for(&name.to_string_lossy()))
+            .max_by_key(String::len)
+            .unwrap_or_else(|| fence_for(""));
+        self.heading(level, 0, label)?;
+        self.section(level, 1, Message::FolderStructure)?;
+        writeln!(self.writer, "{}", fence)?;
+        tree.render(config, &mut self.writer)?;
+        writeln!(self.writer, "{}\n", fence)?;
+        Ok(())
+    }
+
+    fn language_summary(&mut self, label: &str, languages: &[FileGroup]) -> Result<()> {
+        if let Some(level) = self.heading_level {
+            self.section(level, 1, Message::Languages)?;
+        } else {
+            writeln!(self.writer, "### DIRECTORY {} LANGUAGES ###", label)?;
+        }
+        writeln!(self.writer, "| Language | Files | Size |")?;
+        writeln!(self.writer, "|---|---:|---:|")?;
+        for language in languages {
+            writeln!(
+                self.writer,
+                "| {} | {} | {} |",
+                language.name,
+                format_count(language.files),
+                format_size(language.bytes)
+            )?;
+        }
+        if self.heading_level.is_some() {
+            writeln!(self.writer)?;
+        } else {
+            writeln!(self.writer, "### DIRECTORY {} LANGUAGES ###\n", label)?;
+        }
+        Ok(())
+    }
+
+    fn dependencies(&mut self, label: &str, manifests: &[ManifestDependencies]) -> Result<()> {
+        if let Some(level) = self.heading_level {
+            self.section(level, 1, Message::Dependencies)?;
+        } else {
+            writeln!(self.writer, "### DIRECTORY {} DEPENDENCIES ###", label)?;
+        }
+        for manifest in manifests {
+            write_dependencies_text(&mut self.writer, manifest)?;
+        }
+        if self.heading_level.is_some() {
+            writeln!(self.writer)?;
+        } else {
+            writeln!(self.writer, "### DIRECTORY {} DEPENDENCIES ###\n", label)?;
+        }
+        Ok(())
+    }
+
+    fn workspace(&mut self, label: &str, workspace: &CargoWorkspace) -> Result<()> {
+        if let Some(level) = self.heading_level {
+            self.section(level, 1, Message::WorkspaceCrates)?;
+        } else {
+            writeln!(self.writer, "### DIRECTORY {} WORKSPACE ###", label)?;
+        }
+        write_workspace_text(&mut self.writer, workspace)?;
+        if self.heading_level.is_some() {
+            writeln!(self.writer)?;
+        } else {
+            writeln!(self.writer, "### DIRECTORY {} WORKSPACE ###\n", label)?;
+        }
+        Ok(())
+    }
+
+    fn begin_content(&mut self, label: &str) -> Result<()> {
+        self.grouped = false;
+        match self.heading_level {
+            Some(level) => self.section(level, 1, Message::Files),
+            None => {
+                writeln!(self.writer, "### DIRECTORY {} FLATTENED CONTENT ###", label)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn begin_group(&mut self, group: &FileGroup) -> Result<()> {
+        let title = format!(
+            "{} ({} files, {})",
+            group.name,
+            format_count(group.files),
+            format_size(group.bytes)
+        );
+        match self.heading_level {
+            Some(level) => {
+                self.grouped = true;
+                self.heading(level, 2, &title)
+            }
+            None => {
+                writeln!(self.writer, "## {}\n", title)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        content: &Result<FileContent>,
+        priority: bool,
+    ) -> Result<Option<ContentSpan>> {
+        if let Some(file_headers) = &self.file_headers {
+            file_headers
+                .header(self.languages, path, content)
+                .write_yaml(&mut self.writer)?;
+        }
+        let note = file_note(content, priority);
+        self.sections += 1;
+        if let Some(level) = self.heading_level {
+            return self.fenced_file(level, path, content, &note);
+        }
+        writeln!(self.writer, "### {} BEGIN ###{}", path.display(), note)?;
+        let offset = self.writer.position();
+        match content {
+            Ok(file_content) => self
+                .writer
+                .write_all(escape_markers(&file_content.content).as_bytes())?,
+            Err(e) => writeln!(self.writer, "{}", read_error(self.messages, e))?,
+        }
+        let length = self.writer.position() - offset;
+        writeln!(self.writer, "\n### {} END ###\n", path.display())?;
+        Ok(Some(ContentSpan { offset, length }))
+    }
+
+    fn end_content(&mut self, label: &str) -> Result<()> {
+        if self.heading_level.is_none() {
+            writeln!(self.writer, "### DIRECTORY {} FLATTENED CONTENT ###", label)?;
+        }
+        Ok(())
+    }
+
+    fn budget_omissions(&mut self, files: &[OmittedFile]) -> Result<()> {
+        match self.heading_level {
+            Some(level) => self.section(level, 0, Message::OmittedDueToBudget)?,
+            None => writeln!(self.writer, "### OMITTED DUE TO BUDGET ###")?,
+        }
+        for file in files {
+            writeln!(self.writer, "- {} ({})", file.path.display(), format_size(file.size))?;
+        }
+        match self.heading_level {
+            Some(_) => writeln!(self.writer)?,
+            None => writeln!(self.writer, "### OMITTED DUE TO BUDGET ###\n")?,
+        }
+        Ok(())
+    }
+
+    fn skipped_appendix(&mut self, skipped: &SkippedContent) -> Result<()> {
+        match self.heading_level {
+            Some(level) => self.section(level, 0, Message::SkippedContent)?,
+            None => writeln!(self.writer, "### SKIPPED CONTENT ###")?,
+        }
+        for line in skipped_summary(skipped) {
+            writeln!(self.writer, "{}", line)?;
+        }
+        for line in skipped_entries(skipped) {
+            writeln!(self.writer, "- {}", line)?;
+        }
+        match self.heading_level {
+            Some(_) => writeln!(self.writer)?,
+            None => writeln!(self.writer, "### SKIPPED CONTENT ###\n")?,
+        }
+        Ok(())
+    }
+
+    fn truncated(&mut self, truncation: &Truncation) -> Result<()> {
+        match self.heading_level {
+            Some(_) => writeln!(self.writer, "**Truncated:** {}\n", truncation)?,
+            None => writeln!(self.writer, "### TRUNCATED: {} ###\n", truncation)?,
+        }
+        self.truncated = true;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.trailer {
+            let trailer = Trailer {
+                sections: self.sections,
+                bytes: self.writer.position(),
+                sha256: self.writer.sha256_hex(),
+                truncated: self.truncated.then(|| TIME_LIMIT_TRUNCATION.to_string()),
+            };
+            writeln!(self.writer, "{}", trailer)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Итоги раздела пропущенного содержимого: всего, по причинам, по расширениям.
+fn skipped_summary(skipped: &SkippedContent) -> Vec<String> {
+    let total = skipped.total;
+    let mut lines = vec![format!(
+        "Total: {} files ({})",
+        format_count(total.files),
+        format_size(total.bytes)
+    )];
+    if total.files > 0 {
+        lines.push(format!("By reason: {}", skipped.describe_reasons()));
+        lines.push(format!("By extension: {}", skipped.describe_extensions(None)));
+    }
+    lines
+}
+
+/// Строки списка пропущенных файлов и директорий раздела `--skipped-appendix`.
+fn skipped_entries(skipped: &SkippedContent) -> impl Iterator<Item = String> + '_ {
+    let more = (skipped.omitted_entries > 0)
+        .then(|| format!("… and {} more", format_count(skipped.omitted_entries)));
+    skipped.entries.iter().map(ToString::to_string).chain(more)
+}
+
+/// Пометки в заголовке файла: `[priority]`, права доступа (`[mode: 0755]`) и
+/// пометка устаревшего содержимого.
+fn file_note(content: &Result<FileContent>, priority: bool) -> String {
+    let mut note = String::new();
+    if priority {
+        note.push_str(" [priority]");
+    }
+    if let Some(mode) = content.as_ref().ok().and_then(|content| content.mode.as_ref()) {
+        note.push_str(&format!(" [{}]", mode));
+    }
+    if content.as_ref().is_ok_and(|content| content.stale) {
+        note.push(' ');
+        note.push_str(STALE_NOTE);
+    }
+    note
+}
+
+/// Записывает зависимости манифеста списком: пункт манифеста и вложенный пункт на раздел.
+/// Текст вместо содержимого файла, который не удалось прочитать.
+fn read_error(messages: &Messages, error: &anyhow::Error) -> String {
+    messages.format(Message::ReadError, &[("error", error)])
+}
+
+/// Пометка участника workspace в тексте обзора.
+fn crate_note(member: &WorkspaceCrate) -> Option<&'static str> {
+    if member.excluded {
+        Some("excluded")
+    } else if member.outside {
+        Some("outside the base folder")
+    } else {
+        None
+    }
+}
+
+/// Путь участника для вывода: `.` у корневого пакета.
+fn crate_path(member: &WorkspaceCrate) -> String {
+    match member.path.as_os_str().is_empty() {
+        true => ".".to_string(),
+        false => member.path.display().to_string(),
+    }
+}
+
+fn write_workspace_text(writer: &mut dyn Write, workspace: &CargoWorkspace) -> Result<()> {
+    writeln!(
+        writer,
+        "Cargo workspace at {}: {} crates",
+        workspace.root.display(),
+        workspace.members.len()
+    )?;
+    for member in &workspace.members {
+        match crate_note(member) {
+            Some(note) => writeln!(writer, "- {} ({}, {})", member.name, crate_path(member), note)?,
+            None => writeln!(writer, "- {} ({})", member.name, crate_path(member))?,
+        }
+    }
+    Ok(())
+}
+
+fn write_dependencies_text(writer: &mut dyn Write, manifest: &ManifestDependencies) -> Result<()> {
+    match &manifest.package {
+        Some(package) => writeln!(writer, "- {} ({})", manifest.path.display(), package)?,
+        None => writeln!(writer, "- {}", manifest.path.display())?,
+    }
+    if let Some(note) = &manifest.note {
+        writeln!(writer, "  - note: {}", note)?;
+    } else if manifest.sections.is_empty() {
+        writeln!(writer, "  - no dependencies")?;
+    }
+    for section in &manifest.sections {
+        let dependencies: Vec<_> = section
+            .dependencies
+            .iter()
+            .map(|dependency| match dependency.version.as_str() {
+                "" => dependency.name.clone(),
+                version => format!("{} {}", dependency.name, version),
+            })
+            .collect();
+        writeln!(writer, "  - {}: {}", section.name, dependencies.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Проверяет, похожа ли строка (без ведущих `\`) на маркер `### ... ###`.
+fn looks_like_marker(line: &str) -> bool {
+    let line = line.trim_start_matches('\\').trim_end_matches(['\r', '\n']);
+    line.strip_prefix("### ")
+        .is_some_and(|rest| rest.contains(" ###"))
+}
+
+/// Экранирует строки содержимого, которые можно спутать с маркерами документа.
+///
+/// Такая строка получает ведущий `\`; строки, уже начинающиеся с `\` перед
+/// маркером, получают еще один, поэтому экранирование обратимо. Содержимое без
+/// `###` возвращается без копирования.
+pub fn escape_markers(content: &str) -> Cow<'_, str> {
+    if !content.contains("###") {
+        return Cow::Borrowed(content);
+    }
+    let mut escaped = String::with_capacity(content.len() + 16);
+    for line in content.split_inclusive('\n') {
+        if looks_like_marker(line) {
+            escaped.push('\\');
+        }
+        escaped.push_str(line);
+    }
+    Cow::Owned(escaped)
+}
+
+/// Отменяет `escape_markers`.
+pub fn unescape_markers(content: &str) -> Cow<'_, str> {
+    if !content.contains("\\###") {
+        return Cow::Borrowed(content);
+    }
+    let mut unescaped = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        match line.strip_prefix('\\') {
+            Some(rest) if looks_like_marker(line) => unescaped.push_str(rest),
+            _ => unescaped.push_str(line),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// JSON-документ целиком.
+#[derive(Debug, Serialize)]
+struct JsonDocument {
+    version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    front_matter: Option<DocumentHeader>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repositories: Vec<JsonRepository>,
+    folders: Vec<JsonFolder>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    omitted_due_to_budget: Vec<JsonOmitted>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<SkippedContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncated: Option<Truncation>,
+}
+
+/// Сведения о репозитории базовой папки в JSON-документе (`--git-info`).
+#[derive(Debug, Serialize)]
+struct JsonRepository {
+    path: PathBuf,
+    #[serde(flatten)]
+    info: RepositoryInfo,
+}
+
+/// Одна базовая папка в JSON-документе.
+#[derive(Debug, Serialize)]
+struct JsonFolder {
+    path: PathBuf,
+    source: PathBuf,
+    structure: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    languages: Option<Vec<JsonLanguage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dependencies: Option<Vec<ManifestDependencies>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workspace: Option<CargoWorkspace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<JsonFile>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<JsonGroup>>,
+}
+
+/// Строка сводки по языкам (`--overview`).
+#[derive(Debug, Serialize)]
+struct JsonLanguage {
+    name: String,
+    file_count: usize,
+    bytes: u64,
+}
+
+impl From<&FileGroup> for JsonLanguage {
+    fn from(group: &FileGroup) -> Self {
+        Self {
+            name: group.name.clone(),
+            file_count: group.files,
+            bytes: group.bytes,
+        }
+    }
+}
+
+/// Группа файлов в JSON-документе.
+#[derive(Debug, Serialize)]
+struct JsonGroup {
+    name: String,
+    file_count: usize,
+    bytes: u64,
+    files: Vec<JsonFile>,
+}
+
+/// Один файл в JSON-документе.
+#[derive(Debug, Serialize)]
+struct JsonFile {
+    path: PathBuf,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lang: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chunks: Option<Vec<JsonChunk>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    priority: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stale: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<FileMode>,
+}
+
+/// Файл, не вошедший в `--fit-budget`, в JSON-документе.
+#[derive(Debug, Serialize)]
+struct JsonOmitted {
+    path: PathBuf,
+    size: u64,
+}
+
+impl From<&OmittedFile> for JsonOmitted {
+    fn from(file: &OmittedFile) -> Self {
+        Self {
+            path: file.path.clone(),
+            size: file.size,
+        }
+    }
+}
+
+/// Фрагмент содержимого файла в JSON-документе (`--chunk-size`).
+#[derive(Debug, Serialize)]
+struct JsonChunk {
+    chunk_index: usize,
+    start_line: usize,
+    end_line: usize,
+    content: String,
+}
+
+/// Список файлов для консоли, который выводит `--dry-run` вместо документа.
+pub(crate) struct ListingEmitter<W: Write> {
+    writer: W,
+    emoji: bool,
+    /// Ширина консоли, в которую укорачиваются строки дерева.
+    width: usize,
+    base_folder: PathBuf,
+}
+
+impl<W: Write> ListingEmitter<W> {
+    /// Создает эмиттер списка поверх `writer`; `emoji` включает значки.
+    pub(crate) fn new(writer: W, emoji: bool, width: usize) -> Self {
+        Self {
+            writer,
+            emoji,
+            width,
+            base_folder: PathBuf::new(),
+        }
+    }
+}
+
+impl<W: Write> Emitter for ListingEmitter<W> {
+    fn document_header(&mut self, _header: &DocumentHeader) -> Result<()> {
+        Ok(())
+    }
+
+    fn repository(&mut self, label: &str, info: &RepositoryInfo) -> Result<()> {
+        writeln!(self.writer, "Repository of {}: {}\n", label, info.summary())?;
+        Ok(())
+    }
+
+    fn folder_structure(
+        &mut self,
+        tree: &DirTree,
+        label: &str,
+        config: &FlattenConfig,
+    ) -> Result<()> {
+        self.base_folder = tree.root.clone();
+        writeln!(
+            self.writer,
+            "{} Folder structure for {}",
+            glyph(&FOLDER, self.emoji),
+            tree.root.display()
+        )?;
+        // Строки дерева пишутся блоками, а не системным вызовом на строку.
+        let mut writer = BufWriter::new(&mut self.writer);
+        print_folder_structure(tree, label, &mut writer, config, Some(self.width))?;
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn language_summary(&mut self, _label: &str, languages: &[FileGroup]) -> Result<()> {
+        writeln!(self.writer, "Languages:")?;
+        for language in languages {
+            writeln!(
+                self.writer,
+                "  {:>8}  {:>10}  {}",
+                format_count(language.files),
+                format_size(language.bytes),
+                language.name
+            )?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn dependencies(&mut self, _label: &str, manifests: &[ManifestDependencies]) -> Result<()> {
+        writeln!(self.writer, "Dependencies:")?;
+        for manifest in manifests {
+            write_dependencies_text(&mut self.writer, manifest)?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn workspace(&mut self, _label: &str, workspace: &CargoWorkspace) -> Result<()> {
+        writeln!(self.writer, "Workspace crates:")?;
+        write_workspace_text(&mut self.writer, workspace)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn begin_content(&mut self, _label: &str) -> Result<()> {
+        writeln!(
+            self.writer,
+            "{} Files to process from {}:",
+            glyph(&FILE, self.emoji),
+            self.base_folder.display()
+        )?;
+        Ok(())
+    }
+
+    fn begin_group(&mut self, group: &FileGroup) -> Result<()> {
+        writeln!(
+            self.writer,
+            "  [{}] {} files, {}",
+            group.name,
+            group.files,
+            format_size(group.bytes)
+        )?;
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        content: &Result<FileContent>,
+        priority: bool,
+    ) -> Result<Option<ContentSpan>> {
+        match content {
+            Ok(file_content)
+                if matches!(
+                    file_content.skip_reason,
+                    Some(SkipReason::DirFileLimit | SkipReason::Vendored)
+                ) =>
+            {
+                writeln!(self.writer, "  {}", &*file_content.content)?
+            }
+            Ok(file_content) => writeln!(
+                self.writer,
+                "  {} {} ({} bytes){}",
+                glyph(&SUCCESS, self.emoji),
+                path.display(),
+                file_content.original_size,
+                file_note(content, priority)
+            )?,
+            Err(e) => writeln!(
+                self.writer,
+                "  {} {} ({})",
+                glyph(&FAILURE, self.emoji),
+                path.display(),
+                e
+            )?,
+        }
+        Ok(None)
+    }
+
+    fn end_content(&mut self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn budget_omissions(&mut self, files: &[OmittedFile]) -> Result<()> {
+        writeln!(self.writer, "Omitted due to budget:")?;
+        for file in files {
+            writeln!(self.writer, "  {} ({} bytes)", file.path.display(), file.size)?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn skipped_appendix(&mut self, skipped: &SkippedContent) -> Result<()> {
+        writeln!(self.writer, "Skipped content:")?;
+        for line in skipped_summary(skipped).into_iter().chain(skipped_entries(skipped)) {
+            writeln!(self.writer, "  {}", line)?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn truncated(&mut self, truncation: &Truncation) -> Result<()> {
+        writeln!(self.writer, "Truncated: {}\n", truncation)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Эмиттер, передающий события всем документам одного обхода (`-o` несколько раз).
+///
+/// Файлы читаются и обрабатываются один раз, а каждый документ оформляет
+/// общее содержимое в своем формате. Документ, запись которого не удалась,
+/// отключается, а остальные дописываются; ошибка возвращается сразу только
+/// со `strict` или если писать больше некуда. Положение содержимого файла
+/// возвращается для первого документа.
+pub(crate) struct FanOutEmitter<'a> {
+    sinks: Vec<FanOutSink<'a>>,
+    strict: bool,
+}
+
+/// Документ `FanOutEmitter` и ошибка его записи.
+struct FanOutSink<'a> {
+    path: PathBuf,
+    emitter: Box<dyn Emitter + 'a>,
+    error: Option<anyhow::Error>,
+}
+
+impl<'a> FanOutEmitter<'a> {
+    /// Создает эмиттер без документов; `strict` прерывает запись при первой ошибке.
+    pub(crate) fn new(strict: bool) -> Self {
+        Self {
+            sinks: Vec::new(),
+            strict,
+        }
+    }
+
+    /// Добавляет документ `path`, оформляемый эмиттером `emitter`.
+    pub(crate) fn add(&mut self, path: PathBuf, emitter: Box<dyn Emitter + 'a>) {
+        self.sinks.push(FanOutSink {
+            path,
+            emitter,
+            error: None,
+        });
+    }
+
+    /// Есть ли хотя бы один документ.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Возвращает документы, запись которых не удалась, с ошибками.
+    pub(crate) fn into_failures(self) -> Vec<(PathBuf, anyhow::Error)> {
+        self.sinks
+            .into_iter()
+            .filter_map(|sink| Some((sink.path, sink.error?)))
+            .collect()
+    }
+
+    /// Передает событие всем исправным документам; возвращает результат первого.
+    fn each<T>(
+        &mut self,
+        mut write: impl FnMut(&mut dyn Emitter) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let mut first = None;
+        for index in 0..self.sinks.len() {
+            let sink = &mut self.sinks[index];
+            if sink.error.is_some() {
+                continue;
+            }
+            match write(sink.emitter.as_mut()) {
+                Ok(value) if index == 0 => first = Some(value),
+                Ok(_) => {}
+                Err(error) => {
+                    let alive = self
+                        .sinks
+                        .iter()
+                        .enumerate()
+                        .any(|(other, sink)| other != index && sink.error.is_none());
+                    if self.strict || !alive {
+                        return Err(FlattenError::output(&self.sinks[index].path, error).into());
+                    }
+                    self.sinks[index].error = Some(error);
+                }
+            }
+        }
+        Ok(first)
+    }
+}
+
+impl Emitter for FanOutEmitter<'_> {
+    fn document_header(&mut self, header: &DocumentHeader) -> Result<()> {
+        self.each(|emitter| emitter.document_header(header))?;
+        Ok(())
+    }
+
+    fn repository(&mut self, label: &str, info: &RepositoryInfo) -> Result<()> {
+        self.each(|emitter| emitter.repository(label, info))?;
+        Ok(())
+    }
+
+    fn folder_structure(
+        &mut self,
+        tree: &DirTree,
+        label: &str,
+        config: &FlattenConfig,
+    ) -> Result<()> {
+        self.each(|emitter| emitter.folder_structure(tree, label, config))?;
+        Ok(())
+    }
+
+    fn language_summary(&mut self, label: &str, languages: &[FileGroup]) -> Result<()> {
+        self.each(|emitter| emitter.language_summary(label, languages))?;
+        Ok(())
+    }
+
+    fn dependencies(&mut self, label: &str, manifests: &[ManifestDependencies]) -> Result<()> {
+        self.each(|emitter| emitter.dependencies(label, manifests))?;
+        Ok(())
+    }
+
+    fn workspace(&mut self, label: &str, workspace: &CargoWorkspace) -> Result<()> {
+        self.each(|emitter| emitter.workspace(label, workspace))?;
+        Ok(())
+    }
+
+    fn begin_content(&mut self, label: &str) -> Result<()> {
+        self.each(|emitter| emitter.begin_content(label))?;
+        Ok(())
+    }
+
+    fn begin_group(&mut self, group: &FileGroup) -> Result<()> {
+        self.each(|emitter| emitter.begin_group(group))?;
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        content: &Result<FileContent>,
+        priority: bool,
+    ) -> Result<Option<ContentSpan>> {
+        Ok(self.each(|emitter| emitter.file(path, content, priority))?.flatten())
+    }
+
+    fn end_content(&mut self, label: &str) -> Result<()> {
+        self.each(|emitter| emitter.end_content(label))?;
+        Ok(())
+    }
+
+    fn budget_omissions(&mut self, files: &[OmittedFile]) -> Result<()> {
+        self.each(|emitter| emitter.budget_omissions(files))?;
+        Ok(())
+    }
+
+    fn skipped_appendix(&mut self, skipped: &SkippedContent) -> Result<()> {
+        self.each(|emitter| emitter.skipped_appendix(skipped))?;
+        Ok(())
+    }
+
+    fn truncated(&mut self, truncation: &Truncation) -> Result<()> {
+        self.each(|emitter| emitter.truncated(truncation))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.each(|emitter| emitter.finish())?;
+        Ok(())
+    }
+}
+
+/// Единый JSON-документ, записываемый по завершении обхода.
+struct JsonEmitter<'a, W: Write> {
+    writer: BufWriter<W>,
+    document: JsonDocument,
+    file_headers: Option<FileHeaders>,
+    languages: &'a LanguageTable,
+}
+
+impl<W: Write> Emitter for JsonEmitter<'_, W> {
+    fn document_header(&mut self, header: &DocumentHeader) -> Result<()> {
+        self.document.front_matter = Some(header.clone());
+        Ok(())
+    }
+
+    fn repository(&mut self, label: &str, info: &RepositoryInfo) -> Result<()> {
+        self.document.repositories.push(JsonRepository {
+            path: PathBuf::from(label),
+            info: info.clone(),
+        });
+        Ok(())
+    }
+
+    fn folder_structure(
+        &mut self,
+        tree: &DirTree,
+        label: &str,
+        config: &FlattenConfig,
+    ) -> Result<()> {
+        self.document.folders.push(JsonFolder {
+            path: PathBuf::from(label),
+            source: tree.root.clone(),
+            structure: structure_lines(tree, config)?,
+            languages: None,
+            dependencies: None,
+            workspace: None,
+            files: None,
+            groups: None,
+        });
+        Ok(())
+    }
+
+    fn language_summary(&mut self, _label: &str, languages: &[FileGroup]) -> Result<()> {
+        if let Some(folder) = self.document.folders.last_mut() {
+            folder.languages = Some(languages.iter().map(JsonLanguage::from).collect());
+        }
+        Ok(())
+    }
+
+    fn dependencies(&mut self, _label: &str, manifests: &[ManifestDependencies]) -> Result<()> {
+        if let Some(folder) = self.document.folders.last_mut() {
+            folder.dependencies = Some(manifests.to_vec());
+        }
+        Ok(())
+    }
+
+    fn workspace(&mut self, _label: &str, workspace: &CargoWorkspace) -> Result<()> {
+        if let Some(folder) = self.document.folders.last_mut() {
+            folder.workspace = Some(workspace.clone());
+        }
+        Ok(())
+    }
+
+    fn begin_content(&mut self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn begin_group(&mut self, group: &FileGroup) -> Result<()> {
+        if let Some(folder) = self.document.folders.last_mut() {
+            folder.groups.get_or_insert_with(Vec::new).push(JsonGroup {
+                name: group.name.clone(),
+                file_count: group.files,
+                bytes: group.bytes,
+                files: Vec::new(),
+            });
+        }
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        content: &Result<FileContent>,
+        priority: bool,
+    ) -> Result<Option<ContentSpan>> {
+        let mut file = match content {
+            Ok(file_content) if !file_content.chunks.is_empty() => JsonFile {
+                path: path.to_path_buf(),
+                size: file_content.original_size,
+                lang: None,
+                sha256: None,
+                content: None,
+                chunks: Some(
+                    file_content
+                        .chunks
+                        .iter()
+                        .enumerate()
+                        .map(|(chunk_index, chunk)| JsonChunk {
+                            chunk_index,
+                            start_line: chunk.start_line,
+                            end_line: chunk.end_line,
+                            content: file_content.content[chunk.start..chunk.end].to_string(),
+                        })
+                        .collect(),
+                ),
+                skipped: None,
+                error: None,
+                priority,
+                stale: file_content.stale,
+                mode: file_content.mode.clone(),
+            },
+            Ok(file_content) => JsonFile {
+                path: path.to_path_buf(),
+                size: file_content.original_size,
+                lang: None,
+                sha256: None,
+                content: Some(file_content.content.to_string()),
+                chunks: None,
+                skipped: file_content.skip_reason.map(|r| r.as_str()),
+                error: None,
+                priority,
+                stale: file_content.stale,
+                mode: file_content.mode.clone(),
+            },
+            Err(e) => JsonFile {
+                path: path.to_path_buf(),
+                size: 0,
+                lang: None,
+                sha256: None,
+                content: None,
+                chunks: None,
+                skipped: None,
+                error: Some(e.to_string()),
+                priority,
+                stale: false,
+                mode: None,
+            },
+        };
+        if let Some(file_headers) = &self.file_headers {
+            let header = file_headers.header(self.languages, path, content);
+            file.lang = header.lang;
+            file.sha256 = header.sha256;
+        }
+        if let Some(folder) = self.document.folders.last_mut() {
+            match folder.groups.as_mut().and_then(|groups| groups.last_mut()) {
+                Some(group) => group.files.push(file),
+                None => folder.files.get_or_insert_with(Vec::new).push(file),
+            }
+        }
+        Ok(None)
+    }
+
+    fn end_content(&mut self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn budget_omissions(&mut self, files: &[OmittedFile]) -> Result<()> {
+        self.document.omitted_due_to_budget = files.iter().map(JsonOmitted::from).collect();
+        Ok(())
+    }
+
+    fn skipped_appendix(&mut self, skipped: &SkippedContent) -> Result<()> {
+        self.document.skipped = Some(skipped.clone());
+        Ok(())
+    }
+
+    fn truncated(&mut self, truncation: &Truncation) -> Result<()> {
+        self.document.truncated = Some(*truncation);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        serde_json::to_writer_pretty(&mut self.writer, &self.document)?;
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Возвращает строки дерева структуры для JSON-форматов.
+fn structure_lines(tree: &DirTree, config: &FlattenConfig) -> Result<Vec<String>> {
+    let mut rendered = Vec::new();
+    tree.render(config, &mut rendered)?;
+    Ok(String::from_utf8_lossy(&rendered)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Одна строка JSONL-вывода.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonlRecord<'a> {
+    /// Сведения о документе (`--front-matter`); первая строка вывода.
+    Document {
+        #[serde(flatten)]
+        header: &'a DocumentHeader,
+    },
+    /// Сведения о репозитории базовой папки (`--git-info`).
+    Repository {
+        path: &'a str,
+        #[serde(flatten)]
+        info: &'a RepositoryInfo,
+    },
+    /// Структура базовой папки; предшествует ее файлам.
+    Tree {
+        path: &'a str,
+        source: &'a Path,
+        structure: Vec<String>,
+    },
+    /// Сводка по языкам базовой папки (`--overview`).
+    Languages {
+        path: &'a str,
+        languages: Vec<JsonLanguage>,
+    },
+    /// Зависимости из манифестов базовой папки (`--deps-summary`).
+    Dependencies {
+        path: &'a str,
+        manifests: &'a [ManifestDependencies],
+    },
+    /// Крейты Cargo workspace базовой папки.
+    Workspace {
+        path: &'a str,
+        workspace: &'a CargoWorkspace,
+    },
+    /// Начало группы файлов (`--group-by`).
+    Group {
+        name: &'a str,
+        file_count: usize,
+        bytes: u64,
+    },
+    /// Файл; вместо `content` у нечитаемого файла есть `error`.
+    File {
+        path: &'a Path,
+        size: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        lang: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        skipped: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        priority: bool,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        stale: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mode: Option<&'a FileMode>,
+    },
+    /// Фрагмент файла (`--chunk-size`); заменяет запись `File`.
+    Chunk {
+        path: &'a Path,
+        chunk_index: usize,
+        start_line: usize,
+        end_line: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        lang: Option<&'a str>,
+        content: &'a str,
+    },
+    /// Файл, не вошедший в `--fit-budget`; такие записи идут в конце вывода.
+    Omitted {
+        path: &'a Path,
+        size: u64,
+    },
+    /// Пропущенное содержимое (`--skipped-appendix`).
+    Skipped {
+        #[serde(flatten)]
+        skipped: &'a SkippedContent,
+    },
+    /// Документ обрезан по `--time-limit`; последняя запись вывода.
+    Truncated {
+        #[serde(flatten)]
+        truncation: &'a Truncation,
+    },
+}
+
+/// Поток JSON-объектов, по одному на строку.
+///
+/// `LineWriter` сбрасывает каждую завершенную строку, поэтому вывод можно
+/// читать (`tail -f`, конвейер) до окончания обработки.
+struct JsonlEmitter<'a, W: Write> {
+    writer: LineWriter<W>,
+    file_headers: Option<FileHeaders>,
+    languages: &'a LanguageTable,
+}
+
+impl<W: Write> JsonlEmitter<'_, W> {
+    /// Записывает одну строку; `serde_json` экранирует управляющие символы,
+    /// поэтому перевод строки внутри содержимого не разрывает запись.
+    fn record(&mut self, record: &JsonlRecord<'_>) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Emitter for JsonlEmitter<'_, W> {
+    fn document_header(&mut self, header: &DocumentHeader) -> Result<()> {
+        self.record(&JsonlRecord::Document { header })
+    }
+
+    fn repository(&mut self, label: &str, info: &RepositoryInfo) -> Result<()> {
+        self.record(&JsonlRecord::Repository { path: label, info })
+    }
+
+    fn folder_structure(
+        &mut self,
+        tree: &DirTree,
+        label: &str,
+        config: &FlattenConfig,
+    ) -> Result<()> {
+        self.record(&JsonlRecord::Tree {
+            path: label,
+            source: &tree.root,
+            structure: structure_lines(tree, config)?,
+        })
+    }
+
+    fn language_summary(&mut self, label: &str, languages: &[FileGroup]) -> Result<()> {
+        self.record(&JsonlRecord::Languages {
+            path: label,
+            languages: languages.iter().map(JsonLanguage::from).collect(),
+        })
+    }
+
+    fn dependencies(&mut self, label: &str, manifests: &[ManifestDependencies]) -> Result<()> {
+        self.record(&JsonlRecord::Dependencies {
+            path: label,
+            manifests,
+        })
+    }
+
+    fn workspace(&mut self, label: &str, workspace: &CargoWorkspace) -> Result<()> {
+        self.record(&JsonlRecord::Workspace {
+            path: label,
+            workspace,
+        })
+    }
+
+    fn begin_content(&mut self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn begin_group(&mut self, group: &FileGroup) -> Result<()> {
+        self.record(&JsonlRecord::Group {
+            name: &group.name,
+            file_count: group.files,
+            bytes: group.bytes,
+        })
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        content: &Result<FileContent>,
+        priority: bool,
+    ) -> Result<Option<ContentSpan>> {
+        let record = match content {
+            Ok(file_content) if !file_content.chunks.is_empty() => {
+                for (chunk_index, chunk) in file_content.chunks.iter().enumerate() {
+                    self.record(&JsonlRecord::Chunk {
+                        path,
+                        chunk_index,
+                        start_line: chunk.start_line,
+                        end_line: chunk.end_line,
+                        lang: self.languages.language_for(path),
+                        content: &file_content.content[chunk.start..chunk.end],
+                    })?;
+                }
+                return Ok(None);
+            }
+            Ok(file_content) => JsonlRecord::File {
+                path,
+                size: file_content.original_size,
+                lang: match file_content.skip_reason {
+                    None => self.languages.language_for(path),
+                    Some(_) => None,
+                },
+                content: Some(&file_content.content),
+                sha256: self
+                    .file_headers
+                    .and_then(|headers| headers.header(self.languages, path, content).sha256),
+                skipped: file_content.skip_reason.map(|r| r.as_str()),
+                error: None,
+                priority,
+                stale: file_content.stale,
+                mode: file_content.mode.as_ref(),
+            },
+            Err(e) => JsonlRecord::File {
+                path,
+                size: 0,
+                lang: None,
+                content: None,
+                sha256: None,
+                skipped: None,
+                error: Some(e.to_string()),
+                priority,
+                stale: false,
+                mode: None,
+            },
+        };
+        self.record(&record)?;
+        Ok(None)
+    }
+
+    fn end_content(&mut self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn budget_omissions(&mut self, files: &[OmittedFile]) -> Result<()> {
+        for file in files {
+            self.record(&JsonlRecord::Omitted {
+                path: &file.path,
+                size: file.size,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn skipped_appendix(&mut self, skipped: &SkippedContent) -> Result<()> {
+        self.record(&JsonlRecord::Skipped { skipped })
+    }
+
+    fn truncated(&mut self, truncation: &Truncation) -> Result<()> {
+        self.record(&JsonlRecord::Truncated { truncation })
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Встроенные стили HTML-документа.
+const HTML_STYLE: &str = "\
+body{font-family:system-ui,sans-serif;margin:2rem auto;max-width:70rem;padding:0 1rem;color:#1f2328}
+h2{border-bottom:1px solid #d0d7de;padding-bottom:.3rem}
+ul.tree,ul.tree ul{list-style:none;padding-left:1.2rem}
+ul.tree{padding-left:0;font-family:ui-monospace,monospace}
+ul.tree summary{cursor:pointer}
+a{color:#0969da;text-decoration:none}
+a:hover{text-decoration:underline}
+.skipped{color:#8c959f}
+details.file{border:1px solid #d0d7de;border-radius:6px;margin:.5rem 0}
+details.file>summary{cursor:pointer;font-family:ui-monospace,monospace;padding:.4rem .6rem;background:#f6f8fa}
+details.file pre{margin:0;padding:.6rem;overflow-x:auto}
+.priority,.error,.stale,.mode{font-size:.8em;margin-left:.5rem;color:#9a6700}
+.error,.truncated{color:#cf222e}
+table.languages{border-collapse:collapse}
+table.languages td,table.languages th{border:1px solid #d0d7de;padding:.2rem .6rem;text-align:right}
+table.languages td:first-child,table.languages th:first-child{text-align:left}
+";
+
+/// Раскрывает секцию файла, на которую ведет ссылка из дерева.
+const HTML_SCRIPT: &str = "\
+function openTarget(){var t=document.getElementById(location.hash.slice(1));\
+if(t&&t.tagName==='DETAILS')t.open=true;}\
+window.addEventListener('hashchange',openTarget);openTarget();";
+
+/// Экранирует текст для HTML (в том числе для значений атрибутов).
+///
+/// Текст без специальных символов возвращается без копирования.
+pub fn escape_html(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Готовит скрипт к встраиванию в `<script>`: `</script` и `<!--` внутри
+/// него иначе закрыли бы элемент раньше времени.
+fn escape_script(script: &str) -> String {
+    let mut escaped = String::with_capacity(script.len());
+    let mut rest = script;
+    while let Some(index) = rest.find('<') {
+        escaped.push_str(&rest[..index]);
+        let tail = &rest[index..];
+        let closes = tail
+            .get(..8)
+            .is_some_and(|tag| tag.eq_ignore_ascii_case("</script"));
+        if closes {
+            escaped.push_str("<\\/");
+            rest = &tail[2..];
+        } else if let Some(comment) = tail.strip_prefix("<!--") {
+            escaped.push_str("<\\!--");
+            rest = comment;
+        } else {
+            escaped.push('<');
+            rest = &tail[1..];
+        }
+    }
+    escaped.push_str(rest);
+    escaped
+}
+
+/// Возвращает атрибуты `data-*` секции файла (`--front-matter per-file`).
+fn html_data_attributes(header: &FileHeader) -> String {
+    let mut attributes = format!(" data-size=\"{}\"", header.size);
+    if let Some(lang) = &header.lang {
+        attributes.push_str(&format!(" data-lang=\"{}\"", escape_html(lang)));
+    }
+    if let Some(sha256) = &header.sha256 {
+        attributes.push_str(&format!(" data-sha256=\"{}\"", sha256));
+    }
+    attributes
+}
+
+/// Самодостаточный HTML-документ: дерево папки со ссылками и секции
+/// `<details>` с содержимым файлов.
+struct HtmlEmitter<'a, W: Write> {
+    writer: BufWriter<W>,
+    /// Скрипт highlight.js для встраивания (`--html-highlight-js`).
+    highlight_script: Option<&'a str>,
+    /// Сведения о документе для заголовка HTML (`--front-matter`).
+    header: Option<DocumentHeader>,
+    file_headers: Option<FileHeaders>,
+    languages: &'a LanguageTable,
+    messages: &'a Messages,
+    /// Записано ли начало документа.
+    started: bool,
+    /// Якоря секций файлов, на которые ссылается дерево, по путям в документе.
+    anchors: HashMap<PathBuf, usize>,
+    next_anchor: usize,
+}
+
+impl<W: Write> HtmlEmitter<'_, W> {
+    /// Записывает начало документа перед первым событием.
+    fn start(&mut self) -> Result<()> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        writeln!(self.writer, "<!DOCTYPE html>")?;
+        writeln!(self.writer, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(
+            self.writer,
+            "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">"
+        )?;
+        writeln!(
+            self.writer,
+            "<meta name=\"generator\" content=\"flatten-rust {}\">",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        if let Some(header) = &self.header {
+            writeln!(
+                self.writer,
+                "<script type=\"application/json\" id=\"flatten-front-matter\">{}</script>",
+                escape_script(&serde_json::to_string(header)?)
+            )?;
+        }
+        writeln!(self.writer, "<title>Flattened codebase</title>")?;
+        writeln!(self.writer, "<style>\n{}</style>\n</head>\n<body>", HTML_STYLE)?;
+        Ok(())
+    }
+
+    /// Возвращает номер якоря секции файла, выделяя новый при необходимости.
+    fn anchor(&mut self, path: &Path) -> usize {
+        if let Some(anchor) = self.anchors.get(path) {
+            return *anchor;
+        }
+        let anchor = self.next_anchor;
+        self.next_anchor += 1;
+        self.anchors.insert(path.to_path_buf(), anchor);
+        anchor
+    }
+}
+
+impl<W: Write> Emitter for HtmlEmitter<'_, W> {
+    fn document_header(&mut self, header: &DocumentHeader) -> Result<()> {
+        self.header = Some(header.clone());
+        Ok(())
+    }
+
+    fn repository(&mut self, label: &str, info: &RepositoryInfo) -> Result<()> {
+        self.start()?;
+        writeln!(self.writer, "<h2>{} repository</h2>", escape_html(label))?;
+        writeln!(self.writer, "<p class=\"repository\">{}</p>", escape_html(&info.summary()))?;
+        Ok(())
+    }
+
+    fn folder_structure(
+        &mut self,
+        tree: &DirTree,
+        label: &str,
+        config: &FlattenConfig,
+    ) -> Result<()> {
+        self.start()?;
+        writeln!(self.writer, "<h2>{} structure</h2>", escape_html(label))?;
+        writeln!(self.writer, "<ul class=\"tree\">")?;
+        // Число открытых директорий над текущей строкой.
+        let mut open = 0;
+        let mut rows = tree.rows(config);
+        let mut shown = 0;
+        for row in rows.by_ref() {
+            while open >= row.depth {
+                writeln!(self.writer, "</ul></details></li>")?;
+                open -= 1;
+            }
+            let glyph = glyph(row.glyph(), config.emoji);
+            let name = escape_html(&row.name);
+            let note = escape_html(&row.note);
+            if !row.entry.is_dir {
+                let path = labeled_path(label, &tree.root, &row.entry.path);
+                let anchor = self.anchor(&path);
+                writeln!(
+                    self.writer,
+                    "<li>{} <a href=\"#file-{}\">{}</a></li>",
+                    glyph, anchor, name
+                )?;
+            } else if row.entry.skip_reason.is_some() {
+                writeln!(
+                    self.writer,
+                    "<li class=\"skipped\">{} {}/{}</li>",
+                    glyph, name, note
+                )?;
+            } else {
+                writeln!(
+                    self.writer,
+                    "<li><details open><summary>{} {}/{}</summary><ul>",
+                    glyph, name, note
+                )?;
+                open += 1;
+            }
+            shown += 1;
+            if shown == config.tree_max_entries {
+                break;
+            }
+        }
+        for _ in 0..open {
+            writeln!(self.writer, "</ul></details></li>")?;
+        }
+        if let Some(note) = truncation_note(shown, rows) {
+            writeln!(self.writer, "<li class=\"skipped\">{}</li>", note)?;
+        }
+        writeln!(self.writer, "</ul>")?;
+        Ok(())
+    }
+
+    fn language_summary(&mut self, label: &str, languages: &[FileGroup]) -> Result<()> {
+        writeln!(self.writer, "<h2>{} languages</h2>", escape_html(label))?;
+        writeln!(
+            self.writer,
+            "<table class=\"languages\">\n<tr><th>Language</th><th>Files</th><th>Size</th></tr>"
+        )?;
+        for language in languages {
+            writeln!(
+                self.writer,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&language.name),
+                format_count(language.files),
+                format_size(language.bytes)
+            )?;
+        }
+        writeln!(self.writer, "</table>")?;
+        Ok(())
+    }
+
+    fn dependencies(&mut self, label: &str, manifests: &[ManifestDependencies]) -> Result<()> {
+        writeln!(self.writer, "<h2>{} dependencies</h2>", escape_html(label))?;
+        for manifest in manifests {
+            let path = manifest.path.display().to_string();
+            match &manifest.package {
+                Some(package) => writeln!(
+                    self.writer,
+                    "<h3>{} ({})</h3>",
+                    escape_html(&path),
+                    escape_html(package)
+                )?,
+                None => writeln!(self.writer, "<h3>{}</h3>", escape_html(&path))?,
+            }
+            if let Some(note) = &manifest.note {
+                writeln!(self.writer, "<p class=\"error\">{}</p>", escape_html(note))?;
+            }
+            writeln!(self.writer, "<ul class=\"dependencies\">")?;
+            for section in &manifest.sections {
+                writeln!(self.writer, "<li>{}<ul>", escape_html(&section.name))?;
+                for dependency in &section.dependencies {
+                    writeln!(
+                        self.writer,
+                        "<li><code>{}</code> {}</li>",
+                        escape_html(&dependency.name),
+                        escape_html(&dependency.version)
+                    )?;
+                }
+                writeln!(self.writer, "</ul></li>")?;
+            }
+            writeln!(self.writer, "</ul>")?;
+        }
+        Ok(())
+    }
+
+    fn workspace(&mut self, label: &str, workspace: &CargoWorkspace) -> Result<()> {
+        writeln!(self.writer, "<h2>{} workspace crates</h2>", escape_html(label))?;
+        writeln!(
+            self.writer,
+            "<p>Cargo workspace at {}</p>",
+            escape_html(&workspace.root.display().to_string())
+        )?;
+        writeln!(self.writer, "<ul class=\"workspace\">")?;
+        for member in &workspace.members {
+            let note = crate_note(member)
+                .map(|note| format!(" ({})", note))
+                .unwrap_or_default();
+            writeln!(
+                self.writer,
+                "<li><code>{}</code> {}{}</li>",
+                escape_html(&member.name),
+                escape_html(&crate_path(member)),
+                note
+            )?;
+        }
+        writeln!(self.writer, "</ul>")?;
+        Ok(())
+    }
+
+    fn begin_content(&mut self, label: &str) -> Result<()> {
+        self.start()?;
+        writeln!(self.writer, "<h2>{} content</h2>", escape_html(label))?;
+        Ok(())
+    }
+
+    fn begin_group(&mut self, group: &FileGroup) -> Result<()> {
+        writeln!(
+            self.writer,
+            "<h3>{} ({} files, {})</h3>",
+            escape_html(&group.name),
+            format_count(group.files),
+            format_size(group.bytes)
+        )?;
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        path: &Path,
+        content: &Result<FileContent>,
+        priority: bool,
+    ) -> Result<Option<ContentSpan>> {
+        let anchor = self.anchor(path);
+        let display = path.display().to_string();
+        let mut note = String::new();
+        if priority {
+            note.push_str("<span class=\"priority\">priority</span>");
+        }
+        if let Some(mode) = content.as_ref().ok().and_then(|content| content.mode.as_ref()) {
+            let mode = mode.to_string();
+            note.push_str(&format!("<span class=\"mode\">{}</span>", escape_html(&mode)));
+        }
+        if content.as_ref().is_ok_and(|content| content.stale) {
+            note.push_str(&format!("<span class=\"stale\">{}</span>", STALE_NOTE));
+        }
+        let data = match &self.file_headers {
+            Some(file_headers) => html_data_attributes(&file_headers.header(self.languages, path, content)),
+            None => String::new(),
+        };
+        match content {
+            Ok(file_content) => {
+                let language = match file_content.skip_reason {
+                    None => self.languages.language_for(path).unwrap_or("plaintext"),
+                    Some(_) => "plaintext",
+                };
+                writeln!(
+                    self.writer,
+                    "<details class=\"file\" id=\"file-{}\"{}><summary>{}{}</summary>",
+                    anchor,
+                    data,
+                    escape_html(&display),
+                    note
+                )?;
+                write!(self.writer, "<pre><code class=\"language-{}\">", language)?;
+                self.writer
+                    .write_all(escape_html(&file_content.content).as_bytes())?;
+                writeln!(self.writer, "</code></pre></details>")?;
+            }
+            Err(e) => {
+                writeln!(
+                    self.writer,
+                    "<details class=\"file\" id=\"file-{}\"{}><summary>{}{}\
+                     <span class=\"error\">error</span></summary>",
+                    anchor,
+                    data,
+                    escape_html(&display),
+                    note
+                )?;
+                writeln!(
+                    self.writer,
+                    "<pre>{}</pre></details>",
+                    escape_html(&read_error(self.messages, e))
+                )?;
+            }
+        }
+        Ok(None)
+    }
+
+    fn end_content(&mut self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn budget_omissions(&mut self, files: &[OmittedFile]) -> Result<()> {
+        self.start()?;
+        writeln!(
+            self.writer,
+            "<h2>{}</h2>\n<ul class=\"omitted\">",
+            escape_html(&self.messages.format(Message::OmittedDueToBudget, &[]))
+        )?;
+        for file in files {
+            writeln!(
+                self.writer,
+                "<li><code>{}</code> {}</li>",
+                escape_html(&file.path.display().to_string()),
+                format_size(file.size)
+            )?;
+        }
+        writeln!(self.writer, "</ul>")?;
+        Ok(())
+    }
+
+    fn skipped_appendix(&mut self, skipped: &SkippedContent) -> Result<()> {
+        self.start()?;
+        writeln!(
+            self.writer,
+            "<h2>{}</h2>",
+            escape_html(&self.messages.format(Message::SkippedContent, &[]))
+        )?;
+        for line in skipped_summary(skipped) {
+            writeln!(self.writer, "<p>{}</p>", escape_html(&line))?;
+        }
+        writeln!(self.writer, "<ul class=\"skipped\">")?;
+        for line in skipped_entries(skipped) {
+            writeln!(self.writer, "<li>{}</li>", escape_html(&line))?;
+        }
+        writeln!(self.writer, "</ul>")?;
+        Ok(())
+    }
+
+    fn truncated(&mut self, truncation: &Truncation) -> Result<()> {
+        self.start()?;
+        writeln!(
+            self.writer,
+            "<p class=\"truncated\">Truncated: {}</p>",
+            escape_html(&truncation.to_string())
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.start()?;
+        writeln!(self.writer, "<script>{}</script>", HTML_SCRIPT)?;
+        if let Some(script) = self.highlight_script {
+            writeln!(self.writer, "<script>\n{}\n</script>", escape_script(script))?;
+            writeln!(self.writer, "<script>hljs.highlightAll();</script>")?;
+        }
+        writeln!(self.writer, "</body>\n</html>")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker_escaping_round_trip() {
+        let content = "fn main() {}\n### a.rs BEGIN ###\n\\### a.rs END ###\r\n### Heading\n";
+        let escaped = escape_markers(content);
+        assert_eq!(
+            escaped,
+            "fn main() {}\n\\### a.rs BEGIN ###\n\\\\### a.rs END ###\r\n### Heading\n"
+        );
+        assert_eq!(unescape_markers(&escaped), content);
+        assert!(matches!(escape_markers("plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_html_escaping() {
+        assert_eq!(
+            escape_html("</details><script>a && \"b\" || 'c'"),
+            "&lt;/details&gt;&lt;script&gt;a &amp;&amp; &quot;b&quot; || &#39;c&#39;"
+        );
+        assert!(matches!(escape_html("plain text"), Cow::Borrowed(_)));
+        assert_eq!(
+            escape_script("a</SCRIPT>b<!--c<d"),
+            "a<\\/SCRIPT>b<\\!--c<d"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_error_record() -> Result<()> {
+        let mut buffer = Vec::new();
+        {
+            let mut emitter = JsonlEmitter {
+                writer: LineWriter::new(&mut buffer),
+                file_headers: None,
+                languages: &LanguageTable::builtin(),
+            };
+            emitter.file(Path::new("src/locked.rs"), &Err(anyhow::anyhow!("denied")), true)?;
+            emitter.finish()?;
+        }
+        let line = String::from_utf8(buffer)?;
+        assert!(line.ends_with('\n'));
+        let record: serde_json::Value = serde_json::from_str(&line)?;
+        assert_eq!(
+            record,
+            serde_json::json!({
+                "type": "file",
+                "path": "src/locked.rs",
+                "size": 0,
+                "error": "denied",
+                "priority": true
+            })
+        );
+        Ok(())
+    }
+}
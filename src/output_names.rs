@@ -0,0 +1,143 @@
+//! Модуль автоматических имен выходных файлов (`--output-dir`).
+//!
+//! Если вывод направлен в директорию, имя файла строится по имени проекта и
+//! времени запуска в UTC: `<папка>-<yyyymmdd-HHMM>.<расширение>`. С
+//! `--reproducible` время не добавляется. Если файл с таким именем уже есть,
+//! к имени добавляется счетчик `-2`, `-3` и т. д. Несколько базовых папок
+//! попадают в один файл, названный по их общей родительской директории, а с
+//! `--split-per-folder` каждая папка получает собственный файл.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Выходной документ и базовые папки, которые в него попадут.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTarget {
+    /// Путь выходного файла (`-` — стандартный вывод).
+    pub path: PathBuf,
+    /// Базовые папки документа в порядке вывода.
+    pub folders: Vec<PathBuf>,
+}
+
+/// Распределяет базовые папки по автоматически названным файлам в `directory`.
+///
+/// `timestamp` добавляется к имени, если задан; `extension` указывается без точки.
+pub fn plan_outputs(
+    directory: &Path,
+    folders: &[PathBuf],
+    split_per_folder: bool,
+    extension: &str,
+    timestamp: Option<&str>,
+) -> Vec<OutputTarget> {
+    let groups: Vec<Vec<PathBuf>> = if split_per_folder {
+        folders.iter().map(|folder| vec![folder.clone()]).collect()
+    } else {
+        vec![folders.to_vec()]
+    };
+    let mut taken = HashSet::new();
+    groups
+        .into_iter()
+        .map(|folders| {
+            let mut stem = project_name(&folders);
+            if let Some(timestamp) = timestamp {
+                stem = format!("{}-{}", stem, timestamp);
+            }
+            OutputTarget {
+                path: unique_path(directory, &stem, extension, &mut taken),
+                folders,
+            }
+        })
+        .collect()
+}
+
+/// Возвращает имя проекта: имя единственной базовой папки или общей родительской.
+///
+/// Для базового файла используется имя без расширения.
+pub fn project_name(folders: &[PathBuf]) -> String {
+    let canonical: Vec<PathBuf> = folders
+        .iter()
+        .map(|folder| folder.canonicalize().unwrap_or_else(|_| folder.clone()))
+        .collect();
+    let name = match canonical.as_slice() {
+        [single] if single.is_file() => single.file_stem(),
+        [single] => single.file_name(),
+        _ => {
+            let mut ancestor = canonical.first().cloned().unwrap_or_default();
+            for path in &canonical[1..] {
+                while !path.starts_with(&ancestor) && ancestor.pop() {}
+            }
+            return ancestor.file_name().map_or_else(
+                || "root".to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+        }
+    };
+    name.map_or_else(
+        || "root".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Подбирает в `directory` свободное имя `stem.extension`, `stem-2.extension`, ...
+///
+/// Имена из `taken` уже выбраны в этом запуске и тоже считаются занятыми.
+fn unique_path(
+    directory: &Path,
+    stem: &str,
+    extension: &str,
+    taken: &mut HashSet<PathBuf>,
+) -> PathBuf {
+    let mut counter = 1;
+    loop {
+        let name = if counter == 1 {
+            format!("{}.{}", stem, extension)
+        } else {
+            format!("{}-{}.{}", stem, counter, extension)
+        };
+        let path = directory.join(name);
+        if !path.exists() && taken.insert(path.clone()) {
+            return path;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_outputs_names_and_collisions() -> std::io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path();
+        let out = root.join("out");
+        std::fs::create_dir_all(root.join("mono/api"))?;
+        std::fs::create_dir_all(root.join("mono/web"))?;
+        std::fs::create_dir_all(root.join("other/api"))?;
+        std::fs::create_dir_all(&out)?;
+        std::fs::write(out.join("api.md"), "old")?;
+
+        let api = root.join("mono/api");
+        let targets = plan_outputs(&out, std::slice::from_ref(&api), false, "md", None);
+        assert_eq!(targets[0].path, out.join("api-2.md"));
+
+        let folders = [api.clone(), root.join("mono/web")];
+        let targets = plan_outputs(&out, &folders, false, "json", Some("20261014-1746"));
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].path, out.join("mono-20261014-1746.json"));
+        assert_eq!(targets[0].folders.len(), 2);
+
+        let folders = [api, root.join("other/api"), root.join("mono/web")];
+        let targets = plan_outputs(&out, &folders, true, "md", None);
+        let names: Vec<&Path> = targets.iter().map(|target| target.path.as_path()).collect();
+        assert_eq!(
+            names,
+            vec![
+                out.join("api-2.md"),
+                out.join("api-3.md"),
+                out.join("web.md")
+            ]
+        );
+        Ok(())
+    }
+}
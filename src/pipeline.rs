@@ -0,0 +1,3108 @@
+//! Модуль этапов обработки: обход, чтение файлов и дерево структуры.
+//!
+//! `run` собирает выходной документ из этих функций; их же можно вызывать
+//! напрямую, чтобы встроить обход в собственный инструмент. Все функции
+//! применяют правила исключений из `FlattenConfig`.
+//!
+//! ```no_run
+//! use clap::Parser;
+//! use flatten_rust::pipeline::{self, ReadOptions};
+//! use flatten_rust::{Args, FlattenConfig};
+//! use std::path::Path;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let config = FlattenConfig::new(&Args::parse_from(["flatten-rust", "-f", "."])).await?;
+//! let mut tree = Vec::new();
+//! pipeline::render_tree(Path::new("."), &config, &mut tree)?;
+//!
+//! let options = ReadOptions::from_config(&config);
+//! for entry in pipeline::collect_files(Path::new("."), &config)? {
+//!     if entry.skip_reason.is_none() {
+//!         let content = pipeline::read_file(&entry.path, &options)?;
+//!         println!("{}: {} bytes", entry.path.display(), content.content.len());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::chunking::{Chunk, ChunkOptions};
+use crate::content_cache::{CachedContent, ContentCache};
+use crate::converters::{Conversion, ConverterRegistry, ConverterSource, FileConverter};
+use crate::file_modes::{FileMode, SymlinkTarget};
+use crate::git_info::{self, LfsPointer, LfsState, SubmoduleMode};
+use crate::grep::ContentFilter;
+use crate::language::LanguageTable;
+use crate::messages::{Message, Messages};
+use crate::normalize::{self, EolMode, LineEndings};
+use crate::outline::{Abridge, Syntax};
+use crate::progress::ProgressSink;
+use crate::report::{self, FlattenReport, SkipReason, SkipTotals, WarningKind};
+use crate::sanitize::{self, SanitizeCounts};
+use crate::terminal;
+use crate::time_limit::TimeLimit;
+use crate::transform::{TransformChain, TransformContext, TransformMetric};
+use crate::vendored::{VendoredDirectory, VendoredMode};
+use crate::{FILE, FOLDER, FlattenConfig, MORE, SKIP, glyph};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use console::Emoji;
+use memmap2::{Mmap, MmapOptions};
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+
+/// Маркеры сгенерированных файлов, которые ищутся в первых строках (`--skip-generated`).
+const GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+/// Количество первых строк файла, в которых ищутся маркеры генерации.
+const GENERATED_MARKER_LINES: usize = 5;
+/// Сколько байт начала файла читается, чтобы заранее найти маркер генерации.
+const GENERATED_HEAD_BYTES: u64 = 4096;
+/// Глубина строки дерева, после которой отступ больше не растет.
+const MAX_TREE_INDENT: usize = 32;
+
+thread_local! {
+    /// Буфер для чтения файлов меньше `--mmap-threshold`, переиспользуемый потоком.
+    static READ_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Файл, найденный при обходе базовой папки.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// Путь к файлу.
+    pub path: PathBuf,
+    /// Размер файла в байтах.
+    pub size: u64,
+    /// Причина, по которой содержимое будет заменено заглушкой (например, бинарное расширение).
+    pub skip_reason: Option<SkipReason>,
+}
+
+/// Результат чтения одного файла.
+#[derive(Debug, Clone)]
+pub struct FileContent {
+    /// Текст, попадающий в выходной документ.
+    pub content: ContentText,
+    /// Исходный размер файла в байтах.
+    pub original_size: u64,
+    /// Сколько байт прочитано с диска (0 для заглушек, которым чтение не нужно).
+    pub read_bytes: u64,
+    /// Имя конвертера, если содержимое было преобразовано.
+    pub converter: Option<&'static str>,
+    /// Причина, по которой содержимое заменено заглушкой.
+    pub skip_reason: Option<SkipReason>,
+    /// Исходный стиль окончаний строк (для заглушек не определяется).
+    pub line_endings: Option<LineEndings>,
+    /// Фрагменты содержимого при `--chunk-size` (пусто, если файл не разбивается).
+    pub chunks: Vec<Chunk>,
+    /// Для файла-указателя Git LFS — выведен ли указатель или сам объект.
+    pub lfs: Option<LfsState>,
+    /// Изменился ли файл между сбором и чтением: содержимое может не совпадать
+    /// ни с прежним, ни с текущим состоянием файла.
+    pub stale: bool,
+    /// Что заменено в содержимом при `--sanitize` (`None` — ничего).
+    pub sanitized: Option<SanitizeCounts>,
+    /// Изменения содержимого преобразованиями цепочки в порядке применения.
+    pub transforms: Vec<TransformMetric>,
+    /// Права доступа файла (`--file-modes`; `None` без флага).
+    pub mode: Option<FileMode>,
+}
+
+impl FileContent {
+    /// Содержимое файла размером `size`, прочитанное целиком без преобразований.
+    pub(crate) fn read(content: ContentText, size: u64) -> Self {
+        Self {
+            content,
+            original_size: size,
+            read_bytes: size,
+            converter: None,
+            skip_reason: None,
+            line_endings: None,
+            chunks: Vec::new(),
+            lfs: None,
+            stale: false,
+            sanitized: None,
+            transforms: Vec::new(),
+            mode: None,
+        }
+    }
+}
+
+/// Текст содержимого файла.
+///
+/// Валидный UTF-8 из memory-mapped файла не копируется: текст выдается прямо
+/// из отображения и так же записывается в вывод. Собственный буфер создается
+/// только для заглушек, результатов конвертеров, текста с невалидным UTF-8
+/// (lossy-декодирование), файлов меньше `--mmap-threshold` и при изменении
+/// окончаний строк. Перед записью отображение перепроверяется
+/// (`recheck_mapped`), поэтому файл, измененный во время запуска, не попадает
+/// в вывод невалидным UTF-8.
+///
+/// Разыменовывается в `&str`.
+pub struct ContentText(TextRepr);
+
+enum TextRepr {
+    /// Отображение файла, проверенное на UTF-8, начиная с байта `start` (после BOM).
+    Mapped {
+        map: Mmap,
+        start: usize,
+    },
+    Owned(String),
+    /// Текст файла с невалидным UTF-8 после декодирования с заменой.
+    Lossy(String),
+}
+
+impl ContentText {
+    /// Оборачивает отображение файла без копирования, если оно является валидным UTF-8;
+    /// иначе декодирует его с заменой невалидных последовательностей.
+    fn from_mmap(map: Mmap) -> Self {
+        if std::str::from_utf8(&map).is_ok() {
+            Self(TextRepr::Mapped { map, start: 0 })
+        } else {
+            Self(TextRepr::Lossy(String::from_utf8_lossy(&map).into_owned()))
+        }
+    }
+
+    /// Копирует прочитанные байты в собственный буфер; невалидный UTF-8
+    /// декодируется с заменой, как и в `from_mmap`.
+    fn from_bytes(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Self(TextRepr::Owned(text.to_string())),
+            Err(_) => Self(TextRepr::Lossy(String::from_utf8_lossy(bytes).into_owned())),
+        }
+    }
+
+    /// Возвращает, ссылается ли текст на отображение файла без копирования.
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.0, TextRepr::Mapped { .. })
+    }
+
+    /// Перепроверяет текст, отображенный из файла `path`, перед записью.
+    ///
+    /// Если длина файла больше не совпадает с отображением (файл переписан
+    /// или обрезан), к отображению не обращаются: файл читается заново
+    /// обычным чтением. Иначе UTF-8 проверяется повторно, без копирования, а
+    /// невалидный текст декодируется с заменой. Возвращает, изменился ли текст.
+    pub(crate) fn recheck_mapped(&mut self, path: &Path) -> Result<bool> {
+        let TextRepr::Mapped { map, start } = &self.0 else {
+            return Ok(false);
+        };
+        let same_length = std::fs::metadata(path).is_ok_and(|metadata| {
+            metadata.len() == map.len() as u64
+        });
+        if same_length && std::str::from_utf8(&map[*start..]).is_ok() {
+            return Ok(false);
+        }
+        *self = if same_length {
+            Self(TextRepr::Lossy(String::from_utf8_lossy(&map[*start..]).into_owned()))
+        } else {
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?;
+            Self::from_bytes(&bytes)
+        };
+        Ok(true)
+    }
+
+    /// Похож ли текст на бинарные данные: файл не был валидным UTF-8 или содержит NUL.
+    pub fn looks_binary(&self) -> bool {
+        matches!(self.0, TextRepr::Lossy(_)) || self.contains('\0')
+    }
+
+    /// Экранирует управляющие символы и удаляет escape-последовательности ANSI.
+    ///
+    /// Копирует текст, только если в нем есть что заменить.
+    pub(crate) fn sanitize(&mut self) -> Option<SanitizeCounts> {
+        let (text, counts) = sanitize::sanitize(self)?;
+        self.0 = match self.0 {
+            TextRepr::Lossy(_) => TextRepr::Lossy(text),
+            _ => TextRepr::Owned(text),
+        };
+        Some(counts)
+    }
+
+    /// Удаляет ведущий BOM и приводит окончания строк согласно `mode`.
+    ///
+    /// Копирует текст, только если окончания строк действительно меняются.
+    /// Возвращает исходный стиль окончаний строк.
+    pub(crate) fn normalize(&mut self, mode: EolMode) -> LineEndings {
+        match &mut self.0 {
+            TextRepr::Owned(text) | TextRepr::Lossy(text) => normalize::normalize_text(text, mode),
+            TextRepr::Mapped { map, start } => {
+                if map[*start..].starts_with("\u{feff}".as_bytes()) {
+                    *start += '\u{feff}'.len_utf8();
+                }
+                let endings = normalize::detect_line_endings(self);
+                if let Some(normalized) = normalize::convert_line_endings(self, mode, endings) {
+                    self.0 = TextRepr::Owned(normalized);
+                }
+                endings
+            }
+        }
+    }
+}
+
+impl std::ops::Deref for ContentText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match &self.0 {
+            // SAFETY: `Mapped` is only constructed in `from_mmap` after `from_utf8`
+            // succeeded, `start` only moves past a complete BOM character, and the
+            // bytes are validated again by `recheck_mapped` right before they are
+            // written. As with any mapping, the file is assumed not to change
+            // between that check and the write.
+            TextRepr::Mapped { map, start } => unsafe {
+                std::str::from_utf8_unchecked(&map[*start..])
+            },
+            TextRepr::Owned(text) | TextRepr::Lossy(text) => text,
+        }
+    }
+}
+
+impl From<String> for ContentText {
+    fn from(text: String) -> Self {
+        Self(TextRepr::Owned(text))
+    }
+}
+
+impl Clone for ContentText {
+    fn clone(&self) -> Self {
+        match &self.0 {
+            TextRepr::Lossy(text) => Self(TextRepr::Lossy(text.clone())),
+            _ => Self(TextRepr::Owned(self.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Debug for ContentText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl PartialEq<str> for ContentText {
+    fn eq(&self, other: &str) -> bool {
+        **self == *other
+    }
+}
+
+impl PartialEq<&str> for ContentText {
+    fn eq(&self, other: &&str) -> bool {
+        **self == **other
+    }
+}
+
+/// Параметры чтения файла.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOptions<'a> {
+    /// Максимальный размер файла в байтах (0 — без ограничения).
+    pub max_file_size: u64,
+    /// Преобразования содержимого (нормализация, сокращение, очистка).
+    pub transforms: &'a TransformChain,
+    /// Конвертеры содержимого по расширениям файлов.
+    pub converters: &'a ConverterRegistry,
+    /// Сколько ждать открытия и чтения файла (`None` — без ограничения).
+    pub timeout: Option<Duration>,
+    /// Когда прервать чтение, не дожидаясь `timeout` (`--time-limit-hard`).
+    pub deadline: Option<Instant>,
+    /// Разбиение содержимого на фрагменты (`None` — файл целиком).
+    pub chunking: Option<ChunkOptions>,
+    /// Выводить указатели Git LFS как есть, а не заглушкой.
+    pub lfs_include_pointers: bool,
+    /// Читать объекты Git LFS из `.git/lfs/objects` вместо указателей.
+    pub lfs_resolve: bool,
+    /// Таблица языков для `--outline` (`None` — структура не строится).
+    pub outline: Option<&'a LanguageTable>,
+    /// Сколько первых строк выводить, если структура не строится (0 — все).
+    pub head_lines: usize,
+    /// Файлы меньше этого размера читаются в буфер, остальные отображаются в
+    /// память (`--mmap-threshold`; 0 — отображаются все).
+    pub mmap_threshold: u64,
+    /// Кэш обработанного содержимого (`--content-cache`).
+    pub content_cache: Option<&'a Arc<ContentCache>>,
+    /// Тексты заглушек и пометок (`--messages`).
+    pub messages: &'a Messages,
+}
+
+impl<'a> ReadOptions<'a> {
+    /// Берет параметры чтения из конфигурации.
+    pub fn from_config(config: &'a FlattenConfig) -> Self {
+        Self {
+            max_file_size: config.max_file_size,
+            transforms: &config.transforms,
+            converters: &config.converters,
+            timeout: config.file_timeout,
+            deadline: config
+                .time_limit
+                .as_deref()
+                .filter(|limit| limit.is_hard())
+                .map(TimeLimit::deadline),
+            chunking: config.chunking,
+            lfs_include_pointers: config.lfs_include_pointers,
+            lfs_resolve: config.lfs_resolve,
+            outline: config.outline.then_some(&config.languages),
+            head_lines: config.head_lines,
+            mmap_threshold: config.mmap_threshold,
+            content_cache: config.content_cache.as_ref(),
+            messages: &config.messages,
+        }
+    }
+
+    /// Как сокращать содержимое файла `path` (`--outline`, `--head-lines`).
+    pub fn abridge(&self, path: &Path) -> Abridge {
+        Abridge {
+            syntax: self
+                .outline
+                .and_then(|languages| languages.language_for(path))
+                .and_then(Syntax::for_language),
+            head_lines: self.head_lines,
+        }
+    }
+}
+
+/// Элемент дерева базовой папки.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// Путь к файлу или директории.
+    pub path: PathBuf,
+    /// Глубина относительно базовой папки (1 — ее непосредственные потомки).
+    pub depth: usize,
+    /// Является ли элемент директорией.
+    pub is_dir: bool,
+    /// Для директории — причина пропуска (такая директория не обходится);
+    /// для файла — причина, по которой содержимое будет заменено заглушкой.
+    pub skip_reason: Option<SkipReason>,
+    /// Размер файла по метаданным, полученным при обходе (`None` — не известен).
+    pub size: Option<u64>,
+    /// Время изменения файла по тем же метаданным (`None` — не известно).
+    pub modified: Option<SystemTime>,
+}
+
+/// Результат единственного обхода базовой папки.
+///
+/// Из него строятся и дерево структуры, и список файлов содержимого, поэтому
+/// они не могут расходиться в том, что было пропущено: в дереве показан
+/// каждый файл, для которого в выводе есть секция содержимого (в том числе
+/// заглушки для бинарных файлов и lock-файлов), и только такие файлы.
+/// Пропущенные директории хранятся без содержимого и показываются
+/// с `--show-skipped`.
+///
+/// ```no_run
+/// # use clap::Parser;
+/// # use flatten_rust::{Args, FlattenConfig};
+/// use flatten_rust::pipeline::DirTree;
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let config = FlattenConfig::new(&Args::parse_from(["flatten-rust", "-f", "."])).await?;
+/// let tree = DirTree::walk(std::path::Path::new("."), &config)?;
+/// let mut rendered = Vec::new();
+/// tree.render(&config, &mut rendered)?;
+/// println!("{} files", tree.files().count());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirTree {
+    /// Базовая папка.
+    pub root: PathBuf,
+    /// Элементы в порядке обхода, без самой базовой папки.
+    pub entries: Vec<TreeEntry>,
+    /// Сводки `--dir-file-limit` по путям их строк (`<директория>/…`).
+    pub omitted: HashMap<PathBuf, OmittedFiles>,
+}
+
+/// Файлы директории сверх `--dir-file-limit`, замененные одной строкой.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OmittedFiles {
+    /// Директория относительно базовой папки, с `/` на конце.
+    pub directory: String,
+    /// Число замененных файлов.
+    pub files: usize,
+    /// Их суммарный размер.
+    pub bytes: u64,
+}
+
+/// Расхождение дерева структуры и секций содержимого базовой папки.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeMismatch {
+    /// Файлы, показанные в дереве, но без секции содержимого.
+    pub tree_only: Vec<PathBuf>,
+    /// Секции содержимого файлов, которых нет в дереве.
+    pub content_only: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for TreeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let groups = [
+            ("in tree without content", &self.tree_only),
+            ("with content but not in tree", &self.content_only),
+        ];
+        let mut first = true;
+        for (title, paths) in groups.into_iter().filter(|(_, paths)| !paths.is_empty()) {
+            if !first {
+                write!(f, "; ")?;
+            }
+            first = false;
+            let paths: Vec<_> = paths.iter().map(|path| path.display().to_string()).collect();
+            write!(f, "{}: {}", title, paths.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl OmittedFiles {
+    /// Текст сводки: `and 3,812 more files in migrations/ (14.20 MB total)`.
+    pub fn describe(&self, messages: &Messages) -> String {
+        messages.format_count(
+            Message::MoreFiles,
+            self.files,
+            &[
+                ("directory", &self.directory),
+                ("size", &report::format_size(self.bytes)),
+            ],
+        )
+    }
+}
+
+impl DirTree {
+    /// Обходит базовую папку `directory`, применяя правила исключений конфигурации.
+    pub fn walk(directory: &Path, config: &FlattenConfig) -> Result<Self> {
+        Self::walk_with_report(directory, config, &mut FlattenReport::default())
+    }
+
+    /// Обходит базовую папку, учитывая пропущенные элементы в `report` по причинам и шаблонам.
+    pub(crate) fn walk_with_report(
+        directory: &Path,
+        config: &FlattenConfig,
+        report: &mut FlattenReport,
+    ) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut walkdir = WalkDir::new(directory).follow_links(false);
+        if config.max_depth > 0 {
+            walkdir = walkdir.max_depth(config.max_depth);
+        }
+
+        let mut iter = walkdir.into_iter();
+        while let Some(entry) = iter.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // Недоступная вложенная директория пропускается с предупреждением.
+                Err(error) if error.depth() > 0 => {
+                    let path = error.path().unwrap_or(directory).to_path_buf();
+                    let (kind, message) = match error.io_error() {
+                        Some(io_error) => walk_warning(&path, io_error),
+                        None => (WarningKind::UnreadableDirectory, unreadable_message(&path, &error)),
+                    };
+                    report.warn(kind, message);
+                    continue;
+                }
+                Err(error) => return Err(error.into()),
+            };
+            let file_type = entry.file_type();
+            let (tree_entry, skipped) = visit_entry(
+                config,
+                directory,
+                entry.path(),
+                entry.depth(),
+                file_type,
+                || entry.metadata().ok(),
+            );
+            if let Some(reason) = skipped {
+                record_walk_skip(
+                    report,
+                    config,
+                    directory,
+                    entry.path(),
+                    file_type.is_dir(),
+                    reason,
+                    || entry.metadata().map(|m| m.len()).unwrap_or(0),
+                );
+                if file_type.is_dir() {
+                    iter.skip_current_dir();
+                }
+            } else if file_type.is_dir() && at_depth_ceiling(config, entry.depth()) {
+                report.warn(WarningKind::DepthCeiling, depth_ceiling_message(entry.path(), config));
+                iter.skip_current_dir();
+            }
+            entries.extend(tree_entry);
+        }
+
+        Ok(Self {
+            root: directory.to_path_buf(),
+            entries,
+            omitted: HashMap::new(),
+        })
+    }
+
+    /// Строит дерево из одного базового файла, указанного в `-f`.
+    ///
+    /// Файл выводится целиком, даже если его расширение пропускается.
+    pub fn single_file(path: &Path) -> Self {
+        Self {
+            root: path.to_path_buf(),
+            entries: vec![TreeEntry {
+                path: path.to_path_buf(),
+                depth: 1,
+                is_dir: false,
+                skip_reason: None,
+                size: None,
+                modified: None,
+            }],
+            omitted: HashMap::new(),
+        }
+    }
+
+    /// Строит дерево базовой папки `root` из готового списка файлов (`--files-from`).
+    ///
+    /// Правила пропуска папок и скрытых файлов не применяются: в дерево
+    /// попадают все перечисленные файлы внутри `root` и их директории, а
+    /// бинарные расширения и lock-файлы, как и при обходе, заменяются
+    /// заглушками. Файлы упорядочиваются по пути.
+    pub fn from_files(root: &Path, files: &[PathBuf], config: &FlattenConfig) -> Self {
+        let mut files: Vec<&PathBuf> = files
+            .iter()
+            .filter(|file| file.starts_with(root) && file.as_path() != root)
+            .collect();
+        files.sort();
+        let mut entries = Vec::new();
+        let mut directories = HashSet::new();
+        for file in files {
+            let components: Vec<_> = file
+                .strip_prefix(root)
+                .unwrap_or(file)
+                .components()
+                .collect();
+            let mut directory = root.to_path_buf();
+            for (depth, component) in components.iter().enumerate().take(components.len() - 1) {
+                directory.push(component);
+                if directories.insert(directory.clone()) {
+                    entries.push(TreeEntry {
+                        path: directory.clone(),
+                        depth: depth + 1,
+                        is_dir: true,
+                        skip_reason: None,
+                        size: None,
+                        modified: None,
+                    });
+                }
+            }
+            entries.push(TreeEntry {
+                path: file.clone(),
+                depth: components.len(),
+                is_dir: false,
+                skip_reason: config.file_skip_reason(root, file),
+                size: None,
+                modified: None,
+            });
+        }
+        Self {
+            root: root.to_path_buf(),
+            entries,
+            omitted: HashMap::new(),
+        }
+    }
+
+    /// Обходит базовую папку параллельно (`--parallel-walk`).
+    ///
+    /// Поддиректории обходятся в пуле `rayon`, а `on_file` вызывается для
+    /// каждого найденного файла сразу из рабочего потока, поэтому обработку
+    /// файлов можно начать до завершения обхода. Правила исключений те же,
+    /// что у `walk_with_report`, а элементы собираются в том же порядке, что
+    /// и при последовательном обходе, поэтому результат не зависит от режима.
+    pub(crate) fn walk_parallel_with_report(
+        directory: &Path,
+        config: &FlattenConfig,
+        report: &mut FlattenReport,
+        on_file: &(dyn Fn(&TreeEntry) + Sync),
+    ) -> Result<Self> {
+        let root = std::fs::metadata(directory)
+            .with_context(|| format!("Failed to read directory: {}", directory.display()))?;
+        let mut tree = Self {
+            root: directory.to_path_buf(),
+            entries: Vec::new(),
+            omitted: HashMap::new(),
+        };
+        if !root.is_dir() {
+            return Ok(tree);
+        }
+
+        let walk = ParallelWalk {
+            config,
+            directory,
+            report: Mutex::new(report),
+            on_file,
+        };
+        tree.entries = walk.children(directory, 1)?;
+        Ok(tree)
+    }
+
+    /// Возвращает файлы, для которых в выводе будет секция содержимого.
+    pub fn files(&self) -> impl Iterator<Item = &TreeEntry> {
+        self.entries.iter().filter(|entry| !entry.is_dir)
+    }
+
+    /// Сверяет файлы дерева с путями секций содержимого, выведенных для папки (`--self-check`).
+    ///
+    /// `withheld` — файлы, содержимое которых не выводится намеренно
+    /// (`--overview`, `--content-paths`, бюджет, лимит времени). Секцией
+    /// может быть и сводка директории стороннего кода, показанной в дереве.
+    pub fn verify_content(
+        &self,
+        emitted: &HashSet<PathBuf>,
+        withheld: &HashSet<PathBuf>,
+    ) -> std::result::Result<(), TreeMismatch> {
+        let mut shown = HashSet::new();
+        let mut mismatch = TreeMismatch::default();
+        for entry in &self.entries {
+            if entry.is_dir && entry.skip_reason != Some(SkipReason::Vendored) {
+                continue;
+            }
+            shown.insert(&entry.path);
+            if !entry.is_dir && !emitted.contains(&entry.path) && !withheld.contains(&entry.path) {
+                mismatch.tree_only.push(entry.path.clone());
+            }
+        }
+        mismatch.content_only = emitted
+            .iter()
+            .filter(|path| !shown.contains(path))
+            .cloned()
+            .collect();
+        mismatch.content_only.sort();
+        if mismatch.tree_only.is_empty() && mismatch.content_only.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatch)
+        }
+    }
+
+    /// Выводит в `writer` строки дерева без обрамляющих маркеров.
+    ///
+    /// Цепочки директорий, в каждой из которых нет файлов и ровно одна
+    /// поддиректория, выводятся одной строкой (`src/main/java/`), если не
+    /// задан `--no-collapse-dirs`. Пропущенная директория в цепочку не
+    /// объединяется, как и директория, у которой кроме поддиректории есть
+    /// пропущенные, даже если они не выводятся.
+    ///
+    /// С `--tree-annotations` после директории выводится число и объем
+    /// включенных в нее файлов, а после пропущенной — примерное число
+    /// элементов в ней.
+    ///
+    /// С `--tree-depth` строки глубже заданной не выводятся, а директория на
+    /// границе получает пометку `… (N more entries)`. Глубина считается после
+    /// объединения цепочек; на сбор файлов ограничение не влияет.
+    ///
+    /// Строки глубже `MAX_TREE_INDENT` уровней выводятся с отступом этой
+    /// глубины и пометкой `[depth N]`.
+    ///
+    /// Файлы сверх `--dir-file-limit` выводятся одной строкой сводки
+    /// `… and N more files in <директория>/` (см. `limit_dir_files`).
+    ///
+    /// Строки записываются по мере получения, без сборки всего дерева в
+    /// памяти. С `--tree-max-entries` после заданного числа строк выводится
+    /// `… truncated (N shown of M)`.
+    pub fn render<W: Write + ?Sized>(&self, config: &FlattenConfig, writer: &mut W) -> Result<()> {
+        self.render_fitted(config, writer, None)
+    }
+
+    /// Выводит дерево, как `render`, но с `width` укорачивает имена знаком `…`,
+    /// чтобы строки помещались в `width` колонок (для консоли).
+    pub fn render_fitted<W: Write + ?Sized>(
+        &self,
+        config: &FlattenConfig,
+        writer: &mut W,
+        width: Option<usize>,
+    ) -> Result<()> {
+        let mut rows = self.rows(config);
+        let mut shown = 0;
+        for row in rows.by_ref() {
+            // Глубже `MAX_TREE_INDENT` отступ не растет, а глубина пишется числом.
+            let (indent, deep) = if row.depth > MAX_TREE_INDENT {
+                ("    ".repeat(MAX_TREE_INDENT - 1), format!("[depth {}] ", row.depth))
+            } else {
+                ("    ".repeat(row.depth - 1), String::new())
+            };
+            let glyph = glyph(row.glyph(), config.emoji);
+            let slash = if row.entry.is_dir { "/" } else { "" };
+            let prefix = format!("{}{}{} ", indent, deep, glyph);
+            let name = match width {
+                Some(width) => {
+                    let used = console::measure_text_width(&prefix)
+                        + slash.len()
+                        + console::measure_text_width(&row.note);
+                    terminal::fit_end(&row.name, width.saturating_sub(used))
+                }
+                None => Cow::Borrowed(row.name.as_str()),
+            };
+            writeln!(writer, "{}{}{}{}", prefix, name, slash, row.note)?;
+            shown += 1;
+            if shown == config.tree_max_entries {
+                break;
+            }
+        }
+        if let Some(note) = truncation_note(shown, rows) {
+            writeln!(writer, "{}", note)?;
+        }
+        Ok(())
+    }
+
+    /// Возвращает строки дерева в порядке вывода (см. `render`).
+    ///
+    /// Строки формируются лениво: заранее считаются только объединяемые
+    /// цепочки и итоги директорий.
+    pub(crate) fn rows<'a>(&'a self, config: &'a FlattenConfig) -> TreeRows<'a> {
+        TreeRows {
+            tree: self,
+            config,
+            collapsed: self.collapsed_dirs(config),
+            totals: self.dir_totals(config),
+            index: 0,
+            prefix: String::new(),
+            collapsed_depths: Vec::new(),
+            boundary: None,
+            ready: None,
+        }
+    }
+
+    /// Считает для каждой директории число и объем включенных файлов внутри нее.
+    ///
+    /// Учитываются файлы, содержимое которых попадет в вывод: файлы, заменяемые
+    /// заглушкой (по имени или расширению, сверх `--max-file-size`, а с
+    /// `--skip-generated` и сгенерированные), не учитываются. Размеры берутся
+    /// из записей дерева; файлы проверяются параллельно.
+    fn dir_totals(&self, config: &FlattenConfig) -> Vec<(usize, u64)> {
+        let mut totals = vec![(0, 0); self.entries.len()];
+        if config.tree_annotations == TreeAnnotations::None {
+            return totals;
+        }
+        let emitted: Vec<Option<u64>> = self
+            .entries
+            .par_iter()
+            .map(|entry| if entry.is_dir { None } else { emitted_size(entry, config) })
+            .collect();
+        let mut parents: Vec<usize> = Vec::new();
+        let close = |parents: &mut Vec<usize>, totals: &mut [(usize, u64)]| {
+            if let Some(done) = parents.pop()
+                && let Some(&parent) = parents.last()
+            {
+                totals[parent].0 += totals[done].0;
+                totals[parent].1 += totals[done].1;
+            }
+        };
+        for (index, entry) in self.entries.iter().enumerate() {
+            while parents
+                .last()
+                .is_some_and(|&parent| self.entries[parent].depth >= entry.depth)
+            {
+                close(&mut parents, &mut totals);
+            }
+            if entry.is_dir {
+                parents.push(index);
+            } else if let Some(size) = emitted[index]
+                && let Some(&parent) = parents.last()
+            {
+                totals[parent].0 += 1;
+                totals[parent].1 += size;
+            }
+        }
+        while !parents.is_empty() {
+            close(&mut parents, &mut totals);
+        }
+        totals
+    }
+
+    /// Отмечает директории, которые выводятся одной строкой со своей
+    /// единственной поддиректорией.
+    fn collapsed_dirs(&self, config: &FlattenConfig) -> Vec<bool> {
+        let mut collapsed = vec![false; self.entries.len()];
+        if !config.collapse_dirs {
+            return collapsed;
+        }
+        // Для каждой директории: число потомков первого уровня и первый из них.
+        // Пропущенные директории считаются и без `--show-skipped`, чтобы
+        // цепочка не скрывала, что у директории есть другие потомки.
+        let mut children: Vec<(usize, Option<usize>)> = vec![(0, None); self.entries.len()];
+        let mut parents: Vec<usize> = Vec::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            while parents
+                .last()
+                .is_some_and(|&parent| self.entries[parent].depth >= entry.depth)
+            {
+                parents.pop();
+            }
+            if let Some(&parent) = parents.last() {
+                let (count, first) = &mut children[parent];
+                *count += 1;
+                first.get_or_insert(index);
+            }
+            if entry.is_dir {
+                parents.push(index);
+            }
+        }
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            if let (1, Some(child)) = children[index] {
+                let child = &self.entries[child];
+                collapsed[index] = entry.is_dir
+                    && entry.skip_reason.is_none()
+                    && child.is_dir
+                    && child.skip_reason.is_none();
+            }
+        }
+        collapsed
+    }
+}
+
+/// Пометка об обрезке дерева по `--tree-max-entries`, если после `shown`
+/// выведенных строк остались невыведенные `rest`.
+pub(crate) fn truncation_note(shown: usize, rest: TreeRows<'_>) -> Option<String> {
+    let rest = rest.count();
+    (rest > 0).then(|| format!("… truncated ({} shown of {})", shown, shown + rest))
+}
+
+/// Ленивый итератор строк дерева (см. `DirTree::render`).
+pub(crate) struct TreeRows<'a> {
+    tree: &'a DirTree,
+    config: &'a FlattenConfig,
+    collapsed: Vec<bool>,
+    totals: Vec<(usize, u64)>,
+    /// Следующий элемент дерева.
+    index: usize,
+    /// Начало объединяемой цепочки директорий.
+    prefix: String,
+    /// Глубины директорий, объединенных с потомками над текущим элементом.
+    collapsed_depths: Vec<usize>,
+    /// Строка на границе `--tree-depth` и число скрытых строк под ней; она
+    /// выводится, когда встречается строка не глубже границы.
+    boundary: Option<(TreeRow<'a>, usize)>,
+    /// Строка, прочитанная вместе с завершением границы.
+    ready: Option<TreeRow<'a>>,
+}
+
+impl<'a> TreeRows<'a> {
+    /// Следующая строка без учета `--tree-depth`.
+    fn next_row(&mut self) -> Option<TreeRow<'a>> {
+        let config = self.config;
+        while let Some(entry) = self.tree.entries.get(self.index) {
+            let (collapsed, (files, bytes)) = (self.collapsed[self.index], self.totals[self.index]);
+            self.index += 1;
+            if !is_shown(entry, config) {
+                continue;
+            }
+            while self
+                .collapsed_depths
+                .last()
+                .is_some_and(|&depth| depth >= entry.depth)
+            {
+                self.collapsed_depths.pop();
+            }
+
+            let file_name = match self.tree.omitted.get(&entry.path) {
+                Some(omitted) => Cow::Owned(omitted.describe(&config.messages)),
+                None => entry
+                    .path
+                    .file_name()
+                    .unwrap_or_else(|| OsStr::new(""))
+                    .to_string_lossy(),
+            };
+            if collapsed {
+                self.prefix.push_str(&file_name);
+                self.prefix.push('/');
+                self.collapsed_depths.push(entry.depth);
+                continue;
+            }
+
+            let note = if entry.skip_reason == Some(SkipReason::Submodule) {
+                match git_info::pinned_commit(&entry.path) {
+                    Some(commit) => format!(" (submodule @ {})", &commit[..commit.len().min(12)]),
+                    None => " (nested repository)".to_string(),
+                }
+            } else if let (true, Some(reason)) = (entry.is_dir, entry.skip_reason) {
+                let label = match reason {
+                    SkipReason::Vendored => "vendored",
+                    _ => "skipped",
+                };
+                match config.tree_annotations {
+                    TreeAnnotations::None => format!(" ({})", label),
+                    _ => {
+                        let count = std::fs::read_dir(&entry.path).map_or(0, Iterator::count);
+                        let noun = if count == 1 { "entry" } else { "entries" };
+                        format!(" ({}, ~{} {})", label, count, noun)
+                    }
+                }
+            } else if entry.is_dir {
+                config.tree_annotations.describe(files, bytes)
+            } else {
+                String::new()
+            };
+            return Some(TreeRow {
+                entry,
+                depth: entry.depth - self.collapsed_depths.len(),
+                name: std::mem::take(&mut self.prefix) + &file_name,
+                note,
+            });
+        }
+        None
+    }
+}
+
+/// Дописывает строке на границе `--tree-depth` число скрытых под ней строк.
+fn close_boundary((mut row, hidden): (TreeRow<'_>, usize)) -> TreeRow<'_> {
+    if hidden > 0 {
+        let noun = if hidden == 1 { "entry" } else { "entries" };
+        row.note.push_str(&format!(" … ({} more {})", hidden, noun));
+    }
+    row
+}
+
+impl<'a> Iterator for TreeRows<'a> {
+    type Item = TreeRow<'a>;
+
+    fn next(&mut self) -> Option<TreeRow<'a>> {
+        let max_depth = self.config.tree_depth;
+        if max_depth == 0 {
+            return self.next_row();
+        }
+        if let Some(row) = self.ready.take() {
+            return Some(row);
+        }
+        // Строки идут в порядке обхода, поэтому скрытые строки относятся к
+        // последней строке глубины `max_depth`.
+        loop {
+            let Some(row) = self.next_row() else {
+                return self.boundary.take().map(close_boundary);
+            };
+            if row.depth > max_depth {
+                if let Some((_, hidden)) = &mut self.boundary {
+                    *hidden += 1;
+                }
+            } else if row.depth == max_depth {
+                if let Some(boundary) = self.boundary.replace((row, 0)) {
+                    return Some(close_boundary(boundary));
+                }
+            } else {
+                match self.boundary.take() {
+                    Some(boundary) => {
+                        self.ready = Some(row);
+                        return Some(close_boundary(boundary));
+                    }
+                    None => return Some(row),
+                }
+            }
+        }
+    }
+}
+
+/// Строка дерева структуры.
+pub(crate) struct TreeRow<'a> {
+    /// Элемент дерева; для объединенной цепочки — ее последняя директория.
+    pub(crate) entry: &'a TreeEntry,
+    /// Глубина строки после объединения цепочек (1 — верхний уровень).
+    pub(crate) depth: usize,
+    /// Имя элемента; для цепочки — вместе с предшествующими директориями.
+    pub(crate) name: String,
+    /// Пометка после имени с ведущим пробелом (` (skipped)`, ` (2 files)`) или пустая строка.
+    pub(crate) note: String,
+}
+
+impl TreeRow<'_> {
+    /// Значок строки: пропущенная директория, директория, файл или сводка
+    /// `--dir-file-limit`.
+    pub(crate) fn glyph(&self) -> &'static Emoji<'static, 'static> {
+        match (self.entry.is_dir, self.entry.skip_reason) {
+            (false, Some(SkipReason::DirFileLimit)) => &MORE,
+            (true, Some(_)) => &SKIP,
+            (true, None) => &FOLDER,
+            (false, _) => &FILE,
+        }
+    }
+}
+
+/// Какие сведения о директориях выводить в дереве (`--tree-annotations`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum TreeAnnotations {
+    /// Без пометок (по умолчанию).
+    #[default]
+    None,
+    /// Число включенных файлов.
+    Counts,
+    /// Суммарный размер включенных файлов.
+    Sizes,
+    /// Число и размер файлов.
+    Both,
+}
+
+impl TreeAnnotations {
+    /// Возвращает пометку директории вида ` (214 files, 1.80 MB)`.
+    fn describe(self, files: usize, bytes: u64) -> String {
+        let files = format!("{} {}", files, if files == 1 { "file" } else { "files" });
+        match self {
+            TreeAnnotations::None => String::new(),
+            TreeAnnotations::Counts => format!(" ({})", files),
+            TreeAnnotations::Sizes => format!(" ({})", report::format_size(bytes)),
+            TreeAnnotations::Both => format!(" ({}, {})", files, report::format_size(bytes)),
+        }
+    }
+}
+
+/// Показывается ли элемент в дереве: пропущенные директории — только с `--show-skipped`,
+/// вложенные репозитории — также с `--submodules summarize`, сторонний код — всегда.
+fn is_shown(entry: &TreeEntry, config: &FlattenConfig) -> bool {
+    !(entry.is_dir && entry.skip_reason.is_some())
+        || config.show_skipped
+        || (entry.skip_reason == Some(SkipReason::Submodule)
+            && config.submodules == SubmoduleMode::Summarize)
+        || entry.skip_reason == Some(SkipReason::Vendored)
+}
+
+/// Проверяет элемент обхода по правилам исключений.
+///
+/// Общая проверка последовательного и параллельного обхода. Возвращает элемент
+/// дерева (базовая папка и элементы, не являющиеся файлом или директорией,
+/// в дерево не попадают) и причину исключения; исключенная директория
+/// не обходится. Базовая папка указана пользователем явно, поэтому сама
+/// не проверяется: скрытая папка или папка `target` обходится как обычно,
+/// а правила применяются к ее содержимому. `metadata` запрашивает метаданные
+/// элемента обхода только для попавших в дерево файлов: размер и время
+/// изменения запоминаются, чтобы не запрашивать размер у файловой системы
+/// повторно перед чтением и заметить изменение файла во время запуска.
+fn visit_entry(
+    config: &FlattenConfig,
+    directory: &Path,
+    path: &Path,
+    depth: usize,
+    file_type: std::fs::FileType,
+    metadata: impl FnOnce() -> Option<std::fs::Metadata>,
+) -> (Option<TreeEntry>, Option<SkipReason>) {
+    if depth == 0 {
+        return (None, None);
+    }
+    let is_dir = file_type.is_dir();
+    let tree_entry = |skip_reason, metadata: Option<std::fs::Metadata>| {
+        Some(TreeEntry {
+            path: path.to_path_buf(),
+            depth,
+            is_dir,
+            skip_reason,
+            size: metadata.as_ref().map(std::fs::Metadata::len),
+            modified: metadata.and_then(|metadata| metadata.modified().ok()),
+        })
+    };
+
+    if let Some(reason) = config.path_skip_reason(directory, path) {
+        return (
+            if is_dir {
+                tree_entry(Some(reason), None)
+            } else {
+                None
+            },
+            Some(reason),
+        );
+    }
+    if is_dir {
+        (tree_entry(None, None), None)
+    } else if file_type.is_file() || (config.file_modes && file_type.is_symlink()) {
+        (tree_entry(config.file_skip_reason(directory, path), metadata()), None)
+    } else {
+        (None, None)
+    }
+}
+
+/// Учитывает исключенный при обходе элемент в `report` по причине и шаблону.
+///
+/// Размер файла (`len`) и содержимое директории запрашиваются только при
+/// включенной статистике.
+fn record_walk_skip(
+    report: &mut FlattenReport,
+    config: &FlattenConfig,
+    directory: &Path,
+    path: &Path,
+    is_dir: bool,
+    reason: SkipReason,
+    len: impl FnOnce() -> u64,
+) {
+    let totals = match (is_dir, config.measure_skipped) {
+        (true, true) => report.record_skipped_directory(reason, path, directory_files(path)),
+        (true, false) => report.record_skipped_directory(reason, path, []),
+        (false, measure) => {
+            let bytes = if measure { len() } else { 0 };
+            report.record_skipped_file(reason, path, bytes);
+            SkipTotals { files: 1, bytes }
+        }
+    };
+    if let Some(template) = config.skip_template(directory, path) {
+        report.record_template_skip(template, is_dir, totals.files, totals.bytes);
+    }
+}
+
+/// Состояние параллельного обхода одной базовой папки.
+struct ParallelWalk<'a> {
+    config: &'a FlattenConfig,
+    directory: &'a Path,
+    report: Mutex<&'a mut FlattenReport>,
+    on_file: &'a (dyn Fn(&TreeEntry) + Sync),
+}
+
+impl ParallelWalk<'_> {
+    /// Обходит содержимое директории `dir`, чьи потомки имеют глубину `depth`.
+    ///
+    /// Потомки обрабатываются параллельно, но возвращаются в порядке
+    /// `read_dir`, каждая поддиректория — сразу со своим содержимым, как у `WalkDir`.
+    fn children(&self, dir: &Path, depth: usize) -> Result<Vec<TreeEntry>> {
+        let listed = std::fs::read_dir(dir)
+            .and_then(|entries| entries.collect::<std::io::Result<Vec<_>>>())
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+        let subtrees = listed
+            .into_par_iter()
+            .map(|entry| -> Result<Vec<TreeEntry>> {
+                let path = entry.path();
+                let file_type = match entry.file_type() {
+                    Ok(file_type) => file_type,
+                    Err(error) if error.kind() == std::io::ErrorKind::InvalidFilename => {
+                        let (kind, message) = walk_warning(&path, &error);
+                        let mut report = self.report.lock().unwrap_or_else(PoisonError::into_inner);
+                        report.warn(kind, message);
+                        return Ok(Vec::new());
+                    }
+                    Err(error) => {
+                        return Err(error)
+                            .with_context(|| format!("Failed to read file type: {}", path.display()));
+                    }
+                };
+                let (tree_entry, skipped) =
+                    visit_entry(self.config, self.directory, &path, depth, file_type, || {
+                        entry.metadata().ok()
+                    });
+                if let Some(reason) = skipped {
+                    let mut report = self.report.lock().unwrap_or_else(PoisonError::into_inner);
+                    record_walk_skip(
+                        &mut report,
+                        self.config,
+                        self.directory,
+                        &path,
+                        file_type.is_dir(),
+                        reason,
+                        || entry.metadata().map(|m| m.len()).unwrap_or(0),
+                    );
+                }
+
+                let Some(tree_entry) = tree_entry else {
+                    return Ok(Vec::new());
+                };
+                let mut descend = tree_entry.is_dir
+                    && skipped.is_none()
+                    && (self.config.max_depth == 0 || depth < self.config.max_depth);
+                if descend && at_depth_ceiling(self.config, depth) {
+                    let mut report = self.report.lock().unwrap_or_else(PoisonError::into_inner);
+                    report.warn(WarningKind::DepthCeiling, depth_ceiling_message(&path, self.config));
+                    descend = false;
+                }
+                if !tree_entry.is_dir {
+                    (self.on_file)(&tree_entry);
+                }
+                let mut entries = vec![tree_entry];
+                if descend {
+                    match self.children(&path, depth + 1) {
+                        Ok(children) => entries.extend(children),
+                        // Недоступная вложенная директория пропускается с предупреждением.
+                        Err(error) => {
+                            let mut report =
+                                self.report.lock().unwrap_or_else(PoisonError::into_inner);
+                            let (kind, message) = match error.downcast_ref::<std::io::Error>() {
+                                Some(io_error) => walk_warning(&path, io_error),
+                                None => (
+                                    WarningKind::UnreadableDirectory,
+                                    unreadable_message(&path, error.root_cause()),
+                                ),
+                            };
+                            report.warn(kind, message);
+                        }
+                    }
+                }
+                Ok(entries)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(subtrees.into_iter().flatten().collect())
+    }
+}
+
+/// Сообщение о директории, которую не удалось прочитать при обходе.
+fn unreadable_message(path: &Path, error: impl std::fmt::Display) -> String {
+    format!("Cannot read directory {}: {}", path.display(), error)
+}
+
+/// Категория и текст предупреждения об ошибке обхода: слишком длинный путь
+/// выделяется отдельно от прочих ошибок чтения директории.
+fn walk_warning(path: &Path, error: &std::io::Error) -> (WarningKind, String) {
+    if error.kind() == std::io::ErrorKind::InvalidFilename {
+        let message = format!("Path too long, skipped: {}", path.display());
+        (WarningKind::PathTooLong, message)
+    } else {
+        (WarningKind::UnreadableDirectory, unreadable_message(path, error))
+    }
+}
+
+/// Вызвана ли ошибка слишком длинным путем или именем файла (`ENAMETOOLONG`).
+pub(crate) fn is_path_too_long(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|io_error| io_error.kind() == std::io::ErrorKind::InvalidFilename)
+    })
+}
+
+/// Останавливается ли обход на директории глубины `depth` (`--depth-ceiling`).
+///
+/// `--max-depth` не глубже потолка останавливает обход раньше и без предупреждения.
+fn at_depth_ceiling(config: &FlattenConfig, depth: usize) -> bool {
+    config.depth_ceiling > 0
+        && depth >= config.depth_ceiling
+        && (config.max_depth == 0 || depth < config.max_depth)
+}
+
+/// Предупреждение о директории, в которую обход не спускается из-за `--depth-ceiling`.
+fn depth_ceiling_message(path: &Path, config: &FlattenConfig) -> String {
+    format!(
+        "Directory {} is {} levels deep, its contents are skipped (raise --depth-ceiling to descend)",
+        path.display(),
+        config.depth_ceiling
+    )
+}
+
+/// Рекурсивно собирает файлы базовой папки `directory`, учитывая конфигурацию.
+///
+/// Файлы, содержимое которых будет заменено заглушкой, тоже возвращаются,
+/// с заполненным `skip_reason`. Если нужно и дерево, используйте `DirTree`,
+/// чтобы не обходить папку дважды.
+///
+/// ```no_run
+/// # use clap::Parser;
+/// # use flatten_rust::{Args, FlattenConfig};
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let config = FlattenConfig::new(&Args::parse_from(["flatten-rust", "-f", "."])).await?;
+/// let entries = flatten_rust::pipeline::collect_files(std::path::Path::new("."), &config)?;
+/// let total: u64 = entries.iter().map(|entry| entry.size).sum();
+/// # Ok(())
+/// # }
+/// ```
+pub fn collect_files(directory: &Path, config: &FlattenConfig) -> Result<Vec<FileEntry>> {
+    Ok(DirTree::walk(directory, config)?
+        .files()
+        .map(|entry| FileEntry {
+            path: entry.path.clone(),
+            size: file_size(&entry.path, config.file_timeout),
+            skip_reason: entry.skip_reason,
+        })
+        .collect())
+}
+
+/// Возвращает количество файлов и их суммарный размер внутри директории.
+pub(crate) fn directory_usage(directory: &Path) -> (usize, u64) {
+    directory_files(directory).fold((0, 0), |(files, bytes), (_, size)| (files + 1, bytes + size))
+}
+
+/// Перечисляет файлы внутри директории с их размерами.
+fn directory_files(directory: &Path) -> impl Iterator<Item = (PathBuf, u64)> {
+    WalkDir::new(directory)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|entry| {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            (entry.into_path(), size)
+        })
+}
+
+/// Выводит в `writer` строки дерева директории без обрамляющих маркеров.
+///
+/// Обходит папку заново; `DirTree::render` выводит то же дерево по готовому обходу.
+///
+/// ```no_run
+/// # use clap::Parser;
+/// # use flatten_rust::{Args, FlattenConfig};
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let config = FlattenConfig::new(&Args::parse_from(["flatten-rust", "-f", "."])).await?;
+/// let mut tree = Vec::new();
+/// flatten_rust::pipeline::render_tree(std::path::Path::new("."), &config, &mut tree)?;
+/// print!("{}", String::from_utf8(tree)?);
+/// # Ok(())
+/// # }
+/// ```
+pub fn render_tree<W: Write + ?Sized>(
+    directory: &Path,
+    config: &FlattenConfig,
+    writer: &mut W,
+) -> Result<()> {
+    DirTree::walk(directory, config)?.render(config, writer)
+}
+
+/// Читает файл так, как он попадет в вывод.
+///
+/// Применяет конвертеры, лимит размера, `--skip-generated` и нормализацию текста.
+/// Исключения по имени и расширению не проверяются — для этого служит
+/// `FileEntry::skip_reason`.
+///
+/// С `options.timeout` файл читается в отдельном потоке; если он не прочитан
+/// вовремя, возвращается ошибка `timed out after Ns`. Отображенный в память
+/// текст проверяется на UTF-8 в том же потоке, поэтому к возврату его
+/// страницы уже загружены и запись документа не зависнет на них.
+///
+/// ```no_run
+/// # use clap::Parser;
+/// # use flatten_rust::{Args, FlattenConfig};
+/// use flatten_rust::pipeline::{ReadOptions, read_file};
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// let config = FlattenConfig::new(&Args::parse_from(["flatten-rust", "-f", "."])).await?;
+/// let content = read_file(std::path::Path::new("src/main.rs"), &ReadOptions::from_config(&config))?;
+/// assert!(content.skip_reason.is_none());
+/// # Ok(())
+/// # }
+/// ```
+pub fn read_file(path: &Path, options: &ReadOptions) -> Result<FileContent> {
+    read_file_sized(path, None, options)
+}
+
+/// Читает файл, как `read_file`, с размером `size`, уже известным по метаданным
+/// обхода (`None` — размер запрашивается у открытого файла).
+pub(crate) fn read_file_sized(
+    path: &Path,
+    size: Option<u64>,
+    options: &ReadOptions,
+) -> Result<FileContent> {
+    let abridge = options.abridge(path);
+    let timeout = match options.deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                bail!("time limit reached");
+            }
+            Some(options.timeout.map_or(remaining, |timeout| timeout.min(remaining)))
+        }
+        None => options.timeout,
+    };
+    if timeout.is_none() {
+        return read_file_now(path, size, options, abridge);
+    }
+    let path = path.to_path_buf();
+    let converters = options.converters.only_for(&path);
+    let transforms = options.transforms.clone();
+    let (max_file_size, chunking) = (options.max_file_size, options.chunking);
+    let (lfs_include_pointers, lfs_resolve) = (options.lfs_include_pointers, options.lfs_resolve);
+    let mmap_threshold = options.mmap_threshold;
+    let content_cache = options.content_cache.cloned();
+    let messages = options.messages.clone();
+    with_timeout(timeout, move || {
+        let options = ReadOptions {
+            max_file_size,
+            transforms: &transforms,
+            converters: &converters,
+            timeout: None,
+            deadline: None,
+            chunking,
+            lfs_include_pointers,
+            lfs_resolve,
+            outline: None,
+            head_lines: 0,
+            mmap_threshold,
+            content_cache: content_cache.as_ref(),
+            messages: &messages,
+        };
+        read_file_now(&path, size, &options, abridge)
+    })
+}
+
+/// Читает файл в текущем потоке (см. `read_file`) и сокращает его по `abridge`.
+fn read_file_now(
+    path: &Path,
+    size: Option<u64>,
+    options: &ReadOptions,
+    abridge: Abridge,
+) -> Result<FileContent> {
+    let cached = match options.content_cache {
+        Some(cache) => content_cache_key(path, size, options, abridge)?
+            .map(|(key, size)| (cache, key, size)),
+        None => None,
+    };
+    if let Some((cache, key, size)) = &cached
+        && let Some(content) = cache.get(key).and_then(|cached| {
+            cached.into_content(
+                *size,
+                |name| cached_converter(path, options, name),
+                |name| options.transforms.find(name),
+            )
+        })
+    {
+        return Ok(content);
+    }
+
+    let mut file_content = read_file_content_lfs(path, size, options)?;
+    let context = TransformContext {
+        abridge,
+        messages: options.messages,
+    };
+    options.transforms.apply(&mut file_content, &context);
+    // Фрагменты режутся по окончательному тексту, после всех преобразований.
+    if file_content.skip_reason.is_none()
+        && let Some(chunking) = options.chunking
+    {
+        file_content.chunks = chunking.split(&file_content.content);
+    }
+    if let Some((cache, key, _)) = cached {
+        cache.put(&key, &CachedContent::from_content(&file_content));
+    }
+    Ok(file_content)
+}
+
+/// Ключ кэша содержимого для файла `path` и его размер.
+///
+/// `None` — файл обрабатывается без кэша: потоковый конвертер, файл больше
+/// `--max-file-size` или указатель Git LFS, содержимое которого зависит не
+/// только от самого файла.
+fn content_cache_key(
+    path: &Path,
+    size: Option<u64>,
+    options: &ReadOptions,
+    abridge: Abridge,
+) -> Result<Option<(String, u64)>> {
+    let converter = options.converters.find(path);
+    let too_large = |size: u64| options.max_file_size > 0 && size > options.max_file_size;
+    if converter.is_some_and(|converter| converter.is_streaming()) || size.is_some_and(too_large)
+    {
+        return Ok(None);
+    }
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+    let size = bytes.len() as u64;
+    if too_large(size)
+        || (size <= git_info::LFS_POINTER_MAX_SIZE && LfsPointer::parse(&bytes).is_some())
+    {
+        return Ok(None);
+    }
+    // Отпечаток включает все параметры, от которых зависит результат обработки.
+    let fingerprint = format!(
+        "{} {} {:?} {:?} {:?} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        options.max_file_size,
+        options.transforms,
+        options.chunking,
+        converter,
+        abridge,
+        options.messages
+    );
+    Ok(Some((ContentCache::key(&bytes, &fingerprint), size)))
+}
+
+/// Сопоставляет имя конвертера из кэша с конвертером или сокращением файла `path`.
+fn cached_converter(path: &Path, options: &ReadOptions, name: &str) -> Option<&'static str> {
+    options
+        .converters
+        .find(path)
+        .map(|converter| converter.name())
+        .into_iter()
+        .chain([Abridge::OUTLINE, Abridge::HEAD])
+        .find(|candidate| *candidate == name)
+}
+
+/// Читает файл, распознавая указатели Git LFS.
+///
+/// Указатель заменяется заглушкой (или выводится как есть с
+/// `--lfs-include-pointers`); с `--lfs-resolve` вместо него читается
+/// скачанный объект с обычными ограничениями размера и конвертерами по
+/// имени указателя.
+fn read_file_content_lfs(
+    path: &Path,
+    size: Option<u64>,
+    options: &ReadOptions,
+) -> Result<FileContent> {
+    let (file, file_size) = open_file(path, size)?;
+    let pointer = if file_size <= git_info::LFS_POINTER_MAX_SIZE {
+        let mut head = Vec::new();
+        (&file)
+            .read_to_end(&mut head)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        (&file)
+            .seek(SeekFrom::Start(0))
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        LfsPointer::parse(&head)
+    } else {
+        None
+    };
+    let Some(pointer) = pointer else {
+        return read_file_content_fast(path, file, file_size, options);
+    };
+
+    if options.lfs_resolve
+        && let Some(object) = pointer.local_object(path)
+    {
+        let (object_file, object_size) = open_file(&object, None)?;
+        let mut content = read_file_content_fast(path, object_file, object_size, options)?;
+        content.lfs = Some(LfsState::Resolved);
+        return Ok(content);
+    }
+    let mut content = if options.lfs_include_pointers {
+        read_file_content_fast(path, file, file_size, options)?
+    } else {
+        FileContent {
+            content: pointer.placeholder(options.messages).into(),
+            original_size: file_size,
+            read_bytes: file_size,
+            converter: None,
+            skip_reason: Some(SkipReason::LfsPointer),
+            line_endings: None,
+            chunks: Vec::new(),
+            lfs: None,
+            stale: false,
+            sanitized: None,
+            transforms: Vec::new(),
+            mode: None,
+        }
+    };
+    content.lfs = Some(LfsState::Pointer);
+    Ok(content)
+}
+
+/// Открывает файл и возвращает его вместе с размером; известный размер `size`
+/// не запрашивается повторно.
+fn open_file(path: &Path, size: Option<u64>) -> Result<(File, u64)> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    if let Some(size) = size {
+        return Ok((file, size));
+    }
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("Failed to read metadata: {}", path.display()))?;
+    Ok((file, metadata.len()))
+}
+
+/// Эффективно читает содержимое открытого файла `path`, используя memory-mapping.
+///
+/// Если для расширения файла зарегистрирован конвертер, "сырые" байты
+/// передаются ему вместо декодирования в UTF-8. Потоковые конвертеры
+/// получают открытый файл до проверки размера и без memory-mapping. Если
+/// конвертер отказался от файла, он читается как обычный.
+fn read_file_content_fast(
+    path: &Path,
+    file: File,
+    file_size: u64,
+    options: &ReadOptions,
+) -> Result<FileContent> {
+    let max_size = options.max_file_size;
+    let converter = options.converters.find(path);
+
+    if let Some(converter) = converter
+        && converter.is_streaming()
+    {
+        let mut reader = BufReader::new(&file);
+        let source = ConverterSource::Reader {
+            reader: &mut reader,
+            len: file_size,
+        };
+        let conversion = run_converter(converter.as_ref(), path, source)?;
+        if let Some(content) = converted_content(converter.as_ref(), conversion, file_size) {
+            return Ok(content);
+        }
+    }
+
+    if max_size > 0 && file_size > max_size {
+        return Ok(FileContent {
+            content: options
+                .messages
+                .format(Message::FileTooLarge, &[("bytes", &file_size)])
+                .into(),
+            original_size: file_size,
+            read_bytes: 0,
+            converter: None,
+            skip_reason: Some(SkipReason::TooLarge),
+            line_endings: None,
+            chunks: Vec::new(),
+            lfs: None,
+            stale: false,
+            sanitized: None,
+            transforms: Vec::new(),
+            mode: None,
+        });
+    }
+    if file_size == 0 {
+        return Ok(FileContent {
+            content: String::new().into(),
+            original_size: 0,
+            read_bytes: 0,
+            converter: None,
+            skip_reason: None,
+            line_endings: None,
+            chunks: Vec::new(),
+            lfs: None,
+            stale: false,
+            sanitized: None,
+            transforms: Vec::new(),
+            mode: None,
+        });
+    }
+
+    let converter = converter.filter(|converter| !converter.is_streaming());
+    if file_size < options.mmap_threshold {
+        return READ_BUFFER.with_borrow_mut(|buffer| {
+            buffer.clear();
+            (&file)
+                .read_to_end(buffer)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?;
+            let content = match convert_bytes(converter, path, buffer, file_size)? {
+                Some(content) => content,
+                None => FileContent::read(ContentText::from_bytes(buffer), file_size),
+            };
+            // Буфер не удерживает память сверх порога после файла, выросшего после обхода.
+            buffer.shrink_to(options.mmap_threshold as usize);
+            Ok(content)
+        });
+    }
+
+    // SAFETY: The file is mapped read-only. The map owns its pages independently of
+    // the file handle, so it may outlive this function inside `ContentText` until the
+    // content is written; `ContentText::recheck_mapped` checks the file's length and
+    // the text again right before that.
+    let mmap = unsafe {
+        MmapOptions::new()
+            .map(&file)
+            .with_context(|| format!("Failed to memory map file: {}", path.display()))?
+    };
+    if let Some(content) = convert_bytes(converter, path, &mmap, file_size)? {
+        return Ok(content);
+    }
+    Ok(FileContent::read(ContentText::from_mmap(mmap), file_size))
+}
+
+/// Передает прочитанные байты конвертеру, если он есть; `None` — файл
+/// выводится как обычный текст.
+fn convert_bytes(
+    converter: Option<&Arc<dyn FileConverter>>,
+    path: &Path,
+    bytes: &[u8],
+    file_size: u64,
+) -> Result<Option<FileContent>> {
+    let Some(converter) = converter else {
+        return Ok(None);
+    };
+    let conversion = run_converter(converter.as_ref(), path, ConverterSource::Bytes(bytes))?;
+    Ok(converted_content(converter.as_ref(), conversion, file_size))
+}
+
+/// Вызывает конвертер, превращая его панику в ошибку чтения файла.
+fn run_converter(
+    converter: &dyn FileConverter,
+    path: &Path,
+    source: ConverterSource<'_>,
+) -> Result<Conversion> {
+    panic::catch_unwind(AssertUnwindSafe(|| converter.convert(path, source))).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        anyhow!(
+            "Converter {} panicked on {}: {}",
+            converter.name(),
+            path.display(),
+            message
+        )
+    })
+}
+
+/// Собирает содержимое файла из результата конвертера (`None` — конвертер отказался).
+fn converted_content(
+    converter: &dyn FileConverter,
+    conversion: Conversion,
+    file_size: u64,
+) -> Option<FileContent> {
+    let (content, skip_reason) = match conversion {
+        Conversion::Text(text) => (text, None),
+        Conversion::Placeholder(text) => (text, Some(SkipReason::Converter)),
+        Conversion::FallThrough => return None,
+    };
+    Some(FileContent {
+        content: content.into(),
+        original_size: file_size,
+        read_bytes: file_size,
+        converter: Some(converter.name()),
+        skip_reason,
+        line_endings: None,
+        chunks: Vec::new(),
+        lfs: None,
+        stale: false,
+        sanitized: None,
+        transforms: Vec::new(),
+        mode: None,
+    })
+}
+
+/// Задача для потока чтения `with_timeout`.
+type ReaderJob = Box<dyn FnOnce() + Send>;
+
+/// Свободные потоки чтения. Поток, на котором истекло ожидание, сюда не
+/// возвращается: он завершится сам, когда зависший вызов вернется.
+static IDLE_READERS: Mutex<Vec<mpsc::Sender<ReaderJob>>> = Mutex::new(Vec::new());
+
+/// Выполняет `task` в потоке чтения и ждет результат не дольше `timeout`.
+///
+/// Зависший системный вызов (например, на недоступной NFS- или FUSE-точке)
+/// прервать переносимо нельзя, поэтому по истечении времени поток не
+/// ожидается, а оставляется. Успевшие потоки переиспользуются, чтобы не
+/// создавать поток на каждый файл. Без `timeout` задача выполняется в
+/// текущем потоке.
+fn with_timeout<T: Send + 'static>(
+    timeout: Option<Duration>,
+    task: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return task();
+    };
+    let idle = IDLE_READERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pop();
+    let reader = match idle {
+        Some(reader) => reader,
+        None => spawn_reader()?,
+    };
+    let (sender, receiver) = mpsc::sync_channel(1);
+    reader
+        .send(Box::new(move || {
+            // Если ожидание уже истекло, результат никому не нужен.
+            let _ = sender.send(task());
+        }))
+        .map_err(|_| anyhow::anyhow!("File reader thread exited unexpectedly"))?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => {
+            IDLE_READERS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(reader);
+            result
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            Err(anyhow::anyhow!("timed out after {}s", timeout.as_secs()))
+        }
+        Err(RecvTimeoutError::Disconnected) => Err(anyhow::anyhow!("File reader thread panicked")),
+    }
+}
+
+/// Запускает поток чтения, выполняющий задачи до закрытия канала.
+fn spawn_reader() -> Result<mpsc::Sender<ReaderJob>> {
+    let (jobs, receiver) = mpsc::channel::<ReaderJob>();
+    std::thread::Builder::new()
+        .name("flatten-reader".to_string())
+        .spawn(move || {
+            for job in receiver {
+                job();
+            }
+        })
+        .context("Failed to spawn file reader thread")?;
+    Ok(jobs)
+}
+
+/// Возвращает размер файла по метаданным (0, если их не удалось получить вовремя).
+fn file_size(path: &Path, timeout: Option<Duration>) -> u64 {
+    file_stamp(path, timeout).map_or(0, |(size, _)| size)
+}
+
+/// Возвращает размер и время изменения файла (`None`, если метаданные не удалось
+/// получить вовремя).
+fn file_stamp(path: &Path, timeout: Option<Duration>) -> Option<(u64, Option<SystemTime>)> {
+    let path = path.to_path_buf();
+    with_timeout(timeout, move || {
+        let metadata = std::fs::metadata(&path)?;
+        Ok((metadata.len(), metadata.modified().ok()))
+    })
+    .ok()
+}
+
+/// Возвращает размер файла дерева, если его содержимое попадет в вывод, и
+/// `None` для файла, который заменяется заглушкой (см. `DirTree::dir_totals`).
+fn emitted_size(entry: &TreeEntry, config: &FlattenConfig) -> Option<u64> {
+    let file = PlannedFile::new(entry, config);
+    let generated = || {
+        config.skip_generated
+            && config.converters.find(&entry.path).is_none()
+            && starts_generated(&entry.path, config.file_timeout)
+    };
+    (!file.placeholder && !generated()).then_some(file.original_size)
+}
+
+/// Есть ли маркер генерации в начале файла. Читается не больше
+/// `GENERATED_HEAD_BYTES` байт; непрочитанный файл считается обычным.
+fn starts_generated(path: &Path, timeout: Option<Duration>) -> bool {
+    let path = path.to_path_buf();
+    with_timeout(timeout, move || {
+        let mut head = Vec::new();
+        File::open(&path)?
+            .take(GENERATED_HEAD_BYTES)
+            .read_to_end(&mut head)?;
+        Ok(is_generated(&String::from_utf8_lossy(&head)))
+    })
+    .unwrap_or(false)
+}
+
+/// Проверяет, есть ли в первых строках текста маркер сгенерированного файла.
+pub(crate) fn is_generated(content: &str) -> bool {
+    content
+        .lines()
+        .take(GENERATED_MARKER_LINES)
+        .any(|line| GENERATED_MARKERS.iter().any(|marker| line.contains(marker)))
+}
+
+/// Файл базовой папки и результат его обработки.
+pub(crate) type ProcessedFile = (PathBuf, Result<FileContent>);
+
+/// Прочитанные заранее файлы по путям.
+pub(crate) type ReadyFiles = HashMap<PathBuf, Result<FileContent>>;
+
+/// Сведения о файле, известные до его чтения.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct PlannedFile {
+    /// Размер файла на диске.
+    pub(crate) original_size: u64,
+    /// Будет ли файл заменен заглушкой без чтения (бинарный, lock-файл, слишком большой).
+    pub(crate) placeholder: bool,
+    /// Сколько байт файл занимает в бюджете `--max-in-flight-bytes` (заглушки — 0).
+    pub(crate) budget: u64,
+    /// Время изменения файла на момент сбора (`None` — не известно).
+    pub(crate) modified: Option<SystemTime>,
+}
+
+impl PlannedFile {
+    /// Определяет размеры файла по метаданным, не читая его.
+    pub(crate) fn new(entry: &TreeEntry, config: &FlattenConfig) -> Self {
+        let (size, modified) = match entry.size {
+            Some(size) => (size, entry.modified),
+            None => file_stamp(&entry.path, config.file_timeout).unwrap_or_default(),
+        };
+        let streaming = config
+            .converters
+            .find(&entry.path)
+            .is_some_and(|converter| converter.is_streaming());
+        let too_large = !streaming && config.max_file_size > 0 && size > config.max_file_size;
+        let placeholder = entry.skip_reason.is_some() || too_large;
+        Self {
+            original_size: size,
+            placeholder,
+            budget: if placeholder { 0 } else { size },
+            modified,
+        }
+    }
+
+    /// Изменился ли файл после сбора: размер или время изменения на диске
+    /// отличаются от запомненных. Файл, метаданные которого не удалось
+    /// получить, считается неизмененным.
+    fn changed(&self, path: &Path, timeout: Option<Duration>) -> bool {
+        file_stamp(path, timeout).is_some_and(|stamp| stamp != (self.original_size, self.modified))
+    }
+}
+
+/// Обрабатывает файлы параллельно, передавая результаты в `write` строго в порядке `files`.
+///
+/// Файл начинает читаться, только если его бюджет помещается в
+/// `config.max_in_flight_bytes` вместе с прочитанными, но еще не записанными
+/// файлами; бюджет освобождается после `write`, если тот не вернул `true`
+/// (результат остался у вызывающего кода). Файлы допускаются к чтению
+/// в порядке записи, а файл, который пишется следующим, допускается всегда,
+/// поэтому файл больше всего бюджета обрабатывается в одиночку. Результаты
+/// из `ready` (прочитанные во время параллельного обхода) уже занимают бюджет.
+/// Перед записью размер и время изменения файла сверяются с запомненными при
+/// сборе, и содержимое изменившегося файла помечается как возможно устаревшее
+/// (с `--recheck` файл сначала перечитывается).
+///
+/// После истечения `--time-limit` файлы больше не начинают читаться и не
+/// передаются в `write`; начатые дочитываются и записываются, а с
+/// `--time-limit-hard` их результат отбрасывается.
+///
+/// Возвращает наибольший объем одновременно удерживаемых байт и индексы
+/// файлов, не обработанных из-за лимита времени.
+pub(crate) fn process_files_windowed(
+    base_folder: &Path,
+    files: &[(PathBuf, PlannedFile)],
+    mut ready: HashMap<PathBuf, Result<FileContent>>,
+    config: &FlattenConfig,
+    progress: &dyn ProgressSink,
+    mut write: impl FnMut(usize, &Path, Result<FileContent>) -> Result<bool>,
+) -> Result<(u64, Vec<usize>)> {
+    let limit = match config.max_in_flight_bytes {
+        0 => u64::MAX,
+        limit => limit,
+    };
+    let options = ReadOptions::from_config(config);
+    let mut pending: Vec<Option<Result<FileContent>>> =
+        files.iter().map(|(path, _)| ready.remove(path)).collect();
+    let mut admitted: Vec<bool> = pending.iter().map(Option::is_some).collect();
+    let mut unprocessed = vec![false; files.len()];
+    let time_limit = config.time_limit.as_deref();
+    let mut in_flight: u64 = files
+        .iter()
+        .zip(&admitted)
+        .filter(|(_, admitted)| **admitted)
+        .map(|((_, planned), _)| planned.budget)
+        .sum();
+    let mut peak = in_flight;
+    let (sender, receiver) = mpsc::channel();
+
+    rayon::in_place_scope(|scope| -> Result<()> {
+        let mut next_admit = 0;
+        for next_write in 0..files.len() {
+            while next_admit < files.len() {
+                let budget = files[next_admit].1.budget;
+                if !admitted[next_admit] && time_limit.is_some_and(TimeLimit::is_reached) {
+                    admitted[next_admit] = true;
+                    unprocessed[next_admit] = true;
+                } else if !admitted[next_admit] {
+                    if next_admit != next_write && in_flight.saturating_add(budget) > limit {
+                        break;
+                    }
+                    admitted[next_admit] = true;
+                    in_flight += budget;
+                    peak = peak.max(in_flight);
+
+                    let (index, (path, planned)) = (next_admit, &files[next_admit]);
+                    let (sender, options) = (sender.clone(), &options);
+                    let size = Some(planned.original_size);
+                    scope.spawn(move |_| {
+                        // Лимит времени сверяется перед чтением и после него.
+                        let result = match time_limit {
+                            Some(time_limit) if time_limit.check() => None,
+                            _ => Some(process_file(
+                                base_folder, path, size, config, options, progress,
+                            )),
+                        };
+                        let result = match time_limit {
+                            Some(time_limit) if time_limit.check() && time_limit.is_hard() => None,
+                            _ => result,
+                        };
+                        // Получатель живет до конца области, ошибка отправки невозможна.
+                        let _ = sender.send((index, result));
+                    });
+                }
+                next_admit += 1;
+            }
+
+            while pending[next_write].is_none() && !unprocessed[next_write] {
+                let (index, result) = receiver
+                    .recv()
+                    .context("File processing worker exited unexpectedly")?;
+                match result {
+                    Some(result) => pending[index] = Some(result),
+                    None => {
+                        unprocessed[index] = true;
+                        in_flight -= files[index].1.budget;
+                    }
+                }
+            }
+            if unprocessed[next_write] {
+                continue;
+            }
+            let (path, planned) = &files[next_write];
+            let mut result = pending[next_write]
+                .take()
+                .expect("result was received above");
+            if !planned.placeholder && result.is_ok() && planned.changed(path, config.file_timeout)
+            {
+                result = recheck_changed(path, result, config, &options);
+            }
+            if config.file_modes
+                && let Ok(content) = &mut result
+            {
+                content.mode = FileMode::of(base_folder, path);
+            }
+            if !write(next_write, path, result)? {
+                in_flight -= planned.budget;
+            }
+        }
+        Ok(())
+    })?;
+    let unprocessed = (0..files.len()).filter(|&index| unprocessed[index]).collect();
+    Ok((peak, unprocessed))
+}
+
+/// Помечает содержимое файла, изменившегося после сбора, как возможно устаревшее.
+///
+/// С `--recheck` файл один раз перечитывается; если за время повторного
+/// чтения он больше не менялся, новое содержимое выводится без пометки.
+fn recheck_changed(
+    path: &Path,
+    result: Result<FileContent>,
+    config: &FlattenConfig,
+    options: &ReadOptions,
+) -> Result<FileContent> {
+    let mut result = result;
+    let stamp = config.recheck.then(|| file_stamp(path, config.file_timeout)).flatten();
+    if let Some(stamp) = stamp {
+        let reread = read_file_sized(path, Some(stamp.0), options);
+        if reread.is_ok() {
+            let unchanged = file_stamp(path, config.file_timeout) == Some(stamp);
+            result = reread;
+            if unchanged {
+                return result;
+            }
+        }
+    }
+    result.map(|content| FileContent {
+        stale: true,
+        ..content
+    })
+}
+
+/// Оставляет в дереве только файлы, содержимое которых проходит `--grep`.
+///
+/// Дерево структуры выводится до содержимого, поэтому файлы читаются заранее
+/// тем же окном `--max-in-flight-bytes`, что и при выводе; результаты из
+/// `ready` повторно не читаются. Содержимое оставшихся файлов, пока оно
+/// помещается в бюджет, возвращается для записи без повторного чтения, а
+/// остальные оставшиеся файлы перечитываются при выводе. Отброшенные файлы
+/// учитываются в `report`, директории без оставшихся файлов убираются из
+/// дерева. Файлы, не прочитанные из-за `--time-limit`, остаются в дереве.
+/// О начале чтения сообщается в `progress`.
+///
+/// Возвращает пути оставшихся файлов и сохраненное содержимое.
+pub(crate) fn grep_tree(
+    base_folder: &Path,
+    tree: &mut DirTree,
+    ready: ReadyFiles,
+    filter: &ContentFilter,
+    config: &FlattenConfig,
+    report: &mut FlattenReport,
+    progress: &dyn ProgressSink,
+) -> Result<(HashSet<PathBuf>, ReadyFiles)> {
+    // Заглушки, известные до чтения, проверить нечем.
+    let candidates: Vec<(PathBuf, PlannedFile)> = tree
+        .files()
+        .filter(|entry| entry.skip_reason.is_none())
+        .map(|entry| (entry.path.clone(), PlannedFile::new(entry, config)))
+        .collect();
+    progress.on_start(candidates.len());
+    let limit = match config.max_in_flight_bytes {
+        0 => u64::MAX,
+        limit => limit,
+    };
+    // Сохраненное содержимое не должно мешать дочитать любой файл в бюджете.
+    let reserve = candidates.iter().map(|(_, file)| file.budget).max().unwrap_or(0);
+    let mut kept = HashSet::new();
+    let mut retained = HashMap::new();
+    let mut held: u64 = 0;
+    let (peak, unprocessed) = process_files_windowed(
+        base_folder,
+        &candidates,
+        ready,
+        config,
+        progress,
+        |index, path, result| {
+            if !filter.accepts(&result) {
+                return Ok(false);
+            }
+            kept.insert(path.to_path_buf());
+            // Сверх бюджета файл перечитывается при выводе.
+            let budget = candidates[index].1.budget;
+            if held.saturating_add(budget).saturating_add(reserve) > limit {
+                return Ok(false);
+            }
+            held += budget;
+            retained.insert(path.to_path_buf(), result);
+            Ok(true)
+        },
+    )?;
+    report.peak_in_flight_bytes = report.peak_in_flight_bytes.max(peak);
+    kept.extend(unprocessed.into_iter().map(|index| candidates[index].0.clone()));
+
+    let mut directories = HashSet::new();
+    for path in &kept {
+        directories.extend(
+            path.ancestors()
+                .skip(1)
+                .take_while(|directory| *directory != base_folder),
+        );
+    }
+    let directories: HashSet<PathBuf> = directories.into_iter().map(Path::to_path_buf).collect();
+    tree.entries.retain(|entry| {
+        let keep = if entry.is_dir {
+            directories.contains(&entry.path)
+        } else {
+            kept.contains(&entry.path)
+        };
+        if !keep && !entry.is_dir {
+            report.record_skipped_file(SkipReason::Grep, &entry.path, entry.size.unwrap_or(0));
+        }
+        keep
+    });
+    Ok((kept, retained))
+}
+
+/// Оставляет в каждой директории не больше `--dir-file-limit` включенных файлов.
+///
+/// Первые по пути файлы остаются, а остальные убираются из дерева и
+/// учитываются в `report`; вместо них в конце директории появляется строка
+/// `<директория>/…` со сводкой в `tree.omitted`. Файлы `--keep` и заглушки
+/// не ограничиваются и в лимите не считаются. Возвращает содержимое секций
+/// сводок по путям их строк.
+pub(crate) fn limit_dir_files(
+    tree: &mut DirTree,
+    config: &FlattenConfig,
+    report: &mut FlattenReport,
+) -> HashMap<PathBuf, FileContent> {
+    let limit = config.dir_file_limit;
+    let mut by_directory: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for (index, entry) in tree.entries.iter().enumerate() {
+        if !entry.is_dir
+            && entry.skip_reason.is_none()
+            && !config.is_kept(&tree.root, &entry.path)
+            && let Some(directory) = entry.path.parent()
+        {
+            by_directory.entry(directory).or_default().push(index);
+        }
+    }
+
+    let mut omitted = HashSet::new();
+    // Строки сводок по индексу последнего элемента их директории.
+    let mut summaries: HashMap<usize, Vec<(TreeEntry, OmittedFiles)>> = HashMap::new();
+    for (directory, mut files) in by_directory {
+        if files.len() <= limit {
+            continue;
+        }
+        files.sort_by(|a, b| tree.entries[*a].path.cmp(&tree.entries[*b].path));
+        let rest = files.split_off(limit);
+        let bytes = rest
+            .iter()
+            .map(|&index| file_size(&tree.entries[index].path, config.file_timeout))
+            .sum();
+        // Поддерево директории идет в обходе сразу за ней.
+        let (depth, last) = match tree.entries.iter().position(|entry| entry.path == directory) {
+            Some(start) => {
+                let depth = tree.entries[start].depth;
+                let end = tree.entries[start + 1..]
+                    .iter()
+                    .position(|entry| entry.depth <= depth)
+                    .map_or(tree.entries.len(), |offset| start + 1 + offset);
+                (depth + 1, end - 1)
+            }
+            None => (1, tree.entries.len() - 1),
+        };
+        let name = match directory.strip_prefix(&tree.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+            _ => PathBuf::from(directory.file_name().unwrap_or(directory.as_os_str())),
+        };
+        let summary = OmittedFiles {
+            directory: format!("{}/", name.display()),
+            files: rest.len(),
+            bytes,
+        };
+        let row = TreeEntry {
+            path: directory.join("…"),
+            depth,
+            is_dir: false,
+            skip_reason: Some(SkipReason::DirFileLimit),
+            size: None,
+            modified: None,
+        };
+        summaries.entry(last).or_default().push((row, summary));
+        omitted.extend(rest);
+    }
+    if omitted.is_empty() {
+        return HashMap::new();
+    }
+
+    let mut contents = HashMap::new();
+    let mut entries = Vec::with_capacity(tree.entries.len() - omitted.len() + summaries.len());
+    for (index, entry) in std::mem::take(&mut tree.entries).into_iter().enumerate() {
+        if omitted.contains(&index) {
+            let size = entry.size.unwrap_or(0);
+            report.record_skipped_file(SkipReason::DirFileLimit, &entry.path, size);
+        } else {
+            entries.push(entry);
+        }
+        let Some(mut rows) = summaries.remove(&index) else {
+            continue;
+        };
+        // Вложенная директория закрывается раньше родительской.
+        rows.sort_by_key(|(row, _)| std::cmp::Reverse(row.depth));
+        for (row, summary) in rows {
+            contents.insert(
+                row.path.clone(),
+                FileContent {
+                    content: format!("… {}", summary.describe(&config.messages)).into(),
+                    original_size: summary.bytes,
+                    read_bytes: 0,
+                    converter: None,
+                    skip_reason: Some(SkipReason::DirFileLimit),
+                    line_endings: None,
+                    chunks: Vec::new(),
+                    lfs: None,
+                    stale: false,
+                    sanitized: None,
+                    transforms: Vec::new(),
+                    mode: None,
+                },
+            );
+            tree.omitted.insert(row.path.clone(), summary);
+            entries.push(row);
+        }
+    }
+    tree.entries = entries;
+    contents
+}
+
+/// Учитывает директории стороннего кода (`--vendored`) в `report`.
+///
+/// С `--vendored summarize` возвращает содержимое секций сводок по путям
+/// директорий; с `skip` секций нет, директории остаются только в дереве.
+pub(crate) fn summarize_vendored(
+    tree: &DirTree,
+    config: &FlattenConfig,
+    report: &mut FlattenReport,
+) -> HashMap<PathBuf, FileContent> {
+    let mut contents = HashMap::new();
+    for entry in &tree.entries {
+        if !entry.is_dir || entry.skip_reason != Some(SkipReason::Vendored) {
+            continue;
+        }
+        let summary = VendoredDirectory::survey(&tree.root, &entry.path);
+        report.record_vendored(summary.files, summary.bytes);
+        if config.vendored == VendoredMode::Summarize {
+            contents.insert(
+                entry.path.clone(),
+                FileContent {
+                    read_bytes: 0,
+                    skip_reason: Some(SkipReason::Vendored),
+                    ..FileContent::read(summary.describe(&config.messages).into(), summary.bytes)
+                },
+            );
+        }
+    }
+    contents
+}
+
+/// Обрабатывает список файлов в параллельном режиме.
+///
+/// О каждом обработанном файле сообщается в `progress` из рабочего потока.
+pub(crate) fn process_files_parallel(
+    base_folder: &Path,
+    files: Vec<PathBuf>,
+    config: &FlattenConfig,
+    progress: &dyn ProgressSink,
+) -> Vec<ProcessedFile> {
+    let options = ReadOptions::from_config(config);
+
+    files
+        .into_par_iter()
+        .map(|file_path| {
+            let result = process_file(base_folder, &file_path, None, config, &options, progress);
+            (file_path, result)
+        })
+        .collect()
+}
+
+/// Обрабатывает один файл базовой папки: читает его или подставляет заглушку.
+///
+/// О прочитанном файле сообщается в `progress` из вызывающего потока.
+/// Заглушки, известные до чтения (`PlannedFile::placeholder`), в число
+/// файлов полосы прогресса не входят: о них сообщается в `on_file_skipped`.
+pub(crate) fn process_file(
+    base_folder: &Path,
+    file_path: &Path,
+    size: Option<u64>,
+    config: &FlattenConfig,
+    options: &ReadOptions,
+    progress: &dyn ProgressSink,
+) -> Result<FileContent> {
+    if let Some(reason) = config.file_skip_reason(base_folder, file_path) {
+        progress.on_file_skipped(file_path, reason);
+        let size = size.unwrap_or_else(|| file_size(file_path, config.file_timeout));
+        let messages = &config.messages;
+        let content = match reason {
+            SkipReason::Lockfile => messages.format(
+                Message::LockfileSkipped,
+                &[("size", &report::format_size(size))],
+            ),
+            SkipReason::Symlink => {
+                let symlink = SymlinkTarget::of(base_folder, file_path).with_context(|| {
+                    format!("Failed to read symlink: {}", file_path.display())
+                })?;
+                let message = if symlink.outside_tree {
+                    Message::SymlinkOutside
+                } else {
+                    Message::Symlink
+                };
+                messages.format(message, &[("target", &symlink.target.display())])
+            }
+            _ => messages.format(Message::BinarySkipped, &[("path", &file_path.display())]),
+        };
+        return Ok(FileContent {
+            content: content.into(),
+            original_size: size,
+            read_bytes: 0,
+            converter: None,
+            skip_reason: Some(reason),
+            line_endings: None,
+            chunks: Vec::new(),
+            lfs: None,
+            stale: false,
+            sanitized: None,
+            transforms: Vec::new(),
+            mode: None,
+        });
+    }
+
+    progress.on_file_started(file_path.strip_prefix(base_folder).unwrap_or(file_path));
+    let result = read_file_sized(file_path, size, options);
+    match &result {
+        Ok(content) if content.skip_reason == Some(SkipReason::TooLarge) => {
+            progress.on_file_skipped(file_path, SkipReason::TooLarge)
+        }
+        Ok(content) => progress.on_file_done(file_path, content.read_bytes, true),
+        Err(_) => progress.on_file_done(file_path, 0, false),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use crate::config::{FetchFuture, TemplateFetcher, TemplateManager};
+    use crate::exclusions::ExclusionManager;
+    use clap::Parser;
+    use std::fs;
+    use std::sync::Arc;
+
+    /// Создает проект в нескрытой поддиректории временной папки.
+    fn project() -> Result<(tempfile::TempDir, PathBuf)> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(root.join("src"))?;
+        fs::create_dir_all(root.join("node_modules/dep"))?;
+        fs::write(root.join("src/main.rs"), "fn main() {}\r\n")?;
+        fs::write(root.join("node_modules/dep/index.js"), "")?;
+        fs::write(root.join("app.exe"), b"\x00\x01")?;
+        Ok((temp_dir, root))
+    }
+
+    /// Источник шаблонов без сети: тесты используют только кэш.
+    struct CacheOnly;
+
+    impl TemplateFetcher for CacheOnly {
+        fn fetch_list(&self) -> FetchFuture<'_> {
+            Box::pin(async { Err(anyhow::anyhow!("cache only")) })
+        }
+    }
+
+    /// Конфигурация с `--offline` и кэшем шаблонов рядом с проектом, а не в `~/.flatten`.
+    async fn config_for(root: &Path, extra: &[&str]) -> Result<FlattenConfig> {
+        let folder = root.to_str().expect("path is utf8");
+        let args = Args::parse_from(
+            ["flatten-rust", "-f", folder, "--offline"].iter().chain(extra),
+        );
+        let cache = root.parent().expect("project is inside a temp dir").join(".flatten");
+        let templates = TemplateManager::with_fetcher(cache, CacheOnly)?;
+        let manager = ExclusionManager::with_template_manager(templates);
+        Ok(FlattenConfig::with_exclusions(&args, manager).await?)
+    }
+
+    #[tokio::test]
+    async fn test_collect_files() -> Result<()> {
+        let (_temp, root) = project()?;
+        let config = config_for(&root, &[]).await?;
+        let mut entries = collect_files(&root, &config)?;
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        assert_eq!(
+            entries,
+            vec![
+                FileEntry {
+                    path: root.join("app.exe"),
+                    size: 2,
+                    skip_reason: Some(SkipReason::Extension),
+                },
+                FileEntry {
+                    path: root.join("src/main.rs"),
+                    size: 14,
+                    skip_reason: None,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_file() -> Result<()> {
+        let (_temp, root) = project()?;
+        let config = config_for(&root, &[]).await?;
+        let content = read_file(
+            &root.join("src/main.rs"),
+            &ReadOptions::from_config(&config),
+        )?;
+        assert_eq!(content.content, "fn main() {}\n");
+        assert_eq!(content.original_size, 14);
+        assert_eq!(content.line_endings, Some(LineEndings::Crlf));
+
+        let options = ReadOptions {
+            max_file_size: 4,
+            ..ReadOptions::from_config(&config)
+        };
+        let content = read_file(&root.join("src/main.rs"), &options)?;
+        assert_eq!(content.skip_reason, Some(SkipReason::TooLarge));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_file_borrows_mapped_text() -> Result<()> {
+        let (_temp, root) = project()?;
+        let config = config_for(&root, &[]).await?;
+        let options = ReadOptions {
+            mmap_threshold: 0,
+            ..ReadOptions::from_config(&config)
+        };
+
+        let lib = root.join("src/lib.rs");
+        fs::write(&lib, "\u{feff}pub fn lib() {}\n")?;
+        let mut content = read_file(&lib, &options)?;
+        assert!(content.content.is_mapped());
+        assert_eq!(content.content, "pub fn lib() {}\n");
+        assert!(!content.content.recheck_mapped(&lib)?);
+        assert!(content.content.is_mapped());
+
+        // Файл той же длины с невалидным UTF-8 декодируется с заменой,
+        // а файл другой длины перечитывается без обращения к отображению.
+        let mut bytes = "\u{feff}pub fn lib() {}\n".as_bytes().to_vec();
+        bytes[3] = 0xff;
+        fs::write(&lib, &bytes)?;
+        assert!(content.content.recheck_mapped(&lib)?);
+        assert_eq!(content.content, &*String::from_utf8_lossy(&bytes[3..]));
+        fs::write(&lib, "pub fn lib() {}\n")?;
+        let mut content = read_file(&lib, &options)?;
+        assert!(content.content.is_mapped());
+        fs::write(&lib, "fn changed_lib() {}\n")?;
+        assert!(content.content.recheck_mapped(&lib)?);
+        assert!(!content.content.is_mapped());
+        assert_eq!(content.content, "fn changed_lib() {}\n");
+
+        let content = read_file(&root.join("src/main.rs"), &options)?;
+        assert!(
+            !content.content.is_mapped(),
+            "CRLF is converted into an owned buffer"
+        );
+
+        let invalid = b"ok \xff\xfe end\n";
+        fs::write(root.join("src/invalid.rs"), invalid)?;
+        let content = read_file(&root.join("src/invalid.rs"), &options)?;
+        assert!(!content.content.is_mapped());
+        assert_eq!(content.content, &*String::from_utf8_lossy(invalid));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_strategies_match() -> Result<()> {
+        let (_temp, root) = project()?;
+        let mut config = config_for(&root, &[]).await?;
+        config.register_converter("dat", Arc::new(DatConverter));
+        let files: [(&str, &[u8]); 6] = [
+            ("src/bom.rs", "\u{feff}fn bom() {}\n".as_bytes()),
+            ("src/crlf.rs", b"fn a() {}\r\nfn b() {}\r\n"),
+            ("src/invalid.rs", b"ok \xff\xfe end\n"),
+            ("src/empty.rs", b""),
+            ("src/text.dat", b"text for the converter"),
+            ("src/other.dat", b"falls through"),
+        ];
+        for (path, contents) in files {
+            fs::write(root.join(path), contents)?;
+        }
+        let large = "let value = 1;\n".repeat(8 * 1024);
+        fs::write(root.join("src/large.rs"), &large)?;
+
+        let mapped = ReadOptions {
+            mmap_threshold: 0,
+            ..ReadOptions::from_config(&config)
+        };
+        let buffered = ReadOptions {
+            mmap_threshold: u64::MAX,
+            ..ReadOptions::from_config(&config)
+        };
+        let paths = files.iter().map(|(path, _)| *path).chain(["src/large.rs"]);
+        for path in paths {
+            let path = root.join(path);
+            let (a, b) = (read_file(&path, &mapped)?, read_file(&path, &buffered)?);
+            assert_eq!(*a.content, *b.content, "{}", path.display());
+            assert_eq!(
+                (a.original_size, a.read_bytes, a.converter, a.skip_reason, a.line_endings),
+                (b.original_size, b.read_bytes, b.converter, b.skip_reason, b.line_endings),
+                "{}",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Конвертер тестовых файлов `.dat`: решение зависит от первого байта.
+    #[derive(Debug)]
+    struct DatConverter;
+
+    impl FileConverter for DatConverter {
+        fn name(&self) -> &'static str {
+            "dat"
+        }
+
+        fn convert(&self, _path: &Path, source: ConverterSource<'_>) -> Conversion {
+            let bytes = source.into_bytes().expect("bytes are mapped");
+            match bytes.first() {
+                Some(b't') => Conversion::Text(format!("[dat: {} bytes]", bytes.len())),
+                Some(b'p') => Conversion::Placeholder("[dat skipped]".to_string()),
+                Some(b'!') => panic!("corrupt dat"),
+                _ => Conversion::FallThrough,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lfs_pointer_outcomes() -> Result<()> {
+        let (_temp, root) = project()?;
+        let config = config_for(&root, &[]).await?;
+        let oid = "ab12".repeat(16);
+        let pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize 25\n",
+            oid
+        );
+        fs::write(root.join("model.txt"), &pointer)?;
+        let path = root.join("model.txt");
+
+        let content = read_file(&path, &ReadOptions::from_config(&config))?;
+        assert_eq!(
+            content.content,
+            "[Git LFS pointer: 25 bytes object ab12ab12ab12…, content not available locally]"
+        );
+        assert_eq!(content.skip_reason, Some(SkipReason::LfsPointer));
+        assert_eq!(content.lfs, Some(LfsState::Pointer));
+
+        let options = ReadOptions {
+            lfs_include_pointers: true,
+            ..ReadOptions::from_config(&config)
+        };
+        let content = read_file(&path, &options)?;
+        assert_eq!(content.content, pointer.as_str());
+        assert_eq!(content.skip_reason, None);
+        assert_eq!(content.lfs, Some(LfsState::Pointer));
+
+        // Объекта еще нет: --lfs-resolve оставляет заглушку.
+        let options = ReadOptions {
+            lfs_resolve: true,
+            ..ReadOptions::from_config(&config)
+        };
+        let content = read_file(&path, &options)?;
+        assert_eq!(content.skip_reason, Some(SkipReason::LfsPointer));
+
+        let objects = root.join(".git/lfs/objects/ab/12");
+        fs::create_dir_all(&objects)?;
+        fs::write(objects.join(&oid), "resolved model contents\n")?;
+        let content = read_file(&path, &options)?;
+        assert_eq!(content.content, "resolved model contents\n");
+        assert_eq!(content.original_size, 24);
+        assert_eq!(content.lfs, Some(LfsState::Resolved));
+
+        let options = ReadOptions {
+            max_file_size: 8,
+            ..options
+        };
+        let content = read_file(&path, &options)?;
+        assert_eq!(content.skip_reason, Some(SkipReason::TooLarge));
+        assert_eq!(content.lfs, Some(LfsState::Resolved));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_registered_converter_outcomes() -> Result<()> {
+        let (_temp, root) = project()?;
+        let mut config = config_for(&root, &[]).await?;
+        config.register_converter("DAT", Arc::new(DatConverter));
+        let options = ReadOptions::from_config(&config);
+        let read = |contents: &str| {
+            let path = root.join("src/file.dat");
+            fs::write(&path, contents)?;
+            read_file(&path, &options)
+        };
+
+        let content = read("text")?;
+        assert_eq!(content.content, "[dat: 4 bytes]");
+        assert_eq!(content.converter, Some("dat"));
+        assert_eq!(content.skip_reason, None);
+
+        let content = read("placeholder")?;
+        assert_eq!(content.content, "[dat skipped]");
+        assert_eq!(content.skip_reason, Some(SkipReason::Converter));
+
+        let content = read("regular")?;
+        assert_eq!(content.content, "regular");
+        assert_eq!(content.converter, None);
+
+        let error = read("!boom").expect_err("panic becomes a file error");
+        assert!(format!("{:#}", error).contains("Converter dat panicked"), "{:#}", error);
+        assert!(format!("{:#}", error).contains("corrupt dat"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_read_file_times_out_on_stalled_fifo() -> Result<()> {
+        let (_temp, root) = project()?;
+        let fifo = root.join("stalled.log");
+        let status = std::process::Command::new("mkfifo").arg(&fifo).status()?;
+        assert!(status.success());
+
+        let config = config_for(&root, &["--file-timeout", "1"]).await?;
+        let started = std::time::Instant::now();
+        let error = read_file(&fifo, &ReadOptions::from_config(&config))
+            .expect_err("nobody writes to the FIFO");
+        assert_eq!(error.to_string(), "timed out after 1s");
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let content = read_file(
+            &root.join("src/main.rs"),
+            &ReadOptions::from_config(&config),
+        )?;
+        assert_eq!(content.content, "fn main() {}\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_snapshot_with_and_without_emoji() -> Result<()> {
+        let (_temp, root) = project()?;
+        // Порядок обхода не сортируется, поэтому в снимке оставляем одну ветку.
+        fs::remove_file(root.join("app.exe"))?;
+        let render = |config: &FlattenConfig| -> Result<String> {
+            let mut tree = Vec::new();
+            render_tree(&root, config, &mut tree)?;
+            Ok(String::from_utf8(tree)?)
+        };
+
+        let config = config_for(&root, &[]).await?;
+        assert_eq!(render(&config)?, "📁 src/\n    📄 main.rs\n");
+
+        let config = config_for(&root, &["--no-emoji"]).await?;
+        let tree = render(&config)?;
+        assert_eq!(tree, "DIR src/\n    FILE main.rs\n");
+        assert!(tree.is_ascii());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_fitted_to_console_width() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        let bindings = root.join("generated_protocol_buffer_bindings_for_payments");
+        fs::create_dir_all(&bindings)?;
+        let file_name = "payments_service_client_with_retries_and_circuit_breakers.rs";
+        fs::write(bindings.join(file_name), "")?;
+        let config = config_for(&root, &["--no-emoji", "--no-collapse-dirs"]).await?;
+        let tree = DirTree::walk(&root, &config)?;
+        let render = |width: Option<usize>| -> Result<String> {
+            let mut rendered = Vec::new();
+            tree.render_fitted(&config, &mut rendered, width)?;
+            Ok(String::from_utf8(rendered)?)
+        };
+
+        assert_eq!(
+            render(Some(60))?,
+            "DIR generated_protocol_buffer_bindings_for_payments/\n    \
+             FILE payments_service_client_with_retries_and_circuit_b…\n"
+        );
+        let full = format!(
+            "DIR generated_protocol_buffer_bindings_for_payments/\n    FILE {}\n",
+            file_name
+        );
+        assert_eq!(render(Some(120))?, full);
+        // В файл вывода имена попадают целиком.
+        assert_eq!(render(None)?, full);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_collapses_single_child_chain() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        let package = root.join("src/main/java/com/acme");
+        fs::create_dir_all(package.join("util"))?;
+        fs::write(package.join("util/Strings.java"), "")?;
+        let render = |config: &FlattenConfig| -> Result<String> {
+            let mut tree = Vec::new();
+            render_tree(&root, config, &mut tree)?;
+            Ok(String::from_utf8(tree)?)
+        };
+
+        let config = config_for(&root, &[]).await?;
+        assert_eq!(
+            render(&config)?,
+            "📁 src/main/java/com/acme/util/\n    📄 Strings.java\n"
+        );
+
+        // Пропущенный сосед прерывает цепочку и без `--show-skipped`.
+        fs::create_dir_all(root.join("src/main/node_modules"))?;
+        for args in [&[][..], &["--show-skipped"][..]] {
+            let config = config_for(&root, args).await?;
+            let rendered = render(&config)?;
+            assert!(rendered.starts_with("📁 src/main/\n"), "{}", rendered);
+            assert!(rendered.contains("📁 java/com/acme/util/\n"), "{}", rendered);
+            assert_eq!(rendered.contains("node_modules"), !args.is_empty(), "{}", rendered);
+        }
+        fs::remove_dir(root.join("src/main/node_modules"))?;
+
+        let config = config_for(&root, &["--no-collapse-dirs"]).await?;
+        let expected: String = ["src", "main", "java", "com", "acme", "util"]
+            .iter()
+            .enumerate()
+            .map(|(depth, name)| format!("{}📁 {}/\n", "    ".repeat(depth), name))
+            .chain([format!("{}📄 Strings.java\n", "    ".repeat(6))])
+            .collect();
+        assert_eq!(render(&config)?, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_depth_keeps_content_collection() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(root.join("l1/l2/l3/l4"))?;
+        fs::write(root.join("l1/l2/l3/l4/deep.rs"), "")?;
+        fs::write(root.join("l1/l2/mid.rs"), "")?;
+        let render = |config: &FlattenConfig| -> Result<String> {
+            let mut tree = Vec::new();
+            render_tree(&root, config, &mut tree)?;
+            Ok(String::from_utf8(tree)?)
+        };
+
+        let config = config_for(&root, &["--tree-depth", "2", "--no-collapse-dirs"]).await?;
+        assert_eq!(
+            render(&config)?,
+            "📁 l1/\n    📁 l2/ … (4 more entries)\n"
+        );
+        assert_eq!(collect_files(&root, &config)?.len(), 2);
+
+        // Объединенная цепочка считается одним уровнем.
+        let config = config_for(&root, &["--tree-depth", "2"]).await?;
+        let tree = render(&config)?;
+        assert!(tree.starts_with("📁 l1/l2/\n"), "{}", tree);
+        assert!(tree.contains("\n    📁 l3/l4/ … (1 more entry)\n"), "{}", tree);
+        assert!(tree.contains("\n    📄 mid.rs\n"), "{}", tree);
+        assert!(!tree.contains("deep.rs"));
+        assert_eq!(collect_files(&root, &config)?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_max_entries_truncates_rows() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        fs::create_dir_all(root.join("a/b"))?;
+        fs::write(root.join("a/b/one.rs"), "")?;
+        fs::write(root.join("a/two.rs"), "")?;
+        fs::write(root.join("three.rs"), "")?;
+        let render = |config: &FlattenConfig| -> Result<String> {
+            let mut tree = Vec::new();
+            render_tree(&root, config, &mut tree)?;
+            Ok(String::from_utf8(tree)?)
+        };
+
+        let args = ["--tree-max-entries", "2", "--no-collapse-dirs"];
+        let config = config_for(&root, &args).await?;
+        let tree = render(&config)?;
+        assert_eq!(tree.lines().count(), 3, "{}", tree);
+        assert!(tree.ends_with("\n… truncated (2 shown of 5)\n"), "{}", tree);
+        assert_eq!(collect_files(&root, &config)?.len(), 3);
+
+        // Пометка `--tree-depth` не теряется, а скрытые строки не считаются.
+        let args = ["--tree-max-entries", "2", "--tree-depth", "1", "--no-collapse-dirs"];
+        let config = config_for(&root, &args).await?;
+        let tree = render(&config)?;
+        assert_eq!(tree.lines().count(), 2, "{}", tree);
+        assert!(tree.contains("📁 a/ … (3 more entries)\n"), "{}", tree);
+
+        let args = ["--tree-max-entries", "5", "--no-collapse-dirs"];
+        let config = config_for(&root, &args).await?;
+        assert!(!render(&config)?.contains("truncated"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_collapse_mixed_snapshot() -> Result<()> {
+        let (_temp, root) = project()?;
+        let entry = |path: &str, is_dir: bool, skip_reason: Option<SkipReason>| TreeEntry {
+            path: root.join(path),
+            depth: Path::new(path).components().count(),
+            is_dir,
+            skip_reason,
+            size: None,
+            modified: None,
+        };
+        // Порядок обхода не сортируется, поэтому дерево задано явно.
+        let tree = DirTree {
+            root: root.clone(),
+            entries: vec![
+                entry("a", true, None),
+                entry("a/b", true, None),
+                entry("a/b/c", true, None),
+                entry("a/b/c/x.rs", false, None),
+                entry("a/b/c/y.rs", false, None),
+                entry("a/z.rs", false, None),
+                entry("d", true, None),
+                entry("d/node_modules", true, Some(SkipReason::Folder)),
+                entry("g", true, None),
+                entry("g/h", true, None),
+                entry("g/h/target", true, Some(SkipReason::Folder)),
+                entry("g/h/i", true, None),
+                entry("g/h/i/main.rs", false, None),
+                entry("README.md", false, None),
+            ],
+            omitted: HashMap::new(),
+        };
+        let render = |config: &FlattenConfig| -> Result<String> {
+            let mut rendered = Vec::new();
+            tree.render(config, &mut rendered)?;
+            Ok(String::from_utf8(rendered)?)
+        };
+
+        let config = config_for(&root, &[]).await?;
+        assert_eq!(
+            render(&config)?,
+            "📁 a/\n\
+             \x20   📁 b/c/\n\
+             \x20       📄 x.rs\n\
+             \x20       📄 y.rs\n\
+             \x20   📄 z.rs\n\
+             📁 d/\n\
+             📁 g/h/\n\
+             \x20   📁 i/\n\
+             \x20       📄 main.rs\n\
+             📄 README.md\n"
+        );
+
+        let config = config_for(&root, &["--show-skipped"]).await?;
+        assert_eq!(
+            render(&config)?,
+            "📁 a/\n\
+             \x20   📁 b/c/\n\
+             \x20       📄 x.rs\n\
+             \x20       📄 y.rs\n\
+             \x20   📄 z.rs\n\
+             📁 d/\n\
+             \x20   ⏭️ node_modules/ (skipped)\n\
+             📁 g/h/\n\
+             \x20   ⏭️ target/ (skipped)\n\
+             \x20   📁 i/\n\
+             \x20       📄 main.rs\n\
+             📄 README.md\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_annotations_snapshot() -> Result<()> {
+        let (_temp, root) = project()?;
+        fs::create_dir_all(root.join("src/util"))?;
+        fs::write(root.join("src/util/helper.rs"), "pub fn h() {}")?;
+        let entry = |path: &str, is_dir: bool, skip_reason: Option<SkipReason>| TreeEntry {
+            path: root.join(path),
+            depth: Path::new(path).components().count(),
+            is_dir,
+            skip_reason,
+            size: None,
+            modified: None,
+        };
+        let tree = DirTree {
+            root: root.clone(),
+            entries: vec![
+                entry("src", true, None),
+                entry("src/main.rs", false, None),
+                entry("src/util", true, None),
+                entry("src/util/helper.rs", false, None),
+                entry("app.exe", false, Some(SkipReason::Extension)),
+                entry("node_modules", true, Some(SkipReason::Folder)),
+            ],
+            omitted: HashMap::new(),
+        };
+        let mut rendered = Vec::new();
+        for mode in ["none", "both", "counts", "sizes"] {
+            let args = ["--show-skipped", "--tree-annotations", mode];
+            let config = config_for(&root, &args).await?;
+            let mut tree_text = Vec::new();
+            tree.render(&config, &mut tree_text)?;
+            rendered.push(String::from_utf8(tree_text)?);
+        }
+
+        assert_eq!(
+            rendered[0],
+            "📁 src/\n    📄 main.rs\n    📁 util/\n        📄 helper.rs\n\
+             📄 app.exe\n⏭️ node_modules/ (skipped)\n"
+        );
+        assert_eq!(
+            rendered[1],
+            "📁 src/ (2 files, 27 bytes)\n    📄 main.rs\n    📁 util/ (1 file, 13 bytes)\n\
+             \x20       📄 helper.rs\n📄 app.exe\n⏭️ node_modules/ (skipped, ~1 entry)\n"
+        );
+        assert!(rendered[2].starts_with("📁 src/ (2 files)\n"));
+        assert!(rendered[3].contains("📁 util/ (13 bytes)\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tree_annotations_skip_placeholders() -> Result<()> {
+        let (_temp, root) = project()?;
+        fs::create_dir_all(root.join("gen"))?;
+        fs::write(root.join("gen/big.txt"), "x".repeat(2000))?;
+        fs::write(root.join("gen/api.rs"), "// @generated\nfn f() {}")?;
+        let args = ["--tree-annotations", "both", "-m", "1000", "--skip-generated"];
+        let config = config_for(&root, &args).await?;
+        let tree = DirTree::walk(&root, &config)?;
+        let mut rendered = Vec::new();
+        tree.render(&config, &mut rendered)?;
+        let rendered = String::from_utf8(rendered)?;
+
+        // Слишком большой и сгенерированный файлы заменяются заглушками и не считаются.
+        assert!(rendered.contains("📁 gen/ (0 files, 0 bytes)\n"), "{}", rendered);
+        assert!(rendered.contains("📁 src/ (1 file, 14 bytes)\n"), "{}", rendered);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dir_tree_matches_content() -> Result<()> {
+        let (_temp, root) = project()?;
+        let config = config_for(&root, &["--show-skipped"]).await?;
+        let tree = DirTree::walk(&root, &config)?;
+
+        let mut files: Vec<_> = tree.files().map(|entry| entry.path.clone()).collect();
+        files.sort();
+        assert_eq!(files, vec![root.join("app.exe"), root.join("src/main.rs")]);
+        let collected: Vec<_> = collect_files(&root, &config)?
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        assert_eq!(collected.len(), files.len());
+
+        let node_modules = tree
+            .entries
+            .iter()
+            .find(|entry| entry.path == root.join("node_modules"))
+            .expect("skipped folder is recorded");
+        assert!(node_modules.is_dir && node_modules.skip_reason.is_some());
+        assert!(
+            !tree
+                .entries
+                .iter()
+                .any(|entry| entry.path.starts_with(root.join("node_modules/dep")))
+        );
+
+        let mut rendered = Vec::new();
+        tree.render(&config, &mut rendered)?;
+        let rendered = String::from_utf8(rendered)?;
+        assert!(rendered.contains("📄 app.exe\n"));
+        assert!(rendered.contains("⏭️ node_modules/ (skipped)\n"));
+        assert!(!rendered.contains("index.js"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_content_reports_asymmetric_paths() -> Result<()> {
+        let (_temp, root) = project()?;
+        let config = config_for(&root, &[]).await?;
+        let tree = DirTree::walk(&root, &config)?;
+        let paths = |paths: &[&str]| -> HashSet<PathBuf> {
+            paths.iter().map(|path| root.join(path)).collect()
+        };
+
+        let emitted = paths(&["app.exe", "src/main.rs"]);
+        assert_eq!(tree.verify_content(&emitted, &HashSet::new()), Ok(()));
+        // Заглушка бинарного файла — тоже секция содержимого.
+        let mismatch = tree
+            .verify_content(&paths(&["src/main.rs", "node_modules/dep/index.js"]), &HashSet::new())
+            .unwrap_err();
+        assert_eq!(mismatch.tree_only, vec![root.join("app.exe")]);
+        assert_eq!(mismatch.content_only, vec![root.join("node_modules/dep/index.js")]);
+        let message = mismatch.to_string();
+        assert!(message.starts_with("in tree without content: "), "{}", message);
+        assert!(message.contains("; with content but not in tree: "), "{}", message);
+
+        let withheld = paths(&["app.exe"]);
+        assert_eq!(tree.verify_content(&paths(&["src/main.rs"]), &withheld), Ok(()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parallel_walk_matches_serial() -> Result<()> {
+        let (_temp, root) = project()?;
+        for dir in 0..4 {
+            let nested = root.join(format!("pkg_{}/src/deep", dir));
+            fs::create_dir_all(&nested)?;
+            fs::create_dir_all(root.join(format!("pkg_{}/target", dir)))?;
+            fs::write(root.join(format!("pkg_{}/Cargo.lock", dir)), "")?;
+            for file in 0..5 {
+                fs::write(nested.join(format!("file_{}.rs", file)), "")?;
+            }
+        }
+
+        for extra in [&["--stats"][..], &["--max-depth", "2"][..]] {
+            let config = config_for(&root, extra).await?;
+            let mut serial_report = FlattenReport::default();
+            let serial = DirTree::walk_with_report(&root, &config, &mut serial_report)?;
+
+            let found = Mutex::new(Vec::new());
+            let mut parallel_report = FlattenReport::default();
+            let parallel = DirTree::walk_parallel_with_report(
+                &root,
+                &config,
+                &mut parallel_report,
+                &|entry| {
+                    found.lock().unwrap().push(entry.path.clone());
+                },
+            )?;
+
+            assert_eq!(parallel, serial);
+            assert_eq!(parallel_report.skipped, serial_report.skipped);
+            let mut found = found.into_inner().unwrap();
+            found.sort();
+            let mut files: Vec<_> = serial.files().map(|entry| entry.path.clone()).collect();
+            files.sort();
+            assert_eq!(found, files);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explicit_base_folder_is_not_skipped() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        for name in [".config-templates", "build"] {
+            let root = temp_dir.path().join("workspace").join(name);
+            fs::create_dir_all(root.join(".cache"))?;
+            fs::create_dir_all(root.join("build"))?;
+            fs::write(root.join("rust.toml"), "")?;
+            fs::write(root.join(".cache/entry"), "")?;
+            fs::write(root.join("build/out.rs"), "")?;
+
+            let config = config_for(&root, &[]).await?;
+            let files: Vec<_> = DirTree::walk(&root, &config)?
+                .files()
+                .map(|entry| entry.path.clone())
+                .collect();
+            assert_eq!(files, vec![root.join("rust.toml")], "base folder {}", name);
+
+            let mut report = FlattenReport::default();
+            let parallel =
+                DirTree::walk_parallel_with_report(&root, &config, &mut report, &|_| {})?;
+            assert_eq!(parallel.files().count(), 1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Модуль наборов настроек (`--preset`).
+//!
+//! Набор — это именованный список шаблонов исключений и флагов, которые
+//! подставляются в аргументы до остального разбора. Флаги набора работают как
+//! значения по умолчанию: явно заданные в командной строке или в переменных
+//! окружения параметры сильнее, а шаблоны набора добавляются к указанным
+//! через `--enable-template`. Кроме встроенных наборов можно описать свои в
+//! поле `presets` файла `~/.flatten/manager_config.json`; набор с тем же
+//! именем заменяет встроенный.
+
+use crate::config::ManagerConfig;
+use crate::output::OutputFormat;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Описание одного набора настроек.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetDefinition {
+    /// Краткое описание для `--list-presets`.
+    pub description: String,
+    /// Ключи шаблонов, включаемых набором.
+    pub templates: Vec<String>,
+    /// Флаги в виде `--имя` или `--имя=значение`.
+    pub args: Vec<String>,
+}
+
+impl PresetDefinition {
+    fn builtin(description: &str, templates: &[&str], args: &[&str]) -> Self {
+        Self {
+            description: description.to_string(),
+            templates: templates.iter().map(|key| key.to_string()).collect(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    /// Возвращает аргументы, в которые раскрывается набор.
+    pub fn expansion(&self) -> Vec<String> {
+        self.templates
+            .iter()
+            .map(|key| format!("--enable-template={}", key))
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+}
+
+/// Возвращает встроенные наборы.
+pub fn builtin_presets() -> BTreeMap<String, PresetDefinition> {
+    BTreeMap::from([
+        (
+            "jvm".to_string(),
+            PresetDefinition::builtin(
+                "Java and Kotlin projects built with Gradle or Maven",
+                &["java", "gradle", "maven", "kotlin"],
+                &[],
+            ),
+        ),
+        (
+            "llm".to_string(),
+            PresetDefinition::builtin(
+                "Output for language models: no lockfiles, files over 1 MiB skipped",
+                &[],
+                &["--skip-lockfiles", "--max-file-size=1048576"],
+            ),
+        ),
+        (
+            "python-ml".to_string(),
+            PresetDefinition::builtin(
+                "Python and Jupyter projects with virtual environments",
+                &["python", "jupyternotebooks", "venv"],
+                &[],
+            ),
+        ),
+        (
+            "web".to_string(),
+            PresetDefinition::builtin(
+                "Node.js, React and Next.js frontends with Sass",
+                &["node", "react", "nextjs", "sass"],
+                &[],
+            ),
+        ),
+    ])
+}
+
+/// Читает пользовательские наборы из `manager_config.json` в `flatten_dir`.
+///
+/// Отсутствующий или поврежденный файл конфигурации означает отсутствие
+/// пользовательских наборов, как и в `TemplateManager`.
+pub fn load_user_presets(flatten_dir: &Path) -> BTreeMap<String, PresetDefinition> {
+    std::fs::read_to_string(flatten_dir.join("manager_config.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<ManagerConfig>(&content).ok())
+        .map(|config| config.presets)
+        .unwrap_or_default()
+}
+
+/// Возвращает встроенные наборы, дополненные пользовательскими из `~/.flatten/`.
+pub fn available_presets() -> BTreeMap<String, PresetDefinition> {
+    let mut presets = builtin_presets();
+    if let Some(home_dir) = dirs::home_dir() {
+        presets.extend(load_user_presets(&home_dir.join(".flatten")));
+    }
+    presets
+}
+
+/// Выводит список наборов (`--list-presets`).
+pub fn print_presets(
+    presets: &BTreeMap<String, PresetDefinition>,
+    format: OutputFormat,
+) -> Result<()> {
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(presets)?);
+        return Ok(());
+    }
+    println!("Available presets:");
+    for (name, preset) in presets {
+        println!("  {:<12} {}", name, preset.description);
+        println!("  {:<12} expands to: {}", "", preset.expansion().join(" "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_presets_from_config() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        assert!(load_user_presets(temp_dir.path()).is_empty());
+
+        std::fs::write(
+            temp_dir.path().join("manager_config.json"),
+            r#"{"last_updated": 1, "presets": {"docs": {"args": ["--include-extension=md"]}}}"#,
+        )?;
+        let presets = load_user_presets(temp_dir.path());
+        assert_eq!(presets["docs"].expansion(), vec!["--include-extension=md"]);
+
+        assert_eq!(
+            builtin_presets()["jvm"].expansion(),
+            vec![
+                "--enable-template=java",
+                "--enable-template=gradle",
+                "--enable-template=maven",
+                "--enable-template=kotlin",
+            ]
+        );
+        Ok(())
+    }
+}
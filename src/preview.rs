@@ -0,0 +1,157 @@
+//! Модуль сводки и подтверждения больших запусков (`--confirm-threshold`).
+//!
+//! Если в базовой папке найдено больше файлов, чем задано порогом, до чтения
+//! файлов выводится сводка: число файлов, оценка объема чтения и пять
+//! директорий верхнего уровня с наибольшим числом файлов. В терминале
+//! обработка продолжается только после подтверждения, без терминала сводка
+//! лишь выводится. `--yes` отключает и сводку, и вопрос.
+
+use crate::report::format_size;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Сколько директорий показывать в сводке.
+const TOP_DIRECTORIES: usize = 5;
+
+/// Сводка базовой папки перед обработкой.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunPreview {
+    /// Базовая папка.
+    pub base_folder: PathBuf,
+    /// Количество файлов.
+    pub files: usize,
+    /// Оценка объема чтения в байтах (заглушки не читаются и не учитываются).
+    pub bytes: u64,
+    /// Директории верхнего уровня с наибольшим числом файлов (`.` — сама базовая папка).
+    pub top_directories: Vec<(PathBuf, usize)>,
+}
+
+impl RunPreview {
+    /// Строит сводку по файлам базовой папки и объему их чтения.
+    pub fn new<'a>(base_folder: &Path, files: impl IntoIterator<Item = (&'a Path, u64)>) -> Self {
+        let mut preview = Self {
+            base_folder: base_folder.to_path_buf(),
+            files: 0,
+            bytes: 0,
+            top_directories: Vec::new(),
+        };
+        let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+        for (path, bytes) in files {
+            preview.files += 1;
+            preview.bytes += bytes;
+            let relative = path.strip_prefix(base_folder).unwrap_or(path);
+            let mut components = relative.components();
+            let top = match (components.next(), components.next()) {
+                (Some(directory), Some(_)) => PathBuf::from(directory.as_os_str()),
+                _ => PathBuf::from("."),
+            };
+            *counts.entry(top).or_default() += 1;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts.truncate(TOP_DIRECTORIES);
+        preview.top_directories = counts;
+        preview
+    }
+
+    /// Выводит сводку в `output`.
+    pub fn print(&self, output: &mut dyn Write) -> Result<()> {
+        writeln!(
+            output,
+            "Large run: {} files in {} (~{} to read)",
+            self.files,
+            self.base_folder.display(),
+            format_size(self.bytes)
+        )?;
+        writeln!(output, "Top directories by file count:")?;
+        for (directory, count) in &self.top_directories {
+            writeln!(output, "  {:>8}  {}", count, directory.display())?;
+        }
+        Ok(())
+    }
+
+    /// Выводит сводку и спрашивает, продолжать ли обработку.
+    ///
+    /// `input` — ввод терминала; без него сводка только выводится и
+    /// возвращается `true`. Согласием считаются ответы `y` и `yes`.
+    pub fn confirm(&self, input: Option<&mut dyn BufRead>, output: &mut dyn Write) -> Result<bool> {
+        self.print(output)?;
+        let Some(input) = input else {
+            writeln!(output, "Continuing (pass --yes to skip this summary)")?;
+            return Ok(true);
+        };
+        write!(output, "Continue? [y/N] ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        Ok(matches!(
+            answer.trim().to_ascii_lowercase().as_str(),
+            "y" | "yes"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn preview() -> RunPreview {
+        let base = Path::new("/home/user");
+        let files: Vec<(PathBuf, u64)> = [
+            "a/1",
+            "a/2",
+            "a/b/3",
+            "c/4",
+            "notes.txt",
+            "d/5",
+            "e/6",
+            "f/7",
+            "f/8",
+        ]
+        .iter()
+        .map(|path| (base.join(path), 1024))
+        .collect();
+        RunPreview::new(
+            base,
+            files.iter().map(|(path, size)| (path.as_path(), *size)),
+        )
+    }
+
+    #[test]
+    fn test_preview_top_directories() {
+        let preview = preview();
+        assert_eq!(preview.files, 9);
+        assert_eq!(preview.bytes, 9 * 1024);
+        assert_eq!(
+            preview.top_directories,
+            vec![
+                (PathBuf::from("a"), 3),
+                (PathBuf::from("f"), 2),
+                (PathBuf::from("."), 1),
+                (PathBuf::from("c"), 1),
+                (PathBuf::from("d"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_confirm_answers() -> Result<()> {
+        let preview = preview();
+        for (answer, expected) in [("y\n", true), ("Yes\n", true), ("n\n", false), ("", false)] {
+            let mut output = Vec::new();
+            let confirmed = preview.confirm(Some(&mut Cursor::new(answer)), &mut output)?;
+            assert_eq!(confirmed, expected, "answer {:?}", answer);
+            assert!(String::from_utf8(output)?.ends_with("Continue? [y/N] "));
+        }
+
+        let mut output = Vec::new();
+        assert!(preview.confirm(None, &mut output)?);
+        let output = String::from_utf8(output)?;
+        assert!(output.starts_with("Large run: 9 files in /home/user (~9.00 KB to read)\n"));
+        assert!(output.contains("\n         3  a\n"));
+        Ok(())
+    }
+}
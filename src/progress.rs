@@ -0,0 +1,452 @@
+//! Модуль уведомлений о ходе обработки.
+//!
+//! `run` сообщает о ходе обработки каждой базовой папки через трейт
+//! `ProgressSink`. CLI использует адаптер к `indicatif`, а библиотека может
+//! передать собственную реализацию в `run_with` (например, для GUI или
+//! расширения редактора; `--events` записывает те же уведомления потоком JSON).
+//! Методы вызываются из рабочих потоков `rayon`, поэтому реализация должна
+//! быть `Send + Sync` и хранить изменяемое состояние за синхронизацией.
+
+use crate::cache_maintenance::CacheStatus;
+use crate::manifest::ContentSpan;
+use crate::preview::RunPreview;
+use crate::report::{FlattenReport, SkipReason};
+use crate::terminal;
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const PROGRESS_COUNTERS: &str = "included {included}, skipped(ext) {skipped_ext}, \
+     skipped(size) {skipped_size}, errors {errors}";
+/// Колонки строки полосы без самой полосы (спиннер, время, позиция, оценка).
+const BAR_LINE_WIDTH: usize = 36;
+/// С какой ширины консоли счетчики помещаются в строку полосы.
+const WIDE_CONSOLE: usize = 150;
+/// Отступ строки с путем текущего файла.
+const MESSAGE_INDENT: usize = 2;
+/// Как часто обновлять путь текущего файла: на мелких файлах обновление на
+/// каждый файл занимало бы заметную долю времени.
+const MESSAGE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Итоги обработки одной базовой папки.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressSummary {
+    /// Количество обработанных файлов.
+    pub files: usize,
+    /// Суммарный исходный размер файлов в байтах.
+    pub bytes: u64,
+    /// Количество файлов, которые не удалось прочитать.
+    pub failed: usize,
+}
+
+/// Получатель уведомлений о ходе обработки.
+///
+/// Все методы имеют реализацию по умолчанию, которая ничего не делает.
+pub trait ProgressSink: Send + Sync {
+    /// Начат запуск: выходные документы определены, обход еще не начат.
+    fn on_run_started(&self) {}
+
+    /// Начат обход базовой папки `folder`.
+    fn on_folder_started(&self, _folder: &Path) {}
+
+    /// Начата обработка `total` файлов базовой папки.
+    ///
+    /// При `--parallel-walk` число файлов заранее неизвестно: `total` равен 0,
+    /// а найденные файлы сообщаются через `on_discovered`.
+    fn on_start(&self, _total: usize) {}
+
+    /// При параллельном обходе найдено еще `count` файлов.
+    fn on_discovered(&self, _count: usize) {}
+
+    /// Начато чтение файла; `relative` — путь относительно базовой папки.
+    fn on_file_started(&self, _relative: &Path) {}
+
+    /// Обработан файл `path` исходного размера `bytes`; `ok` — удалось ли его прочитать.
+    fn on_file_done(&self, _path: &Path, _bytes: u64, _ok: bool) {}
+
+    /// Файл `path` заменен заглушкой без чтения: по имени или расширению
+    /// (о нем не сообщается в `on_file_done`) или по размеру.
+    fn on_file_skipped(&self, _path: &Path, _reason: SkipReason) {}
+
+    /// Файл `path` записан в документ; `span` — положение его содержимого,
+    /// если формат позволяет его указать. Файлы записываются в порядке документа.
+    fn on_file_written(&self, _path: &Path, _span: Option<ContentSpan>) {}
+
+    /// В базовой папке больше `--confirm-threshold` файлов: они собраны, но
+    /// еще не прочитаны. Возвращает, продолжать ли обработку; по умолчанию
+    /// обработка продолжается без вопроса.
+    fn confirm_run(&self, _preview: &RunPreview) -> bool {
+        true
+    }
+
+    /// `--cache-prune --all` без `--yes` удалит все файлы кэша `status`.
+    /// Возвращает, удалять ли их; по умолчанию кэш не удаляется.
+    fn confirm_cache_prune(&self, _status: &CacheStatus) -> bool {
+        false
+    }
+
+    /// Пора ли остановить команду, работающую до прерывания (`--serve`).
+    /// Проверяется периодически из другого потока; по умолчанию — никогда.
+    fn stop_requested(&self) -> bool {
+        false
+    }
+
+    /// Обработка базовой папки завершена.
+    fn on_finish(&self, _summary: &ProgressSummary) {}
+
+    /// Запуск завершен, документы записаны.
+    fn on_run_finished(&self, _report: &FlattenReport) {}
+}
+
+/// Получатель, игнорирующий все уведомления.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
+
+/// Счетчики файлов текущей базовой папки, обновляемые из рабочих потоков.
+#[derive(Debug, Default)]
+struct FileCounters {
+    /// Прочитанные файлы.
+    included: AtomicUsize,
+    /// Заглушки по имени или расширению.
+    skipped_extension: AtomicUsize,
+    /// Заглушки по `--max-file-size`.
+    skipped_size: AtomicUsize,
+    /// Файлы, которые не удалось прочитать.
+    errors: AtomicUsize,
+}
+
+impl FileCounters {
+    fn reset(&self) {
+        for counter in self.all() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn all(&self) -> [&AtomicUsize; 4] {
+        [
+            &self.included,
+            &self.skipped_extension,
+            &self.skipped_size,
+            &self.errors,
+        ]
+    }
+}
+
+impl fmt::Display for FileCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [included, extension, size, errors] = self.all().map(|c| c.load(Ordering::Relaxed));
+        write!(
+            f,
+            "included {}, skipped(ext) {}, skipped(size) {}, errors {}",
+            included, extension, size, errors
+        )
+    }
+}
+
+/// Адаптер, отображающий ход обработки полосой `indicatif` в stderr.
+///
+/// Под полосой выводится путь текущего файла и счетчики прочитанных,
+/// замененных заглушками и нечитаемых файлов; по завершении папки полоса
+/// остается с итоговыми счетчиками. Полоса строится под ширину консоли, а
+/// длинный путь укорачивается с начала.
+#[derive(Debug)]
+pub struct IndicatifProgress {
+    bar: Mutex<Option<ProgressBar>>,
+    /// Ширина консоли, под которую строится полоса.
+    width: usize,
+    counters: Arc<FileCounters>,
+    /// Момент создания адаптера, от которого отсчитывается `last_message`.
+    created: Instant,
+    /// Когда обновлялся путь текущего файла, в миллисекундах от `created`;
+    /// `u64::MAX` — еще не обновлялся.
+    last_message: AtomicU64,
+    /// Вопрос перед большим запуском (см. `with_confirm`).
+    confirm: Option<fn(&RunPreview) -> bool>,
+    /// Вопрос перед удалением всего кэша (см. `with_cache_prune_confirm`).
+    confirm_cache_prune: Option<fn(&CacheStatus) -> bool>,
+    /// Флаг остановки (см. `with_stop_flag`).
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Default for IndicatifProgress {
+    fn default() -> Self {
+        Self {
+            bar: Mutex::new(None),
+            width: terminal::width(&console::Term::stderr(), None),
+            counters: Arc::default(),
+            created: Instant::now(),
+            last_message: AtomicU64::new(u64::MAX),
+            confirm: None,
+            confirm_cache_prune: None,
+            stop: None,
+        }
+    }
+}
+
+impl IndicatifProgress {
+    /// Создает адаптер; полоса появляется при первом `on_start`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Создает адаптер для консоли шириной `width` колонок (`None` — ширина терминала).
+    pub fn with_width(width: Option<usize>) -> Self {
+        Self {
+            width: terminal::width(&console::Term::stderr(), width),
+            ..Self::default()
+        }
+    }
+
+    /// Задает вопрос, который задается перед большим запуском
+    /// (`ProgressSink::confirm_run`); на время вопроса полоса скрывается.
+    /// Без него обработка продолжается без вопроса.
+    pub fn with_confirm(self, confirm: fn(&RunPreview) -> bool) -> Self {
+        Self {
+            confirm: Some(confirm),
+            ..self
+        }
+    }
+
+    /// Задает вопрос перед удалением всего кэша (`ProgressSink::confirm_cache_prune`).
+    /// Без него кэш без `--yes` не удаляется.
+    pub fn with_cache_prune_confirm(self, confirm: fn(&CacheStatus) -> bool) -> Self {
+        Self {
+            confirm_cache_prune: Some(confirm),
+            ..self
+        }
+    }
+
+    /// Задает флаг, установка которого останавливает `--serve`
+    /// (`ProgressSink::stop_requested`); CLI устанавливает его по Ctrl-C.
+    pub fn with_stop_flag(self, stop: Arc<AtomicBool>) -> Self {
+        Self {
+            stop: Some(stop),
+            ..self
+        }
+    }
+
+    /// Стиль полосы с ключами счетчиков `{included}`, `{skipped_ext}`,
+    /// `{skipped_size}` и `{errors}`.
+    fn style(&self) -> ProgressStyle {
+        let template = progress_template(self.width, console::colors_enabled_stderr());
+        let mut style = ProgressStyle::default_bar()
+            .template(&template)
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("#>-");
+        // Ключи в порядке `FileCounters::all`.
+        let keys = ["included", "skipped_ext", "skipped_size", "errors"];
+        for (index, key) in keys.into_iter().enumerate() {
+            let counters = Arc::clone(&self.counters);
+            style = style.with_key(key, move |_: &ProgressState, w: &mut dyn fmt::Write| {
+                let _ = write!(w, "{}", counters.all()[index].load(Ordering::Relaxed));
+            });
+        }
+        style
+    }
+}
+
+impl ProgressSink for IndicatifProgress {
+    fn confirm_run(&self, preview: &RunPreview) -> bool {
+        let Some(confirm) = self.confirm else {
+            return true;
+        };
+        let bar = self.bar.lock().ok().and_then(|current| current.clone());
+        match bar {
+            Some(bar) => bar.suspend(|| confirm(preview)),
+            None => confirm(preview),
+        }
+    }
+
+    fn confirm_cache_prune(&self, status: &CacheStatus) -> bool {
+        self.confirm_cache_prune.is_some_and(|confirm| confirm(status))
+    }
+
+    fn stop_requested(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+
+    fn on_start(&self, total: usize) {
+        self.counters.reset();
+        self.last_message.store(u64::MAX, Ordering::Relaxed);
+        let bar = ProgressBar::new(total as u64).with_style(self.style());
+        if let Ok(mut current) = self.bar.lock() {
+            *current = Some(bar);
+        }
+    }
+
+    fn on_discovered(&self, count: usize) {
+        if let Ok(current) = self.bar.lock()
+            && let Some(bar) = current.as_ref()
+        {
+            bar.inc_length(count as u64);
+        }
+    }
+
+    fn on_file_started(&self, relative: &Path) {
+        let now = self.created.elapsed().as_millis() as u64;
+        let last = self.last_message.load(Ordering::Relaxed);
+        if last != u64::MAX && now.saturating_sub(last) < MESSAGE_INTERVAL.as_millis() as u64 {
+            return;
+        }
+        // Путь обновляет только поток, первым занявший интервал.
+        if self
+            .last_message
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+        if let Ok(current) = self.bar.lock()
+            && let Some(bar) = current.as_ref()
+        {
+            let width = self.width.saturating_sub(MESSAGE_INDENT + 1);
+            let path = relative.display().to_string();
+            bar.set_message(terminal::fit_start(&path, width).into_owned());
+        }
+    }
+
+    fn on_file_done(&self, _path: &Path, _bytes: u64, ok: bool) {
+        let counter = if ok {
+            &self.counters.included
+        } else {
+            &self.counters.errors
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Ok(current) = self.bar.lock()
+            && let Some(bar) = current.as_ref()
+        {
+            bar.inc(1);
+        }
+    }
+
+    fn on_file_skipped(&self, _path: &Path, reason: SkipReason) {
+        let counter = match reason {
+            SkipReason::TooLarge => &self.counters.skipped_size,
+            _ => &self.counters.skipped_extension,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_finish(&self, _summary: &ProgressSummary) {
+        if let Ok(mut current) = self.bar.lock()
+            && let Some(bar) = current.take()
+        {
+            bar.finish_with_message(format!("Done: {}", self.counters));
+        }
+    }
+}
+
+/// Шаблон полосы для консоли шириной `width`: в узкой консоли полоса
+/// короче, а счетчики переносятся на отдельную строку.
+fn progress_template(width: usize, colors: bool) -> String {
+    let bar_width = width.saturating_sub(BAR_LINE_WIDTH).clamp(10, 40);
+    let (spinner, bar, message) = match colors {
+        true => ("{spinner:.green}", format!("{{bar:{}.cyan/blue}}", bar_width), "{wide_msg:.dim}"),
+        false => ("{spinner}", format!("{{bar:{}}}", bar_width), "{wide_msg}"),
+    };
+    let separator = if width >= WIDE_CONSOLE { " " } else { "\n  " };
+    format!(
+        "{} [{{elapsed_precise}}] [{}] {{pos}}/{{len}} ({{eta}}){}{}\n{}{}",
+        spinner,
+        bar,
+        separator,
+        PROGRESS_COUNTERS,
+        " ".repeat(MESSAGE_INDENT),
+        message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use anyhow::Result;
+    use clap::Parser;
+
+    /// Подсчитывает полученные уведомления.
+    #[derive(Default)]
+    struct Counter {
+        started: AtomicUsize,
+        done: AtomicUsize,
+        skipped: Mutex<Vec<SkipReason>>,
+        finished: Mutex<Vec<ProgressSummary>>,
+    }
+
+    impl ProgressSink for Counter {
+        fn on_start(&self, total: usize) {
+            self.started.fetch_add(total, Ordering::Relaxed);
+        }
+
+        fn on_file_done(&self, _path: &Path, _bytes: u64, ok: bool) {
+            assert!(ok);
+            self.done.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_file_skipped(&self, _path: &Path, reason: SkipReason) {
+            self.skipped.lock().unwrap().push(reason);
+        }
+
+        fn on_finish(&self, summary: &ProgressSummary) {
+            self.finished.lock().unwrap().push(*summary);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_with_custom_sink() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src"))?;
+        std::fs::write(root.join("src/main.rs"), "fn main() {}")?;
+        std::fs::write(root.join("README.md"), "# Demo")?;
+        // Бинарная заглушка известна до чтения и в полосу прогресса не входит.
+        std::fs::write(root.join("app.exe"), b"MZ")?;
+
+        let counter = Arc::new(Counter::default());
+        let args = Args::parse_from(["flatten-rust", "-f", root.to_str().unwrap(), "-d"]);
+        crate::run_with(&args, Some(counter.clone())).await?;
+
+        assert_eq!(counter.started.load(Ordering::Relaxed), 2);
+        assert_eq!(counter.done.load(Ordering::Relaxed), 2);
+        assert_eq!(*counter.skipped.lock().unwrap(), vec![SkipReason::Extension]);
+        let finished = counter.finished.lock().unwrap();
+        assert_eq!(
+            *finished,
+            vec![ProgressSummary {
+                files: 2,
+                bytes: 18,
+                failed: 0
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_counters_in_template() {
+        for (width, colors) in [(60, true), (120, false), (200, true)] {
+            let template = progress_template(width, colors);
+            assert!(ProgressStyle::default_bar().template(&template).is_ok());
+            assert_eq!(template.lines().count(), if width < WIDE_CONSOLE { 3 } else { 2 });
+        }
+        assert!(progress_template(60, false).contains("{bar:24}"));
+        let progress = IndicatifProgress::new();
+        progress.on_start(3);
+        progress.on_file_done(Path::new("a.rs"), 10, true);
+        progress.on_file_done(Path::new("b.rs"), 0, false);
+        progress.on_file_skipped(Path::new("big.log"), SkipReason::TooLarge);
+        progress.on_file_skipped(Path::new("app.exe"), SkipReason::Extension);
+        assert_eq!(
+            progress.counters.to_string(),
+            "included 1, skipped(ext) 1, skipped(size) 1, errors 1"
+        );
+        progress.on_start(1);
+        assert_eq!(
+            progress.counters.to_string(),
+            "included 0, skipped(ext) 0, skipped(size) 0, errors 0"
+        );
+    }
+}
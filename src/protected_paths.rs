@@ -0,0 +1,171 @@
+//! Модуль защищенных путей: защита от случайной обработки домашней директории
+//! или корня файловой системы.
+//!
+//! Запуск вроде `-f ~` или `-f /` собирает гигабайты файлов вместе с профилями
+//! браузеров и ключами SSH. Базовая папка считается защищенной, если после
+//! канонизации она является корнем файловой системы, домашней директорией или
+//! путем из `protected_paths` в `~/.flatten/manager_config.json` (например,
+//! сетевым диском), либо содержит такой путь. Такую папку обрабатывает только
+//! запуск с `--allow-protected-paths`.
+
+use crate::config::ManagerConfig;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Почему базовая папка защищена.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Protection {
+    /// Папка — корень файловой системы.
+    Root,
+    /// Папка совпадает с защищенным путем.
+    Is(ProtectedPath),
+    /// Папка содержит защищенный путь.
+    Contains(ProtectedPath),
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protection::Root => write!(f, "it is the filesystem root"),
+            Protection::Is(path) => write!(f, "it is {}", path),
+            Protection::Contains(path) => write!(f, "it contains {}", path),
+        }
+    }
+}
+
+/// Защищенный путь и его источник.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtectedPath {
+    /// Канонический путь.
+    pub path: PathBuf,
+    /// Задан ли путь в `protected_paths` (иначе это домашняя директория).
+    pub configured: bool,
+}
+
+impl fmt::Display for ProtectedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.configured {
+            true => write!(
+                f,
+                "protected path {} (from protected_paths)",
+                self.path.display()
+            ),
+            false => write!(f, "the home directory {}", self.path.display()),
+        }
+    }
+}
+
+/// Список защищенных путей.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectedPaths {
+    paths: Vec<ProtectedPath>,
+}
+
+impl ProtectedPaths {
+    /// Составляет список из домашней директории `home` и путей `configured`.
+    ///
+    /// Пути канонизируются; несуществующий путь сравнивается в записанном виде.
+    pub fn new(home: Option<&Path>, configured: &[PathBuf]) -> Self {
+        let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let home = home.map(|home| ProtectedPath {
+            path: canonical(home),
+            configured: false,
+        });
+        let configured = configured.iter().map(|path| ProtectedPath {
+            path: canonical(path),
+            configured: true,
+        });
+        Self {
+            paths: home.into_iter().chain(configured).collect(),
+        }
+    }
+
+    /// Проверяет базовую папку; `None`, если папка не защищена или не существует.
+    pub fn check(&self, folder: &Path) -> Option<Protection> {
+        let folder = folder.canonicalize().ok()?;
+        if folder.parent().is_none() {
+            return Some(Protection::Root);
+        }
+        if let Some(path) = self.paths.iter().find(|protected| protected.path == folder) {
+            return Some(Protection::Is(path.clone()));
+        }
+        self.paths
+            .iter()
+            .find(|protected| protected.path.starts_with(&folder))
+            .map(|path| Protection::Contains(path.clone()))
+    }
+}
+
+/// Читает `protected_paths` из `manager_config.json` в `flatten_dir`.
+///
+/// Отсутствующий или поврежденный файл означает пустой список, как и для
+/// наборов `--preset`.
+pub fn load_protected_paths(flatten_dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string(flatten_dir.join("manager_config.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<ManagerConfig>(&content).ok())
+        .map(|config| config.protected_paths)
+        .unwrap_or_default()
+}
+
+/// Возвращает домашнюю директорию и пути из `~/.flatten/manager_config.json`.
+pub fn available_protected_paths() -> ProtectedPaths {
+    let home = dirs::home_dir();
+    let configured = home
+        .as_ref()
+        .map(|home| load_protected_paths(&home.join(".flatten")))
+        .unwrap_or_default();
+    ProtectedPaths::new(home.as_deref(), &configured)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protected_paths() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path().canonicalize()?;
+        let home = root.join("home/user");
+        let mount = root.join("mnt/share");
+        std::fs::create_dir_all(home.join("project"))?;
+        std::fs::create_dir_all(&mount)?;
+        std::fs::create_dir_all(root.join(".flatten"))?;
+        std::fs::write(
+            root.join(".flatten/manager_config.json"),
+            format!(r#"{{"protected_paths": ["{}"]}}"#, mount.display()),
+        )?;
+        let configured = load_protected_paths(&root.join(".flatten"));
+        assert_eq!(configured, vec![mount.clone()]);
+        let protected = ProtectedPaths::new(Some(&home), &configured);
+
+        assert_eq!(protected.check(Path::new("/")), Some(Protection::Root));
+        let protection = protected
+            .check(&home.join("project/.."))
+            .expect("home is protected");
+        assert_eq!(
+            protection.to_string(),
+            format!("it is the home directory {}", home.display())
+        );
+        // Папка выше домашней директории тоже защищена.
+        assert!(matches!(
+            protected.check(&root.join("home")),
+            Some(Protection::Contains(ProtectedPath {
+                configured: false,
+                ..
+            }))
+        ));
+        assert_eq!(
+            protected
+                .check(&mount)
+                .map(|protection| protection.to_string()),
+            Some(format!(
+                "it is protected path {} (from protected_paths)",
+                mount.display()
+            ))
+        );
+        assert_eq!(protected.check(&home.join("project")), None);
+        assert_eq!(protected.check(&root.join("missing")), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,1322 @@
+//! Модуль отчета о выполнении.
+//!
+//! `FlattenReport` накапливает сведения о каждом обработанном файле,
+//! пропущенных элементах и работе конвертеров. По нему строится
+//! статистика для консоли (`--stats`) и машиночитаемый JSON (`--stats-json`).
+//! Кроме того, отчет собирает предупреждения и находит файлы с одинаковыми
+//! именами в разных директориях; обе сводки выводятся в конце запуска.
+
+use crate::git_info::LfsState;
+use crate::output::OutputFormat;
+use crate::sanitize::SanitizeCounts;
+use crate::time_limit::Truncation;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Количество файлов в списке самых больших файлов.
+const TOP_FILES: usize = 10;
+/// Количество директорий в списке самых больших директорий.
+const TOP_DIRECTORIES: usize = 5;
+/// Сколько предупреждений и групп одинаковых имен выводится в консоль.
+const REPORT_LIMIT: usize = 50;
+/// Сколько директорий выводится для одной группы одинаковых имен.
+const DUPLICATE_DIRECTORIES: usize = 5;
+/// Сколько пропущенных путей хранится в отчете; счетчики учитывают все.
+pub const SKIPPED_ENTRIES: usize = 1000;
+/// Сколько расширений пропущенных файлов выводится в консоль.
+const SKIPPED_EXTENSIONS: usize = 10;
+/// Среднее число байт на токен для оценки экономии токенов.
+pub(crate) const BYTES_PER_TOKEN: u64 = 4;
+
+/// Причина, по которой файл или папка не попали в вывод.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Скрытый файл или папка.
+    Hidden,
+    /// Папка из списка `--skip-folders`.
+    Folder,
+    /// Расширение из списка `--skip-extensions`.
+    Extension,
+    /// Папка или расширение из включенного шаблона исключений.
+    Template,
+    /// Файл превышает `--max-file-size`.
+    TooLarge,
+    /// Путь исключен правилом `.flattenignore`.
+    FlattenIgnore,
+    /// Lock-файл (`--skip-lockfiles`).
+    Lockfile,
+    /// Файл с маркером генерации (`--skip-generated`).
+    Generated,
+    /// Содержимое файла не прошло фильтр `--grep`.
+    Grep,
+    /// Выходной документ или манифест самого запуска.
+    Output,
+    /// Конвертер заменил содержимое заглушкой.
+    Converter,
+    /// Вложенный репозиторий или подмодуль (`--submodules`).
+    Submodule,
+    /// Файл-указатель Git LFS без скачанного объекта.
+    LfsPointer,
+    /// Файл директории сверх `--dir-file-limit`.
+    DirFileLimit,
+    /// Путь исключен правилом git (`--gitignore`).
+    GitIgnore,
+    /// Файл вне `--content-paths`: есть в дереве, содержимое не выводится.
+    StructureOnly,
+    /// Сторонний код (`--vendored`).
+    Vendored,
+    /// Файл не поместился в бюджет `--fit-budget`.
+    Budget,
+    /// Крейт workspace, исключенный `--exclude-crate`.
+    Crate,
+    /// Файл не обработан до истечения `--time-limit`.
+    TimeLimit,
+    /// Символическая ссылка (`--file-modes`): выводится цель, а не содержимое.
+    Symlink,
+}
+
+impl SkipReason {
+    /// Возвращает короткое имя причины для вывода в консоль.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::Hidden => "hidden",
+            SkipReason::Folder => "folder",
+            SkipReason::Extension => "extension",
+            SkipReason::Template => "template",
+            SkipReason::TooLarge => "size",
+            SkipReason::FlattenIgnore => "flattenignore",
+            SkipReason::Lockfile => "lockfile",
+            SkipReason::Generated => "generated",
+            SkipReason::Grep => "grep",
+            SkipReason::Output => "output",
+            SkipReason::Converter => "converter",
+            SkipReason::Submodule => "submodule",
+            SkipReason::LfsPointer => "lfs",
+            SkipReason::DirFileLimit => "dir limit",
+            SkipReason::GitIgnore => "gitignore",
+            SkipReason::StructureOnly => "structure only",
+            SkipReason::Vendored => "vendored",
+            SkipReason::Budget => "budget",
+            SkipReason::Crate => "crate",
+            SkipReason::TimeLimit => "time limit",
+            SkipReason::Symlink => "symlink",
+        }
+    }
+}
+
+/// Категория предупреждения.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// Указанная в `-f` папка или файл не существует.
+    MissingInput,
+    /// Указанная в `-f` папка или файл пропущены (повтор, вложенность).
+    IgnoredInput,
+    /// Файл из `-f` выведен, хотя правила его пропустили бы.
+    ExplicitFile,
+    /// Директорию не удалось прочитать при обходе.
+    UnreadableDirectory,
+    /// Файл не удалось прочитать.
+    ReadError,
+    /// Выходной документ лежит внутри базовой папки.
+    OutputInsideInput,
+    /// Обход остановлен на глубине `--depth-ceiling`.
+    DepthCeiling,
+    /// Путь длиннее допустимого в системе.
+    PathTooLong,
+    /// Кэш содержимого не удалось очистить.
+    ContentCache,
+    /// Файл изменился между сбором и чтением.
+    StaleFile,
+    /// Один из выходных документов `-o` не удалось записать.
+    OutputFailed,
+}
+
+impl WarningKind {
+    /// Возвращает заголовок категории для вывода в консоль.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::MissingInput => "missing inputs",
+            WarningKind::IgnoredInput => "ignored inputs",
+            WarningKind::ExplicitFile => "explicitly listed files",
+            WarningKind::UnreadableDirectory => "unreadable directories",
+            WarningKind::ReadError => "read errors",
+            WarningKind::OutputInsideInput => "output inside inputs",
+            WarningKind::DepthCeiling => "depth ceiling",
+            WarningKind::PathTooLong => "paths too long",
+            WarningKind::ContentCache => "content cache",
+            WarningKind::StaleFile => "files changed during run",
+            WarningKind::OutputFailed => "failed outputs",
+        }
+    }
+}
+
+/// Предупреждение, выведенное во время запуска.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Warning {
+    /// Категория предупреждения.
+    pub kind: WarningKind,
+    /// Текст предупреждения без префикса `Warning:`.
+    pub message: String,
+}
+
+/// Файлы с одинаковым именем в разных директориях.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateName {
+    /// Имя файла.
+    pub name: String,
+    /// Пути файлов в порядке вывода.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Статистика по файлам, обработанным одним конвертером.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ConversionStats {
+    /// Количество преобразованных файлов.
+    pub files: usize,
+    /// Суммарный исходный размер в байтах.
+    pub original_bytes: u64,
+    /// Суммарный размер выведенного текста в байтах.
+    pub emitted_bytes: u64,
+    /// Примерное число токенов, сэкономленных преобразованием.
+    pub tokens_saved: usize,
+}
+
+impl ConversionStats {
+    fn record(&mut self, original: u64, emitted: u64) {
+        self.files += 1;
+        self.original_bytes += original;
+        self.emitted_bytes += emitted;
+        let saved = self.original_bytes.saturating_sub(self.emitted_bytes);
+        self.tokens_saved = (saved / BYTES_PER_TOKEN) as usize;
+    }
+}
+
+/// Файлы-указатели Git LFS.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct LfsStats {
+    /// Указатели, выведенные заглушкой или как есть.
+    pub pointers: usize,
+    /// Указатели, замененные содержимым объекта (`--lfs-resolve`).
+    pub resolved: usize,
+}
+
+/// Сторонний код, замененный сводкой или пропущенный (`--vendored`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct VendoredStats {
+    /// Директории стороннего кода.
+    pub directories: usize,
+    /// Файлы в них.
+    pub files: usize,
+    /// Суммарный размер этих файлов.
+    pub bytes: u64,
+}
+
+/// Изображения одного формата, замененные заглушками или выведенные как текст.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AssetStats {
+    /// Количество файлов.
+    pub files: usize,
+    /// Их суммарный размер.
+    pub bytes: u64,
+}
+
+/// Файлы одного крейта Cargo workspace, попавшие в вывод.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CrateStats {
+    /// Количество файлов.
+    pub files: usize,
+    /// Их суммарный исходный размер.
+    pub bytes: u64,
+}
+
+/// Число пропущенных файлов и их суммарный размер.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SkipTotals {
+    /// Количество файлов.
+    pub files: usize,
+    /// Их суммарный размер.
+    pub bytes: u64,
+}
+
+impl SkipTotals {
+    fn add(&mut self, files: usize, bytes: u64) {
+        self.files += files;
+        self.bytes += bytes;
+    }
+
+    /// Описание с именем группы: `.png 2 (500 bytes)`.
+    fn describe(&self, name: &str) -> String {
+        format!("{} {} ({})", name, format_count(self.files), format_size(self.bytes))
+    }
+}
+
+impl fmt::Display for SkippedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.directory {
+            write!(
+                f,
+                "{}/ ({} files, {}, {})",
+                self.path.display(),
+                format_count(self.files),
+                format_size(self.bytes),
+                self.reason.as_str()
+            )
+        } else {
+            write!(
+                f,
+                "{} ({}, {})",
+                self.path.display(),
+                format_size(self.bytes),
+                self.reason.as_str()
+            )
+        }
+    }
+}
+
+/// Пропущенный файл или директория.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedEntry {
+    /// Путь в том виде, в котором он указан в выводе.
+    pub path: PathBuf,
+    /// Причина пропуска.
+    pub reason: SkipReason,
+    /// Пропущена ли директория целиком.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub directory: bool,
+    /// Количество файлов (для директории — внутри нее).
+    pub files: usize,
+    /// Их суммарный размер.
+    pub bytes: u64,
+}
+
+/// Содержимое, не попавшее в вывод, по причинам и расширениям.
+///
+/// Файлы внутри пропущенных директорий учитываются, только если статистика
+/// запрошена (`--stats`, `--stats-json`, `--skipped-appendix`): иначе
+/// директории не обходятся. Пути хранятся для первых `SKIPPED_ENTRIES`
+/// элементов, счетчики и размеры — для всех.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedContent {
+    /// Все пропущенные файлы.
+    pub total: SkipTotals,
+    /// Пропущенные файлы по причинам.
+    pub by_reason: BTreeMap<SkipReason, SkipTotals>,
+    /// Пропущенные файлы по расширениям (`.png`; без расширения — `(none)`).
+    pub by_extension: BTreeMap<String, SkipTotals>,
+    /// Первые пропущенные файлы и директории в порядке обнаружения.
+    pub entries: Vec<SkippedEntry>,
+    /// Сколько элементов не вошло в `entries`.
+    pub omitted_entries: usize,
+}
+
+impl SkippedContent {
+    /// Учитывает пропущенный файл.
+    pub fn record_file(&mut self, reason: SkipReason, path: &Path, bytes: u64) {
+        self.count(reason, path, bytes);
+        self.push(SkippedEntry {
+            path: path.to_path_buf(),
+            reason,
+            directory: false,
+            files: 1,
+            bytes,
+        });
+    }
+
+    /// Учитывает пропущенную директорию с файлами `files` (путь и размер).
+    ///
+    /// Возвращает число файлов директории и их суммарный размер.
+    pub fn record_directory(
+        &mut self,
+        reason: SkipReason,
+        path: &Path,
+        files: impl IntoIterator<Item = (PathBuf, u64)>,
+    ) -> SkipTotals {
+        let mut totals = SkipTotals::default();
+        for (file, bytes) in files {
+            self.count(reason, &file, bytes);
+            totals.add(1, bytes);
+        }
+        self.push(SkippedEntry {
+            path: path.to_path_buf(),
+            reason,
+            directory: true,
+            files: totals.files,
+            bytes: totals.bytes,
+        });
+        totals
+    }
+
+    /// Заменяет пути элементов, начиная с `start`, результатом `relabel`.
+    pub(crate) fn relabel_entries(&mut self, start: usize, relabel: impl Fn(&Path) -> PathBuf) {
+        for entry in self.entries.iter_mut().skip(start) {
+            entry.path = relabel(&entry.path);
+        }
+    }
+
+    /// Расширения по убыванию суммарного размера.
+    pub fn extensions_by_size(&self) -> Vec<(&str, SkipTotals)> {
+        let mut extensions: Vec<_> = self
+            .by_extension
+            .iter()
+            .map(|(extension, totals)| (extension.as_str(), *totals))
+            .collect();
+        extensions.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(b.1.files.cmp(&a.1.files)));
+        extensions
+    }
+
+    /// Описание причин: `extension 2 (500 bytes), folder 3 (55 bytes)`.
+    pub fn describe_reasons(&self) -> String {
+        let reasons: Vec<String> = self
+            .by_reason
+            .iter()
+            .map(|(reason, totals)| totals.describe(reason.as_str()))
+            .collect();
+        reasons.join(", ")
+    }
+
+    /// Описание расширений по убыванию размера; не больше `limit`, если он задан.
+    pub fn describe_extensions(&self, limit: Option<usize>) -> String {
+        let extensions = self.extensions_by_size();
+        let shown = limit.unwrap_or(extensions.len()).min(extensions.len());
+        let mut parts: Vec<String> = extensions[..shown]
+            .iter()
+            .map(|(extension, totals)| totals.describe(extension))
+            .collect();
+        if shown < extensions.len() {
+            parts.push(format!("{} more", extensions.len() - shown));
+        }
+        parts.join(", ")
+    }
+
+    fn count(&mut self, reason: SkipReason, path: &Path, bytes: u64) {
+        let extension = path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy().to_lowercase()))
+            .unwrap_or_else(|| "(none)".to_string());
+        self.total.add(1, bytes);
+        self.by_reason.entry(reason).or_default().add(1, bytes);
+        self.by_extension.entry(extension).or_default().add(1, bytes);
+    }
+
+    fn push(&mut self, entry: SkippedEntry) {
+        if self.entries.len() < SKIPPED_ENTRIES {
+            self.entries.push(entry);
+        } else {
+            self.omitted_entries += 1;
+        }
+    }
+}
+
+/// Попадания в кэш обработанного содержимого (`--content-cache`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Файлы, содержимое которых взято из кэша.
+    pub hits: usize,
+    /// Файлы, обработанные заново.
+    pub misses: usize,
+}
+
+/// Что исключили паттерны одного шаблона за время выполнения.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TemplateStats {
+    /// Количество пропущенных директорий.
+    pub dirs_skipped: usize,
+    /// Количество пропущенных файлов (включая файлы внутри пропущенных директорий).
+    pub files_skipped: usize,
+    /// Суммарный размер пропущенных файлов в байтах.
+    pub bytes_saved: u64,
+}
+
+impl TemplateStats {
+    /// Сработал ли шаблон хотя бы один раз.
+    pub fn matched(&self) -> bool {
+        self.dirs_skipped > 0 || self.files_skipped > 0
+    }
+}
+
+/// Сведения об одном файле, попавшем в вывод.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    /// Базовая папка, в которой найден файл.
+    pub base_folder: PathBuf,
+    /// Путь к файлу.
+    pub path: PathBuf,
+    /// Исходный размер файла в байтах.
+    pub original_bytes: u64,
+    /// Сколько байт прочитано с диска (0, если файл заменен заглушкой без чтения).
+    pub read_bytes: u64,
+    /// Размер содержимого, записанного в вывод, в байтах.
+    pub emitted_bytes: u64,
+    /// Причина, по которой содержимое заменено заглушкой.
+    pub skip_reason: Option<SkipReason>,
+    /// Что заменено в содержимом при `--sanitize`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sanitized: Option<SanitizeCounts>,
+}
+
+impl FileReport {
+    /// Возвращает, записано ли вместо содержимого файла заглушка.
+    pub fn is_placeholder(&self) -> bool {
+        self.skip_reason.is_some()
+    }
+}
+
+/// Записанный выходной документ.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputReport {
+    /// Путь документа (`-` — стандартный вывод).
+    pub path: PathBuf,
+    /// Формат документа.
+    pub format: OutputFormat,
+    /// Размер документа в байтах (`None` для стандартного вывода).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+}
+
+/// Суммарный размер директории.
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryReport {
+    /// Путь к директории.
+    pub path: PathBuf,
+    /// Количество файлов внутри (рекурсивно).
+    pub files: usize,
+    /// Суммарный размер выведенного содержимого в байтах.
+    pub emitted_bytes: u64,
+}
+
+/// Отчет о выполнении процесса "сглаживания".
+#[derive(Debug, Default, Clone)]
+pub struct FlattenReport {
+    /// Все файлы, попавшие в вывод, в порядке записи.
+    pub files: Vec<FileReport>,
+    /// Количество пропущенных элементов по причинам.
+    pub skipped: BTreeMap<SkipReason, usize>,
+    /// Пропущенные файлы по причинам и расширениям с размерами.
+    pub skipped_content: SkippedContent,
+    /// Статистика конвертеров по их именам.
+    pub conversions: BTreeMap<&'static str, ConversionStats>,
+    /// Статистика преобразований содержимого по их именам.
+    pub transforms: BTreeMap<&'static str, ConversionStats>,
+    /// Статистика исключений по ключам включенных шаблонов.
+    pub templates: BTreeMap<String, TemplateStats>,
+    /// Указанные базовые папки, которые не существуют.
+    pub missing_folders: Vec<PathBuf>,
+    /// Наибольший объем прочитанных, но еще не записанных файлов (`--max-in-flight-bytes`).
+    pub peak_in_flight_bytes: u64,
+    /// Количество фрагментов содержимого (`--chunk-size`).
+    pub chunks: usize,
+    /// Файлы-указатели Git LFS.
+    pub lfs: LfsStats,
+    /// Сторонний код, не попавший в вывод (`--vendored`).
+    pub vendored: VendoredStats,
+    /// Изображения по форматам (`PNG`, `SVG` и т. д.).
+    pub assets: BTreeMap<&'static str, AssetStats>,
+    /// Файлы по крейтам Cargo workspace.
+    pub crates: BTreeMap<String, CrateStats>,
+    /// Попадания в кэш содержимого (`None` без `--content-cache`).
+    pub content_cache: Option<CacheStats>,
+    /// Обрезка документа по `--time-limit` (`None`, если лимит не истек).
+    pub time_limit: Option<Truncation>,
+    /// Предупреждения в порядке появления.
+    pub warnings: Vec<Warning>,
+    /// Записанные выходные документы в порядке записи.
+    pub outputs: Vec<OutputReport>,
+}
+
+/// Сводная статистика, выводимая в консоль и экспортируемая в JSON.
+#[derive(Debug, Serialize)]
+pub struct StatsSummary {
+    /// Версия утилиты, сформировавшей отчет.
+    pub version: &'static str,
+    /// Количество файлов, содержимое которых попало в вывод.
+    pub total_files: usize,
+    /// Количество файлов, замененных заглушками (по причинам — в `skipped`).
+    pub placeholder_files: usize,
+    /// Суммарный объем, прочитанный с диска.
+    pub read_bytes: u64,
+    /// Суммарный размер выведенного содержимого.
+    pub emitted_bytes: u64,
+    /// Суммарный размер файлов, замененных заглушками без чтения.
+    pub skipped_bytes: u64,
+    /// Самые большие файлы по размеру вывода.
+    pub top_files: Vec<FileReport>,
+    /// Самые большие директории по суммарному размеру вывода.
+    pub top_directories: Vec<DirectoryReport>,
+    /// Файлы, содержимое которых изменено `--sanitize`, в порядке вывода.
+    pub sanitized_files: Vec<FileReport>,
+    /// Количество пропущенных элементов по причинам.
+    pub skipped: BTreeMap<SkipReason, usize>,
+    /// Пропущенные файлы по причинам и расширениям с размерами.
+    pub skipped_content: SkippedContent,
+    /// Статистика конвертеров.
+    pub conversions: BTreeMap<&'static str, ConversionStats>,
+    /// Статистика преобразований содержимого (файлы, которые они изменили).
+    pub transforms: BTreeMap<&'static str, ConversionStats>,
+    /// Статистика исключений по шаблонам.
+    pub templates: BTreeMap<String, TemplateStats>,
+    /// Указанные базовые папки, которые не существуют.
+    pub missing_folders: Vec<PathBuf>,
+    /// Наибольший объем прочитанных, но еще не записанных файлов.
+    pub peak_in_flight_bytes: u64,
+    /// Количество фрагментов содержимого (`--chunk-size`).
+    pub total_chunks: usize,
+    /// Файлы-указатели Git LFS.
+    pub lfs: LfsStats,
+    /// Сторонний код, не попавший в вывод (`--vendored`).
+    pub vendored: VendoredStats,
+    /// Изображения по форматам (`PNG`, `SVG` и т. д.).
+    pub assets: BTreeMap<&'static str, AssetStats>,
+    /// Файлы по крейтам Cargo workspace.
+    pub crates: BTreeMap<String, CrateStats>,
+    /// Попадания в кэш содержимого (`--content-cache`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_cache: Option<CacheStats>,
+    /// Файлы, не обработанные до истечения `--time-limit`.
+    pub unprocessed_files: usize,
+    /// Обрезка документа по `--time-limit`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_limit: Option<Truncation>,
+    /// Предупреждения в порядке появления.
+    pub warnings: Vec<Warning>,
+    /// Группы файлов с одинаковыми именами в разных директориях.
+    pub duplicate_names: Vec<DuplicateName>,
+    /// Записанные выходные документы.
+    pub outputs: Vec<OutputReport>,
+}
+
+impl FlattenReport {
+    /// Создает отчет, в котором каждый включенный шаблон учитывается даже без срабатываний.
+    pub fn with_templates<'a>(templates: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            templates: templates
+                .into_iter()
+                .map(|key| (key.to_string(), TemplateStats::default()))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Регистрирует файл, записанный в вывод (в том числе заглушкой).
+    pub fn record_file(&mut self, file: FileReport) {
+        self.files.push(file);
+    }
+
+    /// Регистрирует пропущенный элемент.
+    pub fn record_skip(&mut self, reason: SkipReason) {
+        *self.skipped.entry(reason).or_default() += 1;
+    }
+
+    /// Регистрирует пропущенный файл `path` размером `bytes`.
+    pub fn record_skipped_file(&mut self, reason: SkipReason, path: &Path, bytes: u64) {
+        self.record_skip(reason);
+        self.skipped_content.record_file(reason, path, bytes);
+    }
+
+    /// Регистрирует пропущенную директорию `path` с файлами `files`.
+    ///
+    /// Возвращает число файлов директории и их суммарный размер.
+    pub fn record_skipped_directory(
+        &mut self,
+        reason: SkipReason,
+        path: &Path,
+        files: impl IntoIterator<Item = (PathBuf, u64)>,
+    ) -> SkipTotals {
+        self.record_skip(reason);
+        self.skipped_content.record_directory(reason, path, files)
+    }
+
+    /// Регистрирует элемент, исключенный паттерном шаблона `template`.
+    ///
+    /// Для директории `files` и `bytes` описывают ее содержимое.
+    pub fn record_template_skip(&mut self, template: &str, is_dir: bool, files: usize, bytes: u64) {
+        let stats = self.templates.entry(template.to_string()).or_default();
+        if is_dir {
+            stats.dirs_skipped += 1;
+        }
+        stats.files_skipped += files;
+        stats.bytes_saved += bytes;
+    }
+
+    /// Сохраняет предупреждение для итоговой сводки.
+    pub fn record_warning(&mut self, kind: WarningKind, message: String) {
+        self.warnings.push(Warning { kind, message });
+    }
+
+    /// Выводит предупреждение в stderr и сохраняет его для итоговой сводки.
+    pub fn warn(&mut self, kind: WarningKind, message: String) {
+        eprintln!("Warning: {}", message);
+        self.record_warning(kind, message);
+    }
+
+    /// Находит файлы вывода, у которых совпадает имя, но отличается директория.
+    ///
+    /// Группы упорядочены по числу файлов (по убыванию), затем по имени.
+    pub fn duplicate_names(&self) -> Vec<DuplicateName> {
+        let mut by_name: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for file in &self.files {
+            if let Some(name) = file.path.file_name() {
+                let paths = by_name
+                    .entry(name.to_string_lossy().into_owned())
+                    .or_default();
+                if !paths.contains(&file.path) {
+                    paths.push(file.path.clone());
+                }
+            }
+        }
+        let mut duplicates: Vec<DuplicateName> = by_name
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|(name, paths)| DuplicateName { name, paths })
+            .collect();
+        duplicates.sort_by(|a, b| {
+            b.paths
+                .len()
+                .cmp(&a.paths.len())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        duplicates
+    }
+
+    /// Записывает сводку предупреждений и групп одинаковых имен файлов.
+    ///
+    /// Каждая сводка ограничена `REPORT_LIMIT` записями; об остальных сообщается числом.
+    pub fn write_warnings(&self, output: &mut dyn Write) -> std::io::Result<()> {
+        if !self.warnings.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Warnings ({}):", self.warnings.len())?;
+            let mut by_kind: BTreeMap<WarningKind, Vec<&str>> = BTreeMap::new();
+            for warning in &self.warnings {
+                by_kind
+                    .entry(warning.kind)
+                    .or_default()
+                    .push(&warning.message);
+            }
+            let mut shown = 0;
+            for (kind, messages) in &by_kind {
+                if shown == REPORT_LIMIT {
+                    break;
+                }
+                writeln!(output, "  {} ({}):", kind.as_str(), messages.len())?;
+                for message in messages.iter().take(REPORT_LIMIT - shown) {
+                    writeln!(output, "    {}", message)?;
+                    shown += 1;
+                }
+            }
+            if shown < self.warnings.len() {
+                writeln!(output, "  ... and {} more", self.warnings.len() - shown)?;
+            }
+        }
+
+        let duplicates = self.duplicate_names();
+        if !duplicates.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Files sharing a name ({}):", duplicates.len())?;
+            for duplicate in duplicates.iter().take(REPORT_LIMIT) {
+                let directories: Vec<String> = duplicate
+                    .paths
+                    .iter()
+                    .take(DUPLICATE_DIRECTORIES)
+                    .map(|path| {
+                        let parent = path.parent().unwrap_or(Path::new(""));
+                        parent.display().to_string()
+                    })
+                    .collect();
+                let mut line = directories.join(", ");
+                let more = duplicate.paths.len().saturating_sub(DUPLICATE_DIRECTORIES);
+                if more > 0 {
+                    line.push_str(&format!(", ... and {} more", more));
+                }
+                writeln!(
+                    output,
+                    "  {} ({}): {}",
+                    duplicate.name,
+                    duplicate.paths.len(),
+                    line
+                )?;
+            }
+            if duplicates.len() > REPORT_LIMIT {
+                writeln!(output, "  ... and {} more", duplicates.len() - REPORT_LIMIT)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Регистрирует работу конвертера над одним файлом.
+    pub fn record_conversion(&mut self, name: &'static str, original: u64, emitted: u64) {
+        self.conversions
+            .entry(name)
+            .or_default()
+            .record(original, emitted);
+    }
+
+    /// Регистрирует изменение одного файла преобразованием содержимого.
+    pub fn record_transform(&mut self, name: &'static str, before: u64, after: u64) {
+        self.transforms.entry(name).or_default().record(before, after);
+    }
+
+    /// Регистрирует файл-указатель Git LFS.
+    pub fn record_lfs(&mut self, state: LfsState) {
+        match state {
+            LfsState::Pointer => self.lfs.pointers += 1,
+            LfsState::Resolved => self.lfs.resolved += 1,
+        }
+    }
+
+    /// Регистрирует директорию стороннего кода с `files` файлами общим размером `bytes`.
+    pub fn record_vendored(&mut self, files: usize, bytes: u64) {
+        self.vendored.directories += 1;
+        self.vendored.files += files;
+        self.vendored.bytes += bytes;
+    }
+
+    /// Регистрирует изображение формата `format` размером `bytes`.
+    pub fn record_asset(&mut self, format: &'static str, bytes: u64) {
+        let stats = self.assets.entry(format).or_default();
+        stats.files += 1;
+        stats.bytes += bytes;
+    }
+
+    /// Регистрирует файл размером `bytes` из крейта workspace `name`.
+    pub fn record_crate(&mut self, name: &str, bytes: u64) {
+        let stats = self.crates.entry(name.to_string()).or_default();
+        stats.files += 1;
+        stats.bytes += bytes;
+    }
+
+    /// Суммарный объем, прочитанный с диска.
+    pub fn read_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.read_bytes).sum()
+    }
+
+    /// Строит сводную статистику, включая списки самых больших файлов и директорий.
+    pub fn summary(&self) -> StatsSummary {
+        let mut top_files = self.files.clone();
+        top_files.sort_by(|a, b| {
+            b.emitted_bytes
+                .cmp(&a.emitted_bytes)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        top_files.truncate(TOP_FILES);
+
+        // Суммируем размеры по всем директориям-предкам внутри базовой папки.
+        let mut directories: HashMap<&Path, (usize, u64)> = HashMap::new();
+        for file in &self.files {
+            let mut current = file.path.parent();
+            while let Some(dir) = current {
+                if dir == file.base_folder || !dir.starts_with(&file.base_folder) {
+                    break;
+                }
+                let entry = directories.entry(dir).or_default();
+                entry.0 += 1;
+                entry.1 += file.emitted_bytes;
+                current = dir.parent();
+            }
+        }
+        let mut top_directories: Vec<DirectoryReport> = directories
+            .into_iter()
+            .map(|(path, (files, emitted_bytes))| DirectoryReport {
+                path: path.to_path_buf(),
+                files,
+                emitted_bytes,
+            })
+            .collect();
+        top_directories.sort_by(|a, b| {
+            b.emitted_bytes
+                .cmp(&a.emitted_bytes)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        top_directories.truncate(TOP_DIRECTORIES);
+
+        StatsSummary {
+            version: env!("CARGO_PKG_VERSION"),
+            total_files: self.files.iter().filter(|f| !f.is_placeholder()).count(),
+            placeholder_files: self.files.iter().filter(|f| f.is_placeholder()).count(),
+            read_bytes: self.read_bytes(),
+            emitted_bytes: self.files.iter().map(|f| f.emitted_bytes).sum(),
+            skipped_bytes: self
+                .files
+                .iter()
+                .map(|f| f.original_bytes.saturating_sub(f.read_bytes))
+                .sum(),
+            top_files,
+            top_directories,
+            sanitized_files: self
+                .files
+                .iter()
+                .filter(|f| f.sanitized.is_some())
+                .cloned()
+                .collect(),
+            skipped: self.skipped.clone(),
+            skipped_content: self.skipped_content.clone(),
+            conversions: self.conversions.clone(),
+            transforms: self.transforms.clone(),
+            templates: self.templates.clone(),
+            missing_folders: self.missing_folders.clone(),
+            peak_in_flight_bytes: self.peak_in_flight_bytes,
+            total_chunks: self.chunks,
+            lfs: self.lfs,
+            vendored: self.vendored,
+            assets: self.assets.clone(),
+            crates: self.crates.clone(),
+            content_cache: self.content_cache,
+            unprocessed_files: self.time_limit.map_or(0, |t| t.unprocessed_files),
+            time_limit: self.time_limit,
+            warnings: self.warnings.clone(),
+            duplicate_names: self.duplicate_names(),
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    /// Выводит статистику в консоль.
+    pub fn print_stats(&self) {
+        let _ = self.write_stats(&mut std::io::stdout());
+    }
+
+    /// Записывает статистику в `output`.
+    pub fn write_stats(&self, output: &mut dyn Write) -> std::io::Result<()> {
+        let summary = self.summary();
+        writeln!(
+            output,
+            "Total bytes read: {}",
+            format_size(summary.read_bytes)
+        )?;
+        writeln!(
+            output,
+            "Total bytes emitted: {}",
+            format_size(summary.emitted_bytes)
+        )?;
+        if summary.placeholder_files > 0 {
+            writeln!(
+                output,
+                "Replaced by placeholders: {} files, {} not read",
+                summary.placeholder_files,
+                format_size(summary.skipped_bytes)
+            )?;
+        }
+
+        if summary.total_files > 0 {
+            let avg_size = summary.read_bytes / summary.total_files as u64;
+            writeln!(output, "Average file size: {}", format_size(avg_size))?;
+        }
+
+        if summary.peak_in_flight_bytes > 0 {
+            writeln!(
+                output,
+                "Peak in-flight bytes: {}",
+                format_size(summary.peak_in_flight_bytes)
+            )?;
+        }
+
+        if summary.total_chunks > 0 {
+            writeln!(output, "Chunks produced: {}", summary.total_chunks)?;
+        }
+
+        if summary.lfs.pointers + summary.lfs.resolved > 0 {
+            writeln!(
+                output,
+                "Git LFS pointers: {} unresolved, {} resolved",
+                summary.lfs.pointers, summary.lfs.resolved
+            )?;
+        }
+
+        if summary.vendored.directories > 0 {
+            writeln!(
+                output,
+                "Vendored code left out: {} directories, {} files ({})",
+                summary.vendored.directories,
+                summary.vendored.files,
+                format_size(summary.vendored.bytes)
+            )?;
+        }
+
+        if !summary.assets.is_empty() {
+            let parts: Vec<String> = summary
+                .assets
+                .iter()
+                .map(|(format, stats)| {
+                    format!("{} {} ({})", format, stats.files, format_size(stats.bytes))
+                })
+                .collect();
+            writeln!(output, "Images: {}", parts.join(", "))?;
+        }
+
+        if let Some(cache) = summary.content_cache {
+            writeln!(
+                output,
+                "Content cache: {} hits, {} misses",
+                cache.hits, cache.misses
+            )?;
+        }
+
+        if let Some(truncation) = summary.time_limit {
+            writeln!(output, "Stopped early: {}", truncation)?;
+        }
+
+        for (name, stats) in &summary.conversions {
+            write!(
+                output,
+                "Converted {} files: {} ({} -> {})",
+                name,
+                stats.files,
+                format_size(stats.original_bytes),
+                format_size(stats.emitted_bytes)
+            )?;
+            if stats.tokens_saved > 0 {
+                write!(output, ", ~{} tokens saved", format_count(stats.tokens_saved))?;
+            }
+            writeln!(output)?;
+        }
+
+        for (name, stats) in &summary.transforms {
+            writeln!(
+                output,
+                "Transform {}: {} files ({} -> {})",
+                name,
+                stats.files,
+                format_size(stats.original_bytes),
+                format_size(stats.emitted_bytes)
+            )?;
+        }
+
+        if !summary.missing_folders.is_empty() {
+            let folders: Vec<String> = summary
+                .missing_folders
+                .iter()
+                .map(|folder| folder.display().to_string())
+                .collect();
+            writeln!(output, "Missing folders: {}", folders.join(", "))?;
+        }
+
+        if !summary.skipped.is_empty() {
+            let parts: Vec<String> = summary
+                .skipped
+                .iter()
+                .map(|(reason, count)| format!("{} {}", reason.as_str(), count))
+                .collect();
+            writeln!(output, "Skipped by reason: {}", parts.join(", "))?;
+        }
+
+        let skipped = &summary.skipped_content;
+        if skipped.total.files > 0 {
+            writeln!(
+                output,
+                "Skipped content: {} files ({})",
+                format_count(skipped.total.files),
+                format_size(skipped.total.bytes)
+            )?;
+            writeln!(output, "  by reason: {}", skipped.describe_reasons())?;
+            writeln!(
+                output,
+                "  by extension: {}",
+                skipped.describe_extensions(Some(SKIPPED_EXTENSIONS))
+            )?;
+        }
+
+        if !summary.templates.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Template exclusions:")?;
+            writeln!(
+                output,
+                "  {:<20} {:>8} {:>8} {:>12}",
+                "template", "dirs", "files", "saved"
+            )?;
+            for (key, stats) in &summary.templates {
+                writeln!(
+                    output,
+                    "  {:<20} {:>8} {:>8} {:>12}",
+                    key,
+                    format_count(stats.dirs_skipped),
+                    format_count(stats.files_skipped),
+                    format_size(stats.bytes_saved)
+                )?;
+            }
+            let unused: Vec<&str> = summary
+                .templates
+                .iter()
+                .filter(|(_, stats)| !stats.matched())
+                .map(|(key, _)| key.as_str())
+                .collect();
+            if !unused.is_empty() {
+                writeln!(
+                    output,
+                    "Templates that matched nothing: {}",
+                    unused.join(", ")
+                )?;
+            }
+        }
+
+        if !summary.top_files.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Largest files:")?;
+            for file in &summary.top_files {
+                writeln!(
+                    output,
+                    "  {:>12}  {}",
+                    format_size(file.emitted_bytes),
+                    file.path.display()
+                )?;
+            }
+        }
+
+        if !summary.top_directories.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Largest directories:")?;
+            for dir in &summary.top_directories {
+                writeln!(
+                    output,
+                    "  {:>12}  {} ({} files)",
+                    format_size(dir.emitted_bytes),
+                    dir.path.display(),
+                    dir.files
+                )?;
+            }
+        }
+
+        if !summary.crates.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Workspace crates:")?;
+            for (name, stats) in &summary.crates {
+                writeln!(
+                    output,
+                    "  {:>12}  {} ({} files)",
+                    format_size(stats.bytes),
+                    name,
+                    stats.files
+                )?;
+            }
+        }
+
+        if !summary.sanitized_files.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Sanitized files:")?;
+            for file in &summary.sanitized_files {
+                if let Some(counts) = &file.sanitized {
+                    writeln!(output, "  {}: {}", file.path.display(), counts)?;
+                }
+            }
+        }
+
+        if !summary.outputs.is_empty() {
+            writeln!(output)?;
+            writeln!(output, "Outputs:")?;
+            for document in &summary.outputs {
+                let size = document.bytes.map(format_size).unwrap_or_else(|| "-".to_string());
+                writeln!(
+                    output,
+                    "  {:>12}  {} ({})",
+                    size,
+                    document.path.display(),
+                    document.format.extension()
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Записывает сводную статистику в JSON-файл.
+    pub fn write_stats_json(&self, path: &Path) -> Result<()> {
+        let content =
+            serde_json::to_string_pretty(&self.summary()).context("Failed to serialize stats")?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write stats file: {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Форматирует размер в байтах в человекочитаемом виде.
+pub fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = 1_048_576.0;
+
+    if bytes as f64 >= MB {
+        format!("{:.2} MB", bytes as f64 / MB)
+    } else if bytes as f64 >= KB {
+        format!("{:.2} KB", bytes as f64 / KB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// Разбирает размер вида `1048576`, `512KB`, `256MB` или `2GB` (единицы по 1024 байта).
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {}", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Unknown size unit in {}: use B, KB, MB or GB", value)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size is too large: {}", value))
+}
+
+/// Форматирует число с разделителями разрядов (например, `1,204,332`).
+pub fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(ch);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_204_332), "1,204,332");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Ok(1_048_576));
+        assert_eq!(parse_size("512KB"), Ok(512 * 1024));
+        assert_eq!(parse_size("256 mb"), Ok(256 * 1024 * 1024));
+        assert_eq!(parse_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("MB").is_err());
+    }
+
+    #[test]
+    fn test_summary_top_files_and_directories() {
+        let base = Path::new("/repo");
+        let mut report = FlattenReport::default();
+        for (path, size) in [
+            ("/repo/README.md", 10),
+            ("/repo/src/big.rs", 500),
+            ("/repo/src/util/small.rs", 20),
+            ("/repo/docs/guide.md", 100),
+        ] {
+            report.record_file(FileReport {
+                base_folder: base.to_path_buf(),
+                path: PathBuf::from(path),
+                original_bytes: size,
+                read_bytes: size,
+                emitted_bytes: size,
+                skip_reason: None,
+                sanitized: None,
+            });
+        }
+        report.record_skipped_file(SkipReason::Extension, Path::new("/repo/logo.png"), 400);
+        report.record_skipped_file(SkipReason::Extension, Path::new("/repo/icon.PNG"), 100);
+        report.record_skipped_file(SkipReason::Lockfile, Path::new("/repo/Cargo.lock"), 50);
+        let files = [("a.js", 30), ("b.js", 20), ("LICENSE", 5)]
+            .map(|(name, bytes)| (PathBuf::from("/repo/node_modules").join(name), bytes));
+        let totals = report.record_skipped_directory(
+            SkipReason::Folder,
+            Path::new("/repo/node_modules"),
+            files,
+        );
+        assert_eq!(totals, SkipTotals { files: 3, bytes: 55 });
+
+        let summary = report.summary();
+        assert_eq!(summary.total_files, 4);
+        assert_eq!(summary.top_files[0].path, Path::new("/repo/src/big.rs"));
+        assert_eq!(summary.top_directories[0].path, Path::new("/repo/src"));
+        assert_eq!(summary.top_directories[0].emitted_bytes, 520);
+        assert_eq!(summary.top_directories[0].files, 2);
+        assert!(summary.top_directories.iter().all(|d| d.path != base));
+        assert_eq!(summary.skipped[&SkipReason::Extension], 2);
+        assert_eq!(summary.skipped[&SkipReason::Folder], 1);
+
+        let skipped = &summary.skipped_content;
+        assert_eq!(skipped.total, SkipTotals { files: 6, bytes: 605 });
+        assert_eq!(skipped.by_reason[&SkipReason::Folder], SkipTotals { files: 3, bytes: 55 });
+        assert_eq!(skipped.by_extension[".png"], SkipTotals { files: 2, bytes: 500 });
+        assert_eq!(skipped.by_extension["(none)"], SkipTotals { files: 1, bytes: 5 });
+        assert_eq!(skipped.extensions_by_size()[0].0, ".png");
+        assert_eq!(skipped.entries.len(), 4);
+        assert!(skipped.entries[3].directory);
+
+        let mut stats = Vec::new();
+        report.write_stats(&mut stats).unwrap();
+        let stats = String::from_utf8(stats).unwrap();
+        assert!(stats.contains("Skipped content: 6 files (605 bytes)\n"), "{}", stats);
+        let extensions =
+            ".png 2 (500 bytes), .js 2 (50 bytes), .lock 1 (50 bytes), (none) 1 (5 bytes)";
+        assert!(stats.contains(&format!("  by extension: {}\n", extensions)), "{}", stats);
+    }
+
+    #[test]
+    fn test_skipped_entries_are_capped() {
+        let mut content = SkippedContent::default();
+        for index in 0..SKIPPED_ENTRIES + 5 {
+            content.record_file(SkipReason::Hidden, Path::new(&format!(".f{}", index)), 1);
+        }
+        assert_eq!(content.total.files, SKIPPED_ENTRIES + 5);
+        assert_eq!(content.entries.len(), SKIPPED_ENTRIES);
+        assert_eq!(content.omitted_entries, 5);
+    }
+
+    #[test]
+    fn test_template_stats_track_unmatched_templates() {
+        let mut report = FlattenReport::with_templates(["node", "rust"]);
+        report.record_template_skip("rust", true, 3, 300);
+        report.record_template_skip("rust", false, 1, 10);
+
+        let summary = report.summary();
+        let rust = &summary.templates["rust"];
+        assert_eq!(rust.dirs_skipped, 1);
+        assert_eq!(rust.files_skipped, 4);
+        assert_eq!(rust.bytes_saved, 310);
+        assert!(!summary.templates["node"].matched());
+    }
+
+    #[test]
+    fn test_warnings_summary_and_duplicate_names() -> std::io::Result<()> {
+        let mut report = FlattenReport::default();
+        let paths = ["/repo/a/index.ts", "/repo/b/index.ts", "/repo/c/index.ts", "/repo/a/x.ts"];
+        for path in paths {
+            report.record_file(FileReport {
+                base_folder: PathBuf::from("/repo"),
+                path: PathBuf::from(path),
+                original_bytes: 1,
+                read_bytes: 1,
+                emitted_bytes: 1,
+                skip_reason: None,
+                sanitized: None,
+            });
+        }
+        report.record_warning(WarningKind::ReadError, "/repo/a/y.ts: denied".to_string());
+        for index in 0..REPORT_LIMIT + 1 {
+            report.record_warning(WarningKind::MissingInput, format!("missing{}", index));
+        }
+
+        let duplicates = report.duplicate_names();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].name, "index.ts");
+        assert_eq!(duplicates[0].paths.len(), 3);
+
+        let mut output = Vec::new();
+        report.write_warnings(&mut output)?;
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.contains("Warnings (52):\n  missing inputs (51):\n    missing0\n"));
+        assert!(output.contains("    missing49\n  ... and 2 more\n"));
+        assert!(!output.contains("read errors"));
+        assert!(output.contains("Files sharing a name (1):\n"));
+        assert!(output.contains("  index.ts (3): /repo/a, /repo/b, /repo/c\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! Модуль очистки содержимого для встраивания в другие документы (`--sanitize`).
+//!
+//! Управляющие символы, кроме табуляции и перевода строки, заменяются видимыми
+//! экранами (`\x0c`, `\u0085`), как и разделители строк Unicode (U+2028,
+//! U+2029), которые YAML и некоторые Markdown-парсеры считают переводами строк.
+//! Escape-последовательности ANSI удаляются целиком. Очищенный текст можно
+//! вставить в YAML-блок или чужой Markdown-документ: из управляющих символов
+//! в нем остаются только табуляции и переводы строк.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fmt::Write;
+
+/// Символ ESC, с которого начинаются escape-последовательности ANSI.
+const ESC: u8 = 0x1b;
+
+/// Что заменено или удалено при очистке одного файла.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SanitizeCounts {
+    /// Управляющие символы, замененные экранами.
+    pub control_chars: usize,
+    /// Разделители строк Unicode (U+0085, U+2028, U+2029), замененные экранами.
+    pub line_separators: usize,
+    /// Удаленные escape-последовательности ANSI.
+    pub ansi_sequences: usize,
+}
+
+impl fmt::Display for SanitizeCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = [
+            (self.control_chars, "control chars"),
+            (self.line_separators, "line separators"),
+            (self.ansi_sequences, "ANSI sequences"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Очищает текст; `None`, если в нем нечего заменять.
+pub fn sanitize(text: &str) -> Option<(String, SanitizeCounts)> {
+    text.find(needs_escape)?;
+    let mut output = String::with_capacity(text.len() + 16);
+    let mut counts = SanitizeCounts::default();
+    let mut position = 0;
+    while let Some(offset) = text[position..].find(needs_escape) {
+        let index = position + offset;
+        output.push_str(&text[position..index]);
+        let c = text[index..].chars().next().expect("found above");
+        position = index + c.len_utf8();
+        if c == char::from(ESC)
+            && let Some(length) = ansi_sequence_len(&text.as_bytes()[index..])
+        {
+            counts.ansi_sequences += 1;
+            position = index + length;
+        } else if is_line_separator(c) {
+            counts.line_separators += 1;
+            let _ = write!(output, "\\u{:04x}", u32::from(c));
+        } else if c.is_ascii() {
+            counts.control_chars += 1;
+            let _ = write!(output, "\\x{:02x}", u32::from(c));
+        } else {
+            counts.control_chars += 1;
+            let _ = write!(output, "\\u{:04x}", u32::from(c));
+        }
+    }
+    output.push_str(&text[position..]);
+    Some((output, counts))
+}
+
+/// Нужно ли заменить символ: управляющий (кроме табуляции и перевода строки)
+/// или разделитель строк.
+fn needs_escape(c: char) -> bool {
+    (c.is_control() && c != '\t' && c != '\n') || is_line_separator(c)
+}
+
+/// Считают ли символ переводом строки YAML и JavaScript.
+fn is_line_separator(c: char) -> bool {
+    matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+/// Длина escape-последовательности ANSI в начале `bytes` (начинаются с ESC).
+///
+/// `None`, если последовательность не завершена: тогда экранируется только ESC.
+/// Строковые последовательности (OSC, DCS и подобные) не продолжаются за
+/// перевод строки, чтобы случайный ESC не поглотил текст файла.
+fn ansi_sequence_len(bytes: &[u8]) -> Option<usize> {
+    let kind = *bytes.get(1)?;
+    match kind {
+        // CSI: параметры и промежуточные байты, затем завершающий байт.
+        b'[' => {
+            let end = 2 + bytes[2..].iter().position(|b| !(0x20..=0x3f).contains(b))?;
+            (0x40..=0x7e).contains(&bytes[end]).then_some(end + 1)
+        }
+        // Строковые последовательности завершаются ST (`ESC \`), OSC — еще и BEL.
+        b']' | b'P' | b'X' | b'^' | b'_' => {
+            for (index, byte) in bytes.iter().enumerate().skip(2) {
+                match *byte {
+                    0x07 if kind == b']' => return Some(index + 1),
+                    ESC => return (bytes.get(index + 1) == Some(&b'\\')).then_some(index + 2),
+                    b'\n' => return None,
+                    _ => {}
+                }
+            }
+            None
+        }
+        // Остальные: промежуточные байты и завершающий байт (например, `ESC 7`, `ESC (B`).
+        _ => {
+            let end = 1 + bytes[1..].iter().position(|b| !(0x20..=0x2f).contains(b))?;
+            (0x30..=0x7e).contains(&bytes[end]).then_some(end + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("fn main() {}\n\tok\n"), None);
+
+        let text = "\x1b[1;31mred\x1b[0m page\x0cbreak\x0b\r\n\
+            \x1b]0;title\x07a\u{2028}b\u{85}\x7f \x1b(B\x1b\x01 end\x1b";
+        let (sanitized, counts) = sanitize(text).expect("text has control characters");
+        assert_eq!(
+            sanitized,
+            "red page\\x0cbreak\\x0b\\x0d\na\\u2028b\\u0085\\x7f \\x1b\\x01 end\\x1b"
+        );
+        assert_eq!(
+            counts,
+            SanitizeCounts {
+                control_chars: 7,
+                line_separators: 2,
+                ansi_sequences: 4,
+            }
+        );
+        assert_eq!(counts.to_string(), "7 control chars, 2 line separators, 4 ANSI sequences");
+        assert!(sanitized.chars().all(|c| !needs_escape(c)));
+    }
+}
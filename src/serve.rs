@@ -0,0 +1,420 @@
+//! Модуль предпросмотра документа по HTTP (`--serve`).
+//!
+//! Небольшой сервер на `std::net` (один поток принимает соединения, запросы
+//! обслуживаются по очереди) отдает HTML-версию
+//! документа по `/`, Markdown по `/raw` и статистику `--stats-json` по
+//! `/stats.json`. Документ формируется заново на каждый запрос тем же
+//! конвейером, что и `run_to_writer`, поэтому изменения файлов и `.flattenignore`
+//! видны после обновления страницы. По умолчанию сервер слушает только
+//! `127.0.0.1` и останавливается по запросу `ProgressSink::stop_requested`
+//! (в CLI — по Ctrl-C), удаляя временные файлы запуска.
+//!
+//! Привязка к `127.0.0.1` не защищает от DNS rebinding: страница чужого
+//! сайта, имя которого стало указывать на `127.0.0.1`, может читать ответы
+//! сервера. Поэтому запрос отвергается с 403, если его заголовок `Host` не
+//! совпадает с `localhost` или IP-адресом, на который пришло соединение,
+//! вместе с портом сервера.
+
+use crate::error::FlattenError;
+use crate::output::OutputFormat;
+use crate::report::FlattenReport;
+use crate::{Args, FlattenConfig, in_pool, write_document};
+use anyhow::{Context, Result};
+use crate::progress::ProgressSink;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+/// Адрес сервера, если `--serve` указан без значения.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+/// Как часто поток соединений проверяет, не пора ли остановиться, пока нет запросов.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Сколько ждать заголовков запроса от клиента.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Наибольший размер заголовков запроса.
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// Разбирает адрес `--serve`: `host:port` или только порт (на `127.0.0.1`).
+pub fn parse_address(value: &str) -> Result<SocketAddr> {
+    let value = value.trim();
+    if let Ok(port) = value.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    value
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .with_context(|| format!("Invalid --serve address `{}`: expected host:port or port", value))
+}
+
+/// Запускает сервер предпросмотра и обслуживает запросы, пока `progress` не
+/// попросит остановиться (`ProgressSink::stop_requested`; CLI — по Ctrl-C).
+///
+/// Соединения принимаются в отдельном потоке, который передает прочитанные
+/// запросы в очередь; документы формируются асинхронно, не блокируя рантайм
+/// ожиданием соединений.
+///
+/// # Ошибки
+/// Если адрес не удалось занять или принять соединение.
+pub async fn serve(
+    args: &Args,
+    address: &str,
+    pool: Option<&rayon::ThreadPool>,
+    progress: Option<Arc<dyn ProgressSink>>,
+) -> Result<()> {
+    let address = parse_address(address)?;
+    let listener = TcpListener::bind(address)
+        .with_context(|| format!("Failed to bind preview server to {}", address))?;
+    listener.set_nonblocking(true)?;
+
+    println!(
+        "Serving preview at http://{}/ (raw markdown at /raw, stats at /stats.json); press Ctrl-C to stop",
+        listener.local_addr()?
+    );
+    std::io::stdout().flush()?;
+
+    let queue = Arc::new(RequestQueue::default());
+    let accepting = {
+        let queue = Arc::clone(&queue);
+        let stopped = move || progress.as_ref().is_some_and(|progress| progress.stop_requested());
+        std::thread::spawn(move || accept_requests(&listener, &queue, stopped))
+    };
+    while let Some(request) = queue.next().await {
+        if let Err(error) = respond(args, pool, request).await {
+            eprintln!("Warning: preview request failed: {:#}", error);
+        }
+    }
+    accepting
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+    println!("Preview server stopped");
+    Ok(())
+}
+
+/// Принимает соединения и читает их запросы в очередь `queue`, пока не
+/// `stopped`; по завершении закрывает очередь.
+fn accept_requests(
+    listener: &TcpListener,
+    queue: &RequestQueue,
+    stopped: impl Fn() -> bool,
+) -> Result<()> {
+    let result = loop {
+        if stopped() {
+            break Ok(());
+        }
+        match listener.accept() {
+            Ok((stream, _)) => match Request::read(stream) {
+                Ok(request) => queue.push(request),
+                Err(error) => eprintln!("Warning: preview request failed: {:#}", error),
+            },
+            // Поток принимает только соединения: ожидание не задерживает ответы.
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL)
+            }
+            Err(error) => break Err(error).context("Failed to accept preview connection"),
+        }
+    };
+    queue.close();
+    result
+}
+
+/// Запрос с прочитанными заголовками, ожидающий ответа.
+struct Request {
+    stream: TcpStream,
+    head: String,
+}
+
+impl Request {
+    /// Читает заголовки запроса из соединения.
+    fn read(mut stream: TcpStream) -> Result<Self> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        let head = read_request_head(&mut stream)?;
+        Ok(Self { stream, head })
+    }
+}
+
+/// Очередь запросов от потока соединений к асинхронному обработчику.
+#[derive(Default)]
+struct RequestQueue {
+    state: Mutex<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    requests: VecDeque<Request>,
+    /// Поток соединений завершился: новых запросов не будет.
+    closed: bool,
+    /// Обработчик, ожидающий запрос.
+    waker: Option<Waker>,
+}
+
+impl RequestQueue {
+    fn push(&self, request: Request) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.requests.push_back(request);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Следующий запрос; `None`, когда очередь закрыта и пуста.
+    async fn next(&self) -> Option<Request> {
+        std::future::poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(request) = state.requests.pop_front() {
+                return Poll::Ready(Some(request));
+            }
+            if state.closed {
+                return Poll::Ready(None);
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Отвечает на запрос, закрывая соединение.
+async fn respond(args: &Args, pool: Option<&rayon::ThreadPool>, request: Request) -> Result<()> {
+    let Request { mut stream, head } = request;
+    let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or("/"));
+    let path = target.split('?').next().unwrap_or(target);
+
+    let response = if !host_allowed(host_header(&head).as_deref(), stream.local_addr()?) {
+        Response::text("403 Forbidden", "Forbidden: unexpected Host header\n")
+    } else if method != "GET" && method != "HEAD" {
+        Response::text("405 Method Not Allowed", "Only GET and HEAD are supported\n")
+    } else {
+        match path {
+            "/" => render(args, pool, OutputFormat::Html)
+                .await
+                .map(|(document, _)| Response::new("200 OK", "text/html; charset=utf-8", document)),
+            "/raw" => render(args, pool, OutputFormat::Markdown).await.map(|(document, _)| {
+                Response::new("200 OK", "text/markdown; charset=utf-8", document)
+            }),
+            "/stats.json" => {
+                render(args, pool, OutputFormat::Markdown).await.and_then(|(_, report)| {
+                    let stats = serde_json::to_vec_pretty(&report.summary())
+                        .context("Failed to serialize stats")?;
+                    Ok(Response::new("200 OK", "application/json", stats))
+                })
+            }
+            _ => Ok(Response::text("404 Not Found", "Not found: use /, /raw or /stats.json\n")),
+        }
+        .unwrap_or_else(|error| {
+            Response::text("500 Internal Server Error", &format!("Error: {:#}\n", error))
+        })
+    };
+    response.write(&mut stream, method == "HEAD")
+}
+
+/// Читает заголовки запроса до пустой строки; тело запроса не нужно.
+fn read_request_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let read = stream.read(&mut buffer).context("Failed to read request")?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// Значение единственного заголовка `Host`; `None`, если его нет или их несколько.
+fn host_header(head: &str) -> Option<String> {
+    let mut hosts = head.lines().skip(1).filter_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("host").then(|| value.trim().to_string())
+    });
+    let host = hosts.next()?;
+    hosts.next().is_none().then_some(host)
+}
+
+/// Разбирает `Host` на имя и порт: `localhost:8080`, `[::1]:8080`, `127.0.0.1`.
+///
+/// Порт по умолчанию — 80; `None` для неверной записи.
+fn split_host(host: &str) -> Option<(&str, u16)> {
+    let (name, port) = match host.strip_prefix('[') {
+        Some(rest) => {
+            let (name, rest) = rest.split_once(']')?;
+            match rest {
+                "" => (name, None),
+                rest => (name, Some(rest.strip_prefix(':')?)),
+            }
+        }
+        None => match host.split_once(':') {
+            Some((name, port)) => (name, Some(port)),
+            None => (host, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => 80,
+    };
+    (!name.is_empty()).then_some((name, port))
+}
+
+/// Разрешен ли запрос с заголовком `host`, пришедший на адрес `local`.
+///
+/// Разрешены `localhost` (если соединение пришло на loopback) и IP-адрес
+/// соединения или, для loopback, любой loopback-адрес, всегда с портом
+/// сервера. Имя другого сайта не подходит, даже если оно указывает на
+/// этот же адрес.
+fn host_allowed(host: Option<&str>, local: SocketAddr) -> bool {
+    let Some((name, port)) = host.and_then(split_host) else {
+        return false;
+    };
+    if port != local.port() {
+        return false;
+    }
+    if name.eq_ignore_ascii_case("localhost") {
+        return local.ip().is_loopback();
+    }
+    match name.parse::<IpAddr>() {
+        Ok(ip) => ip == local.ip() || (ip.is_loopback() && local.ip().is_loopback()),
+        Err(_) => false,
+    }
+}
+
+/// Формирует документ в формате `format` с остальными настройками запуска.
+///
+/// Ошибки чтения отдельных файлов не мешают ответу: они уже записаны в документ.
+/// Документ, обрезанный по `--time-limit`, тоже отдается.
+async fn render(
+    args: &Args,
+    pool: Option<&rayon::ThreadPool>,
+    format: OutputFormat,
+) -> Result<(Vec<u8>, FlattenReport)> {
+    let mut args = args.clone();
+    args.format = format;
+    args.serve = None;
+    let mut document = Vec::new();
+    let config = FlattenConfig::new(&args).await?;
+    let report = match in_pool(pool, || write_document(&args, &config, &mut document)) {
+        Ok(report) => report,
+        Err(FlattenError::Partial { report, .. } | FlattenError::TimeLimit { report, .. }) => {
+            *report
+        }
+        Err(error) => return Err(error.into()),
+    };
+    Ok((document, report))
+}
+
+/// Ответ сервера.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: &'static str, content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    fn text(status: &'static str, body: &str) -> Self {
+        Self::new(status, "text/plain; charset=utf-8", body.as_bytes().to_vec())
+    }
+
+    fn write(&self, stream: &mut TcpStream, head_only: bool) -> Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.content_type,
+            self.body.len()
+        )?;
+        if !head_only {
+            stream.write_all(&self.body)?;
+        }
+        stream.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("9000").unwrap(), SocketAddr::from(([127, 0, 0, 1], 9000)));
+        assert_eq!(
+            parse_address(DEFAULT_ADDRESS).unwrap(),
+            SocketAddr::from(([127, 0, 0, 1], 8080))
+        );
+        assert!(parse_address("localhost").is_err());
+    }
+
+    #[test]
+    fn test_accepted_requests_reach_queue_until_stopped() -> Result<()> {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let queue = Arc::new(RequestQueue::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let accepting = {
+            let (queue, stop) = (Arc::clone(&queue), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                accept_requests(&listener, &queue, || stop.load(Ordering::SeqCst))
+            })
+        };
+
+        let mut client = TcpStream::connect(address)?;
+        client.write_all(b"GET /raw HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+        let request = crate::block_on(queue.next()).expect("request is queued");
+        assert!(request.head.starts_with("GET /raw HTTP/1.1"));
+
+        // После остановки очередь закрывается, и ожидание запроса завершается.
+        stop.store(true, Ordering::SeqCst);
+        assert!(crate::block_on(queue.next()).is_none());
+        accepting.join().expect("accept thread panicked")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_allowed() {
+        let local = SocketAddr::from(([127, 0, 0, 1], 8080));
+        assert!(host_allowed(Some("localhost:8080"), local));
+        assert!(host_allowed(Some("LOCALHOST:8080"), local));
+        assert!(host_allowed(Some("127.0.0.1:8080"), local));
+        assert!(host_allowed(Some("[::1]:8080"), local));
+        // Имя чужого сайта, указывающее на 127.0.0.1 (DNS rebinding).
+        assert!(!host_allowed(Some("evil.example:8080"), local));
+        assert!(!host_allowed(Some("localhost:9090"), local));
+        assert!(!host_allowed(Some("localhost"), local));
+        assert!(!host_allowed(Some("127.0.0.1:"), local));
+        assert!(!host_allowed(None, local));
+
+        let lan = SocketAddr::from(([192, 168, 1, 5], 80));
+        assert!(host_allowed(Some("192.168.1.5"), lan));
+        assert!(!host_allowed(Some("localhost"), lan));
+        assert!(!host_allowed(Some("127.0.0.1"), lan));
+
+        let head = "GET / HTTP/1.1\r\nhOsT:  localhost:1 \r\n\r\n";
+        assert_eq!(host_header(head).as_deref(), Some("localhost:1"));
+        assert_eq!(host_header("GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n"), None);
+        assert_eq!(host_header("GET / HTTP/1.1\r\n\r\n"), None);
+    }
+}
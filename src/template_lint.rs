@@ -0,0 +1,235 @@
+//! Модуль проверки и нормализации содержимого шаблонов (`--show-template --lint`).
+//!
+//! Шаблоны Toptal пишут разные люди, и часть из них содержит то, что разбор
+//! gitignore понимает не так, как задумано: BOM в начале, переводы строк
+//! Windows, разделители путей `\` вместо `/`, огромные баннеры в комментариях.
+//! После загрузки каждый шаблон приводится к каноническому виду: BOM
+//! удаляется, строки разделяются `\n`, строки длиннее `MAX_LINE_LENGTH`
+//! отбрасываются, а `\`, который не экранирует спецсимвол gitignore,
+//! заменяется на `/`. Число правок сохраняется в кэше вместе с шаблоном.
+//!
+//! Шаблон, который не является текстом UTF-8 или после нормализации не
+//! содержит ни одного паттерна, в кэш не попадает.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Наибольшая длина строки шаблона в символах; более длинные отбрасываются.
+pub const MAX_LINE_LENGTH: usize = 512;
+
+/// Символы, которые `\` экранирует в gitignore; перед остальными он — разделитель.
+///
+/// `\*` и `\?` сюда не входят: `bin\*.dll` в шаблоне встречается намного
+/// чаще, чем звездочка в имени файла.
+const ESCAPED: &[char] = &['#', '!', ' ', '[', ']', '\\'];
+
+/// Правки, внесенные в шаблон при нормализации.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateLint {
+    /// Был ли в начале шаблона BOM.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub bom: bool,
+    /// Строки с переводом строки Windows или старых macOS.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub line_endings: usize,
+    /// Строки, в которых `\` заменен на `/`.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub separators: usize,
+    /// Отброшенные строки длиннее `MAX_LINE_LENGTH`.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub long_lines: usize,
+}
+
+impl TemplateLint {
+    /// Шаблон сохранен без правок.
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for TemplateLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "no issues");
+        }
+        let mut issues = Vec::new();
+        if self.bom {
+            issues.push("byte order mark removed".to_string());
+        }
+        if self.line_endings > 0 {
+            issues.push(format!("{} line endings normalized", self.line_endings));
+        }
+        if self.separators > 0 {
+            issues.push(format!("{} lines with backslash separators", self.separators));
+        }
+        if self.long_lines > 0 {
+            issues.push(format!(
+                "{} lines over {} characters dropped",
+                self.long_lines, MAX_LINE_LENGTH
+            ));
+        }
+        write!(f, "{}", issues.join(", "))
+    }
+}
+
+/// Почему шаблон не сохраняется в кэш.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Содержимое не является текстом UTF-8.
+    NotUtf8,
+    /// После нормализации не осталось ни одного паттерна.
+    Empty,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::NotUtf8 => write!(f, "contents are not valid UTF-8"),
+            Rejection::Empty => write!(f, "no patterns left after normalization"),
+        }
+    }
+}
+
+/// Приводит шаблон к каноническому виду: строки без BOM, разделенные `\n`,
+/// без завершающего перевода строки.
+///
+/// Содержимое приходит уже строкой, поэтому невалидный UTF-8 распознается по
+/// символу замены U+FFFD, который оставляет декодирование, и по нулевым байтам.
+pub fn normalize(contents: &str) -> Result<(String, TemplateLint), Rejection> {
+    if contents.contains(['\u{FFFD}', '\0']) {
+        return Err(Rejection::NotUtf8);
+    }
+    let mut lint = TemplateLint::default();
+    let contents = match contents.strip_prefix('\u{FEFF}') {
+        Some(rest) => {
+            lint.bom = true;
+            rest
+        }
+        None => contents,
+    };
+    let mut lines = Vec::new();
+    for line in contents.split('\n') {
+        let line = match line.strip_suffix('\r') {
+            Some(line) => {
+                lint.line_endings += 1;
+                line
+            }
+            None => line,
+        };
+        // Одиночный `\r` тоже разделяет строки.
+        let parts: Vec<&str> = line.split('\r').collect();
+        lint.line_endings += parts.len() - 1;
+        for part in parts {
+            if part.chars().count() > MAX_LINE_LENGTH {
+                lint.long_lines += 1;
+                continue;
+            }
+            match convert_separators(part) {
+                Some(converted) => {
+                    lint.separators += 1;
+                    lines.push(converted);
+                }
+                None => lines.push(part.to_string()),
+            }
+        }
+    }
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    let has_patterns = lines.iter().any(|line| {
+        let line = line.trim();
+        !line.is_empty() && !line.starts_with('#')
+    });
+    if !has_patterns {
+        return Err(Rejection::Empty);
+    }
+    Ok((lines.join("\n"), lint))
+}
+
+/// Заменяет `\`-разделители путей на `/`; `None`, если заменять нечего.
+///
+/// Комментарии не меняются, а `\` перед спецсимволом gitignore остается
+/// экранированием.
+fn convert_separators(line: &str) -> Option<String> {
+    if !line.contains('\\') || line.starts_with('#') {
+        return None;
+    }
+    let mut converted = String::with_capacity(line.len());
+    let mut changed = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            converted.push(c);
+            continue;
+        }
+        match chars.clone().next() {
+            Some(next) if ESCAPED.contains(&next) => {
+                converted.push(c);
+                converted.push(next);
+                chars.next();
+            }
+            _ => {
+                converted.push('/');
+                changed = true;
+            }
+        }
+    }
+    changed.then_some(converted)
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_malformed_template() {
+        let banner = format!("# {}", "=".repeat(MAX_LINE_LENGTH));
+        let contents = format!(
+            "\u{FEFF}{}\r\n# Build output\r\nbin\\Debug\\\r\nobj\\*.pdb\r\n\\#notes\r\n\r\n",
+            banner
+        );
+        let (canonical, lint) = normalize(&contents).unwrap();
+        assert_eq!(canonical, "# Build output\nbin/Debug/\nobj/*.pdb\n\\#notes");
+        assert_eq!(
+            lint,
+            TemplateLint {
+                bom: true,
+                line_endings: 6,
+                separators: 2,
+                long_lines: 1,
+            }
+        );
+        assert_eq!(
+            lint.to_string(),
+            "byte order mark removed, 6 line endings normalized, \
+             2 lines with backslash separators, 1 lines over 512 characters dropped"
+        );
+
+        // Одиночные `\r` разделяют строки, экранирование сохраняется.
+        let (canonical, lint) = normalize("a\rb\\ c\\[x\\]\n").unwrap();
+        assert_eq!(canonical, "a\nb\\ c\\[x\\]");
+        assert_eq!(lint.line_endings, 1);
+        assert_eq!(lint.separators, 0);
+
+        let (canonical, lint) = normalize("target/\n").unwrap();
+        assert_eq!(canonical, "target/");
+        assert!(lint.is_clean());
+        assert_eq!(lint.to_string(), "no issues");
+    }
+
+    #[test]
+    fn test_reject_invalid_templates() {
+        let lossy = String::from_utf8_lossy(b"build/\n\xff\xfe\n").into_owned();
+        assert_eq!(normalize(&lossy), Err(Rejection::NotUtf8));
+        assert_eq!(normalize("a\0b"), Err(Rejection::NotUtf8));
+        assert_eq!(normalize(""), Err(Rejection::Empty));
+        assert_eq!(normalize("\u{FEFF}\r\n# only comments\r\n\r\n"), Err(Rejection::Empty));
+        let banner = "x".repeat(MAX_LINE_LENGTH + 1);
+        assert_eq!(normalize(&banner), Err(Rejection::Empty));
+    }
+}
@@ -0,0 +1,127 @@
+//! Модуль ширины терминала для консольного вывода.
+//!
+//! Ширина берется у терминала, в который идет вывод (stdout для списков и
+//! `--dry-run`, stderr для полосы прогресса); если поток не терминал,
+//! используются 80 колонок. `--width` задает ширину явно (например, в тестах).
+//! Ширина влияет только на консоль: файлы вывода пишутся без укорачивания.
+
+use console::{Term, measure_text_width};
+use std::borrow::Cow;
+
+/// Ширина, если поток вывода не терминал.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Знак укорачивания.
+const ELLIPSIS: &str = "…";
+
+/// Ширина, до которой текст не укорачивается, даже если места меньше.
+const MIN_FIT_WIDTH: usize = 8;
+
+/// Отступ между колонками списка.
+const COLUMN_GAP: usize = 2;
+
+/// Ширина вывода в колонках: `requested`, иначе ширина терминала `term`.
+pub fn width(term: &Term, requested: Option<usize>) -> usize {
+    requested
+        .or_else(|| term.size_checked().map(|(_, columns)| usize::from(columns)))
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Укорачивает конец текста до `width` колонок, заменяя хвост на `…`.
+pub fn fit_end(text: &str, width: usize) -> Cow<'_, str> {
+    let width = width.max(MIN_FIT_WIDTH);
+    if measure_text_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+    console::truncate_str(text, width, ELLIPSIS)
+}
+
+/// Укорачивает начало текста до `width` колонок, заменяя его на `…`
+/// (для путей, у которых важнее конец).
+pub fn fit_start(text: &str, width: usize) -> Cow<'_, str> {
+    let width = width.max(MIN_FIT_WIDTH);
+    if measure_text_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+    let budget = width - measure_text_width(ELLIPSIS);
+    let mut used = 0;
+    let mut start = text.len();
+    for (index, c) in text.char_indices().rev() {
+        used += measure_text_width(c.encode_utf8(&mut [0; 4]));
+        if used > budget {
+            break;
+        }
+        start = index;
+    }
+    Cow::Owned(format!("{}{}", ELLIPSIS, &text[start..]))
+}
+
+/// Раскладывает `items` по колонкам, чтобы строки с отступом `indent`
+/// помещались в `width` колонок; порядок элементов — по строкам.
+pub fn columns(items: &[String], width: usize, indent: usize) -> Vec<String> {
+    let cell = items
+        .iter()
+        .map(|item| measure_text_width(item))
+        .max()
+        .unwrap_or(0)
+        + COLUMN_GAP;
+    let count = (width.saturating_sub(indent) + COLUMN_GAP) / cell;
+    items
+        .chunks(count.max(1))
+        .map(|row| {
+            let mut line = " ".repeat(indent);
+            for (index, item) in row.iter().enumerate() {
+                line.push_str(item);
+                if index + 1 < row.len() {
+                    line.push_str(&" ".repeat(cell - measure_text_width(item)));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_text() {
+        assert_eq!(fit_end("main.rs", 20), "main.rs");
+        assert_eq!(fit_end("a_very_long_file_name.rs", 12), "a_very_long…");
+        assert_eq!(fit_end("a_very_long_file_name.rs", 2), "a_very_…");
+        assert_eq!(fit_start("src/deep/nested/module.rs", 14), "…ted/module.rs");
+        assert_eq!(fit_start("src/main.rs", 40), "src/main.rs");
+        assert_eq!(width(&Term::stdout(), Some(60)), 60);
+    }
+
+    #[test]
+    fn test_columns_follow_width() {
+        let templates: Vec<String> = [
+            "c++",
+            "go",
+            "jetbrains+all",
+            "macos",
+            "node",
+            "python",
+            "rust",
+            "visualstudiocode",
+        ]
+        .iter()
+        .map(|key| key.to_string())
+        .collect();
+        assert_eq!(
+            columns(&templates, 60, 2).join("\n"),
+            "  c++               go                jetbrains+all\n  \
+             macos             node              python\n  \
+             rust              visualstudiocode"
+        );
+        assert_eq!(
+            columns(&templates, 120, 2).join("\n"),
+            "  c++               go                jetbrains+all     macos             \
+             node              python\n  \
+             rust              visualstudiocode"
+        );
+        assert_eq!(columns(&templates, 10, 2).len(), templates.len());
+    }
+}
@@ -0,0 +1,183 @@
+//! Модуль ограничения времени запуска (`--time-limit`).
+//!
+//! Отсчет идет от создания конфигурации. Истечение проверяется там, где файл
+//! и так начинает и заканчивает обработку: перед чтением очередного файла
+//! берется текущее время, и после лимита файлы больше не читаются. Уже
+//! начатые файлы дочитываются и попадают в документ; с `--time-limit-hard`
+//! их чтение прерывается по тому же механизму, что и `--file-timeout`, а
+//! результат отбрасывается. Документ получает пометку об обрезке, а запуск
+//! завершается с кодом `EXIT_TIME_LIMIT`.
+
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Лимит времени запуска.
+#[derive(Debug)]
+pub struct TimeLimit {
+    /// Начало отсчета.
+    start: Instant,
+    /// Допустимая длительность запуска.
+    limit: Duration,
+    /// Прерывать ли начатые чтения (`--time-limit-hard`).
+    hard: bool,
+    /// Истек ли лимит; однажды установленный флаг не сбрасывается.
+    reached: AtomicBool,
+}
+
+impl TimeLimit {
+    /// Начинает отсчет лимита `limit`.
+    pub fn new(limit: Duration, hard: bool) -> Self {
+        Self {
+            start: Instant::now(),
+            limit,
+            hard,
+            reached: AtomicBool::new(false),
+        }
+    }
+
+    /// Допустимая длительность запуска.
+    pub fn limit(&self) -> Duration {
+        self.limit
+    }
+
+    /// Прерываются ли начатые чтения.
+    pub fn is_hard(&self) -> bool {
+        self.hard
+    }
+
+    /// Момент истечения лимита.
+    pub fn deadline(&self) -> Instant {
+        self.start + self.limit
+    }
+
+    /// Сверяет текущее время с лимитом и запоминает его истечение.
+    pub fn check(&self) -> bool {
+        if self.is_reached() {
+            return true;
+        }
+        let reached = self.start.elapsed() >= self.limit;
+        if reached {
+            self.reached.store(true, Ordering::Relaxed);
+        }
+        reached
+    }
+
+    /// Замечено ли уже истечение лимита (без обращения к часам).
+    pub fn is_reached(&self) -> bool {
+        self.reached.load(Ordering::Relaxed)
+    }
+}
+
+/// Сведения об обрезке документа по лимиту времени.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Truncation {
+    /// Лимит времени в миллисекундах.
+    pub limit_ms: u64,
+    /// Прерывались ли начатые чтения.
+    pub hard: bool,
+    /// Файлы, которые не были обработаны.
+    pub unprocessed_files: usize,
+    /// Базовые папки, обход которых не начинался.
+    pub unprocessed_folders: usize,
+}
+
+impl Truncation {
+    /// Пустые сведения для лимита `limit`.
+    pub fn new(limit: &TimeLimit) -> Self {
+        Self {
+            limit_ms: limit.limit().as_millis() as u64,
+            hard: limit.is_hard(),
+            unprocessed_files: 0,
+            unprocessed_folders: 0,
+        }
+    }
+}
+
+impl fmt::Display for Truncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = format_duration(Duration::from_millis(self.limit_ms));
+        write!(
+            f,
+            "time limit of {} reached, {} files not processed",
+            limit, self.unprocessed_files
+        )?;
+        if self.unprocessed_folders > 0 {
+            write!(f, ", {} folders not started", self.unprocessed_folders)?;
+        }
+        Ok(())
+    }
+}
+
+/// Разбирает длительность: `90` (секунды), `500ms`, `30s`, `5m`, `2h`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", value))?;
+    let duration = match unit.trim().to_ascii_lowercase().as_str() {
+        "ms" => Duration::from_millis(number),
+        "" | "s" => Duration::from_secs(number),
+        "m" | "min" => Duration::from_secs(number.saturating_mul(60)),
+        "h" => Duration::from_secs(number.saturating_mul(3600)),
+        _ => {
+            return Err(format!(
+                "Unknown duration unit in {}: use ms, s, m or h",
+                value
+            ));
+        }
+    };
+    if duration.is_zero() {
+        return Err(format!("Duration must be positive: {}", value));
+    }
+    Ok(duration)
+}
+
+/// Форматирует длительность крупнейшей целой единицей (`500ms`, `30s`, `5m`).
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis.is_multiple_of(3_600_000) {
+        format!("{}h", millis / 3_600_000)
+    } else if millis.is_multiple_of(60_000) {
+        format!("{}m", millis / 60_000)
+    } else if millis.is_multiple_of(1000) {
+        format!("{}s", millis / 1000)
+    } else {
+        format!("{}ms", millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2H"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("soon").is_err());
+
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_secs(300)), "5m");
+    }
+
+    #[test]
+    fn test_check_latches_reached_limit() {
+        let limit = TimeLimit::new(Duration::from_millis(20), false);
+        assert!(!limit.check());
+        assert!(!limit.is_reached());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limit.check());
+        assert!(limit.is_reached());
+    }
+}
@@ -0,0 +1,780 @@
+//! Модуль разбора TOML в `serde_json::Value`.
+//!
+//! Нужен для чтения манифестов (`Cargo.toml`, `pyproject.toml`) без внешних
+//! зависимостей. Поддерживаются таблицы и массивы таблиц, составные ключи,
+//! все виды строк, числа, логические значения, массивы и встроенные таблицы.
+//! Дата и время возвращаются строкой в исходной записи, а `inf` и `nan`,
+//! которых нет в JSON, — строками `"inf"`, `"-inf"` и `"nan"`.
+//!
+//! Вложенность массивов и встроенных таблиц ограничена `MAX_DEPTH`: манифест
+//! с тысячами `[` подряд дает ошибку разбора, а не переполнение стека.
+
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Map, Value};
+
+/// Наибольшая вложенность массивов и встроенных таблиц.
+const MAX_DEPTH: usize = 128;
+
+/// Разбирает TOML-документ; ошибка содержит номер строки.
+///
+/// Метка порядка байтов в начале (так сохраняют файлы некоторые редакторы
+/// Windows) пропускается.
+pub fn parse(input: &str) -> Result<Value> {
+    let mut parser = Parser {
+        chars: input.trim_start_matches('\u{feff}').chars().collect(),
+        position: 0,
+        depth: 0,
+    };
+    parser
+        .document()
+        .map_err(|error| anyhow!("line {}: {}", parser.line(), error))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    /// Текущая вложенность массивов и встроенных таблиц.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(offset, c)| self.peek_at(offset) == Some(c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => bail!("expected '{}', found '{}'", expected, c),
+            None => bail!("expected '{}', found end of input", expected),
+        }
+    }
+
+    /// Номер текущей строки (с 1).
+    fn line(&self) -> usize {
+        let end = self.position.min(self.chars.len());
+        self.chars[..end].iter().filter(|&&c| c == '\n').count() + 1
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.position += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.position += 1;
+            }
+        }
+    }
+
+    /// Пропускает пробелы, комментарии и переводы строк.
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') => self.position += 1,
+                Some('\r') if self.peek_at(1) == Some('\n') => self.position += 2,
+                _ => return,
+            }
+        }
+    }
+
+    /// Проверяет, что до конца строки остались только пробелы и комментарий.
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.peek_at(1) == Some('\n') => Ok(()),
+            Some(c) => bail!("unexpected '{}' after value", c),
+        }
+    }
+
+    fn document(&mut self) -> Result<Value> {
+        let mut root = Map::new();
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => return Ok(Value::Object(root)),
+                Some('[') if self.peek_at(1) == Some('[') => {
+                    self.position += 2;
+                    let path = self.key_path()?;
+                    self.expect(']')?;
+                    self.expect(']')?;
+                    self.end_of_line()?;
+                    let (last, parent) = path.split_last().ok_or_else(|| anyhow!("empty key"))?;
+                    let entry = table_mut(&mut root, parent)?
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    match entry {
+                        Value::Array(tables) => tables.push(Value::Object(Map::new())),
+                        _ => bail!("key '{}' is not an array of tables", last),
+                    }
+                    current = path;
+                }
+                Some('[') => {
+                    self.position += 1;
+                    let path = self.key_path()?;
+                    self.expect(']')?;
+                    self.end_of_line()?;
+                    table_mut(&mut root, &path)?;
+                    current = path;
+                }
+                Some(_) => {
+                    let table = table_mut(&mut root, &current)?;
+                    self.key_value(table)?;
+                    self.end_of_line()?;
+                }
+            }
+        }
+    }
+
+    /// Читает составной ключ `a.b."c"` (пробелы вокруг точек допускаются).
+    fn key_path(&mut self) -> Result<Vec<String>> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_spaces();
+            path.push(self.key()?);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(path);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn key(&mut self) -> Result<String> {
+        match self.peek() {
+            Some('"') => {
+                self.position += 1;
+                self.basic_string()
+            }
+            Some('\'') => {
+                self.position += 1;
+                self.literal_string()
+            }
+            _ => {
+                let start = self.position;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.position += 1;
+                }
+                if start == self.position {
+                    match self.peek() {
+                        Some(c) => bail!("invalid key character '{}'", c),
+                        None => bail!("expected key, found end of input"),
+                    }
+                }
+                Ok(self.chars[start..self.position].iter().collect())
+            }
+        }
+    }
+
+    /// Читает пару `ключ = значение` и добавляет ее в `table`.
+    fn key_value(&mut self, table: &mut Map<String, Value>) -> Result<()> {
+        let path = self.key_path()?;
+        self.skip_spaces();
+        self.expect('=')?;
+        self.skip_spaces();
+        let value = self.value()?;
+        let (last, parent) = path.split_last().ok_or_else(|| anyhow!("empty key"))?;
+        let table = table_mut(table, parent)?;
+        if table.contains_key(last) {
+            bail!("duplicate key '{}'", last);
+        }
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => {
+                self.position += 3;
+                self.multiline_basic_string().map(Value::String)
+            }
+            Some('"') => {
+                self.position += 1;
+                self.basic_string().map(Value::String)
+            }
+            Some('\'') if self.starts_with("'''") => {
+                self.position += 3;
+                self.multiline_literal_string().map(Value::String)
+            }
+            Some('\'') => {
+                self.position += 1;
+                self.literal_string().map(Value::String)
+            }
+            Some('[') => {
+                self.position += 1;
+                self.nested(Self::array)
+            }
+            Some('{') => {
+                self.position += 1;
+                self.nested(Self::inline_table)
+            }
+            Some(_) if self.starts_with("true") => {
+                self.position += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(_) if self.starts_with("false") => {
+                self.position += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(_) => self.scalar(),
+            None => bail!("expected value, found end of input"),
+        }
+    }
+
+    /// Разбирает вложенное значение, не превышая `MAX_DEPTH`.
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Value>) -> Result<Value> {
+        if self.depth >= MAX_DEPTH {
+            bail!("nesting deeper than {} levels", MAX_DEPTH);
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.peek() == Some(']') {
+                self.position += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank();
+            match self.bump() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                Some(c) => bail!("expected ',' or ']' in array, found '{}'", c),
+                None => bail!("unterminated array"),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value> {
+        let mut table = Map::new();
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Value::Object(table));
+        }
+        loop {
+            self.skip_spaces();
+            self.key_value(&mut table)?;
+            self.skip_spaces();
+            match self.bump() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(table)),
+                Some(c) => bail!("expected ',' or '}}' in inline table, found '{}'", c),
+                None => bail!("unterminated inline table"),
+            }
+        }
+    }
+
+    /// Строка в двойных кавычках; открывающая кавычка уже прочитана.
+    fn basic_string(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => text.push(self.escape()?),
+                Some('\n') | None => bail!("unterminated string"),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn multiline_basic_string(&mut self) -> Result<String> {
+        self.skip_first_newline();
+        let mut text = String::new();
+        loop {
+            if self.starts_with("\"\"\"") && !self.starts_with("\"\"\"\"") {
+                self.position += 3;
+                return Ok(text);
+            }
+            match self.bump() {
+                Some('\\') => {
+                    // Обратная косая черта в конце строки убирает перевод
+                    // строки и пробелы в начале следующей.
+                    let rest = self.position;
+                    self.skip_spaces();
+                    if matches!(self.peek(), Some('\n' | '\r')) {
+                        self.skip_blank_lines();
+                    } else {
+                        self.position = rest;
+                        text.push(self.escape()?);
+                    }
+                }
+                Some(c) => text.push(c),
+                None => bail!("unterminated multiline string"),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(text),
+                Some('\n') | None => bail!("unterminated string"),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String> {
+        self.skip_first_newline();
+        let mut text = String::new();
+        loop {
+            if self.starts_with("'''") && !self.starts_with("''''") {
+                self.position += 3;
+                return Ok(text);
+            }
+            match self.bump() {
+                Some(c) => text.push(c),
+                None => bail!("unterminated multiline string"),
+            }
+        }
+    }
+
+    /// Перевод строки сразу после открывающих кавычек не входит в значение.
+    fn skip_first_newline(&mut self) {
+        if self.peek() == Some('\n') {
+            self.position += 1;
+        } else if self.starts_with("\r\n") {
+            self.position += 2;
+        }
+    }
+
+    fn skip_blank_lines(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn escape(&mut self) -> Result<char> {
+        let c = match self.bump() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('e') => '\u{1b}',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('u') => self.unicode_escape(4)?,
+            Some('U') => self.unicode_escape(8)?,
+            Some(c) => bail!("invalid escape '\\{}'", c),
+            None => bail!("unterminated string"),
+        };
+        Ok(c)
+    }
+
+    fn unicode_escape(&mut self, digits: usize) -> Result<char> {
+        let mut code = 0;
+        for _ in 0..digits {
+            let digit = self
+                .bump()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| anyhow!("invalid unicode escape"))?;
+            code = code * 16 + digit;
+        }
+        char::from_u32(code).ok_or_else(|| anyhow!("invalid unicode scalar {:x}", code))
+    }
+
+    /// Число, дата или время: читается до разделителя и затем распознается.
+    fn scalar(&mut self) -> Result<Value> {
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || "+-_.:".contains(c)) {
+            self.position += 1;
+        }
+        // Дата и время могут разделяться пробелом: `1979-05-27 07:32:00`.
+        let date_length = self.position - start;
+        if date_length == 10
+            && self.chars[start + 4] == '-'
+            && self.peek() == Some(' ')
+            && self.peek_at(1).is_some_and(|c| c.is_ascii_digit())
+        {
+            self.position += 1;
+            while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || "+-.:".contains(c))
+            {
+                self.position += 1;
+            }
+        }
+        let token: String = self.chars[start..self.position].iter().collect();
+        if token.is_empty() {
+            match self.peek() {
+                Some(c) => bail!("unexpected '{}'", c),
+                None => bail!("expected value, found end of input"),
+            }
+        }
+        scalar_value(&token).ok_or_else(|| anyhow!("invalid value '{}'", token))
+    }
+}
+
+/// Распознает число, дату или время.
+fn scalar_value(token: &str) -> Option<Value> {
+    let unsigned = token.trim_start_matches(['+', '-']);
+    match unsigned {
+        "inf" | "nan" => {
+            let text = if token.starts_with('-') && unsigned == "inf" {
+                "-inf"
+            } else {
+                unsigned
+            };
+            return Some(Value::String(text.to_string()));
+        }
+        _ => {}
+    }
+    let bytes = token.as_bytes();
+    let is_date_or_time = (bytes.len() >= 10 && bytes[4] == b'-' && bytes[7] == b'-')
+        || (bytes.len() >= 8 && bytes[2] == b':' && bytes[5] == b':');
+    if is_date_or_time {
+        return token
+            .chars()
+            .all(|c| c.is_ascii_digit() || "-:.TZtz +".contains(c))
+            .then(|| Value::String(token.to_string()));
+    }
+    if token.starts_with('_') || token.ends_with('_') || token.contains("__") {
+        return None;
+    }
+    let digits = token.replace('_', "");
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(rest) = digits.strip_prefix(prefix) {
+            return i64::from_str_radix(rest, radix).ok().map(Value::from);
+        }
+    }
+    if digits
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '+' || c == '-')
+    {
+        return digits.parse::<i64>().ok().map(Value::from);
+    }
+    let valid_float = digits.chars().next().is_some_and(|c| c != '.')
+        && !digits.ends_with('.')
+        && !digits.contains(".e")
+        && !digits.contains(".E");
+    if valid_float {
+        return digits
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .map(Value::from);
+    }
+    None
+}
+
+/// Возвращает таблицу по пути относительно `root`, создавая недостающие.
+///
+/// Путь через массив таблиц ведет к его последнему элементу.
+fn table_mut<'a>(
+    root: &'a mut Map<String, Value>,
+    path: &[String],
+) -> Result<&'a mut Map<String, Value>> {
+    let mut table = root;
+    for key in path {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let entry = match entry {
+            Value::Array(items) => items
+                .last_mut()
+                .ok_or_else(|| anyhow!("key '{}' is not a table", key))?,
+            other => other,
+        };
+        table = match entry {
+            Value::Object(table) => table,
+            _ => bail!("key '{}' is not a table", key),
+        };
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_cargo_manifest() -> Result<()> {
+        let value = parse(
+            r#"
+# comment
+[package]
+name = "demo" # trailing
+version = '0.1.0'
+description = """
+Multi \
+  line"""
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+"my-crate".path = "../my-crate"
+numbers = [1, 0x1F, -2_000, 1.5e3,
+  # comment in array
+  3, ]
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[[bin]]
+name = "a"
+[[bin]]
+name = "b"
+released = 1979-05-27 07:32:00Z
+"#,
+        )?;
+        assert_eq!(value["package"]["name"], "demo");
+        assert_eq!(value["package"]["version"], "0.1.0");
+        assert_eq!(value["package"]["description"], "Multi line");
+        assert_eq!(
+            value["dependencies"]["serde"]["features"],
+            json!(["derive"])
+        );
+        assert_eq!(value["dependencies"]["my-crate"]["path"], "../my-crate");
+        assert_eq!(
+            value["dependencies"]["numbers"],
+            json!([1, 31, -2000, 1500.0, 3])
+        );
+        assert_eq!(value["target"]["cfg(unix)"]["dependencies"]["libc"], "0.2");
+        assert_eq!(value["bin"][1]["name"], "b");
+        assert_eq!(value["bin"][1]["released"], "1979-05-27 07:32:00Z");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_real_world_cargo_manifest() -> Result<()> {
+        let value = parse(
+            r#"
+[workspace]
+members = [
+    "crates/*",   # все крейты
+    "tools/xtask",
+]
+resolver = "2"
+
+[workspace.package]
+edition = "2021"
+authors = ["A <a@example.com>", 'B']
+
+[workspace.dependencies]
+tokio = { version = "1", features = ["rt-multi-thread", "macros"], default-features = false }
+serde.workspace = true
+serde.features = [ "derive" ]
+
+[package]
+name = "app"
+version.workspace = true
+description = """
+Первая строка
+вторая строка "в кавычках" и ""две"" """""
+license-file = '''C:\licenses\MIT'''
+keywords = [
+  "cli",
+  "tool"
+  ,
+]
+
+[package.metadata.docs.rs]
+all-features = true
+rustdoc-args = ["--cfg", "docsrs"]
+
+[dependencies]
+anyhow = "1.0.75"
+regex = { version = "1.10", optional = true }
+
+[target.'cfg(target_os = "linux")'.dependencies]
+libc = "0.2"
+
+[target."x86_64-pc-windows-msvc".dev-dependencies]
+winapi = { version = "0.3", features = ["winuser"] }
+
+[features]
+default = []
+full = ["dep:regex", "tokio/full"]
+
+[profile.release]
+lto = "fat"
+opt-level = 3
+debug = false
+panic = 'abort'
+
+[lints.rust]
+unsafe_code = { level = "forbid", priority = -1 }
+
+[[example]]
+name = "demo"
+required-features = ["full"]
+
+[[example]]
+name = "other"
+[example.metadata]
+note = "table of the last array element"
+"#,
+        )?;
+        assert_eq!(value["workspace"]["members"], json!(["crates/*", "tools/xtask"]));
+        assert_eq!(value["workspace"]["package"]["authors"][1], "B");
+        let workspace = &value["workspace"]["dependencies"];
+        assert_eq!(workspace["tokio"]["default-features"], false);
+        assert_eq!(
+            workspace["serde"],
+            json!({"workspace": true, "features": ["derive"]})
+        );
+        let package = &value["package"];
+        assert_eq!(package["version"]["workspace"], true);
+        assert_eq!(
+            package["description"],
+            "Первая строка\nвторая строка \"в кавычках\" и \"\"две\"\" \"\""
+        );
+        assert_eq!(package["license-file"], r"C:\licenses\MIT");
+        assert_eq!(package["keywords"], json!(["cli", "tool"]));
+        assert_eq!(package["metadata"]["docs"]["rs"]["all-features"], true);
+        assert_eq!(value["dependencies"]["regex"]["optional"], true);
+        let linux = &value["target"]["cfg(target_os = \"linux\")"];
+        assert_eq!(linux["dependencies"]["libc"], "0.2");
+        let windows = &value["target"]["x86_64-pc-windows-msvc"]["dev-dependencies"];
+        assert_eq!(windows["winapi"]["features"], json!(["winuser"]));
+        assert_eq!(value["features"]["default"], json!([]));
+        assert_eq!(value["profile"]["release"]["opt-level"], 3);
+        assert_eq!(value["lints"]["rust"]["unsafe_code"]["priority"], -1);
+        assert_eq!(value["example"][0]["required-features"], json!(["full"]));
+        assert_eq!(
+            value["example"][1]["metadata"]["note"],
+            "table of the last array element"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_real_world_pyproject() -> Result<()> {
+        let value = parse(
+            r#"
+[build-system]
+requires = ["setuptools>=61.0", "wheel"]
+build-backend = "setuptools.build_meta"
+
+[project]
+name = "demo-package"
+requires-python = ">=3.9"
+authors = [
+    { name = "Jane Doe", email = "jane@example.com" },
+    {name="John"},
+]
+readme = { file = "README.md", content-type = "text/markdown" }
+dependencies = [
+    "requests>=2.28,<3",
+    'tomli; python_version < "3.11"',
+]
+optional-dependencies.dev = ["pytest", "ruff==0.1.*"]
+
+[project.scripts]
+demo = "demo.cli:main"
+
+[project.urls]
+"Bug Tracker" = "https://example.com/issues"
+
+[tool.poetry.dependencies]
+python = "^3.9"
+numpy = [
+    { version = "<1.25", python = "<3.9" },
+    { version = "^1.25", python = ">=3.9" },
+]
+
+[tool.ruff]
+line-length = 100
+extend-exclude = ["migrations"]
+
+[tool.ruff.lint.per-file-ignores]
+"__init__.py" = ["F401"]
+
+[tool.pytest.ini_options]
+addopts = '''
+-ra
+--strict-markers'''
+markers = [
+  """slow: long \
+     running tests""",
+]
+timeout = 1.5e2
+"#,
+        )?;
+        assert_eq!(value["build-system"]["requires"][0], "setuptools>=61.0");
+        let project = &value["project"];
+        assert_eq!(project["authors"][0]["email"], "jane@example.com");
+        assert_eq!(project["authors"][1], json!({"name": "John"}));
+        assert_eq!(project["readme"]["content-type"], "text/markdown");
+        assert_eq!(project["dependencies"][1], "tomli; python_version < \"3.11\"");
+        assert_eq!(project["optional-dependencies"]["dev"][1], "ruff==0.1.*");
+        assert_eq!(project["scripts"]["demo"], "demo.cli:main");
+        assert_eq!(project["urls"]["Bug Tracker"], "https://example.com/issues");
+        let poetry = &value["tool"]["poetry"]["dependencies"];
+        assert_eq!(poetry["numpy"][1]["python"], ">=3.9");
+        let ruff = &value["tool"]["ruff"];
+        assert_eq!(ruff["line-length"], 100);
+        assert_eq!(ruff["lint"]["per-file-ignores"]["__init__.py"], json!(["F401"]));
+        let pytest = &value["tool"]["pytest"]["ini_options"];
+        assert_eq!(pytest["addopts"], "-ra\n--strict-markers");
+        assert_eq!(pytest["markers"], json!(["slow: long running tests"]));
+        assert_eq!(pytest["timeout"], 150.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_windows_line_endings_and_bom() -> Result<()> {
+        let manifest = "\u{feff}[package]\r\nname = \"demo\" # comment\r\n\
+            description = \"\"\"\r\nfirst \\\r\n  second\"\"\"\r\n\
+            keywords = [\r\n  \"a\",\r\n  \"b\",\r\n]\r\n";
+        let value = parse(manifest)?;
+        assert_eq!(value["package"]["name"], "demo");
+        assert_eq!(value["package"]["description"], "first second");
+        assert_eq!(value["package"]["keywords"], json!(["a", "b"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors_report_line() {
+        let error = parse("[package]\nname = \"demo\"\nname = \"again\"\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: duplicate key 'name'");
+        assert!(parse("a = \"unterminated\n").is_err());
+        assert!(parse("a = 1 b = 2").is_err());
+        assert!(parse("a = [1, 2").is_err());
+        assert!(parse("a = 1__0").is_err());
+        assert_eq!(parse("a = \"\\u00e9\"").unwrap()["a"], "é");
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        let error = parse(&format!("a = {}", "[".repeat(200_000))).unwrap_err();
+        assert!(error.to_string().contains("nesting deeper than"), "{}", error);
+        let error = parse(&format!("a = {}", "{ b = ".repeat(200_000))).unwrap_err();
+        assert!(error.to_string().contains("nesting deeper than"), "{}", error);
+
+        let nested = format!("a = {}1{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&nested).is_ok());
+    }
+}
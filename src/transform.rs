@@ -0,0 +1,353 @@
+//! Модуль цепочки преобразований содержимого файла.
+//!
+//! Преобразования, меняющие текст файла, применяются в порядке этапов `Stage`,
+//! а не в порядке вызовов в коде чтения: распознавание сгенерированных
+//! файлов, нормализация окончаний строк, сокращение (`--outline`,
+//! `--head-lines`), очистка (`--sanitize`). Порядок важен: сокращение после
+//! очистки резало бы строки по экранированным символам, а нормализация после
+//! сокращения определяла бы стиль окончаний по структуре файла, а не по
+//! самому файлу. Новые преобразования встают в этот порядок по смыслу:
+//! редактирование секретов и удаление комментариев — до сокращения, чтобы
+//! граница сокращения не зависела от них, нумерация строк — после очистки.
+//!
+//! Декодирование (чтение, конвертеры) выполняется до цепочки, разбиение на
+//! фрагменты `--chunk-size` — после нее, а обрамление блоком кода — эмиттером
+//! при записи. Заглушки не преобразуются. Цепочка `TransformChain` строится
+//! один раз из конфигурации.
+//!
+//! Каждое преобразование, изменившее файл, оставляет в `FileContent::transforms`
+//! запись с размером до и после; из них складывается раздел `transforms`
+//! статистики.
+
+use crate::FlattenConfig;
+use crate::messages::{Message, Messages};
+use crate::normalize::{self, EolMode};
+use crate::outline::Abridge;
+use crate::pipeline::{FileContent, is_generated};
+use crate::report::{self, SkipReason};
+use std::fmt;
+use std::sync::Arc;
+
+/// Этап цепочки; преобразования применяются в порядке этапов.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Распознавание прочитанного содержимого (`--skip-generated`).
+    Decode,
+    /// Удаление BOM и приведение окончаний строк (`--normalize-eol`).
+    NormalizeEol,
+    /// Сокращение до структуры или первых строк (`--outline`, `--head-lines`).
+    Truncate,
+    /// Экранирование управляющих символов (`--sanitize`).
+    Sanitize,
+}
+
+/// Сведения о файле, от которых зависят преобразования.
+#[derive(Debug, Clone, Copy)]
+pub struct TransformContext<'a> {
+    /// Как сокращать содержимое файла.
+    pub abridge: Abridge,
+    /// Тексты заглушек и пометок (`--messages`).
+    pub messages: &'a Messages,
+}
+
+/// Преобразование содержимого одного файла.
+pub trait Transform: Send + Sync + fmt::Debug {
+    /// Имя преобразования для статистики.
+    fn name(&self) -> &'static str;
+
+    /// Этап, на котором применяется преобразование.
+    fn stage(&self) -> Stage;
+
+    /// Преобразует содержимое файла; возвращает, изменилось ли оно.
+    fn apply(&self, file: &mut FileContent, context: &TransformContext<'_>) -> bool;
+}
+
+/// Изменение файла одним преобразованием.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransformMetric {
+    /// Имя преобразования.
+    pub transform: &'static str,
+    /// Размер содержимого до преобразования в байтах.
+    pub before: u64,
+    /// Размер содержимого после преобразования в байтах.
+    pub after: u64,
+}
+
+/// Упорядоченная цепочка преобразований.
+#[derive(Debug, Clone, Default)]
+pub struct TransformChain {
+    transforms: Vec<Arc<dyn Transform>>,
+}
+
+impl TransformChain {
+    /// Составляет цепочку; преобразования упорядочиваются по этапам, а внутри
+    /// этапа остаются в переданном порядке.
+    pub fn new(mut transforms: Vec<Arc<dyn Transform>>) -> Self {
+        transforms.sort_by_key(|transform| transform.stage());
+        Self { transforms }
+    }
+
+    /// Составляет цепочку по параметрам конфигурации.
+    pub fn from_config(config: &FlattenConfig) -> Self {
+        let mut transforms: Vec<Arc<dyn Transform>> =
+            vec![Arc::new(NormalizeEol(config.normalize_eol))];
+        if config.skip_generated {
+            transforms.push(Arc::new(SkipGenerated));
+        }
+        if config.outline || config.head_lines > 0 {
+            transforms.push(Arc::new(Truncate(config.normalize_eol)));
+        }
+        if config.sanitize {
+            transforms.push(Arc::new(Sanitize));
+        }
+        Self::new(transforms)
+    }
+
+    /// Имена преобразований в порядке применения.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.transforms
+            .iter()
+            .map(|transform| transform.name())
+            .collect()
+    }
+
+    /// Сопоставляет сохраненное имя преобразования с преобразованием цепочки.
+    pub fn find(&self, name: &str) -> Option<&'static str> {
+        self.transforms
+            .iter()
+            .map(|transform| transform.name())
+            .find(|known| *known == name)
+    }
+
+    /// Применяет цепочку к файлу и записывает изменения в `file.transforms`.
+    ///
+    /// Как только содержимое заменено заглушкой, цепочка останавливается.
+    pub fn apply(&self, file: &mut FileContent, context: &TransformContext<'_>) {
+        for transform in &self.transforms {
+            if file.skip_reason.is_some() {
+                break;
+            }
+            let before = file.content.len() as u64;
+            if transform.apply(file, context) {
+                file.transforms.push(TransformMetric {
+                    transform: transform.name(),
+                    before,
+                    after: file.content.len() as u64,
+                });
+            }
+        }
+    }
+}
+
+/// Заменяет заглушкой файлы с маркерами генерации (`--skip-generated`).
+#[derive(Debug, Clone, Copy)]
+pub struct SkipGenerated;
+
+impl Transform for SkipGenerated {
+    fn name(&self) -> &'static str {
+        "skip-generated"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Decode
+    }
+
+    fn apply(&self, file: &mut FileContent, context: &TransformContext<'_>) -> bool {
+        if file.converter.is_some() || !is_generated(&file.content) {
+            return false;
+        }
+        file.content = context
+            .messages
+            .format(
+                Message::GeneratedSkipped,
+                &[("size", &report::format_size(file.original_size))],
+            )
+            .into();
+        file.skip_reason = Some(SkipReason::Generated);
+        true
+    }
+}
+
+/// Удаляет BOM и приводит окончания строк; запоминает исходный стиль.
+#[derive(Debug, Clone, Copy)]
+pub struct NormalizeEol(pub EolMode);
+
+impl Transform for NormalizeEol {
+    fn name(&self) -> &'static str {
+        "normalize-eol"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::NormalizeEol
+    }
+
+    fn apply(&self, file: &mut FileContent, _context: &TransformContext<'_>) -> bool {
+        let before = file.content.len();
+        file.line_endings = Some(file.content.normalize(self.0));
+        file.content.len() != before
+    }
+}
+
+/// Сокращает файл до структуры или первых строк.
+///
+/// Сокращенный текст получает окончания строк режима `--normalize-eol crlf`,
+/// если он задан: структура файла строится с `\n`.
+#[derive(Debug, Clone, Copy)]
+pub struct Truncate(pub EolMode);
+
+impl Transform for Truncate {
+    fn name(&self) -> &'static str {
+        "truncate"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Truncate
+    }
+
+    fn apply(&self, file: &mut FileContent, context: &TransformContext<'_>) -> bool {
+        if file.converter.is_some() {
+            return false;
+        }
+        let abridged = context.abridge.apply(&file.content, context.messages);
+        let Some((converter, mut text)) = abridged else {
+            return false;
+        };
+        if self.0 == EolMode::Crlf {
+            let endings = normalize::detect_line_endings(&text);
+            if let Some(converted) = normalize::convert_line_endings(&text, self.0, endings) {
+                text = converted;
+            }
+        }
+        file.content = text.into();
+        file.converter = Some(converter);
+        true
+    }
+}
+
+/// Экранирует управляющие символы и удаляет escape-последовательности ANSI.
+#[derive(Debug, Clone, Copy)]
+pub struct Sanitize;
+
+impl Transform for Sanitize {
+    fn name(&self) -> &'static str {
+        "sanitize"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Sanitize
+    }
+
+    fn apply(&self, file: &mut FileContent, _context: &TransformContext<'_>) -> bool {
+        file.sanitized = file.content.sanitize();
+        file.sanitized.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\u{feff}fn main() {\r\n    println!(\"\x1b[1mhi\x1b[0m\x07\");\r\n}\r\n";
+
+    fn chain(enabled: &[&str]) -> TransformChain {
+        let all: Vec<Arc<dyn Transform>> = vec![
+            Arc::new(Sanitize),
+            Arc::new(Truncate(EolMode::Lf)),
+            Arc::new(NormalizeEol(EolMode::Lf)),
+            Arc::new(SkipGenerated),
+        ];
+        TransformChain::new(
+            all.into_iter()
+                .filter(|transform| enabled.contains(&transform.name()))
+                .collect(),
+        )
+    }
+
+    fn apply(chain: &TransformChain, text: &str) -> FileContent {
+        let mut file = FileContent::read(text.to_string().into(), text.len() as u64);
+        let context = TransformContext {
+            abridge: Abridge {
+                syntax: None,
+                head_lines: 2,
+            },
+            messages: &Messages::default(),
+        };
+        chain.apply(&mut file, &context);
+        file
+    }
+
+    fn applied(file: &FileContent) -> Vec<&'static str> {
+        file.transforms
+            .iter()
+            .map(|metric| metric.transform)
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let all = ["skip-generated", "normalize-eol", "truncate", "sanitize"];
+        assert_eq!(chain(&all).names(), all);
+        assert_eq!(chain(&all).find("truncate"), Some("truncate"));
+        assert_eq!(chain(&["sanitize"]).find("truncate"), None);
+    }
+
+    #[test]
+    fn test_disabling_one_transform_keeps_others() {
+        let all = ["skip-generated", "normalize-eol", "truncate", "sanitize"];
+        for disabled in all {
+            let enabled: Vec<&str> = all.into_iter().filter(|name| *name != disabled).collect();
+            let chain = chain(&enabled);
+            let file = apply(&chain, SOURCE);
+            let expected: Vec<&str> = enabled
+                .iter()
+                .copied()
+                .filter(|name| *name != "skip-generated")
+                .collect();
+            assert_eq!(applied(&file), expected, "without {}", disabled);
+            // Без нормализации очистка экранирует `\r` окончаний строк.
+            assert_eq!(file.content.contains("\\x0d"), disabled == "normalize-eol");
+            assert_eq!(file.content.contains('\x1b'), disabled == "sanitize");
+            assert_eq!(file.content.contains('}'), disabled == "truncate");
+
+            let generated = apply(&chain, "// @generated\nlet x = 1;\n");
+            assert_eq!(
+                generated.skip_reason == Some(SkipReason::Generated),
+                disabled != "skip-generated"
+            );
+        }
+    }
+
+    #[test]
+    fn test_all_transforms_golden() {
+        let file = apply(
+            &chain(&["skip-generated", "normalize-eol", "truncate", "sanitize"]),
+            SOURCE,
+        );
+        assert_eq!(
+            file.content,
+            "fn main() {\n    println!(\"hi\\x07\");\n[… 1 more line]\n"
+        );
+        assert_eq!(file.line_endings, Some(normalize::LineEndings::Crlf));
+        assert_eq!(file.converter, Some(Abridge::HEAD));
+        assert_eq!(
+            file.transforms,
+            vec![
+                TransformMetric {
+                    transform: "normalize-eol",
+                    before: 49,
+                    after: 43,
+                },
+                TransformMetric {
+                    transform: "truncate",
+                    before: 43,
+                    after: 59,
+                },
+                TransformMetric {
+                    transform: "sanitize",
+                    before: 59,
+                    after: 54,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Модуль стороннего кода, скопированного в репозиторий (`--vendored`).
+//!
+//! Директории `vendor/`, `third_party/` и `extern/`, а также вложенные копии
+//! чужих проектов занимают большую часть вывода, хотя это не код проекта.
+//! Копия проекта распознается по корню с манифестом и собственной лицензией,
+//! отличной от лицензии базовой папки: пакет монорепозитория с той же
+//! лицензией сторонним кодом не считается. `--vendored-path` отмечает
+//! директорию явно, а `--keep` отменяет распознавание.
+//!
+//! С `--vendored summarize` директория заменяется секцией со сводкой: проекты
+//! из манифестов (имя и версия), число файлов и их размер. С `--vendored skip`
+//! содержимое не выводится, а директория остается в дереве с пометкой.
+//! В обоих режимах директория не обходится, а ее объем попадает в статистику.
+
+use crate::dependencies;
+use crate::messages::{Message, Messages};
+use crate::pipeline;
+use crate::report;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Обработка стороннего кода (`--vendored`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+pub enum VendoredMode {
+    /// Обходить как обычные директории (по умолчанию).
+    #[default]
+    Include,
+    /// Заменять секцией со сводкой.
+    Summarize,
+    /// Пропускать, оставляя пометку в дереве.
+    Skip,
+}
+
+/// Имена директорий, в которые принято копировать сторонний код.
+const VENDOR_DIRS: &[&str] = &["vendor", "third_party", "extern"];
+
+/// Имена файлов лицензии.
+const LICENSE_FILES: &[&str] = &["LICENSE", "LICENSE.md", "LICENSE.txt", "LICENCE", "COPYING"];
+
+/// Манифесты, по которым определяются имя и версия проекта, в порядке проверки.
+const MANIFEST_FILES: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml", "go.mod"];
+
+/// Является ли `path` внутри базовой папки `base` директорией стороннего кода.
+///
+/// `explicit` — пути `--vendored-path` относительно базовой папки.
+pub fn is_vendored(base: &Path, path: &Path, explicit: &[PathBuf]) -> bool {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    if explicit.iter().any(|explicit| explicit == relative) {
+        return true;
+    }
+    if !path
+        .symlink_metadata()
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        return false;
+    }
+    let named = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| VENDOR_DIRS.contains(&name));
+    named
+        || (MANIFEST_FILES.iter().any(|name| path.join(name).is_file())
+            && license(path).is_some_and(|own| license(base) != Some(own)))
+}
+
+/// Текст лицензии директории без пробелов по краям.
+fn license(directory: &Path) -> Option<String> {
+    LICENSE_FILES.iter().find_map(|name| {
+        std::fs::read_to_string(directory.join(name))
+            .ok()
+            .map(|text| text.trim().to_string())
+    })
+}
+
+/// Директория стороннего кода, замененная сводкой.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VendoredDirectory {
+    /// Директория относительно базовой папки, с `/` на конце.
+    pub directory: String,
+    /// Проекты из манифестов: `left-pad 1.3.0` или только имя.
+    pub projects: Vec<String>,
+    /// Число файлов в директории.
+    pub files: usize,
+    /// Их суммарный размер.
+    pub bytes: u64,
+}
+
+impl VendoredDirectory {
+    /// Собирает сводку директории `path` внутри базовой папки `base`.
+    ///
+    /// Проекты ищутся в манифесте самой директории, а если его нет — в
+    /// манифестах ее поддиректорий (`vendor/left-pad/package.json`).
+    pub fn survey(base: &Path, path: &Path) -> Self {
+        let relative = path.strip_prefix(base).unwrap_or(path);
+        let projects = match project(path) {
+            Some(project) => vec![project],
+            None => {
+                let mut children: Vec<PathBuf> = std::fs::read_dir(path)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .map(|entry| entry.path())
+                    .collect();
+                children.sort();
+                children.iter().filter_map(|child| project(child)).collect()
+            }
+        };
+        let (files, bytes) = pipeline::directory_usage(path);
+        Self {
+            directory: format!("{}/", relative.display()),
+            projects,
+            files,
+            bytes,
+        }
+    }
+
+    /// Текст секции: `[Vendored code in vendor/: left-pad 1.3.0; 4 files, 1.66 KB]`.
+    pub fn describe(&self, messages: &Messages) -> String {
+        let projects = match self.projects.is_empty() {
+            true => String::new(),
+            false => format!("{}; ", self.projects.join(", ")),
+        };
+        messages.format_count(
+            Message::VendoredCode,
+            self.files,
+            &[
+                ("directory", &self.directory),
+                ("projects", &projects),
+                ("size", &report::format_size(self.bytes)),
+            ],
+        )
+    }
+}
+
+/// Имя и версия проекта из первого найденного манифеста директории.
+fn project(directory: &Path) -> Option<String> {
+    MANIFEST_FILES.iter().find_map(|name| {
+        let path = directory.join(name);
+        let kind = dependencies::manifest_kind(&path)?;
+        let content = std::fs::read_to_string(&path).ok()?;
+        let (name, version) = dependencies::parse_package(kind, &content)?;
+        Some(match version {
+            Some(version) => format!("{} {}", name, version),
+            None => name,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_vendored_detection() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        for (path, contents) in [
+            ("LICENSE", "MIT License\nCopyright (c) App Authors\n"),
+            ("vendor/left-pad/index.js", "module.exports = leftPad;"),
+            ("third_party/zlib/zlib.h", "/* zlib */"),
+            // Пакет монорепозитория с той же лицензией — код проекта.
+            (
+                "packages/app-utils/package.json",
+                r#"{"name": "app-utils"}"#,
+            ),
+            (
+                "packages/app-utils/LICENSE",
+                "MIT License\nCopyright (c) App Authors",
+            ),
+            // Скопированный проект с чужой лицензией.
+            ("lib/mini-assert/package.json", r#"{"name": "mini-assert"}"#),
+            (
+                "lib/mini-assert/LICENSE",
+                "ISC License\nCopyright (c) Someone Else\n",
+            ),
+            ("assets/js/chart.min.js", "!function(){}();"),
+            ("src/vendor.rs", "pub fn vendor() {}"),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, contents)?;
+        }
+        let explicit = [PathBuf::from("assets/js")];
+        let vendored = |path: &str| is_vendored(root, &root.join(path), &explicit);
+
+        assert!(vendored("vendor"));
+        assert!(vendored("third_party"));
+        assert!(vendored("lib/mini-assert"));
+        assert!(vendored("assets/js"));
+        assert!(!vendored("packages/app-utils"));
+        assert!(!vendored("lib"));
+        assert!(!vendored("src"));
+        assert!(!vendored("src/vendor.rs"));
+        assert!(!is_vendored(root, &root.join("assets/js"), &[]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_vendored_summary() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        let package = root.join("vendor/left-pad");
+        fs::create_dir_all(&package)?;
+        fs::write(
+            package.join("package.json"),
+            r#"{"name": "left-pad", "version": "1.3.0"}"#,
+        )?;
+        fs::write(package.join("index.js"), "x".repeat(1000))?;
+        fs::create_dir_all(root.join("vendor/tiny"))?;
+        fs::write(root.join("vendor/tiny/go.mod"), "module example.com/tiny\n")?;
+
+        let summary = VendoredDirectory::survey(root, &root.join("vendor"));
+        assert_eq!(summary.projects, ["left-pad 1.3.0", "example.com/tiny"]);
+        assert_eq!(summary.files, 3);
+        assert_eq!(
+            summary.describe(&Messages::default()),
+            format!(
+                "[Vendored code in vendor/: left-pad 1.3.0, example.com/tiny; 3 files, {}]",
+                report::format_size(summary.bytes)
+            )
+        );
+
+        let summary = VendoredDirectory::survey(root, &package);
+        assert_eq!(summary.projects, ["left-pad 1.3.0"]);
+        assert_eq!(summary.directory, "vendor/left-pad/");
+        Ok(())
+    }
+}
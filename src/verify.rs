@@ -0,0 +1,314 @@
+//! Модуль проверки актуальности выходного документа (`--verify`).
+//!
+//! Ожидаемый набор файлов и хешей содержимого берется из манифеста, а если его
+//! нет — из самого документа (маркеры или заголовки Markdown, JSON-формат). Текущий набор
+//! строится повторным сбором файлов с той же конфигурацией, без записи вывода.
+//!
+//! Файлы, изменившиеся во время создания документа (с пометкой
+//! `[content may be stale: file changed during run]`), могут расходиться
+//! с текущим состоянием: такое расхождение ожидаемо и не делает документ устаревшим.
+
+use crate::manifest::sha256_hex;
+use crate::output::{STALE_NOTE, unescape_markers};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Хеши содержимого по путям файлов в том виде, в котором они указаны в выводе.
+pub type ContentHashes = BTreeMap<PathBuf, String>;
+
+/// Ожидаемое состояние файлов по документу.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Expected {
+    /// Хеши содержимого по путям файлов.
+    pub hashes: ContentHashes,
+    /// Файлы, изменившиеся во время создания документа.
+    pub stale: BTreeSet<PathBuf>,
+    /// Количество секций файлов (повторяющиеся пути считаются каждый раз).
+    pub sections: usize,
+}
+
+impl Expected {
+    /// Добавляет файл с хешем содержимого.
+    fn insert(&mut self, path: PathBuf, hash: String, stale: bool) {
+        if stale {
+            self.stale.insert(path.clone());
+        }
+        self.sections += 1;
+        self.hashes.insert(path, hash);
+    }
+}
+
+/// Загружает ожидаемые хеши из манифеста или из документа.
+pub fn load_expected(document: &Path, manifest: Option<&Path>) -> Result<Expected> {
+    if let Some(manifest) = manifest.filter(|path| path.exists()) {
+        let content = std::fs::read_to_string(manifest)
+            .with_context(|| format!("Failed to read manifest: {}", manifest.display()))?;
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid manifest: {}", manifest.display()))?;
+        let mut expected = Expected::default();
+        for file in value["files"].as_array().into_iter().flatten() {
+            if let (Some(path), Some(hash)) = (file["path"].as_str(), file["sha256"].as_str()) {
+                let stale = file["stale"].as_bool() == Some(true);
+                expected.insert(PathBuf::from(path), hash.to_string(), stale);
+            }
+        }
+        return Ok(expected);
+    }
+
+    let content = std::fs::read_to_string(document)
+        .with_context(|| format!("Failed to read document: {}", document.display()))?;
+    Ok(parse_document(&content))
+}
+
+/// Извлекает хеши файлов из текста документа в любом из форматов.
+pub fn parse_document(content: &str) -> Expected {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(content) {
+        return parse_json_document(&value);
+    }
+    // Документ с маркерами начинается со структуры `### DIRECTORY ... ###`.
+    let markers = content
+        .lines()
+        .find(|line| line.starts_with('#'))
+        .is_none_or(|line| line.starts_with("### DIRECTORY "));
+    if markers {
+        parse_markers(content)
+    } else {
+        parse_headings(content)
+    }
+}
+
+/// Извлекает хеши файлов из JSON-документа (`--format json`).
+fn parse_json_document(value: &serde_json::Value) -> Expected {
+    let mut expected = Expected::default();
+    for folder in value["folders"].as_array().into_iter().flatten() {
+        let grouped = folder["groups"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|group| group["files"].as_array().into_iter().flatten());
+        let plain = folder["files"].as_array().into_iter().flatten();
+        for file in plain.chain(grouped) {
+            if let (Some(path), Some(content)) = (file["path"].as_str(), file["content"].as_str()) {
+                let stale = file["stale"].as_bool() == Some(true);
+                expected.insert(PathBuf::from(path), sha256_hex(content.as_bytes()), stale);
+            }
+        }
+    }
+    expected
+}
+
+/// Извлекает хеши файлов из маркеров `### путь BEGIN ###` / `### путь END ###`.
+///
+/// Экранированные строки содержимого (см. `output::escape_markers`) восстанавливаются
+/// перед вычислением хеша.
+fn parse_markers(content: &str) -> Expected {
+    let mut expected = Expected::default();
+    let mut position = 0;
+    while let Some(found) = content[position..].find("### ") {
+        let line_start = position + found;
+        let line_end = content[line_start..]
+            .find('\n')
+            .map_or(content.len(), |i| line_start + i);
+        let line = &content[line_start..line_end];
+        position = line_end;
+        if line_start > 0 && !content[..line_start].ends_with('\n') {
+            continue;
+        }
+
+        let Some((path, note)) = line
+            .strip_prefix("### ")
+            .and_then(|rest| rest.split_once(" BEGIN ###"))
+        else {
+            continue;
+        };
+        let body_start = (line_end + 1).min(content.len());
+        let end_marker = format!("\n### {} END ###", path);
+        if let Some(end) = content[body_start..].find(&end_marker) {
+            let body = &content[body_start..body_start + end];
+            let body = unescape_markers(body);
+            let stale = note.contains(STALE_NOTE);
+            expected.insert(PathBuf::from(path), sha256_hex(body.as_bytes()), stale);
+            position = body_start + end + end_marker.len();
+        }
+    }
+    expected
+}
+
+/// Извлекает хеши файлов из документа с `--heading-style headings`.
+///
+/// Файл — это заголовок с путем, за которым сразу следует блок кода. Заголовки
+/// разделов отделены от своих блоков пустой строкой и поэтому пропускаются.
+/// Ограничитель блока не встречается в содержимом, экранирования нет.
+fn parse_headings(content: &str) -> Expected {
+    let line_end = |start: usize| content[start..].find('\n').map_or(content.len(), |i| start + i);
+    let mut expected = Expected::default();
+    let mut position = 0;
+    while position < content.len() {
+        let heading_end = line_end(position);
+        let heading = &content[position..heading_end];
+        let fence_start = (heading_end + 1).min(content.len());
+        position = fence_start;
+
+        let Some(path) = heading
+            .strip_prefix('#')
+            .and_then(|rest| rest.trim_start_matches('#').strip_prefix(' '))
+        else {
+            continue;
+        };
+        let unmarked = path.strip_suffix(STALE_NOTE).and_then(|path| path.strip_suffix(' '));
+        let stale = unmarked.is_some();
+        let path = unmarked.unwrap_or(path);
+        let path = path.strip_suffix(" [priority]").unwrap_or(path);
+        let fence_end = line_end(fence_start);
+        let fence_line = &content[fence_start..fence_end];
+        let fence = &fence_line[..fence_line.len() - fence_line.trim_start_matches('`').len()];
+        if fence.len() < 3 {
+            continue;
+        }
+        let body_start = (fence_end + 1).min(content.len());
+        let closing = format!("\n{}", fence);
+        if let Some(end) = content[body_start..].find(&closing) {
+            let body = &content[body_start..body_start + end];
+            expected.insert(PathBuf::from(path), sha256_hex(body.as_bytes()), stale);
+            position = line_end(body_start + end + closing.len());
+        }
+    }
+    expected
+}
+
+/// Расхождения между документом и текущим состоянием файлов.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Файлы, появившиеся после создания документа.
+    pub added: Vec<PathBuf>,
+    /// Файлы, которых больше нет.
+    pub removed: Vec<PathBuf>,
+    /// Файлы с изменившимся содержимым.
+    pub changed: Vec<PathBuf>,
+    /// Файлы, изменившиеся во время создания документа, чье содержимое
+    /// расходится с текущим (ожидаемое расхождение, документ не устарел).
+    pub drifted: Vec<PathBuf>,
+    /// Количество совпавших файлов.
+    pub unchanged: usize,
+}
+
+impl VerifyReport {
+    /// Сравнивает ожидаемые и текущие хеши.
+    pub fn compare(expected: &Expected, actual: &ContentHashes) -> Self {
+        let mut report = Self::default();
+        for (path, hash) in actual {
+            match expected.hashes.get(path) {
+                None => report.added.push(path.clone()),
+                Some(expected_hash) if expected_hash == hash => report.unchanged += 1,
+                Some(_) if expected.stale.contains(path) => report.drifted.push(path.clone()),
+                Some(_) => report.changed.push(path.clone()),
+            }
+        }
+        report.removed = expected
+            .hashes
+            .keys()
+            .filter(|path| !actual.contains_key(*path))
+            .cloned()
+            .collect();
+        report
+    }
+
+    /// Совпадает ли документ с текущим состоянием.
+    pub fn is_clean(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Выводит краткую сводку в консоль.
+    pub fn print(&self, document: &Path) {
+        if !self.drifted.is_empty() {
+            let noun = if self.drifted.len() == 1 { "file" } else { "files" };
+            println!(
+                "{} {} changed during generation (expected drift):",
+                self.drifted.len(),
+                noun
+            );
+            for path in &self.drifted {
+                println!("  {}", path.display());
+            }
+        }
+        if self.is_clean() {
+            println!(
+                "{} is up to date ({} files)",
+                document.display(),
+                self.unchanged
+            );
+            return;
+        }
+        for (paths, what) in [
+            (&self.changed, "changed"),
+            (&self.added, "added"),
+            (&self.removed, "removed"),
+        ] {
+            if paths.is_empty() {
+                continue;
+            }
+            let noun = if paths.len() == 1 { "file" } else { "files" };
+            println!("{} {} {} since generation:", paths.len(), noun, what);
+            for path in paths {
+                println!("  {}", path.display());
+            }
+        }
+        println!(
+            "{} is stale; re-run flatten-rust with the same options to update it",
+            document.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markers_and_compare() {
+        let document = "### DIRECTORY app FLATTENED CONTENT ###\n\
+            ### app/a.rs BEGIN ###\nfn a() {}\n### app/a.rs END ###\n\n\
+            ### app/b.rs BEGIN ### [priority]\nfn b() {}\n### app/b.rs END ###\n\n\
+            ### app/d.rs BEGIN ### [content may be stale: file changed during run]\n\
+            fn d() {}\n### app/d.rs END ###\n\n\
+            ### DIRECTORY app FLATTENED CONTENT ###\n";
+        let expected = parse_markers(document);
+        assert_eq!(expected.hashes[Path::new("app/a.rs")], sha256_hex(b"fn a() {}"));
+        assert_eq!(expected.stale.iter().collect::<Vec<_>>(), [Path::new("app/d.rs")]);
+
+        let mut actual = expected.hashes.clone();
+        actual.insert(PathBuf::from("app/b.rs"), sha256_hex(b"fn b() { todo!() }"));
+        actual.insert(PathBuf::from("app/c.rs"), sha256_hex(b""));
+        actual.insert(PathBuf::from("app/d.rs"), sha256_hex(b"fn d() { todo!() }"));
+        actual.remove(Path::new("app/a.rs"));
+
+        let report = VerifyReport::compare(&expected, &actual);
+        assert_eq!(report.changed, vec![PathBuf::from("app/b.rs")]);
+        assert_eq!(report.added, vec![PathBuf::from("app/c.rs")]);
+        assert_eq!(report.removed, vec![PathBuf::from("app/a.rs")]);
+        assert!(!report.is_clean());
+
+        // Расхождение файла, изменившегося во время создания, ожидаемо.
+        actual = expected.hashes.clone();
+        actual.insert(PathBuf::from("app/d.rs"), sha256_hex(b"fn d() { todo!() }"));
+        let report = VerifyReport::compare(&expected, &actual);
+        assert_eq!(report.drifted, vec![PathBuf::from("app/d.rs")]);
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_parse_headings() {
+        let document = "# app\n\n## Folder structure\n\n```\n📄 a.rs\n```\n\n## Files\n\n\
+            ### app/a.rs\n```rust\nfn a() {}\n```\n\n\
+            ### app/b.md [priority]\n````markdown\n```\n### x\n```\n````\n\n\
+            ### app/empty.txt [content may be stale: file changed during run]\n```text\n\n```\n";
+        let expected = parse_headings(document);
+        assert_eq!(expected.stale.iter().collect::<Vec<_>>(), [Path::new("app/empty.txt")]);
+        let hashes = expected.hashes;
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes[Path::new("app/a.rs")], sha256_hex(b"fn a() {}"));
+        assert_eq!(hashes[Path::new("app/b.md")], sha256_hex(b"```\n### x\n```"));
+        assert_eq!(hashes[Path::new("app/empty.txt")], sha256_hex(b""));
+    }
+}
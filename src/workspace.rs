@@ -0,0 +1,354 @@
+//! Модуль Cargo workspace базовой папки.
+//!
+//! Workspace находится подъемом от базовой папки к первому `Cargo.toml` с
+//! таблицей `[workspace]`, как это делает Cargo, поэтому `-f` может указывать
+//! и на корень, и на одного из участников. Участники берутся из разбора
+//! манифеста, а не угадываются по путям: `members` с glob-паттернами
+//! (`crates/*`) за вычетом `exclude`, плюс корневой пакет, если в манифесте
+//! есть `[package]`. Имена крейтов читаются из их собственных `Cargo.toml`.
+//!
+//! Подъем нужен только для поиска манифеста: содержимое берется лишь из
+//! базовой папки, а участники вне ее перечисляются в обзоре с пометкой.
+//! Файлы выводятся по крейтам: сначала файлы вне участников, затем каждый
+//! крейт со своим `Cargo.toml` во главе. `--exclude-crate` исключает
+//! участника по имени.
+
+use crate::dependencies::{self, ManifestKind};
+use crate::grouping::PriorityRules;
+use crate::toml_value;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+/// Имя манифеста Cargo.
+pub const MANIFEST: &str = "Cargo.toml";
+
+/// Cargo workspace, в который входит базовая папка.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CargoWorkspace {
+    /// Корень workspace относительно базовой папки (`.` или `../..`).
+    pub root: PathBuf,
+    /// Участники в порядке путей.
+    pub members: Vec<WorkspaceCrate>,
+}
+
+/// Крейт-участник workspace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkspaceCrate {
+    /// Имя пакета из `Cargo.toml` (или имя директории, если манифест не разобран).
+    pub name: String,
+    /// Директория относительно корня workspace (пустая у корневого пакета).
+    pub path: PathBuf,
+    /// Директория внутри базовой папки; `None` — участник вне ее.
+    #[serde(skip)]
+    pub local: Option<PathBuf>,
+    /// Лежит ли участник вне базовой папки.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub outside: bool,
+    /// Исключен ли участник `--exclude-crate`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub excluded: bool,
+}
+
+impl CargoWorkspace {
+    /// Находит workspace базовой папки `base`.
+    ///
+    /// `None`, если выше нет `Cargo.toml` с `[workspace]` или базовая папка
+    /// не является ни корнем, ни частью одного из участников.
+    pub fn discover(base: &Path) -> Option<Self> {
+        let canonical = base.canonicalize().ok()?;
+        let (root, manifest) = canonical.ancestors().find_map(|directory| {
+            let manifest = read_manifest(directory)?;
+            manifest
+                .get("workspace")
+                .is_some()
+                .then(|| (directory.to_path_buf(), manifest))
+        })?;
+        let mut members: Vec<WorkspaceCrate> = member_paths(&root, &manifest)
+            .into_iter()
+            .map(|path| WorkspaceCrate {
+                name: crate_name(&root.join(&path)),
+                path,
+                local: None,
+                outside: false,
+                excluded: false,
+            })
+            .collect();
+        let inside = canonical == root
+            || members
+                .iter()
+                .any(|member| canonical.starts_with(root.join(&member.path)));
+        if !inside {
+            return None;
+        }
+        for member in &mut members {
+            member.local = root
+                .join(&member.path)
+                .strip_prefix(&canonical)
+                .ok()
+                .map(|relative| base.join(relative));
+            member.outside = member.local.is_none();
+        }
+        let depth = canonical.strip_prefix(&root).ok()?.components().count();
+        let root = match depth {
+            0 => PathBuf::from("."),
+            _ => std::iter::repeat_n("..", depth).collect(),
+        };
+        Some(Self { root, members })
+    }
+
+    /// Индекс участника, которому принадлежит путь внутри базовой папки
+    /// (самого глубокого из содержащих его).
+    fn member_index(&self, path: &Path) -> Option<usize> {
+        self.members
+            .iter()
+            .enumerate()
+            .filter_map(|(index, member)| Some((index, member.local.as_ref()?)))
+            .filter(|(_, local)| path.starts_with(local))
+            .max_by_key(|(_, local)| local.components().count())
+            .map(|(index, _)| index)
+    }
+
+    /// Крейт, которому принадлежит путь внутри базовой папки.
+    pub fn crate_of(&self, path: &Path) -> Option<&WorkspaceCrate> {
+        self.member_index(path).map(|index| &self.members[index])
+    }
+
+    /// Упорядочивает файлы по крейтам: сначала файлы вне участников, затем
+    /// крейты в порядке путей. В каждом крейте первым идет его `Cargo.toml`,
+    /// остальные файлы упорядочены `--priority` с сохранением порядка обхода.
+    pub fn arrange<T>(&self, base: &Path, priority: &PriorityRules, files: &mut Vec<(PathBuf, T)>) {
+        let mut blocks: BTreeMap<Option<usize>, Vec<(PathBuf, T)>> = BTreeMap::new();
+        for (path, item) in std::mem::take(files) {
+            blocks
+                .entry(self.member_index(&path))
+                .or_default()
+                .push((path, item));
+        }
+        for (member, mut block) in blocks {
+            priority.prioritize(base, &mut block);
+            if let Some(local) = member.and_then(|index| self.members[index].local.as_ref()) {
+                let manifest = local.join(MANIFEST);
+                if let Some(position) = block.iter().position(|(path, _)| *path == manifest) {
+                    let file = block.remove(position);
+                    block.insert(0, file);
+                }
+            }
+            files.extend(block);
+        }
+    }
+}
+
+/// Разобранный `Cargo.toml` директории.
+fn read_manifest(directory: &Path) -> Option<Value> {
+    let content = std::fs::read_to_string(directory.join(MANIFEST)).ok()?;
+    toml_value::parse(&content).ok()
+}
+
+/// Пути участников относительно корня `root` по манифесту workspace.
+fn member_paths(root: &Path, manifest: &Value) -> BTreeSet<PathBuf> {
+    let strings = |key: &str| -> Vec<String> {
+        manifest["workspace"][key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|value| Some(value.as_str()?.to_string()))
+            .collect()
+    };
+    let excluded: Vec<PathBuf> = strings("exclude")
+        .iter()
+        .map(|path| clean(Path::new(path)))
+        .collect();
+    let mut paths = BTreeSet::new();
+    if manifest.get("package").is_some() {
+        paths.insert(PathBuf::new());
+    }
+    let escaped_root = glob::Pattern::escape(&root.to_string_lossy());
+    for pattern in strings("members") {
+        let matches = glob::glob(&format!("{}/{}", escaped_root, pattern));
+        for path in matches.into_iter().flatten().flatten() {
+            if let Ok(relative) = path.strip_prefix(root)
+                && path.join(MANIFEST).is_file()
+            {
+                paths.insert(clean(relative));
+            }
+        }
+    }
+    paths.retain(|path| {
+        !excluded
+            .iter()
+            .any(|excluded| !excluded.as_os_str().is_empty() && path.starts_with(excluded))
+    });
+    paths
+}
+
+/// Путь без компонентов `.`.
+fn clean(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Имя пакета из `Cargo.toml` директории, иначе имя самой директории.
+fn crate_name(directory: &Path) -> String {
+    std::fs::read_to_string(directory.join(MANIFEST))
+        .ok()
+        .and_then(|content| dependencies::parse_package(ManifestKind::Cargo, &content))
+        .map(|(name, _)| name)
+        .unwrap_or_else(|| {
+            directory
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Workspace с участниками `crates/*` (кроме `crates/old`) и `tools/xtask`.
+    fn workspace() -> anyhow::Result<tempfile::TempDir> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        for (path, contents) in [
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"crates/*\", \"./tools/xtask\"]\n\
+                 exclude = [\"crates/old\"]\n",
+            ),
+            ("README.md", "# Workspace\n"),
+            ("crates/core/Cargo.toml", "[package]\nname = \"app-core\"\n"),
+            ("crates/core/src/lib.rs", "pub fn core() {}\n"),
+            ("crates/cli/Cargo.toml", "[package]\nname = \"app-cli\"\n"),
+            ("crates/cli/src/main.rs", "fn main() {}\n"),
+            ("crates/cli/README.md", "# CLI\n"),
+            ("crates/old/Cargo.toml", "[package]\nname = \"old\"\n"),
+            ("crates/notes/todo.txt", "not a crate\n"),
+            ("tools/xtask/Cargo.toml", "[package]\nname = \"xtask\"\n"),
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, contents)?;
+        }
+        Ok(temp_dir)
+    }
+
+    fn names(workspace: &CargoWorkspace) -> Vec<(&str, String, bool)> {
+        workspace
+            .members
+            .iter()
+            .map(|member| {
+                let path = member.path.display().to_string();
+                (member.name.as_str(), path, member.outside)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_discover_from_root_and_member() -> anyhow::Result<()> {
+        let temp_dir = workspace()?;
+        let root = temp_dir.path();
+
+        let workspace = CargoWorkspace::discover(root).expect("workspace");
+        assert_eq!(workspace.root, Path::new("."));
+        assert_eq!(
+            names(&workspace),
+            [
+                ("app-cli", "crates/cli".to_string(), false),
+                ("app-core", "crates/core".to_string(), false),
+                ("xtask", "tools/xtask".to_string(), false),
+            ]
+        );
+        assert_eq!(workspace.members[0].local, Some(root.join("crates/cli")));
+
+        // Из участника: workspace тот же, остальные участники вне базовой папки.
+        let member = root.join("crates/core");
+        let workspace = CargoWorkspace::discover(&member).expect("workspace");
+        assert_eq!(workspace.root, Path::new("../.."));
+        assert!(workspace.members[0].outside);
+        assert_eq!(workspace.members[1].local, Some(member.clone()));
+        assert_eq!(
+            workspace
+                .crate_of(&member.join("src/lib.rs"))
+                .map(|member| &member.name[..]),
+            Some("app-core")
+        );
+
+        // Папка вне участников и не корень — не часть workspace.
+        assert_eq!(CargoWorkspace::discover(&root.join("crates/notes")), None);
+        assert_eq!(CargoWorkspace::discover(&root.join("crates/old")), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_package_is_a_member() -> anyhow::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path().join("app");
+        fs::create_dir_all(root.join("macros/src"))?;
+        fs::write(
+            root.join(MANIFEST),
+            "[package]\nname = \"app\"\n\n[workspace]\nmembers = [\"macros\"]\n",
+        )?;
+        fs::write(
+            root.join("macros/Cargo.toml"),
+            "[package]\nname = \"app-macros\"\n",
+        )?;
+
+        let workspace = CargoWorkspace::discover(&root).expect("workspace");
+        assert_eq!(
+            names(&workspace),
+            [
+                ("app", String::new(), false),
+                ("app-macros", "macros".to_string(), false),
+            ]
+        );
+        let crate_of = |path: &str| workspace.crate_of(&root.join(path)).map(|m| m.name.clone());
+        assert_eq!(crate_of("src/main.rs").as_deref(), Some("app"));
+        assert_eq!(crate_of("macros/src/lib.rs").as_deref(), Some("app-macros"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrange_by_crate() -> anyhow::Result<()> {
+        let temp_dir = workspace()?;
+        let root = temp_dir.path();
+        let workspace = CargoWorkspace::discover(root).expect("workspace");
+        let mut files: Vec<(PathBuf, ())> = [
+            "Cargo.toml",
+            "README.md",
+            "crates/cli/README.md",
+            "crates/cli/src/main.rs",
+            "crates/cli/Cargo.toml",
+            "crates/core/src/lib.rs",
+            "crates/core/Cargo.toml",
+            "tools/xtask/Cargo.toml",
+        ]
+        .iter()
+        .map(|path| (root.join(path), ()))
+        .collect();
+        let priority = PriorityRules::new(&["README*".to_string()])?;
+        workspace.arrange(root, &priority, &mut files);
+
+        let order: Vec<String> = files
+            .iter()
+            .map(|(path, _)| path.strip_prefix(root).unwrap().display().to_string())
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "README.md",
+                "Cargo.toml",
+                "crates/cli/Cargo.toml",
+                "crates/cli/README.md",
+                "crates/cli/src/main.rs",
+                "crates/core/Cargo.toml",
+                "crates/core/src/lib.rs",
+                "tools/xtask/Cargo.toml",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+const leftPad = require("../vendor/left-pad");
+
+// GREETER_MAIN
+module.exports = (name) => leftPad(`Hello, ${name}`, 20);
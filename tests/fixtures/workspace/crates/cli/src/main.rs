@@ -0,0 +1,4 @@
+// CLI_MAIN
+fn main() {
+    println!("{}", fixture_core::answer());
+}
@@ -0,0 +1,4 @@
+// CORE_LIB
+pub fn answer() -> u32 {
+    42
+}